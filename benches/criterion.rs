@@ -16,7 +16,8 @@ criterion_group! {
 criterion_group! {
 	name = medium;
 	config = Criterion::default().measurement_time(Duration::from_secs(20));
-	targets = medium_json, medium_yaml, medium_toml, medium_msgpack
+	targets = medium_json, medium_yaml, medium_toml, medium_msgpack, medium_json_parse,
+		medium_yaml_parse
 }
 
 macro_rules! xt_benchmark {
@@ -117,6 +118,20 @@ xt_benchmark! {
 	translation = Format::Msgpack => Format::Json;
 }
 
+xt_benchmark! {
+	name = medium_json_parse;
+	sources = buffer, reader;
+	loader = load_medium_data;
+	translation = Format::Json => Format::Null;
+}
+
+xt_benchmark! {
+	name = medium_yaml_parse;
+	sources = buffer, reader;
+	loader = load_medium_data;
+	translation = Format::Yaml => Format::Null;
+}
+
 fn load_medium_data(format: Format) -> Vec<u8> {
 	use rmp::Marker;
 
@@ -0,0 +1,259 @@
+//! Output that never leaves part of a document in the underlying writer.
+//!
+//! Output formats write each document to a [`Writer`] that stages it in memory, and the
+//! [`Output`] driving them commits the staged document to the underlying writer only after the
+//! entire document transcodes successfully. A failure partway through a document, whether in the
+//! input or in serialization, discards what the output format wrote of it so far.
+//!
+//! Committed documents wait in the same buffer for up to [`BATCH_LIMIT`] bytes, or until the
+//! next flush, and then go to the underlying writer together, so that a stream of small documents
+//! to an unbuffered writer doesn't cost a write for each one. For a
+//! [`DocumentSink`](crate::DocumentSink), the writer instead writes and flushes each document as
+//! soon as it's committed, which marks where one document ends and the next begins.
+
+use std::io::{self, IoSlice, Write};
+
+use serde::de;
+
+use crate::Dispatcher;
+use crate::transcode::Value;
+
+/// The largest staging buffer that a [`Writer`] keeps allocated between documents, so that one
+/// huge document doesn't pin its memory for the rest of the stream.
+const RETAINED_CAPACITY: usize = 1024 * 1024;
+
+/// The most committed output that a [`Writer`] holds back before writing it, matching the default
+/// capacity of a translator's output buffer.
+const BATCH_LIMIT: usize = crate::DEFAULT_BUFFER_CAPACITY;
+
+/// A writer that stages everything written during a document until the document ends.
+///
+/// Writes outside of a document, like those of [`parallel`](crate::parallel) translation (whose
+/// workers already serialize each document to a separate buffer), go straight to the underlying
+/// writer, after any committed documents that came before them.
+pub(crate) struct Writer<W> {
+	inner: W,
+	/// The committed documents that have yet to be written, followed by the current document.
+	staged: Vec<u8>,
+	/// The length of the committed documents at the start of `staged`.
+	committed: usize,
+	staging: bool,
+	enabled: bool,
+	boundaries: bool,
+}
+
+impl<W: Write> Writer<W> {
+	pub(crate) fn new(inner: W) -> Writer<W> {
+		Writer {
+			inner,
+			staged: vec![],
+			committed: 0,
+			staging: false,
+			enabled: true,
+			boundaries: false,
+		}
+	}
+
+	/// Returns a mutable reference to the underlying writer.
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.inner
+	}
+
+	/// Sets whether the writer stages documents, or passes every write straight through.
+	pub(crate) fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+	}
+
+	/// Sets whether the writer flushes the underlying writer after committing each document.
+	///
+	/// Marking boundaries always stages documents, since a failed document that had already
+	/// reached the underlying writer would become part of the next one.
+	pub(crate) fn set_document_boundaries(&mut self, boundaries: bool) {
+		self.boundaries = boundaries;
+	}
+
+	fn begin_document(&mut self) {
+		self.staging = self.enabled || self.boundaries;
+	}
+
+	/// Ends the current document, committing it if `commit` is true and discarding it otherwise.
+	fn end_document(&mut self, commit: bool) -> io::Result<()> {
+		self.staging = false;
+		if !commit {
+			self.staged.truncate(self.committed);
+			self.staged.shrink_to(RETAINED_CAPACITY);
+			return Ok(());
+		}
+		self.committed = self.staged.len();
+		if self.boundaries {
+			self.write_committed()?;
+			self.inner.flush()
+		} else if self.committed >= BATCH_LIMIT {
+			self.write_committed()
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Writes every committed document to the underlying writer.
+	fn write_committed(&mut self) -> io::Result<()> {
+		if self.committed == 0 {
+			return Ok(());
+		}
+		let result = self.inner.write_all(&self.staged[..self.committed]);
+		self.staged.drain(..self.committed);
+		self.committed = 0;
+		self.staged.shrink_to(RETAINED_CAPACITY);
+		result
+	}
+}
+
+impl<W: Write> Write for Writer<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if self.staging {
+			self.staged.extend_from_slice(buf);
+			Ok(buf.len())
+		} else {
+			self.write_committed()?;
+			self.inner.write(buf)
+		}
+	}
+
+	fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+		if self.staging {
+			let mut len = 0;
+			for buf in bufs {
+				self.staged.extend_from_slice(buf);
+				len += buf.len();
+			}
+			Ok(len)
+		} else {
+			self.write_committed()?;
+			self.inner.write_vectored(bufs)
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.write_committed()?;
+		self.inner.flush()
+	}
+}
+
+/// An [`Output`](crate::Output) that stages each document written to a [`Dispatcher`], and
+/// commits it to the underlying writer only if it transcodes successfully.
+pub(crate) struct Output<'d, W: Write> {
+	output: &'d mut Dispatcher<Writer<W>>,
+	preamble: Option<String>,
+}
+
+impl<'d, W: Write> Output<'d, W> {
+	pub(crate) fn new(output: &'d mut Dispatcher<Writer<W>>) -> Output<'d, W> {
+		Output {
+			output,
+			preamble: None,
+		}
+	}
+
+	/// Sets text to write before the next document as part of it, such as a comment line, so
+	/// that it's discarded along with a document that fails.
+	pub(crate) fn set_preamble(&mut self, preamble: String) {
+		self.preamble = Some(preamble);
+	}
+
+	/// Returns a mutable reference to the underlying writer.
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.output.get_mut().inner
+	}
+
+	/// Writes every committed document to the underlying writer, rather than waiting for the
+	/// batch to fill.
+	pub(crate) fn write_committed(&mut self) -> io::Result<()> {
+		self.output.get_mut().write_committed()
+	}
+
+	fn stage<F>(&mut self, transcode: F) -> crate::Result<()>
+	where
+		F: FnOnce(&mut &'d mut Dispatcher<Writer<W>>) -> crate::Result<()>,
+	{
+		self.output.get_mut().begin_document();
+		let result = match self.preamble.take() {
+			Some(preamble) => self.output.get_mut().write_all(preamble.as_bytes()),
+			None => Ok(()),
+		}
+		.map_err(crate::Error::from)
+		.and_then(|()| transcode(&mut self.output));
+		let committed = self.output.get_mut().end_document(result.is_ok());
+		result?;
+		Ok(committed?)
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<'_, W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		self.stage(|output| output.transcode_from(de))
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		self.stage(|output| output.transcode_value(value))
+	}
+
+	fn transcode_msgpack(&mut self, input: &[u8]) -> crate::Result<()> {
+		self.stage(|output| output.transcode_msgpack(input))
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn discard_uncommitted_documents() {
+		let mut w = Writer::new(vec![]);
+		w.begin_document();
+		w.write_all(b"{\"a\":").unwrap();
+		w.end_document(false).unwrap();
+		w.begin_document();
+		w.write_all(b"{\"b\":1}\n").unwrap();
+		assert!(w.inner.is_empty());
+		w.end_document(true).unwrap();
+		w.begin_document();
+		w.write_all(b"{\"c\":").unwrap();
+		w.end_document(false).unwrap();
+		assert!(w.inner.is_empty());
+		w.write_all(b"{}\n").unwrap();
+		assert_eq!(str::from_utf8(&w.inner), Ok("{\"b\":1}\n{}\n"));
+	}
+
+	#[test]
+	fn batch_committed_documents() {
+		let mut w = Writer::new(vec![]);
+		let document = [b'x'; 1000];
+		for _ in 0..BATCH_LIMIT / document.len() {
+			w.begin_document();
+			w.write_all(&document).unwrap();
+			w.end_document(true).unwrap();
+		}
+		assert!(w.inner.is_empty());
+		w.begin_document();
+		w.write_all(&document).unwrap();
+		w.end_document(true).unwrap();
+		assert_eq!(
+			w.inner.len(),
+			(BATCH_LIMIT / document.len() + 1) * document.len()
+		);
+
+		w.begin_document();
+		w.write_all(b"y").unwrap();
+		w.end_document(true).unwrap();
+		w.flush().unwrap();
+		assert_eq!(w.inner.last(), Some(&b'y'));
+	}
+}
@@ -0,0 +1,286 @@
+//! Reports of strings whose contents are likely damage rather than data, for cleaning inputs that
+//! came through tools with careless handling of text.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::transcode::Value;
+use crate::transform::{key_str, path::push_key};
+
+/// The character that stands in for invalid UTF-8 and lone surrogate escapes.
+const REPLACEMENT: char = char::REPLACEMENT_CHARACTER;
+
+/// A string of an input document containing a control character or a replacement character, as
+/// passed to a [string issue handler](crate::Translator::set_string_issue_handler).
+#[derive(Debug, Clone)]
+pub struct StringIssue {
+	source: Option<String>,
+	document: u64,
+	path: String,
+	key: bool,
+	character: char,
+}
+
+impl StringIssue {
+	/// Returns the [source name](crate::Translator::set_source_name) of the input containing the
+	/// string, for a named input.
+	pub fn source(&self) -> Option<&str> {
+		self.source.as_deref()
+	}
+
+	/// Returns the zero-based index of the document containing the string within its input.
+	pub fn document_index(&self) -> u64 {
+		self.document
+	}
+
+	/// Returns the path to the string, or to the key for a string that is a key, in the syntax of
+	/// [`select`](crate::Transform::select) expressions.
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+
+	/// Returns whether the string is a map key rather than a value.
+	pub fn is_key(&self) -> bool {
+		self.key
+	}
+
+	/// Returns the first character of the string that raised the issue.
+	///
+	/// This is U+FFFD for invalid UTF-8 or a lone surrogate escape that the translator replaced,
+	/// or for a replacement character that the input already contained.
+	pub fn character(&self) -> char {
+		self.character
+	}
+}
+
+impl fmt::Display for StringIssue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let kind = if self.key { "key" } else { "string" };
+		let code = u32::from(self.character);
+		if self.character == REPLACEMENT {
+			write!(
+				f,
+				"{kind} at {} contains U+FFFD, which replaces invalid UTF-8 or a lone surrogate",
+				self.path
+			)
+		} else {
+			write!(
+				f,
+				"{kind} at {} contains the control character U+{code:04X}",
+				self.path
+			)
+		}
+	}
+}
+
+pub(crate) type StringIssueHandler = dyn FnMut(StringIssue) + Send;
+
+/// Returns the first character of a string that an audit reports, if any.
+///
+/// Tabs and line breaks are common enough in real text that only other control characters count.
+fn suspicious(s: &str) -> Option<char> {
+	s.chars()
+		.find(|&c| c == REPLACEMENT || (c.is_control() && !matches!(c, '\t' | '\n' | '\r')))
+}
+
+/// Replaces invalid UTF-8 in a text input, and lone surrogate escapes in a JSON input, with the
+/// replacement character, so that strings containing them reach an audit instead of failing the
+/// translation or vanishing from it.
+pub(crate) fn repair(input: Cow<'_, [u8]>, json: bool) -> Cow<'_, [u8]> {
+	let (input, _) = crate::utf8::lossy(input);
+	if !json {
+		return input;
+	}
+
+	// The escape for U+FFFD is as long as that of any surrogate, so replacing one in place keeps
+	// the offsets of everything after it.
+	let mut input = input;
+	let mut i = 0;
+	while let Some(pos) = input[i..].iter().position(|&b| b == b'\\') {
+		let start = i + pos;
+		let Some(unit) = escaped_unit(&input, start) else {
+			i = start + 2;
+			continue;
+		};
+		i = start + 6;
+		match unit {
+			0xD800..=0xDBFF => {
+				if matches!(escaped_unit(&input, i), Some(0xDC00..=0xDFFF)) {
+					i += 6;
+				} else {
+					input.to_mut()[start..i].copy_from_slice(br"\ufffd");
+				}
+			}
+			0xDC00..=0xDFFF => input.to_mut()[start..i].copy_from_slice(br"\ufffd"),
+			_ => {}
+		}
+	}
+	input
+}
+
+/// Returns the UTF-16 code unit of a `\u` escape at the start position, if there is one.
+fn escaped_unit(input: &[u8], start: usize) -> Option<u16> {
+	let escape = input.get(start..start + 6)?;
+	let hex = escape.strip_prefix(br"\u")?;
+	u16::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+}
+
+/// An [`Output`](crate::Output) that reports suspicious strings in each document to a handler,
+/// when there is one.
+///
+/// Auditing fully deserializes each document into memory.
+pub(crate) struct Output<'h, 's, O> {
+	output: O,
+	handler: Option<&'h mut StringIssueHandler>,
+	source: Option<&'s str>,
+	document: u64,
+}
+
+impl<'h, 's, O> Output<'h, 's, O> {
+	pub(crate) fn new(
+		output: O,
+		handler: Option<&'h mut StringIssueHandler>,
+		source: Option<&'s str>,
+	) -> Output<'h, 's, O> {
+		Output {
+			output,
+			handler,
+			source,
+			document: 0,
+		}
+	}
+
+	fn audit(&mut self, value: &Value) {
+		let Some(handler) = self.handler.as_deref_mut() else {
+			return;
+		};
+		let (source, document) = (self.source, self.document);
+		audit_strings(value, &mut String::new(), &mut |path, key, character| {
+			handler(StringIssue {
+				source: source.map(Into::into),
+				document,
+				path: if path.is_empty() { "." } else { path }.into(),
+				key,
+				character,
+			});
+		});
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, '_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.handler.is_none() {
+			return self.output.transcode_from(de);
+		}
+		self.transcode_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.handler.is_none() {
+			return self.output.transcode_borrowed_from(de);
+		}
+		self.transcode_borrowed_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		self.audit(&value);
+		self.output.transcode_value(value)
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		self.audit(&value);
+		self.output.transcode_borrowed_value(value)
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.document = location.index;
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+/// Calls `report` with the path of every suspicious string within a value, whether it's a key,
+/// and the character that makes it suspicious, where `path` is the path to the value.
+fn audit_strings(value: &Value, path: &mut String, report: &mut impl FnMut(&str, bool, char)) {
+	let len = path.len();
+	match value {
+		Value::String(s) => {
+			if let Some(c) = suspicious(s) {
+				report(path, false, c);
+			}
+		}
+		Value::Seq(seq) => {
+			for (i, v) in seq.iter().enumerate() {
+				path.push_str(&format!("[{i}]"));
+				audit_strings(v, path, report);
+				path.truncate(len);
+			}
+		}
+		Value::Map(map) => {
+			for (k, v) in map {
+				let key = match key_str(k) {
+					Some(key) => key.into_owned(),
+					None => serde_json::to_string(k).unwrap_or_default(),
+				};
+				push_key(path, &key);
+				if let Value::String(k) = k {
+					if let Some(c) = suspicious(k) {
+						report(path, true, c);
+					}
+				}
+				audit_strings(v, path, report);
+				path.truncate(len);
+			}
+		}
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn repair_and_audit_strings() {
+		let input =
+			b"{\"a\\u0007\": [\"ok\\t\", \"\\ud800x\", \"\\ud83d\\ude00\"], \"b\": \"\xff\"}";
+		let repaired = repair(Cow::Borrowed(input), true);
+		let value: Value = serde_json::from_slice(&repaired).unwrap();
+		let mut found = vec![];
+		audit_strings(&value, &mut String::new(), &mut |path, key, c| {
+			found.push((path.to_owned(), key, c));
+		});
+		assert_eq!(
+			found,
+			[
+				(r#"."a\u0007""#.to_owned(), true, '\u{7}'),
+				(r#"."a\u0007"[1]"#.to_owned(), false, REPLACEMENT),
+				(".b".to_owned(), false, REPLACEMENT),
+			]
+		);
+
+		// Valid input passes through without a copy.
+		assert!(matches!(
+			repair(Cow::Borrowed(br#"["\ud83d\ude00"]"#), true),
+			Cow::Borrowed(_)
+		));
+	}
+}
@@ -0,0 +1,629 @@
+//! The Avro object container file format, as written by Hadoop, Kafka Connect, and Spark.
+//!
+//! A container file embeds the schema of its records in a header, followed by blocks of records
+//! that the schema alone can decode, so that xt reads them with no schema of its own. This follows
+//! the Avro specification directly rather than pulling in a dependency, with decoders for the
+//! `deflate` and `snappy` codecs alongside uncompressed blocks.
+//!
+//! Each record translates as a separate document. Records and maps translate as maps, enums as the
+//! names of their symbols, unions as the values of their branches, and fixed values as bytes.
+//! Logical types like dates and decimals translate as their underlying types, like the number of
+//! days since the Unix epoch for a date.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+use std::str;
+
+use serde::de;
+
+use crate::detect::Trial;
+use crate::input::{self, DocumentSizeError, Input, Ref};
+use crate::transcode::{self, Value};
+
+mod inflate;
+mod snappy;
+
+/// The bytes that start every object container file.
+const MAGIC: &[u8; 4] = b"Obj\x01";
+
+/// The maximum allowed nesting depth of values, the same as for BSON.
+pub(crate) const DEPTH_LIMIT: usize = 1024;
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// Container files carry the .avro extension in practice, which selects the format without
+	// detection.
+	Ok(Err("Avro is never detected from content".into()))
+}
+
+/// Transcodes the records of an Avro object container file to the output, one block at a time.
+///
+/// With `max_size`, translation fails on the first block whose size exceeds it, whether
+/// compressed or not, before reading the rest of the block.
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	output: O,
+	max_size: Option<u64>,
+) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	match Input::try_from(input)? {
+		Input::Slice(b) => transcode_from(&mut &*b, output, max_size),
+		Input::Reader(r) => transcode_from(&mut BufReader::new(r), output, max_size),
+	}
+}
+
+fn transcode_from<'i, R, O>(r: &mut R, mut output: O, max_size: Option<u64>) -> crate::Result<()>
+where
+	R: Read,
+	O: crate::Output<'i>,
+{
+	let mut magic = [0; 4];
+	if read_up_to(r, &mut magic)? < MAGIC.len() || magic != *MAGIC {
+		return Err(DecodeError::Header("missing the magic bytes of a container file").into());
+	}
+	let metadata = read_metadata(r)?;
+	let Some(schema) = metadata.get("avro.schema") else {
+		return Err(DecodeError::Header("no schema in the file metadata").into());
+	};
+	let schema: serde_json::Value =
+		serde_json::from_slice(schema).map_err(|err| DecodeError::Schema(err.to_string()))?;
+	let mut schemas = Schemas::default();
+	let root = schemas.parse(&schema, "")?;
+	let codec = match metadata.get("avro.codec").map(Vec::as_slice) {
+		None | Some(b"null") => Codec::Null,
+		Some(b"deflate") => Codec::Deflate,
+		Some(b"snappy") => Codec::Snappy,
+		Some(name) => return Err(DecodeError::Codec(String::from_utf8_lossy(name).into()).into()),
+	};
+	let mut sync = [0; 16];
+	if read_up_to(r, &mut sync)? < sync.len() {
+		return Err(DecodeError::Header("truncated sync marker").into());
+	}
+
+	let mut buf = vec![];
+	for index in 0.. {
+		let invalid = |message| DecodeError::Block { index, message };
+		let Some(count) = read_long(r)? else {
+			break;
+		};
+		let (Ok(count), Some(Ok(size))) = (u64::try_from(count), read_long(r)?.map(u64::try_from))
+		else {
+			return Err(invalid("invalid record count or size").into());
+		};
+		if let Some(max) = max_size.filter(|&max| size > max) {
+			return Err(DocumentSizeError {
+				size: Some(size),
+				max,
+			}
+			.into());
+		}
+		buf.clear();
+		if (r.take(size).read_to_end(&mut buf)? as u64) < size {
+			return Err(invalid("truncated block").into());
+		}
+		let mut marker = [0; 16];
+		if read_up_to(r, &mut marker)? < marker.len() || marker != sync {
+			return Err(invalid("sync marker doesn't match the header").into());
+		}
+
+		let decompressed;
+		let data = match codec {
+			Codec::Null => &buf,
+			Codec::Deflate => {
+				decompressed = inflate::inflate(&buf, max_size).map_err(|err| match err {
+					inflate::InflateError::Invalid(message) => crate::Error::from(invalid(message)),
+					inflate::InflateError::Size(err) => err.into(),
+				})?;
+				&decompressed
+			}
+			Codec::Snappy => {
+				decompressed = snappy::decompress(&buf, max_size).map_err(|err| match err {
+					snappy::SnappyError::Invalid(message) => crate::Error::from(invalid(message)),
+					snappy::SnappyError::Size(err) => err.into(),
+				})?;
+				&decompressed
+			}
+		};
+		let mut decoder = Decoder {
+			input: data,
+			pos: 0,
+			schemas: &schemas.types,
+		};
+		for _ in 0..count {
+			let value = decoder.value(root, DEPTH_LIMIT).map_err(invalid)?;
+			output.transcode_value(value)?;
+		}
+		if decoder.pos != data.len() {
+			return Err(invalid("block continues past its last record").into());
+		}
+	}
+	Ok(())
+}
+
+/// Reads into the buffer until it's full or the input ends, returning how much it read.
+fn read_up_to<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+	let mut len = 0;
+	while len < buf.len() {
+		match r.read(&mut buf[len..]) {
+			Ok(0) => break,
+			Ok(n) => len += n,
+			Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+			Err(err) => return Err(err),
+		}
+	}
+	Ok(len)
+}
+
+/// Reads a zig-zag encoded variable-length long, or returns `None` if the input ends before it.
+fn read_long<R: Read>(r: &mut R) -> crate::Result<Option<i64>> {
+	let mut n = 0;
+	for shift in (0..64).step_by(7) {
+		let mut byte = [0];
+		if read_up_to(r, &mut byte)? == 0 {
+			return match shift {
+				0 => Ok(None),
+				_ => Err(DecodeError::Header("truncated variable-length integer").into()),
+			};
+		}
+		n |= u64::from(byte[0] & 0x7f) << shift;
+		if byte[0] & 0x80 == 0 {
+			return Ok(Some(zigzag(n)));
+		}
+	}
+	Err(DecodeError::Header("invalid variable-length integer").into())
+}
+
+fn zigzag(n: u64) -> i64 {
+	(n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+/// Reads the metadata map of the file header, whose values are all bytes.
+fn read_metadata<R: Read>(r: &mut R) -> crate::Result<HashMap<String, Vec<u8>>> {
+	let mut metadata = HashMap::new();
+	loop {
+		let Some(count) = read_long(r)? else {
+			return Err(DecodeError::Header("truncated metadata").into());
+		};
+		if count == 0 {
+			return Ok(metadata);
+		}
+		if count < 0 {
+			// A negative count comes with the size of the block, which a reader can skip.
+			read_long(r)?;
+		}
+		for _ in 0..count.unsigned_abs() {
+			let key = read_bytes(r)?;
+			let key = String::from_utf8(key)
+				.map_err(|_| DecodeError::Header("metadata key that isn't UTF-8"))?;
+			metadata.insert(key, read_bytes(r)?);
+		}
+	}
+}
+
+fn read_bytes<R: Read>(r: &mut R) -> crate::Result<Vec<u8>> {
+	let Some(Ok(len)) = read_long(r)?.map(u64::try_from) else {
+		return Err(DecodeError::Header("invalid metadata length").into());
+	};
+	// The buffer grows as the bytes arrive, rather than trusting the file with the size of an
+	// allocation up front.
+	let mut buf = vec![];
+	if (r.take(len).read_to_end(&mut buf)? as u64) < len {
+		return Err(DecodeError::Header("truncated metadata").into());
+	}
+	Ok(buf)
+}
+
+#[derive(Clone, Copy)]
+enum Codec {
+	Null,
+	Deflate,
+	Snappy,
+}
+
+/// A type of the schema, with other types referenced by their indexes in [`Schemas::types`].
+enum Schema {
+	Null,
+	Boolean,
+	Int,
+	Long,
+	Float,
+	Double,
+	Bytes,
+	String,
+	Record(Vec<(String, usize)>),
+	Enum(Vec<String>),
+	Array(usize),
+	Map(usize),
+	Union(Vec<usize>),
+	Fixed(usize),
+}
+
+/// Every type of a schema, so that named types can refer to each other and to themselves.
+#[derive(Default)]
+struct Schemas {
+	types: Vec<Schema>,
+	names: HashMap<String, usize>,
+}
+
+impl Schemas {
+	/// Parses a schema within the namespace of its enclosing named type, returning its index.
+	fn parse(&mut self, json: &serde_json::Value, namespace: &str) -> Result<usize, DecodeError> {
+		use serde_json::Value as Json;
+
+		let object = match json {
+			Json::String(name) => return self.named(name, namespace),
+			Json::Array(branches) => {
+				let branches = branches
+					.iter()
+					.map(|branch| self.parse(branch, namespace))
+					.collect::<Result<_, _>>()?;
+				return Ok(self.push(Schema::Union(branches)));
+			}
+			Json::Object(object) => object,
+			_ => return Err(DecodeError::Schema(format!("invalid type {json}"))),
+		};
+		let name = match object.get("type") {
+			Some(Json::String(name)) => name.as_str(),
+			// A field's type may be a full schema instead of a name, which some writers also
+			// nest under the type of a schema.
+			Some(nested) => return self.parse(nested, namespace),
+			None => {
+				return Err(DecodeError::Schema(format!(
+					"type without a name in {json}"
+				)));
+			}
+		};
+		let string = |key: &str| object.get(key).and_then(Json::as_str);
+		let fullname = |kind: &str| match string("name") {
+			Some(name) if name.contains('.') => Ok(name.to_owned()),
+			Some(name) => match string("namespace").unwrap_or(namespace) {
+				"" => Ok(name.to_owned()),
+				namespace => Ok(format!("{namespace}.{name}")),
+			},
+			None => Err(DecodeError::Schema(format!("{kind} without a name"))),
+		};
+		match name {
+			"record" | "error" => {
+				let fullname = fullname("record")?;
+				let namespace = fullname.rsplit_once('.').map_or("", |(ns, _)| ns);
+				// The record takes its index before its fields, which may refer back to it.
+				let index = self.push(Schema::Null);
+				self.names.insert(fullname.clone(), index);
+				let Some(fields) = object.get("fields").and_then(Json::as_array) else {
+					return Err(DecodeError::Schema(format!(
+						"record {fullname} without fields"
+					)));
+				};
+				let mut record = vec![];
+				for field in fields {
+					let (Some(name), Some(ty)) =
+						(field.get("name").and_then(Json::as_str), field.get("type"))
+					else {
+						return Err(DecodeError::Schema(format!(
+							"invalid field in record {fullname}"
+						)));
+					};
+					record.push((name.to_owned(), self.parse(ty, namespace)?));
+				}
+				self.types[index] = Schema::Record(record);
+				Ok(index)
+			}
+			"enum" => {
+				let fullname = fullname("enum")?;
+				let symbols = object.get("symbols").and_then(Json::as_array);
+				let Some(symbols) = symbols
+					.map(|symbols| {
+						symbols
+							.iter()
+							.map(|s| s.as_str().map(str::to_owned))
+							.collect()
+					})
+					.and_then(|symbols: Option<Vec<_>>| symbols)
+				else {
+					return Err(DecodeError::Schema(format!(
+						"enum {fullname} without symbols"
+					)));
+				};
+				let index = self.push(Schema::Enum(symbols));
+				self.names.insert(fullname, index);
+				Ok(index)
+			}
+			"fixed" => {
+				let fullname = fullname("fixed")?;
+				let Some(size) = object.get("size").and_then(Json::as_u64) else {
+					return Err(DecodeError::Schema(format!(
+						"fixed {fullname} without a size"
+					)));
+				};
+				let size = usize::try_from(size)
+					.map_err(|_| DecodeError::Schema(format!("fixed {fullname} too large")))?;
+				let index = self.push(Schema::Fixed(size));
+				self.names.insert(fullname, index);
+				Ok(index)
+			}
+			"array" => {
+				let Some(items) = object.get("items") else {
+					return Err(DecodeError::Schema("array without items".into()));
+				};
+				let items = self.parse(items, namespace)?;
+				Ok(self.push(Schema::Array(items)))
+			}
+			"map" => {
+				let Some(values) = object.get("values") else {
+					return Err(DecodeError::Schema("map without values".into()));
+				};
+				let values = self.parse(values, namespace)?;
+				Ok(self.push(Schema::Map(values)))
+			}
+			// Any other attributes, like a logical type, leave the encoding of a type unchanged.
+			name => self.named(name, namespace),
+		}
+	}
+
+	/// Returns the index of a primitive type, or of a named type defined earlier in the schema.
+	fn named(&mut self, name: &str, namespace: &str) -> Result<usize, DecodeError> {
+		let primitive = match name {
+			"null" => Schema::Null,
+			"boolean" => Schema::Boolean,
+			"int" => Schema::Int,
+			"long" => Schema::Long,
+			"float" => Schema::Float,
+			"double" => Schema::Double,
+			"bytes" => Schema::Bytes,
+			"string" => Schema::String,
+			_ => {
+				let qualified = (!name.contains('.') && !namespace.is_empty())
+					.then(|| self.names.get(&format!("{namespace}.{name}")))
+					.flatten();
+				return qualified
+					.or_else(|| self.names.get(name))
+					.copied()
+					.ok_or_else(|| DecodeError::Schema(format!("unknown type {name}")));
+			}
+		};
+		Ok(self.push(primitive))
+	}
+
+	fn push(&mut self, schema: Schema) -> usize {
+		self.types.push(schema);
+		self.types.len() - 1
+	}
+}
+
+/// A decoder of the records within a single block.
+struct Decoder<'a> {
+	input: &'a [u8],
+	pos: usize,
+	schemas: &'a [Schema],
+}
+
+impl<'a> Decoder<'a> {
+	fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+		let bytes = self
+			.input
+			.get(self.pos..)
+			.and_then(|rest| rest.get(..len))
+			.ok_or("unexpected end of block")?;
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	fn take_array<const N: usize>(&mut self) -> Result<[u8; N], &'static str> {
+		Ok(self
+			.take(N)?
+			.try_into()
+			.expect("slice should have the length of the array"))
+	}
+
+	fn long(&mut self) -> Result<i64, &'static str> {
+		let mut n = 0;
+		for shift in (0..64).step_by(7) {
+			let [byte] = self.take_array()?;
+			n |= u64::from(byte & 0x7f) << shift;
+			if byte & 0x80 == 0 {
+				return Ok(zigzag(n));
+			}
+		}
+		Err("invalid variable-length integer")
+	}
+
+	fn len(&mut self) -> Result<usize, &'static str> {
+		usize::try_from(self.long()?).map_err(|_| "negative length")
+	}
+
+	fn bytes(&mut self) -> Result<&'a [u8], &'static str> {
+		let len = self.len()?;
+		self.take(len)
+	}
+
+	fn string(&mut self) -> Result<String, &'static str> {
+		let bytes = self.bytes()?;
+		Ok(str::from_utf8(bytes)
+			.map_err(|_| "invalid UTF-8")?
+			.to_owned())
+	}
+
+	/// Decodes the items of an array or the entries of a map, given the decoder of each.
+	fn blocks<T>(
+		&mut self,
+		mut item: impl FnMut(&mut Self) -> Result<T, &'static str>,
+	) -> Result<Vec<T>, &'static str> {
+		let mut items = vec![];
+		loop {
+			let count = self.long()?;
+			if count == 0 {
+				return Ok(items);
+			}
+			if count < 0 {
+				// A negative count comes with the size of the block, which a reader can skip.
+				self.long()?;
+			}
+			for _ in 0..count.unsigned_abs() {
+				items.push(item(self)?);
+			}
+		}
+	}
+
+	fn value(&mut self, schema: usize, depth: usize) -> Result<Value<'static>, &'static str> {
+		let Some(depth) = depth.checked_sub(1) else {
+			return Err("values nested too deeply");
+		};
+		let string = |s: String| Value::String(s.into());
+		Ok(match &self.schemas[schema] {
+			Schema::Null => Value::Unit,
+			Schema::Boolean => match self.take_array()? {
+				[0] => Value::Bool(false),
+				[1] => Value::Bool(true),
+				_ => return Err("invalid boolean"),
+			},
+			Schema::Int => Value::I32(i32::try_from(self.long()?).map_err(|_| "int out of range")?),
+			Schema::Long => Value::I64(self.long()?),
+			Schema::Float => Value::F32(f32::from_le_bytes(self.take_array()?)),
+			Schema::Double => Value::F64(f64::from_le_bytes(self.take_array()?)),
+			Schema::Bytes => Value::Bytes(self.bytes()?.to_vec().into()),
+			Schema::String => string(self.string()?),
+			Schema::Record(fields) => {
+				let mut entries = Vec::with_capacity(fields.len());
+				for (name, field) in fields {
+					entries.push((string(name.clone()), self.value(*field, depth)?));
+				}
+				Value::Map(entries)
+			}
+			Schema::Enum(symbols) => {
+				let symbol = usize::try_from(self.long()?)
+					.ok()
+					.and_then(|i| symbols.get(i))
+					.ok_or("enum index out of range")?;
+				string(symbol.clone())
+			}
+			&Schema::Array(items) => Value::Seq(self.blocks(|d| d.value(items, depth))?),
+			&Schema::Map(values) => Value::Map(self.blocks(|d| {
+				let key = string(d.string()?);
+				Ok((key, d.value(values, depth)?))
+			})?),
+			Schema::Union(branches) => {
+				let branch = usize::try_from(self.long()?)
+					.ok()
+					.and_then(|i| branches.get(i))
+					.ok_or("union index out of range")?;
+				self.value(*branch, depth)?
+			}
+			&Schema::Fixed(size) => Value::Bytes(self.take(size)?.to_vec().into()),
+		})
+	}
+}
+
+/// The error for an Avro container file that doesn't follow the specification, or uses a codec
+/// that xt doesn't support.
+#[derive(Debug)]
+pub(crate) enum DecodeError {
+	Header(&'static str),
+	Schema(String),
+	Codec(String),
+	Block { index: u64, message: &'static str },
+}
+
+impl error::Error for DecodeError {}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Header(message) => write!(f, "invalid Avro file header: {message}"),
+			Self::Schema(message) => write!(f, "invalid Avro schema: {message}"),
+			Self::Codec(name) => write!(f, "unsupported Avro codec: {name}"),
+			Self::Block { index, message } => {
+				write!(f, "invalid Avro data in block {index}: {message}")
+			}
+		}
+	}
+}
+
+/// An output for Avro, which is an input format only.
+pub(crate) struct Output<W: Write>(W);
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output(w)
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.0
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, _: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		Err(OutputError.into())
+	}
+
+	fn transcode_value(&mut self, _: transcode::Value) -> crate::Result<()> {
+		Err(OutputError.into())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.0.flush()
+	}
+}
+
+/// The error for an attempt to translate to Avro, whose output would need a schema for every
+/// document.
+#[derive(Debug)]
+pub(crate) struct OutputError;
+
+impl error::Error for OutputError {}
+
+impl fmt::Display for OutputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("Avro is an input format only")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decode_with_named_and_recursive_types() {
+		let schema = serde_json::json!({
+			"type": "record",
+			"name": "Node",
+			"namespace": "test",
+			"fields": [
+				{"name": "label", "type": {"type": "enum", "name": "Label", "symbols": ["A", "B"]}},
+				{"name": "tags", "type": {"type": "map", "values": "test.Label"}},
+				{"name": "next", "type": ["null", "Node"]},
+			],
+		});
+		let mut schemas = Schemas::default();
+		let root = schemas.parse(&schema, "").unwrap();
+		// B, {"x": A}, then a union to a node of A, {}, null.
+		let input = b"\x02\x02\x02x\x00\x00\x02\x00\x00\x00";
+		let mut decoder = Decoder {
+			input,
+			pos: 0,
+			schemas: &schemas.types,
+		};
+		let value = decoder.value(root, DEPTH_LIMIT).unwrap();
+		assert_eq!(decoder.pos, input.len());
+		assert_eq!(
+			serde_json::to_string(&value).unwrap(),
+			r#"{"label":"B","tags":{"x":"A"},"next":{"label":"A","tags":{},"next":null}}"#
+		);
+	}
+
+	#[test]
+	fn reject_unknown_type_names() {
+		let schema = serde_json::json!({"type": "array", "items": "Missing"});
+		let Err(DecodeError::Schema(message)) = Schemas::default().parse(&schema, "") else {
+			panic!("parsed a schema with an unknown type");
+		};
+		assert_eq!(message, "unknown type Missing");
+	}
+}
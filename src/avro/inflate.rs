@@ -0,0 +1,311 @@
+//! A decoder of raw DEFLATE data, as compressed by Avro's `deflate` codec.
+//!
+//! This follows RFC 1951 in the manner of zlib's `puff`, favoring a small and plainly correct
+//! decoder over a fast one, since it only has to keep up with the decoding of Avro records.
+
+use crate::input::DocumentSizeError;
+
+/// The base lengths of the length symbols from 257, and the extra bits after each.
+const LENGTH_BASE: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+	163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The base distances of the distance symbols, and the extra bits after each.
+const DISTANCE_BASE: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+	2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+	13,
+];
+
+/// The order in which a dynamic block lists the code lengths of its code length alphabet.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+	16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const MAX_BITS: usize = 15;
+
+/// The error for compressed data that fails to decompress.
+#[derive(Debug)]
+pub(super) enum InflateError {
+	Invalid(&'static str),
+	Size(DocumentSizeError),
+}
+
+/// Decompresses raw DEFLATE data, producing no more than `max_size` bytes if given.
+pub(super) fn inflate(input: &[u8], max_size: Option<u64>) -> Result<Vec<u8>, InflateError> {
+	let mut inflater = Inflater {
+		input,
+		pos: 0,
+		bits: 0,
+		count: 0,
+		output: vec![],
+		max_size,
+	};
+	loop {
+		let last = inflater.bits(1)? == 1;
+		match inflater.bits(2)? {
+			0 => inflater.stored()?,
+			1 => inflater.codes(&Huffman::fixed_lengths(), &Huffman::fixed_distances())?,
+			2 => {
+				let (lengths, distances) = inflater.dynamic_tables()?;
+				inflater.codes(&lengths, &distances)?;
+			}
+			_ => return Err(InflateError::Invalid("invalid block type")),
+		}
+		if last {
+			return Ok(inflater.output);
+		}
+	}
+}
+
+struct Inflater<'a> {
+	input: &'a [u8],
+	pos: usize,
+	/// Bits read from the input but not yet consumed, starting from the least significant.
+	bits: u32,
+	count: u32,
+	output: Vec<u8>,
+	max_size: Option<u64>,
+}
+
+impl Inflater<'_> {
+	fn bits(&mut self, n: u32) -> Result<u32, InflateError> {
+		while self.count < n {
+			let Some(&byte) = self.input.get(self.pos) else {
+				return Err(InflateError::Invalid("unexpected end of compressed data"));
+			};
+			self.pos += 1;
+			self.bits |= u32::from(byte) << self.count;
+			self.count += 8;
+		}
+		let value = self.bits & ((1 << n) - 1);
+		self.bits >>= n;
+		self.count -= n;
+		Ok(value)
+	}
+
+	fn check_size(&self, additional: usize) -> Result<(), InflateError> {
+		let size = (self.output.len() + additional) as u64;
+		match self.max_size {
+			Some(max) if size > max => {
+				Err(InflateError::Size(DocumentSizeError { size: None, max }))
+			}
+			_ => Ok(()),
+		}
+	}
+
+	fn stored(&mut self) -> Result<(), InflateError> {
+		// A stored block starts at the next byte boundary, discarding the rest of the current one.
+		self.bits = 0;
+		self.count = 0;
+		let header = self
+			.input
+			.get(self.pos..self.pos + 4)
+			.ok_or(InflateError::Invalid("unexpected end of compressed data"))?;
+		let len = u16::from_le_bytes([header[0], header[1]]);
+		if len != !u16::from_le_bytes([header[2], header[3]]) {
+			return Err(InflateError::Invalid(
+				"stored block length doesn't match its complement",
+			));
+		}
+		self.pos += 4;
+		let data = self
+			.input
+			.get(self.pos..self.pos + usize::from(len))
+			.ok_or(InflateError::Invalid("unexpected end of compressed data"))?;
+		self.check_size(data.len())?;
+		self.output.extend_from_slice(data);
+		self.pos += data.len();
+		Ok(())
+	}
+
+	fn dynamic_tables(&mut self) -> Result<(Huffman, Huffman), InflateError> {
+		let nlen = self.bits(5)? as usize + 257;
+		let ndist = self.bits(5)? as usize + 1;
+		let ncode = self.bits(4)? as usize + 4;
+		if nlen > 286 || ndist > 30 {
+			return Err(InflateError::Invalid("too many length or distance codes"));
+		}
+		let mut code_lengths = [0; 19];
+		for &i in &CODE_LENGTH_ORDER[..ncode] {
+			code_lengths[i] = u8::try_from(self.bits(3)?).expect("3 bits should fit in a byte");
+		}
+		let code_lengths = Huffman::new(&code_lengths)?;
+
+		let mut lengths = vec![];
+		while lengths.len() < nlen + ndist {
+			let (len, repeat) = match self.decode(&code_lengths)? {
+				len @ 0..16 => (u8::try_from(len).expect("length should fit in a byte"), 1),
+				16 => {
+					let Some(&previous) = lengths.last() else {
+						return Err(InflateError::Invalid(
+							"repeated code length without a first",
+						));
+					};
+					(previous, 3 + self.bits(2)?)
+				}
+				17 => (0, 3 + self.bits(3)?),
+				_ => (0, 11 + self.bits(7)?),
+			};
+			if lengths.len() + repeat as usize > nlen + ndist {
+				return Err(InflateError::Invalid("too many code lengths"));
+			}
+			lengths.extend((0..repeat).map(|_| len));
+		}
+		if lengths[256] == 0 {
+			return Err(InflateError::Invalid("no code for the end of the block"));
+		}
+		Ok((
+			Huffman::new(&lengths[..nlen])?,
+			Huffman::new(&lengths[nlen..])?,
+		))
+	}
+
+	fn codes(&mut self, lengths: &Huffman, distances: &Huffman) -> Result<(), InflateError> {
+		loop {
+			let symbol = usize::from(self.decode(lengths)?);
+			match symbol {
+				0..256 => {
+					self.check_size(1)?;
+					self.output
+						.push(u8::try_from(symbol).expect("literal should be a byte"));
+				}
+				256 => return Ok(()),
+				_ => {
+					let i = symbol - 257;
+					let (Some(&base), Some(&extra)) = (LENGTH_BASE.get(i), LENGTH_EXTRA.get(i))
+					else {
+						return Err(InflateError::Invalid("invalid length symbol"));
+					};
+					let len = usize::from(base) + self.bits(extra.into())? as usize;
+					let i = usize::from(self.decode(distances)?);
+					let (Some(&base), Some(&extra)) = (DISTANCE_BASE.get(i), DISTANCE_EXTRA.get(i))
+					else {
+						return Err(InflateError::Invalid("invalid distance symbol"));
+					};
+					let distance = usize::from(base) + self.bits(extra.into())? as usize;
+					if distance > self.output.len() {
+						return Err(InflateError::Invalid("distance too far back"));
+					}
+					self.check_size(len)?;
+					// The copy may overlap the bytes it produces, so it proceeds a byte at a time.
+					let start = self.output.len() - distance;
+					for i in start..start + len {
+						self.output.push(self.output[i]);
+					}
+				}
+			}
+		}
+	}
+
+	fn decode(&mut self, huffman: &Huffman) -> Result<u16, InflateError> {
+		// Canonical codes of each length are consecutive, and follow every shorter code.
+		let (mut code, mut first, mut index) = (0, 0, 0);
+		for &count in &huffman.counts[1..] {
+			code |= self.bits(1)? as usize;
+			if code < first + usize::from(count) {
+				return Ok(huffman.symbols[index + code - first]);
+			}
+			index += usize::from(count);
+			first = (first + usize::from(count)) << 1;
+			code <<= 1;
+		}
+		Err(InflateError::Invalid("invalid Huffman code"))
+	}
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in code order.
+struct Huffman {
+	counts: [u16; MAX_BITS + 1],
+	symbols: Vec<u16>,
+}
+
+impl Huffman {
+	fn new(lengths: &[u8]) -> Result<Huffman, InflateError> {
+		let mut counts = [0; MAX_BITS + 1];
+		for &len in lengths {
+			counts[usize::from(len)] += 1;
+		}
+		let mut left: i32 = 1;
+		for &count in &counts[1..] {
+			left = (left << 1) - i32::from(count);
+			if left < 0 {
+				return Err(InflateError::Invalid("over-subscribed Huffman code"));
+			}
+		}
+		let mut offsets = [0; MAX_BITS + 1];
+		for len in 1..MAX_BITS {
+			offsets[len + 1] = offsets[len] + counts[len];
+		}
+		let mut symbols = vec![0; lengths.len()];
+		for (symbol, &len) in lengths.iter().enumerate() {
+			if len != 0 {
+				symbols[usize::from(offsets[usize::from(len)])] =
+					u16::try_from(symbol).expect("symbol should fit in 16 bits");
+				offsets[usize::from(len)] += 1;
+			}
+		}
+		counts[0] = 0;
+		Ok(Huffman { counts, symbols })
+	}
+
+	fn fixed_lengths() -> Huffman {
+		let lengths: Vec<u8> = (0..288)
+			.map(|symbol| match symbol {
+				0..144 => 8,
+				144..256 => 9,
+				256..280 => 7,
+				_ => 8,
+			})
+			.collect();
+		Huffman::new(&lengths).expect("fixed code should be valid")
+	}
+
+	fn fixed_distances() -> Huffman {
+		Huffman::new(&[5; 30]).expect("fixed code should be valid")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn inflate(input: &[u8]) -> Vec<u8> {
+		match super::inflate(input, None) {
+			Ok(output) => output,
+			Err(InflateError::Invalid(message)) => panic!("{message}"),
+			Err(InflateError::Size(err)) => panic!("{err}"),
+		}
+	}
+
+	#[test]
+	fn inflate_each_block_type() {
+		// Compressed by Python's zlib with a window of -15 bits, for raw DEFLATE data.
+		assert_eq!(inflate(b"\x01\x03\x00\xfc\xffxt!"), b"xt!");
+		assert_eq!(
+			inflate(b"\xcb\x48\xcd\xc9\xc9\xcf\x40\x25\x00"),
+			b"hellohellohellohello"
+		);
+		let dynamic = b"\x5d\x92\x31\x0e\x43\x31\x08\x43\xaf\xc2\x11\x02\x24\x24\x51\x4e\x53\xa9\x1d\x3a\xb6\x55\xef\x5f\xfc\x27\xdc\x31\x08\xd9\xcf\x26\x4d\x3e\xaf\xef\xed\xfd\xb8\xcb\xf3\x23\xed\x88\xd6\xb7\x1e\xb1\xfa\xee\x47\xbc\xbe\xf7\x91\x4e\xfb\x71\x64\xd4\x81\x8d\x23\x51\x07\x9e\x1b\x93\x24\x53\x63\xd5\x41\xa4\xc9\xae\x83\x95\x14\xda\xc8\xa6\x01\x94\x49\x0d\x5b\x04\xab\x3d\x95\xd4\x99\x2f\xdd\x94\x91\x77\x12\x29\x43\x83\x5a\x83\x73\x60\x8b\xc0\x6d\x41\x8b\xd0\xdd\xe0\xb8\x39\x2f\x3a\x24\xfa\x0e\x7a\x23\xfa\xde\xb1\xc5\x55\xaf\xd4\x32\xa2\x1f\x96\x8e\x46\xf4\x63\x26\x97\x11\x7d\x80\xde\x88\x3e\xae\x2d\xa2\x9f\x97\x16\xd1\xcf\xcb\x91\xab\x07\x97\x13\xfd\x06\xbd\x13\xfd\x46\x46\xe7\xee\x1b\xaa\x70\x2e\xbf\xa1\x31\xe7\xf6\x15\xc5\xfa\xe0\x53\x22\x81\x07\xcf\x70\x26\xa7\x08\xea\xb8\xa6\xaf\xbf\x9b\xc3\x97\x42\xe8\xc0\xdf\xf8\x01";
+		let squares: String = (0..40)
+			.map(|i| format!("{i} squared is {}; ", i * i))
+			.collect();
+		assert_eq!(inflate(dynamic), squares.as_bytes());
+	}
+
+	#[test]
+	fn inflate_limits_size() {
+		let Err(InflateError::Size(_)) =
+			super::inflate(b"\xcb\x48\xcd\xc9\xc9\xcf\x40\x25\x00", Some(10))
+		else {
+			panic!("inflated past the maximum size");
+		};
+	}
+}
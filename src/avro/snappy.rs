@@ -0,0 +1,154 @@
+//! A decoder of the Snappy blocks of Avro's `snappy` codec.
+//!
+//! Each block is a raw Snappy block without the framing of Snappy's stream format, followed by the
+//! big-endian CRC-32 of the uncompressed data, as the Avro specification describes.
+
+use crate::input::DocumentSizeError;
+
+/// The error for compressed data that fails to decompress.
+#[derive(Debug)]
+pub(super) enum SnappyError {
+	Invalid(&'static str),
+	Size(DocumentSizeError),
+}
+
+/// Decompresses an Avro Snappy block, refusing to produce more than `max_size` bytes if given.
+pub(super) fn decompress(input: &[u8], max_size: Option<u64>) -> Result<Vec<u8>, SnappyError> {
+	let Some((input, checksum)) = input.split_last_chunk::<4>() else {
+		return Err(SnappyError::Invalid("block too short for its checksum"));
+	};
+	let mut pos = 0;
+	let len = varint(input, &mut pos)?;
+	if let Some(max) = max_size.filter(|&max| len > max) {
+		return Err(SnappyError::Size(DocumentSizeError {
+			size: Some(len),
+			max,
+		}));
+	}
+	let len = usize::try_from(len).map_err(|_| SnappyError::Invalid("block too large"))?;
+
+	// The allocation grows with the output, rather than trusting the stated length up front.
+	let mut output = Vec::with_capacity(len.min(input.len().saturating_mul(8)));
+	while pos < input.len() {
+		let tag = input[pos];
+		pos += 1;
+		let (copy_len, offset) = match tag & 0b11 {
+			0 => {
+				let mut literal_len = usize::from(tag >> 2);
+				if literal_len >= 60 {
+					// The length takes the next 1 to 4 bytes, in little-endian order.
+					let n = literal_len - 59;
+					let bytes = take(input, &mut pos, n)?;
+					literal_len = bytes
+						.iter()
+						.rev()
+						.fold(0, |len, &b| len << 8 | usize::from(b));
+				}
+				let literal = take(input, &mut pos, literal_len + 1)?;
+				output.extend_from_slice(literal);
+				if output.len() > len {
+					return Err(SnappyError::Invalid("block longer than its stated length"));
+				}
+				continue;
+			}
+			1 => {
+				let low = take(input, &mut pos, 1)?[0];
+				(
+					usize::from(tag >> 2 & 0b111) + 4,
+					usize::from(tag >> 5) << 8 | usize::from(low),
+				)
+			}
+			2 => {
+				let bytes = take(input, &mut pos, 2)?;
+				let offset = u16::from_le_bytes([bytes[0], bytes[1]]);
+				(usize::from(tag >> 2) + 1, usize::from(offset))
+			}
+			_ => {
+				let bytes = take(input, &mut pos, 4)?;
+				let offset = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+				(usize::from(tag >> 2) + 1, offset as usize)
+			}
+		};
+		if offset == 0 || offset > output.len() {
+			return Err(SnappyError::Invalid("copy from outside the output"));
+		}
+		if output.len() + copy_len > len {
+			return Err(SnappyError::Invalid("block longer than its stated length"));
+		}
+		// The copy may overlap the bytes it produces, so it proceeds a byte at a time.
+		let start = output.len() - offset;
+		for i in start..start + copy_len {
+			output.push(output[i]);
+		}
+	}
+	if output.len() != len {
+		return Err(SnappyError::Invalid("block shorter than its stated length"));
+	}
+	if crc32(&output) != u32::from_be_bytes(*checksum) {
+		return Err(SnappyError::Invalid(
+			"checksum doesn't match the uncompressed data",
+		));
+	}
+	Ok(output)
+}
+
+fn take<'a>(input: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], SnappyError> {
+	let bytes = input
+		.get(*pos..)
+		.and_then(|rest| rest.get(..n))
+		.ok_or(SnappyError::Invalid("unexpected end of compressed data"))?;
+	*pos += n;
+	Ok(bytes)
+}
+
+/// Decodes the little-endian base-128 length at the start of a block.
+fn varint(input: &[u8], pos: &mut usize) -> Result<u64, SnappyError> {
+	let mut value = 0;
+	for shift in (0..64).step_by(7) {
+		let byte = take(input, pos, 1)?[0];
+		value |= u64::from(byte & 0x7f) << shift;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err(SnappyError::Invalid("invalid block length"))
+}
+
+/// Computes the CRC-32 used by zlib and PNG, a bit at a time.
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = !0u32;
+	for &byte in data {
+		crc ^= u32::from(byte);
+		for _ in 0..8 {
+			crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+		}
+	}
+	!crc
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decompress_literals_and_copies() {
+		// A 4-byte literal, then a copy of 8 bytes from 4 bytes back, which overlaps its output.
+		let mut block = b"\x0c\x0cabcd\x11\x04".to_vec();
+		block.extend_from_slice(&crc32(b"abcdabcdabcd").to_be_bytes());
+		let Ok(output) = decompress(&block, None) else {
+			panic!("failed to decompress");
+		};
+		assert_eq!(output, b"abcdabcdabcd");
+
+		let last = block.len() - 1;
+		block[last] ^= 1;
+		let Err(SnappyError::Invalid(_)) = decompress(&block, None) else {
+			panic!("decompressed with a bad checksum");
+		};
+	}
+
+	#[test]
+	fn crc32_check_value() {
+		assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+	}
+}
@@ -0,0 +1,301 @@
+//! The bencode format of BitTorrent metainfo files and tracker responses, as interpreted by xt
+//! itself.
+//!
+//! Each top-level value of an input is a document. Byte strings that are valid UTF-8 translate as
+//! strings, and all others as binary data, as MessagePack `bin` values do, which covers the raw
+//! SHA-1 hashes in the `pieces` of a torrent file. Dictionaries translate as maps in the order of
+//! their keys, which bencode requires to be sorted but some encoders don't.
+//!
+//! Bencode is an input format only.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+use std::str;
+
+use serde::de;
+
+use crate::detect::Trial;
+use crate::input::{self, Ref};
+use crate::transcode::{self, Value};
+
+/// The maximum nesting depth of bencode input, as for MessagePack input.
+pub(crate) const DEPTH_LIMIT: usize = 1024;
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// A torrent file starts with little more than a `d` and a digit, which plenty of text and
+	// binary data do by accident.
+	Ok(Err("bencode is never detected from content".into()))
+}
+
+pub(crate) fn transcode<'i, O>(input: input::Handle<'i>, mut output: O) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	match input.try_into()? {
+		Cow::Borrowed(input) => {
+			let mut decoder = Decoder { input, pos: 0 };
+			while let Some((start, value)) = decoder.next_document()? {
+				output.set_document_offset(start as u64);
+				output.set_document_end(decoder.pos as u64);
+				output.transcode_borrowed_value(value)?;
+			}
+		}
+		Cow::Owned(input) => {
+			let mut decoder = Decoder {
+				input: &input,
+				pos: 0,
+			};
+			while let Some((start, value)) = decoder.next_document()? {
+				output.set_document_offset(start as u64);
+				output.set_document_end(decoder.pos as u64);
+				output.transcode_value(value)?;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// A decoder of the values of bencode input.
+struct Decoder<'b> {
+	input: &'b [u8],
+	pos: usize,
+}
+
+impl<'b> Decoder<'b> {
+	/// Decodes the next top-level value, and returns it with the offset where it starts.
+	///
+	/// Whitespace between top-level values, like the line break at the end of a file, is unusual
+	/// but harmless, since no value starts with it.
+	fn next_document(&mut self) -> Result<Option<(usize, Value<'b>)>, InputError> {
+		while self
+			.input
+			.get(self.pos)
+			.is_some_and(u8::is_ascii_whitespace)
+		{
+			self.pos += 1;
+		}
+		if self.pos == self.input.len() {
+			return Ok(None);
+		}
+		let start = self.pos;
+		Ok(Some((start, self.decode_value(DEPTH_LIMIT)?)))
+	}
+
+	fn error(&self, message: &'static str) -> InputError {
+		InputError {
+			offset: self.pos,
+			message,
+		}
+	}
+
+	fn decode_value(&mut self, depth: usize) -> Result<Value<'b>, InputError> {
+		let Some(&b) = self.input.get(self.pos) else {
+			return Err(self.error("unexpected end of input"));
+		};
+		match b {
+			b'i' => {
+				self.pos += 1;
+				let digits = self.take_until(b'e')?;
+				parse_integer(digits).ok_or_else(|| InputError {
+					offset: self.pos - digits.len() - 1,
+					message: "invalid integer",
+				})
+			}
+			b'0'..=b'9' => {
+				let b = self.decode_bytes()?;
+				Ok(match str::from_utf8(b) {
+					Ok(s) => Value::String(Cow::Borrowed(s)),
+					Err(_) => Value::Bytes(Cow::Borrowed(b)),
+				})
+			}
+			b'l' | b'd' if depth == 0 => Err(self.error("nesting is too deep")),
+			b'l' => {
+				self.pos += 1;
+				let mut seq = vec![];
+				while !self.end_of_container()? {
+					seq.push(self.decode_value(depth - 1)?);
+				}
+				Ok(Value::Seq(seq))
+			}
+			b'd' => {
+				self.pos += 1;
+				let mut map = vec![];
+				while !self.end_of_container()? {
+					if !self.input[self.pos].is_ascii_digit() {
+						return Err(self.error("dictionary key is not a byte string"));
+					}
+					let key = self.decode_value(depth - 1)?;
+					map.push((key, self.decode_value(depth - 1)?));
+				}
+				Ok(Value::Map(map))
+			}
+			_ => Err(self.error("unexpected byte")),
+		}
+	}
+
+	/// Decodes a byte string, which starts with its length in decimal and a `:`.
+	fn decode_bytes(&mut self) -> Result<&'b [u8], InputError> {
+		let start = self.pos;
+		let digits = self.take_until(b':')?;
+		let invalid = |message| InputError {
+			offset: start,
+			message,
+		};
+		if digits.len() > 1 && digits[0] == b'0' {
+			return Err(invalid("byte string length has a leading zero"));
+		}
+		let len: usize = { str::from_utf8(digits).ok() }
+			.filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+			.and_then(|digits| digits.parse().ok())
+			.ok_or_else(|| invalid("invalid byte string length"))?;
+		let Some(bytes) = self.input.get(self.pos..).and_then(|rest| rest.get(..len)) else {
+			return Err(invalid("byte string is longer than the rest of the input"));
+		};
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	/// Returns the bytes before the next occurrence of the terminator, and moves past it.
+	fn take_until(&mut self, terminator: u8) -> Result<&'b [u8], InputError> {
+		let input: &'b [u8] = self.input;
+		let rest = &input[self.pos..];
+		let Some(len) = rest.iter().position(|&b| b == terminator) else {
+			return Err(self.error("unexpected end of input"));
+		};
+		self.pos += len + 1;
+		Ok(&rest[..len])
+	}
+
+	/// Moves past the `e` that ends a list or dictionary and returns true if it's next.
+	fn end_of_container(&mut self) -> Result<bool, InputError> {
+		match self.input.get(self.pos) {
+			None => Err(self.error("unexpected end of input")),
+			Some(b'e') => {
+				self.pos += 1;
+				Ok(true)
+			}
+			Some(_) => Ok(false),
+		}
+	}
+}
+
+/// Parses the digits of an integer, which has no leading zeros and no negative zero.
+fn parse_integer(digits: &[u8]) -> Option<Value<'static>> {
+	let unsigned = digits.strip_prefix(b"-").unwrap_or(digits);
+	let canonical = !unsigned.is_empty()
+		&& unsigned.iter().all(u8::is_ascii_digit)
+		&& (unsigned == b"0" || unsigned[0] != b'0')
+		&& !(unsigned == b"0" && digits.len() > 1);
+	if !canonical {
+		return None;
+	}
+	let n: i128 = str::from_utf8(digits).ok()?.parse().ok()?;
+	Some(match (i64::try_from(n), u64::try_from(n)) {
+		(Ok(n), _) => Value::I64(n),
+		(_, Ok(n)) => Value::U64(n),
+		_ => Value::I128(n),
+	})
+}
+
+/// The error for input that isn't valid bencode.
+#[derive(Debug)]
+pub(crate) struct InputError {
+	offset: usize,
+	message: &'static str,
+}
+
+impl error::Error for InputError {}
+
+impl fmt::Display for InputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"invalid bencode at byte {}: {}",
+			self.offset, self.message
+		)
+	}
+}
+
+pub(crate) struct Output<W: Write>(W);
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output(w)
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.0
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, _: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		Err(OutputError.into())
+	}
+
+	fn transcode_value(&mut self, _: transcode::Value) -> crate::Result<()> {
+		Err(OutputError.into())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.0.flush()
+	}
+}
+
+/// The error for an attempt to translate to bencode, which xt only reads.
+#[derive(Debug)]
+pub(crate) struct OutputError;
+
+impl error::Error for OutputError {}
+
+impl fmt::Display for OutputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("bencode is an input format only")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decode_values() {
+		let input =
+			b"d4:infod6:lengthi-42e6:pieces2:\xff\x00e4:listl0:i0eee\nli18446744073709551615ee";
+		let mut decoder = Decoder { input, pos: 0 };
+		let mut documents = vec![];
+		while let Some((start, value)) = decoder.next_document().unwrap() {
+			documents.push((start, serde_json::to_string(&value).unwrap()));
+		}
+		assert_eq!(
+			documents,
+			[
+				(
+					0,
+					r#"{"info":{"length":-42,"pieces":[255,0]},"list":["",0]}"#.into()
+				),
+				(49, "[18446744073709551615]".into()),
+			]
+		);
+
+		for input in [
+			&b"i-0e"[..],
+			b"i03e",
+			b"ie",
+			b"03:abc",
+			b"5:abc",
+			b"di1ei2ee",
+			b"l",
+			b"x",
+		] {
+			let mut decoder = Decoder { input, pos: 0 };
+			assert!(decoder.next_document().is_err(), "{input:?}");
+		}
+	}
+}
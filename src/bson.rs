@@ -0,0 +1,722 @@
+//! The BSON data format, as stored by MongoDB and written by mongodump.
+//!
+//! Input is a stream of concatenated documents, each starting with its own length, so that a
+//! reader translates one document at a time without ever looking ahead. This follows the BSON
+//! specification directly rather than pulling in a dependency, since the encoding is simple and
+//! only a small part of what a full BSON library provides matters for translation.
+//!
+//! Types without an equivalent in xt's data model translate as single-entry maps in the style of
+//! MongoDB Extended JSON, like `{"$oid": "..."}` for an ObjectId or `{"$date": 1700000000000}`
+//! for a datetime in milliseconds since the Unix epoch. Output turns those two back into their
+//! BSON types, and writes every other map as an ordinary document.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str;
+
+use serde::{Deserialize, de};
+
+use crate::detect::Trial;
+use crate::input::{self, DocumentSizeError, Input, Ref};
+use crate::transcode::{self, Value};
+
+/// The maximum allowed nesting depth of BSON documents, the same as for MessagePack.
+pub(crate) const DEPTH_LIMIT: usize = 1024;
+
+/// The smallest possible document: a length and a terminating null byte.
+const MIN_DOCUMENT_LEN: usize = 5;
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// A document starts with nothing more distinctive than its length, so content alone can't
+	// tell BSON from arbitrary binary data with any confidence.
+	Ok(Err("BSON is never detected from content".into()))
+}
+
+/// Transcodes BSON input to the output, one document at a time.
+///
+/// With `max_size`, translation from a reader fails on the first document whose length exceeds
+/// it, before reading the rest of the document.
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	mut output: O,
+	max_size: Option<u64>,
+) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	match Input::try_from(input)? {
+		Input::Slice(Cow::Borrowed(b)) => {
+			let mut offset = 0;
+			while offset < b.len() {
+				let document = split_document(&b[offset..], offset)?;
+				output.set_document_offset(offset as u64);
+				output.set_document_end((offset + document.len()) as u64);
+				let value = decode_document(document, offset)?;
+				offset += document.len();
+				output.transcode_borrowed_value(value)?;
+			}
+		}
+		Input::Slice(Cow::Owned(b)) => {
+			let mut offset = 0;
+			while offset < b.len() {
+				let document = split_document(&b[offset..], offset)?;
+				output.set_document_offset(offset as u64);
+				output.set_document_end((offset + document.len()) as u64);
+				let value = decode_document(document, offset)?;
+				offset += document.len();
+				output.transcode_value(value)?;
+			}
+		}
+		Input::Reader(mut r) => {
+			let mut offset = 0;
+			let mut buf = vec![];
+			loop {
+				let mut prefix = [0; 4];
+				match read_up_to(&mut r, &mut prefix)? {
+					0 => break,
+					4 => {}
+					_ => return Err(DecodeError::new(offset, "truncated document length").into()),
+				}
+				let len = document_len(prefix, offset)?;
+				if let Some(max) = max_size.filter(|&max| len as u64 > max) {
+					return Err(DocumentSizeError {
+						size: Some(len as u64),
+						max,
+					}
+					.into());
+				}
+				buf.clear();
+				buf.extend_from_slice(&prefix);
+				// The buffer grows as the document arrives, rather than trusting its length with
+				// the size of an allocation up front.
+				r.by_ref().take(len as u64 - 4).read_to_end(&mut buf)?;
+				if buf.len() < len {
+					return Err(DecodeError::new(offset, "truncated document").into());
+				}
+				output.set_document_offset(offset as u64);
+				output.set_document_end((offset + len) as u64);
+				output.transcode_value(decode_document(&buf, offset)?)?;
+				offset += len;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Reads into the buffer until it's full or the input ends, returning how much it read.
+fn read_up_to<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+	let mut len = 0;
+	while len < buf.len() {
+		match r.read(&mut buf[len..]) {
+			Ok(0) => break,
+			Ok(n) => len += n,
+			Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+			Err(err) => return Err(err),
+		}
+	}
+	Ok(len)
+}
+
+/// Returns the length of a document from its first four bytes, if it's a possible length.
+fn document_len(len: [u8; 4], offset: usize) -> Result<usize, DecodeError> {
+	match usize::try_from(i32::from_le_bytes(len)) {
+		Ok(len) if len >= MIN_DOCUMENT_LEN => Ok(len),
+		_ => Err(DecodeError::new(offset, "invalid document length")),
+	}
+}
+
+/// Returns the single document at the start of the input, which is at `offset` in the full input.
+fn split_document(input: &[u8], offset: usize) -> Result<&[u8], DecodeError> {
+	let Some(&len) = input.first_chunk::<4>() else {
+		return Err(DecodeError::new(offset, "truncated document length"));
+	};
+	let len = document_len(len, offset)?;
+	input
+		.get(..len)
+		.ok_or_else(|| DecodeError::new(offset, "truncated document"))
+}
+
+/// Decodes a single complete document, which is at `offset` in the full input.
+fn decode_document(document: &[u8], offset: usize) -> Result<Value<'_>, DecodeError> {
+	let mut decoder = Decoder {
+		input: document,
+		pos: 0,
+		base: offset,
+	};
+	let value = decoder.document(DEPTH_LIMIT)?;
+	match decoder.pos == document.len() {
+		true => Ok(value),
+		false => Err(decoder.error("document continues past its terminating null byte")),
+	}
+}
+
+/// A decoder of the values within a single document.
+struct Decoder<'a> {
+	input: &'a [u8],
+	pos: usize,
+	/// The offset of the document in the full input, for error messages.
+	base: usize,
+}
+
+impl<'a> Decoder<'a> {
+	fn error(&self, message: &'static str) -> DecodeError {
+		DecodeError::new(self.base + self.pos, message)
+	}
+
+	fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+		let bytes = self
+			.input
+			.get(self.pos..)
+			.and_then(|rest| rest.get(..len))
+			.ok_or_else(|| self.error("unexpected end of document"))?;
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	fn take_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+		Ok(self
+			.take(N)?
+			.try_into()
+			.expect("slice should have the length of the array"))
+	}
+
+	fn byte(&mut self) -> Result<u8, DecodeError> {
+		Ok(self.take_array::<1>()?[0])
+	}
+
+	fn i32(&mut self) -> Result<i32, DecodeError> {
+		Ok(i32::from_le_bytes(self.take_array()?))
+	}
+
+	fn i64(&mut self) -> Result<i64, DecodeError> {
+		Ok(i64::from_le_bytes(self.take_array()?))
+	}
+
+	/// Decodes a length-prefixed length, which counts the bytes that follow it.
+	fn len(&mut self) -> Result<usize, DecodeError> {
+		usize::try_from(self.i32()?).map_err(|_| self.error("negative length"))
+	}
+
+	fn cstring(&mut self) -> Result<&'a str, DecodeError> {
+		let rest = self.input.get(self.pos..).unwrap_or_default();
+		let Some(len) = rest.iter().position(|&b| b == 0) else {
+			return Err(self.error("unterminated name or string"));
+		};
+		let s = str::from_utf8(&rest[..len]).map_err(|_| self.error("invalid UTF-8"))?;
+		self.pos += len + 1;
+		Ok(s)
+	}
+
+	fn string(&mut self) -> Result<&'a str, DecodeError> {
+		let len = self.len()?;
+		let start = self.pos;
+		let bytes = self.take(len)?;
+		let Some((&0, s)) = bytes.split_last() else {
+			self.pos = start;
+			return Err(self.error("string without a terminating null byte"));
+		};
+		str::from_utf8(s).map_err(|_| DecodeError::new(self.base + start, "invalid UTF-8"))
+	}
+
+	/// Decodes a document as a list of its elements, with names as keys.
+	fn elements(&mut self, depth: usize) -> Result<Vec<(&'a str, Value<'a>)>, DecodeError> {
+		let Some(depth) = depth.checked_sub(1) else {
+			return Err(self.error("documents nested too deeply"));
+		};
+		let start = self.pos;
+		let len = self.len()?;
+		if len < MIN_DOCUMENT_LEN || start + len > self.input.len() {
+			self.pos = start;
+			return Err(self.error("invalid document length"));
+		}
+		let end = start + len;
+		let mut elements = vec![];
+		loop {
+			let tag_pos = self.pos;
+			let tag = self.byte()?;
+			if tag == 0 {
+				break;
+			}
+			let name = self.cstring()?;
+			let Some(value) = self.element(tag, depth)? else {
+				return Err(DecodeError::new(
+					self.base + tag_pos,
+					"unknown element type",
+				));
+			};
+			elements.push((name, value));
+			if self.pos >= end {
+				return Err(self.error("document without a terminating null byte"));
+			}
+		}
+		if self.pos != end {
+			return Err(self.error("document ends before its length"));
+		}
+		Ok(elements)
+	}
+
+	fn document(&mut self, depth: usize) -> Result<Value<'a>, DecodeError> {
+		let entries = self
+			.elements(depth)?
+			.into_iter()
+			.map(|(name, value)| (borrowed(name), value));
+		Ok(Value::Map(entries.collect()))
+	}
+
+	/// Decodes the value of an element, or returns `None` for an unknown type.
+	fn element(&mut self, tag: u8, depth: usize) -> Result<Option<Value<'a>>, DecodeError> {
+		Ok(Some(match tag {
+			0x01 => Value::F64(f64::from_le_bytes(self.take_array()?)),
+			0x02 | 0x0E => borrowed(self.string()?),
+			0x03 => self.document(depth)?,
+			0x04 => {
+				let elements = self.elements(depth)?;
+				Value::Seq(elements.into_iter().map(|(_, value)| value).collect())
+			}
+			0x05 => {
+				let len = self.len()?;
+				let subtype = self.byte()?;
+				let mut bytes = self.take(len)?;
+				// The old binary subtype repeats the length of its data inside the data.
+				if subtype == 0x02 {
+					bytes = bytes.get(4..).unwrap_or_default();
+				}
+				match subtype {
+					0x00 | 0x02 => Value::Bytes(Cow::Borrowed(bytes)),
+					subtype => wrap(
+						"$binary",
+						Value::Map(vec![
+							(borrowed("bytes"), Value::Bytes(Cow::Borrowed(bytes))),
+							(borrowed("subType"), Value::U8(subtype)),
+						]),
+					),
+				}
+			}
+			0x06 | 0x0A => Value::Unit,
+			0x07 => wrap(
+				"$oid",
+				Value::String(Cow::Owned(hex(&self.take_array::<12>()?))),
+			),
+			0x08 => match self.byte()? {
+				0 => Value::Bool(false),
+				1 => Value::Bool(true),
+				_ => return Err(self.error("invalid boolean")),
+			},
+			0x09 => wrap("$date", Value::I64(self.i64()?)),
+			0x0B => {
+				let pattern = borrowed(self.cstring()?);
+				let options = borrowed(self.cstring()?);
+				wrap(
+					"$regularExpression",
+					Value::Map(vec![
+						(borrowed("pattern"), pattern),
+						(borrowed("options"), options),
+					]),
+				)
+			}
+			0x0C => {
+				let namespace = borrowed(self.string()?);
+				let id = wrap(
+					"$oid",
+					Value::String(Cow::Owned(hex(&self.take_array::<12>()?))),
+				);
+				wrap(
+					"$dbPointer",
+					Value::Map(vec![(borrowed("$ref"), namespace), (borrowed("$id"), id)]),
+				)
+			}
+			0x0D => wrap("$code", borrowed(self.string()?)),
+			0x0F => {
+				// The total length of the code and its scope adds nothing over their own lengths.
+				self.len()?;
+				let code = borrowed(self.string()?);
+				let scope = self.document(depth)?;
+				Value::Map(vec![(borrowed("$code"), code), (borrowed("$scope"), scope)])
+			}
+			0x10 => Value::I32(self.i32()?),
+			0x11 => {
+				let [i, t] = [self.take_array::<4>()?, self.take_array::<4>()?];
+				wrap(
+					"$timestamp",
+					Value::Map(vec![
+						(borrowed("t"), Value::U32(u32::from_le_bytes(t))),
+						(borrowed("i"), Value::U32(u32::from_le_bytes(i))),
+					]),
+				)
+			}
+			0x12 => Value::I64(self.i64()?),
+			0x13 => wrap(
+				"$numberDecimal",
+				Value::String(Cow::Owned(decimal128(u128::from_le_bytes(
+					self.take_array()?,
+				)))),
+			),
+			0xFF => wrap("$minKey", Value::I32(1)),
+			0x7F => wrap("$maxKey", Value::I32(1)),
+			_ => return Ok(None),
+		}))
+	}
+}
+
+fn borrowed(s: &str) -> Value<'_> {
+	Value::String(Cow::Borrowed(s))
+}
+
+/// Returns a single-entry map in the style of MongoDB Extended JSON.
+fn wrap<'a>(key: &'static str, value: Value<'a>) -> Value<'a> {
+	Value::Map(vec![(borrowed(key), value)])
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Returns the string form of an IEEE 754-2008 decimal128 value in its binary integer decimal
+/// encoding, following the algorithm in the BSON specification.
+fn decimal128(bits: u128) -> String {
+	let sign = if bits >> 127 == 1 { "-" } else { "" };
+	let (exponent, coefficient) = match (bits >> 122) & 0x1f {
+		0b11111 => return "NaN".into(),
+		0b11110 => return format!("{sign}Infinity"),
+		// The coefficient would take its implied high bits past the largest that's allowed,
+		// which makes it zero.
+		combination if combination >> 3 == 0b11 => ((bits >> 111) & 0x3fff, 0),
+		_ => ((bits >> 113) & 0x3fff, bits & ((1 << 113) - 1)),
+	};
+	let coefficient = match coefficient {
+		coefficient if coefficient < 10u128.pow(34) => coefficient,
+		_ => 0,
+	};
+	let exponent = i64::try_from(exponent).expect("exponent should have 14 bits") - 6176;
+	let digits = coefficient.to_string();
+	let adjusted = exponent + digits.len() as i64 - 1;
+	if exponent > 0 || adjusted < -6 {
+		let (first, rest) = digits.split_at(1);
+		let point = if rest.is_empty() { "" } else { "." };
+		return format!("{sign}{first}{point}{rest}E{adjusted:+}");
+	}
+	let scale = usize::try_from(-exponent).expect("exponent should not be positive");
+	if scale == 0 {
+		format!("{sign}{digits}")
+	} else if digits.len() > scale {
+		let (whole, fraction) = digits.split_at(digits.len() - scale);
+		format!("{sign}{whole}.{fraction}")
+	} else {
+		format!("{sign}0.{}{digits}", "0".repeat(scale - digits.len()))
+	}
+}
+
+/// The error for BSON input that doesn't follow the specification.
+#[derive(Debug)]
+pub(crate) struct DecodeError {
+	offset: usize,
+	message: &'static str,
+}
+
+impl DecodeError {
+	fn new(offset: usize, message: &'static str) -> DecodeError {
+		DecodeError { offset, message }
+	}
+}
+
+impl error::Error for DecodeError {}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "invalid BSON at byte {}: {}", self.offset, self.message)
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+	buf: Vec<u8>,
+}
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output { w, buf: vec![] }
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	fn output_value(&mut self, value: &Value) -> crate::Result<()> {
+		let Value::Map(entries) = value else {
+			return Err(EncodeError::NonMapRoot.into());
+		};
+		self.buf.clear();
+		encode_document(&mut self.buf, entries.iter().map(|(k, v)| (key(k), v)))?;
+		self.w.write_all(&self.buf)?;
+		Ok(())
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		let value = Value::deserialize(de)?;
+		self.output_value(&value)
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		self.output_value(&value)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.flush()
+	}
+}
+
+/// Returns the name for an element from a map key.
+fn key<'v>(key: &'v Value) -> Result<Cow<'v, str>, EncodeError> {
+	match key {
+		Value::String(s) => Ok(Cow::Borrowed(s)),
+		Value::Char(c) => Ok(Cow::Owned(c.to_string())),
+		_ => Err(EncodeError::NonStringKey),
+	}
+}
+
+/// Encodes a document with the given elements, patching in its length once it's complete.
+fn encode_document<'v, I>(buf: &mut Vec<u8>, elements: I) -> Result<(), EncodeError>
+where
+	I: Iterator<Item = (Result<Cow<'v, str>, EncodeError>, &'v Value<'v>)>,
+{
+	let start = buf.len();
+	buf.extend_from_slice(&[0; 4]);
+	for (name, value) in elements {
+		encode_element(buf, &name?, value)?;
+	}
+	buf.push(0);
+	let len = i32::try_from(buf.len() - start).map_err(|_| EncodeError::TooLarge)?;
+	buf[start..start + 4].copy_from_slice(&len.to_le_bytes());
+	Ok(())
+}
+
+fn encode_element(buf: &mut Vec<u8>, name: &str, value: &Value) -> Result<(), EncodeError> {
+	let start_element = |buf: &mut Vec<u8>, tag: u8| {
+		if name.contains('\0') {
+			return Err(EncodeError::NullInKey);
+		}
+		buf.push(tag);
+		buf.extend_from_slice(name.as_bytes());
+		buf.push(0);
+		Ok(())
+	};
+	match value {
+		Value::Unit => start_element(buf, 0x0A)?,
+		Value::Bool(b) => {
+			start_element(buf, 0x08)?;
+			buf.push(u8::from(*b));
+		}
+		Value::F32(f) => {
+			start_element(buf, 0x01)?;
+			buf.extend_from_slice(&f64::from(*f).to_le_bytes());
+		}
+		Value::F64(f) => {
+			start_element(buf, 0x01)?;
+			buf.extend_from_slice(&f.to_le_bytes());
+		}
+		Value::Char(c) => {
+			start_element(buf, 0x02)?;
+			encode_string(buf, c.encode_utf8(&mut [0; 4]))?;
+		}
+		Value::String(s) => {
+			start_element(buf, 0x02)?;
+			encode_string(buf, s)?;
+		}
+		Value::Bytes(b) => {
+			start_element(buf, 0x05)?;
+			let len = i32::try_from(b.len()).map_err(|_| EncodeError::TooLarge)?;
+			buf.extend_from_slice(&len.to_le_bytes());
+			buf.push(0x00);
+			buf.extend_from_slice(b);
+		}
+		Value::Seq(values) => {
+			start_element(buf, 0x04)?;
+			let names = (0..).map(|i: usize| Ok(Cow::Owned(i.to_string())));
+			encode_document(buf, names.zip(values))?;
+		}
+		Value::Map(entries) => match extended(entries) {
+			Some(Extended::ObjectId(id)) => {
+				start_element(buf, 0x07)?;
+				buf.extend_from_slice(&id);
+			}
+			Some(Extended::Date(millis)) => {
+				start_element(buf, 0x09)?;
+				buf.extend_from_slice(&millis.to_le_bytes());
+			}
+			None => {
+				start_element(buf, 0x03)?;
+				encode_document(buf, entries.iter().map(|(k, v)| (key(k), v)))?;
+			}
+		},
+		value => {
+			let Some(n) = integer(value) else {
+				unreachable!("every other value is an integer");
+			};
+			let n = n.map_err(EncodeError::IntegerRange)?;
+			if let Ok(n) = i32::try_from(n) {
+				start_element(buf, 0x10)?;
+				buf.extend_from_slice(&n.to_le_bytes());
+			} else {
+				start_element(buf, 0x12)?;
+				buf.extend_from_slice(&n.to_le_bytes());
+			}
+		}
+	}
+	Ok(())
+}
+
+fn encode_string(buf: &mut Vec<u8>, s: &str) -> Result<(), EncodeError> {
+	let len = i32::try_from(s.len() + 1).map_err(|_| EncodeError::TooLarge)?;
+	buf.extend_from_slice(&len.to_le_bytes());
+	buf.extend_from_slice(s.as_bytes());
+	buf.push(0);
+	Ok(())
+}
+
+/// Returns the value of an integer as an int64, or its text if it's out of range, or `None` if
+/// the value isn't an integer.
+fn integer(value: &Value) -> Option<Result<i64, String>> {
+	let n: i128 = match *value {
+		Value::I8(n) => n.into(),
+		Value::I16(n) => n.into(),
+		Value::I32(n) => n.into(),
+		Value::I64(n) => n.into(),
+		Value::I128(n) => n,
+		Value::U8(n) => n.into(),
+		Value::U16(n) => n.into(),
+		Value::U32(n) => n.into(),
+		Value::U64(n) => n.into(),
+		Value::U128(n) => match i128::try_from(n) {
+			Ok(n) => n,
+			Err(_) => return Some(Err(n.to_string())),
+		},
+		_ => return None,
+	};
+	Some(i64::try_from(n).map_err(|_| n.to_string()))
+}
+
+/// A BSON type that output recognizes from its Extended JSON form.
+enum Extended {
+	ObjectId([u8; 12]),
+	Date(i64),
+}
+
+fn extended(entries: &[(Value, Value)]) -> Option<Extended> {
+	let [(Value::String(key), value)] = entries else {
+		return None;
+	};
+	match (&**key, value) {
+		("$oid", Value::String(id)) if id.len() == 24 => {
+			let mut bytes = [0; 12];
+			for (byte, digits) in bytes.iter_mut().zip(id.as_bytes().chunks_exact(2)) {
+				*byte = u8::from_str_radix(str::from_utf8(digits).ok()?, 16).ok()?;
+			}
+			Some(Extended::ObjectId(bytes))
+		}
+		("$date", value) => integer(value)?.ok().map(Extended::Date),
+		_ => None,
+	}
+}
+
+/// The error for a document that BSON can't represent.
+#[derive(Debug)]
+pub(crate) enum EncodeError {
+	NonMapRoot,
+	NonStringKey,
+	NullInKey,
+	IntegerRange(String),
+	TooLarge,
+}
+
+impl error::Error for EncodeError {}
+
+impl fmt::Display for EncodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NonMapRoot => f.write_str("root of BSON output must be a map"),
+			Self::NonStringKey => f.write_str("BSON keys must be strings"),
+			Self::NullInKey => f.write_str("BSON keys can't contain null characters"),
+			Self::IntegerRange(n) => write!(f, "integer {n} doesn't fit in a BSON int64"),
+			Self::TooLarge => f.write_str("document is too large for BSON"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn decode(input: &[u8]) -> String {
+		serde_json::to_string(&decode_document(input, 0).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn decode_specification_examples() {
+		// {"hello": "world"} and {"BSON": ["awesome", 5.05, 1986]}, from bsonspec.org.
+		let hello = b"\x16\x00\x00\x00\x02hello\x00\x06\x00\x00\x00world\x00\x00";
+		assert_eq!(decode(hello), r#"{"hello":"world"}"#);
+		let awesome = b"\x31\x00\x00\x00\x04BSON\x00\x26\x00\x00\x00\x020\x00\x08\x00\x00\x00awesome\x00\x011\x00\x33\x33\x33\x33\x33\x33\x14\x40\x102\x00\xc2\x07\x00\x00\x00\x00";
+		assert_eq!(decode(awesome), r#"{"BSON":["awesome",5.05,1986]}"#);
+	}
+
+	#[test]
+	fn round_trip_extended_types() {
+		let value = Value::Map(vec![
+			(
+				borrowed("_id"),
+				wrap("$oid", borrowed("5f1e2d3c4b5a697887a6b5c4")),
+			),
+			(borrowed("at"), wrap("$date", Value::I64(1_700_000_000_000))),
+			(borrowed("big"), Value::I64(1 << 40)),
+			(borrowed("raw"), Value::Bytes(Cow::Borrowed(b"\x00\x01"))),
+			(borrowed("none"), Value::Unit),
+		]);
+		let mut buf = vec![];
+		let Value::Map(entries) = &value else {
+			unreachable!();
+		};
+		encode_document(&mut buf, entries.iter().map(|(k, v)| (key(k), v))).unwrap();
+		assert_eq!(buf[4 + 1..4 + 5], *b"_id\0");
+		assert_eq!(buf[4], 0x07);
+		assert!(decode_document(&buf, 0).unwrap() == value);
+	}
+
+	#[test]
+	fn format_decimal128() {
+		const ZERO_EXPONENT: u128 = 6176 << 113;
+		assert_eq!(decimal128(ZERO_EXPONENT | 1), "1");
+		assert_eq!(decimal128((ZERO_EXPONENT - (2 << 113)) | 12345), "123.45");
+		assert_eq!(decimal128((ZERO_EXPONENT - (7 << 113)) | 1), "1E-7");
+		assert_eq!(decimal128((ZERO_EXPONENT + (3 << 113)) | 15), "1.5E+4");
+		assert_eq!(
+			decimal128((1 << 127) | (ZERO_EXPONENT - (1 << 113)) | 5),
+			"-0.5"
+		);
+		assert_eq!(decimal128(0x1f << 122), "NaN");
+		assert_eq!(decimal128((1 << 127) | (0x1e << 122)), "-Infinity");
+	}
+
+	#[test]
+	fn reject_malformed_documents() {
+		let err = split_document(b"\x10\x00\x00\x00\x00", 7).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"invalid BSON at byte 7: truncated document"
+		);
+		let Err(err) = decode_document(b"\x08\x00\x00\x00\x99a\x00\x00", 0) else {
+			panic!("decoded a document with an unknown element type");
+		};
+		assert_eq!(
+			err.to_string(),
+			"invalid BSON at byte 4: unknown element type"
+		);
+	}
+}
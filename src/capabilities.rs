@@ -0,0 +1,103 @@
+//! A description of what a build of xt supports, for tools that drive xt to adapt to.
+
+use crate::Format;
+
+/// Every format, in the order of their declaration.
+const FORMATS: &[Format] = &[
+	Format::Json,
+	Format::JsonLines,
+	Format::Msgpack,
+	Format::Toml,
+	Format::Yaml,
+	Format::Null,
+	Format::Csv,
+	Format::Tsv,
+	Format::Bson,
+	Format::Avro,
+	Format::Protobuf,
+	Format::Ini,
+	Format::Edn,
+	Format::Bencode,
+	Format::Ron,
+];
+
+/// The optional Cargo features that this build enables.
+const FEATURES: &[&str] = &[
+	#[cfg(feature = "template")]
+	"template",
+	#[cfg(feature = "rust-types")]
+	"rust-types",
+];
+
+/// What this build of xt supports, as returned by [`capabilities`](crate::capabilities).
+#[derive(Clone, Copy, Debug)]
+pub struct Capabilities(());
+
+/// Returns what this build of xt supports: its version, the optional Cargo features it enables,
+/// the formats it translates, and the limits it places on inputs.
+pub fn capabilities() -> Capabilities {
+	Capabilities(())
+}
+
+impl Capabilities {
+	/// Returns the version of xt, like `0.20.1`.
+	pub fn version(&self) -> &'static str {
+		env!("CARGO_PKG_VERSION")
+	}
+
+	/// Returns the names of the optional Cargo features that this build enables, like `template`.
+	pub fn features(&self) -> &'static [&'static str] {
+		FEATURES
+	}
+
+	/// Returns every format that xt supports, for input, output, or both.
+	pub fn formats(&self) -> &'static [Format] {
+		FORMATS
+	}
+
+	/// Returns true if a format can be the format of an input.
+	pub fn supports_input(&self, format: Format) -> bool {
+		format != Format::Null
+	}
+
+	/// Returns true if a format can be the format of an output.
+	pub fn supports_output(&self, format: Format) -> bool {
+		FORMATS.contains(&format) && format != Format::Bencode
+	}
+
+	/// Returns true if detection can identify an input in a format from its content, rather than
+	/// only from an explicit choice of format.
+	pub fn is_detected(&self, format: Format) -> bool {
+		crate::detect::DEFAULT_ORDER.contains(&format)
+	}
+
+	/// Returns true if output in a format can hold more than one document without a container.
+	pub fn supports_multi_document_output(&self, format: Format) -> bool {
+		format.is_multi_document()
+	}
+
+	/// Returns the deepest nesting of arrays and maps that input in a format may have, if xt or
+	/// the format's parser limits it.
+	pub fn depth_limit(&self, format: Format) -> Option<usize> {
+		match format {
+			Format::Json | Format::JsonLines => Some(crate::json::DEPTH_LIMIT),
+			// The recursion limit of serde_yaml.
+			Format::Yaml => Some(128),
+			Format::Msgpack => Some(crate::msgpack::DEPTH_LIMIT),
+			Format::Bson => Some(crate::bson::DEPTH_LIMIT),
+			Format::Avro => Some(crate::avro::DEPTH_LIMIT),
+			Format::Protobuf => Some(crate::protobuf::DEPTH_LIMIT),
+			Format::Edn => Some(crate::edn::DEPTH_LIMIT),
+			Format::Bencode => Some(crate::bencode::DEPTH_LIMIT),
+			Format::Ron => Some(crate::ron::DEPTH_LIMIT),
+			Format::Toml | Format::Null | Format::Csv | Format::Tsv | Format::Ini => None,
+		}
+	}
+
+	/// Returns the most input that detection buffers from a reader to try the formats that must
+	/// fully buffer an input, like TOML, without a
+	/// [detection limit](crate::Translator::set_detection_limit).
+	pub fn detection_buffer_limit(&self) -> usize {
+		crate::toml::DETECTION_CUTOFF
+	}
+}
@@ -0,0 +1,52 @@
+//! The choice of whether the xt binary colors what it writes to a terminal.
+//!
+//! Every stream that xt might color makes the same choice in the same order: `--color always` or
+//! `--color never` if given; otherwise no color if `NO_COLOR` is set to anything but the empty
+//! string (see <https://no-color.org>); otherwise color if `CLICOLOR_FORCE` is set to anything but
+//! the empty string or `0`; otherwise color only if the stream is a terminal other than `dumb`.
+
+use std::env;
+use std::io::IsTerminal;
+
+/// The choice of `--color`.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ColorChoice {
+	#[default]
+	Auto,
+	Always,
+	Never,
+}
+
+impl ColorChoice {
+	/// Parses the argument of `--color`.
+	pub(crate) fn parse(s: &str) -> Result<ColorChoice, &'static str> {
+		match s {
+			"auto" => Ok(ColorChoice::Auto),
+			"always" => Ok(ColorChoice::Always),
+			"never" => Ok(ColorChoice::Never),
+			_ => Err("not a valid color choice"),
+		}
+	}
+
+	/// Returns true if xt should color what it writes to the given stream.
+	pub(crate) fn applies_to<S: IsTerminal>(self, stream: &S) -> bool {
+		match self {
+			ColorChoice::Always => true,
+			ColorChoice::Never => false,
+			ColorChoice::Auto if is_set("NO_COLOR") => false,
+			ColorChoice::Auto if is_set("CLICOLOR_FORCE") && !is_value("CLICOLOR_FORCE", "0") => {
+				true
+			}
+			ColorChoice::Auto => stream.is_terminal() && !is_value("TERM", "dumb"),
+		}
+	}
+}
+
+/// Returns true if an environment variable is set to something other than the empty string.
+fn is_set(name: &str) -> bool {
+	env::var_os(name).is_some_and(|value| !value.is_empty())
+}
+
+fn is_value(name: &str, value: &str) -> bool {
+	env::var_os(name).is_some_and(|v| v == value)
+}
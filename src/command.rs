@@ -0,0 +1,986 @@
+//! The commands of the xt binary that do more than translate every input to standard output.
+//!
+//! These commands build on the same translators as plain conversion, but translate into memory
+//! and then examine the result. `diff` compares documents as JSON values, `split` slices each
+//! document out of a MessagePack translation using a sidecar index before converting it to the
+//! final output format, `hash` digests the same slices of a translation with sorted keys,
+//! `to-env` writes shell variables from the same JSON values as `diff`, and `fmt` compares each
+//! input with its own translation. Conversion with `--out-dir` instead gives
+//! each input a translator and an output file of its own, and `--editor` translates a single
+//! document from standard input for an editor to put in place of the original.
+//!
+//! With `--dry-run`, every command that writes files goes through the same translations, but
+//! prints what it would write instead of writing it.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use serde_json::Value;
+use xt::Format;
+
+use crate::sha256;
+use crate::{Cli, Input, InputPath, die, interrupt};
+
+/// Prints the format that conversion would translate each input from, and how xt chose it.
+///
+/// Exits with status 1 after printing every result if xt can't detect the format of any input.
+pub(crate) fn detect(args: &Cli) {
+	let mut stdout = die::stdout();
+	let mut stdin_used = false;
+	let mut undetected = false;
+	for path in args.input_paths() {
+		let (format, method) = match (args.from, args.extension_format(&path)) {
+			(Some(format), _) => (Some(format), "-f".into()),
+			(None, Some(format)) => (Some(format), "extension".into()),
+			(None, None) => {
+				let detection = detect_content(args, &path, &mut stdin_used);
+				let format = detected_format(args, &detection);
+				let method = match format {
+					Some(Format::Msgpack) => "content".into(),
+					_ => format!("content, {}", detection.encoding()),
+				};
+				(format, method)
+			}
+		};
+		let _ = match format {
+			Some(format) => writeln!(
+				stdout,
+				"{path}: {} (by {method})",
+				crate::format_name(format)
+			),
+			None => {
+				undetected = true;
+				writeln!(stdout, "{path}: unknown")
+			}
+		};
+	}
+	let _ = stdout.flush();
+	if undetected {
+		process::exit(1);
+	}
+}
+
+/// Detects the format of an input from its contents.
+fn detect_content(args: &Cli, path: &InputPath, stdin_used: &mut bool) -> xt::Detection {
+	let result = match crate::open_input(path, stdin_used) {
+		Input::Stdin => detect_reader(args, io::stdin().lock()),
+		Input::File(file) | Input::SeekableFile(file) => detect_reader(args, file),
+		Input::Mmap(map) => xt::detect_slice(&map),
+	};
+	let Ok(detection) = result.map_err(|err| die_in!(path, "{err}"));
+	detection
+}
+
+/// Detects the format of a reader's contents within any detection limit.
+fn detect_reader<R: Read>(args: &Cli, input: R) -> xt::Result<xt::Detection> {
+	match args.detect_limit {
+		Some(limit) => xt::Input::from_reader(input).detect_within(limit),
+		None => xt::detect_reader(input),
+	}
+}
+
+/// Returns the first format in any detection order that matched an input.
+fn detected_format(args: &Cli, detection: &xt::Detection) -> Option<Format> {
+	match &args.detect {
+		Some(order) => order.iter().copied().find(|&format| {
+			detection
+				.probes()
+				.iter()
+				.any(|probe| probe.format() == format && probe.matched())
+		}),
+		None => detection.format(),
+	}
+}
+
+/// Prints every difference between the documents of two inputs, after translating both with the
+/// same options.
+///
+/// Exits with status 1 if there are any differences.
+pub(crate) fn diff(args: &Cli) {
+	let [left, right] = &args.input_pathnames[..] else {
+		unreachable!("diff requires exactly two inputs");
+	};
+	let mut stdin_used = false;
+	let left = read_documents(args, &left.clone().into(), &mut stdin_used);
+	let right = read_documents(args, &right.clone().into(), &mut stdin_used);
+
+	let mut stdout = die::stdout();
+	let mut differs = false;
+	for i in 0..left.len().max(right.len()) {
+		let mut changes = vec![];
+		match (left.get(i), right.get(i)) {
+			(Some(left), Some(right)) => compare("", left, right, &mut changes),
+			(Some(_), None) => changes.push("removed".into()),
+			(None, Some(_)) => changes.push("added".into()),
+			(None, None) => {}
+		}
+		for change in &changes {
+			let _ = writeln!(stdout, "document {i}: {change}");
+		}
+		differs |= !changes.is_empty();
+	}
+	let _ = stdout.flush();
+	if differs {
+		process::exit(1);
+	}
+}
+
+/// Translates a single input to JSON values, exiting on any error.
+fn read_documents(args: &Cli, path: &InputPath, stdin_used: &mut bool) -> Vec<Value> {
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	crate::configure_documents(&mut translator, args);
+	crate::set_protobuf(&mut translator, args);
+	crate::set_yaml_warnings(&mut translator, args);
+	crate::translate_path(&mut translator, args, path, stdin_used);
+	if let Err(err) = translator.finish() {
+		die_in!(path, "{err}");
+	}
+	drop(translator);
+	let Ok(documents) = serde_json::Deserializer::from_slice(&output)
+		.into_iter()
+		.collect::<Result<_, _>>()
+		.map_err(|err| die_in!(path, "{err}"));
+	documents
+}
+
+/// Appends a description of each difference between two values to `changes`, where `path` is
+/// the path to both values in the syntax of `--select`.
+fn compare(path: &str, left: &Value, right: &Value, changes: &mut Vec<String>) {
+	match (left, right) {
+		(Value::Object(left), Value::Object(right)) => {
+			for (key, left_value) in left {
+				let path = key_path(path, key);
+				match right.get(key) {
+					Some(right_value) => compare(&path, left_value, right_value, changes),
+					None => changes.push(format!("{path}: removed {left_value}")),
+				}
+			}
+			for (key, right_value) in right {
+				if !left.contains_key(key) {
+					changes.push(format!("{}: added {right_value}", key_path(path, key)));
+				}
+			}
+		}
+		(Value::Array(left), Value::Array(right)) => {
+			for i in 0..left.len().max(right.len()) {
+				let path = format!("{path}[{i}]");
+				match (left.get(i), right.get(i)) {
+					(Some(left), Some(right)) => compare(&path, left, right, changes),
+					(Some(left), None) => changes.push(format!("{path}: removed {left}")),
+					(None, Some(right)) => changes.push(format!("{path}: added {right}")),
+					(None, None) => {}
+				}
+			}
+		}
+		(left, right) if left != right => {
+			let path = if path.is_empty() { "." } else { path };
+			changes.push(format!("{path}: {left} -> {right}"));
+		}
+		_ => {}
+	}
+}
+
+/// Returns the path to a key of the map at `path`, quoting the key if a bare key can't spell it.
+fn key_path(path: &str, key: &str) -> String {
+	let bare = !key.is_empty()
+		&& key
+			.chars()
+			.all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+	if bare {
+		format!("{path}.{key}")
+	} else {
+		format!("{path}.{}", Value::from(key))
+	}
+}
+
+/// Writes each output document to its own file, named by the prefix and the name template (by
+/// default, the zero-based index of the document), with the extension of the output format.
+pub(crate) fn split(args: &Cli) {
+	interrupt::install();
+	let output = SharedBuffer::default();
+	let index = SharedBuffer::default();
+	let mut translator = xt::Translator::new(output.clone(), Format::Msgpack);
+	translator.set_index_output(index.clone(), Format::Json);
+	crate::configure_documents(&mut translator, args);
+	crate::set_protobuf(&mut translator, args);
+	crate::set_yaml_warnings(&mut translator, args);
+	let mut stdin_used = false;
+	for path in args.input_paths() {
+		if interrupt::requested() {
+			break;
+		}
+		crate::translate_path(&mut translator, args, &path, &mut stdin_used);
+	}
+	if let Err(err) = translator.finish() {
+		die!("{err}");
+	}
+	drop(translator);
+
+	let to = args.to.unwrap_or(Format::Json);
+	let extension = output_extension(args);
+	let prefix = args.prefix.as_deref().unwrap_or("doc-");
+	let output = output.take();
+	let index = index.take();
+	let entries = serde_json::Deserializer::from_slice(&index).into_iter::<Value>();
+	let mut stdout = die::stdout();
+	let mut routed: Vec<_> = args.routes.iter().map(|_| (None, 0)).collect();
+	for (i, entry) in entries.enumerate() {
+		// Each document is complete once written, so an interruption only stops the next one.
+		if interrupt::requested() {
+			break;
+		}
+		let Some(range) = entry.ok().as_ref().and_then(document_range) else {
+			die!("invalid index of split documents");
+		};
+		let Some(document) = output.get(range) else {
+			die!("invalid index of split documents");
+		};
+		let route = args
+			.routes
+			.iter()
+			.position(|r| r.matches(i as u64, document));
+		if let Some(route) = route {
+			let (translator, count) = &mut routed[route];
+			let route = &args.routes[route];
+			*count += 1;
+			let result = if args.dry_run {
+				translate_split(args, document, io::sink(), route.format)
+			} else {
+				translator
+					.get_or_insert_with(|| {
+						let Ok(file) = File::create(&route.pathname)
+							.map_err(|err| die_in!(route.pathname.display(), "{err}"));
+						crate::output_translator(args, file, route.format)
+					})
+					.translate_slice(document, Some(Format::Msgpack))
+			};
+			if let Err(err) = result {
+				die_in!(route.pathname.display(), "{err}");
+			}
+			continue;
+		}
+		if args.name.uses_hash() {
+			// The name depends on the content of the file, which has to come first.
+			let mut content = vec![];
+			if let Err(err) = translate_split(args, document, &mut content, to) {
+				die!("{err}");
+			}
+			let name = args.name.render(i, &content);
+			let pathname = crate::native_path(format!("{prefix}{name}.{extension}").into());
+			if args.dry_run {
+				report_dry_run(&mut stdout, &pathname, 1);
+			} else if let Err(err) = fs::write(&pathname, &content) {
+				die_in!(pathname.display(), "{err}");
+			}
+			continue;
+		}
+		let name = args.name.render(i, &[]);
+		let pathname = crate::native_path(format!("{prefix}{name}.{extension}").into());
+		let result = if args.dry_run {
+			let result = translate_split(args, document, io::sink(), to);
+			report_dry_run(&mut stdout, &pathname, 1);
+			result
+		} else {
+			let Ok(file) =
+				File::create(&pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+			translate_split(args, document, file, to)
+		};
+		if let Err(err) = result {
+			die_in!(pathname.display(), "{err}");
+		}
+	}
+	for (route, (mut translator, count)) in args.routes.iter().zip(routed) {
+		if args.dry_run && count > 0 {
+			report_dry_run(&mut stdout, &route.pathname, count);
+		}
+		if let Some(Err(err)) = translator.as_mut().map(xt::Translator::finish) {
+			die_in!(route.pathname.display(), "{err}");
+		}
+	}
+	interrupt::exit_if_requested();
+}
+
+/// Prints the SHA-256 digest of each output document's canonical encoding, along with the name
+/// of its input (where standard input is `-`).
+///
+/// The canonical encoding is MessagePack with the entries of every map sorted by key, applied
+/// after any other transforms. Since each format's integers, floats, and strings translate to the
+/// same MessagePack values, the same document gets the same digest in any input format.
+pub(crate) fn hash(args: &Cli) {
+	interrupt::install();
+	let output = SharedBuffer::default();
+	let index = SharedBuffer::default();
+	let mut translator = xt::Translator::new(output.clone(), Format::Msgpack);
+	translator.set_index_output(index.clone(), Format::Json);
+	crate::configure_documents(&mut translator, args);
+	crate::set_protobuf(&mut translator, args);
+	crate::set_yaml_warnings(&mut translator, args);
+	translator.add_transform(xt::Transform::sort_keys());
+	let mut stdin_used = false;
+	for path in args.input_paths() {
+		if interrupt::requested() {
+			break;
+		}
+		crate::translate_path(&mut translator, args, &path, &mut stdin_used);
+	}
+	if let Err(err) = translator.finish() {
+		die!("{err}");
+	}
+	drop(translator);
+
+	let output = output.take();
+	let index = index.take();
+	let entries = serde_json::Deserializer::from_slice(&index).into_iter::<Value>();
+	let mut stdout = die::stdout();
+	for entry in entries {
+		let entry = entry.ok();
+		let document = entry
+			.as_ref()
+			.and_then(document_range)
+			.and_then(|range| output.get(range));
+		let Some(document) = document else {
+			die!("invalid index of hashed documents");
+		};
+		let source = entry
+			.as_ref()
+			.and_then(|entry| entry.get("source")?.as_str())
+			.unwrap_or("-");
+		let _ = writeln!(stdout, "{}  {source}", sha256::hex_digest(document));
+	}
+	let _ = stdout.flush();
+	interrupt::exit_if_requested();
+}
+
+/// Prints an `export` line for every value in every document, each of which must be a map.
+///
+/// Nested maps contribute a variable for each of their values, named by the path of keys to the
+/// value joined with underscores after the prefix. Every character of a name other than an ASCII
+/// letter, digit, or underscore becomes an underscore, so that `--flatten` output like `db.port`
+/// names the same variable as a nested value would. Strings print as they are, other scalars in
+/// their JSON syntax, null as the empty string, and arrays and empty maps as compact JSON.
+///
+/// Different keys can name the same variable, like `a.b` and `a-b`, which would leave the later
+/// value in place of the earlier one, so a name that comes up twice is an error instead, and
+/// nothing prints. So is a name of just `_`, from a key like `""` or `"é"` with no prefix.
+pub(crate) fn to_env(args: &Cli) {
+	let mut stdin_used = false;
+	let mut documents = vec![];
+	for path in args.input_paths() {
+		documents.extend(read_documents(args, &path, &mut stdin_used));
+	}
+	let prefix = args.prefix.as_deref().unwrap_or("");
+	let mut exports = Exports::default();
+	for (i, document) in documents.iter().enumerate() {
+		let Value::Object(map) = document else {
+			die!("document {i} is not a map, and has no keys to name variables after");
+		};
+		let mut name = prefix.to_owned();
+		for (key, value) in map {
+			exports.add(i, &mut name, &mut vec![key], value);
+		}
+	}
+	let mut stdout = die::stdout();
+	let _ = stdout.write_all(exports.text.as_bytes());
+	let _ = stdout.flush();
+}
+
+/// The export lines from to-env, with the key path that named each variable so far.
+#[derive(Default)]
+struct Exports<'v> {
+	text: String,
+	names: HashMap<String, (usize, Vec<&'v str>)>,
+}
+
+impl<'v> Exports<'v> {
+	/// Adds the variables for a single entry of a map in a document, whose name starts with
+	/// `name`, given the path of keys to the entry.
+	fn add(
+		&mut self,
+		document: usize,
+		name: &mut String,
+		path: &mut Vec<&'v str>,
+		value: &'v Value,
+	) {
+		let key = *path
+			.last()
+			.expect("the path should end with the entry's key");
+		let len = name.len();
+		if len > 0 && !name.ends_with('_') {
+			name.push('_');
+		}
+		name.extend(key.chars().map(|c| match c {
+			'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+			_ => '_',
+		}));
+		if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+			name.insert(0, '_');
+		}
+		match value {
+			Value::Object(map) if !map.is_empty() => {
+				for (key, value) in map {
+					path.push(key);
+					self.add(document, name, path, value);
+					path.pop();
+				}
+			}
+			value => {
+				// An empty key, or one made only of other characters, leaves the variable that
+				// shells set to the last argument of each command.
+				if name == "_" {
+					die!(
+						"the key {} of document {document} names the variable _, which the shell sets itself",
+						select_path(path)
+					);
+				}
+				if let Some((earlier, other)) = self.names.get(name.as_str()) {
+					die!(
+						"the keys {} of document {earlier} and {} of document {document} both name the variable {name}",
+						select_path(other),
+						select_path(path)
+					);
+				}
+				self.names.insert(name.clone(), (document, path.clone()));
+				let text = match value {
+					Value::String(s) => s.clone(),
+					Value::Null => String::new(),
+					value => value.to_string(),
+				};
+				let _ = writeln!(self.text, "export {name}={}", shell_quote(&text));
+			}
+		}
+		name.truncate(len);
+	}
+}
+
+/// Returns the path of keys to a value, in the syntax of `--select`.
+fn select_path(path: &[&str]) -> String {
+	path.iter()
+		.fold(String::new(), |path, key| key_path(&path, key))
+}
+
+/// Quotes a string for a POSIX shell, in single quotes unless it only contains characters that
+/// no shell treats specially.
+fn shell_quote(s: &str) -> Cow<'_, str> {
+	let plain = !s.is_empty()
+		&& s.chars().all(|c| {
+			c.is_ascii_alphanumeric()
+				|| matches!(c, '_' | '-' | '.' | '/' | ':' | '@' | '%' | '+' | ',')
+		});
+	match plain {
+		true => Cow::Borrowed(s),
+		false => Cow::Owned(format!("'{}'", s.replace('\'', r"'\''"))),
+	}
+}
+
+/// Translates a single document sliced out of the MessagePack translation for split.
+fn translate_split<W: Write>(args: &Cli, document: &[u8], output: W, to: Format) -> xt::Result<()> {
+	let mut translator = crate::output_translator(args, output, to);
+	translator.translate_slice(document, Some(Format::Msgpack))?;
+	translator.finish()
+}
+
+/// Translates each input to its own file in `--out-dir`, named after the input with the extension
+/// of the output format, so that `configs/a.json` becomes `out/a.yaml`.
+///
+/// Exits before writing anything if two inputs would write the same file, or if an input would
+/// overwrite itself. An interruption removes the file in progress, which would be missing the rest
+/// of its input.
+pub(crate) fn convert_to_dir(args: &Cli) {
+	let Some(dir) = &args.out_dir else {
+		unreachable!("conversion to a directory requires --out-dir");
+	};
+	interrupt::install();
+	let to = args.to.unwrap_or(Format::Json);
+	let extension = output_extension(args);
+	let mut inputs = HashMap::new();
+	let mut outputs = vec![];
+	for path in args.input_paths() {
+		let InputPath::File(pathname) = &path else {
+			unreachable!("--out-dir requires named input files");
+		};
+		let Some(name) = pathname.file_name() else {
+			die_in!(path, "cannot name an output file after this input");
+		};
+		let output = dir.join(Path::new(name).with_extension(extension));
+		if let Some(other) = inputs.insert(output.clone(), pathname.clone()) {
+			die!(
+				"{} and {} would both write to {}",
+				other.display(),
+				pathname.display(),
+				output.display()
+			);
+		}
+		if is_same_file(pathname, &output) {
+			die_in!(
+				path,
+				"output to {} would overwrite the input",
+				output.display()
+			);
+		}
+		outputs.push((path, output));
+	}
+	if args.dry_run {
+		let mut stdout = die::stdout();
+		let mut stdin_used = false;
+		for (path, pathname) in outputs {
+			let count = IndexCount::default();
+			let mut translator = crate::output_translator(args, io::sink(), to);
+			translator.set_index_output(count.clone(), Format::Json);
+			crate::configure_documents(&mut translator, args);
+			crate::translate_path(&mut translator, args, &path, &mut stdin_used);
+			if let Err(err) = translator.finish() {
+				die_in!(pathname.display(), "{err}");
+			}
+			interrupt::exit_if_requested();
+			report_dry_run(&mut stdout, &pathname, count.get());
+		}
+		return;
+	}
+	if let Err(err) = fs::create_dir_all(dir) {
+		die_in!(dir.display(), "{err}");
+	}
+
+	let mut stdin_used = false;
+	for (path, pathname) in outputs {
+		if interrupt::requested() {
+			break;
+		}
+		let Ok(file) = File::create(&pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+		let mut translator = crate::output_translator(args, &file, to);
+		crate::configure_documents(&mut translator, args);
+		crate::translate_path(&mut translator, args, &path, &mut stdin_used);
+		let interrupted = interrupt::requested();
+		if let Err(err) = translator.finish() {
+			die_in!(pathname.display(), "{err}");
+		}
+		drop(translator);
+		if interrupted {
+			let _ = fs::remove_file(&pathname);
+		} else if args.preserve_metadata {
+			let InputPath::File(source) = &path else {
+				unreachable!("--out-dir requires named input files");
+			};
+			let result = fs::metadata(source).and_then(|metadata| set_metadata(&file, &metadata));
+			if let Err(err) = result {
+				die_in!(pathname.display(), "{err}");
+			}
+		}
+	}
+	interrupt::exit_if_requested();
+}
+
+/// Reformats each file in place in its own format, or standard input to standard output.
+///
+/// With `--keep-comments`, reformats YAML inputs from their parsed structure instead, to keep
+/// their comments.
+///
+/// With `--check`, prints the name of each input that isn't already formatted instead, and exits
+/// with status 1 if there are any.
+pub(crate) fn fmt(args: &Cli) {
+	interrupt::install();
+	let mut stdout = die::stdout();
+	let mut stdin_used = false;
+	let mut unformatted = false;
+	for path in args.input_paths() {
+		if interrupt::requested() {
+			break;
+		}
+		let result = match crate::open_input(&path, &mut stdin_used) {
+			Input::Stdin => read_all(interrupt::Reader(io::stdin().lock())),
+			Input::File(file) | Input::SeekableFile(file) => read_all(interrupt::Reader(file)),
+			Input::Mmap(map) => Ok(map.to_vec()),
+		};
+		// Reformatting part of an input would write a truncated copy of it.
+		if interrupt::requested() {
+			break;
+		}
+		let Ok(input) = result.map_err(|err| die_in!(path, "{err}"));
+		let format = args
+			.from
+			.or_else(|| args.extension_format(&path))
+			.or_else(|| {
+				let Ok(detection) = xt::detect_slice(&input).map_err(|err| die_in!(path, "{err}"));
+				detected_format(args, &detection)
+			});
+		let Some(format) = format else {
+			die_in!(path, "unable to detect input format");
+		};
+
+		let result = match format {
+			Format::Yaml if args.keep_comments => xt::reformat_yaml(&input),
+			_ => reformat(args, &input, format),
+		};
+		let output = match result {
+			Ok(output) => output,
+			Err(err) => die::exit_translation(&path, Some(format), &err),
+		};
+
+		if args.check {
+			if output != input {
+				unformatted = true;
+				let _ = writeln!(stdout, "{path}");
+			}
+			continue;
+		}
+		let result = match &path {
+			InputPath::Stdin if args.dry_run => Ok(()),
+			InputPath::Stdin => {
+				crate::check_terminal_output(format, None);
+				stdout.write_all(&output)
+			}
+			InputPath::File(_) if output == input => Ok(()),
+			InputPath::File(pathname) if args.dry_run => {
+				let Ok(documents) = count_documents(&output, format)
+					.map_err(|err| die::exit_translation(&path, Some(format), &err));
+				report_dry_run(&mut stdout, pathname, documents);
+				Ok(())
+			}
+			InputPath::File(pathname) => rewrite(args, pathname, &output),
+		};
+		if let Err(err) = result {
+			die_in!(path, "{err}");
+		}
+	}
+	let _ = stdout.flush();
+	interrupt::exit_if_requested();
+	if unformatted {
+		process::exit(1);
+	}
+}
+
+/// Translates exactly one document from standard input for an editor, exiting on any error.
+///
+/// The output is the document alone, without the YAML document start that separates it from
+/// others, and ends in a newline only if the input does, so that it can replace the exact range
+/// of a buffer that the input came from. Errors give the byte offset within the input where they
+/// occurred, for input formats whose errors have a position.
+pub(crate) fn editor(args: &Cli) {
+	let path = InputPath::Stdin;
+	let Ok(input) = read_all(io::stdin().lock()).map_err(|err| die_in!(path, "{err}"));
+	let to = args.to.unwrap_or(Format::Json);
+	let count = IndexCount::default();
+	let mut output = vec![];
+	let mut translator = crate::output_translator(args, &mut output, to);
+	crate::configure_documents(&mut translator, args);
+	translator.set_index_output(count.clone(), Format::Json);
+	let result = translator
+		.translate_slice(&input, args.from)
+		.and_then(|()| translator.finish());
+	drop(translator);
+	if let Err(err) = result {
+		let offset = error_offset(&err, &input);
+		die::exit_translation_at(&path, args.from, &err, offset);
+	}
+	match count.get() {
+		1 => {}
+		0 => die_in!(path, "found no document to translate"),
+		n => die_in!(
+			path,
+			"found {n} documents, rather than a single one to translate"
+		),
+	}
+
+	let mut output = output.as_slice();
+	if to == Format::Yaml {
+		output = output.strip_prefix(b"---\n").unwrap_or(output);
+	}
+	if !matches!(to, Format::Msgpack | Format::Bson | Format::Protobuf) && !input.ends_with(b"\n") {
+		output = output.strip_suffix(b"\n").unwrap_or(output);
+	}
+	crate::check_terminal_output(to, args.encoding);
+	let mut stdout = die::stdout();
+	let _ = stdout.write_all(output);
+	let _ = stdout.flush();
+}
+
+/// Returns the byte offset within an input where a translation error occurred, if the error
+/// comes from a parser that reports its position.
+fn error_offset(err: &xt::Error, input: &[u8]) -> Option<u64> {
+	let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err.as_ref());
+	while let Some(err) = source {
+		if let Some(err) = err.downcast_ref::<serde_json::Error>() {
+			return line_column_offset(input, err.line(), err.column());
+		}
+		if let Some(err) = err.downcast_ref::<serde_yaml::Error>() {
+			return err
+				.location()
+				.and_then(|loc| u64::try_from(loc.index()).ok());
+		}
+		if let Some(err) = err.downcast_ref::<toml::de::Error>() {
+			return err.span().and_then(|span| u64::try_from(span.start).ok());
+		}
+		source = err.source();
+	}
+	None
+}
+
+/// Returns the byte offset of a one-based line and column, as `serde_json` counts them.
+fn line_column_offset(input: &[u8], line: usize, column: usize) -> Option<u64> {
+	if line == 0 {
+		return None;
+	}
+	let start: usize = input
+		.split_inclusive(|&b| b == b'\n')
+		.take(line - 1)
+		.map(<[u8]>::len)
+		.sum();
+	u64::try_from(start + column.saturating_sub(1)).ok()
+}
+
+/// Reformats an input by translating it to its own format.
+fn reformat(args: &Cli, input: &[u8], format: Format) -> xt::Result<Vec<u8>> {
+	let mut output = vec![];
+	let mut translator = crate::output_translator(args, &mut output, format);
+	translator.translate_slice(input, Some(format))?;
+	translator.finish()?;
+	drop(translator);
+	Ok(output)
+}
+
+/// Rewrites a file with new contents, after copying it to any `--backup`.
+///
+/// The new contents go to a temporary file in the same directory, which then replaces the
+/// original, so that a failed write never leaves a truncated file behind. The replacement takes
+/// the permissions and ownership of the original, and with `--preserve-metadata` its modification
+/// time. A symbolic link is followed, and the file it points to is replaced. A file with several
+/// hard links, or whose owner the replacement can't take, is instead overwritten in place with a
+/// warning, since replacing it would split it from its other links or its owner.
+fn rewrite(args: &Cli, pathname: &Path, contents: &[u8]) -> io::Result<()> {
+	let target = fs::canonicalize(pathname)?;
+	let metadata = fs::metadata(&target)?;
+	if let Some(suffix) = &args.backup {
+		let mut backup = pathname.as_os_str().to_owned();
+		backup.push(suffix);
+		fs::copy(pathname, &backup).map_err(|err| {
+			io::Error::new(
+				err.kind(),
+				format!(
+					"failed to back up to {}: {err}",
+					Path::new(&backup).display()
+				),
+			)
+		})?;
+	}
+	let modified = if args.preserve_metadata {
+		Some(metadata.modified()?)
+	} else {
+		None
+	};
+	if let Some(reason) = in_place_reason(&metadata) {
+		return overwrite(pathname, contents, modified, reason);
+	}
+	let (mut file, temporary) = match create_temporary(&target) {
+		Ok(created) => created,
+		Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+			return overwrite(pathname, contents, modified, "its directory isn't writable");
+		}
+		Err(err) => return Err(err),
+	};
+	let written = (|| {
+		file.write_all(contents)?;
+		file.set_permissions(metadata.permissions())?;
+		if let Some(modified) = modified {
+			file.set_modified(modified)?;
+		}
+		if !take_owner(&file, &metadata)? {
+			return Ok(false);
+		}
+		file.sync_all()?;
+		Ok(true)
+	})();
+	drop(file);
+	let result = match written {
+		Ok(true) => fs::rename(&temporary, &target),
+		Ok(false) => {
+			let _ = fs::remove_file(&temporary);
+			return overwrite(pathname, contents, modified, "its owner can't be kept");
+		}
+		Err(err) => Err(err),
+	};
+	if result.is_err() {
+		let _ = fs::remove_file(&temporary);
+	}
+	result
+}
+
+/// Overwrites a file in place with new contents, warning that a failure could truncate it.
+fn overwrite(
+	pathname: &Path,
+	contents: &[u8],
+	modified: Option<SystemTime>,
+	reason: &str,
+) -> io::Result<()> {
+	die::warn_file(
+		pathname,
+		&format!("rewriting in place, since {reason}; an interrupted write could truncate it"),
+	);
+	let mut file = File::create(pathname)?;
+	file.write_all(contents)?;
+	match modified {
+		Some(modified) => file.set_modified(modified),
+		None => Ok(()),
+	}
+}
+
+/// Sets the modification time and permissions of a file to those from another file's metadata.
+fn set_metadata(file: &File, metadata: &fs::Metadata) -> io::Result<()> {
+	file.set_permissions(metadata.permissions())?;
+	file.set_modified(metadata.modified()?)
+}
+
+/// Creates a new file next to `target` to hold its replacement.
+fn create_temporary(target: &Path) -> io::Result<(File, PathBuf)> {
+	let name = target.file_name().unwrap_or_default().to_string_lossy();
+	for attempt in 0.. {
+		let temporary =
+			target.with_file_name(format!(".{name}.xt-{}-{attempt}.tmp", process::id()));
+		match File::options()
+			.write(true)
+			.create_new(true)
+			.open(&temporary)
+		{
+			Ok(file) => return Ok((file, temporary)),
+			Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {}
+			Err(err) => return Err(err),
+		}
+	}
+	unreachable!("the attempts should end with a file or an error")
+}
+
+/// Returns why a file must be overwritten in place rather than replaced, if it must.
+#[cfg(unix)]
+fn in_place_reason(metadata: &fs::Metadata) -> Option<&'static str> {
+	use std::os::unix::fs::MetadataExt;
+	(metadata.nlink() > 1).then_some("it has more than one hard link")
+}
+
+#[cfg(not(unix))]
+fn in_place_reason(_: &fs::Metadata) -> Option<&'static str> {
+	None
+}
+
+/// Gives a file the owner and group from another file's metadata, returning false when the
+/// system doesn't permit it.
+#[cfg(unix)]
+fn take_owner(file: &File, metadata: &fs::Metadata) -> io::Result<bool> {
+	use std::os::unix::fs::MetadataExt;
+	match std::os::unix::fs::fchown(file, Some(metadata.uid()), Some(metadata.gid())) {
+		Ok(()) => Ok(true),
+		Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Ok(false),
+		Err(err) => Err(err),
+	}
+}
+
+#[cfg(not(unix))]
+fn take_owner(_: &File, _: &fs::Metadata) -> io::Result<bool> {
+	Ok(true)
+}
+
+/// Counts the documents in the output of a reformatted input.
+fn count_documents(output: &[u8], format: Format) -> xt::Result<u64> {
+	let count = IndexCount::default();
+	let mut translator = xt::Translator::new(io::sink(), Format::Msgpack);
+	translator.set_index_output(count.clone(), Format::Json);
+	translator.translate_slice(output, Some(format))?;
+	translator.finish()?;
+	Ok(count.get())
+}
+
+/// Prints a line for a file that a dry run would write, with the number of documents in it.
+fn report_dry_run<W: Write>(stdout: &mut W, pathname: &Path, documents: u64) {
+	let action = if pathname.exists() {
+		"overwrite"
+	} else {
+		"create"
+	};
+	let plural = if documents == 1 { "" } else { "s" };
+	let _ = writeln!(
+		stdout,
+		"would {action} {} ({documents} document{plural})",
+		pathname.display()
+	);
+}
+
+fn read_all<R: Read>(mut input: R) -> io::Result<Vec<u8>> {
+	let mut buf = vec![];
+	input.read_to_end(&mut buf)?;
+	Ok(buf)
+}
+
+/// Returns the extension of the files that split or `--out-dir` conversion writes.
+fn output_extension(args: &Cli) -> &'static str {
+	#[cfg(feature = "template")]
+	if args.template.is_some() {
+		return "txt";
+	}
+	crate::format_name(args.to.unwrap_or(Format::Json))
+}
+
+/// Returns true if two paths both exist and refer to the same file.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+	match (fs::canonicalize(a), fs::canonicalize(b)) {
+		(Ok(a), Ok(b)) => a == b,
+		_ => false,
+	}
+}
+
+/// Returns the range of bytes that an index entry's document occupies in the output.
+fn document_range(entry: &Value) -> Option<Range<usize>> {
+	let offset = |key: &str| usize::try_from(entry.get(key)?.as_u64()?).ok();
+	Some(offset("output_offset")?..offset("output_end")?)
+}
+
+/// An in-memory writer that a translator can own while the caller keeps access to its contents.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+	fn take(&self) -> Vec<u8> {
+		std::mem::take(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner))
+	}
+}
+
+impl Write for SharedBuffer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let mut inner = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+		inner.extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// A writer for a JSON index that only counts its entries, which each take one line.
+#[derive(Clone, Default)]
+struct IndexCount(Arc<AtomicU64>);
+
+impl IndexCount {
+	fn get(&self) -> u64 {
+		self.0.load(Ordering::Relaxed)
+	}
+}
+
+impl Write for IndexCount {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let lines = buf.iter().filter(|&&b| b == b'\n').count();
+		self.0
+			.fetch_add(u64::try_from(lines).unwrap_or(u64::MAX), Ordering::Relaxed);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
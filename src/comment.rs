@@ -0,0 +1,175 @@
+//! Comments that YAML and TOML output write before the entries at chosen paths, to mark generated
+//! files with where their values came from.
+//!
+//! Serializers have no place for comments, so output serializes each document twice: once as it
+//! is, and once with the key of every commented entry replaced by a unique placeholder. Neither
+//! format wraps lines by the lengths of keys, so the line where each placeholder first appears is
+//! the line of its entry in the first serialization, and the comment goes before that line.
+
+use std::borrow::Cow;
+
+use crate::transcode::Value;
+use crate::transform::path::{self, Segment, key_matches};
+
+/// The comments to write into each output document, as added by
+/// [`Translator::add_output_comment`](crate::Translator::add_output_comment).
+#[derive(Clone, Default)]
+pub(crate) struct Comments(Vec<(Vec<Segment>, String)>);
+
+impl Comments {
+	/// Adds a comment at a path, after any other comment at the same path.
+	pub(crate) fn add(&mut self, path: Vec<Segment>, comment: &str) {
+		match self.0.iter_mut().find(|(p, _)| *p == path) {
+			Some((_, text)) => {
+				text.push('\n');
+				text.push_str(comment);
+			}
+			None => self.0.push((path, comment.into())),
+		}
+	}
+
+	pub(crate) fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Serializes a document with `serialize`, with the comment lines for each path before the
+	/// line of its entry, and the comment lines for the root path before everything else.
+	pub(crate) fn write<F>(&self, value: &Value, serialize: F) -> crate::Result<String>
+	where
+		F: Fn(&Value) -> crate::Result<String>,
+	{
+		let text = serialize(value)?;
+		let mut prefix = String::from("xt-comment-");
+		while value.contains_str(&prefix) {
+			prefix.push('-');
+		}
+
+		let mut marked = value.clone();
+		let mut header = None;
+		let mut placeholders = vec![];
+		for (i, (path, comment)) in self.0.iter().enumerate() {
+			let Some((last, parent)) = path.split_last() else {
+				header = Some(comment);
+				continue;
+			};
+			let Segment::Key(key) = last else {
+				continue;
+			};
+			// A key with a line break may take a different form of entry in YAML output.
+			if key.contains(['\r', '\n']) {
+				continue;
+			}
+			let Some(Value::Map(map)) = path::lookup_mut(&mut marked, parent) else {
+				continue;
+			};
+			if let Some((k, _)) = map.iter_mut().find(|(k, _)| key_matches(k, key)) {
+				let placeholder = format!("{prefix}{i}");
+				*k = Value::String(Cow::Owned(placeholder.clone()));
+				placeholders.push((placeholder, comment));
+			}
+		}
+
+		let mut before = vec![];
+		if !placeholders.is_empty() {
+			let marked = serialize(&marked)?;
+			for (placeholder, comment) in placeholders {
+				let found = marked.split('\n').enumerate().find_map(|(n, line)| {
+					let (col, _) = line.match_indices(&placeholder).find(|&(i, _)| {
+						!line[i + placeholder.len()..].starts_with(|c: char| c.is_ascii_digit())
+					})?;
+					Some((
+						n,
+						line[..col]
+							.trim_start_matches(['-', ' '])
+							.is_empty()
+							.then_some(col),
+					))
+				});
+				if let Some((line, col)) = found {
+					before.push((line, col, comment));
+				}
+			}
+			before.sort_by_key(|&(line, ..)| line);
+		}
+
+		let mut out = String::with_capacity(text.len());
+		if let Some(header) = header {
+			push_comment(&mut out, "", header);
+		}
+		let mut before = before.into_iter().peekable();
+		for (n, line) in text.split_inclusive('\n').enumerate() {
+			// A comment lines up with its key when only indentation and sequence indicators come
+			// before the key, and with the start of the line otherwise, as for TOML headers.
+			let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+			while let Some((_, col, comment)) = before.next_if(|&(line, ..)| line == n) {
+				match col {
+					Some(col) => push_comment(&mut out, &" ".repeat(col), comment),
+					None => push_comment(&mut out, indent, comment),
+				}
+			}
+			out.push_str(line);
+		}
+		Ok(out)
+	}
+}
+
+/// Writes a `#` comment line for each line of a comment, at the given indentation.
+fn push_comment(out: &mut String, indent: &str, comment: &str) {
+	for line in comment.split('\n') {
+		let line = line.trim_end_matches('\r');
+		out.push_str(indent);
+		out.push('#');
+		if !line.is_empty() {
+			out.push(' ');
+			out.push_str(line);
+		}
+		out.push('\n');
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn comments_before_entries() {
+		let string = |s: &'static str| Value::String(s.into());
+		let value = Value::Map(vec![
+			(string("name"), string("xt")),
+			(
+				string("items"),
+				Value::Seq(vec![Value::Map(vec![
+					(string("id"), Value::U8(1)),
+					(string("xt-comment-0"), Value::Bool(true)),
+				])]),
+			),
+		]);
+		let mut comments = Comments::default();
+		for (expr, comment) in [
+			(".", "generated"),
+			(".items[0].id", "managed"),
+			(".name", "one\ntwo"),
+			(".missing", "nowhere"),
+			(".items[0].id", "by xt"),
+		] {
+			comments.add(path::parse("comment path", expr).unwrap(), comment);
+		}
+		let text = comments
+			.write(&value, |value| Ok(serde_yaml::to_string(value)?))
+			.unwrap();
+		assert_eq!(
+			text,
+			"\
+# generated
+# one
+# two
+name: xt
+items:
+  # managed
+  # by xt
+- id: 1
+  xt-comment-0: true
+"
+		);
+	}
+}
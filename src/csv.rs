@@ -0,0 +1,473 @@
+//! Delimiter-separated values, for CSV and TSV input and output.
+//!
+//! Every input starts with a header record naming its fields, and each record after the header
+//! translates as a separate document: a map from each name in the header to the string in the same
+//! position of the record. Records follow RFC 4180, with fields separated by the delimiter and
+//! records by `\n` or `\r\n`. A field in double quotes can contain the delimiter, line breaks, and
+//! doubled quotes that stand for a single quote, while a quote within an unquoted field is just a
+//! quote. Empty lines between records are skipped.
+//!
+//! Input is read one line at a time, plus however many more lines a quoted field spans, so that a
+//! stream translates record by record as it arrives.
+//!
+//! Output goes the other way, writing each map document as a record, or each map in an array
+//! document. The keys of the first record name the fields of the header, and a [`CsvMismatch`]
+//! decides what happens to any later record with different keys. Every field must be a scalar,
+//! so nested data needs flattening before it can become a record.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+
+use serde::{Deserialize, de};
+
+use crate::Format;
+use crate::detect::Trial;
+use crate::input::{self, Input, Ref};
+use crate::transcode::{self, Value};
+
+/// The byte order mark that some tools write at the start of UTF-8 text.
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// A single line of almost any text is a valid header, and little more than equal numbers of
+	// delimiters on each line make a valid record, so content alone says little about a format
+	// that other formats can't say better.
+	Ok(Err(
+		"delimiter-separated values are never detected from content".into(),
+	))
+}
+
+/// Transcodes input with fields separated by the delimiter to the output, one record at a time.
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	output: O,
+	delimiter: u8,
+) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	match Input::try_from(input)? {
+		Input::Slice(b) => transcode_records(Records::new(&*b, delimiter), output),
+		Input::Reader(r) => transcode_records(Records::new(BufReader::new(r), delimiter), output),
+	}
+}
+
+fn transcode_records<'i, R, O>(mut records: Records<R>, mut output: O) -> crate::Result<()>
+where
+	R: BufRead,
+	O: crate::Output<'i>,
+{
+	let Some(header) = records.next_record()? else {
+		return Ok(());
+	};
+	while let Some(record) = records.next_record()? {
+		if record.fields.len() != header.fields.len() {
+			return Err(format!(
+				"record on line {} has {} fields, but the header has {}",
+				record.line,
+				record.fields.len(),
+				header.fields.len(),
+			)
+			.into());
+		}
+		output.set_document_offset(record.offset);
+		output.set_document_end(record.end);
+		let entries = header
+			.fields
+			.iter()
+			.zip(record.fields)
+			.map(|(name, field)| {
+				(
+					Value::String(Cow::Owned(name.clone())),
+					Value::String(Cow::Owned(field)),
+				)
+			});
+		output.transcode_value(Value::Map(entries.collect()))?;
+	}
+	Ok(())
+}
+
+/// A reader of the records of delimiter-separated input.
+struct Records<R> {
+	r: R,
+	delimiter: u8,
+	/// The last line read from the input, including its line break.
+	line: Vec<u8>,
+	/// The byte offset of the next unread line.
+	offset: u64,
+	/// The one-based line number of the last line read.
+	line_number: u64,
+}
+
+/// A single record of delimiter-separated input.
+struct Record {
+	fields: Vec<String>,
+	/// The byte offset where the record starts.
+	offset: u64,
+	/// The byte offset just past the line break that ends the record, if it has one.
+	end: u64,
+	/// The one-based line number where the record starts.
+	line: u64,
+}
+
+/// Where the parser of a record stands.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+	/// At the start of a field.
+	FieldStart,
+	/// Inside a field without quotes.
+	Unquoted,
+	/// Inside a field in quotes.
+	Quoted,
+	/// Just past a quote inside a field in quotes, which either closes the field or starts a
+	/// doubled quote.
+	QuoteInQuoted,
+}
+
+impl<R: BufRead> Records<R> {
+	fn new(r: R, delimiter: u8) -> Records<R> {
+		Records {
+			r,
+			delimiter,
+			line: vec![],
+			offset: 0,
+			line_number: 0,
+		}
+	}
+
+	/// Reads the next line into the line buffer, and returns false at the end of the input.
+	fn read_line(&mut self) -> crate::Result<bool> {
+		self.line.clear();
+		let len = self.r.read_until(b'\n', &mut self.line)?;
+		if self.offset == 0 && self.line.starts_with(BOM) {
+			self.line.drain(..BOM.len());
+		}
+		self.offset += len as u64;
+		self.line_number += 1;
+		Ok(len > 0)
+	}
+
+	/// Reads the next record, skipping any empty lines before it, or returns `None` at the end of
+	/// the input.
+	fn next_record(&mut self) -> crate::Result<Option<Record>> {
+		let offset = loop {
+			let offset = self.offset;
+			if !self.read_line()? {
+				return Ok(None);
+			}
+			if !split_line_break(&self.line).0.is_empty() {
+				break offset;
+			}
+		};
+		let line = self.line_number;
+
+		let (mut fields, mut field) = (vec![], vec![]);
+		let mut state = State::FieldStart;
+		loop {
+			let (content, line_break) = split_line_break(&self.line);
+			for &b in content {
+				state = match (state, b) {
+					(State::FieldStart, b'"') => State::Quoted,
+					(State::FieldStart | State::Unquoted | State::QuoteInQuoted, b)
+						if b == self.delimiter =>
+					{
+						fields.push(into_field(&mut field, self.line_number)?);
+						State::FieldStart
+					}
+					(State::Quoted, b'"') => State::QuoteInQuoted,
+					(State::QuoteInQuoted, b'"') => {
+						field.push(b'"');
+						State::Quoted
+					}
+					(State::QuoteInQuoted, _) => {
+						return Err(format!(
+							"unexpected character after the closing quote of a field on line {}",
+							self.line_number
+						)
+						.into());
+					}
+					(State::FieldStart | State::Unquoted, b) => {
+						field.push(b);
+						State::Unquoted
+					}
+					(State::Quoted, b) => {
+						field.push(b);
+						State::Quoted
+					}
+				};
+			}
+			if state != State::Quoted {
+				fields.push(into_field(&mut field, self.line_number)?);
+				return Ok(Some(Record {
+					fields,
+					offset,
+					end: self.offset,
+					line,
+				}));
+			}
+			// The line break belongs to the quoted field, which continues on the next line.
+			field.extend_from_slice(line_break);
+			if !self.read_line()? {
+				return Err(format!("unterminated quoted field starting on line {line}").into());
+			}
+		}
+	}
+}
+
+/// Splits a line into its content and its trailing line break, if it has one.
+fn split_line_break(line: &[u8]) -> (&[u8], &[u8]) {
+	let len = match line {
+		[.., b'\r', b'\n'] => 2,
+		[.., b'\n'] => 1,
+		_ => 0,
+	};
+	line.split_at(line.len() - len)
+}
+
+/// Takes the bytes of a complete field as a string.
+fn into_field(field: &mut Vec<u8>, line: u64) -> crate::Result<String> {
+	String::from_utf8(std::mem::take(field))
+		.map_err(|err| format!("invalid UTF-8 in a field on line {line}: {err}").into())
+}
+
+/// What CSV and TSV output does with a record whose keys differ from the fields of the header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CsvMismatch {
+	/// Fail translation at the first record that lacks a field of the header, or has a key that
+	/// the header doesn't name.
+	#[default]
+	Error,
+	/// Write an empty field for each field of the header that a record lacks, and leave out any
+	/// key of a record that the header doesn't name.
+	Empty,
+}
+
+/// An output of delimiter-separated records, with a header taken from the first record.
+pub(crate) struct Output<W: Write> {
+	w: W,
+	delimiter: u8,
+	mismatch: CsvMismatch,
+	/// The names of the fields in the header, once the first record has written it.
+	header: Option<Vec<String>>,
+}
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W, format: Format) -> Output<W> {
+		Output {
+			w,
+			delimiter: match format {
+				Format::Tsv => b'\t',
+				_ => b',',
+			},
+			mismatch: CsvMismatch::default(),
+			header: None,
+		}
+	}
+
+	pub(crate) fn set_mismatch(&mut self, mismatch: CsvMismatch) {
+		self.mismatch = mismatch;
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	fn output_document(&mut self, value: Value) -> crate::Result<()> {
+		match value {
+			Value::Map(entries) => self.output_record(entries),
+			Value::Seq(records) => {
+				for record in records {
+					let Value::Map(entries) = record else {
+						return Err(OutputError::NonMapRecord.into());
+					};
+					self.output_record(entries)?;
+				}
+				Ok(())
+			}
+			_ => Err(OutputError::NonMapRecord.into()),
+		}
+	}
+
+	fn output_record(&mut self, entries: Vec<(Value, Value)>) -> crate::Result<()> {
+		let mut record = Vec::with_capacity(entries.len());
+		for (key, value) in entries {
+			let key = scalar_text(key).ok_or(OutputError::NonScalarKey)?;
+			let Some(value) = scalar_text(value) else {
+				return Err(OutputError::NonScalarField(key.into_owned()).into());
+			};
+			record.push((key, value));
+		}
+
+		let Some(header) = &self.header else {
+			let header: Vec<String> = record.iter().map(|(key, _)| key.to_string()).collect();
+			let (w, delimiter) = (&mut self.w, self.delimiter);
+			write_record(w, delimiter, header.iter().map(String::as_str))?;
+			write_record(w, delimiter, record.iter().map(|(_, value)| &**value))?;
+			self.header = Some(header);
+			return Ok(());
+		};
+
+		if self.mismatch == CsvMismatch::Error {
+			if let Some((key, _)) = record
+				.iter()
+				.find(|(key, _)| !header.iter().any(|name| name == key))
+			{
+				return Err(OutputError::ExtraField(key.to_string()).into());
+			}
+		}
+		let mut fields = Vec::with_capacity(header.len());
+		for name in header {
+			match record.iter().find(|(key, _)| key == name) {
+				Some((_, value)) => fields.push(&**value),
+				None if self.mismatch == CsvMismatch::Empty => fields.push(""),
+				None => return Err(OutputError::MissingField(name.clone()).into()),
+			}
+		}
+		write_record(&mut self.w, self.delimiter, fields.into_iter())
+	}
+}
+
+/// Writes a single record, quoting the fields that would otherwise read back differently.
+fn write_record<'f, W, I>(w: &mut W, delimiter: u8, fields: I) -> crate::Result<()>
+where
+	W: Write,
+	I: Iterator<Item = &'f str>,
+{
+	for (i, field) in fields.enumerate() {
+		if i > 0 {
+			w.write_all(&[delimiter])?;
+		}
+		let quote = field
+			.bytes()
+			.any(|b| b == delimiter || matches!(b, b'"' | b'\r' | b'\n'));
+		if quote {
+			write!(w, "\"{}\"", field.replace('"', "\"\""))?;
+		} else {
+			w.write_all(field.as_bytes())?;
+		}
+	}
+	w.write_all(b"\n")?;
+	Ok(())
+}
+
+/// Returns the text of a scalar for a field or key, or `None` for an array or map.
+///
+/// Strings and characters are written as they are, null as an empty field, and other scalars in
+/// their JSON syntax.
+pub(crate) fn scalar_text(value: Value) -> Option<Cow<str>> {
+	match value {
+		Value::String(s) => Some(s),
+		Value::Char(c) => Some(Cow::Owned(c.to_string())),
+		Value::Unit => Some(Cow::Borrowed("")),
+		Value::Bytes(_) | Value::Seq(_) | Value::Map(_) => None,
+		value => serde_json::to_string(&value).ok().map(Cow::Owned),
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		let value = Value::deserialize(de)?;
+		self.output_document(value)
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		self.output_document(value)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.flush()
+	}
+}
+
+/// The error for a document that can't be written as CSV or TSV records.
+#[derive(Debug)]
+pub(crate) enum OutputError {
+	NonMapRecord,
+	NonScalarKey,
+	NonScalarField(String),
+	MissingField(String),
+	ExtraField(String),
+}
+
+impl error::Error for OutputError {}
+
+impl fmt::Display for OutputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NonMapRecord => f.write_str(
+				"delimiter-separated output requires each document to be a map or an array of maps",
+			),
+			Self::NonScalarKey => f.write_str("delimiter-separated output requires scalar keys"),
+			Self::NonScalarField(key) => write!(
+				f,
+				"field {key:?} is an array or map, which needs flattening to fit in a record"
+			),
+			Self::MissingField(key) => {
+				write!(f, "record lacks the field {key:?} from the header")
+			}
+			Self::ExtraField(key) => write!(f, "record has the field {key:?} outside the header"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn read_records(input: &[u8], delimiter: u8) -> crate::Result<Vec<(Vec<String>, u64, u64)>> {
+		let mut records = Records::new(input, delimiter);
+		let mut all = vec![];
+		while let Some(record) = records.next_record()? {
+			all.push((record.fields, record.offset, record.end));
+		}
+		Ok(all)
+	}
+
+	#[test]
+	fn read_quoted_and_unquoted_fields() {
+		let input = b"\xef\xbb\xbfa,b\r\n\n\"x,\"\"y\"\"\",say \"hi\"\n\"multi\r\nline\",\n";
+		let records = read_records(input, b',').unwrap();
+		let fields: Vec<_> = records.iter().map(|(fields, ..)| fields.clone()).collect();
+		assert_eq!(
+			fields,
+			[
+				vec!["a", "b"],
+				vec!["x,\"y\"", "say \"hi\""],
+				vec!["multi\r\nline", ""],
+			]
+		);
+		let bounds: Vec<_> = records
+			.iter()
+			.map(|&(_, offset, end)| (offset, end))
+			.collect();
+		assert_eq!(bounds, [(0, 8), (9, 28), (28, 43)]);
+	}
+
+	#[test]
+	fn read_tab_separated_fields() {
+		let records = read_records(b"a\tb,c\n1\t\"2\t3\"", b'\t').unwrap();
+		let fields: Vec<_> = records.into_iter().map(|(fields, ..)| fields).collect();
+		assert_eq!(fields, [vec!["a", "b,c"], vec!["1", "2\t3"]]);
+	}
+
+	#[test]
+	fn reject_malformed_records() {
+		let err = read_records(b"a\n\"x\ny\n", b',').unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"unterminated quoted field starting on line 2"
+		);
+		let err = read_records(b"a\n\"x\"y\n", b',').unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"unexpected character after the closing quote of a field on line 2"
+		);
+	}
+}
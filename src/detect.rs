@@ -0,0 +1,602 @@
+//! Format detection by classification and trial parsing.
+//!
+//! Full detection requires trial parsing, but many inputs can rule out most formats from their
+//! first few bytes. For example, a document starting with `#` cannot be JSON or MessagePack, and
+//! one starting with `{` cannot be TOML. The classifier examines the start of the input once and
+//! reports which formats remain plausible, so that detection only trial parses those.
+//!
+//! The classifier must never rule out a format that would have matched the input, but may leave
+//! in formats that fail to match. How much of the input it examines is bounded, and when it can't
+//! find the start of the first document within that bound it leaves every text format in play.
+//!
+//! Binary formats are identified by the magic bytes at the start of every input in the format,
+//! registered in a single table of [`SIGNATURES`]. The classifier finds the signatures that could
+//! match an input from an index on the first byte, so the cost of classification doesn't grow
+//! with the number of binary formats.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::input::{self, Handle, Ref};
+use crate::{Encoding, Format};
+
+/// The outcome of trial parsing an input in a single format: `Ok` if the input matched, or
+/// otherwise an explanation of why it didn't.
+pub(crate) type Trial = Result<(), String>;
+
+/// The result of detecting the format of an input.
+#[derive(Debug, Clone)]
+pub struct Detection {
+	probes: Vec<Probe>,
+	encoding: Encoding,
+}
+
+impl Detection {
+	/// Returns the detected format, which is the first format in the detection order that matched
+	/// the input, or `None` if no format matched.
+	pub fn format(&self) -> Option<Format> {
+		self.probes.iter().find(|p| p.matched()).map(|p| p.format)
+	}
+
+	/// Returns the result of probing each format that detection considered, in the detection
+	/// order.
+	pub fn probes(&self) -> &[Probe] {
+		&self.probes
+	}
+
+	/// Returns true if the input matched more than one format.
+	pub fn is_ambiguous(&self) -> bool {
+		self.probes.iter().filter(|p| p.matched()).count() > 1
+	}
+
+	/// Returns the text encoding that the input appears to have from its first few bytes.
+	///
+	/// Only YAML inputs support encodings other than UTF-8, and JSON or TOML inputs that appear to
+	/// have another encoding fail to translate with an error that names it. The encoding has no
+	/// meaning for MessagePack inputs, whose first bytes are binary data.
+	pub fn encoding(&self) -> Encoding {
+		self.encoding
+	}
+}
+
+/// The result of probing an input for a single format during detection.
+#[derive(Debug, Clone)]
+pub struct Probe {
+	format: Format,
+	failure: Option<String>,
+}
+
+impl Probe {
+	/// Returns the format that detection probed for.
+	pub fn format(&self) -> Format {
+		self.format
+	}
+
+	/// Returns true if the input matched the format.
+	pub fn matched(&self) -> bool {
+		self.failure.is_none()
+	}
+
+	/// Returns a score between 0 and 1 for how likely the input is to be in the format.
+	///
+	/// Inputs that don't match a format score 0. Inputs that match score higher for more
+	/// restrictive formats, where an input in some other format is less likely to match by
+	/// accident. Scores are relative, and have no meaning beyond their order.
+	pub fn confidence(&self) -> f64 {
+		if !self.matched() {
+			return 0.0;
+		}
+		match self.format {
+			Format::Msgpack => 1.0,
+			Format::Json => 0.9,
+			Format::Yaml => 0.7,
+			Format::Toml => 0.5,
+			Format::JsonLines
+			| Format::Null
+			| Format::Csv
+			| Format::Tsv
+			| Format::Bson
+			| Format::Avro
+			| Format::Protobuf
+			| Format::Ini
+			| Format::Edn
+			| Format::Bencode
+			| Format::Ron => 0.0,
+		}
+	}
+
+	/// Returns the reason that the input didn't match the format, if it didn't.
+	pub fn failure(&self) -> Option<&str> {
+		self.failure.as_deref()
+	}
+}
+
+/// A function that trial parses an input in a single format.
+type TrialParser = fn(Ref) -> io::Result<Trial>;
+
+/// The order in which detection tries each format by default.
+pub(crate) const DEFAULT_ORDER: [Format; 4] = [
+	// As a binary format, we expect MessagePack to be more restrictive than any text format.
+	// Detection of MessagePack inputs is limited to collection types; see comments in the
+	// implementation for details.
+	Format::Msgpack,
+	// We expect JSON to be more restrictive than other text formats. For example, a "#" comment
+	// at the start of a document could be TOML or YAML, but definitely not JSON.
+	Format::Json,
+	// YAML is _less_ restrictive than TOML, but we want to try it first since it supports
+	// streaming input (so detection may require less buffering). Detection of YAML inputs is
+	// limited to collection types; see comments in the implementation for details.
+	Format::Yaml,
+	// TOML is the only format that must fully buffer its input, and imposes its own limits to
+	// avoid unbounded memory consumption.
+	Format::Toml,
+];
+
+fn trial_parser(format: Format) -> TrialParser {
+	match format {
+		Format::Msgpack => crate::msgpack::input_matches,
+		Format::Json => crate::json::input_matches,
+		Format::Yaml => crate::yaml::input_matches,
+		Format::Toml => crate::toml::input_matches,
+		Format::JsonLines => crate::json::lines_input_matches,
+		Format::Null => crate::null::input_matches,
+		Format::Csv | Format::Tsv => crate::csv::input_matches,
+		Format::Bson => crate::bson::input_matches,
+		Format::Avro => crate::avro::input_matches,
+		Format::Protobuf => crate::protobuf::input_matches,
+		Format::Ini => crate::ini::input_matches,
+		Format::Edn => crate::edn::input_matches,
+		Format::Bencode => crate::bencode::input_matches,
+		Format::Ron => crate::ron::input_matches,
+	}
+}
+
+/// Detects the input format by trying to parse a single document with each plausible format of
+/// `order`, in that order.
+///
+/// Unless `exhaustive` is set, detection stops at the first format that matches, and the result
+/// only covers the formats before it.
+///
+/// With a `limit`, trial parsing captures at most that many bytes of a reader input, and a format
+/// whose trial would need more fails to match for that reason. The classifier's look at the start
+/// of the input isn't subject to the limit.
+pub(crate) fn detect(
+	input: &mut Handle,
+	order: &[Format],
+	exhaustive: bool,
+	limit: Option<usize>,
+) -> io::Result<Detection> {
+	// A single look at the start of the input rules out most formats for most inputs, so we can
+	// skip trial parsing formats that couldn't possibly match.
+	let candidates = classify(input.borrow_mut())?;
+	let encoding = crate::yaml::input_encoding(input.borrow_mut())?;
+
+	input.set_detection_limit(limit);
+	let probes = probe(input, order, exhaustive, candidates);
+	input.set_detection_limit(None);
+	Ok(Detection {
+		probes: probes?,
+		encoding,
+	})
+}
+
+/// Trial parses the input with each candidate format of `order` for [`detect`].
+fn probe(
+	input: &mut Handle,
+	order: &[Format],
+	exhaustive: bool,
+	candidates: Candidates,
+) -> io::Result<Vec<Probe>> {
+	let mut probes = vec![];
+	for &format in order {
+		let failure = if candidates.includes(format) {
+			match trial_parser(format)(input.borrow_mut()) {
+				Ok(trial) => trial.err(),
+				Err(err) if input::is_detection_limit(&err) => Some(err.to_string()),
+				Err(err) => return Err(err),
+			}
+		} else {
+			Some("ruled out by the start of the input".into())
+		};
+		let matched = failure.is_none();
+		probes.push(Probe { format, failure });
+		if matched && !exhaustive {
+			break;
+		}
+	}
+	Ok(probes)
+}
+
+/// An error for an input that doesn't match any format.
+#[derive(Debug)]
+pub(crate) struct Undetected(pub(crate) Detection);
+
+impl error::Error for Undetected {}
+
+impl fmt::Display for Undetected {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("unable to detect input format")?;
+		for (i, probe) in self.0.probes.iter().enumerate() {
+			let sep = if i == 0 { " (" } else { "; " };
+			let failure = probe.failure().unwrap_or("matched");
+			write!(f, "{sep}{}: {failure}", probe.format)?;
+		}
+		if !self.0.probes.is_empty() {
+			f.write_str(")")?;
+		}
+		Ok(())
+	}
+}
+
+/// The first bytes of every input in each binary format that detection supports, as inclusive
+/// ranges that must cover every input the format's trial parser could match.
+///
+/// First bytes that could also start a text document don't rule out any text formats, so these
+/// ranges can safely overlap with text.
+const SIGNATURES: &[(Format, &[(u8, u8)])] = &[
+	// See msgpack::input_matches for why detection is limited to collection types. These are
+	// the fixmap, fixarray, array 16, array 32, map 16, and map 32 markers.
+	(Format::Msgpack, &[(0x80, 0x9f), (0xdc, 0xdf)]),
+];
+
+/// For each possible first byte of an input, the set of [`SIGNATURES`] that it could start, with
+/// bit `i` set for the signature at index `i`.
+const SIGNATURES_BY_FIRST_BYTE: [u32; 256] = index_signatures();
+
+// From conversions aren't const, and none of these casts can lose information.
+#[allow(clippy::cast_lossless)]
+const fn index_signatures() -> [u32; 256] {
+	assert!(SIGNATURES.len() <= 32, "too many signatures to index");
+	let mut index = [0; 256];
+	let mut i = 0;
+	while i < SIGNATURES.len() {
+		let ranges = SIGNATURES[i].1;
+		let mut r = 0;
+		while r < ranges.len() {
+			let (first, last) = ranges[r];
+			let mut b = first as usize;
+			while b <= last as usize {
+				index[b] |= 1 << i;
+				b += 1;
+			}
+			r += 1;
+		}
+		i += 1;
+	}
+	index
+}
+
+/// Calls `f` with each binary format that an input starting with `first` could be in.
+fn sniff_binary(first: u8, mut f: impl FnMut(Format)) {
+	let mut bits = SIGNATURES_BY_FIRST_BYTE[usize::from(first)];
+	while bits != 0 {
+		f(SIGNATURES[bits.trailing_zeros() as usize].0);
+		bits &= bits - 1;
+	}
+}
+
+/// Returns true if the input starts with the magic bytes of the binary format.
+pub(crate) fn has_magic(input: &mut Ref, format: Format) -> io::Result<bool> {
+	let Some(&first) = input.prefix(1)?.first() else {
+		return Ok(false);
+	};
+	let mut found = false;
+	sniff_binary(first, |f| found |= f == format);
+	Ok(found)
+}
+
+/// The maximum size of the leading whitespace that the classifier will scan past.
+const MAX_SCAN_LEN: usize = 4096;
+
+/// The formats that could plausibly match an input.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Candidates {
+	pub(crate) msgpack: bool,
+	pub(crate) json: bool,
+	pub(crate) yaml: bool,
+	pub(crate) toml: bool,
+}
+
+impl Candidates {
+	const NONE: Candidates = Candidates {
+		msgpack: false,
+		json: false,
+		yaml: false,
+		toml: false,
+	};
+
+	const TEXT: Candidates = Candidates {
+		msgpack: false,
+		json: true,
+		yaml: true,
+		toml: true,
+	};
+
+	fn includes(&self, format: Format) -> bool {
+		match format {
+			Format::Msgpack => self.msgpack,
+			Format::Json => self.json,
+			Format::Yaml => self.yaml,
+			Format::Toml => self.toml,
+			// These never match, but say so themselves.
+			Format::JsonLines
+			| Format::Null
+			| Format::Csv
+			| Format::Tsv
+			| Format::Bson
+			| Format::Avro
+			| Format::Protobuf
+			| Format::Ini
+			| Format::Edn
+			| Format::Bencode
+			| Format::Ron => true,
+		}
+	}
+
+	fn insert(&mut self, format: Format) {
+		match format {
+			Format::Msgpack => self.msgpack = true,
+			Format::Json => self.json = true,
+			Format::Yaml => self.yaml = true,
+			Format::Toml => self.toml = true,
+			Format::JsonLines
+			| Format::Null
+			| Format::Csv
+			| Format::Tsv
+			| Format::Bson
+			| Format::Avro
+			| Format::Protobuf
+			| Format::Ini
+			| Format::Edn
+			| Format::Bencode
+			| Format::Ron => {}
+		}
+	}
+}
+
+/// Classifies an input based on its first bytes.
+pub(crate) fn classify(mut input: Ref) -> io::Result<Candidates> {
+	let first = input.prefix(1)?.first().copied();
+	let Some(first) = first else {
+		// An empty input is a valid TOML document, and nothing else.
+		return Ok(Candidates {
+			toml: true,
+			..Candidates::NONE
+		});
+	};
+
+	// Magic bytes could also start a text document (e.g. the MessagePack markers from 0xDC through
+	// 0xDF are lead bytes of 2-byte UTF-8 sequences), so binary formats join the text formats
+	// rather than replacing them.
+	let mut candidates = classify_text(&mut input)?;
+	sniff_binary(first, |format| candidates.insert(format));
+	Ok(candidates)
+}
+
+/// Classifies a non-empty input for text formats only.
+fn classify_text(input: &mut Ref) -> io::Result<Candidates> {
+	let Some(start) = first_significant_byte(input)? else {
+		// A whitespace-only input is an empty TOML document.
+		return Ok(Candidates {
+			toml: true,
+			..Candidates::NONE
+		});
+	};
+	let Some(start) = start else {
+		return Ok(Candidates::TEXT);
+	};
+
+	// Inputs starting with null bytes or byte order marks are likely UTF-16 or UTF-32, which only
+	// the YAML input supports.
+	if matches!(start, 0x00 | 0xFE | 0xFF) {
+		return Ok(Candidates {
+			yaml: true,
+			..Candidates::NONE
+		});
+	}
+
+	Ok(Candidates {
+		msgpack: false,
+		json: could_start_json(start),
+		yaml: could_start_yaml(start),
+		toml: could_start_toml(start),
+	})
+}
+
+/// Returns the first non-whitespace byte of the input.
+///
+/// The outer `Option` is `None` if the input contains only whitespace. The inner `Option` is
+/// `None` if the whitespace continues past [`MAX_SCAN_LEN`].
+///
+/// For reader inputs, this captures only as much of the input as needed, so that classification
+/// never blocks on a stream that has already provided the start of its first document.
+fn first_significant_byte(input: &mut Ref) -> io::Result<Option<Option<u8>>> {
+	let mut scanned = 0;
+	loop {
+		let prefix = input.prefix(scanned + 1)?;
+		if let Some(&b) = prefix[scanned..].iter().find(|&&b| !is_whitespace(b)) {
+			return Ok(Some(Some(b)));
+		}
+		if prefix.len() <= scanned {
+			return Ok(None);
+		}
+		scanned = prefix.len();
+		if scanned >= MAX_SCAN_LEN {
+			return Ok(Some(None));
+		}
+	}
+}
+
+/// Returns true for whitespace that all of the text formats allow before a document.
+fn is_whitespace(b: u8) -> bool {
+	matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Returns true if a JSON value can start with the byte.
+fn could_start_json(b: u8) -> bool {
+	matches!(
+		b,
+		b'{' | b'[' | b'"' | b'-' | b'0'..=b'9' | b't' | b'f' | b'n'
+	)
+}
+
+/// Returns true if a YAML stream can start with the byte.
+///
+/// The `@` and `` ` `` indicators are reserved, and cannot start a plain scalar.
+fn could_start_yaml(b: u8) -> bool {
+	!matches!(b, b'@' | b'`')
+}
+
+/// Returns true if a TOML document can start with the byte.
+///
+/// A TOML document starts with a table header, a comment, or a bare or quoted key. Non-ASCII bytes
+/// are allowed, as the parser may accept a UTF-8 byte order mark.
+fn could_start_toml(b: u8) -> bool {
+	matches!(b, b'[' | b'#' | b'"' | b'\'' | b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-')
+		|| !b.is_ascii()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::input::Handle;
+
+	fn classify_slice(input: &[u8]) -> Candidates {
+		classify(Handle::from_slice(input).borrow_mut()).unwrap()
+	}
+
+	fn classify_reader(input: &[u8]) -> Candidates {
+		classify(Handle::from_reader(input).borrow_mut()).unwrap()
+	}
+
+	const fn candidates(msgpack: bool, json: bool, yaml: bool, toml: bool) -> Candidates {
+		Candidates {
+			msgpack,
+			json,
+			yaml,
+			toml,
+		}
+	}
+
+	#[test]
+	fn classify_inputs() {
+		const CASES: &[(&[u8], Candidates)] = &[
+			(b"", candidates(false, false, false, true)),
+			(b" \n\t\r\n", candidates(false, false, false, true)),
+			(b"\x81\xa2xt\xc3", candidates(true, false, true, true)),
+			(b"{\"xt\": true}", candidates(false, true, true, false)),
+			(b"\n\n  [1, 2]", candidates(false, true, true, true)),
+			(b"\"xt\" = true", candidates(false, true, true, true)),
+			(b"# comment\nxt: true", candidates(false, false, true, true)),
+			(b"xt = true", candidates(false, false, true, true)),
+			(b"true", candidates(false, true, true, true)),
+			(b"- xt", candidates(false, true, true, true)),
+			(b"---\nxt: true", candidates(false, true, true, true)),
+			(b"&anchor xt", candidates(false, false, true, false)),
+			(b"@xt", candidates(false, false, false, false)),
+			(b"\xfe\xff\x00x", candidates(false, false, true, false)),
+			(b"x\x00t\x00", candidates(false, false, true, true)),
+		];
+		for (input, want) in CASES {
+			assert_eq!(&classify_slice(input), want, "slice {input:?}");
+			assert_eq!(&classify_reader(input), want, "reader {input:?}");
+		}
+	}
+
+	#[test]
+	fn detect_all_candidates() {
+		let detection = detect(
+			&mut Handle::from_slice(b"{\"xt\": [1]}"),
+			&DEFAULT_ORDER,
+			true,
+			None,
+		)
+		.unwrap();
+		let results: Vec<_> = detection
+			.probes()
+			.iter()
+			.map(|p| (p.format().to_string(), p.matched(), p.confidence()))
+			.collect();
+		assert_eq!(
+			results,
+			[
+				("MessagePack".into(), false, 0.0),
+				("JSON".into(), true, 0.9),
+				("YAML".into(), true, 0.7),
+				("TOML".into(), false, 0.0),
+			]
+		);
+		assert!(detection.is_ambiguous());
+		assert!(matches!(detection.format(), Some(Format::Json)));
+
+		let detection = detect(
+			&mut Handle::from_slice(b"{\"xt\": [1]}"),
+			&DEFAULT_ORDER,
+			false,
+			None,
+		)
+		.unwrap();
+		assert_eq!(detection.probes().len(), 2);
+		assert!(!detection.is_ambiguous());
+	}
+
+	#[test]
+	fn detect_in_custom_order() {
+		// A table header could also start a YAML flow sequence, but only TOML is in the order.
+		const INPUT: &[u8] = b"[xt]\nkey = 1\n";
+		let detection =
+			detect(&mut Handle::from_slice(INPUT), &[Format::Toml], false, None).unwrap();
+		assert!(matches!(detection.format(), Some(Format::Toml)));
+		assert_eq!(detection.probes().len(), 1);
+
+		let detection =
+			detect(&mut Handle::from_slice(INPUT), &[Format::Json], true, None).unwrap();
+		assert!(detection.format().is_none());
+	}
+
+	#[test]
+	fn undetected_error_lists_probes() {
+		let detection =
+			detect(&mut Handle::from_slice(b"@xt"), &DEFAULT_ORDER, false, None).unwrap();
+		assert!(detection.format().is_none());
+		let message = Undetected(detection).to_string();
+		assert!(
+			message.starts_with(concat!(
+				"unable to detect input format (",
+				"MessagePack: ruled out by the start of the input; ",
+				"JSON: ruled out by the start of the input; ",
+				"YAML: ruled out by the start of the input; ",
+				"TOML: ",
+			)),
+			"{message}"
+		);
+	}
+
+	#[test]
+	fn msgpack_signature_matches_collection_markers() {
+		use rmp::Marker;
+		for b in 0..=u8::MAX {
+			let want = matches!(
+				Marker::from_u8(b),
+				Marker::FixArray(_)
+					| Marker::Array16
+					| Marker::Array32
+					| Marker::FixMap(_)
+					| Marker::Map16 | Marker::Map32
+			);
+			let mut got = false;
+			sniff_binary(b, |format| got |= format == Format::Msgpack);
+			assert_eq!(got, want, "{b:#04x}");
+		}
+	}
+
+	#[test]
+	fn classify_long_whitespace() {
+		let mut input = vec![b' '; MAX_SCAN_LEN];
+		input.extend_from_slice(b"# comment");
+		assert_eq!(classify_slice(&input), candidates(false, false, true, true));
+		assert_eq!(classify_reader(&input), Candidates::TEXT);
+	}
+}
@@ -1,29 +1,349 @@
 //! Handle errors in the xt binary by logging and exiting.
+//!
+//! By default, errors go to standard error as free text. With `--errors json`, each error instead
+//! goes to standard error as a single-line JSON record, with the fields of a [`Failure`] and a
+//! `message`, some of which may be null:
+//!
+//! ```text
+//! {"file":"in.json","format":"JSON","document":3,"offset":120,"error_offset":null,"message":"..."}
+//! ```
+//!
+//! The `error_offset` of the error itself within its input is only known with `--editor`.
+//!
+//! Warnings, which don't stop the process, follow the same choice. A JSON warning record has a
+//! `warning` field set to true, along with the location of the value that it warns about. Text
+//! errors and warnings start with a label that's colored when [`crate::color`] chooses color for
+//! standard error.
+//!
+//! Failures to write to standard output exit from within the write, through the same path as any
+//! other error. A broken pipe instead terminates the process silently, as a pipeline expects of
+//! a process whose downstream (like `head`) has stopped reading.
 
-/// Formats a message to standard error, then terminates the current process with exit code 1.
-macro_rules! die {
-	($fmt:literal $(, $($args:tt)* )?) => {{
-		use ::std::io::Write;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use xt::Format;
+
+/// Whether errors go to standard error as JSON records rather than as text.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Whether text errors and warnings have colored labels.
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether errors go to standard error as JSON records rather than as text.
+pub(crate) fn set_json(json: bool) {
+	JSON.store(json, Ordering::Relaxed);
+}
+
+/// Sets whether text errors and warnings have colored labels.
+pub(crate) fn set_color(color: bool) {
+	COLOR.store(color, Ordering::Relaxed);
+}
+
+/// The label at the start of a text error or warning, with the SGR code that colors it.
+struct Label(&'static str, &'static str);
+
+impl Label {
+	const ERROR: Label = Label("xt error", "1;31");
+	const WARNING: Label = Label("xt warning", "1;33");
+}
+
+impl Display for Label {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Label(text, code) = self;
+		if COLOR.load(Ordering::Relaxed) {
+			write!(f, "\x1b[{code}m{text}\x1b[0m")
+		} else {
+			f.write_str(text)
+		}
+	}
+}
+
+/// The details of an error beyond its message, for JSON error records.
+#[derive(Default)]
+pub(crate) struct Failure {
+	/// The input that the error occurred in.
+	pub(crate) file: Option<String>,
+	/// The format of that input, if known before translating it.
+	pub(crate) format: Option<Format>,
+	/// The zero-based index of the document that the error occurred in within its input.
+	pub(crate) document: Option<u64>,
+	/// The byte offset where that document starts within its input.
+	pub(crate) offset: Option<u64>,
+	/// The byte offset where the error itself occurred within its input, for `--editor`.
+	pub(crate) error_offset: Option<u64>,
+}
+
+/// Writes an error to standard error, then terminates the current process with exit code 1.
+pub(crate) fn exit(failure: &Failure, message: &dyn Display) -> ! {
+	if JSON.load(Ordering::Relaxed) {
+		write_json(failure, message);
+	} else {
+		write_text(failure.file.as_deref(), message);
+	}
+	process::exit(1);
+}
+
+/// Writes an error from translating an input to standard error, then terminates the current
+/// process with exit code 1.
+///
+/// The text form of the error includes the location of any document it occurred in, which the
+/// JSON form has in separate fields instead.
+pub(crate) fn exit_translation(file: &dyn Display, format: Option<Format>, err: &xt::Error) -> ! {
+	exit_translation_at(file, format, err, None)
+}
+
+/// Writes an error from translating an input to standard error, with the byte offset where the
+/// error occurred within the input, then terminates the current process with exit code 1.
+pub(crate) fn exit_translation_at(
+	file: &dyn Display,
+	format: Option<Format>,
+	err: &xt::Error,
+	error_offset: Option<u64>,
+) -> ! {
+	let file = file.to_string();
+	if JSON.load(Ordering::Relaxed) {
+		let failure = Failure {
+			file: Some(file),
+			format,
+			document: err.document_index(),
+			offset: err.document_offset(),
+			error_offset,
+		};
+		write_json(&failure, &err.as_ref());
+	} else {
+		write_text(Some(&file), err);
+	}
+	process::exit(1);
+}
+
+/// A writer to standard output that exits the process on any error from it.
+pub(crate) struct Stdout(pipecheck::Writer<io::StdoutLock<'static>>);
+
+/// Locks standard output for the rest of the process, and returns a writer to it that exits on
+/// any error.
+pub(crate) fn stdout() -> Stdout {
+	Stdout(pipecheck::wrap(io::stdout().lock()))
+}
+
+impl Stdout {
+	fn check<T>(result: io::Result<T>) -> io::Result<T> {
+		match result {
+			Err(err) if err.kind() != io::ErrorKind::Interrupted => exit(
+				&Failure::default(),
+				&format_args!("failed to write to standard output: {err}"),
+			),
+			result => result,
+		}
+	}
+}
+
+impl Write for Stdout {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		Stdout::check(self.0.write(buf))
+	}
+
+	fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+		Stdout::check(self.0.write_vectored(bufs))
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Stdout::check(self.0.flush())
+	}
+}
+
+/// Writes a warning about a risky YAML scalar to standard error.
+pub(crate) fn warn(warning: &xt::YamlWarning) {
+	let mut stderr = io::stderr().lock();
+	if JSON.load(Ordering::Relaxed) {
+		let (line, column) = warning.line_column().unzip();
+		let _ = writeln!(
+			stderr,
+			r#"{{"warning":true,"file":{},"document":{},"output":{},"line":{},"column":{},"path":{},"message":{}}}"#,
+			to_json(&warning.source()),
+			to_json(&warning.document_index()),
+			to_json(&warning.is_output()),
+			to_json(&line),
+			to_json(&column),
+			to_json(&warning.path()),
+			to_json(&warning.to_string()),
+		);
+	} else {
+		let _ = match warning.source() {
+			Some(file) => writeln!(stderr, "{} in {file}: {warning}", Label::WARNING),
+			None => writeln!(stderr, "{}: {warning}", Label::WARNING),
+		};
+	}
+}
+
+/// Writes a warning about a file that xt is working on to standard error.
+pub(crate) fn warn_file(file: &Path, message: &str) {
+	let mut stderr = io::stderr().lock();
+	if JSON.load(Ordering::Relaxed) {
+		let _ = writeln!(
+			stderr,
+			r#"{{"warning":true,"file":{},"message":{}}}"#,
+			to_json(&file.display().to_string()),
+			to_json(&message),
+		);
+	} else {
+		let _ = writeln!(
+			stderr,
+			"{} in {}: {message}",
+			Label::WARNING,
+			file.display()
+		);
+	}
+}
+
+/// Writes a warning about a key that the reference of `--known-keys` lacks to standard error.
+pub(crate) fn warn_unknown_key(unknown: &xt::UnknownKey) {
+	let mut stderr = io::stderr().lock();
+	if JSON.load(Ordering::Relaxed) {
+		let _ = writeln!(
+			stderr,
+			r#"{{"warning":true,"file":{},"document":{},"path":{},"key":{},"message":{}}}"#,
+			to_json(&unknown.source()),
+			to_json(&unknown.document_index()),
+			to_json(&unknown.path()),
+			to_json(&unknown.key()),
+			to_json(&unknown.to_string()),
+		);
+	} else {
+		let document = unknown.document_index();
+		let _ = match unknown.source() {
+			Some(file) => writeln!(
+				stderr,
+				"{} in {file}: {unknown} (in document {document})",
+				Label::WARNING
+			),
+			None => writeln!(
+				stderr,
+				"{}: {unknown} (in document {document})",
+				Label::WARNING
+			),
+		};
+	}
+}
+
+/// Writes a warning about a string that `--audit-strings` found to standard error.
+pub(crate) fn warn_string_issue(issue: &xt::StringIssue) {
+	let mut stderr = io::stderr().lock();
+	if JSON.load(Ordering::Relaxed) {
+		let _ = writeln!(
+			stderr,
+			r#"{{"warning":true,"file":{},"document":{},"path":{},"key":{},"message":{}}}"#,
+			to_json(&issue.source()),
+			to_json(&issue.document_index()),
+			to_json(&issue.path()),
+			to_json(&issue.is_key()),
+			to_json(&issue.to_string()),
+		);
+	} else {
+		let document = issue.document_index();
+		let _ = match issue.source() {
+			Some(file) => writeln!(
+				stderr,
+				"{} in {file}: {issue} (in document {document})",
+				Label::WARNING
+			),
+			None => writeln!(
+				stderr,
+				"{}: {issue} (in document {document})",
+				Label::WARNING
+			),
+		};
+	}
+}
+
+/// Writes a warning about skipped corrupt MessagePack input to standard error.
+pub(crate) fn warn_msgpack_resync(resync: &xt::MsgpackResync) {
+	let mut stderr = io::stderr().lock();
+	if JSON.load(Ordering::Relaxed) {
 		let _ = writeln!(
-			::std::io::stderr().lock(),
-			"xt error: {}",
-			format_args!($fmt $(, $($args)* )?),
+			stderr,
+			r#"{{"warning":true,"file":{},"offset":{},"resume":{},"message":{}}}"#,
+			to_json(&resync.source()),
+			to_json(&resync.offset()),
+			to_json(&resync.resume_offset()),
+			to_json(&resync.to_string()),
 		);
-		::std::process::exit(1);
-	}};
+	} else {
+		let _ = match resync.source() {
+			Some(file) => writeln!(stderr, "{} in {file}: {resync}", Label::WARNING),
+			None => writeln!(stderr, "{}: {resync}", Label::WARNING),
+		};
+	}
+}
+
+/// Writes a warning about invalid UTF-8 that `--repair-utf8` replaced to standard error.
+pub(crate) fn warn_utf8_repair(repair: &xt::Utf8Repair) {
+	let mut stderr = io::stderr().lock();
+	if JSON.load(Ordering::Relaxed) {
+		let _ = writeln!(
+			stderr,
+			r#"{{"warning":true,"file":{},"count":{},"message":{}}}"#,
+			to_json(&repair.source()),
+			to_json(&repair.count()),
+			to_json(&repair.to_string()),
+		);
+	} else {
+		let _ = match repair.source() {
+			Some(file) => writeln!(stderr, "{} in {file}: {repair}", Label::WARNING),
+			None => writeln!(stderr, "{}: {repair}", Label::WARNING),
+		};
+	}
+}
+
+fn write_text(file: Option<&str>, message: &dyn Display) {
+	let mut stderr = io::stderr().lock();
+	let _ = match file {
+		Some(file) => writeln!(stderr, "{} in {file}: {message}", Label::ERROR),
+		None => writeln!(stderr, "{}: {message}", Label::ERROR),
+	};
+}
+
+fn write_json(failure: &Failure, message: &dyn Display) {
+	let _ = writeln!(
+		io::stderr().lock(),
+		r#"{{"file":{},"format":{},"document":{},"offset":{},"error_offset":{},"message":{}}}"#,
+		to_json(&failure.file),
+		to_json(&failure.format.map(|format| format.to_string())),
+		to_json(&failure.document),
+		to_json(&failure.offset),
+		to_json(&failure.error_offset),
+		to_json(&message.to_string()),
+	);
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+	serde_json::to_string(value).unwrap_or_else(|_| "null".into())
+}
+
+/// Formats a message to standard error, then terminates the current process with exit code 1.
+macro_rules! die {
+	($fmt:literal $(, $($args:tt)* )?) => {
+		$crate::die::exit(
+			&$crate::die::Failure::default(),
+			&format_args!($fmt $(, $($args)* )?),
+		)
+	};
 }
 
 /// Formats a message to standard error, including the provided file path, then terminates the
 /// current process with exit code 1.
 macro_rules! die_in {
-	($path:expr, $fmt:literal $(, $($args:tt)* )?) => {{
-		use ::std::io::Write;
-		let _ = writeln!(
-			::std::io::stderr().lock(),
-			"xt error in {}: {}",
-			$path,
-			format_args!($fmt $(, $($args)* )?),
-		);
-		::std::process::exit(1);
-	}};
+	($path:expr, $fmt:literal $(, $($args:tt)* )?) => {
+		$crate::die::exit(
+			&$crate::die::Failure {
+				file: Some($path.to_string()),
+				..Default::default()
+			},
+			&format_args!($fmt $(, $($args)* )?),
+		)
+	};
 }
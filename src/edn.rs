@@ -0,0 +1,554 @@
+//! The EDN format of Clojure data, as interpreted by xt itself.
+//!
+//! Each top-level element of an input is a document. Keywords and symbols translate as strings of
+//! their names, without the leading `:` of a keyword, so that `{:port 80}` translates like the
+//! JSON `{"port": 80}`. Lists, vectors, and sets all translate as arrays, characters as strings,
+//! decimals with an `M` suffix as floats, and integers with an `N` suffix as plain integers. A
+//! tagged literal like `#inst "2024-01-02T03:04:05Z"` translates as its value alone, or as a
+//! string of its source text under [`EdnTags::Stringify`].
+//!
+//! Output goes the other way: arrays become vectors, and each map key that is a string with the
+//! form of a keyword name becomes a keyword.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::str;
+
+use serde::{Deserialize, de};
+
+use crate::detect::Trial;
+use crate::input::{self, Ref};
+use crate::transcode::{self, Value};
+
+/// The maximum nesting depth of EDN input, as for MessagePack input.
+pub(crate) const DEPTH_LIMIT: usize = 1024;
+
+/// What EDN input does with a tagged literal like `#inst "2024-01-02T03:04:05Z"`, as set by
+/// [`Translator::set_edn_tags`](crate::Translator::set_edn_tags).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EdnTags {
+	/// Translate the tagged value alone, as if the tag weren't there.
+	#[default]
+	Passthrough,
+	/// Translate a string of the tag and the source text of its value, like
+	/// `#inst "2024-01-02T03:04:05Z"`.
+	Stringify,
+}
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// Much of JSON and YAML is also valid EDN, or close enough that the difference would take a
+	// full parse to find.
+	Ok(Err("EDN is never detected from content".into()))
+}
+
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	mut output: O,
+	tags: EdnTags,
+) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	let input: Cow<'_, [u8]> = input.try_into()?;
+	let text = str::from_utf8(&input)?;
+	let mut parser = Parser { text, pos: 0, tags };
+	while let Some((start, value)) = parser.next_document()? {
+		output.set_document_offset(start as u64);
+		output.set_document_end(parser.pos as u64);
+		output.transcode_value(value)?;
+	}
+	Ok(())
+}
+
+/// A parser of the elements of EDN text.
+struct Parser<'t> {
+	text: &'t str,
+	pos: usize,
+	tags: EdnTags,
+}
+
+impl<'t> Parser<'t> {
+	/// Parses the next top-level element, and returns it with the offset where it starts.
+	fn next_document(&mut self) -> Result<Option<(usize, Value<'static>)>, InputError> {
+		self.skip_whitespace()?;
+		if self.pos == self.text.len() {
+			return Ok(None);
+		}
+		let start = self.pos;
+		Ok(Some((start, self.parse_value(DEPTH_LIMIT)?)))
+	}
+
+	fn rest(&self) -> &'t str {
+		&self.text[self.pos..]
+	}
+
+	fn error(&self, message: &'static str) -> InputError {
+		InputError {
+			line: self.text[..self.pos].matches('\n').count() + 1,
+			message,
+		}
+	}
+
+	/// Skips whitespace, commas, comments, and elements discarded with `#_`.
+	fn skip_whitespace(&mut self) -> Result<(), InputError> {
+		loop {
+			let rest = self.rest();
+			let trimmed = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+			self.pos += rest.len() - trimmed.len();
+			if trimmed.starts_with(';') {
+				self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+			} else if trimmed.starts_with("#_") {
+				self.pos += 2;
+				self.skip_whitespace()?;
+				self.parse_value(DEPTH_LIMIT)?;
+			} else {
+				return Ok(());
+			}
+		}
+	}
+
+	fn parse_value(&mut self, depth: usize) -> Result<Value<'static>, InputError> {
+		if depth == 0 {
+			return Err(self.error("nesting is too deep"));
+		}
+		let string = |s: String| Value::String(Cow::Owned(s));
+		let Some(c) = self.rest().chars().next() else {
+			return Err(self.error("input ends before an element"));
+		};
+		match c {
+			'"' => {
+				self.pos += 1;
+				self.parse_string().map(string)
+			}
+			'\\' => {
+				self.pos += 1;
+				self.parse_char().map(Value::Char)
+			}
+			'[' | '(' => {
+				self.pos += 1;
+				let end = if c == '[' { ']' } else { ')' };
+				self.parse_elements(end, depth).map(Value::Seq)
+			}
+			'{' => {
+				self.pos += 1;
+				self.parse_map(depth)
+			}
+			'#' => self.parse_dispatch(depth),
+			':' => {
+				self.pos += 1;
+				let name = self.token();
+				if name.is_empty() || name.starts_with(':') {
+					return Err(self.error("keyword lacks a valid name"));
+				}
+				Ok(string(name.into()))
+			}
+			')' | ']' | '}' => Err(self.error("closing delimiter has no opening one")),
+			_ => {
+				let token = self.token();
+				match token {
+					"nil" => Ok(Value::Unit),
+					"true" => Ok(Value::Bool(true)),
+					"false" => Ok(Value::Bool(false)),
+					_ if is_number(token) => {
+						parse_number(token).ok_or(self.error("invalid number"))
+					}
+					_ => Ok(string(token.into())),
+				}
+			}
+		}
+	}
+
+	/// Takes the characters up to the next delimiter.
+	fn token(&mut self) -> &'t str {
+		let rest = &self.text[self.pos..];
+		let len = rest
+			.find(|c: char| c.is_whitespace() || "()[]{}\",;".contains(c))
+			.unwrap_or(rest.len());
+		self.pos += len;
+		&rest[..len]
+	}
+
+	/// Parses the elements of a collection up to its closing delimiter.
+	fn parse_elements(
+		&mut self,
+		end: char,
+		depth: usize,
+	) -> Result<Vec<Value<'static>>, InputError> {
+		let mut elements = vec![];
+		loop {
+			self.skip_whitespace()?;
+			if self.rest().starts_with(end) {
+				self.pos += 1;
+				return Ok(elements);
+			}
+			if self.pos == self.text.len() {
+				return Err(self.error("collection lacks a closing delimiter"));
+			}
+			elements.push(self.parse_value(depth - 1)?);
+		}
+	}
+
+	fn parse_map(&mut self, depth: usize) -> Result<Value<'static>, InputError> {
+		let elements = self.parse_elements('}', depth)?;
+		if elements.len() % 2 != 0 {
+			return Err(self.error("map has a key without a value"));
+		}
+		let mut elements = elements.into_iter();
+		let mut entries = Vec::with_capacity(elements.len() / 2);
+		while let (Some(k), Some(v)) = (elements.next(), elements.next()) {
+			entries.push((k, v));
+		}
+		Ok(Value::Map(entries))
+	}
+
+	/// Parses a set, a symbolic value, or a tagged literal, all of which start with `#`.
+	fn parse_dispatch(&mut self, depth: usize) -> Result<Value<'static>, InputError> {
+		let rest = self.rest();
+		if rest.starts_with("#{") {
+			self.pos += 2;
+			return self.parse_elements('}', depth).map(Value::Seq);
+		}
+		if rest.starts_with("##") {
+			self.pos += 2;
+			return match self.token() {
+				"Inf" => Ok(Value::F64(f64::INFINITY)),
+				"-Inf" => Ok(Value::F64(f64::NEG_INFINITY)),
+				"NaN" => Ok(Value::F64(f64::NAN)),
+				_ => Err(self.error("unknown symbolic value")),
+			};
+		}
+
+		self.pos += 1;
+		let tag = self.token();
+		if !tag.starts_with(char::is_alphabetic) {
+			return Err(self.error("tag must start with a letter"));
+		}
+		self.skip_whitespace()?;
+		let start = self.pos;
+		let value = self.parse_value(depth - 1)?;
+		Ok(match self.tags {
+			EdnTags::Passthrough => value,
+			EdnTags::Stringify => {
+				let source = &self.text[start..self.pos];
+				Value::String(Cow::Owned(format!("#{tag} {source}")))
+			}
+		})
+	}
+
+	/// Parses the rest of a string after its opening quote.
+	fn parse_string(&mut self) -> Result<String, InputError> {
+		let mut s = String::new();
+		let mut chars = self.rest().char_indices();
+		loop {
+			let Some((i, c)) = chars.next() else {
+				return Err(self.error("string lacks a closing quote"));
+			};
+			match c {
+				'"' => {
+					self.pos += i + 1;
+					return Ok(s);
+				}
+				'\\' => {
+					let escaped = match chars.next().map(|(_, c)| c) {
+						Some('t') => '\t',
+						Some('r') => '\r',
+						Some('n') => '\n',
+						Some('b') => '\u{8}',
+						Some('f') => '\u{c}',
+						Some(c @ ('\\' | '"')) => c,
+						Some('u') => {
+							let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+							u32::from_str_radix(&hex, 16)
+								.ok()
+								.filter(|_| hex.len() == 4)
+								.and_then(char::from_u32)
+								.ok_or(self.error("invalid unicode escape in a string"))?
+						}
+						_ => return Err(self.error("invalid escape in a string")),
+					};
+					s.push(escaped);
+				}
+				c => s.push(c),
+			}
+		}
+	}
+
+	/// Parses the rest of a character literal after its backslash.
+	fn parse_char(&mut self) -> Result<char, InputError> {
+		let Some(first) = self.rest().chars().next() else {
+			return Err(self.error("input ends within a character"));
+		};
+		// A delimiter right after the backslash is the character itself, like `\(`.
+		self.pos += first.len_utf8();
+		let rest = self.token();
+		let name = &self.text[self.pos - rest.len() - first.len_utf8()..self.pos];
+		let c = match name {
+			_ if rest.is_empty() => Some(first),
+			"newline" => Some('\n'),
+			"return" => Some('\r'),
+			"space" => Some(' '),
+			"tab" => Some('\t'),
+			"formfeed" => Some('\u{c}'),
+			"backspace" => Some('\u{8}'),
+			_ => { name.strip_prefix('u') }
+				.filter(|hex| hex.len() == 4)
+				.and_then(|hex| u32::from_str_radix(hex, 16).ok())
+				.and_then(char::from_u32),
+		};
+		c.ok_or(self.error("invalid character"))
+	}
+}
+
+/// Returns true if a token has the form of a number rather than a symbol.
+fn is_number(token: &str) -> bool {
+	let digits = token.strip_prefix(['+', '-']).unwrap_or(token);
+	digits.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn parse_number(token: &str) -> Option<Value<'static>> {
+	if let Some(decimal) = token.strip_suffix('M') {
+		return decimal.parse().ok().map(Value::F64);
+	}
+	if token.contains(['.', 'e', 'E']) {
+		return token.parse().ok().map(Value::F64);
+	}
+	let int = token.strip_suffix('N').unwrap_or(token);
+	let int = int.strip_prefix('+').unwrap_or(int);
+	// EDN has no octal integers, and so allows no leading zeros.
+	let digits = int.strip_prefix('-').unwrap_or(int);
+	if digits.len() > 1 && digits.starts_with('0') {
+		return None;
+	}
+	if let Ok(n) = int.parse() {
+		return Some(Value::I64(n));
+	}
+	if let Ok(n) = int.parse() {
+		return Some(Value::U64(n));
+	}
+	int.parse().ok().map(Value::I128)
+}
+
+/// The error for input that isn't valid EDN.
+#[derive(Debug)]
+pub(crate) struct InputError {
+	line: usize,
+	message: &'static str,
+}
+
+impl error::Error for InputError {}
+
+impl fmt::Display for InputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "invalid EDN on line {}: {}", self.line, self.message)
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+}
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output { w }
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	fn output_document(&mut self, value: &Value) -> crate::Result<()> {
+		let mut text = String::new();
+		write_value(&mut text, value, false);
+		text.push('\n');
+		self.w.write_all(text.as_bytes())?;
+		Ok(())
+	}
+}
+
+/// Writes the EDN text of a value, where `key` says whether the value is a map key.
+fn write_value(text: &mut String, value: &Value, key: bool) {
+	let _ = match value {
+		Value::Unit => text.write_str("nil"),
+		Value::Bool(b) => write!(text, "{b}"),
+		Value::I8(n) => write!(text, "{n}"),
+		Value::I16(n) => write!(text, "{n}"),
+		Value::I32(n) => write!(text, "{n}"),
+		Value::I64(n) => write!(text, "{n}"),
+		Value::I128(n) => write!(text, "{n}"),
+		Value::U8(n) => write!(text, "{n}"),
+		Value::U16(n) => write!(text, "{n}"),
+		Value::U32(n) => write!(text, "{n}"),
+		Value::U64(n) => write!(text, "{n}"),
+		Value::U128(n) => write!(text, "{n}"),
+		Value::F32(n) => write_float(text, f64::from(*n)),
+		Value::F64(n) => write_float(text, *n),
+		Value::Char(c) => write_string(text, &c.to_string()),
+		Value::String(s) if key && is_keyword_name(s) => write!(text, ":{s}"),
+		Value::String(s) => write_string(text, s),
+		Value::Bytes(b) => {
+			text.push('[');
+			for (i, b) in b.iter().enumerate() {
+				if i > 0 {
+					text.push(' ');
+				}
+				let _ = write!(text, "{b}");
+			}
+			text.write_str("]")
+		}
+		Value::Seq(seq) => {
+			text.push('[');
+			for (i, v) in seq.iter().enumerate() {
+				if i > 0 {
+					text.push(' ');
+				}
+				write_value(text, v, false);
+			}
+			text.write_str("]")
+		}
+		Value::Map(map) => {
+			text.push('{');
+			for (i, (k, v)) in map.iter().enumerate() {
+				if i > 0 {
+					text.push_str(", ");
+				}
+				write_value(text, k, true);
+				text.push(' ');
+				write_value(text, v, false);
+			}
+			text.write_str("}")
+		}
+	};
+}
+
+fn write_float(text: &mut String, n: f64) -> fmt::Result {
+	match n {
+		_ if n.is_nan() => text.write_str("##NaN"),
+		f64::INFINITY => text.write_str("##Inf"),
+		f64::NEG_INFINITY => text.write_str("##-Inf"),
+		// Debug formatting always includes a decimal point or an exponent, as EDN floats need.
+		n => write!(text, "{n:?}"),
+	}
+}
+
+fn write_string(text: &mut String, s: &str) -> fmt::Result {
+	text.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => text.push_str("\\\""),
+			'\\' => text.push_str("\\\\"),
+			'\n' => text.push_str("\\n"),
+			'\r' => text.push_str("\\r"),
+			'\t' => text.push_str("\\t"),
+			c if c.is_control() => write!(text, "\\u{:04x}", u32::from(c))?,
+			c => text.push(c),
+		}
+	}
+	text.write_str("\"")
+}
+
+/// Returns true if a string is the name of a keyword that EDN input would read back as the
+/// same string.
+fn is_keyword_name(s: &str) -> bool {
+	let symbolic = |c: char| c.is_alphanumeric() || "*+!-_?<>=.".contains(c);
+	let part = |part: &str| {
+		!part.is_empty()
+			&& part.chars().all(symbolic)
+			&& !part.starts_with(|c: char| c.is_ascii_digit())
+			&& !is_number(part)
+	};
+	let (namespace, name) = match s.split_once('/') {
+		Some((namespace, name)) => (Some(namespace), name),
+		None => (None, s),
+	};
+	part(name) && namespace.is_none_or(part) && !matches!(s, "nil" | "true" | "false")
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		let value = Value::deserialize(de)?;
+		self.output_document(&value)
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		self.output_document(&value)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_and_write_elements() {
+		const INPUT: &str = r#"
+; a comment
+{:name "xt", :tags #{:a b}, :ratio 1.5M, :big 10N, #_ :skipped #_ 1
+ :when #inst "2024-01-02T03:04:05Z", "str key" [\a \newline A nil ##Inf]}
+(1 -2)"#;
+		let mut parser = Parser {
+			text: INPUT,
+			pos: 0,
+			tags: EdnTags::Passthrough,
+		};
+		let mut text = String::new();
+		while let Some((_, value)) = parser.next_document().unwrap() {
+			write_value(&mut text, &value, false);
+			text.push('\n');
+		}
+		assert_eq!(
+			text,
+			concat!(
+				r#"{:name "xt", :tags ["a" "b"], :ratio 1.5, :big 10, "#,
+				r#":when "2024-01-02T03:04:05Z", "str key" ["a" "\n" "A" nil ##Inf]}"#,
+				"\n[1 -2]\n",
+			)
+		);
+
+		let mut parser = Parser {
+			text: "#inst \"2024\"",
+			pos: 0,
+			tags: EdnTags::Stringify,
+		};
+		let (_, value) = parser.next_document().unwrap().unwrap();
+		assert!(matches!(value, Value::String(s) if s == "#inst \"2024\""));
+
+		for (input, line) in [
+			("{:a}", 1),
+			("[1\n2", 2),
+			("\n)", 2),
+			("007", 1),
+			(r#""\q""#, 1),
+		] {
+			let mut parser = Parser {
+				text: input,
+				pos: 0,
+				tags: EdnTags::Passthrough,
+			};
+			let Err(err) = parser.next_document() else {
+				panic!("parsed invalid EDN {input:?}");
+			};
+			assert_eq!(err.line, line, "{input:?}");
+		}
+	}
+
+	#[test]
+	fn keyword_names() {
+		for name in ["a", "a-b?", "ns/name", "*x*", "-a", "a.b"] {
+			assert!(is_keyword_name(name), "{name}");
+		}
+		for name in ["", "1a", "-1", "a b", "/a", "a/", "nil", "a:b", "a/b/c"] {
+			assert!(!is_keyword_name(name), "{name}");
+		}
+	}
+}
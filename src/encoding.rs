@@ -0,0 +1,215 @@
+//! Text encoding of serialized output.
+//!
+//! Output formats always serialize UTF-8, and a translator re-encodes their output on its way to
+//! the underlying writer when text output should have a different encoding. This mirrors the YAML
+//! input support for UTF-16 and UTF-32 streams, and likewise supports only Unicode encodings.
+//!
+//! Serializers may split the bytes of a single character across writes, so the encoder holds back
+//! any incomplete UTF-8 sequence at the end of a write until the next one completes it.
+//!
+//! The same encodings describe inputs too, so that an input in a format that only supports UTF-8
+//! can fail with an error naming the encoding it appears to have.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::str;
+
+use crate::Format;
+
+/// The text encoding of a translator's output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+	/// UTF-8, which output formats produce natively.
+	#[default]
+	Utf8,
+	/// UTF-16 with the least significant byte of each code unit first.
+	Utf16Le,
+	/// UTF-16 with the most significant byte of each code unit first.
+	Utf16Be,
+	/// UTF-32 with the least significant byte of each code unit first.
+	Utf32Le,
+	/// UTF-32 with the most significant byte of each code unit first.
+	Utf32Be,
+}
+
+impl Encoding {
+	/// Appends the encoded form of a character to `out`.
+	fn encode(self, c: char, out: &mut Vec<u8>) {
+		match self {
+			Encoding::Utf8 => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+			Encoding::Utf16Le => {
+				for unit in c.encode_utf16(&mut [0; 2]) {
+					out.extend_from_slice(&unit.to_le_bytes());
+				}
+			}
+			Encoding::Utf16Be => {
+				for unit in c.encode_utf16(&mut [0; 2]) {
+					out.extend_from_slice(&unit.to_be_bytes());
+				}
+			}
+			Encoding::Utf32Le => out.extend_from_slice(&u32::from(c).to_le_bytes()),
+			Encoding::Utf32Be => out.extend_from_slice(&u32::from(c).to_be_bytes()),
+		}
+	}
+}
+
+impl Display for Encoding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Encoding::Utf8 => "UTF-8",
+			Encoding::Utf16Le => "UTF-16LE",
+			Encoding::Utf16Be => "UTF-16BE",
+			Encoding::Utf32Le => "UTF-32LE",
+			Encoding::Utf32Be => "UTF-32BE",
+		})
+	}
+}
+
+/// The error for an input that appears to be in an encoding its format doesn't support, in place
+/// of whatever error the format's parser produced.
+#[derive(Debug)]
+pub(crate) struct UnsupportedInputError {
+	pub(crate) encoding: Encoding,
+	pub(crate) format: Format,
+}
+
+impl Error for UnsupportedInputError {}
+
+impl Display for UnsupportedInputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"input appears to be {}, but {} input must be UTF-8",
+			self.encoding, self.format
+		)
+	}
+}
+
+/// A writer that re-encodes UTF-8 text written to it in another encoding.
+pub(crate) struct Writer<W> {
+	inner: W,
+	encoding: Encoding,
+	/// Whether the next write should start with a byte order mark.
+	bom_pending: bool,
+	/// An incomplete UTF-8 sequence from the end of the last write.
+	partial: Vec<u8>,
+	encoded: Vec<u8>,
+	/// The number of bytes written to the underlying writer.
+	position: u64,
+}
+
+impl<W: Write> Writer<W> {
+	/// Creates a writer that passes UTF-8 through as is.
+	pub(crate) fn new(inner: W) -> Writer<W> {
+		Writer {
+			inner,
+			encoding: Encoding::Utf8,
+			bom_pending: false,
+			partial: vec![],
+			encoded: vec![],
+			position: 0,
+		}
+	}
+
+	/// Sets the encoding of everything written from now on.
+	pub(crate) fn set_encoding(&mut self, encoding: Encoding) {
+		self.encoding = encoding;
+	}
+
+	/// Sets whether the next write starts with a byte order mark.
+	pub(crate) fn set_bom(&mut self, bom: bool) {
+		self.bom_pending = bom;
+	}
+
+	/// Returns the number of bytes written to the underlying writer so far, after encoding.
+	pub(crate) fn position(&self) -> u64 {
+		self.position
+	}
+
+	fn write_encoded(&mut self, buf: &[u8]) -> io::Result<()> {
+		self.encoded.clear();
+		if self.bom_pending {
+			self.encoding.encode('\u{FEFF}', &mut self.encoded);
+			self.bom_pending = false;
+		}
+		self.partial.extend_from_slice(buf);
+		let len = match str::from_utf8(&self.partial) {
+			Ok(text) => text.len(),
+			Err(err) if err.error_len().is_none() => err.valid_up_to(),
+			Err(_) => {
+				self.partial.clear();
+				return Err(invalid_utf8());
+			}
+		};
+		let valid = str::from_utf8(&self.partial[..len]).map_err(|_| invalid_utf8())?;
+		for c in valid.chars() {
+			self.encoding.encode(c, &mut self.encoded);
+		}
+		self.partial.drain(..len);
+		self.inner.write_all(&self.encoded)?;
+		self.position += self.encoded.len() as u64;
+		Ok(())
+	}
+}
+
+impl<W: Write> Write for Writer<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		if self.encoding == Encoding::Utf8 && !self.bom_pending && self.partial.is_empty() {
+			let len = self.inner.write(buf)?;
+			self.position += len as u64;
+			return Ok(len);
+		}
+		self.write_encoded(buf)?;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+fn invalid_utf8() -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, "output is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn encode_split_characters() {
+		const TEXT: &str = "a\u{e9}\u{1f600}\n";
+		const CASES: &[(Encoding, &[u8])] = &[
+			(
+				Encoding::Utf16Le,
+				b"\xff\xfea\x00\xe9\x00\x3d\xd8\x00\xde\n\x00",
+			),
+			(
+				Encoding::Utf32Be,
+				b"\x00\x00\xfe\xff\x00\x00\x00a\x00\x00\x00\xe9\x00\x01\xf6\x00\x00\x00\x00\n",
+			),
+		];
+		for &(encoding, want) in CASES {
+			let mut w = Writer::new(vec![]);
+			w.set_encoding(encoding);
+			w.set_bom(true);
+			// Write one byte at a time to split every multibyte sequence.
+			for b in TEXT.as_bytes() {
+				w.write_all(std::slice::from_ref(b)).unwrap();
+			}
+			assert_eq!(w.inner, want, "{encoding:?}");
+		}
+	}
+
+	#[test]
+	fn reject_invalid_utf8() {
+		let mut w = Writer::new(vec![]);
+		w.set_encoding(Encoding::Utf16Be);
+		assert!(w.write_all(b"a\xff").is_err());
+	}
+}
@@ -8,21 +8,117 @@ use std::result;
 pub type Result<T> = result::Result<T, Error>;
 
 /// An error encountered during translation.
+///
+/// Errors that occur while translating a document carry the location of that document within
+/// its input, which appears at the end of the error's [`Display`] output and is available through
+/// [`document_index`](Error::document_index) and [`document_offset`](Error::document_offset).
 #[derive(Debug)]
-pub struct Error(Box<dyn StdError + Send + Sync + 'static>);
+pub struct Error {
+	inner: Box<dyn StdError + Send + Sync + 'static>,
+	location: Option<Location>,
+}
+
+impl Error {
+	/// Returns the zero-based index of the reader that the error occurred in, if the error
+	/// occurred while translating a document from a chain of
+	/// [readers](crate::Translator::translate_readers) at a known offset.
+	///
+	/// For these errors, the [index](Error::document_index) and [offset](Error::document_offset)
+	/// of the document are relative to that reader.
+	pub fn input_index(&self) -> Option<usize> {
+		self.location.and_then(|loc| loc.input)
+	}
+
+	/// Returns the zero-based index of the document that the error occurred in within its input,
+	/// if the error occurred while translating a document.
+	///
+	/// Indexes count every document that the input format produced, including documents that
+	/// transforms or document selection left out of the output.
+	pub fn document_index(&self) -> Option<u64> {
+		self.location.map(|loc| loc.index)
+	}
+
+	/// Returns the byte offset where the document that the error occurred in starts within its
+	/// input, if the input format reports it.
+	///
+	/// Offsets are available in the same cases as for the
+	/// [`provenance`](crate::Transform::provenance) transform.
+	pub fn document_offset(&self) -> Option<u64> {
+		self.location.and_then(|loc| loc.offset)
+	}
+
+	/// Returns the result of format detection, if the error is a failure to detect the format of
+	/// an input.
+	pub fn detection(&self) -> Option<&crate::Detection> {
+		self.inner
+			.downcast_ref::<crate::detect::Undetected>()
+			.map(|undetected| &undetected.0)
+	}
+
+	/// Replaces the error with another one, keeping the location of the document that it
+	/// occurred in.
+	pub(crate) fn replace<E>(self, inner: E) -> Error
+	where
+		E: Into<Box<dyn StdError + Send + Sync + 'static>>,
+	{
+		Error {
+			inner: inner.into(),
+			location: self.location,
+		}
+	}
+
+	/// Attaches the location of the document that the error occurred in, unless the error already
+	/// has one.
+	pub(crate) fn in_document(mut self, location: Location) -> Error {
+		self.location.get_or_insert(location);
+		self
+	}
+}
 
 impl AsRef<dyn StdError + Send + Sync> for Error {
 	fn as_ref(&self) -> &(dyn StdError + Send + Sync + 'static) {
-		self.0.as_ref()
+		self.inner.as_ref()
 	}
 }
 
 impl Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		Display::fmt(&self.0, f)
+		Display::fmt(&self.inner, f)?;
+		let Some(Location {
+			input,
+			index,
+			offset,
+			..
+		}) = self.location
+		else {
+			return Ok(());
+		};
+		f.write_str(" (in ")?;
+		if let Some(input) = input {
+			write!(f, "input {input}, ")?;
+		}
+		write!(f, "document {index}")?;
+		if let Some(offset) = offset {
+			write!(f, ", starting at byte {offset}")?;
+		}
+		f.write_str(")")
 	}
 }
 
+/// The location of a document within its input.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Location {
+	/// The zero-based index of the reader containing the document, for documents at known offsets
+	/// within a chain of readers.
+	pub(crate) input: Option<usize>,
+	/// The zero-based index of the document within its input.
+	pub(crate) index: u64,
+	/// The byte offset where the document starts within its input, if known.
+	pub(crate) offset: Option<u64>,
+	/// The byte offset just past the end of the document within its input, if known.
+	pub(crate) end: Option<u64>,
+}
+
 /// Box any error into an [`Error`].
 ///
 /// **This impl is unstable**, and external code must not rely on it.
@@ -32,6 +128,9 @@ where
 	T: Into<Box<dyn StdError + Send + Sync + 'static>>,
 {
 	fn from(err: T) -> Self {
-		Self(err.into())
+		Self {
+			inner: err.into(),
+			location: None,
+		}
 	}
 }
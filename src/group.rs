@@ -0,0 +1,126 @@
+//! Grouping of every document in a stream by the value at a path.
+//!
+//! Grouping collects documents into arrays keyed by the value that each document has at the
+//! grouping path, and outputs a single map from each group's value to its array. Groups appear in
+//! the order of their first documents, and documents within each group keep their original order.
+//! Documents without a value at the path fall into a group with a null key.
+//!
+//! A translator that groups must hold every document in memory until it finishes the stream, and
+//! only then passes the map of groups on to the output.
+
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::intern;
+use crate::transcode::Value;
+use crate::transform::path::{self, Segment};
+
+/// The state of grouping across all documents in a stream.
+pub(crate) struct Grouping {
+	path: Vec<Segment>,
+	groups: Vec<(Value<'static>, Vec<Value<'static>>)>,
+	/// Whether grouped documents take their map keys from the [`intern`] pool.
+	intern: bool,
+}
+
+impl Grouping {
+	/// Creates a grouping by the value at a path expression like `.kind`.
+	pub(crate) fn new(expr: &str) -> crate::Result<Grouping> {
+		Ok(Grouping {
+			path: path::parse("group-by path", expr)?,
+			groups: vec![],
+			intern: false,
+		})
+	}
+
+	pub(crate) fn set_intern_keys(&mut self, intern: bool) {
+		self.intern = intern;
+	}
+
+	fn add(&mut self, value: Value) {
+		let value = intern::into_owned(value, self.intern);
+		let key = match path::lookup(&value, &self.path) {
+			Some(key) => key.clone(),
+			None => Value::Unit,
+		};
+		match self.groups.iter_mut().find(|(k, _)| *k == key) {
+			Some((_, docs)) => docs.push(value),
+			None => self.groups.push((key, vec![value])),
+		}
+	}
+}
+
+/// An [`Output`](crate::Output) that groups every document instead of passing it through, when
+/// grouping is enabled.
+pub(crate) struct Output<'g, O> {
+	output: O,
+	grouping: Option<&'g mut Grouping>,
+}
+
+impl<'g, O> Output<'g, O> {
+	pub(crate) fn new(output: O, grouping: Option<&'g mut Grouping>) -> Output<'g, O> {
+		Output { output, grouping }
+	}
+
+	/// Passes the map of groups to the output, if there were any documents, and returns the
+	/// output.
+	pub(crate) fn finish<'i>(mut self) -> crate::Result<O>
+	where
+		O: crate::Output<'i>,
+	{
+		if let Some(grouping) = self.grouping {
+			if !grouping.groups.is_empty() {
+				let groups = grouping
+					.groups
+					.drain(..)
+					.map(|(k, docs)| (k, Value::Seq(docs)))
+					.collect();
+				self.output.transcode_value(Value::Map(groups))?;
+			}
+		}
+		Ok(self.output)
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		match &mut self.grouping {
+			Some(_) => self.transcode_value(Value::deserialize(de)?),
+			None => self.output.transcode_from(de),
+		}
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		match &mut self.grouping {
+			Some(grouping) => {
+				grouping.add(value);
+				Ok(())
+			}
+			None => self.output.transcode_value(value),
+		}
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		match &mut self.grouping {
+			Some(_) => self.transcode_value(value),
+			None => self.output.transcode_borrowed_value(value),
+		}
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
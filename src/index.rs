@@ -0,0 +1,161 @@
+//! A sidecar index of the documents that a translator outputs.
+//!
+//! The index has one entry per output document, serialized as a separate document to its own
+//! writer in any output format. Each entry records where the document came from and the range of
+//! bytes it occupies in the translated output, so that downstream tools can seek straight to a
+//! document rather than parsing the output from the start. Documents that don't correspond to a
+//! single input document, like the result of merging, have entries with only output ranges.
+//!
+//! Entries take output ranges from the output position after re-encoding, so they stay accurate
+//! for any [output encoding](crate::Encoding).
+//!
+//! The same knowledge of each document's origin supplies the comment line that output formats with
+//! comments can write before each document, with [document
+//! comments](crate::Translator::set_document_comments) enabled.
+
+use std::borrow::Cow;
+use std::io::{self, BufWriter, Write};
+
+use serde::de;
+
+use crate::error::Location;
+use crate::transcode::Value;
+use crate::{Dispatcher, Format, Output as _, atomic, encoding};
+
+/// The state of a sidecar index across all documents in a stream.
+pub(crate) struct Index {
+	output: Dispatcher<BufWriter<Box<dyn Write + Send>>>,
+	count: u64,
+}
+
+impl Index {
+	pub(crate) fn new(output: Box<dyn Write + Send>, format: Format) -> Index {
+		Index {
+			output: Dispatcher::new(BufWriter::new(output), format),
+			count: 0,
+		}
+	}
+
+	/// Writes the entry for the next output document.
+	fn record(
+		&mut self,
+		source: Option<&str>,
+		location: Option<Location>,
+		output: (u64, u64),
+	) -> crate::Result<()> {
+		let string = |s: &str| Value::String(Cow::Owned(s.into()));
+		let mut entry = vec![(string("document"), Value::U64(self.count))];
+		if let Some(source) = source {
+			entry.push((string("source"), string(source)));
+		}
+		if let Some(location) = location {
+			if let Some(input) = location.input {
+				entry.push((string("input"), Value::U64(input as u64)));
+			}
+			entry.push((string("input_document"), Value::U64(location.index)));
+			if let Some(offset) = location.offset {
+				entry.push((string("input_offset"), Value::U64(offset)));
+			}
+			if let Some(end) = location.end {
+				entry.push((string("input_end"), Value::U64(end)));
+			}
+		}
+		entry.push((string("output_offset"), Value::U64(output.0)));
+		entry.push((string("output_end"), Value::U64(output.1)));
+		self.count += 1;
+		(&mut self.output).transcode_value(Value::Map(entry))
+	}
+
+	pub(crate) fn flush(&mut self) -> io::Result<()> {
+		(&mut self.output).flush()
+	}
+}
+
+/// An [`Output`](crate::Output) that records every document written to an [`atomic::Output`] in
+/// an index, when indexing is enabled, and comments on where each document came from, when
+/// comments are enabled.
+pub(crate) struct Output<'x, 'd, W: Write> {
+	output: atomic::Output<'d, encoding::Writer<W>>,
+	index: Option<&'x mut Index>,
+	source: Option<&'x str>,
+	location: Option<Location>,
+	comments: bool,
+}
+
+impl<'x, 'd, W: Write> Output<'x, 'd, W> {
+	/// Creates an output that indexes the documents from a single input, where `source` is the
+	/// name of the input if known, and `comments` enables a comment line before each document
+	/// for an output format with `#` comments.
+	pub(crate) fn new(
+		output: atomic::Output<'d, encoding::Writer<W>>,
+		index: Option<&'x mut Index>,
+		source: Option<&'x str>,
+		comments: bool,
+	) -> Output<'x, 'd, W> {
+		Output {
+			output,
+			index,
+			source,
+			location: None,
+			comments,
+		}
+	}
+
+	/// Writes a document with `transcode`, then records it in the index.
+	fn record<F>(&mut self, transcode: F) -> crate::Result<()>
+	where
+		F: FnOnce(&mut atomic::Output<'d, encoding::Writer<W>>) -> crate::Result<()>,
+	{
+		let location = self.location.take();
+		if let Some(location) = location.filter(|_| self.comments) {
+			self.output
+				.set_preamble(comment(self.source, location.index));
+		}
+		let Some(index) = &mut self.index else {
+			return transcode(&mut self.output);
+		};
+		// Positions only count what has reached the encoding writer, so documents can't wait in
+		// a batch.
+		self.output.write_committed()?;
+		let start = self.output.get_mut().position();
+		transcode(&mut self.output)?;
+		self.output.write_committed()?;
+		let end = self.output.get_mut().position();
+		index.record(self.source, location, (start, end))
+	}
+}
+
+/// Returns the comment line for a document, like `# doc 12 from service.yaml`.
+fn comment(source: Option<&str>, index: u64) -> String {
+	match source {
+		// A line break in the name would end the comment early.
+		Some(source) => format!("# doc {index} from {}\n", source.replace(['\r', '\n'], " ")),
+		None => format!("# doc {index}\n"),
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<'_, '_, W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		self.record(|output| output.transcode_from(de))
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		self.record(|output| output.transcode_value(value))
+	}
+
+	fn transcode_msgpack(&mut self, input: &[u8]) -> crate::Result<()> {
+		self.record(|output| output.transcode_msgpack(input))
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.location = Some(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
@@ -0,0 +1,317 @@
+//! The INI format of legacy configuration files, as interpreted by xt itself.
+//!
+//! An input is a single document: a map from each key before the first section header to its
+//! value, followed by a map for each `[section]` from its keys to their values. Keys and values
+//! are separated by the first `=` or `:` on their line, with the whitespace around each trimmed,
+//! and every value is a string, since INI has no types of its own. A value in double quotes keeps
+//! the whitespace within them. Lines starting with `;` or `#` are comments. Sections that appear
+//! more than once combine their keys.
+//!
+//! Output goes the other way, and fails on anything that INI can't represent: arrays, bytes,
+//! values with line breaks, and maps more than one level below the root.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+use std::str;
+
+use serde::{Deserialize, de};
+
+use crate::csv::scalar_text;
+use crate::detect::Trial;
+use crate::input::{self, Ref};
+use crate::transcode::{self, Value};
+
+/// The byte order mark that some tools write at the start of UTF-8 text.
+const BOM: &str = "\u{feff}";
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// Most INI files are also valid TOML, which detection prefers for its typed values, and most
+	// other text has a line with an `=` or `:` somewhere.
+	Ok(Err("INI is never detected from content".into()))
+}
+
+pub(crate) fn transcode<'i, O>(input: input::Handle<'i>, mut output: O) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	let input: Cow<'_, [u8]> = input.try_into()?;
+	let text = str::from_utf8(&input)?;
+	let root = parse(text.strip_prefix(BOM).unwrap_or(text))?;
+	output.set_document_offset(0);
+	output.set_document_end(input.len() as u64);
+	output.transcode_value(root)
+}
+
+fn parse(text: &str) -> Result<Value<'static>, InputError> {
+	let string = |s: &str| Value::String(Cow::Owned(s.into()));
+	let mut root = vec![];
+	// The position of the current section among the entries of the root.
+	let mut section: Option<usize> = None;
+	for (i, line) in text.lines().enumerate() {
+		let invalid = |message| InputError {
+			line: i + 1,
+			message,
+		};
+		let line = line.trim();
+		if line.is_empty() || line.starts_with([';', '#']) {
+			continue;
+		}
+		if let Some(header) = line.strip_prefix('[') {
+			let Some(name) = header.strip_suffix(']') else {
+				return Err(invalid("section header lacks a closing ']'"));
+			};
+			let name = name.trim();
+			let existing = root.iter().position(
+				|(k, v)| matches!((k, v), (Value::String(k), Value::Map(_)) if k == name),
+			);
+			section = Some(existing.unwrap_or_else(|| {
+				root.push((string(name), Value::Map(vec![])));
+				root.len() - 1
+			}));
+			continue;
+		}
+
+		let Some((key, value)) = line.split_once(['=', ':']) else {
+			return Err(invalid(
+				"line is neither a section header nor a key and value",
+			));
+		};
+		let key = key.trim_end();
+		if key.is_empty() {
+			return Err(invalid("key is empty"));
+		}
+		let value = value.trim_start();
+		let value = { value.strip_prefix('"') }
+			.and_then(|v| v.strip_suffix('"'))
+			.unwrap_or(value);
+		let entries = match section {
+			Some(i) => {
+				let Value::Map(entries) = &mut root[i].1 else {
+					unreachable!("sections should be maps");
+				};
+				entries
+			}
+			None => &mut root,
+		};
+		entries.push((string(key), string(value)));
+	}
+	Ok(Value::Map(root))
+}
+
+/// The error for an input line that isn't valid INI.
+#[derive(Debug)]
+pub(crate) struct InputError {
+	line: usize,
+	message: &'static str,
+}
+
+impl error::Error for InputError {}
+
+impl fmt::Display for InputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "invalid INI on line {}: {}", self.line, self.message)
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+	used: bool,
+}
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output { w, used: false }
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	fn output_document(&mut self, value: Value) -> crate::Result<()> {
+		// Like TOML, INI has no way to separate documents.
+		if self.used {
+			return Err(OutputError::MultiDocument.into());
+		}
+		let Value::Map(entries) = value else {
+			return Err(OutputError::NonMapRoot.into());
+		};
+
+		// Keys outside of any section must come before the first header, wherever they appear
+		// among the sections of the document.
+		let mut text = String::new();
+		let mut sections = vec![];
+		for (key, value) in entries {
+			let key = key_text(key)?;
+			match value {
+				Value::Map(section) => sections.push((key, section)),
+				value => push_entry(&mut text, None, &key, value)?,
+			}
+		}
+		for (name, entries) in sections {
+			if name.is_empty() || name.contains([']', '\r', '\n']) || name.trim() != name {
+				return Err(OutputError::InvalidSection(name.into_owned()).into());
+			}
+			if !text.is_empty() {
+				text.push('\n');
+			}
+			text.push_str(&format!("[{name}]\n"));
+			for (key, value) in entries {
+				let key = key_text(key)?;
+				push_entry(&mut text, Some(&name), &key, value)?;
+			}
+		}
+		self.w.write_all(text.as_bytes())?;
+		self.used = true;
+		Ok(())
+	}
+}
+
+fn key_text(key: Value) -> Result<Cow<str>, OutputError> {
+	let key = scalar_text(key).ok_or(OutputError::NonScalarKey)?;
+	let invalid = key.is_empty()
+		|| key.trim() != key
+		|| key.contains(['=', ':', '\r', '\n'])
+		|| key.starts_with(['[', ';', '#']);
+	if invalid {
+		return Err(OutputError::InvalidKey(key.into_owned()));
+	}
+	Ok(key)
+}
+
+/// Writes a line for a key and its value, within the named section if there is one.
+fn push_entry(
+	text: &mut String,
+	section: Option<&str>,
+	key: &str,
+	value: Value,
+) -> Result<(), OutputError> {
+	let path = || match section {
+		Some(section) => format!("{section}.{key}"),
+		None => key.into(),
+	};
+	let value = match value {
+		Value::Map(_) => return Err(OutputError::NestedSection(path())),
+		Value::Seq(_) | Value::Bytes(_) => return Err(OutputError::NonScalarValue(path())),
+		value => scalar_text(value).expect("scalars should have text"),
+	};
+	if value.contains(['\r', '\n']) {
+		return Err(OutputError::MultilineValue(path()));
+	}
+	// Quotes keep whitespace at the ends of a value, and keep quotes that would otherwise read
+	// as quoting the value.
+	let quoted = value.len() > 1 && value.starts_with('"') && value.ends_with('"');
+	if quoted || value.trim() != value {
+		text.push_str(&format!("{key} = \"{value}\"\n"));
+	} else if value.is_empty() {
+		text.push_str(&format!("{key} =\n"));
+	} else {
+		text.push_str(&format!("{key} = {value}\n"));
+	}
+	Ok(())
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.used {
+			return Err(OutputError::MultiDocument.into());
+		}
+		let value = Value::deserialize(de)?;
+		self.output_document(value)
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		self.output_document(value)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.flush()
+	}
+}
+
+/// The error for a document that INI can't represent.
+#[derive(Debug)]
+pub(crate) enum OutputError {
+	NonMapRoot,
+	MultiDocument,
+	NonScalarKey,
+	InvalidKey(String),
+	InvalidSection(String),
+	NestedSection(String),
+	NonScalarValue(String),
+	MultilineValue(String),
+}
+
+impl error::Error for OutputError {}
+
+impl fmt::Display for OutputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NonMapRoot => f.write_str("root of INI output must be a map"),
+			Self::MultiDocument => f.write_str("INI does not support multi-document output"),
+			Self::NonScalarKey => f.write_str("INI output requires scalar keys"),
+			Self::InvalidKey(key) => write!(f, "INI can't represent the key {key:?}"),
+			Self::InvalidSection(name) => {
+				write!(f, "INI can't represent the section name {name:?}")
+			}
+			Self::NestedSection(path) => write!(
+				f,
+				"map at {path:?} is within a section, but INI allows only one level of sections"
+			),
+			Self::NonScalarValue(path) => {
+				write!(
+					f,
+					"value at {path:?} is an array or bytes, which INI can't represent"
+				)
+			}
+			Self::MultilineValue(path) => {
+				write!(
+					f,
+					"value at {path:?} has a line break, which INI can't represent"
+				)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_sections_and_comments() {
+		const INPUT: &str = "\
+name = top
+; a comment
+[server]
+host: example.com
+port = 8080
+# another
+[empty]
+
+[server]
+motd = \"  hi  \"
+url = http://a/?b=c
+";
+		let value = parse(INPUT).unwrap();
+		assert_eq!(
+			serde_json::to_string(&value).unwrap(),
+			concat!(
+				r#"{"name":"top","server":{"host":"example.com","port":"8080","motd":"  hi  ","#,
+				r#""url":"http://a/?b=c"},"empty":{}}"#,
+			)
+		);
+
+		for (input, line) in [("[a\n", 1), ("a = 1\nnope\n", 2), ("= 1\n", 1)] {
+			let Err(err) = parse(input) else {
+				panic!("parsed invalid INI {input:?}");
+			};
+			assert_eq!(err.line, line, "{input:?}");
+		}
+	}
+}
@@ -31,8 +31,17 @@
 //! original reader with no wrapping beyond boxing as a trait object.
 
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::cmp;
-use std::io::{self, Cursor, Read, Write};
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::timeout;
 
 /// A reusable container for xt's input.
 ///
@@ -42,7 +51,7 @@ pub(crate) struct Handle<'i>(Source<'i>);
 /// The private container for xt's original input source.
 enum Source<'i> {
 	Slice(&'i [u8]),
-	Reader(GuardedCaptureReader<Box<dyn Read + 'i>>),
+	Reader(GuardedCaptureReader<Box<dyn InputSource + 'i>>),
 }
 
 impl<'i> Handle<'i> {
@@ -56,7 +65,87 @@ impl<'i> Handle<'i> {
 	where
 		R: Read + 'i,
 	{
-		Handle(Source::Reader(GuardedCaptureReader::new(Box::new(r))))
+		Handle(Source::Reader(GuardedCaptureReader::new(Box::new(Stream(
+			r,
+		)))))
+	}
+
+	/// Creates a handle for a seekable input reader.
+	///
+	/// Rather than capturing the reader's input for replay, the handle rewinds the reader by
+	/// seeking back to its position at the time of this call. The reader must produce the same
+	/// input every time it seeks back to that position.
+	pub(crate) fn from_seekable_reader<R>(mut r: R) -> io::Result<Handle<'i>>
+	where
+		R: Read + Seek + 'i,
+	{
+		let start = r.stream_position()?;
+		let r =
+			CaptureReader::with_seek_start(Box::new(Seekable(r)) as Box<dyn InputSource>, start);
+		Ok(Handle(Source::Reader(GuardedCaptureReader(r))))
+	}
+
+	/// Sets the memory budget for buffers of the input's contents.
+	///
+	/// Slice inputs are already in memory, and are not subject to the budget.
+	pub(crate) fn set_budget(&mut self, budget: &Budget) {
+		if let Source::Reader(r) = &mut self.0 {
+			r.0.charge = budget.charge();
+		}
+	}
+
+	/// Limits how much of the input format detection may capture, or removes the limit.
+	///
+	/// Capturing past the limit fails with an error that [`is_detection_limit`] identifies. Slice
+	/// inputs are already in memory, and are not subject to the limit.
+	pub(crate) fn set_detection_limit(&mut self, limit: Option<usize>) {
+		if let Source::Reader(r) = &mut self.0 {
+			r.0.detection_limit = limit;
+		}
+	}
+
+	/// Fails every read from the input's source around which the deadline passes.
+	///
+	/// Slice inputs are already in memory, and don't read from a source.
+	pub(crate) fn set_deadline(&mut self, deadline: timeout::Deadline) {
+		if let Source::Reader(r) = &mut self.0 {
+			let source = mem::replace(&mut r.0.source, Box::new(Stream(io::empty())));
+			r.0.source = Box::new(timeout::Reader::new(source, deadline));
+		}
+	}
+
+	/// Returns the memory budget for buffers of the input's contents.
+	pub(crate) fn budget(&self) -> Budget {
+		match &self.0 {
+			Source::Slice(_) => Budget::UNLIMITED,
+			Source::Reader(r) => r.0.budget(),
+		}
+	}
+
+	/// Returns a prefix of the input, following the same rules as [`Ref::prefix`].
+	pub(crate) fn prefix(&mut self, size_hint: usize) -> io::Result<&[u8]> {
+		match &mut self.0 {
+			Source::Slice(b) => Ok(b),
+			Source::Reader(r) => {
+				let r = r.rewind_and_borrow_mut();
+				r.capture_up_to_size(size_hint)?;
+				Ok(r.captured())
+			}
+		}
+	}
+
+	/// Discards the first `n` bytes of the input, as if it started after them.
+	pub(crate) fn skip(&mut self, n: u64) -> io::Result<()> {
+		match &mut self.0 {
+			Source::Slice(b) => {
+				*b = usize::try_from(n)
+					.ok()
+					.and_then(|n| b.get(n..))
+					.unwrap_or_default();
+				Ok(())
+			}
+			Source::Reader(r) => r.rewind_and_borrow_mut().skip(n),
+		}
 	}
 
 	/// Borrows a temporary reference to the input.
@@ -89,7 +178,7 @@ impl<'i> TryFrom<Handle<'i>> for Cow<'i, [u8]> {
 			Source::Reader(r) => {
 				let mut r = r.rewind_and_take();
 				r.capture_to_end()?;
-				let (cursor, _) = r.into_inner();
+				let (cursor, _) = r.into_inner()?;
 				Ok(Cow::Owned(cursor.into_inner()))
 			}
 		}
@@ -105,23 +194,24 @@ pub(crate) enum Input<'i> {
 	Reader(Box<dyn Read + 'i>),
 }
 
-impl<'i> From<Handle<'i>> for Input<'i> {
-	fn from(handle: Handle<'i>) -> Self {
-		match handle.0 {
-			Source::Slice(b) => Input::Slice(Cow::Borrowed(b)),
-			Source::Reader(r) => {
-				let r = r.rewind_and_take();
-				let source_eof = r.is_source_eof();
-				let (cursor, source) = r.into_inner();
-				if source_eof {
-					Input::Slice(Cow::Owned(cursor.into_inner()))
-				} else if cursor.get_ref().is_empty() {
-					Input::Reader(source)
-				} else {
-					Input::Reader(Box::new(FusedReader::new(cursor).chain(source)))
-				}
-			}
-		}
+impl<'i> TryFrom<Handle<'i>> for Input<'i> {
+	type Error = io::Error;
+
+	fn try_from(handle: Handle<'i>) -> io::Result<Input<'i>> {
+		let mut r = match handle.0 {
+			Source::Slice(b) => return Ok(Input::Slice(Cow::Borrowed(b))),
+			Source::Reader(r) => r.rewind_and_take(),
+		};
+		let source_eof = r.is_source_eof();
+		let charge = r.take_charge();
+		let (cursor, source) = r.into_inner()?;
+		Ok(if source_eof {
+			Input::Slice(Cow::Owned(cursor.into_inner()))
+		} else if cursor.get_ref().is_empty() {
+			Input::Reader(Box::new(source))
+		} else {
+			Input::Reader(Box::new(FusedReader::new(cursor, charge).chain(source)))
+		})
 	}
 }
 
@@ -131,7 +221,7 @@ where
 	'i: 'h,
 {
 	Slice(&'h [u8]),
-	Reader(&'h mut CaptureReader<Box<dyn Read + 'i>>),
+	Reader(&'h mut CaptureReader<Box<dyn InputSource + 'i>>),
 }
 
 impl<'i, 'h> Ref<'i, 'h>
@@ -158,11 +248,12 @@ where
 	}
 }
 
-/// A wrapper that drops a reader as soon as it first reaches EOF.
+/// A wrapper that drops a reader, along with its charge against the memory budget, as soon as it
+/// first reaches EOF.
 ///
 /// As the first half of a [`Chain`](std::io::Chain), this cleans up the first reader's resources
 /// as soon as the chain moves to the second reader, rather than when the whole `Chain` is dropped.
-struct FusedReader<R>(Option<R>)
+struct FusedReader<R>(Option<(R, Charge)>)
 where
 	R: Read;
 
@@ -170,8 +261,8 @@ impl<R> FusedReader<R>
 where
 	R: Read,
 {
-	fn new(r: R) -> FusedReader<R> {
-		FusedReader(Some(r))
+	fn new(r: R, charge: Charge) -> FusedReader<R> {
+		FusedReader(Some((r, charge)))
 	}
 }
 
@@ -182,7 +273,7 @@ where
 	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
 		let n = match &mut self.0 {
 			None => return Ok(0),
-			Some(r) => r.read(buf)?,
+			Some((r, _)) => r.read(buf)?,
 		};
 		if n == 0 && !buf.is_empty() {
 			self.0 = None;
@@ -191,15 +282,65 @@ where
 	}
 }
 
+/// A source of reader input, which may support seeking back to earlier input.
+///
+/// This is a trait rather than a plain [`Seek`] bound so that seekable and non-seekable sources
+/// can share a single trait object type.
+pub(crate) trait InputSource: Read {
+	/// Seeks to the provided offset from the start of the stream.
+	///
+	/// Sources that can't seek return an [`Unsupported`](io::ErrorKind::Unsupported) error.
+	fn seek_to(&mut self, offset: u64) -> io::Result<()>;
+}
+
+impl<S> InputSource for Box<S>
+where
+	S: InputSource + ?Sized,
+{
+	fn seek_to(&mut self, offset: u64) -> io::Result<()> {
+		(**self).seek_to(offset)
+	}
+}
+
+/// An [`InputSource`] for a reader that can't seek.
+pub(crate) struct Stream<R>(pub(crate) R);
+
+impl<R: Read> Read for Stream<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.0.read(buf)
+	}
+}
+
+impl<R: Read> InputSource for Stream<R> {
+	fn seek_to(&mut self, _: u64) -> io::Result<()> {
+		Err(io::ErrorKind::Unsupported.into())
+	}
+}
+
+/// An [`InputSource`] for a reader that can seek.
+pub(crate) struct Seekable<R>(pub(crate) R);
+
+impl<R: Read> Read for Seekable<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.0.read(buf)
+	}
+}
+
+impl<R: Read + Seek> InputSource for Seekable<R> {
+	fn seek_to(&mut self, offset: u64) -> io::Result<()> {
+		self.0.seek(SeekFrom::Start(offset)).map(|_| ())
+	}
+}
+
 /// A wrapper that forces a [`CaptureReader`] to be rewound prior to use, which eliminates a class
 /// of bugs xt has had in the past.
 struct GuardedCaptureReader<R>(CaptureReader<R>)
 where
-	R: Read;
+	R: InputSource;
 
 impl<R> GuardedCaptureReader<R>
 where
-	R: Read,
+	R: InputSource,
 {
 	fn new(r: R) -> Self {
 		Self(CaptureReader::new(r))
@@ -223,18 +364,35 @@ where
 ///
 /// A `CaptureReader` also tracks end-of-file conditions from the source, so consumers can switch
 /// to fully buffered input, analogous to [`Read::read_to_end`].
+///
+/// For a seekable source, a `CaptureReader` only captures the prefixes that consumers explicitly
+/// request. Plain reads go straight to the source, and rewinding seeks the source back to where it
+/// started, so that reading a large input during format detection doesn't also buffer it.
 pub(crate) struct CaptureReader<R>
 where
-	R: Read,
+	R: InputSource,
 {
 	prefix: Cursor<Vec<u8>>,
 	source: R,
 	source_eof: bool,
+	charge: Charge,
+	detection_limit: Option<usize>,
+	seek: Option<SeekState>,
+}
+
+/// The reading state of a [`CaptureReader`] with a seekable source.
+struct SeekState {
+	/// The source's offset at the start of the input.
+	start: u64,
+	/// The reader's position relative to the start of the input.
+	pos: u64,
+	/// Whether the source's actual offset matches the reader's position.
+	synced: bool,
 }
 
 impl<R> CaptureReader<R>
 where
-	R: Read,
+	R: InputSource,
 {
 	/// Creates a new reader that captures `source`.
 	fn new(source: R) -> Self {
@@ -242,6 +400,61 @@ where
 			prefix: Cursor::new(vec![]),
 			source,
 			source_eof: false,
+			charge: Budget::UNLIMITED.charge(),
+			detection_limit: None,
+			seek: None,
+		}
+	}
+
+	/// Creates a new reader that rewinds `source` by seeking to `start`, where `start` is the
+	/// source's current offset.
+	fn with_seek_start(source: R, start: u64) -> Self {
+		Self {
+			seek: Some(SeekState {
+				start,
+				pos: 0,
+				synced: true,
+			}),
+			..Self::new(source)
+		}
+	}
+
+	/// Returns the memory budget for the capture buffer.
+	pub(crate) fn budget(&self) -> Budget {
+		self.charge.budget().clone()
+	}
+
+	/// Takes the capture buffer's charge against the memory budget, for whatever holds the buffer
+	/// after the reader.
+	fn take_charge(&mut self) -> Charge {
+		mem::replace(&mut self.charge, Budget::UNLIMITED.charge())
+	}
+
+	/// Returns the limit on the capture buffer during format detection, if any.
+	pub(crate) fn detection_limit(&self) -> Option<usize> {
+		self.detection_limit
+	}
+
+	/// Returns the number of bytes that the capture buffer can grow by without exceeding the
+	/// memory budget or any detection limit, or `None` if neither one limits it.
+	fn capture_remaining(&self) -> Option<usize> {
+		let captured = self.prefix.get_ref().len();
+		let detection = self
+			.detection_limit
+			.map(|limit| limit.saturating_sub(captured));
+		match (self.charge.remaining(), detection) {
+			(Some(budget), Some(detection)) => Some(cmp::min(budget, detection)),
+			(budget, detection) => budget.or(detection),
+		}
+	}
+
+	/// Charges a capture buffer of the provided size against the memory budget, and returns an
+	/// error if it exceeds the detection limit or the budget.
+	fn check_capture(&mut self, size: usize) -> io::Result<()> {
+		let charged = self.charge.resize(size);
+		match self.detection_limit {
+			Some(limit) if size > limit => Err(detection_limit_error(limit)),
+			_ => charged,
 		}
 	}
 
@@ -261,12 +474,40 @@ where
 	/// from the source.
 	fn rewind(&mut self) {
 		self.prefix.set_position(0);
+		if let Some(seek) = &mut self.seek {
+			seek.pos = 0;
+		}
+	}
+
+	/// Discards the first `n` bytes of the input, including any captured bytes among them, as if
+	/// the source had started after them.
+	///
+	/// The reader must be rewound.
+	fn skip(&mut self, n: u64) -> io::Result<()> {
+		let captured = self.prefix.get_ref().len();
+		let drained = cmp::min(n, captured as u64);
+		self.prefix
+			.get_mut()
+			.drain(..cast_read_offset_usize(drained));
+		self.charge.shrink(self.prefix.get_ref().len());
+		let rest = n - drained;
+		match &mut self.seek {
+			Some(seek) => {
+				seek.start += n;
+				seek.synced = false;
+			}
+			None if rest > 0 && !self.source_eof => {
+				io::copy(&mut self.source.by_ref().take(rest), &mut io::sink())?;
+			}
+			None => {}
+		}
+		Ok(())
 	}
 
 	/// Captures all of the source's remaining input without modifying the reader's position.
 	fn capture_to_end(&mut self) -> io::Result<()> {
 		if !self.source_eof {
-			self.source.read_to_end(self.prefix.get_mut())?;
+			self.capture_from_source(u64::MAX)?;
 			self.source_eof = true;
 		}
 		Ok(())
@@ -283,30 +524,87 @@ where
 			return Ok(());
 		}
 
-		let mut take = self.source.by_ref().take(needed as u64);
-		take.read_to_end(self.prefix.get_mut())?;
-		if take.limit() > 0 {
+		if self.capture_from_source(needed as u64)? {
 			self.source_eof = true;
 		}
 		Ok(())
 	}
 
+	/// Captures up to `limit` more bytes from the source, returning true if the source reached EOF
+	/// before the limit.
+	///
+	/// This reads at most one byte past the memory budget or detection limit, and fails if the
+	/// capture buffer exceeds either one.
+	fn capture_from_source(&mut self, limit: u64) -> io::Result<bool> {
+		let captured = self.prefix.get_ref().len();
+		let limit = match self.capture_remaining() {
+			Some(remaining) => cmp::min(limit, (remaining as u64).saturating_add(1)),
+			None => limit,
+		};
+		if let Some(seek) = &mut self.seek {
+			self.source.seek_to(seek.start + captured as u64)?;
+			seek.synced = false;
+		}
+		let mut take = self.source.by_ref().take(limit);
+		take.read_to_end(self.prefix.get_mut())?;
+		let eof = take.limit() > 0;
+		self.check_capture(self.prefix.get_ref().len())?;
+		Ok(eof)
+	}
+
 	/// Returns true if the latest read from the source indicated an EOF.
 	fn is_source_eof(&self) -> bool {
 		self.source_eof
 	}
 
-	/// Returns any captured prefix along with the source reader.
-	fn into_inner(self) -> (Cursor<Vec<u8>>, R) {
-		(self.prefix, self.source)
+	/// Returns any captured prefix along with the source reader, positioned to produce the rest of
+	/// the input after the prefix.
+	fn into_inner(mut self) -> io::Result<(Cursor<Vec<u8>>, R)> {
+		if let Some(seek) = &self.seek {
+			let captured = self.prefix.get_ref().len();
+			self.source.seek_to(seek.start + captured as u64)?;
+		}
+		Ok((self.prefix, self.source))
+	}
+
+	/// Reads from a seekable source, serving captured bytes from memory but otherwise reading
+	/// directly from the source without capturing.
+	fn read_seekable(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let seek = self
+			.seek
+			.as_mut()
+			.expect("read_seekable should only be called with a seekable source");
+		let rest = self
+			.prefix
+			.get_ref()
+			.get(cast_read_offset_usize(seek.pos)..)
+			.unwrap_or_default();
+		if !rest.is_empty() {
+			let len = cmp::min(buf.len(), rest.len());
+			buf[..len].copy_from_slice(&rest[..len]);
+			seek.pos += len as u64;
+			return Ok(len);
+		}
+
+		if !seek.synced {
+			self.source.seek_to(seek.start + seek.pos)?;
+			seek.synced = true;
+		}
+		let len = self.source.read(buf)?;
+		seek.pos += len as u64;
+		Ok(len)
 	}
 }
 
 impl<R> Read for CaptureReader<R>
 where
-	R: Read,
+	R: InputSource,
 {
 	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.seek.is_some() {
+			return self.read_seekable(buf);
+		}
+
 		// First, copy as much data as we can from the unread portion of the cursor into the
 		// buffer.
 		let prefix_size = cmp::min(buf.len(), self.captured_unread_size());
@@ -323,7 +621,10 @@ where
 		// reads makes our presence more transparent to both sides. We try to be good citizens by
 		// only reading the parts of `buf` the source tells us were freshly written.
 		let buf = &mut buf[prefix_size..];
+		let buf_len = limit_read_len(self.capture_remaining(), buf.len());
+		let buf = &mut buf[..buf_len];
 		let source_size = self.source.read(buf)?;
+		self.check_capture(self.prefix.get_ref().len().saturating_add(source_size))?;
 		self.prefix.write_all(&buf[..source_size])?;
 
 		// Finally, mark whether the source is at EOF (keeping in mind that it can technically
@@ -336,6 +637,380 @@ where
 	}
 }
 
+/// A limit on the total size of the buffers that xt keeps of a translator's reader inputs.
+///
+/// The budget covers the capture buffer for format detection, the full buffering of input for
+/// formats that can't stream from a reader, and the per-document buffers of formats that stream by
+/// splitting the input into documents. Each buffer holds a [`Charge`] for its size, and every
+/// clone of a budget shares the same total, so a buffer can only grow into what the others leave.
+#[derive(Clone)]
+pub(crate) struct Budget(Option<Arc<Shared>>);
+
+/// The limit and running total that the clones of a limited [`Budget`] share.
+struct Shared {
+	limit: usize,
+	total: AtomicUsize,
+}
+
+impl Budget {
+	/// A budget that never limits buffer sizes.
+	pub(crate) const UNLIMITED: Budget = Budget(None);
+
+	/// Creates a budget with the provided limit in bytes, or an unlimited budget.
+	pub(crate) fn new(limit: Option<usize>) -> Budget {
+		Budget(limit.map(|limit| {
+			Arc::new(Shared {
+				limit,
+				total: AtomicUsize::new(0),
+			})
+		}))
+	}
+
+	/// Starts a charge for a new, empty buffer against the budget.
+	pub(crate) fn charge(&self) -> Charge {
+		Charge {
+			budget: self.clone(),
+			size: 0,
+		}
+	}
+}
+
+/// The size of one buffer as charged against a [`Budget`], which returns to the budget when the
+/// charge is dropped.
+pub(crate) struct Charge {
+	budget: Budget,
+	size: usize,
+}
+
+impl Charge {
+	/// Returns the budget that the charge counts against.
+	pub(crate) fn budget(&self) -> &Budget {
+		&self.budget
+	}
+
+	/// Returns the number of bytes that the buffer can grow by without exceeding the budget, or
+	/// `None` if the budget is unlimited.
+	fn remaining(&self) -> Option<usize> {
+		let shared = self.budget.0.as_ref()?;
+		Some(
+			shared
+				.limit
+				.saturating_sub(shared.total.load(Ordering::Relaxed)),
+		)
+	}
+
+	/// Limits the length of a read into the buffer, so that it can exceed the budget by at most
+	/// one byte.
+	///
+	/// Reading one byte past the budget makes it possible to tell whether the source would
+	/// actually exceed the budget, rather than just filling it exactly.
+	pub(crate) fn limit_read_len(&self, len: usize) -> usize {
+		limit_read_len(self.remaining(), len)
+	}
+
+	/// Charges the buffer at a new size, and returns an error if growing it to that size exceeds
+	/// the budget.
+	///
+	/// The charge takes the new size even on an error, since the buffer already holds it.
+	pub(crate) fn resize(&mut self, size: usize) -> io::Result<()> {
+		if size <= self.size {
+			self.shrink(size);
+			return Ok(());
+		}
+		let Some(shared) = &self.budget.0 else {
+			return Ok(());
+		};
+		let old = mem::replace(&mut self.size, size);
+		let total = shared.total.fetch_add(size - old, Ordering::Relaxed) + (size - old);
+		match total > shared.limit {
+			true => Err(io::Error::new(
+				io::ErrorKind::OutOfMemory,
+				BudgetExceededError(shared.limit),
+			)),
+			false => Ok(()),
+		}
+	}
+
+	/// Charges the buffer at a new size no larger than its current one.
+	pub(crate) fn shrink(&mut self, size: usize) {
+		let old = mem::replace(&mut self.size, size);
+		if let Some(shared) = &self.budget.0 {
+			shared.total.fetch_sub(old - size, Ordering::Relaxed);
+		}
+	}
+}
+
+impl Drop for Charge {
+	fn drop(&mut self) {
+		if let Some(shared) = &self.budget.0 {
+			shared.total.fetch_sub(self.size, Ordering::Relaxed);
+		}
+	}
+}
+
+/// Limits the length of a read so that it can exceed the `remaining` room in a buffer by at most
+/// one byte.
+fn limit_read_len(remaining: Option<usize>, len: usize) -> usize {
+	match remaining {
+		Some(remaining) => cmp::min(len, remaining.saturating_add(1)),
+		None => len,
+	}
+}
+
+/// The error produced when buffering input would exceed the memory budget.
+#[derive(Debug)]
+struct BudgetExceededError(usize);
+
+impl Error for BudgetExceededError {}
+
+impl Display for BudgetExceededError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"buffering input would exceed the memory budget of {} bytes",
+			self.0
+		)
+	}
+}
+
+/// The error produced when format detection needs more input than its limit.
+#[derive(Debug)]
+struct DetectionLimitError(usize);
+
+impl Error for DetectionLimitError {}
+
+impl Display for DetectionLimitError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "input exceeds the detection limit of {} bytes", self.0)
+	}
+}
+
+/// Returns the error for format detection needing more input than its limit.
+pub(crate) fn detection_limit_error(limit: usize) -> io::Error {
+	io::Error::other(DetectionLimitError(limit))
+}
+
+/// Returns true if an error is from format detection needing more input than its limit.
+pub(crate) fn is_detection_limit(err: &io::Error) -> bool {
+	err.get_ref()
+		.is_some_and(|err| err.is::<DetectionLimitError>())
+}
+
+/// The error produced when a document is larger than the maximum document size.
+///
+/// The size is known for documents that the input format measures before parsing them, and
+/// unknown for documents that stop at the limit while streaming from a reader.
+#[derive(Debug)]
+pub(crate) struct DocumentSizeError {
+	pub(crate) size: Option<u64>,
+	pub(crate) max: u64,
+}
+
+impl Error for DocumentSizeError {}
+
+impl Display for DocumentSizeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.size {
+			Some(size) => write!(
+				f,
+				"document of {size} bytes exceeds the maximum document size of {} bytes",
+				self.max
+			),
+			None => write!(
+				f,
+				"document exceeds the maximum document size of {} bytes",
+				self.max
+			),
+		}
+	}
+}
+
+/// A reader that counts the bytes read through it, for consumers that need to know their position
+/// in an input after handing ownership of the reader to a deserializer.
+///
+/// The reader also enforces a [`DocumentLimit`] for formats that stream documents from it.
+pub(crate) struct CountingReader<R> {
+	inner: R,
+	count: Rc<Cell<u64>>,
+	limit: Rc<DocumentLimit>,
+}
+
+impl<R: Read> CountingReader<R> {
+	/// Wraps a reader, returning the wrapper along with handles to its running count and to a
+	/// limit of `max` bytes per document, if any.
+	pub(crate) fn new(
+		inner: R,
+		max: Option<u64>,
+	) -> (CountingReader<R>, Rc<Cell<u64>>, Rc<DocumentLimit>) {
+		let count = Rc::new(Cell::new(0));
+		let limit = Rc::new(DocumentLimit {
+			max,
+			end: Cell::new(u64::MAX),
+			reached: Cell::new(false),
+			last_byte: Cell::new(0),
+		});
+		let reader = CountingReader {
+			inner,
+			count: Rc::clone(&count),
+			limit: Rc::clone(&limit),
+		};
+		(reader, count, limit)
+	}
+}
+
+impl<R: Read> Read for CountingReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let allowed = self.limit.end.get().saturating_sub(self.count.get());
+		if let (Some(max), 0) = (self.limit.max, allowed) {
+			if !buf.is_empty() {
+				self.limit.reached.set(true);
+				return Err(io::Error::other(DocumentSizeError { size: None, max }));
+			}
+		}
+		let allowed = usize::try_from(allowed).map_or(buf.len(), |n| cmp::min(n, buf.len()));
+		let len = self.inner.read(&mut buf[..allowed])?;
+		self.count.set(self.count.get() + len as u64);
+		if let Some(&byte) = buf[..len].last() {
+			self.limit.last_byte.set(byte);
+		}
+		Ok(len)
+	}
+}
+
+/// A limit on how far a [`CountingReader`] lets an input format read past the start of each
+/// document, for formats that can't know how large a document is until they finish parsing it.
+///
+/// Reads never go past the limit, and a read right at the limit fails, so that a format fails on
+/// any document that needs more input than the limit allows. The format passes that failure
+/// through [`check`](DocumentLimit::check) to report it as a [`DocumentSizeError`], whatever its
+/// deserializer made of the read error.
+pub(crate) struct DocumentLimit {
+	max: Option<u64>,
+	end: Cell<u64>,
+	reached: Cell<bool>,
+	last_byte: Cell<u8>,
+}
+
+impl DocumentLimit {
+	/// Limits reads to the maximum size past the provided position, where the next document
+	/// starts.
+	pub(crate) fn start(&self, offset: u64) {
+		let end = self.max.map_or(u64::MAX, |max| offset.saturating_add(max));
+		self.end.set(end);
+		self.reached.set(false);
+	}
+
+	/// Lifts the limit until the start of the next document, so that the format can look for the
+	/// start of that document.
+	pub(crate) fn lift(&self) {
+		self.end.set(u64::MAX);
+	}
+
+	/// Returns the last byte read through the reader, or 0 before the first read.
+	pub(crate) fn last_byte(&self) -> u8 {
+		self.last_byte.get()
+	}
+
+	/// Replaces an error from the format with a [`DocumentSizeError`] if a read reached the limit
+	/// for the current document.
+	pub(crate) fn check(&self, err: crate::Error) -> crate::Error {
+		match self.max {
+			Some(max) if self.reached.get() => err.replace(DocumentSizeError { size: None, max }),
+			_ => err,
+		}
+	}
+}
+
+/// A reader that reads each of a sequence of readers to the end in turn, recording where each one
+/// starts in the logical stream that they form together.
+pub(crate) struct ChainReader<I>
+where
+	I: Iterator,
+{
+	readers: I,
+	current: Option<I::Item>,
+	position: u64,
+	boundaries: Rc<Boundaries>,
+}
+
+impl<I> ChainReader<I>
+where
+	I: Iterator,
+	I::Item: Read,
+{
+	/// Chains a sequence of readers, returning the chain along with a handle to the boundaries
+	/// between them.
+	pub(crate) fn new<T>(readers: T) -> (ChainReader<I>, Rc<Boundaries>)
+	where
+		T: IntoIterator<IntoIter = I>,
+	{
+		let mut readers = readers.into_iter();
+		let boundaries = Rc::new(Boundaries {
+			starts: RefCell::new(vec![0]),
+			origin: Cell::new(0),
+		});
+		let reader = ChainReader {
+			current: readers.next(),
+			readers,
+			position: 0,
+			boundaries: Rc::clone(&boundaries),
+		};
+		(reader, boundaries)
+	}
+
+	/// Marks the current position as the start of the chain's translatable input, so that
+	/// offsets within the input are relative to it.
+	pub(crate) fn mark_origin(&self) {
+		self.boundaries.origin.set(self.position);
+	}
+}
+
+impl<I> Read for ChainReader<I>
+where
+	I: Iterator,
+	I::Item: Read,
+{
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		loop {
+			let Some(r) = &mut self.current else {
+				return Ok(0);
+			};
+			let n = r.read(buf)?;
+			if n > 0 || buf.is_empty() {
+				self.position += n as u64;
+				return Ok(n);
+			}
+			self.current = self.readers.next();
+			if self.current.is_some() {
+				self.boundaries.starts.borrow_mut().push(self.position);
+			}
+		}
+	}
+}
+
+/// The boundaries between the readers of a [`ChainReader`].
+pub(crate) struct Boundaries {
+	/// The position in the chain where each reader reached so far starts.
+	starts: RefCell<Vec<u64>>,
+	/// The position in the chain that offsets within its input are relative to.
+	origin: Cell<u64>,
+}
+
+impl Boundaries {
+	/// Returns the zero-based index of the reader containing an offset within the chain's input,
+	/// along with the offset relative to the start of that reader.
+	///
+	/// For the reader containing the chain's origin, the offset is relative to the origin instead.
+	/// An offset at the boundary of one or more empty readers belongs to the last of them.
+	pub(crate) fn locate(&self, offset: u64) -> (usize, u64) {
+		let origin = self.origin.get();
+		let position = origin + offset;
+		let starts = self.starts.borrow();
+		let index = starts.partition_point(|&start| start <= position) - 1;
+		(index, position - cmp::max(starts[index], origin))
+	}
+}
+
 /// Cast the offset of a memory-based [`io::Read`] to a [`usize`].
 ///
 /// While `Read` APIs present offsets as `u64`s, any offset into a reader over an in-memory slice
@@ -356,9 +1031,9 @@ pub(crate) fn cast_read_offset_usize(n: u64) -> usize {
 
 #[cfg(test)]
 mod tests {
-	use super::{CaptureReader, Handle, Input, Ref};
+	use super::{CaptureReader, ChainReader, Handle, Input, Ref, Seekable, Stream};
 	use std::borrow::Cow;
-	use std::io::{self, Cursor, Read};
+	use std::io::{self, Cursor, Read, Seek, SeekFrom};
 
 	const DATA: &str = "abcdefghij";
 	const HALF: usize = DATA.len() / 2;
@@ -390,7 +1065,7 @@ mod tests {
 
 		// If we only consume part of a borrowed reader, we need to reset the reader before giving
 		// ownership away.
-		let mut r = match handle.into() {
+		let mut r = match handle.try_into().unwrap() {
 			Input::Slice(_) => unreachable!(),
 			Input::Reader(r) => r,
 		};
@@ -415,18 +1090,18 @@ mod tests {
 
 	#[test]
 	fn capture_reader_straight_read() {
-		let mut r = CaptureReader::new(Cursor::new(String::from(DATA)));
+		let mut r = CaptureReader::new(Stream(Cursor::new(String::from(DATA))));
 
 		assert_eq!(io::read_to_string(&mut r).unwrap(), DATA);
 		assert!(r.is_source_eof());
 
-		let (cursor, _) = r.into_inner();
+		let (cursor, _) = r.into_inner().unwrap();
 		assert!(matches!(std::str::from_utf8(cursor.get_ref()), Ok(DATA)));
 	}
 
 	#[test]
 	fn capture_reader_rewind() {
-		let mut r = CaptureReader::new(Cursor::new(String::from(DATA)));
+		let mut r = CaptureReader::new(Stream(Cursor::new(String::from(DATA))));
 
 		let mut tmp = [0; HALF];
 		assert!(matches!(r.read_exact(&mut tmp), Ok(())));
@@ -443,7 +1118,7 @@ mod tests {
 
 	#[test]
 	fn capture_reader_to_end() {
-		let mut r = CaptureReader::new(Cursor::new(String::from(DATA)));
+		let mut r = CaptureReader::new(Stream(Cursor::new(String::from(DATA))));
 		assert!(r.capture_to_end().is_ok());
 		assert_eq!(std::str::from_utf8(r.captured()), Ok(DATA));
 		assert!(r.is_source_eof());
@@ -451,9 +1126,90 @@ mod tests {
 
 	#[test]
 	fn capture_reader_up_to() {
-		let mut r = CaptureReader::new(Cursor::new(String::from(DATA)));
+		let mut r = CaptureReader::new(Stream(Cursor::new(String::from(DATA))));
 		assert!(r.capture_up_to_size(HALF).is_ok());
 		assert_eq!(std::str::from_utf8(r.captured()), Ok(&DATA[..HALF]));
 		assert!(!r.is_source_eof());
 	}
+
+	#[test]
+	fn seekable_handle_rewind() {
+		let mut source = Cursor::new(format!("--{DATA}"));
+		source.seek(SeekFrom::Start(2)).unwrap();
+		let mut handle = Handle::from_seekable_reader(source).unwrap();
+
+		let prefix = handle.borrow_mut().prefix(HALF).unwrap().to_vec();
+		assert_eq!(prefix, &DATA.as_bytes()[..HALF]);
+
+		// Reading past the captured prefix should seek rather than capture, and rewinding should
+		// return to where the source started rather than to its beginning.
+		match handle.borrow_mut() {
+			Ref::Slice(_) => unreachable!(),
+			Ref::Reader(r) => {
+				assert_eq!(io::read_to_string(&mut *r).unwrap(), DATA);
+				assert_eq!(r.captured(), &DATA.as_bytes()[..HALF]);
+			}
+		};
+
+		let mut r = match handle.try_into().unwrap() {
+			Input::Slice(_) => unreachable!(),
+			Input::Reader(r) => r,
+		};
+		assert_eq!(io::read_to_string(&mut r).unwrap(), DATA);
+	}
+
+	#[test]
+	fn seekable_capture_reader_to_end() {
+		let source = Seekable(Cursor::new(String::from(DATA)));
+		let mut r = CaptureReader::with_seek_start(source, 0);
+
+		let mut tmp = [0; HALF];
+		assert!(matches!(r.read_exact(&mut tmp), Ok(())));
+		assert!(r.captured().is_empty());
+
+		assert!(r.capture_to_end().is_ok());
+		assert_eq!(std::str::from_utf8(r.captured()), Ok(DATA));
+		assert!(r.is_source_eof());
+	}
+
+	#[test]
+	fn skip_after_capture() {
+		let mut handle = Handle::from_reader(DATA.as_bytes());
+		assert_eq!(
+			handle.borrow_mut().prefix(2).unwrap(),
+			&DATA.as_bytes()[..2]
+		);
+		handle.skip(HALF as u64).unwrap();
+		assert_eq!(
+			handle.borrow_mut().prefix(1).unwrap(),
+			&DATA.as_bytes()[HALF..=HALF]
+		);
+
+		let mut source = Cursor::new(DATA);
+		let mut handle = Handle::from_seekable_reader(&mut source).unwrap();
+		assert_eq!(
+			handle.borrow_mut().prefix(2).unwrap(),
+			&DATA.as_bytes()[..2]
+		);
+		handle.skip(1).unwrap();
+		let mut r = match handle.try_into().unwrap() {
+			Input::Slice(_) => unreachable!(),
+			Input::Reader(r) => r,
+		};
+		assert_eq!(io::read_to_string(&mut r).unwrap(), &DATA[1..]);
+	}
+
+	#[test]
+	fn chain_reader_boundaries() {
+		let readers: [&[u8]; 4] = [b"abc", b"", b"de", b"f"];
+		let (mut r, boundaries) = ChainReader::new(readers);
+		let mut skipped = [0; 1];
+		r.read_exact(&mut skipped).unwrap();
+		r.mark_origin();
+		assert_eq!(io::read_to_string(&mut r).unwrap(), "bcdef");
+		assert_eq!(boundaries.locate(0), (0, 0));
+		assert_eq!(boundaries.locate(1), (0, 1));
+		assert_eq!(boundaries.locate(2), (2, 0));
+		assert_eq!(boundaries.locate(4), (3, 0));
+	}
 }
@@ -0,0 +1,140 @@
+//! Interning of map keys in documents held in memory.
+//!
+//! Record streams tend to repeat the same map keys in every document, so a translator that holds
+//! many documents in memory at once (for a tail, grouping, merging, or parallel serialization) can
+//! spend much of that memory on copies of the same few strings. With interning enabled, held
+//! documents take their key strings from a pool shared by every translator in the process, so
+//! that each distinct key takes a single allocation.
+//!
+//! Pooled keys live for the rest of the process. To bound that memory, the pool only takes keys
+//! of up to [`MAX_KEY_LEN`] bytes, and stops taking new keys once they add up to
+//! [`MAX_POOL_BYTES`]. Keys that don't fit are copied into each document as usual.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+
+use crate::transcode::Value;
+
+/// The longest key that the pool takes, in bytes.
+const MAX_KEY_LEN: usize = 128;
+
+/// The total size of the keys that the pool takes before it stops taking new ones, in bytes.
+const MAX_POOL_BYTES: usize = 1024 * 1024;
+
+struct Pool {
+	keys: HashSet<&'static str>,
+	bytes: usize,
+}
+
+static POOL: LazyLock<Mutex<Pool>> = LazyLock::new(|| {
+	Mutex::new(Pool {
+		keys: HashSet::new(),
+		bytes: 0,
+	})
+});
+
+thread_local! {
+	/// The keys that this thread has already found in the pool, to skip locking it for them.
+	static FOUND: RefCell<HashSet<&'static str>> = RefCell::new(HashSet::new());
+}
+
+/// Converts a value into one that owns all of its data like [`Value::into_owned`], except that
+/// with `enabled`, map keys come from the pool wherever they fit.
+pub(crate) fn into_owned(value: Value, enabled: bool) -> Value<'static> {
+	if !enabled {
+		return value.into_owned();
+	}
+	match value {
+		Value::Seq(seq) => Value::Seq(seq.into_iter().map(|v| into_owned(v, true)).collect()),
+		Value::Map(map) => Value::Map(
+			map.into_iter()
+				.map(|(k, v)| (into_key(k), into_owned(v, true)))
+				.collect(),
+		),
+		value => value.into_owned(),
+	}
+}
+
+fn into_key(key: Value) -> Value<'static> {
+	match key {
+		Value::String(key) => match intern(&key) {
+			Some(key) => Value::String(Cow::Borrowed(key)),
+			None => Value::String(Cow::Owned(key.into_owned())),
+		},
+		key => into_owned(key, true),
+	}
+}
+
+/// Returns the pooled copy of a key, or `None` if the key doesn't fit in the pool.
+fn intern(key: &str) -> Option<&'static str> {
+	if key.len() > MAX_KEY_LEN {
+		return None;
+	}
+	if let Some(key) = FOUND.with_borrow(|found| found.get(key).copied()) {
+		return Some(key);
+	}
+	let key = {
+		let mut pool = POOL.lock().expect("key pool should not be poisoned");
+		match pool.keys.get(key) {
+			Some(&key) => key,
+			None if pool.bytes + key.len() > MAX_POOL_BYTES => return None,
+			None => {
+				let key: &'static str = Box::leak(key.into());
+				pool.bytes += key.len();
+				pool.keys.insert(key);
+				key
+			}
+		}
+	};
+	FOUND.with_borrow_mut(|found| found.insert(key));
+	Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::ptr;
+
+	use super::*;
+
+	/// Collects the string keys of every map within a value.
+	fn keys<'v>(value: &'v Value<'static>, keys_out: &mut Vec<&'v Cow<'static, str>>) {
+		if let Value::Map(map) = value {
+			for (k, v) in map {
+				if let Value::String(k) = k {
+					keys_out.push(k);
+				}
+				keys(v, keys_out);
+			}
+		}
+	}
+
+	#[test]
+	fn intern_map_keys() {
+		let string = |s: &str| Value::String(Cow::Owned(s.into()));
+		let long = "x".repeat(MAX_KEY_LEN + 1);
+		let document = || {
+			Value::Map(vec![
+				(string("a"), Value::Map(vec![(string("b"), Value::Unit)])),
+				(string(&long), Value::Unit),
+			])
+		};
+
+		let (first, second) = (into_owned(document(), true), into_owned(document(), true));
+		assert!(first == document());
+		let (mut first_keys, mut second_keys) = (vec![], vec![]);
+		keys(&first, &mut first_keys);
+		keys(&second, &mut second_keys);
+		for (first, second) in first_keys.iter().zip(&second_keys).take(2) {
+			assert!(matches!(first, Cow::Borrowed(_)), "{first}");
+			assert!(ptr::eq(first.as_ref(), second.as_ref()), "{first}");
+		}
+		assert!(matches!(first_keys[2], Cow::Owned(_)));
+
+		let mut owned_keys = vec![];
+		let owned = into_owned(document(), false);
+		keys(&owned, &mut owned_keys);
+		assert!(owned_keys.iter().all(|key| matches!(key, Cow::Owned(_))));
+	}
+}
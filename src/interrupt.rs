@@ -0,0 +1,104 @@
+//! Clean interruption of the xt binary with Ctrl-C.
+//!
+//! By default, SIGINT terminates xt wherever it happens to be, which can leave half of a document
+//! at the end of its output. Commands that write output [`install`] a handler that only sets a
+//! flag instead. Translators stop between documents once the flag is set, a [`Reader`] fails any
+//! read that the signal interrupts so that slow inputs stop too, and the command exits with
+//! [`EXIT_CODE`] after finishing the output of every complete document. A second SIGINT
+//! terminates xt right away, in case something keeps the first one from taking effect.
+//!
+//! Other platforms keep the default behavior.
+
+use std::io::{self, Read};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// The exit code of an interrupted process, by the shell convention of 128 plus the signal
+/// number.
+const EXIT_CODE: i32 = 130;
+
+/// The flag set by the first SIGINT, once [`install`] has created it.
+static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Returns the flag that the first SIGINT sets, for translators to stop on.
+pub(crate) fn flag() -> Arc<AtomicBool> {
+	Arc::clone(FLAG.get_or_init(Arc::default))
+}
+
+/// Returns true if xt has been interrupted.
+pub(crate) fn requested() -> bool {
+	FLAG.get().is_some_and(|flag| flag.load(Ordering::Relaxed))
+}
+
+/// Terminates the current process with [`EXIT_CODE`] if xt has been interrupted.
+pub(crate) fn exit_if_requested() {
+	if requested() {
+		process::exit(EXIT_CODE);
+	}
+}
+
+/// Replaces the default handling of SIGINT with the handling described for this module, unless
+/// xt started with SIGINT ignored (as for a background job in a non-interactive shell).
+pub(crate) fn install() {
+	let _ = flag();
+	#[cfg(unix)]
+	unix::install();
+}
+
+/// A reader that fails once xt has been interrupted.
+pub(crate) struct Reader<R>(pub(crate) R);
+
+impl<R: Read> Read for Reader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if !requested() {
+			match self.0.read(buf) {
+				Err(err) if err.kind() == io::ErrorKind::Interrupted && requested() => {}
+				result => return result,
+			}
+		}
+		Err(io::Error::other("interrupted"))
+	}
+}
+
+#[cfg(unix)]
+mod unix {
+	use std::mem;
+	use std::ptr;
+	use std::sync::atomic::Ordering;
+
+	use super::FLAG;
+
+	pub(super) fn install() {
+		// SAFETY: sigaction is a plain C struct, for which all zeroes is a valid value.
+		let mut action: libc::sigaction = unsafe { mem::zeroed() };
+		// SAFETY: A null pointer for the new action only queries the current one.
+		if unsafe { libc::sigaction(libc::SIGINT, ptr::null(), &raw mut action) } != 0
+			|| action.sa_sigaction == libc::SIG_IGN
+		{
+			return;
+		}
+		// No SA_RESTART, so that the signal interrupts blocking reads instead of resuming them.
+		action.sa_sigaction = handle as *const () as libc::sighandler_t;
+		action.sa_flags = 0;
+		// SAFETY: The mask is a valid sigset_t within the action.
+		unsafe { libc::sigemptyset(&raw mut action.sa_mask) };
+		// SAFETY: The handler only touches atomics and async-signal-safe functions. If this
+		// fails, SIGINT keeps its default behavior.
+		unsafe { libc::sigaction(libc::SIGINT, &raw const action, ptr::null_mut()) };
+	}
+
+	extern "C" fn handle(_: libc::c_int) {
+		let first = FLAG
+			.get()
+			.is_some_and(|flag| !flag.swap(true, Ordering::Relaxed));
+		if !first {
+			// SAFETY: signal and raise are async-signal-safe, and restoring the default action
+			// before raising the signal again terminates the process as SIGINT normally would.
+			unsafe {
+				libc::signal(libc::SIGINT, libc::SIG_DFL);
+				libc::raise(libc::SIGINT);
+			}
+		}
+	}
+}
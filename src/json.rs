@@ -1,25 +1,42 @@
 //! The JSON data format.
 
+use std::borrow::Cow;
+use std::fmt;
 use std::io::{self, BufReader, Read, Write};
+use std::iter;
 use std::str;
 
-use serde::{Deserialize, de, ser};
+use serde::{Deserialize, Serialize, de};
+use serde_json::ser::PrettyFormatter;
 
-use crate::input::{self, Input, Ref};
+use crate::detect::Trial;
+use crate::input::{self, CountingReader, Input, Ref};
+use crate::msgpack;
 use crate::transcode;
 
-pub(crate) fn input_matches(mut input: Ref) -> io::Result<bool> {
+mod direct;
+mod separators;
+
+pub(crate) use self::direct::DEPTH_LIMIT;
+
+/// Trial parses an input as JSON Lines, which detection leaves to JSON itself.
+pub(crate) fn lines_input_matches(_: Ref) -> io::Result<Trial> {
+	// Every JSON Lines input is also JSON, and translates the same way.
+	Ok(Err("JSON Lines is detected as JSON".into()))
+}
+
+pub(crate) fn input_matches(mut input: Ref) -> io::Result<Trial> {
 	let result = match &mut input {
 		Ref::Reader(r) => match_input_reader(r),
 		Ref::Slice(b) => match str::from_utf8(b) {
 			Ok(s) => match_input_str(s),
-			Err(_) => return Ok(false),
+			Err(err) => return Ok(Err(err.to_string())),
 		},
 	};
 	match result {
 		Err(err) if err.is_io() => Err(err.into()),
-		Err(_) => Ok(false),
-		Ok(()) => Ok(true),
+		Err(err) => Ok(Err(err.to_string())),
+		Ok(()) => Ok(Ok(())),
 	}
 }
 
@@ -33,11 +50,41 @@ fn match_input_reader<R: Read>(input: R) -> Result<(), serde_json::Error> {
 	de::IgnoredAny::deserialize(&mut de).and(Ok(()))
 }
 
-pub(crate) fn transcode<O>(input: input::Handle, mut output: O) -> crate::Result<()>
+/// Transcodes JSON input to the output.
+///
+/// With `split_arrays`, each element of a top-level array is translated as a separate document.
+/// Documents split from arrays, and any other documents from an input with array splitting
+/// enabled, don't report their offsets to the output.
+///
+/// With `lenient`, documents may also be separated by commas and by comment lines starting with
+/// `//` or `#`.
+///
+/// With `direct`, each document from a slice goes to the output already encoded as MessagePack,
+/// through [`transcode_msgpack`](crate::Output::transcode_msgpack), until the first document that
+/// only serde can translate. Direct translation is off with `split_arrays`.
+///
+/// With `max_size`, translation from a reader fails on the first document that needs to read more
+/// than that many bytes, as soon as it reads that far. Documents split from arrays aren't limited.
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	mut output: O,
+	split_arrays: bool,
+	lenient: bool,
+	direct: bool,
+	max_size: Option<u64>,
+) -> crate::Result<()>
 where
-	O: crate::Output,
+	O: crate::Output<'i>,
 {
-	match input.into() {
+	let input = match Input::try_from(input)? {
+		Input::Slice(b) if lenient => match separators::blank_slice(&b) {
+			Some(blanked) => Input::Slice(Cow::Owned(blanked)),
+			None => Input::Slice(b),
+		},
+		Input::Reader(r) if lenient => Input::Reader(Box::new(separators::Reader::new(r))),
+		input => input,
+	};
+	match input {
 		Input::Slice(b) => {
 			// Direct transcoding would be nice, but the .end() method is unusually slow in slice
 			// mode. serde_json only supports iteration if we let it deserialize into a value,
@@ -49,53 +96,308 @@ where
 			// validity as it parses a byte slice. The drawback is that UTF-8 encoding errors only
 			// provide a byte position rather than line and column information. I consider the
 			// performance improvement worth that drawback.
-			let de = serde_json::Deserializer::from_str(str::from_utf8(&b)?);
-			for value in de.into_iter::<transcode::Value>() {
-				output.transcode_value(value?)?;
+			//
+			// When the slice comes straight from the caller, the values can keep borrowing from it
+			// for as long as the output needs. Otherwise, they only live as long as our buffer.
+			match b {
+				Cow::Borrowed(b) => {
+					let input = str::from_utf8(b)?;
+					if split_arrays {
+						return split_str(input, |v| output.transcode_borrowed_value(v));
+					}
+					let base = match direct {
+						true => transcode_direct(input, &mut output)?,
+						false => 0,
+					};
+					for value in values_from_str(&input[base..]) {
+						let (offset, end, value) = value?;
+						output.set_document_offset(base as u64 + offset);
+						output.set_document_end(base as u64 + end);
+						output.transcode_borrowed_value(value)?;
+					}
+				}
+				Cow::Owned(b) => {
+					let input = str::from_utf8(&b)?;
+					if split_arrays {
+						return split_str(input, |v| output.transcode_value(v));
+					}
+					let base = match direct {
+						true => transcode_direct(input, &mut output)?,
+						false => 0,
+					};
+					for value in values_from_str(&input[base..]) {
+						let (offset, end, value) = value?;
+						output.set_document_offset(base as u64 + offset);
+						output.set_document_end(base as u64 + end);
+						output.transcode_value(value)?;
+					}
+				}
 			}
 		}
 		Input::Reader(r) => {
 			// Direct transcoding here performs better than deserializing into a value, probably
 			// because transcode::Value is forced to copy every string from a &str reference.
-			let mut de = serde_json::Deserializer::from_reader(BufReader::new(r));
+			let (r, count, limit) = CountingReader::new(BufReader::new(r), max_size);
+			let mut de = serde_json::Deserializer::from_reader(r);
 			while de.end().is_err() {
-				output.transcode_from(&mut de)?;
+				if split_arrays {
+					transcode_split(&mut de, |v| output.transcode_value(v))?;
+				} else {
+					// serde_json reads one byte at a time, and end() leaves exactly one byte of the
+					// next value peeked.
+					let offset = count.get() - 1;
+					output.set_document_offset(offset);
+					// A number only ends at the first byte that can't continue it, so its limit
+					// leaves room to peek at that byte too.
+					let lookahead = matches!(limit.last_byte(), b'-' | b'0'..=b'9');
+					limit.start(offset + u64::from(lookahead));
+					output
+						.transcode_from(&mut de)
+						.map_err(|err| limit.check(err))?;
+					limit.lift();
+					// ...and peeks at that byte unless the number ends the input.
+					let peeked = lookahead && !is_number_byte(limit.last_byte());
+					output.set_last_document_end(count.get() - u64::from(peeked));
+				}
 			}
 		}
 	}
 	Ok(())
 }
 
-pub(crate) struct Output<W: Write>(W);
+/// Transcodes the documents of a JSON string to the output as MessagePack, returning the offset
+/// of the first document that only serde can translate, or the length of the input if there's
+/// none.
+fn transcode_direct<'i, O>(input: &str, output: &mut O) -> crate::Result<usize>
+where
+	O: crate::Output<'i>,
+{
+	let mut buf = vec![];
+	let mut offset = skip_whitespace(input, 0);
+	while offset < input.len() {
+		buf.clear();
+		let Some(len) = direct::to_msgpack(&input[offset..], &mut buf) else {
+			break;
+		};
+		output.set_document_offset(offset as u64);
+		output.set_document_end((offset + len) as u64);
+		output.transcode_msgpack(&buf)?;
+		offset = skip_whitespace(input, offset + len);
+	}
+	Ok(offset)
+}
+
+/// Returns true if a byte can continue a JSON number.
+fn is_number_byte(b: u8) -> bool {
+	matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')
+}
+
+/// Returns the offset of the first byte at or after `offset` in a JSON string that isn't
+/// whitespace.
+fn skip_whitespace(input: &str, offset: usize) -> usize {
+	let whitespace = input.as_bytes()[offset..]
+		.iter()
+		.take_while(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+		.count();
+	offset + whitespace
+}
+
+/// Iterates over the values in a JSON string, along with the byte offsets where each one starts
+/// and ends.
+fn values_from_str(
+	input: &str,
+) -> impl Iterator<Item = Result<(u64, u64, transcode::Value<'_>), serde_json::Error>> {
+	let mut values = serde_json::Deserializer::from_str(input).into_iter();
+	iter::from_fn(move || {
+		let offset = skip_whitespace(input, values.byte_offset()) as u64;
+		let value = values.next()?;
+		let end = values.byte_offset() as u64;
+		Some(value.map(|value| (offset, end, value)))
+	})
+}
+
+/// Transcodes each JSON value of a string with [`transcode_split`].
+///
+/// The slow `end` method of a slice deserializer only runs once for each top-level value, rather
+/// than once for each split document.
+fn split_str<'de, F>(input: &'de str, mut emit: F) -> crate::Result<()>
+where
+	F: FnMut(transcode::Value<'de>) -> crate::Result<()>,
+{
+	let mut de = serde_json::Deserializer::from_str(input);
+	while de.end().is_err() {
+		transcode_split(&mut de, &mut emit)?;
+	}
+	Ok(())
+}
+
+/// Transcodes the next JSON value from a deserializer, emitting each element of an array as a
+/// separate document.
+///
+/// This deserializes one element at a time, so translating a huge array never requires holding
+/// more than one element in memory. Values other than arrays are fully deserialized before being
+/// emitted.
+fn transcode_split<'de, R, F>(de: &mut serde_json::Deserializer<R>, emit: F) -> crate::Result<()>
+where
+	R: serde_json::de::Read<'de>,
+	F: FnMut(transcode::Value<'de>) -> crate::Result<()>,
+{
+	let mut emit_error = None;
+	let visitor = SplitVisitor {
+		emit,
+		emit_error: &mut emit_error,
+	};
+	let result = de::Deserializer::deserialize_any(de, visitor);
+	match emit_error {
+		Some(err) => Err(err),
+		None => Ok(result?),
+	}
+}
+
+/// A visitor that emits the elements of arrays, or other values, with a callback.
+///
+/// Errors from the callback halt deserialization with a generic error, and are saved for the
+/// caller to return instead.
+struct SplitVisitor<'e, F> {
+	emit: F,
+	emit_error: &'e mut Option<crate::Error>,
+}
+
+impl<'de, F> SplitVisitor<'_, F>
+where
+	F: FnMut(transcode::Value<'de>) -> crate::Result<()>,
+{
+	fn emit<E: de::Error>(&mut self, value: transcode::Value<'de>) -> Result<(), E> {
+		(self.emit)(value).map_err(|err| {
+			*self.emit_error = Some(err);
+			E::custom("translation failed")
+		})
+	}
+}
+
+/// Implements [`de::Visitor`] methods that emit the [`transcode::Value`] for a scalar.
+macro_rules! impl_split_scalar_visitors {
+	( $( $name:ident($($arg:ident: $ty:ty)?); )* ) => {
+		$(fn $name<E: de::Error>(mut self, $($arg: $ty)?) -> Result<(), E> {
+			let value = de::Visitor::$name(transcode::ValueVisitor, $($arg)?)?;
+			self.emit(value)
+		})*
+	};
+}
+
+impl<'de, F> de::Visitor<'de> for SplitVisitor<'_, F>
+where
+	F: FnMut(transcode::Value<'de>) -> crate::Result<()>,
+{
+	type Value = ();
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("any JSON value")
+	}
+
+	impl_split_scalar_visitors! {
+		visit_unit();
+		visit_bool(v: bool);
+		visit_i64(v: i64);
+		visit_u64(v: u64);
+		visit_f64(v: f64);
+		visit_borrowed_str(v: &'de str);
+		visit_str(v: &str);
+		visit_string(v: String);
+	}
+
+	fn visit_seq<A: de::SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+		while let Some(value) = seq.next_element()? {
+			self.emit(value)?;
+		}
+		Ok(())
+	}
+
+	fn visit_map<A: de::MapAccess<'de>>(mut self, map: A) -> Result<(), A::Error> {
+		let value = de::Visitor::visit_map(transcode::ValueVisitor, map)?;
+		self.emit(value)
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+	/// The indentation of each nested level when pretty printing.
+	indent: Option<Vec<u8>>,
+	/// A buffer for the direct translation of MessagePack documents, which may give up partway.
+	scratch: Vec<u8>,
+}
 
 impl<W: Write> Output<W> {
 	pub(crate) fn new(w: W) -> Output<W> {
-		Output(w)
+		Output {
+			w,
+			indent: None,
+			scratch: vec![],
+		}
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	/// Sets the number of spaces that indent each nested level of pretty printed output, or
+	/// `None` to output each document on a single line.
+	pub(crate) fn set_indent(&mut self, indent: Option<usize>) {
+		self.indent = indent.map(|width| vec![b' '; width]);
+	}
+
+	pub(crate) fn is_indented(&self) -> bool {
+		self.indent.is_some()
 	}
 }
 
-impl<W: Write> crate::Output for Output<W> {
+impl<W: Write> crate::Output<'_> for Output<W> {
 	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
 	where
 		D: de::Deserializer<'de, Error = E>,
 		E: de::Error + Send + Sync + 'static,
 	{
-		let mut ser = serde_json::Serializer::new(&mut self.0);
-		transcode::transcode(&mut ser, de)?;
-		writeln!(&mut self.0)?;
+		match &self.indent {
+			None => {
+				let mut ser = serde_json::Serializer::new(&mut self.w);
+				transcode::transcode(&mut ser, de)?;
+			}
+			Some(indent) => {
+				let formatter = PrettyFormatter::with_indent(indent);
+				let mut ser = serde_json::Serializer::with_formatter(&mut self.w, formatter);
+				transcode::transcode(&mut ser, de)?;
+			}
+		}
+		writeln!(&mut self.w)?;
 		Ok(())
 	}
 
-	fn transcode_value<S>(&mut self, value: S) -> crate::Result<()>
-	where
-		S: ser::Serialize,
-	{
-		serde_json::to_writer(&mut self.0, &value)?;
-		writeln!(&mut self.0)?;
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		match &self.indent {
+			None => serde_json::to_writer(&mut self.w, &value)?,
+			Some(indent) => {
+				let formatter = PrettyFormatter::with_indent(indent);
+				let mut ser = serde_json::Serializer::with_formatter(&mut self.w, formatter);
+				value.serialize(&mut ser)?;
+			}
+		}
+		writeln!(&mut self.w)?;
 		Ok(())
 	}
 
+	fn transcode_msgpack(&mut self, input: &[u8]) -> crate::Result<()> {
+		if self.indent.is_none() {
+			self.scratch.clear();
+			if msgpack::to_json(input, &mut self.scratch).is_some() {
+				self.scratch.push(b'\n');
+				self.w.write_all(&self.scratch)?;
+				return Ok(());
+			}
+		}
+		msgpack::transcode_document(self, input)
+	}
+
 	fn flush(&mut self) -> io::Result<()> {
-		self.0.flush()
+		self.w.flush()
 	}
 }
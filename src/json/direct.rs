@@ -0,0 +1,350 @@
+//! Direct translation of JSON documents to MessagePack.
+//!
+//! JSON to MessagePack is common enough to deserve a path that skips serde. Rather than
+//! deserialize each document from a slice into a [`transcode::Value`](crate::transcode::Value)
+//! and serialize that, [`to_msgpack`] parses the document's tokens and encodes each one as it
+//! goes. Collections are encoded in place behind room for their longest possible header, which
+//! shrinks to fit once the parser reaches the end of the collection and knows its length.
+//!
+//! The parser gives up on any document that it can't translate exactly like `serde_json`, which
+//! in practice means only documents that `serde_json` rejects too: malformed JSON, nesting past
+//! its recursion limit, and strings with unpaired surrogate escapes. The caller translates those
+//! through serde instead, with the same error as always.
+
+use rmp::encode;
+
+/// The deepest nesting of arrays and maps that the recursion limit of `serde_json` accepts.
+pub(crate) const DEPTH_LIMIT: usize = 127;
+
+/// The size of the longest MessagePack header for an array or map.
+const MAX_HEADER_SIZE: usize = 5;
+
+/// Writes the MessagePack encoding of the JSON value at the start of `input` to `out`, returning
+/// the length of the value's text, or `None` if the value needs translation through serde.
+///
+/// Like a `serde_json` stream, the input must not start with whitespace, and a value without
+/// closing delimiters must be followed by the end of the input, whitespace, or a delimiter.
+pub(super) fn to_msgpack(input: &str, out: &mut Vec<u8>) -> Option<usize> {
+	let mut parser = Parser {
+		input,
+		pos: 0,
+		out,
+		unescaped: String::new(),
+	};
+	parser.value(0)?;
+	let delimited = matches!(input.as_bytes()[0], b'[' | b'{' | b'"');
+	let next = input.as_bytes().get(parser.pos);
+	let ended = matches!(
+		next,
+		None | Some(b' ' | b'\n' | b'\t' | b'\r' | b'"' | b'[' | b']' | b'{' | b'}' | b',' | b':')
+	);
+	(delimited || ended).then_some(parser.pos)
+}
+
+struct Parser<'a, 'o> {
+	input: &'a str,
+	pos: usize,
+	out: &'o mut Vec<u8>,
+	/// A buffer for the contents of strings with escapes.
+	unescaped: String,
+}
+
+impl Parser<'_, '_> {
+	fn value(&mut self, depth: usize) -> Option<()> {
+		match self.peek()? {
+			b'n' => self.literal("null", encode::write_nil),
+			b't' => self.literal("true", |out| encode::write_bool(out, true)),
+			b'f' => self.literal("false", |out| encode::write_bool(out, false)),
+			b'"' => self.string(),
+			b'-' | b'0'..=b'9' => self.number(),
+			b'[' if depth < DEPTH_LIMIT => self.array(depth),
+			b'{' if depth < DEPTH_LIMIT => self.map(depth),
+			_ => None,
+		}
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.input.as_bytes().get(self.pos).copied()
+	}
+
+	/// Takes the next byte if it's the expected one.
+	fn eat(&mut self, expected: u8) -> bool {
+		let matched = self.peek() == Some(expected);
+		if matched {
+			self.pos += 1;
+		}
+		matched
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(b' ' | b'\n' | b'\t' | b'\r') = self.peek() {
+			self.pos += 1;
+		}
+	}
+
+	fn skip_digits(&mut self) -> usize {
+		let start = self.pos;
+		while let Some(b'0'..=b'9') = self.peek() {
+			self.pos += 1;
+		}
+		self.pos - start
+	}
+
+	fn literal<F, E>(&mut self, text: &str, write: F) -> Option<()>
+	where
+		F: FnOnce(&mut Vec<u8>) -> Result<(), E>,
+	{
+		if !self.input[self.pos..].starts_with(text) {
+			return None;
+		}
+		self.pos += text.len();
+		write(self.out).ok()
+	}
+
+	fn number(&mut self) -> Option<()> {
+		let start = self.pos;
+		let negative = self.eat(b'-');
+		match self.peek()? {
+			b'0' => self.pos += 1,
+			b'1'..=b'9' => _ = self.skip_digits(),
+			_ => return None,
+		}
+		let mut integer = true;
+		if self.eat(b'.') {
+			integer = false;
+			if self.skip_digits() == 0 {
+				return None;
+			}
+		}
+		if self.eat(b'e') || self.eat(b'E') {
+			integer = false;
+			_ = self.eat(b'+') || self.eat(b'-');
+			if self.skip_digits() == 0 {
+				return None;
+			}
+		}
+		let text = &self.input[start..self.pos];
+		// serde_json reads -0 as a float, and integers beyond 64 bits as floats after reading
+		// their digits in its own way. Parsing the text of any float with serde_json keeps
+		// every number exactly as it would be through serde.
+		if integer {
+			if negative {
+				match text.parse::<i64>() {
+					Ok(n) if n != 0 => return encode::write_sint(self.out, n).ok().map(drop),
+					_ => {}
+				}
+			} else if let Ok(n) = text.parse::<u64>() {
+				return encode::write_uint(self.out, n).ok().map(drop);
+			}
+		}
+		let n = serde_json::from_str::<f64>(text).ok()?;
+		encode::write_f64(self.out, n).ok()
+	}
+
+	fn string(&mut self) -> Option<()> {
+		self.pos += 1; // The opening quote.
+		let start = self.pos;
+		self.skip_unescaped()?;
+		if self.eat(b'"') {
+			let s = &self.input[start..self.pos - 1];
+			return encode::write_str(self.out, s).ok();
+		}
+		self.unescaped.clear();
+		self.unescaped.push_str(&self.input[start..self.pos]);
+		while self.eat(b'\\') {
+			let escape = self.peek()?;
+			self.pos += 1;
+			let c = match escape {
+				b'"' => '"',
+				b'\\' => '\\',
+				b'/' => '/',
+				b'b' => '\x08',
+				b'f' => '\x0c',
+				b'n' => '\n',
+				b'r' => '\r',
+				b't' => '\t',
+				b'u' => self.unicode_escape()?,
+				_ => return None,
+			};
+			self.unescaped.push(c);
+			let run = self.pos;
+			self.skip_unescaped()?;
+			self.unescaped.push_str(&self.input[run..self.pos]);
+		}
+		if !self.eat(b'"') {
+			return None;
+		}
+		encode::write_str(self.out, &self.unescaped).ok()
+	}
+
+	/// Skips ahead to the next quote or backslash, giving up on control characters and the end
+	/// of the input.
+	fn skip_unescaped(&mut self) -> Option<()> {
+		let rest = &self.input.as_bytes()[self.pos..];
+		let len = rest
+			.iter()
+			.position(|&b| b == b'"' || b == b'\\' || b < 0x20)?;
+		self.pos += len;
+		(rest[len] >= 0x20).then_some(())
+	}
+
+	/// Takes the hex digits of a `\u` escape starting at the current position, along with the
+	/// escape for the low half of a surrogate pair.
+	fn unicode_escape(&mut self) -> Option<char> {
+		let high = self.hex()?;
+		let code = match high {
+			0xD800..=0xDBFF => {
+				if !self.input[self.pos..].starts_with("\\u") {
+					return None;
+				}
+				self.pos += 2;
+				let low = self.hex()?;
+				if !(0xDC00..=0xDFFF).contains(&low) {
+					return None;
+				}
+				0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+			}
+			0xDC00..=0xDFFF => return None,
+			code => code,
+		};
+		char::from_u32(code)
+	}
+
+	fn hex(&mut self) -> Option<u32> {
+		let digits = self.input.get(self.pos..self.pos + 4)?;
+		// from_str_radix alone would accept a leading +.
+		if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+			return None;
+		}
+		self.pos += 4;
+		u32::from_str_radix(digits, 16).ok()
+	}
+
+	fn array(&mut self, depth: usize) -> Option<()> {
+		self.pos += 1; // The opening bracket.
+		let start = self.begin_collection();
+		let mut len: u32 = 0;
+		self.skip_whitespace();
+		if !self.eat(b']') {
+			loop {
+				self.value(depth + 1)?;
+				len = len.checked_add(1)?;
+				self.skip_whitespace();
+				if self.eat(b']') {
+					break;
+				}
+				if !self.eat(b',') {
+					return None;
+				}
+				self.skip_whitespace();
+			}
+		}
+		self.end_collection(start, |header| encode::write_array_len(header, len))
+	}
+
+	fn map(&mut self, depth: usize) -> Option<()> {
+		self.pos += 1; // The opening brace.
+		let start = self.begin_collection();
+		let mut len: u32 = 0;
+		self.skip_whitespace();
+		if !self.eat(b'}') {
+			loop {
+				if self.peek() != Some(b'"') {
+					return None;
+				}
+				self.string()?;
+				self.skip_whitespace();
+				if !self.eat(b':') {
+					return None;
+				}
+				self.skip_whitespace();
+				self.value(depth + 1)?;
+				len = len.checked_add(1)?;
+				self.skip_whitespace();
+				if self.eat(b'}') {
+					break;
+				}
+				if !self.eat(b',') {
+					return None;
+				}
+				self.skip_whitespace();
+			}
+		}
+		self.end_collection(start, |header| encode::write_map_len(header, len))
+	}
+
+	/// Leaves room for a collection's header, returning the offset of that room in the output.
+	fn begin_collection(&mut self) -> usize {
+		let start = self.out.len();
+		self.out.extend_from_slice(&[0; MAX_HEADER_SIZE]);
+		start
+	}
+
+	/// Writes a collection's header into the room left for it, shifting the collection's
+	/// elements back to close any gap.
+	fn end_collection<F, E>(&mut self, start: usize, write_header: F) -> Option<()>
+	where
+		F: FnOnce(&mut Vec<u8>) -> Result<rmp::Marker, E>,
+	{
+		let mut header = Vec::with_capacity(MAX_HEADER_SIZE);
+		write_header(&mut header).ok()?;
+		let body = start + MAX_HEADER_SIZE;
+		let header_end = start + header.len();
+		self.out.copy_within(body.., header_end);
+		self.out[start..header_end].copy_from_slice(&header);
+		self.out
+			.truncate(self.out.len() - (MAX_HEADER_SIZE - header.len()));
+		Some(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::transcode::Value;
+
+	#[test]
+	fn json_to_msgpack() {
+		let inputs = [
+			r#"{"a": [1, -1, 0, -0, 1.5, 1e3, -9223372036854775808, 18446744073709551616]}"#,
+			r#"["x\n\"é😀/", "", "é", null, true, false, {}, [], [[]]]"#,
+			&format!("[{}]", ["0"; 300].join(",")),
+			&format!(
+				"{{{}}}",
+				(0..20)
+					.map(|i| format!(r#""{i}":{i}"#))
+					.collect::<Vec<_>>()
+					.join(",")
+			),
+			"1 ",
+			"2.5[",
+		];
+		for input in inputs {
+			let mut out = vec![];
+			let len = to_msgpack(input, &mut out).unwrap();
+			assert_eq!(len, input.trim_end_matches([' ', '[']).len(), "{input}");
+			let value: Value = serde_json::from_str(&input[..len]).unwrap();
+			assert_eq!(out, rmp_serde::to_vec(&value).unwrap(), "{input}");
+		}
+		for input in [
+			"[1,]",
+			"01",
+			"1.",
+			"-",
+			"truex",
+			r#"{"a" 1}"#,
+			r#""\ud83d""#,
+			r#""\u+123""#,
+			"\"\t\"",
+		] {
+			assert_eq!(to_msgpack(input, &mut vec![]), None, "{input}");
+		}
+
+		let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+		assert!(to_msgpack(&nested(DEPTH_LIMIT), &mut vec![]).is_some());
+		assert!(serde_json::from_str::<Value>(&nested(DEPTH_LIMIT)).is_ok());
+		assert_eq!(to_msgpack(&nested(DEPTH_LIMIT + 1), &mut vec![]), None);
+		assert!(serde_json::from_str::<Value>(&nested(DEPTH_LIMIT + 1)).is_err());
+	}
+}
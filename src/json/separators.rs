@@ -0,0 +1,161 @@
+//! Lenient separators between the documents of a JSON stream.
+//!
+//! Streams from shell loops and log files often separate their documents with more than
+//! whitespace: a comma after each one, or comment lines starting with `//` or `#`. Rather than
+//! teach `serde_json` to skip these, xt blanks them out of the input with spaces before parsing.
+//! The input keeps its length and line breaks, so byte offsets and the line numbers in parse
+//! errors stay the same as in the original input.
+//!
+//! Only separators outside of every document are blanked. A comma or comment inside of an array,
+//! map, or string is left alone for `serde_json` to accept or reject as usual.
+
+use std::io::{self, Read};
+
+/// The state of the scan for separators, which may span any number of buffers.
+#[derive(Default)]
+struct Scanner {
+	/// The number of unclosed arrays and maps.
+	depth: usize,
+	string: bool,
+	escape: bool,
+	comment: bool,
+}
+
+impl Scanner {
+	/// Blanks the separators in `buf`, where `eof` indicates that no more input follows it.
+	///
+	/// Returns the number of bytes at the start of `buf` that are final. Without `eof`, that may
+	/// exclude a trailing `/` that can't be classified until the next byte arrives.
+	fn blank(&mut self, buf: &mut [u8], eof: bool) -> usize {
+		let mut i = 0;
+		while i < buf.len() {
+			let b = buf[i];
+			if self.comment {
+				if b == b'\n' {
+					self.comment = false;
+				} else {
+					buf[i] = b' ';
+				}
+			} else if self.string {
+				match b {
+					_ if self.escape => self.escape = false,
+					b'\\' => self.escape = true,
+					b'"' => self.string = false,
+					_ => {}
+				}
+			} else {
+				match b {
+					b'"' => self.string = true,
+					b'[' | b'{' => self.depth += 1,
+					b']' | b'}' => self.depth = self.depth.saturating_sub(1),
+					b',' if self.depth == 0 => buf[i] = b' ',
+					b'#' if self.depth == 0 => {
+						self.comment = true;
+						buf[i] = b' ';
+					}
+					b'/' if self.depth == 0 => match buf.get(i + 1) {
+						Some(b'/') => {
+							self.comment = true;
+							buf[i] = b' ';
+						}
+						None if !eof => return i,
+						_ => {}
+					},
+					_ => {}
+				}
+			}
+			i += 1;
+		}
+		buf.len()
+	}
+}
+
+/// Returns a copy of a JSON input with its separators blanked, or `None` if it has none.
+pub(super) fn blank_slice(input: &[u8]) -> Option<Vec<u8>> {
+	let mut blanked = input.to_vec();
+	Scanner::default().blank(&mut blanked, true);
+	(blanked != input).then_some(blanked)
+}
+
+/// A reader that blanks the separators in a JSON input.
+pub(super) struct Reader<R> {
+	inner: R,
+	scanner: Scanner,
+	/// A `/` held back from the end of the last read until the next byte shows what it starts.
+	held: Option<u8>,
+}
+
+impl<R: Read> Reader<R> {
+	pub(super) fn new(inner: R) -> Reader<R> {
+		Reader {
+			inner,
+			scanner: Scanner::default(),
+			held: None,
+		}
+	}
+}
+
+impl<R: Read> Read for Reader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if buf.len() < 2 {
+			// There's no room to read past a held byte, so give it up undecided. A one-byte read
+			// can't hold one back either, which at worst leaves a comment unrecognized.
+			if let Some(b) = self.held.take() {
+				buf[0] = b;
+				return Ok(1);
+			}
+			let len = self.inner.read(buf)?;
+			return Ok(self.scanner.blank(&mut buf[..len], true));
+		}
+		loop {
+			let start = match self.held.take() {
+				Some(b) => {
+					buf[0] = b;
+					1
+				}
+				None => 0,
+			};
+			let len = start + self.inner.read(&mut buf[start..])?;
+			let eof = len == start;
+			let done = self.scanner.blank(&mut buf[..len], eof);
+			if done < len {
+				self.held = Some(buf[done]);
+			}
+			if done > 0 || eof {
+				return Ok(done);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const INPUT: &[u8] = b"{\"a\":[1,2]},\n# one\n// two, \"three\n\"x,#y\" , 3 / 4";
+	const BLANKED: &[u8] = b"{\"a\":[1,2]} \n     \n              \n\"x,#y\"   3 / 4";
+
+	#[test]
+	fn blank_separators_in_slice() {
+		assert_eq!(blank_slice(INPUT).unwrap(), BLANKED);
+		assert_eq!(blank_slice(b"[1, 2] {\"a\": \"//\"}"), None);
+	}
+
+	#[test]
+	fn blank_separators_in_reader() {
+		// Read in small chunks to split the comment's slashes across reads.
+		for size in 2..=INPUT.len() {
+			let mut r = Reader::new(INPUT);
+			let mut output = vec![];
+			let mut buf = vec![0; size];
+			loop {
+				let len = r.read(&mut buf).unwrap();
+				if len == 0 {
+					break;
+				}
+				output.extend_from_slice(&buf[..len]);
+			}
+			assert_eq!(output, BLANKED, "size {size}");
+		}
+	}
+}
@@ -0,0 +1,354 @@
+//! Checks of input documents against the keys of a reference document, as a lightweight
+//! alternative to a full schema for catching misspelled keys.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::Format;
+use crate::error::Location;
+use crate::transcode::Value;
+use crate::transform::{key_str, path::push_key};
+
+/// The keys that input documents may contain at each position, as taken from a reference
+/// document for [`Translator::set_known_keys`](crate::Translator::set_known_keys).
+///
+/// Every map of an input document may contain only the keys of the map in the same position of
+/// the reference. The value of each key in the reference gives the keys of the map under that key
+/// in the same way, while a scalar or empty map places no limit on the keys under it, since an
+/// example can't show keys it doesn't have. The maps of an array in the reference combine their
+/// keys, which then apply to every map of an array in its position.
+#[derive(Debug, Clone)]
+pub struct KnownKeys(Node);
+
+impl KnownKeys {
+	/// Reads the keys of a reference document, such as an example configuration file.
+	///
+	/// The input must contain a single document in any supported format, whose root is a map or
+	/// an array. When `from` is `None`, the format is detected from the input itself.
+	pub fn from_slice(input: &[u8], from: Option<Format>) -> crate::Result<KnownKeys> {
+		let mut json = vec![];
+		crate::translate_slice(input, from, Format::Json, &mut json)?;
+
+		let mut values = serde_json::Deserializer::from_slice(&json).into_iter::<Value>();
+		let (Some(reference), None) = (values.next().transpose()?, values.next()) else {
+			return Err(ReferenceError::DocumentCount.into());
+		};
+		if !matches!(reference, Value::Seq(_) | Value::Map(_)) {
+			return Err(ReferenceError::RootScalar.into());
+		}
+		Ok(KnownKeys(Node::from_value(&reference)))
+	}
+}
+
+/// The known keys at one position of a reference.
+#[derive(Debug, Clone, Default)]
+enum Node {
+	/// Any keys, for a scalar or an empty map.
+	#[default]
+	Any,
+	Keys(HashMap<String, Node>),
+}
+
+impl Node {
+	fn from_value(value: &Value) -> Node {
+		match value {
+			Value::Map(map) if !map.is_empty() => {
+				let mut keys = HashMap::new();
+				for (k, v) in map {
+					let key = key_str(k).map(Into::into).unwrap_or_default();
+					Node::insert(&mut keys, key, Node::from_value(v));
+				}
+				Node::Keys(keys)
+			}
+			Value::Seq(seq) => {
+				let mut node: Option<Node> = None;
+				for v in seq {
+					match &mut node {
+						Some(node) => node.merge(Node::from_value(v)),
+						None => node = Some(Node::from_value(v)),
+					}
+				}
+				node.unwrap_or_default()
+			}
+			_ => Node::Any,
+		}
+	}
+
+	/// Combines the keys of another node with those of this one, where either allowing any keys
+	/// leaves the combination allowing any keys.
+	fn merge(&mut self, other: Node) {
+		match (&mut *self, other) {
+			(Node::Any, _) | (_, Node::Any) => *self = Node::Any,
+			(Node::Keys(keys), Node::Keys(other)) => {
+				for (key, node) in other {
+					Node::insert(keys, key, node);
+				}
+			}
+		}
+	}
+
+	/// Adds a key to a set of known keys, merging its node with that of any earlier instance.
+	fn insert(keys: &mut HashMap<String, Node>, key: String, node: Node) {
+		match keys.get_mut(&key) {
+			Some(existing) => existing.merge(node),
+			None => {
+				keys.insert(key, node);
+			}
+		}
+	}
+}
+
+/// A key that a document contains but its [reference](crate::Translator::set_known_keys) doesn't,
+/// as passed to an [unknown key handler](crate::Translator::set_unknown_key_handler) or returned
+/// as the error of a translation without one.
+#[derive(Debug, Clone)]
+pub struct UnknownKey {
+	source: Option<String>,
+	document: u64,
+	path: String,
+	key: String,
+}
+
+impl UnknownKey {
+	/// Returns the [source name](crate::Translator::set_source_name) of the input containing the
+	/// key, for a named input.
+	pub fn source(&self) -> Option<&str> {
+		self.source.as_deref()
+	}
+
+	/// Returns the zero-based index of the document containing the key within its input.
+	pub fn document_index(&self) -> u64 {
+		self.document
+	}
+
+	/// Returns the path to the map containing the key, in the syntax of
+	/// [`select`](crate::Transform::select) expressions.
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+
+	/// Returns the unknown key, or the JSON form of a key without a string form.
+	pub fn key(&self) -> &str {
+		&self.key
+	}
+}
+
+impl error::Error for UnknownKey {}
+
+impl fmt::Display for UnknownKey {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let key = serde_json::to_string(&self.key).unwrap_or_default();
+		write!(f, "unknown key {key} in map at {}", self.path)
+	}
+}
+
+pub(crate) type UnknownKeyHandler = dyn FnMut(UnknownKey) + Send;
+
+/// An [`Output`](crate::Output) that checks the keys of each document against a reference, when
+/// there is one.
+///
+/// Checking fully deserializes each document into memory.
+pub(crate) struct Output<'k, 'h, O> {
+	output: O,
+	known: Option<&'k KnownKeys>,
+	handler: Option<&'h mut UnknownKeyHandler>,
+	source: Option<&'k str>,
+	document: u64,
+}
+
+impl<'k, 'h, O> Output<'k, 'h, O> {
+	pub(crate) fn new(
+		output: O,
+		known: Option<&'k KnownKeys>,
+		handler: Option<&'h mut UnknownKeyHandler>,
+		source: Option<&'k str>,
+	) -> Output<'k, 'h, O> {
+		Output {
+			output,
+			known,
+			handler,
+			source,
+			document: 0,
+		}
+	}
+
+	/// Reports every unknown key of a document to the handler, or returns the first one as an
+	/// error without a handler.
+	fn check(&mut self, value: &Value) -> crate::Result<()> {
+		let Some(known) = self.known else {
+			return Ok(());
+		};
+		let mut unknown = |path: &str, key: String| {
+			let unknown = UnknownKey {
+				source: self.source.map(Into::into),
+				document: self.document,
+				path: if path.is_empty() { "." } else { path }.into(),
+				key,
+			};
+			match self.handler.as_deref_mut() {
+				Some(handler) => {
+					handler(unknown);
+					Ok(())
+				}
+				None => Err(unknown),
+			}
+		};
+		Ok(check_keys(
+			&known.0,
+			value,
+			&mut String::new(),
+			&mut unknown,
+		)?)
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, '_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.known.is_none() {
+			return self.output.transcode_from(de);
+		}
+		self.transcode_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.known.is_none() {
+			return self.output.transcode_borrowed_from(de);
+		}
+		self.transcode_borrowed_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		self.check(&value)?;
+		self.output.transcode_value(value)
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		self.check(&value)?;
+		self.output.transcode_borrowed_value(value)
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.document = location.index;
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+/// Calls `unknown` with the path and key of every key within a value that the node doesn't know,
+/// where `path` is the path to the value, stopping at its first error.
+fn check_keys<E>(
+	node: &Node,
+	value: &Value,
+	path: &mut String,
+	unknown: &mut impl FnMut(&str, String) -> Result<(), E>,
+) -> Result<(), E> {
+	let Node::Keys(keys) = node else {
+		return Ok(());
+	};
+	let len = path.len();
+	match value {
+		Value::Seq(seq) => {
+			for (i, v) in seq.iter().enumerate() {
+				path.push_str(&format!("[{i}]"));
+				check_keys(node, v, path, unknown)?;
+				path.truncate(len);
+			}
+		}
+		Value::Map(map) => {
+			for (k, v) in map {
+				let key = match key_str(k) {
+					Some(key) => key.into_owned(),
+					None => serde_json::to_string(k).unwrap_or_default(),
+				};
+				match keys.get(&key) {
+					Some(node) => {
+						push_key(path, &key);
+						check_keys(node, v, path, unknown)?;
+						path.truncate(len);
+					}
+					None => unknown(path, key)?,
+				}
+			}
+		}
+		_ => {}
+	}
+	Ok(())
+}
+
+/// An error in the contents of a reference document.
+#[derive(Debug)]
+enum ReferenceError {
+	DocumentCount,
+	RootScalar,
+}
+
+impl error::Error for ReferenceError {}
+
+impl fmt::Display for ReferenceError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ReferenceError::DocumentCount => {
+				f.write_str("reference must contain exactly one document")
+			}
+			ReferenceError::RootScalar => f.write_str("reference must be a map or an array"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn check_keys_against_reference() {
+		const REFERENCE: &[u8] =
+			br#"{"server": {"port": 80}, "hosts": [{"name": "a"}, {"ip": "b"}], "env": {}}"#;
+		const CASES: &[(&str, &[(&str, &str)])] = &[
+			(
+				r#"{"server":{"port":1},"hosts":[{"name":"x","ip":"y"}],"env":{"ANY":1}}"#,
+				&[],
+			),
+			(
+				r#"{"sever":{"prot":1},"server":{"prot":1},"hosts":[{},{"nmae":"x"}]}"#,
+				&[(".", "sever"), (".server", "prot"), (".hosts[1]", "nmae")],
+			),
+		];
+		let known = KnownKeys::from_slice(REFERENCE, Some(Format::Json)).unwrap();
+		for &(input, want) in CASES {
+			let value = Value::deserialize(&mut serde_json::Deserializer::from_str(input)).unwrap();
+			let mut found = vec![];
+			let result = check_keys(&known.0, &value, &mut String::new(), &mut |path, key| {
+				found.push((if path.is_empty() { "." } else { path }.to_owned(), key));
+				Ok::<_, ()>(())
+			});
+			assert!(result.is_ok());
+			let found: Vec<_> = found
+				.iter()
+				.map(|(p, k)| (p.as_str(), k.as_str()))
+				.collect();
+			assert_eq!(found, want, "{input}");
+		}
+
+		for input in [&b"1"[..], b"{} {}"] {
+			assert!(KnownKeys::from_slice(input, Some(Format::Json)).is_err());
+		}
+	}
+}
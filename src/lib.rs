@@ -39,20 +39,79 @@
 	clippy::similar_names,
 )]
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp;
 use std::fmt;
-use std::io::{self, Read, Write};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 
-use serde::{de, ser};
+use serde::de::{self, Deserialize};
 
+mod atomic;
+mod audit;
+mod avro;
+mod bencode;
+mod bson;
+mod capabilities;
+mod comment;
+mod csv;
+mod detect;
+mod edn;
+mod encoding;
 mod error;
+mod group;
+mod index;
+mod ini;
 mod input;
+mod intern;
 mod json;
+mod known;
+mod merge;
 mod msgpack;
+mod null;
+mod order;
+mod parallel;
+mod protobuf;
+mod ron;
+mod schema;
+mod sink;
+mod strictness;
+#[cfg(feature = "template")]
+mod template;
+mod timeout;
 mod toml;
 mod transcode;
+mod transform;
+mod utf8;
+mod window;
 mod yaml;
 
+pub use audit::StringIssue;
+pub use capabilities::{Capabilities, capabilities};
+pub use csv::CsvMismatch;
+pub use detect::{Detection, Probe};
+pub use edn::EdnTags;
+pub use encoding::Encoding;
 pub use error::{Error, Result};
+pub use known::{KnownKeys, UnknownKey};
+pub use merge::ArrayMerge;
+pub use msgpack::{MsgpackKeys, MsgpackResync};
+pub use order::StreamOrder;
+pub use protobuf::ProtobufMessage;
+pub use sink::{DocumentSink, DocumentWriter};
+pub use strictness::Strictness;
+#[cfg(feature = "template")]
+pub use template::Template;
+pub use transform::{Coercion, Transform};
+pub use utf8::Utf8Repair;
+pub use yaml::blocks::YamlBlockStyle;
+pub use yaml::lint::{YamlRisk, YamlWarning};
 
 /// Translates the contents of a single input slice to a different format.
 ///
@@ -75,6 +134,118 @@ where
 	Translator::new(output, to).translate_reader(input, from)
 }
 
+/// Translates the contents of a single seekable reader to a different format.
+///
+/// See [`Translator::translate_seekable`].
+pub fn translate_seekable<R, W>(input: R, from: Option<Format>, to: Format, output: W) -> Result<()>
+where
+	R: Read + Seek,
+	W: Write,
+{
+	Translator::new(output, to).translate_seekable(input, from)
+}
+
+/// Detects the format of an input slice, trying every format that could plausibly match it.
+///
+/// Translation without a known input format uses the same detection, except that it stops at the
+/// first format that matches.
+pub fn detect_slice(input: &[u8]) -> Result<Detection> {
+	let mut input = input::Handle::from_slice(input);
+	Ok(detect::detect(
+		&mut input,
+		&detect::DEFAULT_ORDER,
+		true,
+		None,
+	)?)
+}
+
+/// Detects the format of a reader's contents, trying every format that could plausibly match
+/// them.
+///
+/// Detection buffers as much of the reader as the trial parse of each format requires, which may
+/// be the entire input.
+pub fn detect_reader<R: Read>(input: R) -> Result<Detection> {
+	let mut input = input::Handle::from_reader(input);
+	Ok(detect::detect(
+		&mut input,
+		&detect::DEFAULT_ORDER,
+		true,
+		None,
+	)?)
+}
+
+/// Reformats a UTF-8 YAML stream in the layout of translated YAML output, keeping its comments
+/// and blank lines.
+///
+/// Unlike translation, which discards comments along with the rest of the input's layout, this
+/// works from the parser's events without deserializing any values. It re-indents every block
+/// collection and normalizes the spacing around its indicators, but keeps the source text of
+/// scalars and flow collections as they are, and applies none of a translator's transforms.
+/// Maps with keys that span multiple lines, like block collections used as keys, are errors.
+pub fn reformat_yaml(input: &[u8]) -> Result<Vec<u8>> {
+	yaml::layout::reformat(input)
+}
+
+/// An input to translate, which supports peeking at its contents beforehand.
+///
+/// Peeking lets an application examine the start of an input, for example to route it
+/// somewhere other than xt, without consuming it. For reader inputs, the input captures
+/// everything that peeking or [detection](Input::detect) reads, and replays it during
+/// translation. For seekable reader inputs, it replays the input by seeking back to the reader's
+/// starting position instead.
+pub struct Input<'i>(input::Handle<'i>);
+
+impl<'i> Input<'i> {
+	/// Creates an input from a slice.
+	pub fn from_slice(input: &'i [u8]) -> Input<'i> {
+		Input(input::Handle::from_slice(input))
+	}
+
+	/// Creates an input from a reader.
+	pub fn from_reader<R: Read + 'i>(input: R) -> Input<'i> {
+		Input(input::Handle::from_reader(input))
+	}
+
+	/// Creates an input from a seekable reader, which must produce the same input every time it
+	/// seeks back to its current position.
+	pub fn from_seekable<R: Read + Seek + 'i>(input: R) -> io::Result<Input<'i>> {
+		Ok(Input(input::Handle::from_seekable_reader(input)?))
+	}
+
+	/// Returns the first `n` bytes of the input, or the entire input if it's shorter, without
+	/// consuming them.
+	pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+		let prefix = self.0.prefix(n)?;
+		Ok(&prefix[..cmp::min(n, prefix.len())])
+	}
+
+	/// Detects the format of the input without consuming it, trying every format that could
+	/// plausibly match it.
+	///
+	/// See [`detect_reader`] for the buffering that detection requires.
+	pub fn detect(&mut self) -> Result<Detection> {
+		Ok(detect::detect(
+			&mut self.0,
+			&detect::DEFAULT_ORDER,
+			true,
+			None,
+		)?)
+	}
+
+	/// Detects the format of the input like [`detect`](Input::detect), buffering at most `limit`
+	/// bytes of a reader input.
+	///
+	/// See [`Translator::set_detection_limit`] for how the limit affects detection.
+	pub fn detect_within(&mut self, limit: usize) -> Result<Detection> {
+		Ok(detect::detect(
+			&mut self.0,
+			&detect::DEFAULT_ORDER,
+			true,
+			Some(limit),
+		)?)
+	}
+}
+
 /// Translates multiple inputs to a single serialized output.
 ///
 /// A `Translator` accepts both slice and reader input. See [`translate_slice`] and
@@ -85,82 +256,1950 @@ where
 /// single input. When translating to a format without multi-document support, translation fails if
 /// the translator encounters more than one document in the first input, or if the translator is
 /// called a second time with another input.
-pub struct Translator<W>(Dispatcher<W>)
+///
+/// A `Translator` buffers its output internally, and flushes the buffer whenever it fills and at
+/// the end of every input, so there's no need to wrap the output in a [`BufWriter`].
+pub struct Translator<W>
 where
-	W: Write;
+	W: Write,
+{
+	output: Dispatcher<OutputWriter<W>>,
+	to: Format,
+	threads: NonZeroUsize,
+	budget: input::Budget,
+	detection_order: Vec<Format>,
+	detection_limit: Option<usize>,
+	max_document_size: Option<u64>,
+	timeout: Option<Duration>,
+	split_json_arrays: bool,
+	lenient_json: bool,
+	reject_duplicate_keys: bool,
+	known_keys: Option<KnownKeys>,
+	unknown_key_handler: Option<Box<known::UnknownKeyHandler>>,
+	string_issue_handler: Option<Box<audit::StringIssueHandler>>,
+	utf8_repair_handler: Option<Box<utf8::RepairHandler>>,
+	protobuf_message: Option<ProtobufMessage>,
+	protobuf_delimited: bool,
+	edn_tags: EdnTags,
+	reject_risky_yaml: bool,
+	translate_msgpack_ext: bool,
+	msgpack_keys: MsgpackKeys,
+	flush_each_document: bool,
+	document_comments: bool,
+	output_comments: comment::Comments,
+	intern_keys: bool,
+	report_merge_conflicts: bool,
+	attribute_merge_sources: bool,
+	document_sink: bool,
+	transforms: Vec<Transform>,
+	source_name: Option<String>,
+	skip_bytes: u64,
+	skip_documents: u64,
+	window: window::Window,
+	merge: Option<merge::Merge>,
+	schema: Option<schema::Inference>,
+	group: Option<group::Grouping>,
+	order: Option<order::Reorder>,
+	document_handler: Option<Box<DocumentHandler>>,
+	span_handler: Option<Box<SpanHandler>>,
+	index: Option<index::Index>,
+	yaml_lint: Option<yaml::lint::Lint>,
+	msgpack_resync: Option<Box<msgpack::ResyncHandler>>,
+}
+
+impl<W> Translator<W>
+where
+	W: Write,
+{
+	/// Creates a translator that produces output in the given format.
+	pub fn new(output: W, to: Format) -> Translator<W> {
+		Translator::with_capacity(DEFAULT_BUFFER_CAPACITY, output, to)
+	}
+
+	/// Creates a translator that produces output in the given format, with an output buffer of at
+	/// least the specified capacity.
+	///
+	/// A capacity of 0 disables the buffer. Complete documents still gather into batches of up to
+	/// 8 KiB that each reach the underlying writer in a single write, no later than the flush at
+	/// the end of each input, unless [flushing each document](Translator::set_flush_each_document)
+	/// or turning off [atomic documents](Translator::set_atomic_documents).
+	pub fn with_capacity(capacity: usize, output: W, to: Format) -> Translator<W> {
+		let output = Dispatcher::new(output_writer(capacity, output), to);
+		Translator::with_dispatcher(output, to)
+	}
+
+	/// Creates a translator that renders each document as text through a template, rather than
+	/// serializing it in a data format.
+	///
+	/// Template output always runs on the calling thread, regardless of
+	/// [`set_threads`](Translator::set_threads).
+	#[cfg(feature = "template")]
+	pub fn with_template(output: W, template: Template) -> Translator<W> {
+		let output = output_writer(DEFAULT_BUFFER_CAPACITY, output);
+		let output = Dispatcher::Template(template::Output::new(output, template));
+		// Templates support any number of documents, like JSON. The format is otherwise unused,
+		// since parallel translation (which creates new outputs from it) is disabled.
+		Translator::with_dispatcher(output, Format::Json)
+	}
+
+	fn with_dispatcher(output: Dispatcher<OutputWriter<W>>, to: Format) -> Translator<W> {
+		Translator {
+			output,
+			to,
+			threads: NonZeroUsize::MIN,
+			budget: input::Budget::UNLIMITED,
+			detection_order: detect::DEFAULT_ORDER.to_vec(),
+			detection_limit: None,
+			max_document_size: None,
+			timeout: None,
+			split_json_arrays: false,
+			lenient_json: false,
+			reject_duplicate_keys: false,
+			known_keys: None,
+			unknown_key_handler: None,
+			string_issue_handler: None,
+			utf8_repair_handler: None,
+			protobuf_message: None,
+			protobuf_delimited: false,
+			edn_tags: EdnTags::Passthrough,
+			reject_risky_yaml: false,
+			translate_msgpack_ext: false,
+			msgpack_keys: MsgpackKeys::Preserve,
+			flush_each_document: false,
+			document_comments: false,
+			output_comments: comment::Comments::default(),
+			intern_keys: false,
+			report_merge_conflicts: false,
+			attribute_merge_sources: false,
+			document_sink: false,
+			transforms: vec![],
+			source_name: None,
+			skip_bytes: 0,
+			skip_documents: 0,
+			window: window::Window::default(),
+			merge: None,
+			schema: None,
+			group: None,
+			order: None,
+			document_handler: None,
+			span_handler: None,
+			index: None,
+			yaml_lint: None,
+			msgpack_resync: None,
+		}
+	}
+
+	/// Sets the number of threads that serialize output documents.
+	///
+	/// With more than one thread, the translator deserializes each input document on the calling
+	/// thread, hands it to a pool of worker threads for serialization, and writes the results in
+	/// their original order. This can speed up the translation of large multi-document inputs, at
+	/// the cost of fully buffering every document in memory (including documents that would
+	/// otherwise stream). Translations to formats without multi-document support always run on
+	/// the calling thread.
+	///
+	/// The default is one thread, which disables the worker pool.
+	pub fn set_threads(&mut self, threads: NonZeroUsize) {
+		self.threads = threads;
+	}
+
+	/// Sets a memory budget for buffering reader inputs, in bytes.
+	///
+	/// The budget limits the total size of the buffers that the translator keeps of a reader's
+	/// contents, including the input captured during format detection, a full copy of the input for
+	/// formats that don't support streaming (currently TOML), and the buffer for each document of a
+	/// streaming YAML input. Translation fails with an error as soon as the buffers together
+	/// would exceed the budget, making it possible to safely translate untrusted inputs of
+	/// unbounded size.
+	/// Detecting the format of a YAML input holds its first document in two buffers at once, which
+	/// both count against the budget.
+	///
+	/// Some formats read ahead of the document they're working on, and the budget covers that
+	/// read-ahead too. In particular, the YAML parser reads up to 16 KiB at a time, so YAML inputs
+	/// may need a budget somewhat larger than their largest document.
+	///
+	/// The budget does not apply to input slices, which are already in memory, or to the memory
+	/// that input or output formats use for the values that they translate.
+	///
+	/// The default is `None`, which leaves the size of input buffers unlimited.
+	pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+		self.budget = input::Budget::new(budget);
+	}
+
+	/// Sets the formats that format detection considers, in the order that it tries them.
+	///
+	/// Detection chooses the first format in the order that matches the input, so restricting or
+	/// reordering the formats can avoid false positives when the possible input formats are known
+	/// in advance. For example, an input that is valid YAML and valid TOML is detected as YAML by
+	/// default, but as TOML with an order that puts TOML first. Repeated formats are ignored. An
+	/// empty order makes detection fail for every input.
+	///
+	/// The default order is MessagePack, JSON, YAML, then TOML.
+	pub fn set_detection_order(&mut self, order: &[Format]) {
+		self.detection_order.clear();
+		for &format in order {
+			if !self.detection_order.contains(&format) {
+				self.detection_order.push(format);
+			}
+		}
+	}
+
+	/// Sets the maximum number of bytes of a reader input that format detection may buffer, or
+	/// `None` to remove the limit.
+	///
+	/// Detection buffers the input that it needs to try each format. A format whose trial would
+	/// need more than the limit fails to match, and if no format matches, the error for the
+	/// failed detection says which formats the limit ruled out. A limit lower than a few KiB may
+	/// keep JSON and YAML inputs from matching at all. Detection within the limit is still subject
+	/// to the memory budget.
+	///
+	/// The default is `None`, which leaves the size of detection buffers up to each format: TOML
+	/// buffers at most 2 MiB, and other formats are limited only by the memory budget.
+	pub fn set_detection_limit(&mut self, limit: Option<usize>) {
+		self.detection_limit = limit;
+	}
+
+	/// Sets the maximum size of any single document in an input, in bytes, or `None` to remove
+	/// the limit.
+	///
+	/// Where the input format knows the size of a document before parsing it, as for documents
+	/// from input slices, TOML inputs, and YAML inputs, a larger document fails without being
+	/// parsed. That failure goes to the [document handler](Translator::set_document_handler) like
+	/// an output error, so a handler can skip the document and continue with the next one.
+	///
+	/// JSON and MessagePack documents streamed from a reader don't have a known size until they
+	/// end. Instead, their translation fails as soon as a document needs to read past the limit,
+	/// before the translator buffers any more of it. Like any other error in the input, that
+	/// failure stops translation of the input without a call to the handler. Documents split from
+	/// [JSON arrays](Translator::set_split_json_arrays) aren't subject to the limit.
+	///
+	/// The limit bounds the input that each document may span, while the
+	/// [memory budget](Translator::set_memory_budget) bounds the buffers that hold it. The default
+	/// is `None`.
+	pub fn set_max_document_size(&mut self, max: Option<u64>) {
+		self.max_document_size = max;
+	}
+
+	/// Sets whether the translator treats each element of a top-level JSON array as a separate
+	/// input document.
+	///
+	/// This supports the many inputs that represent a sequence of records as one huge JSON array,
+	/// rather than as a stream of separate documents. When translating from a reader, the
+	/// translator parses and outputs one element at a time instead of holding the entire array in
+	/// memory. A top-level value other than an array is translated as a single document, as usual.
+	///
+	/// This option has no effect on inputs in formats other than JSON. The default is `false`.
+	pub fn set_split_json_arrays(&mut self, split: bool) {
+		self.split_json_arrays = split;
+	}
+
+	/// Sets whether to accept commas and comment lines between the documents of JSON inputs.
+	///
+	/// Strictly, the documents of a JSON stream are separated only by whitespace, if at all. With
+	/// leniency, a translator also skips any commas between documents, and comments that start
+	/// with `//` or `#` outside of any document and run to the end of their line. This supports
+	/// the messier streams that shell loops and logs tend to produce. Commas and comments within a
+	/// document are still errors.
+	///
+	/// Format detection isn't lenient, so an input that starts with a comment may need an explicit
+	/// input format. This option has no effect on inputs in formats other than JSON. The default
+	/// is `false`.
+	pub fn set_lenient_json(&mut self, lenient: bool) {
+		self.lenient_json = lenient;
+	}
+
+	/// Sets whether to reject input documents containing a map with the same key more than once.
+	///
+	/// Most formats leave the meaning of a duplicate key up to the parser. By default, a
+	/// translator passes every entry of such a map through to the output, which may keep all of
+	/// them, or may fail if the output format doesn't allow them. Rejecting duplicate keys fully
+	/// deserializes each document into memory to check it. TOML inputs always reject duplicate
+	/// keys. The default is `false`.
+	pub fn set_reject_duplicate_keys(&mut self, reject: bool) {
+		self.reject_duplicate_keys = reject;
+	}
+
+	/// Sets the keys that input documents may contain, as taken from a reference document, or
+	/// `None` to allow any keys.
+	///
+	/// With known keys, a translator fails on the first input document containing a key that the
+	/// reference doesn't, or reports every such key to an [unknown key
+	/// handler](Self::set_unknown_key_handler) instead. This catches misspelled keys without the
+	/// weight of a full schema. Checking keys fully deserializes each document into memory. The
+	/// default is `None`.
+	pub fn set_known_keys(&mut self, known: Option<KnownKeys>) {
+		self.known_keys = known;
+	}
+
+	/// Sets a function to call for each key of an input document that the [known
+	/// keys](Self::set_known_keys) don't include, rather than failing on it.
+	///
+	/// With a handler, unknown keys don't affect translation. A handler has no effect without
+	/// known keys.
+	pub fn set_unknown_key_handler<F>(&mut self, handler: F)
+	where
+		F: FnMut(UnknownKey) + Send + 'static,
+	{
+		self.unknown_key_handler = Some(Box::new(handler));
+	}
+
+	/// Sets a function to call for each string of an input document that contains a control
+	/// character other than a tab or line break, or a replacement character.
+	///
+	/// With a handler, a translator replaces invalid UTF-8 in JSON, TOML, INI, EDN, RON, CSV, TSV,
+	/// and UTF-8 YAML inputs, as well as lone surrogate escapes in JSON inputs, with U+FFFD rather
+	/// than failing on them, so that the handler can report where they were. This reads each such
+	/// input fully into memory, and fully deserializes each document to check its strings.
+	/// Strings don't otherwise affect translation. The default is no handler.
+	pub fn set_string_issue_handler<F>(&mut self, handler: F)
+	where
+		F: FnMut(StringIssue) + Send + 'static,
+	{
+		self.string_issue_handler = Some(Box::new(handler));
+	}
+
+	/// Sets a function to call with the number of invalid UTF-8 sequences that the translator
+	/// replaced in each JSON or MessagePack input, rather than failing on them.
+	///
+	/// With a handler, a translator replaces every invalid UTF-8 sequence in a JSON input, or in
+	/// the strings of a MessagePack input, with U+FFFD before decoding it, and calls the handler
+	/// for each input that needed any. Without one, such a JSON input fails, and such a MessagePack
+	/// string is binary data, which most output formats reject. Repair reads each such input fully
+	/// into memory, and moves the byte offsets of the MessagePack documents after a repaired
+	/// string. The default is no handler.
+	pub fn set_utf8_repair_handler<F>(&mut self, handler: F)
+	where
+		F: FnMut(Utf8Repair) + Send + 'static,
+	{
+		self.utf8_repair_handler = Some(Box::new(handler));
+	}
+
+	/// Sets whether to reject YAML inputs containing plain scalars that xt reads as strings, but
+	/// that YAML 1.1 parsers read as other types.
+	///
+	/// These are the same scalars that a [YAML warning handler](Self::set_yaml_warning_handler)
+	/// warns about in YAML input, like `no`, `0123`, `1:20`, and `2001-12-14`. Rejecting them
+	/// makes a translator fail on the first document that contains one. The default is `false`.
+	pub fn set_reject_risky_yaml(&mut self, reject: bool) {
+		self.reject_risky_yaml = reject;
+	}
+
+	/// Sets whether to translate MessagePack extension types rather than failing on them.
+	///
+	/// Extension types carry application-specific data that xt can't interpret. With translation,
+	/// each extension value becomes a two-element array of its integer type and its data bytes,
+	/// which requires fully deserializing each document into memory. The default is `false`.
+	pub fn set_translate_msgpack_ext(&mut self, translate: bool) {
+		self.translate_msgpack_ext = translate;
+	}
+
+	/// Sets what to do with the map keys of MessagePack input that are integers, booleans, or
+	/// binary data.
+	///
+	/// Converting such keys to strings lets documents with them translate to output formats that
+	/// only allow string keys, which fully deserializes each document into memory. The default is
+	/// [`MsgpackKeys::Preserve`].
+	pub fn set_msgpack_keys(&mut self, keys: MsgpackKeys) {
+		self.msgpack_keys = keys;
+	}
+
+	/// Sets every input strictness setting of the translator from a profile.
+	///
+	/// A profile sets [lenient JSON](Self::set_lenient_json), [duplicate key
+	/// rejection](Self::set_reject_duplicate_keys), [risky YAML
+	/// rejection](Self::set_reject_risky_yaml), and [MessagePack extension
+	/// translation](Self::set_translate_msgpack_ext) all at once. Any of those setters can still
+	/// override the profile's choice afterward.
+	pub fn set_strictness(&mut self, strictness: Strictness) {
+		let strict = strictness == Strictness::Strict;
+		self.lenient_json = !strict;
+		self.reject_duplicate_keys = strict;
+		self.reject_risky_yaml = strict;
+		self.translate_msgpack_ext = !strict;
+	}
+
+	/// Sets the number of spaces that indent each nested level of JSON output, which pretty
+	/// prints each document across multiple lines.
+	///
+	/// A translator with indented output always serializes on the calling thread. This setting
+	/// has no effect for formats other than JSON, whose serializers use a fixed style, including
+	/// [JSON Lines](Format::JsonLines). The default is `None`, which outputs each JSON document on
+	/// a single line.
+	pub fn set_json_indent(&mut self, indent: Option<usize>) {
+		if self.to == Format::JsonLines {
+			return;
+		}
+		if let Dispatcher::Json(output) = &mut self.output {
+			output.set_indent(indent);
+		}
+	}
+
+	/// Sets the style of the multi-line strings of YAML output.
+	///
+	/// Block scalars keep each line of a string on a line of its own, which reads and edits more
+	/// easily than a quoted string with `\n` escapes. A translator with a style other than
+	/// [`YamlBlockStyle::Auto`] always serializes on the calling thread. This setting has no
+	/// effect for formats other than YAML. The default is [`YamlBlockStyle::Auto`].
+	pub fn set_yaml_block_style(&mut self, style: YamlBlockStyle) {
+		if let Dispatcher::Yaml(output) = &mut self.output {
+			output.set_block_style(style);
+		}
+	}
+
+	/// Sets the text encoding of the output.
+	///
+	/// Output formats serialize UTF-8, which the translator re-encodes before writing it to the
+	/// underlying writer. The encoding applies to everything written after this call, so it
+	/// should be set before translating any input. Binary output formats like MessagePack ignore
+	/// the encoding. The default is [`Encoding::Utf8`].
+	pub fn set_output_encoding(&mut self, encoding: Encoding) {
+		if self.output.is_text() {
+			self.output.get_mut().get_mut().set_encoding(encoding);
+		}
+	}
+
+	/// Sets whether text output starts with a Unicode byte order mark in the
+	/// [output encoding](Translator::set_output_encoding).
+	///
+	/// The mark precedes the next byte that the translator writes, so this should be set before
+	/// translating any input. Binary output formats like MessagePack never start with a mark. The
+	/// default is `false`.
+	pub fn set_byte_order_mark(&mut self, bom: bool) {
+		if self.output.is_text() {
+			self.output.get_mut().get_mut().set_bom(bom);
+		}
+	}
+
+	/// Sets whether the translator flushes its output after every document.
+	///
+	/// By default, the translator only flushes when its output buffer fills and at the end of
+	/// every input, which can delay output from a slow streaming input (like a log file that's
+	/// still being written) for a long time. Flushing after every document supports live pipelines
+	/// where downstream consumers should see each document as soon as possible, at the cost of
+	/// more frequent writes to the underlying writer.
+	///
+	/// When serializing on multiple threads, each flush covers the documents that have finished
+	/// serializing so far, which may lag behind the input. The default is `false`.
+	pub fn set_flush_each_document(&mut self, flush: bool) {
+		self.flush_each_document = flush;
+	}
+
+	/// Sets whether to write a comment line before each output document, giving the document's
+	/// index within its input and the input's [source name](Self::set_source_name), like
+	/// `# doc 12 from service.yaml`.
+	///
+	/// Comments mark where each document came from when reviewing large concatenated outputs.
+	/// Only YAML and TOML output have comments, and documents that don't correspond to a single
+	/// input document, like the result of merging, have none. Comments serialize every document on
+	/// the calling thread. The default is `false`.
+	pub fn set_document_comments(&mut self, comments: bool) {
+		self.document_comments = comments;
+	}
+
+	/// Adds a comment that YAML and TOML output write on the lines before the entry at a path
+	/// expression like `.replicas` in every document, or before all of a document for the path
+	/// `.`, like `# managed by xt`.
+	///
+	/// Comments mark generated files with where their values came from, so that they're less
+	/// likely to drift from their source through hand edits. A comment with line breaks spans
+	/// several comment lines, and comments at the same path follow one another. Documents without
+	/// an entry at the path go without the comment, as do entries with keys that span lines.
+	///
+	/// Paths use the same syntax as [`Transform::select`], and must end with a key rather than an
+	/// array index. Comments serialize every document on the calling thread. This setting has no
+	/// effect for formats other than YAML and TOML.
+	pub fn add_output_comment(&mut self, expr: &str, comment: &str) -> Result<()> {
+		let path = transform::path::parse("comment path", expr)?;
+		if matches!(path.last(), Some(transform::path::Segment::Index(_))) {
+			return Err("comment path must end with a key, not an array index".into());
+		}
+		self.output_comments.add(path, comment);
+		match &mut self.output {
+			Dispatcher::Yaml(output) => output.set_comments(self.output_comments.clone()),
+			Dispatcher::Toml(output) => output.set_comments(self.output_comments.clone()),
+			_ => {}
+		}
+		Ok(())
+	}
+
+	/// Sets whether documents held in memory share one copy of each distinct map key string.
+	///
+	/// Translators hold documents in memory for the [tail](Translator::set_tail),
+	/// [reordering](Translator::set_stream_order), [grouping](Translator::set_group_by),
+	/// [merging](Translator::set_merge), and parallel [serialization](Translator::set_threads).
+	/// Streams of records with the same keys in every document can spend most of that memory on
+	/// copies of the keys, which interning replaces with a single copy from a pool shared by every
+	/// translator in the process. Pooled keys stay in memory until the process exits, so the pool
+	/// only takes keys up to a small fixed length, and stops taking new keys past a fixed total
+	/// size. Other keys are copied as usual.
+	///
+	/// The default is `false`.
+	pub fn set_intern_keys(&mut self, intern: bool) {
+		self.intern_keys = intern;
+		self.window.set_intern_keys(intern);
+		if let Some(group) = &mut self.group {
+			group.set_intern_keys(intern);
+		}
+		if let Some(order) = &mut self.order {
+			order.set_intern_keys(intern);
+		}
+		if let Some(merge) = &mut self.merge {
+			merge.set_intern_keys(intern);
+		}
+	}
+
+	/// Sets whether the translator holds each output document in memory until it's complete, so
+	/// that a failure partway through a document never leaves part of it in the output.
+	///
+	/// With atomic documents, a failed translation leaves the output with every document before
+	/// the one that failed, and nothing of the one that failed. Without them, output formats write
+	/// straight to the output buffer, so that a huge document that would otherwise stream can do so
+	/// without a full copy of its output in memory, but an error in the input or in serialization
+	/// can leave a truncated document (like half of a JSON object) in the output.
+	///
+	/// The default is `true`, and translators with a
+	/// [document sink](Translator::with_document_sink) ignore this setting.
+	pub fn set_atomic_documents(&mut self, atomic: bool) {
+		self.output.get_mut().set_enabled(atomic);
+	}
+
+	/// Adds a transform to apply to every document before output.
+	///
+	/// Transforms run in the order they were added. A translator with any transforms fully
+	/// deserializes each document into memory before applying them, even for inputs that would
+	/// otherwise stream.
+	pub fn add_transform(&mut self, transform: Transform) {
+		self.transforms.push(transform);
+	}
+
+	/// Sets the name of the source for the inputs that follow, as reported by the
+	/// [`provenance`](Transform::provenance) transform.
+	///
+	/// The name applies to every later input until changed. The default is `None`.
+	pub fn set_source_name(&mut self, name: Option<String>) {
+		self.source_name = name;
+	}
+
+	/// Sets a function to call with the result of translating each document, which decides
+	/// whether translation continues.
+	///
+	/// The translator calls the handler after it finishes with each document from an input,
+	/// including documents that transforms or document selection leave out of the output. An
+	/// [`Ok`] result summarizes a document that translated successfully. An [`Err`] result is the
+	/// error from a document that failed to translate after the translator fully parsed it, such
+	/// as an error from a transform or from the output format. When the handler returns [`Ok`],
+	/// translation continues with the next document, leaving the failed document out of the
+	/// output. When it returns an error, translation stops with that error.
+	///
+	/// Errors in the input itself, like syntax errors, always stop translation of that input
+	/// without a call to the handler, since the input format can't find the next document after
+	/// them.
+	///
+	/// A translator with a handler fully deserializes each document into memory before
+	/// serializing it, so that it can tell these kinds of errors apart, and always serializes on
+	/// the calling thread. Leaving out failed documents relies on
+	/// [atomic documents](Translator::set_atomic_documents) to discard any part of them that the
+	/// output format already wrote.
+	pub fn set_document_handler<F>(&mut self, handler: F)
+	where
+		F: FnMut(Result<DocumentSummary>) -> Result<()> + Send + 'static,
+	{
+		self.document_handler = Some(Box::new(handler));
+	}
+
+	/// Sets a function to call with the range of bytes that each document spans in its input, for
+	/// callers that need to slice the raw source of particular documents out of the input later.
+	///
+	/// The translator calls the handler for every document of an input whose bounds the input
+	/// format finds, including documents that transforms or document selection leave out of the
+	/// output: JSON documents other than those from
+	/// [split arrays](Translator::set_split_json_arrays), MessagePack and TOML documents, and
+	/// documents from UTF-8 YAML streams. Documents from
+	/// slices and YAML streams get their span before they translate, and other documents from
+	/// readers get theirs once they've translated successfully, so a document that stops
+	/// translation (by failing, or by filling the [head](Translator::set_head)) may not get one.
+	///
+	/// Spans of documents from a chain of [readers](Translator::translate_readers) are relative
+	/// to the reader containing them, like the offsets of the [`provenance`](Transform::provenance)
+	/// transform. YAML documents from a chain have spans relative to the chain as a whole.
+	///
+	/// YAML slices go through the same document splitting as YAML readers to find the spans.
+	pub fn set_document_span_handler<F>(&mut self, handler: F)
+	where
+		F: FnMut(DocumentSpan) + Send + 'static,
+	{
+		self.span_handler = Some(Box::new(handler));
+	}
+
+	/// Sets a function to call with a warning for each YAML scalar that parsers other than xt are
+	/// likely to misinterpret.
+	///
+	/// xt follows the YAML 1.2 core schema, where plain scalars like `no`, `0123`, `1:20`, and
+	/// `2001-12-14` are strings. Parsers that follow YAML 1.1 read them as a boolean, an octal
+	/// integer, a base 60 number, and a timestamp, respectively. With a handler, the translator
+	/// warns about every such scalar in YAML input that isn't quoted or tagged, which xt reads as
+	/// a string where the author may have intended another type, and about every such string in
+	/// YAML output that the serializer writes without quotes. Warnings don't affect translation.
+	///
+	/// Linting YAML input parses it one extra time, and linting YAML output fully deserializes
+	/// each document into memory and serializes it on the calling thread.
+	pub fn set_yaml_warning_handler<F>(&mut self, handler: F)
+	where
+		F: FnMut(YamlWarning) + Send + 'static,
+	{
+		self.yaml_lint = Some(yaml::lint::Lint::new(Box::new(handler)));
+	}
+
+	/// Sets a function to call for each stretch of corrupt MessagePack input that the translator
+	/// skips, rather than failing on it.
+	///
+	/// With a handler, a MessagePack document that fails to decode doesn't stop translation.
+	/// Instead, the translator scans forward for the next byte that starts a map or array that
+	/// decodes in full, calls the handler, and resumes translation there, or skips the rest of
+	/// the input if it finds no such byte. This is a best effort for long-lived streams like log
+	/// buffers, where losing a record is better than losing every record after it: a resumed
+	/// document could turn out to be a value nested inside the corrupt one.
+	///
+	/// Resynchronization reads each MessagePack input into memory in full, and decodes every
+	/// document before translating it, even for output that could otherwise skip serde. It
+	/// disregards the [maximum document size](Translator::set_max_document_size).
+	pub fn set_msgpack_resync_handler<F>(&mut self, handler: F)
+	where
+		F: FnMut(MsgpackResync) + Send + 'static,
+	{
+		self.msgpack_resync = Some(Box::new(handler));
+	}
+
+	/// Sets a writer for a sidecar index of the documents that the translator outputs, serialized
+	/// as a separate document per entry in the given format.
+	///
+	/// Each entry is a map containing the zero-based index of the document within the output
+	/// under `document`, and the range of bytes that the document occupies in the output under
+	/// `output_offset` (inclusive) and `output_end` (exclusive). For a document translated from a
+	/// single input document, the entry also contains the same `source` and `input` as the
+	/// [`provenance`](Transform::provenance) transform when known, the index of the input document
+	/// under `input_document`, the byte offset where it starts under `input_offset` when the input
+	/// format reports it, and the byte offset just past its end under `input_end` when the input
+	/// format splits its input into documents before translating them (like JSON and MessagePack
+	/// slices, and TOML).
+	///
+	/// The index needs a format that supports multiple documents. A translator with an index
+	/// always serializes on the calling thread, and flushes the index along with its output.
+	pub fn set_index_output<X>(&mut self, output: X, format: Format)
+	where
+		X: Write + Send + 'static,
+	{
+		self.index = Some(index::Index::new(Box::new(output), format));
+	}
+
+	/// Sets the number of documents to skip at the start of the stream.
+	///
+	/// The translator counts documents across all of its inputs, after applying any transforms, and
+	/// still parses the skipped documents to find the ones that follow. The default is 0.
+	pub fn set_skip(&mut self, skip: u64) {
+		self.window.set_skip(skip);
+	}
+
+	/// Sets the number of bytes to skip at the start of every input before translating it.
+	///
+	/// Skipping bytes lets a translation start from the known offset of a document within a large
+	/// input, such as one reported by the [`provenance`](Transform::provenance) transform, without
+	/// parsing everything before it. The offsets of documents after the skipped bytes are relative
+	/// to the end of the skipped bytes. The default is 0.
+	pub fn set_skip_bytes(&mut self, skip: u64) {
+		self.skip_bytes = skip;
+	}
+
+	/// Sets the number of documents to skip at the start of every input before translating it,
+	/// after any [skipped bytes](Translator::set_skip_bytes).
+	///
+	/// Unlike [`set_skip`](Translator::set_skip), this counts the documents that the input format
+	/// produces, before any transforms, so that a translation can resume from the index of a
+	/// document within its input, such as one reported by the
+	/// [`provenance`](Transform::provenance) transform or by an [`Error`]. Skipped documents still
+	/// count toward the index of each later document, but the translator streams through them
+	/// without holding them in memory, running transforms, or reporting them to any
+	/// [document handler](Translator::set_document_handler). The default is 0.
+	pub fn set_skip_documents(&mut self, skip: u64) {
+		self.skip_documents = skip;
+	}
+
+	/// Sets the maximum number of documents to output after any skipped documents.
+	///
+	/// Once the translator has output that many documents, it stops reading the current input,
+	/// and ignores every later input without reading it at all. The default is `None`, with no
+	/// limit.
+	pub fn set_head(&mut self, head: Option<u64>) {
+		self.window.set_head(head);
+	}
+
+	/// Sets the number of documents to output from the end of the stream.
+	///
+	/// The translator holds back the most recent documents in memory, and only outputs them when
+	/// [`finish`](Translator::finish) marks the end of the stream. Combined with
+	/// [`set_head`](Translator::set_head), the tail covers the last documents within the head.
+	/// The default is `None`, which outputs every document as soon as possible.
+	pub fn set_tail(&mut self, tail: Option<usize>) {
+		self.window.set_tail(tail);
+	}
+
+	/// Sets a flag that interrupts the stream once set from any thread, like a signal handler.
+	///
+	/// After the flag is set, the translator finishes outputting the current document, then stops
+	/// reading the current input and ignores every later input without reading it at all, as if
+	/// the [head](Translator::set_head) were full. [`finish`](Translator::finish) still outputs any
+	/// held documents and flushes the underlying writer, so the output ends with the last complete
+	/// document. The translator only checks the flag between documents, so a reader that blocks
+	/// on slow input delays the interruption until it returns. The default is `None`.
+	pub fn set_interrupt_flag(&mut self, flag: Option<Arc<AtomicBool>>) {
+		self.window.set_interrupt(flag);
+	}
+
+	/// Sets the longest wall-clock time that translating any single input may take, or `None` to
+	/// remove the limit.
+	///
+	/// The time starts when a `translate_*` method starts translating the input, and covers format
+	/// detection along with translation. Once it runs out, the translator fails the input with a
+	/// timeout error at the next document or read from a reader, whichever comes first, and
+	/// keeps the output of every document before that. The translator can't interrupt a read
+	/// that's already blocked, so a reader that never returns still hangs translation, but a read
+	/// that returns late fails even if it read something. Readers that can time out on their own,
+	/// like sockets with a read timeout, bound the wait for a stalled source. The default is
+	/// `None`.
+	pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+		self.timeout = timeout;
+	}
+
+	/// Sets whether to deep-merge every document in the stream into a single document, and how to
+	/// merge arrays.
+	///
+	/// Maps merge recursively by key, arrays merge according to the given strategy, and any other
+	/// value from a later document replaces the value from an earlier one. The translator holds
+	/// the merged document in memory, and only outputs it when [`finish`](Translator::finish)
+	/// marks the end of the stream. Merging applies after any transforms and document selection.
+	///
+	/// The default is `None`, which outputs every document separately.
+	pub fn set_merge(&mut self, arrays: Option<ArrayMerge>) {
+		self.merge = arrays.map(merge::Merge::new);
+		if let Some(merge) = &mut self.merge {
+			merge.set_intern_keys(self.intern_keys);
+			merge.set_report_conflicts(self.report_merge_conflicts);
+			merge.set_attribute_sources(self.attribute_merge_sources);
+		}
+	}
+
+	/// Sets whether [merging](Translator::set_merge) outputs a report of the conflicts between
+	/// documents, rather than the merged document.
+	///
+	/// A conflict is a path where a later document replaces a different value from an earlier
+	/// one: a scalar with another scalar, a value with one of a different type, or an array with
+	/// another under [`ArrayMerge::Replace`]. The report is a single array with a map for every
+	/// path with a conflict, in the order of its first conflict, containing the path in the
+	/// syntax of [`Transform::select`] under `path`, the [source name](Translator::set_source_name)
+	/// of the document whose value won under `winner`, and every value that the path took under
+	/// `values`, as maps of the `source` that set it and the `value` itself. Documents held for
+	/// the [tail](Translator::set_tail) have a null source.
+	///
+	/// The default is `false`, which outputs the merged document.
+	pub fn set_report_merge_conflicts(&mut self, report: bool) {
+		self.report_merge_conflicts = report;
+		if let Some(merge) = &mut self.merge {
+			merge.set_report_conflicts(report);
+		}
+	}
+
+	/// Sets whether [merging](Translator::set_merge) follows the merged document with a second
+	/// document attributing each of its values to a source.
+	///
+	/// The second document has the same shape as the merged document, with the
+	/// [source name](Translator::set_source_name) of the input that set each value other than a
+	/// non-empty map or array in place of the value, or null for an input without a name (like a
+	/// document held for the [tail](Translator::set_tail)). A value that several inputs set
+	/// identically comes from the first of them. This doesn't apply to a
+	/// [report of conflicts](Translator::set_report_merge_conflicts), which stands alone.
+	///
+	/// The default is `false`, which outputs the merged document alone.
+	pub fn set_attribute_merge_sources(&mut self, attribute: bool) {
+		self.attribute_merge_sources = attribute;
+		if let Some(merge) = &mut self.merge {
+			merge.set_attribute_sources(attribute);
+		}
+	}
+
+	/// Sets a path expression like `.kind` to group every document in the stream by, outputting a
+	/// single map from each value at the path to an array of the documents with that value.
+	///
+	/// Groups appear in the order of their first documents, and documents without a value at the
+	/// path fall into a group with a null key. The translator holds every document in memory, and
+	/// only outputs the map of groups when [`finish`](Translator::finish) marks the end of the
+	/// stream. Grouping applies after any transforms and document selection, and before
+	/// [merging](Translator::set_merge).
+	///
+	/// Paths use the same syntax as [`Transform::select`]. The default is `None`, which outputs
+	/// every document separately.
+	pub fn set_group_by(&mut self, expr: Option<&str>) -> Result<()> {
+		self.group = expr.map(group::Grouping::new).transpose()?;
+		if let Some(group) = &mut self.group {
+			group.set_intern_keys(self.intern_keys);
+		}
+		Ok(())
+	}
+
+	/// Sets an order for every document in the stream other than the order of the inputs, or
+	/// `None` to output each input's documents in turn.
+	///
+	/// The translator holds every document in memory, and only outputs them in the new order when
+	/// [`finish`](Translator::finish) marks the end of the stream. Reordering applies after any
+	/// transforms and document selection, and before [grouping](Translator::set_group_by). Every
+	/// call to translate an input starts a new input for
+	/// [interleaving](StreamOrder::Interleave).
+	///
+	/// Paths for [sorting](StreamOrder::SortBy) use the same syntax as [`Transform::select`].
+	/// Returns an error if the path is invalid. The default is `None`.
+	pub fn set_stream_order(&mut self, order: Option<StreamOrder>) -> Result<()> {
+		self.order = order.as_ref().map(order::Reorder::new).transpose()?;
+		if let Some(order) = &mut self.order {
+			order.set_intern_keys(self.intern_keys);
+		}
+		Ok(())
+	}
+
+	/// Sets whether to output a JSON Schema inferred from every document in the stream, rather
+	/// than the documents themselves.
+	///
+	/// The schema records the types of the values observed at each position, with map properties
+	/// present in every map at a position marked as required. Strings at a position with at most
+	/// 10 distinct values, at least one of which repeats, form an enum. The schema itself is a
+	/// single document in the output format, which the translator only outputs when
+	/// [`finish`](Translator::finish) marks the end of the stream. Inference applies after any
+	/// transforms, document selection, and [merging](Translator::set_merge).
+	///
+	/// The default is `false`.
+	pub fn set_infer_schema(&mut self, infer: bool) {
+		self.schema = infer.then(|| schema::Inference::new(schema::Target::JsonSchema));
+	}
+
+	/// Sets whether to output Rust type definitions inferred from every document in the stream,
+	/// rather than the documents themselves.
+	///
+	/// This uses the same inference as [`set_infer_schema`](Translator::set_infer_schema), and
+	/// replaces any earlier setting of it. The definitions are Rust source text written directly
+	/// to the underlying writer, regardless of the output format. Every map becomes a struct
+	/// deriving Serde's `Serialize` and `Deserialize` traits, with the root of every document
+	/// described by a type named `Root`, and values with mixed types described by
+	/// `serde_json::Value`.
+	///
+	/// The default is `false`.
+	#[cfg(feature = "rust-types")]
+	pub fn set_infer_rust_types(&mut self, infer: bool) {
+		self.schema = infer.then(|| schema::Inference::new(schema::Target::RustTypes));
+	}
+
+	/// Sets whether to output the number of values of each type observed at each path of every
+	/// document in the stream, rather than the documents themselves, to find paths whose values
+	/// have inconsistent types.
+	///
+	/// The counts are a single document in the output format, mapping each path to a map from the
+	/// name of each type observed there (`null`, `bool`, `int`, `float`, `string`, `bytes`,
+	/// `array`, or `map`) to its count. Paths use the syntax of [`Transform::select`], with `[]`
+	/// for every element of an array, like `.hosts[].port`. The translator only outputs the counts
+	/// when [`finish`](Translator::finish) marks the end of the stream. This replaces any earlier
+	/// setting of [`set_infer_schema`](Translator::set_infer_schema), and applies at the same
+	/// point of translation.
+	///
+	/// The default is `false`.
+	pub fn set_type_stats(&mut self, stats: bool) {
+		self.schema = stats.then(|| schema::Inference::new(schema::Target::TypeStats));
+	}
+
+	/// Sets the key of an array of tables that wraps every document in TOML output.
+	///
+	/// TOML has no concept of multiple documents in a single stream, so translation to TOML
+	/// normally fails when the input contains more than one document. With a container key,
+	/// the translator instead outputs each document as an entry in a root-level array of tables
+	/// with that key (e.g. `[[documents]]`). Since the translator can't know the number of input
+	/// documents in advance, the container wraps even a single document. Every document must
+	/// still have a table at its root.
+	///
+	/// This setting has no effect for formats other than TOML. The default is `None`.
+	pub fn set_toml_container(&mut self, key: Option<String>) {
+		if let Dispatcher::Toml(output) = &mut self.output {
+			output.set_container(key);
+		}
+	}
+
+	/// Sets what CSV and TSV output does with a record whose keys differ from the fields of the
+	/// header, which come from the keys of the first record.
+	///
+	/// This setting has no effect for other formats. The default is [`CsvMismatch::Error`].
+	pub fn set_csv_mismatch(&mut self, mismatch: CsvMismatch) {
+		if let Dispatcher::Csv(output) = &mut self.output {
+			output.set_mismatch(mismatch);
+		}
+	}
+
+	/// Sets the message type of Protocol Buffers input and output, or `None` for no message type.
+	///
+	/// Translation from or to [`Format::Protobuf`] fails without a message type. The default is
+	/// `None`.
+	pub fn set_protobuf_message(&mut self, message: Option<ProtobufMessage>) {
+		if let Dispatcher::Protobuf(output) = &mut self.output {
+			output.set_message(message.clone());
+		}
+		self.protobuf_message = message;
+	}
+
+	/// Sets whether Protocol Buffers input and output hold a stream of messages that each follow
+	/// their length as a varint, rather than a single message.
+	///
+	/// Delimited messages are the usual way to store more than one message in a file or stream,
+	/// since messages can't mark their own ends. The default is `false`.
+	pub fn set_protobuf_delimited(&mut self, delimited: bool) {
+		if let Dispatcher::Protobuf(output) = &mut self.output {
+			output.set_delimited(delimited);
+		}
+		self.protobuf_delimited = delimited;
+	}
+
+	/// Sets what EDN input does with tagged literals like `#inst "2024-01-02T03:04:05Z"`.
+	///
+	/// EDN tags name types that only the reading application knows how to build, so xt can only
+	/// drop them or keep them as text. The default is [`EdnTags::Passthrough`].
+	pub fn set_edn_tags(&mut self, tags: EdnTags) {
+		self.edn_tags = tags;
+	}
+
+	/// Translates the contents of a single input slice to a different format.
+	///
+	/// Slices are typically more efficient to translate than readers, but require all input to be
+	/// available in memory. For unbounded streams like standard input or non-regular files,
+	/// consider [`translate_reader`] rather than manually buffering the entire reader.
+	///
+	/// When `from` is `None`, the translator attempts to detect the format from the input itself.
+	pub fn translate_slice(&mut self, input: &[u8], from: Option<Format>) -> Result<()> {
+		let input = usize::try_from(self.skip_bytes)
+			.ok()
+			.and_then(|skip| input.get(skip..))
+			.unwrap_or_default();
+		self.translate(input::Handle::from_slice(input), from, None)
+	}
+
+	/// Translates the contents of a single reader to a different format.
+	///
+	/// Reader inputs enable streaming translation for most formats, where the translator handles
+	/// multiple documents without buffering more than one in memory at a time. When translating
+	/// from a format without streaming support, the translator automatically buffers the entire
+	/// input.
+	///
+	/// When `from` is `None`, the translator attempts to detect the format from the input itself.
+	/// The current implementation must buffer at least one full document to perform the detection
+	/// before starting translation.
+	pub fn translate_reader<R>(&mut self, mut input: R, from: Option<Format>) -> Result<()>
+	where
+		R: Read,
+	{
+		if self.window.is_full() {
+			return Ok(());
+		}
+		io::copy(&mut (&mut input).take(self.skip_bytes), &mut io::sink())?;
+		self.translate(input::Handle::from_reader(input), from, None)
+	}
+
+	/// Translates the contents of an [`Input`] to a different format, starting from the beginning
+	/// of the input regardless of how much of it was peeked at.
+	///
+	/// This works like [`translate_slice`](Translator::translate_slice),
+	/// [`translate_reader`](Translator::translate_reader), or
+	/// [`translate_seekable`](Translator::translate_seekable), depending on how the input was
+	/// created.
+	pub fn translate_input(&mut self, input: Input<'_>, from: Option<Format>) -> Result<()> {
+		if self.window.is_full() {
+			return Ok(());
+		}
+		let mut input = input.0;
+		input.skip(self.skip_bytes)?;
+		self.translate(input, from, None)
+	}
+
+	/// Translates the contents of a sequence of readers to a different format, as a single
+	/// logical input.
+	///
+	/// This works like [`translate_reader`](Translator::translate_reader) with the concatenation
+	/// of every reader, where documents may span the boundaries between readers and bytes to
+	/// [skip](Translator::set_skip_bytes) apply to the start of the whole sequence. Unlike a
+	/// plain [`Read::chain`], the translator keeps track of which reader each document comes
+	/// from. For documents at offsets that the input format reports, errors carry the
+	/// [index](Error::input_index) of the reader containing the document, and the index and
+	/// offset of each document are relative to that reader, including for the
+	/// [provenance](Transform::provenance) transform and any
+	/// [document handler](Translator::set_document_handler). Other documents have locations
+	/// relative to the whole sequence.
+	pub fn translate_readers<I>(&mut self, inputs: I, from: Option<Format>) -> Result<()>
+	where
+		I: IntoIterator,
+		I::Item: Read,
+	{
+		if self.window.is_full() {
+			return Ok(());
+		}
+		let (mut input, boundaries) = input::ChainReader::new(inputs);
+		io::copy(&mut (&mut input).take(self.skip_bytes), &mut io::sink())?;
+		input.mark_origin();
+		let input = input::Handle::from_reader(input);
+		self.translate(input, from, Some(boundaries))
+	}
+
+	/// Translates the contents of a single seekable reader to a different format.
+	///
+	/// This works like [`translate_reader`](Translator::translate_reader), except that format
+	/// detection rewinds the reader by seeking back to its starting position rather than buffering
+	/// everything it reads in memory. Seekable readers are a good fit for regular files that can't
+	/// be memory mapped.
+	///
+	/// The reader must produce the same input every time it seeks back to a given position.
+	pub fn translate_seekable<R>(&mut self, mut input: R, from: Option<Format>) -> Result<()>
+	where
+		R: Read + Seek,
+	{
+		if self.window.is_full() {
+			return Ok(());
+		}
+		if self.skip_bytes > 0 {
+			input.seek(SeekFrom::Current(i64::try_from(self.skip_bytes)?))?;
+		}
+		self.translate(input::Handle::from_seekable_reader(input)?, from, None)
+	}
+
+	/// Translates a single serialized input to a different format, where `chain` is the set of
+	/// boundaries between readers for an input chained from several of them.
+	fn translate(
+		&mut self,
+		mut input: input::Handle<'_>,
+		from: Option<Format>,
+		chain: Option<Rc<input::Boundaries>>,
+	) -> Result<()> {
+		if self.window.is_full() {
+			return Ok(());
+		}
+		input.set_budget(&self.budget);
+		let deadline = self.timeout.map(timeout::Deadline::after);
+		if let Some(deadline) = deadline {
+			input.set_deadline(deadline);
+		}
+		if let Some(order) = &mut self.order {
+			order.start_input();
+		}
+		let from = match from {
+			Some(format) => format,
+			None => {
+				let detection = detect::detect(
+					&mut input,
+					&self.detection_order,
+					false,
+					self.detection_limit,
+				)?;
+				match detection.format() {
+					Some(format) => format,
+					None => return Err(detect::Undetected(detection).into()),
+				}
+			}
+		};
+		// YAML reads other Unicode encodings, but JSON and TOML inputs in them would only fail
+		// with confusing errors about invalid UTF-8 or unexpected characters.
+		let unsupported_encoding = match from {
+			Format::Json
+			| Format::JsonLines
+			| Format::Toml
+			| Format::Ini
+			| Format::Edn
+			| Format::Ron
+			| Format::Csv
+			| Format::Tsv => { Some(yaml::input_encoding(input.borrow_mut())?) }
+				.filter(|&encoding| encoding != Encoding::Utf8),
+			Format::Msgpack
+			| Format::Bson
+			| Format::Bencode
+			| Format::Avro
+			| Format::Protobuf
+			| Format::Yaml
+			| Format::Null => None,
+		};
+		// A string issue handler reports invalid UTF-8 and lone surrogates where they were, rather
+		// than having them fail the translation.
+		let repairable = self.string_issue_handler.is_some()
+			&& match from {
+				Format::Json
+				| Format::JsonLines
+				| Format::Toml
+				| Format::Ini
+				| Format::Edn
+				| Format::Ron
+				| Format::Csv
+				| Format::Tsv => unsupported_encoding.is_none(),
+				Format::Yaml => yaml::input_encoding(input.borrow_mut())? == Encoding::Utf8,
+				_ => false,
+			};
+		let repair_utf8 = self
+			.utf8_repair_handler
+			.as_deref_mut()
+			.filter(|_| match from {
+				Format::Json | Format::JsonLines => unsupported_encoding.is_none(),
+				Format::Msgpack => true,
+				_ => false,
+			});
+		let repaired;
+		let mut input = input;
+		if repairable || repair_utf8.is_some() {
+			let mut buffered: Cow<'_, [u8]> = input.try_into()?;
+			if let Some(handler) = repair_utf8 {
+				let (msgpack, source) = (from == Format::Msgpack, self.source_name.as_deref());
+				buffered = utf8::repair(buffered, msgpack, source, handler);
+			}
+			if repairable {
+				let json = matches!(from, Format::Json | Format::JsonLines);
+				buffered = audit::repair(buffered, json);
+			}
+			repaired = buffered;
+			input = input::Handle::from_slice(&repaired);
+		}
+		let (split, flush) = (self.split_json_arrays, self.flush_each_document);
+		let reject_duplicate_keys = self.reject_duplicate_keys;
+		let msgpack_keys = match from {
+			Format::Msgpack => self.msgpack_keys,
+			_ => MsgpackKeys::Preserve,
+		};
+		let known = self.known_keys.as_ref();
+		let unknown_key_handler = self.unknown_key_handler.as_deref_mut();
+		let string_issue_handler = self.string_issue_handler.as_deref_mut();
+		let (transforms, source) = (&self.transforms, self.source_name.as_deref());
+		let window = &mut self.window;
+		let (group, order, merge, schema) = (
+			self.group.as_mut(),
+			self.order.as_mut(),
+			self.merge.as_mut(),
+			self.schema.as_mut(),
+		);
+		let handler = self.document_handler.as_deref_mut();
+		let index = self.index.as_mut();
+		let lint = self.yaml_lint.as_mut().map(RefCell::new);
+		let input_lint = lint.as_ref().filter(|_| from == Format::Yaml);
+		let output_lint = lint.as_ref().filter(|_| self.output.is_yaml());
+		let resync = self.msgpack_resync.as_deref_mut().map(RefCell::new);
+		let resync = resync.as_ref().filter(|_| from == Format::Msgpack);
+		let comments = self.document_comments && self.output.supports_comments();
+		let holds = group.is_some() || order.is_some() || merge.is_some() || schema.is_some();
+		let serial =
+			holds || handler.is_some() || index.is_some() || output_lint.is_some() || comments;
+		let parallel = self.threads.get() > 1
+			&& self.to.is_multi_document()
+			&& !serial
+			&& !self.document_sink
+			&& self.output.supports_parallel();
+		// MessagePack to JSON and JSON to MessagePack are common enough to skip serde when
+		// nothing needs the documents as values, though not at the cost of parallel serialization.
+		let direct = !holds
+			&& transforms.is_empty()
+			&& !reject_duplicate_keys
+			&& known.is_none()
+			&& string_issue_handler.is_none()
+			&& msgpack_keys == MsgpackKeys::Preserve
+			&& !parallel
+			&& match from {
+				Format::Msgpack => {
+					self.output.is_json() && !self.translate_msgpack_ext && resync.is_none()
+				}
+				Format::Json | Format::JsonLines => self.output.is_msgpack() && !split,
+				_ => false,
+			};
+		let options = InputOptions {
+			split_json_arrays: split,
+			lenient_json: self.lenient_json,
+			reject_risky_yaml: self.reject_risky_yaml,
+			translate_msgpack_ext: self.translate_msgpack_ext,
+			direct,
+			yaml_lint: input_lint.map(|lint| (lint, source)),
+			msgpack_resync: resync.map(|resync| (resync, source)),
+			max_document_size: self.max_document_size,
+			document_spans: self.span_handler.is_some(),
+			protobuf_message: self.protobuf_message.as_ref(),
+			protobuf_delimited: self.protobuf_delimited,
+			edn_tags: self.edn_tags,
+		};
+		let (skip, max_size) = (self.skip_documents, self.max_document_size);
+		let spans = self.span_handler.as_deref_mut();
+		let mut next = NextDocument::new(chain, skip, max_size, deadline, spans);
+		let result = if parallel {
+			let (to, threads, intern) = (self.to, self.threads, self.intern_keys);
+			parallel::transcode(&mut self.output, to, threads, intern, |output| {
+				let output = window::Output::new(FlushEach::new(output, flush), window);
+				let output = transform::Output::new(output, transforms, source);
+				let output = known::Output::new(output, known, unknown_key_handler, source);
+				let output = audit::Output::new(output, string_issue_handler, source);
+				let output = strictness::Output::new(output, reject_duplicate_keys);
+				let output = msgpack::keys::Output::new(output, msgpack_keys);
+				transcode_input(input, from, options, Locate::new(output, &mut next, None))
+			})
+		} else if direct {
+			// Only the outputs that pass MessagePack through without serde belong in this chain.
+			let output = atomic::Output::new(&mut self.output);
+			let output = index::Output::new(output, index, source, false);
+			let output = FlushEach::new(output, flush);
+			let output = window::Output::new(output, window);
+			transcode_input(
+				input,
+				from,
+				options,
+				Locate::new(output, &mut next, handler),
+			)
+		} else {
+			let output = atomic::Output::new(&mut self.output);
+			let output = index::Output::new(output, index, source, comments);
+			let output = FlushEach::new(output, flush);
+			let output = yaml::lint::Output::new(output, output_lint);
+			let output = schema::Output::new(output, schema);
+			let output = merge::Output::new(output, merge, source);
+			let output = group::Output::new(output, group);
+			let output = order::Output::new(output, order);
+			let output = window::Output::new(output, window);
+			let output = transform::Output::new(output, transforms, source);
+			let output = known::Output::new(output, known, unknown_key_handler, source);
+			let output = audit::Output::new(output, string_issue_handler, source);
+			let output = strictness::Output::new(output, reject_duplicate_keys);
+			let output = msgpack::keys::Output::new(output, msgpack_keys);
+			transcode_input(
+				input,
+				from,
+				options,
+				Locate::new(output, &mut next, handler),
+			)
+		};
+		// Input formats that parse a document before passing it to the output fail outside of any
+		// output call, but the document they were parsing is still the next one.
+		let timed_out = deadline.map_or(Ok(()), timeout::Deadline::check);
+		let result = match (result, timed_out) {
+			(Err(err), _) if window::Stop::is(&err) => Ok(()),
+			// However the input format reported a read that failed on the deadline, the timeout
+			// is the cause.
+			(Err(err), Err(timeout)) => Err(err.replace(timeout).in_document(next.locate())),
+			(result, _) => result.map_err(|err| {
+				let err = match unsupported_encoding {
+					Some(encoding) => Error::from(encoding::UnsupportedInputError {
+						encoding,
+						format: from,
+					}),
+					None => err,
+				};
+				err.in_document(next.locate())
+			}),
+		};
+
+		// Output from a failed translation is flushed too, as it would have been if the caller's
+		// writer were unbuffered, so the caller can see every document that translated before the
+		// failure.
+		let flushed = self.flush();
+		result?;
+		Ok(flushed?)
+	}
+
+	/// Marks the end of the stream, outputting any documents held back for the
+	/// [tail](Translator::set_tail), [reordering](Translator::set_stream_order),
+	/// [grouping](Translator::set_group_by), [merging](Translator::set_merge), or
+	/// [schema inference](Translator::set_infer_schema), and flushing the underlying writer.
+	///
+	/// Translators without any of these output every document as soon as possible, and don't need
+	/// to be finished.
+	pub fn finish(&mut self) -> Result<()> {
+		let is_yaml = self.output.is_yaml();
+		let lint = self
+			.yaml_lint
+			.as_mut()
+			.filter(|_| is_yaml)
+			.map(RefCell::new);
+		let output = atomic::Output::new(&mut self.output);
+		let output = index::Output::new(output, self.index.as_mut(), None, false);
+		let output = FlushEach::new(output, self.flush_each_document);
+		let output = yaml::lint::Output::new(output, lint.as_ref());
+		let output = schema::Output::new(output, self.schema.as_mut());
+		// Documents held for the tail have already lost track of their inputs.
+		let output = merge::Output::new(output, self.merge.as_mut(), None);
+		let output = group::Output::new(output, self.group.as_mut());
+		let mut output = order::Output::new(output, self.order.as_mut());
+		let held = self.window.take_held();
+		let result = held
+			.into_iter()
+			.try_for_each(|value| output.transcode_value(value))
+			.and_then(|()| output.finish()?.finish()?.finish()?.finish())
+			.and_then(|text| match text {
+				Some(text) => Ok(self.output.get_mut().write_all(text.as_bytes())?),
+				None => Ok(()),
+			});
+		let flushed = self.flush();
+		result?;
+		Ok(flushed?)
+	}
+
+	/// [Flushes](Write::flush) the underlying writer, along with the writer of any
+	/// [index](Translator::set_index_output).
+	pub fn flush(&mut self) -> io::Result<()> {
+		(&mut self.output).flush()?;
+		match &mut self.index {
+			Some(index) => index.flush(),
+			None => Ok(()),
+		}
+	}
+}
+
+impl<S> Translator<DocumentWriter<S>>
+where
+	S: DocumentSink,
+{
+	/// Creates a translator that passes each complete output document in the given format to a
+	/// sink, rather than writing a single stream of bytes.
+	///
+	/// The sink receives exactly the bytes that a writer would have received for each document,
+	/// including any separator or trailing newline that the format writes with it, and a
+	/// [byte order mark](Translator::set_byte_order_mark) at the start of the first document only.
+	/// Output that the translator produces at the [finish](Translator::finish) of the stream
+	/// without documents of its own, like an inferred schema, reaches the sink as one document.
+	///
+	/// Translation to a sink always uses [atomic documents](Translator::set_atomic_documents),
+	/// and always serializes on the calling thread.
+	pub fn with_document_sink(sink: S, to: Format) -> Translator<DocumentWriter<S>> {
+		let mut translator = Translator::with_capacity(0, DocumentWriter::new(sink), to);
+		translator.output.get_mut().set_document_boundaries(true);
+		translator.document_sink = true;
+		translator
+	}
+}
 
-impl<W> Translator<W>
+/// A summary of a document that translated successfully, as passed to a
+/// [document handler](Translator::set_document_handler).
+#[derive(Debug, Clone)]
+pub struct DocumentSummary {
+	location: error::Location,
+}
+
+impl DocumentSummary {
+	/// Returns the zero-based index of the reader containing the document, for documents at
+	/// known offsets in a chain of [readers](Translator::translate_readers).
+	pub fn input_index(&self) -> Option<usize> {
+		self.location.input
+	}
+
+	/// Returns the zero-based index of the document within its input.
+	pub fn index(&self) -> u64 {
+		self.location.index
+	}
+
+	/// Returns the byte offset where the document starts within its input, if the input format
+	/// reports it.
+	pub fn offset(&self) -> Option<u64> {
+		self.location.offset
+	}
+}
+
+/// A function that handles the result of translating each document.
+type DocumentHandler = dyn FnMut(Result<DocumentSummary>) -> Result<()> + Send;
+
+/// The range of bytes that a document spans in its input, as passed to a
+/// [span handler](Translator::set_document_span_handler).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocumentSpan {
+	input: Option<usize>,
+	index: u64,
+	range: Range<u64>,
+}
+
+impl DocumentSpan {
+	/// Returns the zero-based index of the reader containing the document, for documents at
+	/// known offsets in a chain of [readers](Translator::translate_readers).
+	pub fn input_index(&self) -> Option<usize> {
+		self.input
+	}
+
+	/// Returns the zero-based index of the document within its input.
+	pub fn index(&self) -> u64 {
+		self.index
+	}
+
+	/// Returns the range of bytes that the document spans within its input, from the offset where
+	/// it starts (inclusive) to the offset just past its end (exclusive).
+	pub fn range(&self) -> Range<u64> {
+		self.range.clone()
+	}
+}
+
+/// A function that receives the span of each document.
+type SpanHandler = dyn FnMut(DocumentSpan) + Send;
+
+/// Transcodes a single input in a known format to the provided output.
+fn transcode_input<'i, O>(
+	input: input::Handle<'i>,
+	from: Format,
+	options: InputOptions,
+	output: O,
+) -> Result<()>
 where
-	W: Write,
+	O: Output<'i>,
 {
-	/// Creates a translator that produces output in the given format.
-	pub fn new(output: W, to: Format) -> Translator<W> {
-		Translator(Dispatcher::new(output, to))
+	match from {
+		Format::Json | Format::JsonLines => json::transcode(
+			input,
+			output,
+			options.split_json_arrays,
+			options.lenient_json,
+			options.direct,
+			options.max_document_size,
+		),
+		Format::Msgpack => msgpack::transcode(
+			input,
+			output,
+			options.translate_msgpack_ext,
+			options.direct,
+			options.max_document_size,
+			options.msgpack_resync,
+		),
+		Format::Toml => toml::transcode(input, output),
+		Format::Ini => ini::transcode(input, output),
+		Format::Edn => edn::transcode(input, output, options.edn_tags),
+		Format::Ron => ron::transcode(input, output),
+		Format::Bson => bson::transcode(input, output, options.max_document_size),
+		Format::Bencode => bencode::transcode(input, output),
+		Format::Avro => avro::transcode(input, output, options.max_document_size),
+		Format::Protobuf => protobuf::transcode(
+			input,
+			output,
+			options.protobuf_message,
+			options.protobuf_delimited,
+			options.max_document_size,
+		),
+		Format::Csv => csv::transcode(input, output, b','),
+		Format::Tsv => csv::transcode(input, output, b'\t'),
+		Format::Null => Err(null::InputError.into()),
+		Format::Yaml => yaml::transcode(
+			input,
+			output,
+			options.yaml_lint,
+			options.reject_risky_yaml,
+			options.max_document_size.is_some(),
+			options.document_spans,
+		),
 	}
+}
 
-	/// Translates the contents of a single input slice to a different format.
-	///
-	/// Slices are typically more efficient to translate than readers, but require all input to be
-	/// available in memory. For unbounded streams like standard input or non-regular files,
-	/// consider [`translate_reader`] rather than manually buffering the entire reader.
+/// The options of a translator that apply to a particular input format.
+#[derive(Clone, Copy)]
+struct InputOptions<'l, 'h, 's, 'p> {
+	split_json_arrays: bool,
+	lenient_json: bool,
+	reject_risky_yaml: bool,
+	translate_msgpack_ext: bool,
+	/// Whether documents go to the output as MessagePack through [`Output::transcode_msgpack`],
+	/// rather than through serde.
+	direct: bool,
+	yaml_lint: Option<yaml::Linter<'l, 'h, 's>>,
+	msgpack_resync: Option<msgpack::Resync<'l, 'h, 's>>,
+	max_document_size: Option<u64>,
+	/// Whether the translator reports the span of each document.
+	document_spans: bool,
+	protobuf_message: Option<&'p ProtobufMessage>,
+	protobuf_delimited: bool,
+	edn_tags: EdnTags,
+}
+
+/// The writer that a [`Translator`]'s output formats write to.
+type OutputWriter<W> = atomic::Writer<encoding::Writer<BufWriter<W>>>;
+
+fn output_writer<W: Write>(capacity: usize, output: W) -> OutputWriter<W> {
+	atomic::Writer::new(encoding::Writer::new(BufWriter::with_capacity(
+		capacity, output,
+	)))
+}
+
+/// The default capacity of a [`Translator`]'s output buffer, matching [`BufWriter::new`].
+const DEFAULT_BUFFER_CAPACITY: usize = 8 * 1024;
+
+/// A trait for output formats to receive their translatable input.
+///
+/// The `'i` lifetime is that of the original input. Input formats that can deserialize directly
+/// from an input slice pass borrowed data through the `transcode_borrowed_*` methods, so that
+/// outputs needing to hold on to values past a single call (like parallel serialization) can keep
+/// borrowing strings and bytes from the input instead of copying them.
+trait Output<'i> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static;
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		self.transcode_from(de)
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> Result<()>;
+
+	fn transcode_borrowed_value(&mut self, value: transcode::Value<'i>) -> Result<()> {
+		self.transcode_value(value)
+	}
+
+	/// Transcodes a slice holding exactly one MessagePack document, from MessagePack input or
+	/// from an input format that encodes its documents as MessagePack directly.
 	///
-	/// When `from` is `None`, the translator attempts to detect the format from the input itself.
-	pub fn translate_slice(&mut self, input: &[u8], from: Option<Format>) -> Result<()> {
-		self.translate(input::Handle::from_slice(input), from)
+	/// Outputs that can translate MessagePack without serde override this, along with the outputs
+	/// wrapping them on the way there. Everything else deserializes the document as usual.
+	fn transcode_msgpack(&mut self, input: &[u8]) -> Result<()>
+	where
+		Self: Sized,
+	{
+		msgpack::transcode_document(self, input)
 	}
 
-	/// Translates the contents of a single reader to a different format.
+	/// Notes the byte offset in the input where the next document starts.
 	///
-	/// Reader inputs enable streaming translation for most formats, where the translator handles
-	/// multiple documents without buffering more than one in memory at a time. When translating
-	/// from a format without streaming support, the translator automatically buffers the entire
-	/// input.
+	/// Input formats call this before transcoding each document whose offset they know, so the
+	/// offset applies only to the very next document.
+	fn set_document_offset(&mut self, _offset: u64) {}
+
+	/// Notes the byte offset in the input just past the end of the next document.
 	///
-	/// When `from` is `None`, the translator attempts to detect the format from the input itself.
-	/// The current implementation must buffer at least one full document to perform the detection
-	/// before starting translation.
-	pub fn translate_reader<R>(&mut self, input: R, from: Option<Format>) -> Result<()>
+	/// Input formats that split their input into documents before transcoding them call this
+	/// along with [`set_document_offset`](Output::set_document_offset).
+	fn set_document_end(&mut self, _end: u64) {}
+
+	/// Notes the size in bytes of the next document, for input formats that know it without
+	/// knowing where the document starts and ends in the input.
+	fn set_document_size(&mut self, _size: u64) {}
+
+	/// Notes the range of bytes that the next document spans in the input, for input formats that
+	/// only find it for the translator's [span handler](Translator::set_document_span_handler).
+	fn set_document_span(&mut self, _offset: u64, _end: u64) {}
+
+	/// Notes the byte offset in the input just past the end of the document that the input format
+	/// last transcoded successfully, for streaming input formats that only find it afterward.
+	fn set_last_document_end(&mut self, _end: u64) {}
+
+	/// Notes the location of the next document within its input.
+	///
+	/// A [`Locate`] output calls this before passing each document on, for outputs that need the
+	/// document's location (like the [`provenance`](Transform::provenance) transform) or that
+	/// report a document's errors after moving on to later ones (like parallel serialization).
+	fn set_document_location(&mut self, _location: error::Location) {}
+
+	fn flush(&mut self) -> io::Result<()>;
+}
+
+/// An [`Output`] that optionally flushes after every document.
+struct FlushEach<O> {
+	output: O,
+	enabled: bool,
+}
+
+impl<O> FlushEach<O> {
+	fn new(output: O, enabled: bool) -> FlushEach<O> {
+		FlushEach { output, enabled }
+	}
+
+	fn finish_document<'i>(&mut self) -> Result<()>
 	where
-		R: Read,
+		O: Output<'i>,
 	{
-		self.translate(input::Handle::from_reader(input), from)
+		if self.enabled {
+			self.output.flush()?;
+		}
+		Ok(())
 	}
+}
 
-	/// Translates a single serialized input to a different format.
-	fn translate(&mut self, mut input: input::Handle<'_>, from: Option<Format>) -> Result<()> {
-		let from = match from {
-			Some(format) => format,
-			None => match Format::detect(&mut input)? {
-				Some(format) => format,
-				None => return Err("unable to detect input format".into()),
-			},
+impl<'i, O> Output<'i> for FlushEach<O>
+where
+	O: Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		self.output.transcode_from(de)?;
+		self.finish_document()
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		self.output.transcode_borrowed_from(de)?;
+		self.finish_document()
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> Result<()> {
+		self.output.transcode_value(value)?;
+		self.finish_document()
+	}
+
+	fn transcode_borrowed_value(&mut self, value: transcode::Value<'i>) -> Result<()> {
+		self.output.transcode_borrowed_value(value)?;
+		self.finish_document()
+	}
+
+	fn transcode_msgpack(&mut self, input: &[u8]) -> Result<()> {
+		self.output.transcode_msgpack(input)?;
+		self.finish_document()
+	}
+
+	fn set_document_location(&mut self, location: error::Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+/// An [`Output`] that attaches the location of each document within its input to any error from
+/// translating it.
+///
+/// The output tracks the location of the next document in a caller-owned [`NextDocument`], so
+/// that the caller can attach it to errors from the input format between documents. With a
+/// [`DocumentHandler`], the output also reports the result of each document to the handler.
+struct Locate<'l, 's, O> {
+	output: O,
+	next: &'l mut NextDocument<'s>,
+	handler: Option<&'l mut DocumentHandler>,
+}
+
+impl<'l, 's, O> Locate<'l, 's, O> {
+	fn new(
+		output: O,
+		next: &'l mut NextDocument<'s>,
+		handler: Option<&'l mut DocumentHandler>,
+	) -> Locate<'l, 's, O> {
+		Locate {
+			output,
+			next,
+			handler,
+		}
+	}
+
+	/// Returns the location of the next document, and passes it on to the output, along with an
+	/// error if the document is known to exceed the maximum document size.
+	///
+	/// Fails right away if the input runs out of time before the document.
+	fn next_location<'i>(&mut self) -> Result<(error::Location, Result<()>)>
+	where
+		O: Output<'i>,
+	{
+		if let Some(deadline) = self.next.deadline {
+			deadline.check()?;
+		}
+		let size = self.next.check_size();
+		let location = self.next.advance();
+		self.output.set_document_location(location);
+		Ok((location, size))
+	}
+
+	/// Skips the next document by deserializing it without keeping any of it.
+	fn skip_document<'de, D, E>(&mut self, de: D) -> Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		let location = self.next.advance();
+		de::IgnoredAny::deserialize(de).map_err(|err| Error::from(err).in_document(location))?;
+		Ok(())
+	}
+
+	/// Attaches a document's location to any error from translating it, and reports the result
+	/// to the handler if there is one.
+	fn finish_document(&mut self, location: error::Location, result: Result<()>) -> Result<()> {
+		let result = result.map_err(|err| err.in_document(location));
+		let Some(handler) = &mut self.handler else {
+			return result;
 		};
-		match from {
-			Format::Json => json::transcode(input, &mut self.0),
-			Format::Msgpack => msgpack::transcode(input, &mut self.0),
-			Format::Toml => toml::transcode(input, &mut self.0),
-			Format::Yaml => yaml::transcode(input, &mut self.0),
+		let summary = DocumentSummary { location };
+		match result {
+			// A full window stops translation right after the document that filled it, which
+			// translated successfully.
+			Err(err) if window::Stop::is(&err) => {
+				handler(Ok(summary)).map_err(|err| err.in_document(location))?;
+				Err(err)
+			}
+			result => handler(result.map(|()| summary)).map_err(|err| err.in_document(location)),
 		}
 	}
+}
 
-	/// [Flushes](Write::flush) the underlying writer.
-	pub fn flush(&mut self) -> io::Result<()> {
-		(&mut self.0).flush()
+/// The location of the next document in an input, as tracked by a [`Locate`] output.
+///
+/// For an input chained from several readers, documents at known offsets are attributed to the
+/// reader containing them, and counted from the first document of that reader.
+struct NextDocument<'s> {
+	/// The location of the next document within the input as a whole.
+	location: error::Location,
+	chain: Option<ChainPosition>,
+	/// The number of documents to skip at the start of the input.
+	skip: u64,
+	/// The size of the next document, for input formats that report it without its bounds.
+	size: Option<u64>,
+	max_size: Option<u64>,
+	deadline: Option<timeout::Deadline>,
+	/// The span of the next document within the whole input, for input formats that report it
+	/// without its bounds.
+	span: Option<(u64, u64)>,
+	/// The location of the last document, and its offset within the whole input, while its end
+	/// is still unknown.
+	open_span: Option<(error::Location, u64)>,
+	span_handler: Option<&'s mut SpanHandler>,
+}
+
+/// The reader of a chained input that the last document at a known offset came from.
+struct ChainPosition {
+	boundaries: Rc<input::Boundaries>,
+	input: usize,
+	/// The index within the whole input of the first document from the reader.
+	first_index: u64,
+}
+
+impl<'s> NextDocument<'s> {
+	fn new(
+		chain: Option<Rc<input::Boundaries>>,
+		skip: u64,
+		max_size: Option<u64>,
+		deadline: Option<timeout::Deadline>,
+		span_handler: Option<&'s mut SpanHandler>,
+	) -> NextDocument<'s> {
+		NextDocument {
+			location: error::Location::default(),
+			skip,
+			size: None,
+			max_size,
+			deadline,
+			span: None,
+			open_span: None,
+			span_handler,
+			chain: chain.map(|boundaries| ChainPosition {
+				boundaries,
+				input: 0,
+				first_index: 0,
+			}),
+		}
+	}
+
+	/// Returns true if the next document falls among those to skip.
+	fn skips_next(&self) -> bool {
+		self.location.index < self.skip
+	}
+
+	/// Returns an error if the next document is known to exceed the maximum document size.
+	fn check_size(&self) -> Result<()> {
+		let size = match (self.location.offset, self.location.end) {
+			(Some(offset), Some(end)) => Some(end - offset),
+			_ => self.size,
+		};
+		match (size, self.max_size) {
+			(Some(size), Some(max)) if size > max => Err(input::DocumentSizeError {
+				size: Some(size),
+				max,
+			}
+			.into()),
+			_ => Ok(()),
+		}
+	}
+
+	/// Returns the location of the next document, and moves on to the document after it.
+	///
+	/// With a span handler, this reports the span of the document if it's fully known, or holds
+	/// the document for the input format to report its end.
+	fn advance(&mut self) -> error::Location {
+		let (offset, end) = (self.location.offset, self.location.end);
+		let location = self.locate();
+		if self.span_handler.is_some() {
+			self.open_span = None;
+			match (offset, end, self.span) {
+				(Some(offset), Some(end), _) | (_, _, Some((offset, end))) => {
+					self.report_span(location, offset, end);
+				}
+				(Some(offset), None, None) => self.open_span = Some((location, offset)),
+				_ => {}
+			}
+		}
+		self.location.index += 1;
+		self.location.offset = None;
+		self.location.end = None;
+		self.size = None;
+		self.span = None;
+		location
+	}
+
+	/// Reports the span of the last document once the input format finds its end.
+	fn end_open_span(&mut self, end: u64) {
+		if let Some((location, offset)) = self.open_span.take() {
+			self.report_span(location, offset, end);
+		}
+	}
+
+	/// Reports the span of a document from its location and its bounds within the whole input.
+	fn report_span(&mut self, location: error::Location, offset: u64, end: u64) {
+		let Some(handler) = &mut self.span_handler else {
+			return;
+		};
+		// Located documents have offsets relative to their reader.
+		let start = location.offset.unwrap_or(offset);
+		handler(DocumentSpan {
+			input: location.input,
+			index: location.index,
+			range: start..start + (end - offset),
+		});
+	}
+
+	/// Returns the location of the next document, relative to its reader for a chained input.
+	fn locate(&mut self) -> error::Location {
+		let location = self.location;
+		let (Some(chain), Some(offset)) = (&mut self.chain, location.offset) else {
+			return location;
+		};
+		let (input, local) = chain.boundaries.locate(offset);
+		if input != chain.input {
+			chain.input = input;
+			chain.first_index = location.index;
+		}
+		error::Location {
+			input: Some(input),
+			index: location.index - chain.first_index,
+			offset: Some(local),
+			end: location.end.map(|end| local + (end - offset)),
+		}
 	}
 }
 
-/// A trait for output formats to receive their translatable input.
-trait Output {
+/// Deserializes a document for a [`Locate`] with a handler, so that input errors can bypass it.
+fn deserialize_document<'de, D, E>(
+	de: D,
+	location: error::Location,
+) -> Result<transcode::Value<'de>>
+where
+	D: de::Deserializer<'de, Error = E>,
+	E: de::Error + Send + Sync + 'static,
+{
+	transcode::Value::deserialize(de).map_err(|err| Error::from(err).in_document(location))
+}
+
+impl<'i, O> Output<'i> for Locate<'_, '_, O>
+where
+	O: Output<'i>,
+{
 	fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<()>
 	where
 		D: de::Deserializer<'de, Error = E>,
-		E: de::Error + Send + Sync + 'static;
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.next.skips_next() {
+			return self.skip_document(de);
+		}
+		let (location, size) = self.next_location()?;
+		if size.is_err() {
+			return self.finish_document(location, size);
+		}
+		let result = match self.handler {
+			None => self.output.transcode_from(de),
+			Some(_) => {
+				let value = deserialize_document(de, location)?;
+				self.output.transcode_value(value)
+			}
+		};
+		self.finish_document(location, result)
+	}
 
-	fn transcode_value<S>(&mut self, value: S) -> Result<()>
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> Result<()>
 	where
-		S: ser::Serialize;
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.next.skips_next() {
+			return self.skip_document(de);
+		}
+		let (location, size) = self.next_location()?;
+		if size.is_err() {
+			return self.finish_document(location, size);
+		}
+		let result = match self.handler {
+			None => self.output.transcode_borrowed_from(de),
+			Some(_) => {
+				let value = deserialize_document(de, location)?;
+				self.output.transcode_borrowed_value(value)
+			}
+		};
+		self.finish_document(location, result)
+	}
 
-	fn flush(&mut self) -> io::Result<()>;
+	fn transcode_value(&mut self, value: transcode::Value) -> Result<()> {
+		if self.next.skips_next() {
+			self.next.advance();
+			return Ok(());
+		}
+		let (location, size) = self.next_location()?;
+		if size.is_err() {
+			return self.finish_document(location, size);
+		}
+		let result = self.output.transcode_value(value);
+		self.finish_document(location, result)
+	}
+
+	fn transcode_borrowed_value(&mut self, value: transcode::Value<'i>) -> Result<()> {
+		if self.next.skips_next() {
+			self.next.advance();
+			return Ok(());
+		}
+		let (location, size) = self.next_location()?;
+		if size.is_err() {
+			return self.finish_document(location, size);
+		}
+		let result = self.output.transcode_borrowed_value(value);
+		self.finish_document(location, result)
+	}
+
+	fn transcode_msgpack(&mut self, input: &[u8]) -> Result<()> {
+		if self.handler.is_some() {
+			return msgpack::transcode_document(self, input);
+		}
+		if self.next.skips_next() {
+			self.next.advance();
+			return Ok(());
+		}
+		let (location, size) = self.next_location()?;
+		if size.is_err() {
+			return self.finish_document(location, size);
+		}
+		let result = self.output.transcode_msgpack(input);
+		self.finish_document(location, result)
+	}
+
+	fn set_document_offset(&mut self, offset: u64) {
+		self.next.location.offset = Some(offset);
+	}
+
+	fn set_document_end(&mut self, end: u64) {
+		self.next.location.end = Some(end);
+	}
+
+	fn set_document_size(&mut self, size: u64) {
+		self.next.size = Some(size);
+	}
+
+	fn set_document_span(&mut self, offset: u64, end: u64) {
+		self.next.span = Some((offset, end));
+	}
+
+	fn set_last_document_end(&mut self, end: u64) {
+		self.next.end_open_span(end);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
 }
 
 /// An [`Output`] supporting static dispatch based on a known output format.
@@ -171,7 +2210,18 @@ where
 	Json(json::Output<W>),
 	Msgpack(msgpack::Output<W>),
 	Toml(toml::Output<W>),
+	Ini(ini::Output<W>),
+	Edn(edn::Output<W>),
+	Ron(ron::Output<W>),
+	Bencode(bencode::Output<W>),
 	Yaml(yaml::Output<W>),
+	Null(null::Output<W>),
+	Csv(csv::Output<W>),
+	Bson(bson::Output<W>),
+	Avro(avro::Output<W>),
+	Protobuf(protobuf::Output<W>),
+	#[cfg(feature = "template")]
+	Template(template::Output<W>),
 }
 
 impl<W> Dispatcher<W>
@@ -180,15 +2230,95 @@ where
 {
 	fn new(writer: W, to: Format) -> Dispatcher<W> {
 		match to {
-			Format::Json => Dispatcher::Json(json::Output::new(writer)),
+			Format::Json | Format::JsonLines => Dispatcher::Json(json::Output::new(writer)),
 			Format::Msgpack => Dispatcher::Msgpack(msgpack::Output::new(writer)),
 			Format::Toml => Dispatcher::Toml(toml::Output::new(writer)),
+			Format::Ini => Dispatcher::Ini(ini::Output::new(writer)),
+			Format::Edn => Dispatcher::Edn(edn::Output::new(writer)),
+			Format::Ron => Dispatcher::Ron(ron::Output::new(writer)),
+			Format::Bencode => Dispatcher::Bencode(bencode::Output::new(writer)),
 			Format::Yaml => Dispatcher::Yaml(yaml::Output::new(writer)),
+			Format::Null => Dispatcher::Null(null::Output::new(writer)),
+			Format::Csv | Format::Tsv => Dispatcher::Csv(csv::Output::new(writer, to)),
+			Format::Bson => Dispatcher::Bson(bson::Output::new(writer)),
+			Format::Avro => Dispatcher::Avro(avro::Output::new(writer)),
+			Format::Protobuf => Dispatcher::Protobuf(protobuf::Output::new(writer)),
+		}
+	}
+
+	/// Returns a mutable reference to the underlying writer.
+	fn get_mut(&mut self) -> &mut W {
+		match self {
+			Dispatcher::Json(output) => output.get_mut(),
+			Dispatcher::Msgpack(output) => output.get_mut(),
+			Dispatcher::Toml(output) => output.get_mut(),
+			Dispatcher::Ini(output) => output.get_mut(),
+			Dispatcher::Edn(output) => output.get_mut(),
+			Dispatcher::Ron(output) => output.get_mut(),
+			Dispatcher::Bencode(output) => output.get_mut(),
+			Dispatcher::Yaml(output) => output.get_mut(),
+			Dispatcher::Null(output) => output.get_mut(),
+			Dispatcher::Csv(output) => output.get_mut(),
+			Dispatcher::Bson(output) => output.get_mut(),
+			Dispatcher::Avro(output) => output.get_mut(),
+			Dispatcher::Protobuf(output) => output.get_mut(),
+			#[cfg(feature = "template")]
+			Dispatcher::Template(output) => output.get_mut(),
+		}
+	}
+
+	/// Returns true if this output produces JSON.
+	fn is_json(&self) -> bool {
+		matches!(self, Dispatcher::Json(_))
+	}
+
+	/// Returns true if this output produces MessagePack.
+	fn is_msgpack(&self) -> bool {
+		matches!(self, Dispatcher::Msgpack(_))
+	}
+
+	/// Returns true if this output produces YAML.
+	fn is_yaml(&self) -> bool {
+		matches!(self, Dispatcher::Yaml(_))
+	}
+
+	/// Returns true if this output produces a format with `#` comments.
+	fn supports_comments(&self) -> bool {
+		matches!(self, Dispatcher::Yaml(_) | Dispatcher::Toml(_))
+	}
+
+	/// Returns true if this output produces text rather than binary data.
+	fn is_text(&self) -> bool {
+		!matches!(
+			self,
+			Dispatcher::Msgpack(_)
+				| Dispatcher::Bson(_)
+				| Dispatcher::Bencode(_)
+				| Dispatcher::Protobuf(_)
+		)
+	}
+
+	/// Returns true if [`parallel`] workers can reproduce this output from its [`Format`].
+	fn supports_parallel(&self) -> bool {
+		match self {
+			Dispatcher::Json(output) => !output.is_indented(),
+			// Workers would write documents in the serializers' own styles.
+			Dispatcher::Yaml(output) => !output.is_styled(),
+			Dispatcher::Toml(output) => !output.has_comments(),
+			// Workers would serialize nothing, after the calling thread copied each document.
+			Dispatcher::Null(_) => false,
+			// Only the first record of the whole stream writes the header.
+			Dispatcher::Csv(_) => false,
+			// Workers would have no message type to encode with.
+			Dispatcher::Protobuf(_) => false,
+			#[cfg(feature = "template")]
+			Dispatcher::Template(_) => false,
+			_ => true,
 		}
 	}
 }
 
-impl<W> Output for &mut Dispatcher<W>
+impl<W> Output<'_> for &mut Dispatcher<W>
 where
 	W: Write,
 {
@@ -201,19 +2331,46 @@ where
 			Dispatcher::Json(output) => output.transcode_from(de),
 			Dispatcher::Msgpack(output) => output.transcode_from(de),
 			Dispatcher::Toml(output) => output.transcode_from(de),
+			Dispatcher::Ini(output) => output.transcode_from(de),
+			Dispatcher::Edn(output) => output.transcode_from(de),
+			Dispatcher::Ron(output) => output.transcode_from(de),
+			Dispatcher::Bencode(output) => output.transcode_from(de),
 			Dispatcher::Yaml(output) => output.transcode_from(de),
+			Dispatcher::Null(output) => output.transcode_from(de),
+			Dispatcher::Csv(output) => output.transcode_from(de),
+			Dispatcher::Bson(output) => output.transcode_from(de),
+			Dispatcher::Avro(output) => output.transcode_from(de),
+			Dispatcher::Protobuf(output) => output.transcode_from(de),
+			#[cfg(feature = "template")]
+			Dispatcher::Template(output) => output.transcode_from(de),
 		}
 	}
 
-	fn transcode_value<S>(&mut self, value: S) -> Result<()>
-	where
-		S: ser::Serialize,
-	{
+	fn transcode_value(&mut self, value: transcode::Value) -> Result<()> {
 		match self {
 			Dispatcher::Json(output) => output.transcode_value(value),
 			Dispatcher::Msgpack(output) => output.transcode_value(value),
 			Dispatcher::Toml(output) => output.transcode_value(value),
+			Dispatcher::Ini(output) => output.transcode_value(value),
+			Dispatcher::Edn(output) => output.transcode_value(value),
+			Dispatcher::Ron(output) => output.transcode_value(value),
+			Dispatcher::Bencode(output) => output.transcode_value(value),
 			Dispatcher::Yaml(output) => output.transcode_value(value),
+			Dispatcher::Null(output) => output.transcode_value(value),
+			Dispatcher::Csv(output) => output.transcode_value(value),
+			Dispatcher::Bson(output) => output.transcode_value(value),
+			Dispatcher::Avro(output) => output.transcode_value(value),
+			Dispatcher::Protobuf(output) => output.transcode_value(value),
+			#[cfg(feature = "template")]
+			Dispatcher::Template(output) => output.transcode_value(value),
+		}
+	}
+
+	fn transcode_msgpack(&mut self, input: &[u8]) -> Result<()> {
+		match self {
+			Dispatcher::Json(output) => output.transcode_msgpack(input),
+			Dispatcher::Msgpack(output) => output.transcode_msgpack(input),
+			_ => msgpack::transcode_document(self, input),
 		}
 	}
 
@@ -222,7 +2379,18 @@ where
 			Dispatcher::Json(output) => output.flush(),
 			Dispatcher::Msgpack(output) => output.flush(),
 			Dispatcher::Toml(output) => output.flush(),
+			Dispatcher::Ini(output) => output.flush(),
+			Dispatcher::Edn(output) => output.flush(),
+			Dispatcher::Ron(output) => output.flush(),
+			Dispatcher::Bencode(output) => output.flush(),
 			Dispatcher::Yaml(output) => output.flush(),
+			Dispatcher::Null(output) => output.flush(),
+			Dispatcher::Csv(output) => output.flush(),
+			Dispatcher::Bson(output) => output.flush(),
+			Dispatcher::Avro(output) => output.flush(),
+			Dispatcher::Protobuf(output) => output.flush(),
+			#[cfg(feature = "template")]
+			Dispatcher::Template(output) => output.flush(),
 		}
 	}
 }
@@ -232,7 +2400,7 @@ where
 /// Support for each format comes largely from external crates, with some additional preprocessing
 /// by xt for select formats. The crate selection for each format is **not stable**,
 /// and is documented for informational purposes only.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum Format {
 	/// The [JSON][json] format as interpreted by [`serde_json`].
@@ -241,6 +2409,16 @@ pub enum Format {
 	///
 	/// [json]: https://datatracker.ietf.org/doc/html/rfc8259
 	Json,
+	/// [JSON Lines][jsonl], also known as NDJSON: JSON with exactly one compact document on each
+	/// line.
+	///
+	/// Input in this format translates the same as [`Json`](Format::Json) input, but detection
+	/// never matches it. As an output format, it differs from JSON in ignoring
+	/// [`Translator::set_json_indent`], so that the framing of a stream of documents is always
+	/// one per line.
+	///
+	/// [jsonl]: https://jsonlines.org/
+	JsonLines,
 	/// The [MessagePack][msgpack] format as interpreted by [`rmp_serde`].
 	///
 	/// This format supports multi-document translation and streaming input.
@@ -260,48 +2438,117 @@ pub enum Format {
 	///
 	/// [yaml]: https://yaml.org/spec/1.2.2/
 	Yaml,
+	/// An output format that discards every document after fully parsing it.
+	///
+	/// This format supports multi-document translation and streaming input, for measuring the
+	/// cost of parsing an input without that of serializing it, or for checking that an input
+	/// parses. It can't be the format of an input.
+	Null,
+	/// The [CSV][csv] format with a header record.
+	///
+	/// Each record after the header translates as a separate document, mapping each name in the
+	/// header to the string in the same position of the record. This format supports
+	/// multi-document translation and streaming input, but detection never matches it.
+	///
+	/// As an output format, each map document becomes a record, as does each map in an array
+	/// document, with the header taken from the keys of the first record. See
+	/// [`Translator::set_csv_mismatch`] for records with other keys.
+	///
+	/// [csv]: https://datatracker.ietf.org/doc/html/rfc4180
+	Csv,
+	/// Tab-separated values, read and written like [`Csv`](Format::Csv) but with tabs between
+	/// fields.
+	Tsv,
+	/// The [BSON][bson] format of MongoDB documents, as interpreted by xt itself.
+	///
+	/// This format supports multi-document translation and streaming input, as a concatenation
+	/// of documents like the output of mongodump, but detection never matches it. BSON types
+	/// like ObjectIds and datetimes translate as maps in the style of MongoDB Extended JSON,
+	/// like `{"$oid": "..."}`. Every output document must be a map.
+	///
+	/// [bson]: https://bsonspec.org/
+	Bson,
+	/// The [Avro][avro] object container file format, as interpreted by xt itself.
+	///
+	/// Each record of the file translates as a separate document, decoded by the schema in the
+	/// file's header. This format supports multi-document translation and streaming input, but
+	/// detection never matches it. It can't be the format of an output.
+	///
+	/// [avro]: https://avro.apache.org/docs/1.12.0/specification/
+	Avro,
+	/// [Protocol Buffers][protobuf] messages of the type set by
+	/// [`Translator::set_protobuf_message`], as interpreted by xt itself.
+	///
+	/// An input or output is a single message, or with [`Translator::set_protobuf_delimited`] a
+	/// stream of messages that each follow their length, which supports multi-document translation
+	/// and streaming input. Detection never matches this format. Every output document must be a
+	/// map, whose keys name fields of the message type.
+	///
+	/// [protobuf]: https://protobuf.dev/programming-guides/encoding/
+	Protobuf,
+	/// The INI format of legacy configuration files, as interpreted by xt itself.
+	///
+	/// This format supports single-document translation only, and detection never matches it.
+	/// Each `[section]` translates as a map under its name, after the keys that precede the first
+	/// section, and every value translates as a string. Output fails on arrays and on maps within
+	/// sections, since INI has no deeper levels of nesting.
+	Ini,
+	/// The [EDN] format of Clojure data, as interpreted by xt itself.
+	///
+	/// Each top-level element is a document, and detection never matches it. Keywords and
+	/// symbols translate as strings of their names, without the leading `:` of a keyword, and
+	/// lists, vectors, and sets translate as arrays. A tagged literal translates as set by
+	/// [`Translator::set_edn_tags`]. Output writes arrays as vectors, and each map key that is a
+	/// string with the form of a keyword name as a keyword, so that the JSON `{"port": 80}`
+	/// becomes `{:port 80}`.
+	///
+	/// [EDN]: https://github.com/edn-format/edn
+	Edn,
+	/// The [bencode] format of BitTorrent metainfo files and tracker responses.
+	///
+	/// Each top-level value is a document, and detection never matches it. Byte strings that are
+	/// valid UTF-8 translate as strings, and all others as binary data, like MessagePack `bin`
+	/// values. Bencode is an input format only, and output to it fails.
+	///
+	/// [bencode]: https://www.bittorrent.org/beps/bep_0003.html#bencoding
+	Bencode,
+	/// The [RON] format of Rusty Object Notation, as interpreted by xt itself.
+	///
+	/// This format supports single-document translation only, and detection never matches it.
+	/// Lists and tuples translate as arrays, `()` and `None` as null, and `Some(value)` as its
+	/// value alone. A struct translates as a map of its fields, without its name, so that
+	/// `Point(x: 1, y: 2)` translates like the JSON `{"x": 1, "y": 2}`, and a unit variant like
+	/// `Red` translates as a string of its name. Output writes maps as RON maps and null as `()`.
+	///
+	/// [RON]: https://github.com/ron-rs/ron
+	Ron,
 }
 
 impl fmt::Display for Format {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		f.write_str(match self {
 			Self::Json => "JSON",
+			Self::JsonLines => "JSON Lines",
 			Self::Msgpack => "MessagePack",
 			Self::Toml => "TOML",
+			Self::Ini => "INI",
+			Self::Edn => "EDN",
+			Self::Bencode => "bencode",
+			Self::Ron => "RON",
 			Self::Yaml => "YAML",
+			Self::Null => "null",
+			Self::Csv => "CSV",
+			Self::Tsv => "TSV",
+			Self::Bson => "BSON",
+			Self::Avro => "Avro",
+			Self::Protobuf => "Protocol Buffers",
 		})
 	}
 }
 
 impl Format {
-	/// Detects the input format by trying to parse a single document with each one.
-	fn detect(input: &mut input::Handle) -> io::Result<Option<Format>> {
-		// As a binary format, we expect MessagePack to be more restrictive than any text format.
-		// Detection of MessagePack inputs is limited to collection types; see comments in the
-		// implementation for details.
-		if crate::msgpack::input_matches(input.borrow_mut())? {
-			return Ok(Some(Format::Msgpack));
-		}
-
-		// We expect JSON to be more restrictive than other text formats. For example, a "#"
-		// comment at the start of a document could be TOML or YAML, but definitely not JSON.
-		if crate::json::input_matches(input.borrow_mut())? {
-			return Ok(Some(Format::Json));
-		}
-
-		// YAML is _less_ restrictive than TOML, but we want to try it first since it supports
-		// streaming input (so detection may require less buffering). Detection of YAML inputs is
-		// limited to collection types; see comments in the implementation for details.
-		if crate::yaml::input_matches(input.borrow_mut())? {
-			return Ok(Some(Format::Yaml));
-		}
-
-		// TOML is the only format that must fully buffer its input, and imposes its own limits to
-		// avoid unbounded memory consumption.
-		if crate::toml::input_matches(input.borrow_mut())? {
-			return Ok(Some(Format::Toml));
-		}
-
-		Ok(None)
+	/// Returns true if the format supports multiple documents in a single output.
+	fn is_multi_document(self) -> bool {
+		!matches!(self, Format::Toml | Format::Ini | Format::Ron)
 	}
 }
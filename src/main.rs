@@ -33,16 +33,32 @@
 use std::borrow::Cow;
 use std::env;
 use std::fmt;
-use std::fs::File;
-use std::io::{self, BufWriter, IsTerminal, Write};
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::num::NonZeroUsize;
+use std::ops;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 
-use xt::Format;
+use xt::{
+	ArrayMerge, Coercion, CsvMismatch, EdnTags, Encoding, Format, MsgpackKeys, Strictness,
+	YamlBlockStyle,
+};
+
+use crate::color::ColorChoice;
 
 #[macro_use]
 mod die;
 
+mod color;
+mod command;
+mod interrupt;
+#[cfg(unix)]
+mod serve;
+mod sha256;
+
 fn main() {
 	let Ok(args) = Cli::parse_args().map_err(|err| {
 		let mut stderr = io::stderr().lock();
@@ -50,72 +66,790 @@ fn main() {
 		write_short_help(stderr);
 		process::exit(2);
 	});
+	die::set_json(args.json_errors);
+	die::set_color(args.color.applies_to(&io::stderr()));
 
-	let stdout = io::stdout();
-	if stdout.is_terminal() && format_is_unsafe_for_terminal(args.to) {
-		die!(
-			"refusing to output {format} to a terminal",
-			format = args.to
-		);
+	match args.command {
+		#[cfg(unix)]
+		Command::Convert if args.serve_unix.is_some() || args.daemon => serve::serve_unix(&args),
+		#[cfg(unix)]
+		Command::Convert if args.via_daemon => serve::via_daemon(&args),
+		Command::Convert if args.out_dir.is_some() => command::convert_to_dir(&args),
+		Command::Convert if args.editor => command::editor(&args),
+		Command::Convert | Command::Merge => {
+			let to = args.to.unwrap_or(Format::Json);
+			check_terminal_output(to, args.encoding);
+			translate_all(&args, die::stdout(), to);
+		}
+		// MessagePack can represent every document, so checking doesn't fail on documents that
+		// only some other output format can't represent.
+		Command::Check => translate_all(&args, io::sink(), args.to.unwrap_or(Format::Msgpack)),
+		Command::Detect => command::detect(&args),
+		Command::Diff => command::diff(&args),
+		Command::Split => command::split(&args),
+		Command::Fmt => command::fmt(&args),
+		Command::Hash => command::hash(&args),
+		Command::ToEnv => command::to_env(&args),
 	}
+}
 
+/// Translates every input to a single output, exiting on any error.
+fn translate_all<W: Write>(args: &Cli, output: W, to: Format) {
+	interrupt::install();
+	let mut translator = output_translator(args, output, to);
+	configure_documents(&mut translator, args);
 	let mut stdin_used = false;
-	let mut output = BufWriter::new(pipecheck::wrap(stdout.lock()));
-	let mut translator = xt::Translator::new(&mut output, args.to);
+	for path in args.input_paths() {
+		if interrupt::requested() {
+			break;
+		}
+		translate_path(&mut translator, args, &path, &mut stdin_used);
+	}
+	if let Err(err) = translator.finish() {
+		die!("{err}");
+	}
+	interrupt::exit_if_requested();
+}
 
-	let input_paths = if args.input_pathnames.is_empty() {
-		InputPaths::one(InputPath::Stdin)
-	} else {
-		InputPaths::many(args.input_pathnames.into_iter().map(Into::into))
+/// Creates a translator with the options that shape its output.
+fn output_translator<W: Write>(args: &Cli, output: W, to: Format) -> xt::Translator<W> {
+	#[cfg(feature = "template")]
+	let mut translator = match &args.template {
+		Some(_) => {
+			let template = loaded(args).template.clone();
+			xt::Translator::with_template(output, template.expect("templates should be loaded"))
+		}
+		None => xt::Translator::new(output, to),
 	};
+	#[cfg(not(feature = "template"))]
+	let mut translator = xt::Translator::new(output, to);
+	if let Some(threads) = args.threads {
+		translator.set_threads(threads);
+	}
+	if let Some(encoding) = args.encoding {
+		translator.set_output_encoding(encoding);
+	}
+	translator.set_byte_order_mark(args.bom);
+	translator.set_json_indent(args.indent);
+	translator.set_yaml_block_style(args.yaml_blocks);
+	if let Some(pathname) = &args.index {
+		let Ok(file) =
+			fs::File::create(pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+		let format = InputPath::File(pathname.clone()).extension_format();
+		translator.set_index_output(file, format.unwrap_or(Format::Json));
+	}
+	translator.set_flush_each_document(args.flush_each_document);
+	translator.set_document_comments(args.doc_comments);
+	translator.set_intern_keys(args.intern_keys);
+	translator.set_toml_container(args.toml_container.clone());
+	for (path, comment) in &args.comments {
+		if let Err(err) = translator.add_output_comment(path, comment) {
+			die!("{err}");
+		}
+	}
+	translator.set_csv_mismatch(args.csv_mismatch);
+	set_protobuf(&mut translator, args);
+	set_yaml_warnings(&mut translator, args);
+	translator
+}
+
+/// The options that xt loads from files, which every translator shares once they're loaded.
+struct Loaded {
+	#[cfg(feature = "template")]
+	template: Option<xt::Template>,
+	protobuf: Option<xt::ProtobufMessage>,
+	known_keys: Option<xt::KnownKeys>,
+	transforms: Vec<xt::Transform>,
+}
 
-	for path in input_paths {
-		let Ok(input) = path.open().map_err(|err| die_in!(path, "{err}"));
+/// Returns the options that xt loads from files, loading them the first time, and exiting if any
+/// can't be loaded.
+///
+/// The first call comes before any translation starts, so that only the calling thread can exit.
+fn loaded(args: &Cli) -> &'static Loaded {
+	static LOADED: OnceLock<Loaded> = OnceLock::new();
 
-		if matches!(input, Input::Stdin) {
-			// TODO: Is this check worth it? You can pass /dev/stdin more than once, though the
-			// behavior might be weird.
-			if stdin_used {
-				die!("cannot read from standard input more than once");
+	LOADED.get_or_init(|| Loaded {
+		#[cfg(feature = "template")]
+		template: args.template.as_deref().map(load_template),
+		protobuf: match (&args.proto_descriptor, &args.proto_message) {
+			(Some(pathname), Some(name)) => {
+				let Ok(input) =
+					fs::read(pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+				let Ok(message) = xt::ProtobufMessage::from_descriptor_set(&input, name)
+					.map_err(|err| die_in!(pathname.display(), "{err}"));
+				Some(message)
 			}
-			stdin_used = true;
-		}
+			_ => None,
+		},
+		known_keys: args.known_keys.as_ref().map(|pathname| {
+			let Ok(input) = fs::read(pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+			let path = InputPath::File(pathname.clone());
+			let Ok(known) = xt::KnownKeys::from_slice(&input, path.extension_format())
+				.map_err(|err| die_in!(path, "{err}"));
+			known
+		}),
+		transforms: args.transforms.iter().map(TransformArg::load).collect(),
+	})
+}
 
-		let from = args.from.or_else(|| path.extension_format());
-		let result = match input {
-			Input::Stdin => translator.translate_reader(io::stdin().lock(), from),
-			Input::File(file) => translator.translate_reader(file, from),
-			Input::Mmap(map) => translator.translate_slice(&map, from),
-		};
-		if let Err(err) = result {
-			die_in!(path, "{err}");
+/// Sets up a translator with the message type of Protocol Buffers input and output, if given.
+fn set_protobuf<W: Write>(translator: &mut xt::Translator<W>, args: &Cli) {
+	let Some(message) = &loaded(args).protobuf else {
+		return;
+	};
+	translator.set_protobuf_message(Some(message.clone()));
+	translator.set_protobuf_delimited(args.proto_delimited);
+}
+
+/// Sets up a translator to report risky YAML scalars, if requested.
+fn set_yaml_warnings<W: Write>(translator: &mut xt::Translator<W>, args: &Cli) {
+	if args.yaml_warnings {
+		translator.set_yaml_warning_handler(|warning| die::warn(&warning));
+	}
+}
+
+/// Sets up a translator with the options that choose which documents to output, and what they
+/// contain.
+///
+/// A translator that [`output_translator`] didn't create also needs [`set_protobuf`] and
+/// [`set_yaml_warnings`] for its input.
+fn configure_documents<W: Write>(translator: &mut xt::Translator<W>, args: &Cli) {
+	if let Some(order) = &args.detect {
+		translator.set_detection_order(order);
+	}
+	translator.set_detection_limit(args.detect_limit);
+	translator.set_max_document_size(args.max_doc_size);
+	translator.set_split_json_arrays(args.split_json_arrays);
+	translator.set_msgpack_keys(args.msgpack_keys);
+	translator.set_edn_tags(args.edn_tags);
+	if args.msgpack_resync {
+		translator.set_msgpack_resync_handler(|resync| die::warn_msgpack_resync(&resync));
+	}
+	if args.repair_utf8 {
+		translator.set_utf8_repair_handler(|repair| die::warn_utf8_repair(&repair));
+	}
+	translator.set_interrupt_flag(Some(interrupt::flag()));
+	if let Some(strictness) = args.strictness {
+		translator.set_strictness(strictness);
+	}
+	for &(check, allow) in &args.input_checks {
+		match check {
+			InputCheck::DuplicateKeys => translator.set_reject_duplicate_keys(!allow),
+			InputCheck::JsonSeparators => translator.set_lenient_json(allow),
+			InputCheck::YamlScalars => translator.set_reject_risky_yaml(!allow),
+			InputCheck::MsgpackExt => translator.set_translate_msgpack_ext(allow),
 		}
-		if let Err(err) = translator.flush() {
-			die!("{err}");
+	}
+	if let Some(known) = &loaded(args).known_keys {
+		translator.set_known_keys(Some(known.clone()));
+		if args.warn_unknown_keys {
+			translator.set_unknown_key_handler(|unknown| die::warn_unknown_key(&unknown));
 		}
 	}
+	if args.audit_strings {
+		translator.set_string_issue_handler(|issue| die::warn_string_issue(&issue));
+	}
+	for transform in &loaded(args).transforms {
+		translator.add_transform(transform.clone());
+	}
+	translator.set_skip(args.skip);
+	translator.set_skip_bytes(args.skip_bytes);
+	translator.set_skip_documents(args.skip_docs);
+	translator.set_head(args.head);
+	translator.set_tail(args.tail);
+	let order = match (&args.sort_by, args.interleave) {
+		(Some(path), _) => Some(xt::StreamOrder::SortBy(path.clone())),
+		(None, true) => Some(xt::StreamOrder::Interleave),
+		(None, false) => None,
+	};
+	if let Err(err) = translator.set_stream_order(order) {
+		die!("{err}");
+	}
+	if let Err(err) = translator.set_group_by(args.group_by.as_deref()) {
+		die!("{err}");
+	}
+	translator.set_merge(args.merge.clone());
+	translator.set_report_merge_conflicts(args.report_conflicts);
+	translator.set_attribute_merge_sources(args.merge_sources);
+	translator.set_infer_schema(args.infer_schema);
+	if args.type_stats {
+		translator.set_type_stats(true);
+	}
+	#[cfg(feature = "rust-types")]
+	if args.infer_rust_types {
+		translator.set_infer_rust_types(true);
+	}
+}
+
+/// Translates a single input, exiting on any error that isn't from an interruption.
+fn translate_path<W: Write>(
+	translator: &mut xt::Translator<W>,
+	args: &Cli,
+	path: &InputPath,
+	stdin_used: &mut bool,
+) {
+	let input = open_input(path, stdin_used);
+	let from = args.from.or_else(|| args.extension_format(path));
+	translator.set_source_name(Some(match path {
+		InputPath::Stdin => "-".into(),
+		InputPath::File(pathname) => pathname.display().to_string(),
+	}));
+	let result = match input {
+		Input::Stdin => translator.translate_reader(interrupt::Reader(io::stdin().lock()), from),
+		Input::File(file) => translator.translate_reader(interrupt::Reader(file), from),
+		Input::SeekableFile(file) => translator.translate_seekable(file, from),
+		Input::Mmap(map) => translator.translate_slice(&map, from),
+	};
+	match result {
+		// The document that the interruption cut off is simply left out.
+		Err(_) if interrupt::requested() => {}
+		Err(err) => die::exit_translation(path, from, &err),
+		Ok(()) => {}
+	}
+}
+
+/// Opens a single input, exiting if it can't be opened.
+fn open_input(path: &InputPath, stdin_used: &mut bool) -> Input {
+	let Ok(input) = path.open().map_err(|err| die_in!(path, "{err}"));
+	if matches!(input, Input::Stdin) {
+		// TODO: Is this check worth it? You can pass /dev/stdin more than once, though the
+		// behavior might be weird.
+		if *stdin_used {
+			die!("cannot read from standard input more than once");
+		}
+		*stdin_used = true;
+	}
+	input
+}
+
+/// Loads a template from a file, exiting if it can't be loaded.
+#[cfg(feature = "template")]
+fn load_template(pathname: &Path) -> xt::Template {
+	let Ok(source) =
+		fs::read_to_string(pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+	let Ok(template) =
+		xt::Template::new(source).map_err(|err| die_in!(pathname.display(), "{err}"));
+	template
+}
+
+/// Exits if standard output is a terminal that would mangle output in the format and encoding.
+pub(crate) fn check_terminal_output(to: Format, encoding: Option<Encoding>) {
+	if !io::stdout().is_terminal() {
+		return;
+	}
+	if format_is_unsafe_for_terminal(to) {
+		die!("refusing to output {to} to a terminal");
+	}
+	if let Some(encoding) = encoding.filter(|&encoding| encoding_is_unsafe_for_terminal(encoding)) {
+		die!("refusing to output {encoding} to a terminal");
+	}
 }
 
 fn format_is_unsafe_for_terminal(format: Format) -> bool {
-	matches!(format, Format::Msgpack)
+	matches!(format, Format::Msgpack | Format::Bson | Format::Protobuf)
+}
+
+/// Returns true if a terminal would fail on text in the encoding.
+///
+/// The Windows console takes UTF-16 text, which the standard library converts from UTF-8 output
+/// on our behalf. Output in any other encoding is invalid UTF-8 to that conversion, so it fails
+/// partway through the first document rather than reaching the console as some other encoding.
+fn encoding_is_unsafe_for_terminal(encoding: Encoding) -> bool {
+	cfg!(windows) && encoding != Encoding::Utf8
+}
+
+/// Returns a path with any forward slashes turned into backslashes if it's a Windows verbatim
+/// path, like `\\?\C:\dir/file.json`.
+///
+/// Windows doesn't normalize a path starting with `\\?\`, which is how such a path can exceed
+/// the usual length limit, so a forward slash in one is just part of a file name instead of a
+/// separator. Users type forward slashes out of habit, and split builds names from a prefix and
+/// template that may contain them, so without this they would name files that can't exist.
+pub(crate) fn native_path(path: PathBuf) -> PathBuf {
+	if !cfg!(windows) {
+		return path;
+	}
+	match path.to_str() {
+		Some(s) if s.starts_with(r"\\?\") && s.contains('/') => s.replace('/', r"\").into(),
+		_ => path,
+	}
 }
 
 struct Cli {
+	command: Command,
 	input_pathnames: Vec<PathBuf>,
 	from: Option<Format>,
-	to: Format,
+	detect: Option<Vec<Format>>,
+	detect_limit: Option<usize>,
+	max_doc_size: Option<u64>,
+	to: Option<Format>,
+	threads: Option<NonZeroUsize>,
+	encoding: Option<Encoding>,
+	bom: bool,
+	index: Option<PathBuf>,
+	out_dir: Option<PathBuf>,
+	/// The Unix domain socket to serve translation requests on, instead of translating inputs.
+	serve_unix: Option<PathBuf>,
+	/// Whether to serve translation requests on the daemon's socket, instead of translating inputs.
+	daemon: bool,
+	/// Whether to send the input to the daemon to translate, instead of translating it here.
+	via_daemon: bool,
+	/// Whether to translate a single document from standard input for an editor.
+	editor: bool,
+	dry_run: bool,
+	preserve_metadata: bool,
+	split_json_arrays: bool,
+	msgpack_resync: bool,
+	msgpack_keys: MsgpackKeys,
+	edn_tags: EdnTags,
+	repair_utf8: bool,
+	/// A reference document whose keys are the only ones that input documents may contain.
+	known_keys: Option<PathBuf>,
+	warn_unknown_keys: bool,
+	audit_strings: bool,
+	/// A compiled descriptor set with the message type of Protocol Buffers input and output.
+	proto_descriptor: Option<PathBuf>,
+	proto_message: Option<String>,
+	proto_delimited: bool,
+	strictness: Option<Strictness>,
+	/// Overrides of the strictness profile, each allowing or rejecting one kind of input.
+	input_checks: Vec<(InputCheck, bool)>,
+	flush_each_document: bool,
+	doc_comments: bool,
+	intern_keys: bool,
+	toml_container: Option<String>,
+	/// The comments from --comment, with the path that each one comes before.
+	comments: Vec<(String, String)>,
+	csv_mismatch: CsvMismatch,
+	indent: Option<usize>,
+	yaml_blocks: YamlBlockStyle,
+	/// The start of the name of each file from split, or of each variable from to-env.
+	prefix: Option<String>,
+	/// The name of each file from split after the prefix.
+	name: SplitName,
+	/// The rules that send matching documents from split to shared files, in priority order.
+	routes: Vec<Route>,
+	check: bool,
+	keep_comments: bool,
+	/// The suffix of the backup that fmt copies each file to before rewriting it.
+	backup: Option<String>,
+	transforms: Vec<TransformArg>,
+	skip: u64,
+	skip_bytes: u64,
+	skip_docs: u64,
+	head: Option<u64>,
+	tail: Option<usize>,
+	merge: Option<ArrayMerge>,
+	report_conflicts: bool,
+	merge_sources: bool,
+	infer_schema: bool,
+	type_stats: bool,
+	interleave: bool,
+	sort_by: Option<String>,
+	group_by: Option<String>,
+	json_errors: bool,
+	color: ColorChoice,
+	yaml_warnings: bool,
+	#[cfg(feature = "rust-types")]
+	infer_rust_types: bool,
+	#[cfg(feature = "template")]
+	template: Option<PathBuf>,
+}
+
+/// A kind of input that `--allow` and `--reject` can override the strictness profile for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InputCheck {
+	DuplicateKeys,
+	JsonSeparators,
+	YamlScalars,
+	MsgpackExt,
+}
+
+/// The name of each file from split after the prefix and before the extension, from the template
+/// of `--name`.
+struct SplitName(Vec<NamePart>);
+
+/// A piece of a [`SplitName`].
+enum NamePart {
+	Text(String),
+	/// The zero-based index of the document in six digits, from `{index}`.
+	Index,
+	/// The first 16 hexadecimal digits of the SHA-256 digest of the file's content, from `{hash}`.
+	Hash,
+}
+
+impl SplitName {
+	/// Returns true if the name depends on the content of the file.
+	fn uses_hash(&self) -> bool {
+		self.0.iter().any(|part| matches!(part, NamePart::Hash))
+	}
+
+	/// Returns the name of the file for a document, given its index and the content of its file,
+	/// which only matters to names that use the hash.
+	fn render(&self, index: usize, content: &[u8]) -> String {
+		let hash = self.uses_hash().then(|| sha256::hex_digest(content));
+		let mut name = String::new();
+		for part in &self.0 {
+			match part {
+				NamePart::Text(text) => name.push_str(text),
+				NamePart::Index => name.push_str(&format!("{index:06}")),
+				NamePart::Hash => name.push_str(hash.as_deref().map_or("", |hash| &hash[..16])),
+			}
+		}
+		name
+	}
+}
+
+/// A rule from `--route` that sends the documents it matches to a single file of its own.
+struct Route {
+	pathname: PathBuf,
+	format: Format,
+	rule: RouteRule,
+}
+
+/// The documents that a [`Route`] matches.
+enum RouteRule {
+	/// Documents with zero-based indexes in the range.
+	Indexes(ops::Range<u64>),
+	/// Documents that a `--select` expression keeps.
+	Select(Box<Mutex<RouteSelect>>),
+}
+
+/// A translator with a `--select` transform, set up once for every document that a route tries.
+struct RouteSelect {
+	translator: xt::Translator<Kept>,
+	kept: Arc<AtomicBool>,
+}
+
+/// A writer that records whether anything was written to it.
+struct Kept(Arc<AtomicBool>);
+
+impl Write for Kept {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if !buf.is_empty() {
+			self.0.store(true, Ordering::Relaxed);
+		}
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl RouteRule {
+	fn select(select: xt::Transform) -> RouteRule {
+		let kept = Arc::<AtomicBool>::default();
+		// Without an output buffer, each document that the transform keeps is written right away.
+		let mut translator =
+			xt::Translator::with_capacity(0, Kept(Arc::clone(&kept)), Format::Msgpack);
+		translator.add_transform(select);
+		RouteRule::Select(Box::new(Mutex::new(RouteSelect { translator, kept })))
+	}
+}
+
+impl Route {
+	/// Returns true if the route matches a document, given its index and its MessagePack content.
+	fn matches(&self, index: u64, document: &[u8]) -> bool {
+		match &self.rule {
+			RouteRule::Indexes(range) => range.contains(&index),
+			RouteRule::Select(select) => {
+				let mut select = select.lock().unwrap_or_else(PoisonError::into_inner);
+				select.kept.store(false, Ordering::Relaxed);
+				let result = select
+					.translator
+					.translate_slice(document, Some(Format::Msgpack));
+				result.is_ok() && select.kept.load(Ordering::Relaxed)
+			}
+		}
+	}
+}
+
+/// A transform from the command line, which may need to load a file before use.
+enum TransformArg {
+	Ready(xt::Transform),
+	Rename(PathBuf),
+	KeyOrder(PathBuf),
+}
+
+impl TransformArg {
+	/// Returns the transform, exiting if it can't be loaded.
+	fn load(&self) -> xt::Transform {
+		match self {
+			TransformArg::Ready(transform) => transform.clone(),
+			TransformArg::Rename(pathname) => {
+				let Ok(input) =
+					fs::read(pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+				let path = InputPath::File(pathname.clone());
+				let Ok(transform) =
+					xt::Transform::rename_from_slice(&input, path.extension_format())
+						.map_err(|err| die_in!(path, "{err}"));
+				transform
+			}
+			TransformArg::KeyOrder(pathname) => {
+				let Ok(input) =
+					fs::read(pathname).map_err(|err| die_in!(pathname.display(), "{err}"));
+				let path = InputPath::File(pathname.clone());
+				let Ok(transform) =
+					xt::Transform::key_order_from_slice(&input, path.extension_format())
+						.map_err(|err| die_in!(path, "{err}"));
+				transform
+			}
+		}
+	}
+}
+
+/// A subcommand of the binary, which comes before any options.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Command {
+	Convert,
+	Detect,
+	Check,
+	Merge,
+	Diff,
+	Split,
+	Fmt,
+	Hash,
+	ToEnv,
+}
+
+impl Command {
+	fn from_name(name: &str) -> Option<Command> {
+		match name {
+			"convert" => Some(Command::Convert),
+			"detect" => Some(Command::Detect),
+			"check" => Some(Command::Check),
+			"merge" => Some(Command::Merge),
+			"diff" => Some(Command::Diff),
+			"split" => Some(Command::Split),
+			"fmt" => Some(Command::Fmt),
+			"hash" => Some(Command::Hash),
+			"to-env" => Some(Command::ToEnv),
+			_ => None,
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Command::Convert => "convert",
+			Command::Detect => "detect",
+			Command::Check => "check",
+			Command::Merge => "merge",
+			Command::Diff => "diff",
+			Command::Split => "split",
+			Command::Fmt => "fmt",
+			Command::Hash => "hash",
+			Command::ToEnv => "to-env",
+		}
+	}
+
+	/// Returns true if the command accepts options of the given kind.
+	fn accepts(self, kind: OptionKind) -> bool {
+		if let OptionKind::Only(command) = kind {
+			return command == self;
+		}
+		match self {
+			Command::Convert | Command::Check | Command::Merge => kind != OptionKind::Naming,
+			Command::Detect => kind == OptionKind::Format,
+			Command::Diff | Command::Hash => matches!(
+				kind,
+				OptionKind::Format
+					| OptionKind::Input
+					| OptionKind::Document
+					| OptionKind::Diagnostics
+			),
+			Command::Split => kind != OptionKind::Stream,
+			Command::ToEnv => matches!(
+				kind,
+				OptionKind::Format
+					| OptionKind::Input
+					| OptionKind::Document
+					| OptionKind::Diagnostics
+					| OptionKind::Naming
+			),
+			Command::Fmt => matches!(
+				kind,
+				OptionKind::Format | OptionKind::Style | OptionKind::Diagnostics
+			),
+		}
+	}
+}
+
+/// A group of related options, which each command accepts or rejects as a whole.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OptionKind {
+	/// Options that choose the format of each input.
+	Format,
+	/// Options that choose how to read inputs.
+	Input,
+	/// Options that transform and select documents.
+	Document,
+	/// Options that shape the output documents.
+	Output,
+	/// Options that choose the style of text output.
+	Style,
+	/// Options that affect how the output is written as a single stream.
+	Stream,
+	/// Options that report problems in the data without affecting translation.
+	Diagnostics,
+	/// Options that name what a command writes, for commands that write more than documents.
+	Naming,
+	/// Options specific to a single command.
+	Only(Command),
+}
+
+impl OptionKind {
+	/// Returns the kind of an option, or `None` for arguments that every command accepts.
+	fn of(arg: &lexopt::Arg) -> Option<OptionKind> {
+		use lexopt::Arg::{Long, Short};
+
+		match arg {
+			Short('f') | Long("detect" | "detect-limit") => Some(OptionKind::Format),
+			Short('s')
+			| Long(
+				"skip-bytes" | "seek-bytes" | "skip-docs" | "lenient-json" | "strict" | "lenient"
+				| "allow" | "reject" | "max-doc-size" | "msgpack-resync" | "msgpack-keys"
+				| "edn-tags" | "repair-utf8" | "known-keys" | "proto-descriptor" | "proto-message"
+				| "proto-delimited",
+			) => Some(OptionKind::Input),
+			Long(
+				"coerce" | "flatten" | "indexed-arrays" | "indexed-maps" | "key-order"
+				| "max-depth" | "prune" | "prune-empty" | "provenance" | "redact" | "rename"
+				| "select" | "sort-arrays" | "sort-arrays-by" | "sort-keys" | "stringify"
+				| "unflatten" | "head" | "skip" | "tail",
+			) => Some(OptionKind::Document),
+			Short('t' | 'c')
+			| Long(
+				"encoding" | "bom" | "comment" | "csv-mismatch" | "template" | "merge"
+				| "report-conflicts" | "merge-sources" | "interleave" | "sort-by" | "group-by"
+				| "schema" | "rust-types" | "type-stats",
+			) => Some(OptionKind::Output),
+			Long("indent" | "yaml-blocks" | "doc-comments") => Some(OptionKind::Style),
+			Short('j' | 'u') | Long("index" | "intern-keys") => Some(OptionKind::Stream),
+			Long("yaml-warnings" | "warn-unknown-keys" | "audit-strings") => {
+				Some(OptionKind::Diagnostics)
+			}
+			Long("prefix") => Some(OptionKind::Naming),
+			Long("name" | "route") => Some(OptionKind::Only(Command::Split)),
+			Long("out-dir" | "serve-unix" | "daemon" | "via-daemon" | "editor") => {
+				Some(OptionKind::Only(Command::Convert))
+			}
+			Long("check" | "keep-comments" | "backup") => Some(OptionKind::Only(Command::Fmt)),
+			_ => None,
+		}
+	}
+}
+
+/// Returns an option as written on the command line.
+fn option_name(arg: &lexopt::Arg) -> String {
+	match arg {
+		lexopt::Arg::Short(c) => format!("-{c}"),
+		lexopt::Arg::Long(name) => format!("--{name}"),
+		lexopt::Arg::Value(value) => value.to_string_lossy().into_owned(),
+	}
 }
 
 impl Cli {
 	fn parse_args() -> Result<Self, lexopt::Error> {
 		use lexopt::prelude::*;
 
+		// A command is only recognized as the very first argument, so that `xt -- check` or
+		// `xt ./check` can still convert a file named "check".
+		let mut args = env::args_os().skip(1).peekable();
+		let command = args
+			.peek()
+			.and_then(|arg| arg.to_str())
+			.and_then(Command::from_name);
+		if command.is_some() {
+			args.next();
+		}
+		let command = command.unwrap_or(Command::Convert);
+
 		let mut input_pathnames: Vec<PathBuf> = vec![];
 		let mut from: Option<Format> = None;
+		let mut detect: Option<Vec<Format>> = None;
+		let mut detect_limit: Option<usize> = None;
+		let mut max_doc_size: Option<u64> = None;
 		let mut to: Option<Format> = None;
+		let mut threads: Option<NonZeroUsize> = None;
+		let mut encoding: Option<Encoding> = None;
+		let mut bom = false;
+		let mut index: Option<PathBuf> = None;
+		let mut out_dir: Option<PathBuf> = None;
+		let mut serve_unix: Option<PathBuf> = None;
+		let mut daemon = false;
+		let mut via_daemon = false;
+		let mut editor = false;
+		// The daemon translates with options of its own, so a client can't take any that would
+		// change the translation.
+		let mut client_local_option: Option<String> = None;
+		let mut dry_run = false;
+		let mut preserve_metadata = false;
+		let mut split_json_arrays = false;
+		let mut msgpack_resync = false;
+		let mut msgpack_keys: Option<MsgpackKeys> = None;
+		let mut edn_tags: Option<EdnTags> = None;
+		let mut repair_utf8 = false;
+		let mut known_keys: Option<PathBuf> = None;
+		let mut warn_unknown_keys = false;
+		let mut audit_strings = false;
+		let mut proto_descriptor: Option<PathBuf> = None;
+		let mut proto_message: Option<String> = None;
+		let mut proto_delimited = false;
+		let mut strictness: Option<Strictness> = None;
+		let mut input_checks: Vec<(InputCheck, bool)> = vec![];
+		let mut flush_each_document = false;
+		let mut doc_comments = false;
+		let mut intern_keys = false;
+		let mut toml_container: Option<String> = None;
+		let mut comments: Vec<(String, String)> = vec![];
+		let mut csv_mismatch: Option<CsvMismatch> = None;
+		let mut indent: Option<usize> = None;
+		let mut yaml_blocks: Option<YamlBlockStyle> = None;
+		let mut prefix: Option<String> = None;
+		let mut name: Option<SplitName> = None;
+		let mut routes: Vec<Route> = vec![];
+		let mut check = false;
+		let mut keep_comments = false;
+		let mut backup: Option<String> = None;
+		let mut transforms: Vec<TransformArg> = vec![];
+		let mut skip: Option<u64> = None;
+		let mut skip_bytes: Option<u64> = None;
+		let mut skip_docs: Option<u64> = None;
+		let mut head: Option<u64> = None;
+		let mut tail: Option<usize> = None;
+		let mut merge: Option<ArrayMerge> = None;
+		let mut report_conflicts = false;
+		let mut merge_sources = false;
+		let mut infer_schema = false;
+		let mut type_stats = false;
+		let mut interleave = false;
+		let mut sort_by: Option<String> = None;
+		let mut group_by: Option<String> = None;
+		let mut json_errors: Option<bool> = None;
+		let mut color: Option<ColorChoice> = None;
+		let mut yaml_warnings = false;
+		#[cfg(feature = "rust-types")]
+		let mut infer_rust_types = false;
+		#[cfg(feature = "template")]
+		let mut template: Option<PathBuf> = None;
 
-		let mut parser = lexopt::Parser::from_env();
+		let mut parser = lexopt::Parser::from_args(args);
 		while let Some(arg) = parser.next()? {
+			if OptionKind::of(&arg).is_some_and(|kind| !command.accepts(kind)) {
+				return Err(format!(
+					"'{}' does not apply to the '{}' command",
+					option_name(&arg),
+					command.name()
+				)
+				.into());
+			}
+			if OptionKind::of(&arg).is_some()
+				&& !matches!(arg, Short('f' | 't') | Long("via-daemon"))
+			{
+				client_local_option.get_or_insert_with(|| option_name(&arg));
+			}
 			match arg {
 				Short('f') => {
 					if from.is_some() {
@@ -129,16 +863,383 @@ impl Cli {
 					}
 					to = Some(parser.value()?.parse_with(try_parse_format)?);
 				}
+				Short('j') => {
+					if threads.is_some() {
+						return Err("cannot provide '-j' more than once".into());
+					}
+					threads = Some(parser.value()?.parse()?);
+				}
+				Short('c') => {
+					if toml_container.is_some() {
+						return Err("cannot provide '-c' more than once".into());
+					}
+					toml_container = Some(parser.value()?.string()?);
+				}
+				Long("comment") => {
+					comments.push(parser.value()?.parse_with(try_parse_comment)?);
+				}
+				Long("csv-mismatch") => {
+					if csv_mismatch.is_some() {
+						return Err("cannot provide '--csv-mismatch' more than once".into());
+					}
+					csv_mismatch = Some(parser.value()?.parse_with(try_parse_csv_mismatch)?);
+				}
+				Short('s') => {
+					split_json_arrays = true;
+				}
+				Long("lenient-json") => {
+					input_checks.push((InputCheck::JsonSeparators, true));
+				}
+				Long("max-doc-size") => {
+					if max_doc_size.is_some() {
+						return Err("cannot provide '--max-doc-size' more than once".into());
+					}
+					max_doc_size = Some(parser.value()?.parse()?);
+				}
+				Long("msgpack-resync") => {
+					msgpack_resync = true;
+				}
+				Long("msgpack-keys") => {
+					if msgpack_keys.is_some() {
+						return Err("cannot provide '--msgpack-keys' more than once".into());
+					}
+					msgpack_keys = Some(parser.value()?.parse_with(try_parse_msgpack_keys)?);
+				}
+				Long("edn-tags") => {
+					if edn_tags.is_some() {
+						return Err("cannot provide '--edn-tags' more than once".into());
+					}
+					edn_tags = Some(parser.value()?.parse_with(try_parse_edn_tags)?);
+				}
+				Long("repair-utf8") => {
+					repair_utf8 = true;
+				}
+				Long("known-keys") => {
+					if known_keys.is_some() {
+						return Err("cannot provide '--known-keys' more than once".into());
+					}
+					known_keys = Some(parser.value()?.into());
+				}
+				Long("warn-unknown-keys") => {
+					warn_unknown_keys = true;
+				}
+				Long("audit-strings") => {
+					audit_strings = true;
+				}
+				Long("proto-descriptor") => {
+					if proto_descriptor.is_some() {
+						return Err("cannot provide '--proto-descriptor' more than once".into());
+					}
+					proto_descriptor = Some(parser.value()?.into());
+				}
+				Long("proto-message") => {
+					if proto_message.is_some() {
+						return Err("cannot provide '--proto-message' more than once".into());
+					}
+					proto_message = Some(parser.value()?.string()?);
+				}
+				Long("proto-delimited") => {
+					proto_delimited = true;
+				}
+				Long(name @ ("strict" | "lenient")) => {
+					if strictness.is_some() {
+						return Err(
+							"cannot provide '--strict' or '--lenient' more than once".into()
+						);
+					}
+					strictness = Some(match name {
+						"strict" => Strictness::Strict,
+						_ => Strictness::Lenient,
+					});
+				}
+				Long(name @ ("allow" | "reject")) => {
+					let allow = name == "allow";
+					let checks = parser.value()?.parse_with(try_parse_input_checks)?;
+					input_checks.extend(checks.into_iter().map(|check| (check, allow)));
+				}
+				Long("indent") => {
+					if indent.is_some() {
+						return Err("cannot provide '--indent' more than once".into());
+					}
+					indent = Some(parser.value()?.parse()?);
+				}
+				Long("yaml-blocks") => {
+					if yaml_blocks.is_some() {
+						return Err("cannot provide '--yaml-blocks' more than once".into());
+					}
+					yaml_blocks = Some(parser.value()?.parse_with(try_parse_yaml_blocks)?);
+				}
+				Long("check") => {
+					check = true;
+				}
+				Long("keep-comments") => {
+					keep_comments = true;
+				}
+				Long("backup") => {
+					if backup.is_some() {
+						return Err("cannot provide '--backup' more than once".into());
+					}
+					let suffix = match parser.optional_value() {
+						Some(suffix) => suffix.string()?,
+						None => ".orig".into(),
+					};
+					if suffix.is_empty() {
+						return Err("the suffix for '--backup' cannot be empty".into());
+					}
+					backup = Some(suffix);
+				}
+				Long("prefix") => {
+					if prefix.is_some() {
+						return Err("cannot provide '--prefix' more than once".into());
+					}
+					prefix = Some(parser.value()?.string()?);
+				}
+				Long("name") => {
+					if name.is_some() {
+						return Err("cannot provide '--name' more than once".into());
+					}
+					name = Some(parser.value()?.parse_with(try_parse_split_name)?);
+				}
+				Long("route") => {
+					let route = parser.value()?.parse_with(try_parse_route)?;
+					if routes.iter().any(|r: &Route| r.pathname == route.pathname) {
+						return Err(format!(
+							"cannot provide '--route' to {} more than once",
+							route.pathname.display()
+						)
+						.into());
+					}
+					routes.push(route);
+				}
+				Short('u') => {
+					flush_each_document = true;
+				}
+				Long("doc-comments") => {
+					doc_comments = true;
+				}
+				Long("intern-keys") => {
+					intern_keys = true;
+				}
+				#[cfg(feature = "template")]
+				Long("template") => {
+					if template.is_some() {
+						return Err("cannot provide '--template' more than once".into());
+					}
+					template = Some(parser.value()?.into());
+				}
+				Long("detect") => {
+					if detect.is_some() {
+						return Err("cannot provide '--detect' more than once".into());
+					}
+					detect = Some(parser.value()?.parse_with(try_parse_formats)?);
+				}
+				Long("detect-limit") => {
+					if detect_limit.is_some() {
+						return Err("cannot provide '--detect-limit' more than once".into());
+					}
+					detect_limit = Some(parser.value()?.parse()?);
+				}
+				Long("encoding") => {
+					if encoding.is_some() {
+						return Err("cannot provide '--encoding' more than once".into());
+					}
+					encoding = Some(parser.value()?.parse_with(try_parse_encoding)?);
+				}
+				Long("bom") => {
+					bom = true;
+				}
+				Long("index") => {
+					if index.is_some() {
+						return Err("cannot provide '--index' more than once".into());
+					}
+					index = Some(native_path(parser.value()?.into()));
+				}
+				Long("out-dir") => {
+					if out_dir.is_some() {
+						return Err("cannot provide '--out-dir' more than once".into());
+					}
+					out_dir = Some(native_path(parser.value()?.into()));
+				}
+				Long("serve-unix") => {
+					if serve_unix.is_some() {
+						return Err("cannot provide '--serve-unix' more than once".into());
+					}
+					serve_unix = Some(parser.value()?.into());
+				}
+				Long("daemon") => {
+					daemon = true;
+				}
+				Long("via-daemon") => {
+					via_daemon = true;
+				}
+				Long("editor") => {
+					editor = true;
+				}
+				Long("dry-run") => {
+					dry_run = true;
+				}
+				Long("preserve-metadata") => {
+					preserve_metadata = true;
+				}
+				Long("errors") => {
+					if json_errors.is_some() {
+						return Err("cannot provide '--errors' more than once".into());
+					}
+					json_errors = Some(parser.value()?.parse_with(try_parse_error_format)?);
+				}
+				Long("color") => {
+					if color.is_some() {
+						return Err("cannot provide '--color' more than once".into());
+					}
+					color = Some(parser.value()?.parse_with(ColorChoice::parse)?);
+				}
+				Long("yaml-warnings") => {
+					yaml_warnings = true;
+				}
+				Long("interleave") => {
+					interleave = true;
+				}
+				Long("sort-by") => {
+					if sort_by.is_some() {
+						return Err("cannot provide '--sort-by' more than once".into());
+					}
+					sort_by = Some(parser.value()?.string()?);
+				}
+				Long("group-by") => {
+					if group_by.is_some() {
+						return Err("cannot provide '--group-by' more than once".into());
+					}
+					group_by = Some(parser.value()?.string()?);
+				}
+				Long("schema") => {
+					infer_schema = true;
+				}
+				Long("type-stats") => {
+					type_stats = true;
+				}
+				#[cfg(feature = "rust-types")]
+				Long("rust-types") => {
+					infer_rust_types = true;
+				}
+				Long("merge") => {
+					if merge.is_some() {
+						return Err("cannot provide '--merge' more than once".into());
+					}
+					merge = Some(parser.value()?.parse_with(try_parse_array_merge)?);
+				}
+				Long("report-conflicts") => {
+					report_conflicts = true;
+				}
+				Long("merge-sources") => {
+					merge_sources = true;
+				}
+				Long("head") => {
+					if head.is_some() {
+						return Err("cannot provide '--head' more than once".into());
+					}
+					head = Some(parser.value()?.parse()?);
+				}
+				Long("skip") => {
+					if skip.is_some() {
+						return Err("cannot provide '--skip' more than once".into());
+					}
+					skip = Some(parser.value()?.parse()?);
+				}
+				// Resuming a translation is more naturally described as seeking.
+				Long("skip-bytes" | "seek-bytes") => {
+					if skip_bytes.is_some() {
+						return Err("cannot provide '--skip-bytes' more than once".into());
+					}
+					skip_bytes = Some(parser.value()?.parse()?);
+				}
+				Long("skip-docs") => {
+					if skip_docs.is_some() {
+						return Err("cannot provide '--skip-docs' more than once".into());
+					}
+					skip_docs = Some(parser.value()?.parse()?);
+				}
+				Long("tail") => {
+					if tail.is_some() {
+						return Err("cannot provide '--tail' more than once".into());
+					}
+					tail = Some(parser.value()?.parse()?);
+				}
+				Long("coerce") => {
+					let coercions = parser.value()?.parse_with(try_parse_coercions)?;
+					transforms.push(TransformArg::Ready(xt::Transform::coerce(coercions)));
+				}
+				Long("flatten") => {
+					transforms.push(TransformArg::Ready(
+						xt::Transform::flatten(".").expect("the separator should not be empty"),
+					));
+				}
+				Long("indexed-arrays") => {
+					transforms.push(TransformArg::Ready(xt::Transform::indexed_arrays()));
+				}
+				Long("indexed-maps") => {
+					transforms.push(TransformArg::Ready(xt::Transform::indexed_maps()));
+				}
+				Long("prune") => {
+					transforms.push(TransformArg::Ready(xt::Transform::prune(false)));
+				}
+				Long("prune-empty") => {
+					transforms.push(TransformArg::Ready(xt::Transform::prune(true)));
+				}
+				Long("provenance") => {
+					let key = parser.value()?.string()?;
+					transforms.push(TransformArg::Ready(xt::Transform::provenance(key)));
+				}
+				Long("redact") => {
+					let keys = parser.value()?.string()?;
+					transforms.push(TransformArg::Ready(xt::Transform::redact(keys.split(','))));
+				}
+				Long("rename") => {
+					transforms.push(TransformArg::Rename(parser.value()?.into()));
+				}
+				Long("max-depth") => {
+					let depth = parser.value()?.parse()?;
+					transforms.push(TransformArg::Ready(xt::Transform::max_depth(depth)));
+				}
+				Long("key-order") => {
+					transforms.push(TransformArg::KeyOrder(parser.value()?.into()));
+				}
+				Long("select") => {
+					let expr = parser.value()?.string()?;
+					let select = xt::Transform::select(&expr).map_err(|err| err.to_string())?;
+					transforms.push(TransformArg::Ready(select));
+				}
+				Long("sort-arrays") => {
+					transforms.push(TransformArg::Ready(xt::Transform::sort_arrays()));
+				}
+				Long("sort-arrays-by") => {
+					let key = parser.value()?.string()?;
+					transforms.push(TransformArg::Ready(xt::Transform::sort_arrays_by(key)));
+				}
+				Long("sort-keys") => {
+					transforms.push(TransformArg::Ready(xt::Transform::sort_keys()));
+				}
+				Long("stringify") => {
+					transforms.push(TransformArg::Ready(xt::Transform::stringify()));
+				}
+				Long("unflatten") => {
+					transforms.push(TransformArg::Ready(
+						xt::Transform::unflatten(".").expect("the separator should not be empty"),
+					));
+				}
 				Value(val) => {
-					input_pathnames.push(PathBuf::from(val));
+					input_pathnames.push(native_path(PathBuf::from(val)));
 				}
 				Short('V') | Long("version") => {
 					const VERSION: &str = version_string();
-					let _ = writeln!(io::stdout().lock(), "{VERSION}");
+					let _ = writeln!(die::stdout(), "{VERSION}");
+					process::exit(0);
+				}
+				Long("capabilities") => {
+					let _ = writeln!(die::stdout(), "{}", capabilities_json());
 					process::exit(0);
 				}
 				Short('h') => {
-					write_short_help(io::stdout().lock());
+					write_short_help(die::stdout());
 					process::exit(0);
 				}
 				Long("help") => {
@@ -149,26 +1250,486 @@ impl Cli {
 			}
 		}
 
+		#[cfg(feature = "rust-types")]
+		if infer_schema && infer_rust_types {
+			return Err("cannot provide both '--schema' and '--rust-types'".into());
+		}
+		#[cfg(feature = "rust-types")]
+		if type_stats && infer_rust_types {
+			return Err("cannot provide both '--type-stats' and '--rust-types'".into());
+		}
+		if type_stats && infer_schema {
+			return Err("cannot provide both '--type-stats' and '--schema'".into());
+		}
+		#[cfg(feature = "template")]
+		if template.is_some() && to.is_some() {
+			return Err("cannot provide both '-t' and '--template'".into());
+		}
+
+		let serve = match (&serve_unix, daemon) {
+			(Some(_), true) => {
+				return Err("cannot provide both '--serve-unix' and '--daemon'".into());
+			}
+			(Some(_), false) => Some("--serve-unix"),
+			(None, true) => Some("--daemon"),
+			(None, false) => None,
+		};
+		if let Some(serve) = serve {
+			if cfg!(not(unix)) {
+				return Err(format!("'{serve}' needs a platform with Unix domain sockets").into());
+			}
+			if !input_pathnames.is_empty() {
+				return Err(format!("'{serve}' takes its inputs from requests, not files").into());
+			}
+			if via_daemon {
+				return Err(format!("cannot provide both '{serve}' and '--via-daemon'").into());
+			}
+			if editor {
+				return Err(format!("cannot provide both '{serve}' and '--editor'").into());
+			}
+			if out_dir.is_some() {
+				return Err(format!("cannot provide both '{serve}' and '--out-dir'").into());
+			}
+			if index.is_some() {
+				return Err(format!("cannot provide both '{serve}' and '--index'").into());
+			}
+		}
+		if via_daemon {
+			if cfg!(not(unix)) {
+				return Err("'--via-daemon' needs a platform with Unix domain sockets".into());
+			}
+			if let Some(option) = client_local_option {
+				return Err(format!(
+					"'{option}' does not apply with '--via-daemon', which translates with the daemon's options"
+				)
+				.into());
+			}
+			if input_pathnames.len() > 1 {
+				return Err("'--via-daemon' translates a single input".into());
+			}
+		}
+		if editor {
+			if input_pathnames.iter().any(|p| p != Path::new("-")) {
+				return Err(
+					"'--editor' reads a single document from standard input, not files".into(),
+				);
+			}
+			if out_dir.is_some() {
+				return Err("cannot provide both '--editor' and '--out-dir'".into());
+			}
+			if index.is_some() {
+				return Err("cannot provide both '--editor' and '--index'".into());
+			}
+		}
+		if out_dir.is_some() && index.is_some() {
+			return Err("cannot provide both '--out-dir' and '--index'".into());
+		}
+		if warn_unknown_keys && known_keys.is_none() {
+			return Err("'--warn-unknown-keys' only applies to '--known-keys'".into());
+		}
+		match (&proto_descriptor, &proto_message) {
+			(Some(_), None) => {
+				return Err("'--proto-descriptor' needs '--proto-message' to name a type".into());
+			}
+			(None, Some(_)) => {
+				return Err("'--proto-message' needs '--proto-descriptor' to define it".into());
+			}
+			_ => {}
+		}
+		if proto_delimited && proto_message.is_none() {
+			return Err("'--proto-delimited' only applies to '--proto-message'".into());
+		}
+		if out_dir.is_some()
+			&& (input_pathnames.is_empty() || input_pathnames.iter().any(|p| p == Path::new("-")))
+		{
+			return Err("'--out-dir' needs named input files, not standard input".into());
+		}
+		if dry_run && !matches!(command, Command::Split | Command::Fmt) && out_dir.is_none() {
+			return Err("'--dry-run' only applies to split, fmt, or '--out-dir'".into());
+		}
+		if check && backup.is_some() {
+			return Err("cannot provide both '--check' and '--backup'".into());
+		}
+		if preserve_metadata && command != Command::Fmt && out_dir.is_none() {
+			return Err("'--preserve-metadata' only applies to fmt or '--out-dir'".into());
+		}
+		if interleave && sort_by.is_some() {
+			return Err("cannot provide both '--interleave' and '--sort-by'".into());
+		}
+		if dry_run && check {
+			return Err("cannot provide both '--check' and '--dry-run'".into());
+		}
+		// A prefix goes into every export line as is, where anything but a name would be code.
+		if command == Command::ToEnv
+			&& prefix.as_deref().is_some_and(|prefix| {
+				!prefix.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+					|| !prefix
+						.bytes()
+						.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+			}) {
+			return Err(
+				"'--prefix' for to-env must be a shell variable name like APP_, of ASCII letters, digits, and underscores".into(),
+			);
+		}
+		if command == Command::Diff && input_pathnames.len() != 2 {
+			return Err("the 'diff' command needs exactly two inputs".into());
+		}
+		if command == Command::Merge && merge.is_none() {
+			merge = Some(ArrayMerge::Replace);
+		}
+		if report_conflicts && merge.is_none() {
+			return Err("'--report-conflicts' only applies to merge or '--merge'".into());
+		}
+		if merge_sources && merge.is_none() {
+			return Err("'--merge-sources' only applies to merge or '--merge'".into());
+		}
+		if report_conflicts && merge_sources {
+			return Err("cannot provide both '--report-conflicts' and '--merge-sources'".into());
+		}
+		// Formatting implies pretty printing, where conversion defaults to one line per document.
+		if command == Command::Fmt && indent.is_none() {
+			indent = Some(2);
+		}
+
 		Ok(Cli {
+			command,
 			input_pathnames,
 			from,
-			to: to.unwrap_or(Format::Json),
+			detect,
+			detect_limit,
+			max_doc_size,
+			to,
+			threads,
+			encoding,
+			bom,
+			index,
+			out_dir,
+			serve_unix,
+			daemon,
+			via_daemon,
+			editor,
+			dry_run,
+			preserve_metadata,
+			split_json_arrays,
+			msgpack_resync,
+			msgpack_keys: msgpack_keys.unwrap_or_default(),
+			edn_tags: edn_tags.unwrap_or_default(),
+			repair_utf8,
+			known_keys,
+			warn_unknown_keys,
+			audit_strings,
+			proto_descriptor,
+			proto_message,
+			proto_delimited,
+			strictness,
+			input_checks,
+			flush_each_document,
+			doc_comments,
+			intern_keys,
+			toml_container,
+			comments,
+			csv_mismatch: csv_mismatch.unwrap_or_default(),
+			indent: indent.filter(|&indent| indent > 0),
+			yaml_blocks: yaml_blocks.unwrap_or_default(),
+			prefix,
+			name: name.unwrap_or(SplitName(vec![NamePart::Index])),
+			routes,
+			check,
+			keep_comments,
+			backup,
+			transforms,
+			skip: skip.unwrap_or(0),
+			skip_bytes: skip_bytes.unwrap_or(0),
+			skip_docs: skip_docs.unwrap_or(0),
+			head,
+			tail,
+			merge,
+			report_conflicts,
+			merge_sources,
+			infer_schema,
+			type_stats,
+			interleave,
+			sort_by,
+			group_by,
+			json_errors: json_errors.unwrap_or(editor),
+			color: color.unwrap_or_default(),
+			yaml_warnings,
+			#[cfg(feature = "rust-types")]
+			infer_rust_types,
+			#[cfg(feature = "template")]
+			template,
+		})
+	}
+
+	/// Returns the paths of every input, or of standard input if none were given.
+	fn input_paths(&self) -> InputPaths<impl Iterator<Item = InputPath>> {
+		if self.input_pathnames.is_empty() {
+			InputPaths::one(InputPath::Stdin)
+		} else {
+			InputPaths::many(self.input_pathnames.iter().cloned().map(Into::into))
+		}
+	}
+
+	/// Returns the format of an input from its file extension, if detection could choose it.
+	fn extension_format(&self, path: &InputPath) -> Option<Format> {
+		path.extension_format().filter(|format| {
+			self.detect
+				.as_ref()
+				.is_none_or(|order| order.contains(format))
 		})
 	}
 }
 
+/// Returns the name of a format as accepted by `-f` and `-t`, which is also the file extension
+/// that selects it.
+fn format_name(format: Format) -> &'static str {
+	match format {
+		Format::Json => "json",
+		Format::JsonLines => "jsonl",
+		Format::Msgpack => "msgpack",
+		Format::Toml => "toml",
+		Format::Yaml => "yaml",
+		Format::Null => "null",
+		Format::Csv => "csv",
+		Format::Tsv => "tsv",
+		Format::Bson => "bson",
+		Format::Avro => "avro",
+		Format::Protobuf => "protobuf",
+		Format::Ini => "ini",
+		Format::Edn => "edn",
+		Format::Bencode => "bencode",
+		Format::Ron => "ron",
+		_ => "data",
+	}
+}
+
 fn try_parse_format(s: &str) -> Result<Format, &'static str> {
 	match s {
 		"j" | "json" => Ok(Format::Json),
+		"jsonl" | "ndjson" => Ok(Format::JsonLines),
 		"m" | "msgpack" => Ok(Format::Msgpack),
 		"t" | "toml" => Ok(Format::Toml),
 		"y" | "yaml" => Ok(Format::Yaml),
+		"n" | "null" => Ok(Format::Null),
+		"csv" => Ok(Format::Csv),
+		"tsv" => Ok(Format::Tsv),
+		"bson" => Ok(Format::Bson),
+		"avro" => Ok(Format::Avro),
+		"protobuf" | "pb" => Ok(Format::Protobuf),
+		"ini" => Ok(Format::Ini),
+		"edn" => Ok(Format::Edn),
+		"bencode" => Ok(Format::Bencode),
+		"ron" => Ok(Format::Ron),
 		_ => Err("not a valid format name"),
 	}
 }
 
+fn try_parse_formats(s: &str) -> Result<Vec<Format>, &'static str> {
+	s.split(',').map(try_parse_format).collect()
+}
+
+fn try_parse_encoding(s: &str) -> Result<Encoding, &'static str> {
+	match s.to_ascii_lowercase().as_str() {
+		"utf-8" | "utf8" => Ok(Encoding::Utf8),
+		"utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+		"utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+		"utf-32le" | "utf32le" => Ok(Encoding::Utf32Le),
+		"utf-32be" | "utf32be" => Ok(Encoding::Utf32Be),
+		_ => Err("not a valid output encoding name"),
+	}
+}
+
+/// Parses the name of an error format, returning true for JSON.
+fn try_parse_error_format(s: &str) -> Result<bool, &'static str> {
+	match s {
+		"text" => Ok(false),
+		"json" => Ok(true),
+		_ => Err("not a valid error format name"),
+	}
+}
+
+fn try_parse_coercions(s: &str) -> Result<Vec<Coercion>, &'static str> {
+	s.split(',').try_fold(vec![], |mut coercions, name| {
+		match name {
+			"all" => coercions.extend([Coercion::Number, Coercion::Bool, Coercion::Null]),
+			"number" => coercions.push(Coercion::Number),
+			"bool" => coercions.push(Coercion::Bool),
+			"null" => coercions.push(Coercion::Null),
+			_ => return Err("not a valid coercion name"),
+		}
+		Ok(coercions)
+	})
+}
+
+fn try_parse_input_checks(s: &str) -> Result<Vec<InputCheck>, &'static str> {
+	s.split(',')
+		.map(|name| match name {
+			"duplicate-keys" => Ok(InputCheck::DuplicateKeys),
+			"json-separators" => Ok(InputCheck::JsonSeparators),
+			"yaml-1.1-scalars" => Ok(InputCheck::YamlScalars),
+			"msgpack-ext" => Ok(InputCheck::MsgpackExt),
+			_ => Err("not a valid input check name"),
+		})
+		.collect()
+}
+
+/// Parses the template of `--name`, which must include `{index}` or `{hash}` so that different
+/// documents can get different files.
+fn try_parse_split_name(s: &str) -> Result<SplitName, String> {
+	let mut parts = vec![];
+	let mut rest = s;
+	while let Some(start) = rest.find('{') {
+		if start > 0 {
+			parts.push(NamePart::Text(rest[..start].into()));
+		}
+		let Some(len) = rest[start..].find('}') else {
+			return Err("unclosed '{' in name template".into());
+		};
+		parts.push(match &rest[start + 1..start + len] {
+			"index" => NamePart::Index,
+			"hash" => NamePart::Hash,
+			var => return Err(format!("unknown name template variable '{{{var}}}'")),
+		});
+		rest = &rest[start + len + 1..];
+	}
+	if !rest.is_empty() {
+		parts.push(NamePart::Text(rest.into()));
+	}
+	if !parts
+		.iter()
+		.any(|part| matches!(part, NamePart::Index | NamePart::Hash))
+	{
+		return Err("a name template needs {index} or {hash}".into());
+	}
+	Ok(SplitName(parts))
+}
+
+/// Returns the length of the drive letter and colon at the start of a Windows path, including
+/// any verbatim `\\?\` before them, or 0 if the path doesn't start with a drive.
+fn drive_prefix_len(s: &str) -> usize {
+	let verbatim = if s.starts_with(r"\\?\") { 4 } else { 0 };
+	match s.as_bytes()[verbatim..] {
+		[letter, b':', ..] if letter.is_ascii_alphabetic() => verbatim + 2,
+		_ => 0,
+	}
+}
+
+/// Parses the argument of `--route`, a file and a rule separated by the first `:`.
+///
+/// On Windows, the colon after the drive letter of an absolute file, as in `C:\out\a.json:rule`,
+/// belongs to the file.
+fn try_parse_route(s: &str) -> Result<Route, String> {
+	let drive = if cfg!(windows) {
+		drive_prefix_len(s)
+	} else {
+		0
+	};
+	let Some((pathname, rule)) = s[drive..]
+		.split_once(':')
+		.map(|(rest, rule)| (&s[..drive + rest.len()], rule))
+	else {
+		return Err("a route needs a file and a rule separated by ':'".into());
+	};
+	let pathname = native_path(PathBuf::from(pathname));
+	let Some(format) = InputPath::File(pathname.clone()).extension_format() else {
+		return Err(format!(
+			"cannot choose a format for {} from its extension",
+			pathname.display()
+		));
+	};
+	let rule = match try_parse_index_range(rule) {
+		Some(range) => RouteRule::Indexes(range),
+		None => RouteRule::select(xt::Transform::select(rule).map_err(|err| err.to_string())?),
+	};
+	Ok(Route {
+		pathname,
+		format,
+		rule,
+	})
+}
+
+/// Parses a range of document indexes like `2..5`, `..5`, or `2..`, returning `None` if the
+/// string isn't one.
+fn try_parse_index_range(s: &str) -> Option<ops::Range<u64>> {
+	let (start, end) = s.split_once("..")?;
+	let parse = |bound: &str, default| match bound {
+		"" => Some(default),
+		bound if bound.bytes().all(|b| b.is_ascii_digit()) => bound.parse().ok(),
+		_ => None,
+	};
+	Some(parse(start, 0)?..parse(end, u64::MAX)?)
+}
+
+fn try_parse_array_merge(s: &str) -> Result<ArrayMerge, &'static str> {
+	match s {
+		"replace" => Ok(ArrayMerge::Replace),
+		"concat" => Ok(ArrayMerge::Concat),
+		"union" => Ok(ArrayMerge::Union),
+		_ => match s.strip_prefix("key=") {
+			Some(key) if !key.is_empty() => Ok(ArrayMerge::ByKey(key.into())),
+			_ => Err("not a valid array merge strategy"),
+		},
+	}
+}
+
+/// Splits a comment option like `.replicas=managed by xt` at the first `=` outside of a quoted
+/// key in the path.
+fn try_parse_comment(s: &str) -> Result<(String, String), &'static str> {
+	let (mut quoted, mut escaped) = (false, false);
+	for (i, c) in s.char_indices() {
+		match c {
+			_ if escaped => escaped = false,
+			'\\' if quoted => escaped = true,
+			'"' => quoted = !quoted,
+			'=' if !quoted => return Ok((s[..i].into(), s[i + 1..].into())),
+			_ => {}
+		}
+	}
+	Err("not a path and comment like '.key=text'")
+}
+
+fn try_parse_csv_mismatch(s: &str) -> Result<CsvMismatch, &'static str> {
+	match s {
+		"error" => Ok(CsvMismatch::Error),
+		"empty" => Ok(CsvMismatch::Empty),
+		_ => Err("not a valid CSV mismatch policy"),
+	}
+}
+
+fn try_parse_edn_tags(s: &str) -> Result<EdnTags, &'static str> {
+	match s {
+		"passthrough" => Ok(EdnTags::Passthrough),
+		"stringify" => Ok(EdnTags::Stringify),
+		_ => Err("not a valid EDN tag policy"),
+	}
+}
+
+fn try_parse_msgpack_keys(s: &str) -> Result<MsgpackKeys, &'static str> {
+	match s {
+		"preserve" => Ok(MsgpackKeys::Preserve),
+		"decimal" => Ok(MsgpackKeys::Decimal),
+		"hex" => Ok(MsgpackKeys::Hex),
+		"base64" => Ok(MsgpackKeys::Base64),
+		_ => Err("not a valid msgpack key scheme"),
+	}
+}
+
+fn try_parse_yaml_blocks(s: &str) -> Result<YamlBlockStyle, &'static str> {
+	match s {
+		"auto" => Ok(YamlBlockStyle::Auto),
+		"literal" => Ok(YamlBlockStyle::Literal),
+		"folded" => Ok(YamlBlockStyle::Folded),
+		_ => Err("not a valid YAML block style"),
+	}
+}
+
 /// A usage summary string shared across short and long help output.
-static USAGE: &str = "[-f format] [-t format] [file ...]";
+static USAGE: &str = "[-su] [-f format] [-t format] [-c key] [-j threads] [selection ...] [transform ...] [file ...]";
+
+/// A usage summary string for commands other than conversion, shared across short and long help
+/// output.
+static COMMAND_USAGE: &str =
+	"detect|check|merge|diff|split|fmt|hash|to-env [option ...] [file ...]";
 
 /// Writes short help output to the provided writer, ignoring errors.
 fn write_short_help<W>(mut w: W)
@@ -178,23 +1739,26 @@ where
 	let argv0 = usage_name();
 	let _ = write!(
 		w,
-		r"Usage: {argv0} {USAGE}
-Formats: json, msgpack, toml, yaml
+		r"Usage: {argv0} [convert] {USAGE}
+       {argv0} {COMMAND_USAGE}
+Formats: json, jsonl, msgpack, toml, yaml, null, csv, tsv, bson, avro, protobuf, ini, edn,
+         bencode, ron
 Try '{argv0} --help' for more information.
 "
 	);
 }
 
-/// Writes long help output to standard output, ignoring errors.
+/// Writes long help output to standard output.
 fn print_long_help() {
 	const VERSION: &str = version_string();
 	let argv0 = usage_name();
 	let _ = write!(
-		io::stdout().lock(),
+		die::stdout(),
 		r#"{VERSION} - Translate between serialized data formats
 
 USAGE
-    {argv0} {USAGE}
+    {argv0} [convert] {USAGE}
+    {argv0} {COMMAND_USAGE}
 
     Without -f, xt detects the format of each input by extension
     or content inspection.
@@ -202,17 +1766,228 @@ USAGE
     With no file, or with the special name "-" at any one position,
     xt translates from standard input.
 
+COMMANDS
+    With no command, xt converts its inputs. Each command accepts only the
+    options that apply to it. To convert a file with the name of a command,
+    write it as ./name or after --.
+
+    convert        Translate every input to standard output, or to one file
+                   per input with --out-dir
+    detect         Print the format that xt would translate each input from,
+                   and whether it chose the format by -f, extension, or content
+                   (with the encoding of text content)
+    check          Translate every input without any output, to msgpack
+                   unless -t is given
+    merge          Convert with --merge, merging arrays by replace unless
+                   --merge is given
+    diff           Print each difference between the documents of exactly two
+                   inputs, exiting with status 1 if there are any
+    split          Write each output document to its own file, named by
+                   --prefix and --name (e.g. doc-000000.json)
+    fmt            Reformat each file in place in its own format, or standard
+                   input to standard output, without preserving comments
+    hash           Print the SHA-256 digest of each document with sorted keys,
+                   the same for the same data in any format
+    to-env         Print an export line for each value of each map document,
+                   naming shell variables by key path (e.g. db.port as db_port)
+
 OPTIONS
+    --allow checks Accept the given comma-separated kinds of input, overriding
+                   --strict: duplicate-keys, json-separators, yaml-1.1-scalars,
+                   or msgpack-ext
+    --audit-strings
+                   Warn on standard error about each string with a control
+                   character, invalid UTF-8, or a lone surrogate escape
+    --backup[=suffix]
+                   With fmt, copy each file to file.orig (or the file name plus
+                   suffix) before rewriting it
+    --bom          Start text output with a byte order mark
+    --capabilities Print a JSON document of this build's version, features,
+                   formats, and limits, then exit
+    -c key         Wrap each document of TOML output in an array of tables named key
+    --csv-mismatch policy
+                   Fail at a CSV or TSV output record whose keys differ from the
+                   header (error, the default), or write empty fields (empty)
+    --check        With fmt, list the inputs that aren't formatted instead of
+                   rewriting them, exiting with status 1 if there are any
+    --comment path=text
+                   Write a comment with text before the entry at path in every
+                   YAML or TOML output document, or before each document for .
+    --color when   Color diagnostics on standard error always, never, or if it's
+                   a terminal (auto, the default)
+    --daemon       Serve requests as --serve-unix does on a socket of the user's
+                   own, for clients started with --via-daemon
+    --detect formats
+                   Detect input formats only from the given comma-separated formats,
+                   tried in the given order
+    --detect-limit bytes
+                   Buffer at most bytes of a streamed input like standard input
+                   for format detection, failing to detect formats that need more
+    --doc-comments Write a comment like # doc 12 from service.yaml before each
+                   YAML or TOML output document
+    --dry-run      Write no files with split, fmt, or --out-dir, and list the files
+                   that would be created or overwritten with their document counts
+    --edn-tags policy
+                   Read EDN tagged literals like #inst "..." as their values alone
+                   (passthrough, the default), or as strings of their source text
+                   (stringify)
+    --editor       Translate a single document from standard input for an editor to
+                   replace it with, reporting errors as JSON with byte offsets
+    --encoding enc Encode text output as utf-8 (default), utf-16le, utf-16be,
+                   utf-32le, or utf-32be
+    --errors format
+                   Report errors on standard error as text (default) or as
+                   one-line json records
     -f format      Skip detection and convert every input from the given format
+    --group-by path
+                   Output one map from each value at path to an array of documents
     -h, --help     Print a usage summary, then exit
+    --indent n     Pretty-print JSON output with n spaces per level (default: 0,
+                   one line per document, or 2 with fmt)
+    --index file   Write an index of each output document's source and output
+                   byte range to file, in the format of its extension (default: json)
+    --interleave   Output one document from each input in turn, rather than every
+                   document of each input in turn
+    --intern-keys  Share one copy of each repeated map key among the documents held
+                   in memory for -j, --tail, reordering, --group-by, or --merge
+    -j threads     Serialize multi-document output on the given number of threads
+    --keep-comments
+                   With fmt, keep the comments and blank lines of YAML inputs
+    --known-keys file
+                   Fail on any key of an input map that the map in the same
+                   position of the reference document in file lacks
+    --lenient      Accept duplicate keys, commas and comments between JSON
+                   documents, YAML 1.1 scalars, and msgpack extension types
+    --lenient-json Accept commas and // or # comment lines between JSON
+                   documents (same as --allow json-separators)
+    --max-doc-size bytes
+                   Fail on any input document larger than bytes, before reading
+                   past the limit where the format allows
+    --merge arrays Deep-merge all documents into one, merging arrays by replace,
+                   concat, union, or key=name
+    --merge-sources
+                   With merge or --merge, follow the merged document with one of
+                   the same shape naming the input that set each value
+    --msgpack-keys scheme
+                   Write integer, boolean, and bin map keys of msgpack input as
+                   strings in decimal, hex (0x2a), or base64 (for bin), or keep
+                   them as they are (preserve, the default)
+    --msgpack-resync
+                   Skip past corrupt msgpack input to the next map or array that
+                   decodes, with a warning, rather than failing
+    --name template
+                   Name each file from split by template after --prefix, where
+                   {{index}} is the document's index and {{hash}} a SHA-256 prefix
+                   of the file's content (default: {{index}})
+    --out-dir dir  Write each input to its own file in dir instead, named after
+                   the input with the extension of the output format
+    --prefix p     Start the name of each file from split with p (default: doc-),
+                   or the name of each variable from to-env
+    --preserve-metadata
+                   Give each file that fmt rewrites or --out-dir writes the
+                   modification time and permissions of its input
+    --proto-delimited
+                   Read and write protobuf as a stream of messages that each
+                   follow their length, rather than a single message
+    --proto-descriptor file
+                   Read the protobuf message type from the compiled descriptor set
+                   in file, as from protoc --descriptor_set_out --include_imports
+    --proto-message name
+                   Translate protobuf as messages of the type with the full name
+                   given, like package.Message, from --proto-descriptor
+    -s             Translate each element of a top-level JSON array as a document
+    --repair-utf8  Replace invalid UTF-8 in JSON input and msgpack strings with
+                   U+FFFD, with a warning, rather than failing
+    --reject checks
+                   Reject the given comma-separated kinds of input, as for
+                   --allow, overriding --lenient
+    --report-conflicts
+                   With merge or --merge, output every path where documents
+                   disagreed, with each value and its source, instead of the
+                   merged document
+    --route file:rule
+                   With split, write every document that rule matches to file, in
+                   the format of its extension, where rule is a --select
+                   expression or a range of document indexes like 0..10
+    --rust-types   Output Rust type definitions inferred from all documents, instead
+                   of them (requires the "rust-types" build feature)
+    --schema       Output a JSON Schema inferred from all documents, instead of them
+    --serve-unix path
+                   Translate length-prefixed requests from clients of a Unix
+                   socket at path, rather than any input files, until interrupted
+    --sort-by path Output every document from every input sorted by its value at
+                   path, rather than in input order
+    --strict       Reject duplicate keys, anything but whitespace between JSON
+                   documents, YAML 1.1 scalars, and msgpack extension types
     -t format      Convert to the given format (default: json)
+    --template file
+                   Render each document as text through a TinyTemplate file, rather
+                   than converting it (requires the "template" build feature)
+    --type-stats   Output the count of each type of value at each path of all
+                   documents, like .port: {{int: 998, string: 2}}, instead of them
+    -u             Flush output after every document
     -V, --version  Print version information, then exit
+    --via-daemon   Translate the input through the daemon from --daemon, whose
+                   options apply instead of any but -f and -t
+    --warn-unknown-keys
+                   With --known-keys, warn on standard error about each unknown
+                   key rather than failing
+    --yaml-blocks style
+                   Write the multi-line strings of YAML output as literal (|) or
+                   folded (>) block scalars, rather than as the serializer
+                   chooses (auto, the default)
+    --yaml-warnings
+                   Warn on standard error about unquoted YAML scalars like no,
+                   0123, 1:20, or 2001-12-14, which YAML 1.1 parsers read as
+                   other types than xt does
+
+TRANSFORMS
+    Transforms apply to every document in the order given.
+
+    --coerce types Convert strings that look like other types (comma-separated:
+                   number, bool, null, or all) into those types
+    --flatten      Flatten nested maps into dotted keys (a.b.c)
+    --indexed-arrays
+                   Convert maps with keys 0, 1, 2, and so on into arrays
+    --indexed-maps Convert arrays into maps with keys 0, 1, 2, and so on
+    --key-order file
+                   Order the keys of maps as the keys of the map in file list them
+    --max-depth n  Keep n levels of nesting, summarizing deeper arrays and maps
+                   (like [… 42 items]) to preview large documents
+    --prune        Remove null values from maps and arrays
+    --prune-empty  Remove null values and empty maps and arrays
+    --provenance key
+                   Record the source, index, and offset of each document under key
+    --redact keys  Replace the values of comma-separated keys (or globs) at any depth
+    --rename file  Rename keys using a mapping of paths to new names from file
+    --select expr  Keep only documents matching expr (e.g. '.kind == "Deployment"')
+    --sort-arrays  Sort arrays of scalars (null, bools, numbers, then strings)
+    --sort-arrays-by key
+                   Sort arrays of scalars, and arrays of maps by the value of key
+    --sort-keys    Sort the entries of every map by key
+    --stringify    Convert every scalar value into a string
+    --unflatten    Expand dotted keys into nested maps
+
+SELECTION
+    Selection counts documents across all inputs, after any transforms.
+
+    --head n       Output at most n documents, then stop reading input
+    --skip n       Skip the first n documents
+    --skip-bytes n Skip the first n bytes of every input (alias: --seek-bytes)
+    --skip-docs n  Skip the first n documents of every input, before any
+                   transforms
+    --tail n       Output only the last n documents
 
 FORMATS
     json, j
         Default for .json files.
         Multi-document (self-delineating or whitespace between values).
 
+    jsonl, ndjson
+        Default for .jsonl and .ndjson files. Never detected from content.
+        Reads as json does. Outputs one compact document per line, even with
+        --indent.
+
     msgpack, m
         Default for .msgpack files.
         Multi-document (naturally self-delineating).
@@ -225,6 +2000,57 @@ FORMATS
         Default for .yaml and .yml files.
         Multi-document (with --- or ... syntax).
 
+    null, n
+        Output only. Parses every document and discards it, to measure or
+        check parsing alone.
+
+    csv
+        Default for .csv files. Never detected from content.
+        Multi-document (one per record after the header, mapping each header
+        field to the record's field as a string). Outputs each map document,
+        or each map in an array document, as a record, with the header from
+        the keys of the first.
+
+    tsv
+        Default for .tsv and .tab files. Like csv, with tabs between fields.
+
+    bson
+        Default for .bson files. Never detected from content.
+        Multi-document (by concatenation, as from mongodump). ObjectIds,
+        datetimes, and other BSON types translate as {{"$oid": ...}} style maps.
+
+    avro
+        Input only. Default for .avro files. Never detected from content.
+        Multi-document (one per record of an object container file, decoded
+        by the schema in its header, with null, deflate, or snappy blocks).
+
+    protobuf, pb
+        Default for .protobuf and .binpb files. Never detected from content.
+        Needs --proto-descriptor and --proto-message. Single message per input
+        or output, or multi-document with --proto-delimited.
+
+    ini
+        Default for .ini files. Never detected from content.
+        Single document per input or output (keys, then a map for each
+        [section], with string values). Outputs fail on deeper nesting.
+
+    edn
+        Default for .edn files. Never detected from content.
+        Multi-document (one top-level element per document). Keywords and
+        symbols read as strings of their names; map keys that look like keyword
+        names write as keywords. See --edn-tags for tagged literals.
+
+    bencode
+        Default for .torrent files. Never detected from content. Input only.
+        Multi-document (one top-level value per document). Byte strings that
+        are not valid UTF-8 read as binary data, like MessagePack bin values.
+
+    ron
+        Default for .ron files. Never detected from content.
+        Single document per input or output. Structs read as maps of their
+        fields without their names, tuples as arrays, and unit variants as
+        strings of their names. Outputs write maps as RON maps.
+
 CAVEATS
     xt does not guarantee that every translation is possible, or lossless, or
     reversible. xt's behavior is undefined if an input file is modified while
@@ -253,6 +2079,34 @@ const fn version_string() -> &'static str {
 	}
 }
 
+/// Returns a JSON document describing what this build supports, for tools that drive xt.
+///
+/// Later versions may add keys to the document, but won't remove or change the meaning of any.
+fn capabilities_json() -> String {
+	let capabilities = xt::capabilities();
+	let formats: Vec<_> = { capabilities.formats().iter() }
+		.map(|&format| {
+			serde_json::json!({
+				"name": format_name(format),
+				"input": capabilities.supports_input(format),
+				"output": capabilities.supports_output(format),
+				"detected": capabilities.is_detected(format),
+				"multi_document": capabilities.supports_multi_document_output(format),
+				"depth_limit": capabilities.depth_limit(format),
+			})
+		})
+		.collect();
+	let document = serde_json::json!({
+		"version": capabilities.version(),
+		"features": capabilities.features(),
+		"formats": formats,
+		"limits": {
+			"detection_buffer": capabilities.detection_buffer_limit(),
+		},
+	});
+	serde_json::to_string_pretty(&document).expect("capabilities should serialize")
+}
+
 enum InputPath {
 	Stdin,
 	File(PathBuf),
@@ -261,6 +2115,7 @@ enum InputPath {
 enum Input {
 	Stdin,
 	File(File),
+	SeekableFile(File),
 	Mmap(memmap2::Mmap),
 }
 
@@ -281,6 +2136,13 @@ impl InputPath {
 			Self::File(path) => File::open(path)?,
 		};
 
+		// Mapping only makes sense for regular files. Some non-regular files (like character
+		// devices) can be mapped without error while producing nothing like their actual stream
+		// contents, so we skip the attempt entirely rather than relying on mmap to fail.
+		if !file.metadata().is_ok_and(|meta| meta.is_file()) {
+			return Ok(Input::File(file));
+		}
+
 		// (UN)SAFETY: An Mmap provides access to arbitrary file data as a &[u8], and it's
 		// Undefined Behavior for the data behind a &[u8] to change. However, this _can_ happen
 		// if the mapped file is modified outside of the process.
@@ -312,8 +2174,9 @@ impl InputPath {
 			return Ok(Input::Mmap(map));
 		}
 
-		// If mmap fails (process substitution, named pipes, etc.), fall back to reader input.
-		Ok(Input::File(file))
+		// If mmap fails for some other reason, fall back to reader input. Regular files can still
+		// seek, which saves format detection from buffering everything it reads.
+		Ok(Input::SeekableFile(file))
 	}
 
 	fn extension_format(&self) -> Option<Format> {
@@ -328,9 +2191,19 @@ impl InputPath {
 			.as_deref()
 		{
 			Some("json") => Some(Format::Json),
+			Some("jsonl" | "ndjson") => Some(Format::JsonLines),
 			Some("msgpack") => Some(Format::Msgpack),
 			Some("toml") => Some(Format::Toml),
 			Some("yaml" | "yml") => Some(Format::Yaml),
+			Some("csv") => Some(Format::Csv),
+			Some("tsv" | "tab") => Some(Format::Tsv),
+			Some("bson") => Some(Format::Bson),
+			Some("avro") => Some(Format::Avro),
+			Some("protobuf" | "binpb") => Some(Format::Protobuf),
+			Some("ini") => Some(Format::Ini),
+			Some("edn") => Some(Format::Edn),
+			Some("torrent") => Some(Format::Bencode),
+			Some("ron") => Some(Format::Ron),
 			_ => None,
 		}
 	}
@@ -0,0 +1,591 @@
+//! Deep merging of every document in a stream into a single document.
+//!
+//! Merging combines each document into the result of merging all documents before it. Maps merge
+//! recursively by key, arrays merge according to an [`ArrayMerge`] strategy, and any other value
+//! (including a map or array meeting a value of a different type) replaces the earlier value.
+//!
+//! A translator that merges must hold the merged document in memory until it finishes the stream,
+//! and only then passes it on to the output.
+//!
+//! A merge can instead output a report of its conflicts: the paths where a later document replaced
+//! a different value from an earlier one, with every value the path took and the source of each.
+//! It can also follow the merged document with a second document of the same shape, giving the
+//! source of each value in place of the value. Both mean remembering which document set every
+//! path that a merge adds or replaces, which an [`Audit`] does as a map from paths to documents,
+//! where the nearest ancestor of a path in the map gives the document that set it.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::intern;
+use crate::transcode::Value;
+use crate::transform::path;
+
+/// A strategy for merging an array from a later document into an array from an earlier one.
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum ArrayMerge {
+	/// Replace the earlier array with the later one.
+	Replace,
+	/// Append the elements of the later array to the earlier one.
+	Concat,
+	/// Append the elements of the later array that are not identical to any element of the
+	/// earlier one.
+	Union,
+	/// Merge each map element of the later array into the map element of the earlier array with
+	/// an identical value for the given key, and append any other elements.
+	///
+	/// This supports strategic merges of lists like Kubernetes container specs, where elements
+	/// match on a `name` key.
+	ByKey(String),
+}
+
+/// The state of a merge across all documents in a stream.
+pub(crate) struct Merge {
+	arrays: ArrayMerge,
+	merged: Option<Value<'static>>,
+	/// Whether the merged document takes its map keys from the [`intern`] pool.
+	intern: bool,
+	/// Whether to output a report of conflicts instead of the merged document.
+	report_conflicts: bool,
+	/// Whether to follow the merged document with the source of each of its values.
+	attribute_sources: bool,
+	/// The sources of merged values, for conflict reports and source attribution.
+	audit: Option<Audit>,
+}
+
+impl Merge {
+	pub(crate) fn new(arrays: ArrayMerge) -> Merge {
+		Merge {
+			arrays,
+			merged: None,
+			intern: false,
+			report_conflicts: false,
+			attribute_sources: false,
+			audit: None,
+		}
+	}
+
+	pub(crate) fn set_intern_keys(&mut self, intern: bool) {
+		self.intern = intern;
+	}
+
+	pub(crate) fn set_report_conflicts(&mut self, report: bool) {
+		self.report_conflicts = report;
+		self.audit = (self.report_conflicts || self.attribute_sources).then(Audit::default);
+	}
+
+	pub(crate) fn set_attribute_sources(&mut self, attribute: bool) {
+		self.attribute_sources = attribute;
+		self.audit = (self.report_conflicts || self.attribute_sources).then(Audit::default);
+	}
+
+	fn add(&mut self, value: Value, source: Option<&str>) {
+		let value = intern::into_owned(value, self.intern);
+		let mut audit = self.audit.as_mut();
+		if let Some(audit) = audit.as_deref_mut() {
+			audit.start_document(source, self.merged.is_none());
+		}
+		match &mut self.merged {
+			Some(merged) => merge(merged, value, &self.arrays, audit),
+			None => self.merged = Some(value),
+		}
+	}
+
+	/// Returns the documents to output at the end of the stream, if any documents were merged:
+	/// the report of conflicts, or the merged document followed by the source of each value if
+	/// requested.
+	fn take_output(&mut self) -> Vec<Value<'static>> {
+		let Some(merged) = self.merged.take() else {
+			return vec![];
+		};
+		match self.audit.take() {
+			Some(audit) if self.report_conflicts => vec![audit.into_conflicts()],
+			Some(audit) => {
+				let sources = audit.attribute(&merged, &mut String::new(), 0);
+				vec![merged, sources]
+			}
+			None => vec![merged],
+		}
+	}
+}
+
+/// Merges `next` into `base`, keeping track of conflicts in the audit if there is one.
+fn merge(
+	base: &mut Value<'static>,
+	next: Value<'static>,
+	arrays: &ArrayMerge,
+	mut audit: Option<&mut Audit>,
+) {
+	match (base, next) {
+		(Value::Map(base), Value::Map(next)) => {
+			for (key, value) in next {
+				let saved = audit.as_deref_mut().map(|audit| audit.enter_key(&key));
+				match base.iter_mut().find(|(k, _)| *k == key) {
+					Some((_, existing)) => merge(existing, value, arrays, audit.as_deref_mut()),
+					None => {
+						if let Some(audit) = audit.as_deref_mut() {
+							audit.add();
+						}
+						base.push((key, value));
+					}
+				}
+				if let (Some(audit), Some(saved)) = (audit.as_deref_mut(), saved) {
+					audit.leave(saved);
+				}
+			}
+		}
+		(Value::Seq(base), Value::Seq(next)) => match arrays {
+			ArrayMerge::Replace => {
+				if let Some(audit) = audit {
+					audit.replace(&Value::Seq(base.clone()), &Value::Seq(next.clone()));
+				}
+				*base = next;
+			}
+			ArrayMerge::Concat => {
+				for value in next {
+					push_element(base, value, audit.as_deref_mut());
+				}
+			}
+			ArrayMerge::Union => {
+				for value in next {
+					if !base.contains(&value) {
+						push_element(base, value, audit.as_deref_mut());
+					}
+				}
+			}
+			ArrayMerge::ByKey(key) => {
+				for value in next {
+					let pos = element_key(&value, key)
+						.and_then(|id| base.iter().position(|e| element_key(e, key) == Some(id)));
+					match pos {
+						Some(pos) => {
+							let saved = audit.as_deref_mut().map(|audit| audit.enter_index(pos));
+							merge(&mut base[pos], value, arrays, audit.as_deref_mut());
+							if let (Some(audit), Some(saved)) = (audit.as_deref_mut(), saved) {
+								audit.leave(saved);
+							}
+						}
+						None => push_element(base, value, audit.as_deref_mut()),
+					}
+				}
+			}
+		},
+		(base, next) => {
+			if let Some(audit) = audit {
+				audit.replace(base, &next);
+			}
+			*base = next;
+		}
+	}
+}
+
+/// Appends an element from a later document to an array.
+fn push_element(base: &mut Vec<Value<'static>>, value: Value<'static>, audit: Option<&mut Audit>) {
+	if let Some(audit) = audit {
+		let saved = audit.enter_index(base.len());
+		audit.add();
+		audit.leave(saved);
+	}
+	base.push(value);
+}
+
+/// Returns the value of `key` in a map element of an array.
+fn element_key<'v, 'a>(element: &'v Value<'a>, key: &str) -> Option<&'v Value<'a>> {
+	let Value::Map(map) = element else {
+		return None;
+	};
+	map.iter()
+		.find(|(k, _)| matches!(k, Value::String(k) if k == key))
+		.map(|(_, v)| v)
+}
+
+/// The source of each value of a merge, and the conflicts between them.
+#[derive(Default)]
+struct Audit {
+	/// The source name of every merged document, by its index in the stream.
+	sources: Vec<Option<String>>,
+	/// The index of the document that set each path that a document added or replaced.
+	origins: BTreeMap<String, usize>,
+	conflicts: Vec<Conflict>,
+	/// The path of the value being merged.
+	path: String,
+	/// The index of the document that set the value being merged over.
+	origin: usize,
+}
+
+/// A path where documents disagreed, with every value it took.
+struct Conflict {
+	path: String,
+	/// Each document index and the value it set at the path, in order.
+	values: Vec<(usize, Value<'static>)>,
+}
+
+impl Audit {
+	/// Prepares to merge the next document of the stream, which sets every path if it's first.
+	fn start_document(&mut self, source: Option<&str>, first: bool) {
+		if first {
+			self.origins.insert(String::new(), self.sources.len());
+		}
+		self.sources.push(source.map(Into::into));
+		self.path.clear();
+		self.origin = self.origins.get("").copied().unwrap_or_default();
+	}
+
+	/// Returns the index of the document being merged.
+	fn document(&self) -> usize {
+		self.sources.len() - 1
+	}
+
+	/// Descends into the value at a map key, and returns what [`leave`](Audit::leave) needs to
+	/// come back up.
+	fn enter_key(&mut self, key: &Value) -> (usize, usize) {
+		let saved = (self.path.len(), self.origin);
+		push_key(&mut self.path, key);
+		self.descend();
+		saved
+	}
+
+	/// Descends into the element at an array index, like [`enter_key`](Audit::enter_key).
+	fn enter_index(&mut self, index: usize) -> (usize, usize) {
+		let saved = (self.path.len(), self.origin);
+		self.path.push_str(&format!("[{index}]"));
+		self.descend();
+		saved
+	}
+
+	fn descend(&mut self) {
+		if let Some(&origin) = self.origins.get(&self.path) {
+			self.origin = origin;
+		}
+	}
+
+	fn leave(&mut self, (len, origin): (usize, usize)) {
+		self.path.truncate(len);
+		self.origin = origin;
+	}
+
+	/// Records that the document being merged adds a value at the current path.
+	fn add(&mut self) {
+		self.origins.insert(self.path.clone(), self.document());
+	}
+
+	/// Records that the document being merged replaces a value at the current path, which is a
+	/// conflict if the values differ.
+	fn replace(&mut self, old: &Value<'static>, new: &Value<'static>) {
+		if old == new {
+			return;
+		}
+		let document = self.document();
+		let index = match self.conflicts.iter().position(|c| c.path == self.path) {
+			Some(index) => index,
+			None => {
+				self.conflicts.push(Conflict {
+					path: self.path.clone(),
+					values: vec![(self.origin, old.clone())],
+				});
+				self.conflicts.len() - 1
+			}
+		};
+		self.conflicts[index].values.push((document, new.clone()));
+
+		// The replacement sets everything under the path too.
+		let path = &self.path;
+		let descendants: Vec<_> = self
+			.origins
+			.range::<String, _>(path..)
+			.map(|(p, _)| p)
+			.take_while(|p| p.starts_with(path.as_str()))
+			.filter(|p| p[path.len()..].starts_with(['.', '[']))
+			.cloned()
+			.collect();
+		for descendant in descendants {
+			self.origins.remove(&descendant);
+		}
+		self.origins.insert(self.path.clone(), document);
+	}
+
+	/// Returns the source name of a document as a value, or null for an unnamed source.
+	fn source(&self, index: usize) -> Value<'static> {
+		match &self.sources[index] {
+			Some(source) => Value::String(source.clone().into()),
+			None => Value::Unit,
+		}
+	}
+
+	/// Returns a copy of a merged value at a path, with the source of each value other than a
+	/// non-empty map or array in its place, given the document that set its parent.
+	fn attribute(
+		&self,
+		value: &Value<'static>,
+		path: &mut String,
+		origin: usize,
+	) -> Value<'static> {
+		let origin = self.origins.get(path.as_str()).copied().unwrap_or(origin);
+		let len = path.len();
+		match value {
+			Value::Map(map) if !map.is_empty() => Value::Map(
+				map.iter()
+					.map(|(key, value)| {
+						push_key(path, key);
+						let value = self.attribute(value, path, origin);
+						path.truncate(len);
+						(key.clone(), value)
+					})
+					.collect(),
+			),
+			Value::Seq(seq) if !seq.is_empty() => Value::Seq(
+				seq.iter()
+					.enumerate()
+					.map(|(i, value)| {
+						path.push_str(&format!("[{i}]"));
+						let value = self.attribute(value, path, origin);
+						path.truncate(len);
+						value
+					})
+					.collect(),
+			),
+			_ => self.source(origin),
+		}
+	}
+
+	/// Returns the conflicts as a document: an array with a map for every conflict in the order
+	/// of its first occurrence, containing its `path`, the source that set its final value under
+	/// `winner`, and its `values` as maps of each `source` and `value`.
+	fn into_conflicts(self) -> Value<'static> {
+		let source = |index: usize| self.source(index);
+		let key = |key: &'static str| Value::String(key.into());
+		let conflicts = self.conflicts.iter().map(|conflict| {
+			let winner = conflict
+				.values
+				.last()
+				.map_or(Value::Unit, |&(i, _)| source(i));
+			let values = conflict.values.iter().map(|(i, value)| {
+				Value::Map(vec![
+					(key("source"), source(*i)),
+					(key("value"), value.clone()),
+				])
+			});
+			let path = match conflict.path.as_str() {
+				"" => ".".to_owned(),
+				path => path.to_owned(),
+			};
+			Value::Map(vec![
+				(key("path"), Value::String(path.into())),
+				(key("winner"), winner),
+				(key("values"), Value::Seq(values.collect())),
+			])
+		});
+		Value::Seq(conflicts.collect())
+	}
+}
+
+/// Appends a map key to a path, spelling a key other than a string as its JSON encoding.
+pub(crate) fn push_key(path: &mut String, key: &Value) {
+	match key {
+		Value::String(key) => path::push_key(path, key),
+		key => path::push_key(path, &serde_json::to_string(key).unwrap_or_default()),
+	}
+}
+
+/// An [`Output`](crate::Output) that merges every document instead of passing it through, when
+/// merging is enabled.
+pub(crate) struct Output<'m, 's, O> {
+	output: O,
+	merge: Option<&'m mut Merge>,
+	/// The source name of the input, for conflict reports and source attribution.
+	source: Option<&'s str>,
+}
+
+impl<'m, 's, O> Output<'m, 's, O> {
+	pub(crate) fn new(
+		output: O,
+		merge: Option<&'m mut Merge>,
+		source: Option<&'s str>,
+	) -> Output<'m, 's, O> {
+		Output {
+			output,
+			merge,
+			source,
+		}
+	}
+
+	/// Passes the merged document of the stream (or the report of its conflicts) to the output,
+	/// along with the sources of its values if requested, and returns the output.
+	pub(crate) fn finish<'i>(mut self) -> crate::Result<O>
+	where
+		O: crate::Output<'i>,
+	{
+		for merged in self.merge.map(Merge::take_output).unwrap_or_default() {
+			self.output.transcode_value(merged)?;
+		}
+		Ok(self.output)
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, '_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		match &mut self.merge {
+			Some(merge) => {
+				merge.add(Value::deserialize(de)?, self.source);
+				Ok(())
+			}
+			None => self.output.transcode_from(de),
+		}
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		match &mut self.merge {
+			Some(merge) => {
+				merge.add(Value::deserialize(de)?, self.source);
+				Ok(())
+			}
+			None => self.output.transcode_borrowed_from(de),
+		}
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		match &mut self.merge {
+			Some(merge) => {
+				merge.add(value, self.source);
+				Ok(())
+			}
+			None => self.output.transcode_value(value),
+		}
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		match &mut self.merge {
+			Some(merge) => {
+				merge.add(value, self.source);
+				Ok(())
+			}
+			None => self.output.transcode_borrowed_value(value),
+		}
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn merge_json(arrays: ArrayMerge, docs: &[&str]) -> String {
+		let mut merge = Merge::new(arrays);
+		for doc in docs {
+			let value = Value::deserialize(&mut serde_json::Deserializer::from_str(doc)).unwrap();
+			merge.add(value, None);
+		}
+		serde_json::to_string(&merge.merged.unwrap()).unwrap()
+	}
+
+	#[test]
+	fn merge_array_strategies() {
+		const DOCS: &[&str] = &[
+			r#"{"a":{"b":1,"c":[1,2]},"d":[{"name":"x","v":1},{"name":"y"}]}"#,
+			r#"{"a":{"c":[2,3],"e":true},"d":[{"name":"x","w":2},{"name":"z"},3]}"#,
+		];
+		const CASES: &[(ArrayMerge, &str)] = &[
+			(
+				ArrayMerge::Replace,
+				r#"{"a":{"b":1,"c":[2,3],"e":true},"d":[{"name":"x","w":2},{"name":"z"},3]}"#,
+			),
+			(
+				ArrayMerge::Concat,
+				r#"{"a":{"b":1,"c":[1,2,2,3],"e":true},"d":[{"name":"x","v":1},{"name":"y"},{"name":"x","w":2},{"name":"z"},3]}"#,
+			),
+			(
+				ArrayMerge::Union,
+				r#"{"a":{"b":1,"c":[1,2,3],"e":true},"d":[{"name":"x","v":1},{"name":"y"},{"name":"x","w":2},{"name":"z"},3]}"#,
+			),
+		];
+		for (arrays, want) in CASES {
+			assert_eq!(merge_json(arrays.clone(), DOCS), *want);
+		}
+		assert_eq!(
+			merge_json(ArrayMerge::ByKey("name".into()), DOCS),
+			r#"{"a":{"b":1,"c":[1,2,2,3],"e":true},"d":[{"name":"x","v":1,"w":2},{"name":"y"},{"name":"z"},3]}"#,
+		);
+	}
+
+	#[test]
+	fn report_conflicts_with_sources() {
+		const DOCS: &[(&str, &str)] = &[
+			("a", r#"{"x":1,"y":{"z":[1]},"n":[{"name":"p","v":1}]}"#),
+			(
+				"b",
+				r#"{"x":1,"y":{"z":[2],"w":true},"n":[{"name":"p","v":2}]}"#,
+			),
+			("c", r#"{"x":2,"y":{"w":false}}"#),
+			("d", r#"{"y":{"w":false},"n":[{"name":"p","v":3}]}"#),
+		];
+		let mut merge = Merge::new(ArrayMerge::ByKey("name".into()));
+		merge.set_report_conflicts(true);
+		for (source, doc) in DOCS {
+			let value = Value::deserialize(&mut serde_json::Deserializer::from_str(doc)).unwrap();
+			merge.add(value, Some(source));
+		}
+		assert_eq!(
+			serde_json::to_string(&merge.take_output()).unwrap(),
+			concat!(
+				r#"[[{"path":".n[0].v","winner":"d","values":[{"source":"a","value":1},{"source":"b","value":2},{"source":"d","value":3}]},"#,
+				r#"{"path":".x","winner":"c","values":[{"source":"a","value":1},{"source":"c","value":2}]},"#,
+				r#"{"path":".y.w","winner":"c","values":[{"source":"b","value":true},{"source":"c","value":false}]}]]"#,
+			),
+		);
+	}
+
+	#[test]
+	fn attribute_sources_of_merged_values() {
+		const DOCS: &[(&str, &str)] = &[
+			("a", r#"{"x":1,"y":{"z":1},"l":[1],"e":{}}"#),
+			("b", r#"{"x":1,"y":2,"l":[2]}"#),
+			("c", r#"{"y":{"w":3},"l":[2,3]}"#),
+		];
+		let mut merge = Merge::new(ArrayMerge::Union);
+		merge.set_attribute_sources(true);
+		for (source, doc) in DOCS {
+			let value = Value::deserialize(&mut serde_json::Deserializer::from_str(doc)).unwrap();
+			merge.add(value, Some(source));
+		}
+		assert_eq!(
+			serde_json::to_string(&merge.take_output()).unwrap(),
+			concat!(
+				r#"[{"x":1,"y":{"w":3},"l":[1,2,3],"e":{}},"#,
+				r#"{"x":"a","y":{"w":"c"},"l":["a","b","c"],"e":"a"}]"#,
+			),
+		);
+	}
+
+	#[test]
+	fn merge_replaces_mismatched_types() {
+		assert_eq!(
+			merge_json(
+				ArrayMerge::Concat,
+				&[r#"{"a":[1]}"#, r#"{"a":{"b":1}}"#, "[2]"]
+			),
+			"[2]"
+		);
+	}
+}
@@ -1,42 +1,46 @@
 //! The MessagePack data format.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::error;
 use std::fmt::{self, Display};
 use std::io::{self, BufRead, BufReader, Read, Write};
 
 use rmp::Marker;
 use rmp_serde::decode::Error::{InvalidDataRead, InvalidMarkerRead};
-use serde::{Deserialize, de, ser};
+use serde::{Deserialize, Serialize, de};
 
-use crate::input::{self, Input, Ref};
+use crate::Format;
+use crate::detect::{self, Trial};
+use crate::input::{self, CountingReader, Input, Ref};
 use crate::transcode;
 
+mod direct;
+pub(crate) mod keys;
+mod serializer;
+
+use self::serializer::{BufferPool, Serializer};
+
+pub(crate) use self::direct::to_json;
+pub use self::keys::MsgpackKeys;
+
 /// The maximum allowed nesting depth of MessagePack values.
 ///
 /// This particular value is the undocumented default from [`rmp_serde`], which seems to reliably
 /// prevent stack overflows on debug builds using the default main thread stack size on Linux and
 /// macOS.
-const DEPTH_LIMIT: usize = 1024;
+pub(crate) const DEPTH_LIMIT: usize = 1024;
 
-pub(crate) fn input_matches(mut input: Ref) -> io::Result<bool> {
+pub(crate) fn input_matches(mut input: Ref) -> io::Result<Trial> {
 	// In MessagePack, any byte below 0x80 represents a literal unsigned integer. That means any
 	// ASCII text input is a valid multi-document MessagePack stream, where every "document" is
 	// practically meaningless. To prevent these kinds of weird matches, we only detect input as
 	// MessagePack when the first byte indicates that the next value will be a map or array.
 	// Arbitrary non-ASCII input that happens to match one of these markers (e.g. certain UTF-8
 	// multibyte sequences) is extremely unlikely to be a valid sequence of MessagePack values.
-	if !matches!(
-		input.prefix(1)?.first().copied().map(Marker::from_u8),
-		Some(
-			Marker::FixArray(_)
-				| Marker::Array16
-				| Marker::Array32
-				| Marker::FixMap(_)
-				| Marker::Map16
-				| Marker::Map32
-		)
-	) {
-		return Ok(false);
+	// The markers themselves are registered with the rest of the binary signatures in detect.
+	if !detect::has_magic(&mut input, Format::Msgpack)? {
+		return Ok(Err("input does not start with a map or array".into()));
 	}
 
 	let result = match &mut input {
@@ -45,8 +49,8 @@ pub(crate) fn input_matches(mut input: Ref) -> io::Result<bool> {
 	};
 	match result {
 		Err(InvalidMarkerRead(err) | InvalidDataRead(err)) => Err(err),
-		Err(_) => Ok(false),
-		Ok(()) => Ok(true),
+		Err(err) => Ok(Err(err.to_string())),
+		Ok(()) => Ok(Ok(())),
 	}
 }
 
@@ -62,64 +66,550 @@ fn match_input_reader<R: Read>(input: R) -> Result<(), rmp_serde::decode::Error>
 	de::IgnoredAny::deserialize(&mut de).and(Ok(()))
 }
 
-pub(crate) fn transcode<O>(input: input::Handle, mut output: O) -> crate::Result<()>
+/// Transcodes MessagePack input to the output.
+///
+/// With `translate_ext`, each extension type value translates as an array of its type and its
+/// data, which requires fully deserializing each document. Otherwise, extension types are errors.
+///
+/// With `direct`, each document goes to the output as a slice of its encoding through
+/// [`transcode_msgpack`](crate::Output::transcode_msgpack), for outputs that can translate it
+/// without serde. This ignores `translate_ext`.
+///
+/// With `max_size`, translation from a reader fails on the first document that needs to read more
+/// than that many bytes, as soon as it reads that far.
+///
+/// With `resync`, the whole input is read into memory, and every document is decoded in full
+/// before it goes to the output, so that a document that fails to decode can be skipped (see
+/// [`transcode_resync`]). This ignores `direct` and `max_size`.
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	mut output: O,
+	translate_ext: bool,
+	direct: bool,
+	max_size: Option<u64>,
+	resync: Option<Resync>,
+) -> crate::Result<()>
 where
-	O: crate::Output,
+	O: crate::Output<'i>,
 {
-	match input.into() {
-		Input::Slice(b) => {
-			let mut rest = &*b;
-			while !rest.is_empty() {
-				let next;
-				(next, rest) = rest.split_at(next_value_size(rest, DEPTH_LIMIT)?);
+	if let Some(resync) = resync {
+		let input: Cow<'_, [u8]> = input.try_into()?;
+		return transcode_resync(&input, output, translate_ext, resync);
+	}
+
+	match Input::try_from(input)? {
+		Input::Slice(Cow::Borrowed(b)) => {
+			let mut offset = 0;
+			for next in values_from_slice(b) {
+				let next = next?;
+				output.set_document_offset(offset);
+				offset += next.len() as u64;
+				output.set_document_end(offset);
+				if direct {
+					output.transcode_msgpack(next)?;
+					continue;
+				}
 				let mut de = rmp_serde::Deserializer::from_read_ref(next);
 				de.set_max_depth(DEPTH_LIMIT);
-				output.transcode_from(&mut de)?;
+				if translate_ext {
+					output.transcode_borrowed_value(ExtVisitor::deserialize(&mut de)?)?;
+				} else {
+					output.transcode_borrowed_from(&mut de)?;
+				}
 			}
 		}
-		Input::Reader(r) => {
-			let mut r = BufReader::new(r);
-			while !r.fill_buf()?.is_empty() {
-				let mut de = rmp_serde::Deserializer::new(&mut r);
+		Input::Slice(Cow::Owned(b)) => {
+			let mut offset = 0;
+			for next in values_from_slice(&b) {
+				let next = next?;
+				output.set_document_offset(offset);
+				offset += next.len() as u64;
+				output.set_document_end(offset);
+				if direct {
+					output.transcode_msgpack(next)?;
+					continue;
+				}
+				let mut de = rmp_serde::Deserializer::from_read_ref(next);
 				de.set_max_depth(DEPTH_LIMIT);
-				output.transcode_from(&mut de)?;
+				if translate_ext {
+					output.transcode_value(ExtVisitor::deserialize(&mut de)?)?;
+				} else {
+					output.transcode_from(&mut de)?;
+				}
+			}
+		}
+		Input::Reader(r) if direct => {
+			// Each document is read into a single reused buffer, and only as far as its markers
+			// and lengths require.
+			let (r, count, limit) = CountingReader::new(r, max_size);
+			let mut r = BufReader::new(r);
+			let mut buf = vec![];
+			loop {
+				// Nothing is buffered when this reads, so the next document starts at the count.
+				limit.start(count.get());
+				let buffered = r.fill_buf().map_err(|err| limit.check(err.into()))?.len();
+				if buffered == 0 {
+					break;
+				}
+				let offset = count.get() - buffered as u64;
+				output.set_document_offset(offset);
+				limit.start(offset);
+				buf.clear();
+				read_value(&mut r, &mut buf, DEPTH_LIMIT).map_err(|err| limit.check(err))?;
+				output.transcode_msgpack(&buf)?;
+				output.set_last_document_end(offset + buf.len() as u64);
+			}
+		}
+		Input::Reader(r) => {
+			// A single deserializer for the whole stream reuses its internal scratch buffer for
+			// strings and binary data across documents.
+			let (r, count, limit) = CountingReader::new(r, max_size);
+			let mut de = rmp_serde::Deserializer::new(BufReader::new(r));
+			de.set_max_depth(DEPTH_LIMIT);
+			loop {
+				limit.start(count.get());
+				let buffered = de
+					.get_mut()
+					.fill_buf()
+					.map_err(|err| limit.check(err.into()))?
+					.len();
+				if buffered == 0 {
+					break;
+				}
+				let offset = count.get() - buffered as u64;
+				output.set_document_offset(offset);
+				limit.start(offset);
+				if translate_ext {
+					let value =
+						ExtVisitor::deserialize(&mut de).map_err(|err| limit.check(err.into()))?;
+					output.transcode_value(value)?;
+				} else {
+					output
+						.transcode_from(&mut de)
+						.map_err(|err| limit.check(err))?;
+				}
+				let buffered = de.get_ref().buffer().len() as u64;
+				output.set_last_document_end(count.get() - buffered);
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Transcodes buffered MessagePack input to the output, skipping past any document that fails to
+/// decode.
+///
+/// After a failure, translation resumes at the next byte that starts a map or array that decodes
+/// in full, for the same reason that detection only matches input that starts with one (see
+/// [`input_matches`]). The handler hears about every stretch of input skipped this way.
+fn transcode_resync<'i, O>(
+	input: &[u8],
+	mut output: O,
+	translate_ext: bool,
+	(handler, source): Resync,
+) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	let mut offset = 0;
+	while offset < input.len() {
+		let err = match decode_value(&input[offset..], translate_ext) {
+			Ok((value, len)) => {
+				output.set_document_offset(offset as u64);
+				offset += len;
+				output.set_document_end(offset as u64);
+				output.transcode_value(value)?;
+				continue;
 			}
+			Err(err) => err,
+		};
+		let resume = (offset + 1..input.len()).find(|&start| {
+			is_container_marker(input[start])
+				&& decode_value(&input[start..], translate_ext).is_ok()
+		});
+		(handler.borrow_mut())(MsgpackResync {
+			source: source.map(Into::into),
+			offset: offset as u64,
+			resume: resume.map(|resume| resume as u64),
+			message: err.to_string(),
+		});
+		match resume {
+			Some(resume) => offset = resume,
+			None => break,
 		}
 	}
 	Ok(())
 }
 
-pub(crate) struct Output<W: Write>(W);
+/// Decodes the next complete MessagePack value at the start of the input, and returns it along
+/// with the length of its encoding.
+fn decode_value(input: &[u8], translate_ext: bool) -> crate::Result<(transcode::Value<'_>, usize)> {
+	let len = next_value_size(input, DEPTH_LIMIT)?;
+	let mut de = rmp_serde::Deserializer::from_read_ref(&input[..len]);
+	de.set_max_depth(DEPTH_LIMIT);
+	let value = match translate_ext {
+		true => ExtVisitor::deserialize(&mut de)?,
+		false => transcode::Value::deserialize(&mut de)?,
+	};
+	Ok((value, len))
+}
+
+/// Returns true if the byte is the marker of a map or array.
+fn is_container_marker(b: u8) -> bool {
+	matches!(b, 0x80..=0x9f | 0xdc..=0xdf)
+}
+
+/// A function that handles skipped stretches of corrupt MessagePack input.
+pub(crate) type ResyncHandler = dyn FnMut(MsgpackResync) + Send;
+
+/// The resynchronization handler for a MessagePack input, along with the source name of the
+/// input.
+pub(crate) type Resync<'r, 'h, 's> = (&'r RefCell<&'h mut ResyncHandler>, Option<&'s str>);
+
+/// A stretch of corrupt input that the translator skipped with a
+/// [MessagePack resync handler](crate::Translator::set_msgpack_resync_handler).
+#[derive(Debug, Clone)]
+pub struct MsgpackResync {
+	source: Option<String>,
+	offset: u64,
+	resume: Option<u64>,
+	message: String,
+}
+
+impl MsgpackResync {
+	/// Returns the [source name](crate::Translator::set_source_name) of the input containing the
+	/// corruption, for a named input.
+	pub fn source(&self) -> Option<&str> {
+		self.source.as_deref()
+	}
+
+	/// Returns the byte offset within the input of the document that failed to decode.
+	pub fn offset(&self) -> u64 {
+		self.offset
+	}
+
+	/// Returns the byte offset within the input where translation resumed, or `None` if nothing
+	/// after the corruption decoded and the rest of the input was skipped.
+	pub fn resume_offset(&self) -> Option<u64> {
+		self.resume
+	}
+
+	/// Returns the message of the error that the corrupt document failed to decode with.
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+}
+
+impl Display for MsgpackResync {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let offset = self.offset;
+		match self.resume {
+			Some(resume) => write!(
+				f,
+				"skipped corrupt input from byte {offset} to byte {resume}: {}",
+				self.message
+			),
+			None => write!(
+				f,
+				"skipped corrupt input from byte {offset} to the end of the input: {}",
+				self.message
+			),
+		}
+	}
+}
+
+/// Replaces each invalid UTF-8 sequence in the strings of MessagePack input with U+FFFD, and
+/// returns the result with the number of sequences replaced.
+///
+/// Repair rewrites the length of every string it changes, which moves the values after it. Input
+/// past the first marker or length that doesn't fit is left alone for decoding to report.
+pub(crate) fn repair_strings(input: Cow<'_, [u8]>) -> (Cow<'_, [u8]>, usize) {
+	let mut out = Vec::with_capacity(input.len());
+	let mut count = 0;
+	let mut pos = 0;
+	while pos < input.len() {
+		let rest = &input[pos..];
+		let length = |n| match n {
+			1 => try_read_length_8(rest).map(usize::from),
+			2 => try_read_length_16(rest).map(usize::from),
+			_ => try_read_length_32(rest).map(|len| len as usize),
+		};
+		// Containers are only headers here, so that their elements are scanned in turn.
+		let (header, data, str) = match Marker::from_u8(rest[0]) {
+			Marker::FixStr(n) => (1, Ok(usize::from(n)), true),
+			Marker::Str8 => (2, length(1), true),
+			Marker::Str16 => (3, length(2), true),
+			Marker::Str32 => (5, length(4), true),
+			Marker::Bin8 => (2, length(1), false),
+			Marker::Bin16 => (3, length(2), false),
+			Marker::Bin32 => (5, length(4), false),
+			Marker::Ext8 => (2, length(1).map(|len| len + 1), false),
+			Marker::Ext16 => (3, length(2).map(|len| len + 1), false),
+			Marker::Ext32 => (5, length(4).map(|len| len + 1), false),
+			Marker::FixExt1 => (1, Ok(2), false),
+			Marker::FixExt2 => (1, Ok(3), false),
+			Marker::FixExt4 => (1, Ok(5), false),
+			Marker::FixExt8 => (1, Ok(9), false),
+			Marker::FixExt16 => (1, Ok(17), false),
+			Marker::U8 | Marker::I8 => (1, Ok(1), false),
+			Marker::U16 | Marker::I16 => (1, Ok(2), false),
+			Marker::U32 | Marker::I32 | Marker::F32 => (1, Ok(4), false),
+			Marker::U64 | Marker::I64 | Marker::F64 => (1, Ok(8), false),
+			Marker::Array16 | Marker::Map16 => (3, Ok(0), false),
+			Marker::Array32 | Marker::Map32 => (5, Ok(0), false),
+			_ => (1, Ok(0), false),
+		};
+		let Some(value) = data.ok().and_then(|len| rest.get(..header + len)) else {
+			break;
+		};
+		pos += value.len();
+		if !str {
+			out.extend_from_slice(value);
+			continue;
+		}
+		let (text, n) = crate::utf8::lossy(Cow::Borrowed(&value[header..]));
+		if n == 0 {
+			out.extend_from_slice(value);
+			continue;
+		}
+		count += n;
+		let len = u32::try_from(text.len()).expect("repaired string should fit a str32 length");
+		rmp::encode::write_str_len(&mut out, len).expect("writes to a Vec should not fail");
+		out.extend_from_slice(&text);
+	}
+	if count == 0 {
+		return (input, 0);
+	}
+	out.extend_from_slice(&input[pos..]);
+	(Cow::Owned(out), count)
+}
+
+/// Transcodes a slice holding exactly one MessagePack document to the output through serde, as
+/// outputs do by default for [`transcode_msgpack`](crate::Output::transcode_msgpack).
+pub(crate) fn transcode_document<'i, O>(output: &mut O, input: &[u8]) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	let mut de = rmp_serde::Deserializer::from_read_ref(input);
+	de.set_max_depth(DEPTH_LIMIT);
+	output.transcode_from(&mut de)
+}
+
+/// A visitor that deserializes a [`transcode::Value`] like [`transcode::ValueVisitor`], but
+/// represents each extension type value as an array of its type and its data.
+///
+/// `rmp_serde` presents extension types as newtype structs wrapping that same array, which the
+/// transcoder and `ValueVisitor` reject like any other Rust-specific type.
+#[derive(Clone, Copy)]
+struct ExtVisitor;
+
+impl ExtVisitor {
+	fn deserialize<'de, D: de::Deserializer<'de>>(
+		de: D,
+	) -> Result<transcode::Value<'de>, D::Error> {
+		de::DeserializeSeed::deserialize(ExtVisitor, de)
+	}
+}
+
+impl<'de> de::DeserializeSeed<'de> for ExtVisitor {
+	type Value = transcode::Value<'de>;
+
+	fn deserialize<D: de::Deserializer<'de>>(self, de: D) -> Result<Self::Value, D::Error> {
+		de.deserialize_any(self)
+	}
+}
+
+/// Implements [`de::Visitor`] methods that produce the [`transcode::Value`] for a scalar.
+macro_rules! impl_ext_scalar_visitors {
+	( $( $name:ident($($arg:ident: $ty:ty)?); )* ) => {
+		$(fn $name<E: de::Error>(self, $($arg: $ty)?) -> Result<Self::Value, E> {
+			de::Visitor::$name(transcode::ValueVisitor, $($arg)?)
+		})*
+	};
+}
+
+impl<'de> de::Visitor<'de> for ExtVisitor {
+	type Value = transcode::Value<'de>;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("any MessagePack value")
+	}
+
+	impl_ext_scalar_visitors! {
+		visit_unit();
+		visit_bool(v: bool);
+		visit_i8(v: i8);
+		visit_i16(v: i16);
+		visit_i32(v: i32);
+		visit_i64(v: i64);
+		visit_u8(v: u8);
+		visit_u16(v: u16);
+		visit_u32(v: u32);
+		visit_u64(v: u64);
+		visit_f32(v: f32);
+		visit_f64(v: f64);
+		visit_borrowed_str(v: &'de str);
+		visit_str(v: &str);
+		visit_string(v: String);
+		visit_borrowed_bytes(v: &'de [u8]);
+		visit_bytes(v: &[u8]);
+		visit_byte_buf(v: Vec<u8>);
+	}
+
+	fn visit_newtype_struct<D>(self, de: D) -> Result<Self::Value, D::Error>
+	where
+		D: de::Deserializer<'de>,
+	{
+		ExtVisitor::deserialize(de)
+	}
+
+	fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+		while let Some(e) = seq.next_element_seed(self)? {
+			vec.push(e);
+		}
+		Ok(transcode::Value::Seq(vec))
+	}
+
+	fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+		let mut vec = Vec::with_capacity(map.size_hint().unwrap_or(0));
+		while let Some(entry) = map.next_entry_seed(self, self)? {
+			vec.push(entry);
+		}
+		Ok(transcode::Value::Map(vec))
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+	pool: BufferPool,
+}
 
 impl<W: Write> Output<W> {
 	pub(crate) fn new(w: W) -> Output<W> {
-		Output(w)
+		Output {
+			w,
+			pool: BufferPool::default(),
+		}
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
 	}
 }
 
-impl<W: Write> crate::Output for Output<W> {
+impl<W: Write> crate::Output<'_> for Output<W> {
 	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
 	where
 		D: de::Deserializer<'de, Error = E>,
 		E: de::Error + Send + Sync + 'static,
 	{
-		let mut ser = rmp_serde::Serializer::new(&mut self.0);
+		let mut ser = Serializer::new(&mut self.w, &mut self.pool);
 		transcode::transcode(&mut ser, de)?;
 		Ok(())
 	}
 
-	fn transcode_value<S>(&mut self, value: S) -> crate::Result<()>
-	where
-		S: ser::Serialize,
-	{
-		let mut ser = rmp_serde::Serializer::new(&mut self.0);
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		let mut ser = Serializer::new(&mut self.w, &mut self.pool);
 		value.serialize(&mut ser)?;
 		Ok(())
 	}
 
+	fn transcode_msgpack(&mut self, input: &[u8]) -> crate::Result<()> {
+		Ok(self.w.write_all(input)?)
+	}
+
 	fn flush(&mut self) -> io::Result<()> {
-		self.0.flush()
+		self.w.flush()
+	}
+}
+
+/// Splits an input slice into the subslices holding each of its MessagePack values.
+fn values_from_slice(input: &[u8]) -> impl Iterator<Item = Result<&[u8], ReadSizeError>> {
+	let mut rest = input;
+	std::iter::from_fn(move || {
+		if rest.is_empty() {
+			return None;
+		}
+		match next_value_size(rest, DEPTH_LIMIT) {
+			Ok(size) => {
+				let next;
+				(next, rest) = rest.split_at(size);
+				Some(Ok(next))
+			}
+			Err(err) => {
+				rest = &[];
+				Some(Err(err))
+			}
+		}
+	})
+}
+
+/// Reads the MessagePack value at the start of a reader into `buf`, like [`next_value_size`] for
+/// a slice.
+fn read_value<R: Read>(r: &mut R, buf: &mut Vec<u8>, depth_limit: usize) -> crate::Result<()> {
+	if depth_limit == 0 {
+		return Err(ReadSizeError::DepthLimitExceeded.into());
+	}
+	read_bytes(r, buf, 1)?;
+	let marker = Marker::from_u8(buf[buf.len() - 1]);
+	let mut length = |size: usize| -> crate::Result<u64> {
+		read_bytes(r, buf, size)?;
+		let bytes = &buf[buf.len() - size..];
+		Ok(bytes.iter().fold(0, |n, &b| (n << 8) | u64::from(b)))
+	};
+	let (data, values) = match marker {
+		Marker::Reserved => return Err(ReadSizeError::InvalidMarker.into()),
+
+		Marker::Null | Marker::True | Marker::False | Marker::FixPos(_) | Marker::FixNeg(_) => {
+			(0, 0)
+		}
+
+		Marker::U8 | Marker::I8 => (1, 0),
+		Marker::U16 | Marker::I16 => (2, 0),
+		Marker::U32 | Marker::I32 | Marker::F32 => (4, 0),
+		Marker::U64 | Marker::I64 | Marker::F64 => (8, 0),
+
+		Marker::FixExt1 => (2, 0),
+		Marker::FixExt2 => (3, 0),
+		Marker::FixExt4 => (5, 0),
+		Marker::FixExt8 => (9, 0),
+		Marker::FixExt16 => (17, 0),
+		Marker::Ext8 => (1 + length(1)?, 0),
+		Marker::Ext16 => (1 + length(2)?, 0),
+		Marker::Ext32 => (1 + length(4)?, 0),
+
+		Marker::FixStr(n) => (u64::from(n), 0),
+		Marker::Str8 | Marker::Bin8 => (length(1)?, 0),
+		Marker::Str16 | Marker::Bin16 => (length(2)?, 0),
+		Marker::Str32 | Marker::Bin32 => (length(4)?, 0),
+
+		Marker::FixArray(count) => (0, u64::from(count)),
+		Marker::FixMap(pairs) => (0, 2 * u64::from(pairs)),
+		Marker::Array16 => (0, length(2)?),
+		Marker::Map16 => (0, 2 * length(2)?),
+		Marker::Array32 => (0, length(4)?),
+		Marker::Map32 => (0, 2 * length(4)?),
+	};
+	let copied = r.take(data).read_to_end(buf)?;
+	if copied as u64 != data {
+		return Err(ReadSizeError::Truncated.into());
+	}
+	for _ in 0..values {
+		read_value(r, buf, depth_limit - 1)?;
 	}
+	Ok(())
+}
+
+/// Reads exactly `len` bytes from a reader onto the end of `buf`.
+fn read_bytes<R: Read>(r: &mut R, buf: &mut Vec<u8>, len: usize) -> crate::Result<()> {
+	let start = buf.len();
+	buf.resize(start + len, 0);
+	r.read_exact(&mut buf[start..])
+		.map_err(|err| match err.kind() {
+			io::ErrorKind::UnexpectedEof => ReadSizeError::Truncated.into(),
+			_ => crate::Error::from(err),
+		})
 }
 
 /// Returns the size in bytes of the MessagePack value at the start of the input slice.
@@ -433,6 +923,21 @@ mod tests {
 		assert_eq!(next_value_size(&hex!("91 a2 78 74 c1"), DEPTH_LIMIT), Ok(4));
 	}
 
+	#[test]
+	fn split_values_from_slice() {
+		// true; ["xt"]; <invalid>; false
+		let input = hex!("c3 91 a2 78 74 c1 c2");
+		let values: Vec<_> = values_from_slice(&input).collect();
+		assert_eq!(
+			values,
+			[
+				Ok(&hex!("c3")[..]),
+				Ok(&hex!("91 a2 78 74")[..]),
+				Err(ReadSizeError::InvalidMarker),
+			]
+		);
+	}
+
 	#[test]
 	fn excessively_deep_input_size() {
 		// [[true]]
@@ -444,6 +949,58 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn read_value_from_reader() {
+		// ["xt", {"a": bin [0xaa]}]; true; truncated
+		let input = hex!("92 a2 78 74 81 a1 61 c4 01 aa c3 92 c3");
+		let mut r = &input[..];
+		let mut buf = vec![];
+		read_value(&mut r, &mut buf, DEPTH_LIMIT).unwrap();
+		assert_eq!(buf, &input[..10]);
+		buf.clear();
+		read_value(&mut r, &mut buf, DEPTH_LIMIT).unwrap();
+		assert_eq!(buf, hex!("c3"));
+		let err = read_value(&mut r, &mut vec![], DEPTH_LIMIT).unwrap_err();
+		assert_eq!(err.to_string(), ReadSizeError::Truncated.to_string());
+		let err = read_value(&mut &hex!("91 91 c3")[..], &mut vec![], 2).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			ReadSizeError::DepthLimitExceeded.to_string()
+		);
+	}
+
+	#[test]
+	fn translate_ext_types() {
+		// [ext type 5 of 0xaa] => [[5, [0xaa]]]
+		let input = hex!("91 d4 05 aa");
+		for reader in [false, true] {
+			let handle = || match reader {
+				false => input::Handle::from_slice(&input[..]),
+				true => input::Handle::from_reader(&input[..]),
+			};
+			let mut output = vec![];
+			super::transcode(
+				handle(),
+				super::Output::new(&mut output),
+				true,
+				false,
+				None,
+				None,
+			)
+			.unwrap();
+			assert_eq!(output, hex!("91 92 05 91 cc aa"), "reader: {reader}");
+			super::transcode(
+				handle(),
+				super::Output::new(io::sink()),
+				false,
+				false,
+				None,
+				None,
+			)
+			.unwrap_err();
+		}
+	}
+
 	#[test]
 	#[cfg_attr(miri, ignore)] // Takes unusually long to run, but no unsafe in tested code paths.
 	fn consistent_depth_limits() {
@@ -459,18 +1016,27 @@ mod tests {
 			.stack_size(8 * 1024 * 1024)
 			.spawn(move || {
 				match_input_buffer(&input[..]).expect("buffer should be valid MessagePack");
-				super::transcode(
-					input::Handle::from_slice(&input[..]),
-					super::Output::new(io::sink()),
-				)
-				.expect("buffer should be valid MessagePack");
-
 				match_input_reader(&input[..]).expect("buffer should be valid MessagePack");
-				super::transcode(
-					input::Handle::from_reader(&input[..]),
-					super::Output::new(io::sink()),
-				)
-				.expect("buffer should be valid MessagePack");
+				for direct in [false, true] {
+					super::transcode(
+						input::Handle::from_slice(&input[..]),
+						super::Output::new(io::sink()),
+						false,
+						direct,
+						None,
+						None,
+					)
+					.expect("buffer should be valid MessagePack");
+					super::transcode(
+						input::Handle::from_reader(&input[..]),
+						super::Output::new(io::sink()),
+						false,
+						direct,
+						None,
+						None,
+					)
+					.expect("buffer should be valid MessagePack");
+				}
 			})
 			.unwrap()
 			.join()
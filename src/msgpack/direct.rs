@@ -0,0 +1,181 @@
+//! Direct translation of MessagePack documents to compact JSON text.
+//!
+//! MessagePack to JSON is common enough to deserve a path that skips serde. Rather than drive a
+//! `serde_json` serializer from an `rmp_serde` deserializer through the transcoder, [`to_json`]
+//! walks the markers of a document and writes its JSON text straight to a buffer. Scalars still
+//! go through `serde_json`, so that numbers and strings come out exactly as they would through
+//! serde.
+//!
+//! The walk gives up on the few values whose translation depends on serde's finer points:
+//! extension types, strings that aren't valid UTF-8, and map keys other than strings. The caller
+//! translates those documents through serde instead, with the same result (or error) as always.
+
+use std::str;
+
+use rmp::Marker;
+use serde::Serialize;
+
+/// Writes the compact JSON text of a slice holding exactly one MessagePack value to `out`,
+/// returning `None` if the value needs translation through serde.
+///
+/// The slice must already be known to hold a value within the depth limit, as from
+/// [`next_value_size`](super::next_value_size) or [`read_value`](super::read_value), since the
+/// walk recurses without a limit of its own.
+pub(crate) fn to_json(input: &[u8], out: &mut Vec<u8>) -> Option<()> {
+	Walk { input, out }.value()
+}
+
+struct Walk<'a, 'o> {
+	input: &'a [u8],
+	out: &'o mut Vec<u8>,
+}
+
+impl<'a> Walk<'a, '_> {
+	fn value(&mut self) -> Option<()> {
+		match Marker::from_u8(self.take::<1>()?[0]) {
+			Marker::Null => self.out.extend_from_slice(b"null"),
+			Marker::True => self.out.extend_from_slice(b"true"),
+			Marker::False => self.out.extend_from_slice(b"false"),
+			Marker::FixPos(n) => self.scalar(n)?,
+			Marker::FixNeg(n) => self.scalar(n)?,
+			Marker::U8 => self.number(u8::from_be_bytes)?,
+			Marker::U16 => self.number(u16::from_be_bytes)?,
+			Marker::U32 => self.number(u32::from_be_bytes)?,
+			Marker::U64 => self.number(u64::from_be_bytes)?,
+			Marker::I8 => self.number(i8::from_be_bytes)?,
+			Marker::I16 => self.number(i16::from_be_bytes)?,
+			Marker::I32 => self.number(i32::from_be_bytes)?,
+			Marker::I64 => self.number(i64::from_be_bytes)?,
+			Marker::F32 => self.number(f32::from_be_bytes)?,
+			Marker::F64 => self.number(f64::from_be_bytes)?,
+			marker @ (Marker::FixStr(_) | Marker::Str8 | Marker::Str16 | Marker::Str32) => {
+				let s = self.str(marker)?;
+				self.scalar(s)?;
+			}
+			Marker::Bin8 => self.length(1).and_then(|len| self.bin(len))?,
+			Marker::Bin16 => self.length(2).and_then(|len| self.bin(len))?,
+			Marker::Bin32 => self.length(4).and_then(|len| self.bin(len))?,
+			Marker::FixArray(len) => self.array(usize::from(len))?,
+			Marker::Array16 => self.length(2).and_then(|len| self.array(len))?,
+			Marker::Array32 => self.length(4).and_then(|len| self.array(len))?,
+			Marker::FixMap(len) => self.map(usize::from(len))?,
+			Marker::Map16 => self.length(2).and_then(|len| self.map(len))?,
+			Marker::Map32 => self.length(4).and_then(|len| self.map(len))?,
+			_ => return None, // Extension types, and the reserved marker.
+		}
+		Some(())
+	}
+
+	/// Takes the next `N` bytes of the input.
+	fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+		let (next, rest) = self.input.split_first_chunk::<N>()?;
+		self.input = rest;
+		Some(*next)
+	}
+
+	/// Takes the next `len` bytes of the input.
+	fn take_slice(&mut self, len: usize) -> Option<&'a [u8]> {
+		if len > self.input.len() {
+			return None;
+		}
+		let next;
+		(next, self.input) = self.input.split_at(len);
+		Some(next)
+	}
+
+	/// Takes a big-endian length of `size` bytes.
+	fn length(&mut self, size: usize) -> Option<usize> {
+		let bytes = self.take_slice(size)?;
+		usize::try_from(bytes.iter().fold(0, |n, &b| (n << 8) | u64::from(b))).ok()
+	}
+
+	fn number<T: Serialize, const N: usize>(&mut self, from: fn([u8; N]) -> T) -> Option<()> {
+		let bytes = self.take()?;
+		self.scalar(from(bytes))
+	}
+
+	fn scalar<T: Serialize>(&mut self, value: T) -> Option<()> {
+		value
+			.serialize(&mut serde_json::Serializer::new(&mut *self.out))
+			.ok()
+	}
+
+	/// Takes the UTF-8 content of a string with the given marker.
+	fn str(&mut self, marker: Marker) -> Option<&'a str> {
+		let len = match marker {
+			Marker::FixStr(len) => usize::from(len),
+			Marker::Str8 => self.length(1)?,
+			Marker::Str16 => self.length(2)?,
+			Marker::Str32 => self.length(4)?,
+			_ => return None,
+		};
+		str::from_utf8(self.take_slice(len)?).ok()
+	}
+
+	/// Writes binary data of the given length as an array of bytes, as `serde_json` does.
+	fn bin(&mut self, len: usize) -> Option<()> {
+		let bytes = self.take_slice(len)?;
+		self.out.push(b'[');
+		for (i, &byte) in bytes.iter().enumerate() {
+			if i > 0 {
+				self.out.push(b',');
+			}
+			self.scalar(byte)?;
+		}
+		self.out.push(b']');
+		Some(())
+	}
+
+	fn array(&mut self, len: usize) -> Option<()> {
+		self.out.push(b'[');
+		for i in 0..len {
+			if i > 0 {
+				self.out.push(b',');
+			}
+			self.value()?;
+		}
+		self.out.push(b']');
+		Some(())
+	}
+
+	fn map(&mut self, len: usize) -> Option<()> {
+		self.out.push(b'{');
+		for i in 0..len {
+			if i > 0 {
+				self.out.push(b',');
+			}
+			let marker = Marker::from_u8(self.take::<1>()?[0]);
+			let key = self.str(marker)?;
+			self.scalar(key)?;
+			self.out.push(b':');
+			self.value()?;
+		}
+		self.out.push(b'}');
+		Some(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use hex_literal::hex;
+
+	#[test]
+	fn msgpack_to_json() {
+		// {"a": [1, -1, 1.5, null, true, "x\n", bin "xt"], "b": {}} then ext, invalid UTF-8, and
+		// an integer key, which all need serde.
+		let input = hex!(
+			"82 a1 61 97 01 ff cb 3f f8 00 00 00 00 00 00 c0 c3 a2 78 0a c4 02 78 74 a1 62 80"
+		);
+		let mut out = vec![];
+		assert_eq!(to_json(&input, &mut out), Some(()));
+		assert_eq!(
+			str::from_utf8(&out).unwrap(),
+			r#"{"a":[1,-1,1.5,null,true,"x\n",[120,116]],"b":{}}"#
+		);
+		for input in [&hex!("d4 01 09")[..], &hex!("a1 ff"), &hex!("81 01 c0")] {
+			assert_eq!(to_json(input, &mut vec![]), None);
+		}
+	}
+}
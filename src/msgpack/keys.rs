@@ -0,0 +1,210 @@
+//! Conversion of the integer, boolean, and binary map keys of MessagePack input to strings.
+
+use std::borrow::Cow;
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::transcode::Value;
+
+/// What a translator does with the map keys of MessagePack input that are integers, booleans, or
+/// binary data, as set by
+/// [`Translator::set_msgpack_keys`](crate::Translator::set_msgpack_keys).
+///
+/// Every scheme other than [`Preserve`](MsgpackKeys::Preserve) writes booleans as `true` and
+/// `false`, and leaves string keys and keys of other types alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MsgpackKeys {
+	/// Keep every key as it is, for output formats that allow keys other than strings, like YAML
+	/// and MessagePack. Other output formats represent or reject such keys as they would any
+	/// others.
+	#[default]
+	Preserve,
+	/// Write integers in decimal, like `42`, and binary data in hexadecimal after `0x`, since it
+	/// has no decimal form.
+	Decimal,
+	/// Write integers and binary data in hexadecimal after `0x`, like `0x2a`, `-0x2a`, and
+	/// `0x0102`.
+	Hex,
+	/// Write integers in decimal, and binary data in padded standard base64, like `AQI=`.
+	Base64,
+}
+
+/// An [`Output`](crate::Output) that converts the map keys of each document to strings, when
+/// the scheme calls for it.
+///
+/// Conversion fully deserializes each document into memory.
+pub(crate) struct Output<O> {
+	output: O,
+	keys: MsgpackKeys,
+}
+
+impl<O> Output<O> {
+	pub(crate) fn new(output: O, keys: MsgpackKeys) -> Output<O> {
+		Output { output, keys }
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.keys == MsgpackKeys::Preserve {
+			return self.output.transcode_from(de);
+		}
+		self.transcode_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.keys == MsgpackKeys::Preserve {
+			return self.output.transcode_borrowed_from(de);
+		}
+		self.transcode_borrowed_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_value(&mut self, mut value: Value) -> crate::Result<()> {
+		convert_keys(&mut value, self.keys);
+		self.output.transcode_value(value)
+	}
+
+	fn transcode_borrowed_value(&mut self, mut value: Value<'i>) -> crate::Result<()> {
+		convert_keys(&mut value, self.keys);
+		self.output.transcode_borrowed_value(value)
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+/// Converts the keys of every map within a value to strings under the scheme.
+fn convert_keys(value: &mut Value, keys: MsgpackKeys) {
+	match value {
+		Value::Seq(seq) => {
+			for v in seq {
+				convert_keys(v, keys);
+			}
+		}
+		Value::Map(map) => {
+			for (k, v) in map {
+				if let Some(key) = key_string(k, keys) {
+					*k = Value::String(Cow::Owned(key));
+				}
+				convert_keys(v, keys);
+			}
+		}
+		_ => {}
+	}
+}
+
+/// Returns the string that a key converts to under the scheme, if it converts at all.
+fn key_string(key: &Value, keys: MsgpackKeys) -> Option<String> {
+	let hex = keys == MsgpackKeys::Hex;
+	let (negative, n) = match *key {
+		Value::Bool(b) if keys != MsgpackKeys::Preserve => return Some(b.to_string()),
+		Value::Bytes(ref b) => {
+			return match keys {
+				MsgpackKeys::Preserve => None,
+				MsgpackKeys::Base64 => Some(base64(b)),
+				_ => Some(b.iter().fold("0x".into(), |s, b| s + &format!("{b:02x}"))),
+			};
+		}
+		_ if keys == MsgpackKeys::Preserve => return None,
+		Value::I8(n) => (n < 0, u128::from(n.unsigned_abs())),
+		Value::I16(n) => (n < 0, u128::from(n.unsigned_abs())),
+		Value::I32(n) => (n < 0, u128::from(n.unsigned_abs())),
+		Value::I64(n) => (n < 0, u128::from(n.unsigned_abs())),
+		Value::I128(n) => (n < 0, n.unsigned_abs()),
+		Value::U8(n) => (false, u128::from(n)),
+		Value::U16(n) => (false, u128::from(n)),
+		Value::U32(n) => (false, u128::from(n)),
+		Value::U64(n) => (false, u128::from(n)),
+		Value::U128(n) => (false, n),
+		_ => return None,
+	};
+	let sign = if negative { "-" } else { "" };
+	Some(match hex {
+		true => format!("{sign}{n:#x}"),
+		false => format!("{sign}{n}"),
+	})
+}
+
+/// Encodes bytes in padded standard base64.
+fn base64(bytes: &[u8]) -> String {
+	const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+	for chunk in bytes.chunks(3) {
+		let n = chunk
+			.iter()
+			.enumerate()
+			.fold(0, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+		for i in 0..4 {
+			match i <= chunk.len() {
+				true => out.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 63])),
+				false => out.push('='),
+			}
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn key_strings_by_scheme() {
+		let bytes = Value::Bytes(Cow::Borrowed(b"\x01\x02"));
+		let cases = [
+			(
+				Value::I64(-42),
+				[None, Some("-42"), Some("-0x2a"), Some("-42")],
+			),
+			(
+				Value::U8(255),
+				[None, Some("255"), Some("0xff"), Some("255")],
+			),
+			(
+				Value::Bool(true),
+				[None, Some("true"), Some("true"), Some("true")],
+			),
+			(bytes, [None, Some("0x0102"), Some("0x0102"), Some("AQI=")]),
+			(Value::F64(1.5), [None; 4]),
+		];
+		let schemes = [
+			MsgpackKeys::Preserve,
+			MsgpackKeys::Decimal,
+			MsgpackKeys::Hex,
+			MsgpackKeys::Base64,
+		];
+		for (key, want) in cases {
+			for (scheme, want) in schemes.into_iter().zip(want) {
+				assert_eq!(key_string(&key, scheme).as_deref(), want, "{scheme:?}");
+			}
+		}
+
+		for (input, want) in [
+			(&b""[..], ""),
+			(b"f", "Zg=="),
+			(b"fo", "Zm8="),
+			(b"foo", "Zm9v"),
+		] {
+			assert_eq!(base64(input), want);
+		}
+	}
+}
@@ -0,0 +1,61 @@
+//! The null output format, which parses every document and discards it.
+//!
+//! Translating to null still drives each input format's deserializer through every value of every
+//! document, so that it measures the cost of parsing alone, and fails wherever the input does.
+
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+
+use serde::{Deserialize, de};
+
+use crate::detect::Trial;
+use crate::input::Ref;
+use crate::transcode;
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	Ok(Err(InputError.to_string()))
+}
+
+pub(crate) struct Output<W: Write>(W);
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output(w)
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.0
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		de::IgnoredAny::deserialize(de)?;
+		Ok(())
+	}
+
+	fn transcode_value(&mut self, _: transcode::Value) -> crate::Result<()> {
+		Ok(())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.0.flush()
+	}
+}
+
+/// The error for an attempt to translate from null, which has no input syntax.
+#[derive(Debug)]
+pub(crate) struct InputError;
+
+impl error::Error for InputError {}
+
+impl fmt::Display for InputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("null is an output format only")
+	}
+}
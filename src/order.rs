@@ -0,0 +1,199 @@
+//! Reordering of the documents in a stream from multiple inputs.
+//!
+//! By default, a stream holds the documents of each input in turn, as if the inputs were
+//! concatenated. A [`StreamOrder`] instead interleaves documents from every input, or sorts the
+//! whole stream by the value at a path, so that shards of the same record stream can come
+//! together as one.
+//!
+//! A translator that reorders must hold every document in memory until it finishes the stream,
+//! and only then passes the reordered documents on to the output.
+
+use std::io;
+use std::mem;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::intern;
+use crate::transcode::Value;
+use crate::transform::{self, path};
+
+/// An order for the documents of a stream other than the order of its inputs.
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum StreamOrder {
+	/// Take one document from each input in turn, skipping inputs that have run out of documents.
+	Interleave,
+	/// Sort every document by the scalar value at a path expression like `.time`.
+	///
+	/// Values sort in the same order as for
+	/// [`Transform::sort_arrays`](crate::Transform::sort_arrays), and documents without a scalar
+	/// value at the path sort after every other document. Documents with equal values keep their
+	/// original order.
+	SortBy(String),
+}
+
+/// The way that [`Reorder`] orders the documents it holds.
+enum Kind {
+	Interleave,
+	SortBy(Vec<path::Segment>),
+}
+
+/// The state of reordering across all documents in a stream.
+pub(crate) struct Reorder {
+	kind: Kind,
+	/// The documents from each input, including inputs that haven't produced any so far.
+	inputs: Vec<Vec<Value<'static>>>,
+	/// Whether held documents take their map keys from the [`intern`] pool.
+	intern: bool,
+}
+
+impl Reorder {
+	pub(crate) fn new(order: &StreamOrder) -> crate::Result<Reorder> {
+		let kind = match order {
+			StreamOrder::Interleave => Kind::Interleave,
+			StreamOrder::SortBy(expr) => Kind::SortBy(path::parse("sort-by path", expr)?),
+		};
+		Ok(Reorder {
+			kind,
+			inputs: vec![],
+			intern: false,
+		})
+	}
+
+	pub(crate) fn set_intern_keys(&mut self, intern: bool) {
+		self.intern = intern;
+	}
+
+	/// Marks the start of another input, whose documents interleave with those of earlier ones.
+	pub(crate) fn start_input(&mut self) {
+		if self.inputs.last().is_none_or(|docs| !docs.is_empty()) {
+			self.inputs.push(vec![]);
+		}
+	}
+
+	fn add(&mut self, value: Value) {
+		let value = intern::into_owned(value, self.intern);
+		match self.inputs.last_mut() {
+			Some(docs) => docs.push(value),
+			None => self.inputs.push(vec![value]),
+		}
+	}
+
+	/// Takes every held document in the new order.
+	fn take(&mut self) -> Vec<Value<'static>> {
+		let inputs = mem::take(&mut self.inputs);
+		match &self.kind {
+			Kind::Interleave => {
+				let mut inputs: Vec<_> = inputs.into_iter().map(Vec::into_iter).collect();
+				let mut docs = vec![];
+				loop {
+					let taken = docs.len();
+					docs.extend(inputs.iter_mut().filter_map(Iterator::next));
+					if docs.len() == taken {
+						return docs;
+					}
+				}
+			}
+			Kind::SortBy(path) => {
+				let mut docs: Vec<_> = inputs.into_iter().flatten().collect();
+				docs.sort_by(|a, b| {
+					transform::compare_values(path::lookup(a, path), path::lookup(b, path))
+				});
+				docs
+			}
+		}
+	}
+}
+
+/// An [`Output`](crate::Output) that holds every document instead of passing it through, when
+/// reordering is enabled.
+pub(crate) struct Output<'r, O> {
+	output: O,
+	reorder: Option<&'r mut Reorder>,
+}
+
+impl<'r, O> Output<'r, O> {
+	pub(crate) fn new(output: O, reorder: Option<&'r mut Reorder>) -> Output<'r, O> {
+		Output { output, reorder }
+	}
+
+	/// Passes every held document to the output in the new order, and returns the output.
+	pub(crate) fn finish<'i>(mut self) -> crate::Result<O>
+	where
+		O: crate::Output<'i>,
+	{
+		if let Some(reorder) = self.reorder {
+			for doc in reorder.take() {
+				self.output.transcode_value(doc)?;
+			}
+		}
+		Ok(self.output)
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		match &mut self.reorder {
+			Some(_) => self.transcode_value(Value::deserialize(de)?),
+			None => self.output.transcode_from(de),
+		}
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		match &mut self.reorder {
+			Some(reorder) => {
+				reorder.add(value);
+				Ok(())
+			}
+			None => self.output.transcode_value(value),
+		}
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		match &mut self.reorder {
+			Some(_) => self.transcode_value(value),
+			None => self.output.transcode_borrowed_value(value),
+		}
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn int(n: i64) -> Value<'static> {
+		Value::I64(n)
+	}
+
+	#[test]
+	fn reorder_documents() {
+		let mut reorder = Reorder::new(&StreamOrder::Interleave).unwrap();
+		for input in [&[1, 4, 6][..], &[], &[2], &[3, 5]] {
+			reorder.start_input();
+			input.iter().for_each(|&n| reorder.add(int(n)));
+		}
+		assert!(reorder.take() == (1..=6).map(int).collect::<Vec<_>>());
+
+		let mut reorder = Reorder::new(&StreamOrder::SortBy(".".into())).unwrap();
+		for value in [int(3), Value::Seq(vec![]), int(1), int(2)] {
+			reorder.add(value);
+		}
+		assert!(reorder.take() == [int(1), int(2), int(3), Value::Seq(vec![])]);
+	}
+}
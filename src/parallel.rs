@@ -0,0 +1,294 @@
+//! Parallel serialization of multi-document output.
+//!
+//! The input formats drive translation from the calling thread, handing each document to a
+//! [`crate::Output`] in turn. When parallel translation is enabled, that output deserializes each
+//! document into a [`Value`] and sends it to a pool of scoped worker threads, each of which
+//! serializes the value into a private buffer with its own [`Dispatcher`]. The calling thread
+//! writes finished buffers to the real output strictly in their original order, buffering any
+//! that finish early.
+//!
+//! Values from documents that borrow directly from the original input slice keep borrowing from
+//! it, since the workers are scoped to the translation of that input. Values from any other
+//! source must be copied into owned values first.
+//!
+//! To keep memory usage bounded, the calling thread stops deserializing new documents while too
+//! many are in flight, and waits for the oldest to finish instead.
+
+use std::collections::BTreeMap;
+use std::io::{self, IoSlice, Write};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, mpsc};
+use std::thread;
+
+use serde::{Deserialize, de};
+
+use crate::error::Location;
+use crate::intern;
+use crate::transcode::Value;
+use crate::{Dispatcher, Format};
+
+/// The maximum number of documents per worker that may be serialized or awaiting output at once.
+const IN_FLIGHT_PER_THREAD: usize = 4;
+
+/// The stack size of each worker thread.
+///
+/// Serialization recurses through nested values, and workers must handle the same depths that the
+/// main thread would. Test threads and other secondary threads typically default to smaller stacks
+/// than the main thread, so we match a typical main thread stack size explicitly.
+const WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+type Job<'i> = (usize, Option<Location>, Value<'i>);
+type Done = (usize, crate::Result<Vec<u8>>);
+
+/// Runs `source` with an output that serializes documents on `threads` worker threads, writing the
+/// serialized documents to `output` in order.
+///
+/// `output` must be a [`Dispatcher`] for the `to` format, and `to` must support multi-document
+/// output. With `intern`, documents that must be copied take their map keys from the [`intern`]
+/// pool.
+pub(crate) fn transcode<'i, W, F>(
+	output: &mut Dispatcher<W>,
+	to: Format,
+	threads: NonZeroUsize,
+	intern: bool,
+	source: F,
+) -> crate::Result<()>
+where
+	W: Write,
+	F: FnOnce(&mut Output<'_, 'i, W>) -> crate::Result<()>,
+{
+	let (job_tx, job_rx) = mpsc::sync_channel::<Job<'i>>(threads.get());
+	let (done_tx, done_rx) = mpsc::channel::<Done>();
+	let job_rx = Mutex::new(job_rx);
+
+	thread::scope(|scope| {
+		for _ in 0..threads.get() {
+			let (job_rx, done_tx) = (&job_rx, done_tx.clone());
+			thread::Builder::new()
+				.stack_size(WORKER_STACK_SIZE)
+				.spawn_scoped(scope, move || serialize_jobs(to, job_rx, &done_tx))?;
+		}
+		drop(done_tx);
+
+		let mut output = Output {
+			output,
+			jobs: Some(job_tx),
+			done: done_rx,
+			finished: BTreeMap::new(),
+			next_index: 0,
+			next_write: 0,
+			max_in_flight: threads.get() * IN_FLIGHT_PER_THREAD,
+			location: None,
+			intern,
+		};
+		let result = source(&mut output);
+
+		// Any documents sent before a deserialization failure were fully valid, so we output them
+		// before reporting the failure, just as we would if translating on a single thread.
+		// A serialization failure from any of those documents takes precedence.
+		output.jobs = None;
+		output.write_all_remaining()?;
+		result
+	})
+}
+
+/// Serializes jobs from the shared receiver until the calling thread stops sending them.
+fn serialize_jobs(to: Format, jobs: &Mutex<mpsc::Receiver<Job<'_>>>, done: &mpsc::Sender<Done>) {
+	use crate::Output as _;
+
+	loop {
+		let job = jobs
+			.lock()
+			.expect("parallel job receiver should not be poisoned")
+			.recv();
+		let Ok((index, location, value)) = job else {
+			return;
+		};
+
+		let mut buf = vec![];
+		let result = (&mut Dispatcher::new(&mut buf, to)).transcode_value(value);
+		// The calling thread reports this error while working on some later document, so it
+		// needs this document's location attached up front.
+		let result = result.map_err(|err| match location {
+			Some(location) => err.in_document(location),
+			None => err,
+		});
+		if done.send((index, result.map(|()| buf))).is_err() {
+			return;
+		}
+	}
+}
+
+/// Writes every byte of every slice to the writer, using vectored writes to cover as many slices
+/// with each call as the writer allows.
+///
+/// This is a stand-in for the unstable [`Write::write_all_vectored`].
+fn write_all_vectored<W: Write>(w: &mut W, mut slices: &mut [IoSlice<'_>]) -> io::Result<()> {
+	IoSlice::advance_slices(&mut slices, 0);
+	while !slices.is_empty() {
+		match w.write_vectored(slices) {
+			Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+			Ok(n) => IoSlice::advance_slices(&mut slices, n),
+			Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+			Err(err) => return Err(err),
+		}
+	}
+	Ok(())
+}
+
+/// A [`crate::Output`] that hands documents off to worker threads for serialization.
+pub(crate) struct Output<'o, 'i, W>
+where
+	W: Write,
+{
+	output: &'o mut Dispatcher<W>,
+	jobs: Option<mpsc::SyncSender<Job<'i>>>,
+	done: mpsc::Receiver<Done>,
+	finished: BTreeMap<usize, crate::Result<Vec<u8>>>,
+	next_index: usize,
+	next_write: usize,
+	max_in_flight: usize,
+	location: Option<Location>,
+	intern: bool,
+}
+
+impl<'i, W> Output<'_, 'i, W>
+where
+	W: Write,
+{
+	/// Sends a document to the worker pool, first waiting for older documents to finish if too
+	/// many are already in flight.
+	fn send(&mut self, value: Value<'i>) -> crate::Result<()> {
+		self.write_finished()?;
+		while self.next_index - self.next_write >= self.max_in_flight {
+			self.wait_for_one()?;
+			self.write_finished()?;
+		}
+
+		let jobs = self
+			.jobs
+			.as_ref()
+			.expect("parallel output should not send after finishing");
+		if jobs
+			.send((self.next_index, self.location.take(), value))
+			.is_err()
+		{
+			return Err("parallel translation workers stopped unexpectedly".into());
+		}
+		self.next_index += 1;
+		Ok(())
+	}
+
+	/// Writes every remaining document, waiting for workers to finish as necessary.
+	fn write_all_remaining(&mut self) -> crate::Result<()> {
+		self.write_finished()?;
+		while self.next_write < self.next_index {
+			self.wait_for_one()?;
+			self.write_finished()?;
+		}
+		Ok(())
+	}
+
+	/// Blocks until a worker finishes serializing any document.
+	fn wait_for_one(&mut self) -> crate::Result<()> {
+		let Ok((index, result)) = self.done.recv() else {
+			return Err("parallel translation workers stopped unexpectedly".into());
+		};
+		self.finished.insert(index, result);
+		Ok(())
+	}
+
+	/// Writes all finished documents that are next in line for output.
+	///
+	/// Consecutive documents go out in a single vectored write, so that an unbuffered output
+	/// doesn't see a separate write for every small document. If any of the documents failed to
+	/// serialize, the documents before it are written before the error is returned.
+	fn write_finished(&mut self) -> crate::Result<()> {
+		let mut ready = vec![];
+		let mut failure = None;
+		while let Some(result) = self.finished.remove(&self.next_write) {
+			match result {
+				Ok(buf) => ready.push(buf),
+				Err(err) => {
+					failure = Some(err);
+					break;
+				}
+			}
+			self.next_write += 1;
+		}
+
+		let mut slices: Vec<_> = ready.iter().map(|buf| IoSlice::new(buf)).collect();
+		write_all_vectored(self.output.get_mut(), &mut slices)?;
+		match failure {
+			Some(err) => Err(err),
+			None => Ok(()),
+		}
+	}
+}
+
+impl<'i, W> crate::Output<'i> for &mut Output<'_, 'i, W>
+where
+	W: Write,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		let value = Value::deserialize(de)?;
+		self.send(intern::into_owned(value, self.intern))
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		self.send(Value::deserialize(de)?)
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		self.send(intern::into_owned(value, self.intern))
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		self.send(value)
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.location = Some(location);
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.output.get_mut().flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A writer that accepts at most a few bytes from the first slice of each vectored write.
+	struct TrickleWriter(Vec<u8>);
+
+	impl Write for TrickleWriter {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			let n = buf.len().min(3);
+			self.0.extend_from_slice(&buf[..n]);
+			Ok(n)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn write_all_vectored_partial_writes() {
+		let bufs: [&[u8]; 4] = [b"", b"first\n", b"", b"second\n"];
+		let mut slices: Vec<_> = bufs.iter().map(|buf| IoSlice::new(buf)).collect();
+		let mut w = TrickleWriter(vec![]);
+		write_all_vectored(&mut w, &mut slices).unwrap();
+		assert_eq!(w.0, b"first\nsecond\n");
+	}
+}
@@ -0,0 +1,880 @@
+//! Protocol Buffers messages, decoded and encoded by a message type from a descriptor set.
+//!
+//! Encoded messages don't carry the names or types of their fields, so translation needs the
+//! compiled descriptors that `protoc --descriptor_set_out` writes alongside generated code, as
+//! parsed in [`descriptor`]. Input is a single message, or a stream of messages that each follow
+//! their length as a varint, as gRPC tooling and `writeDelimitedTo` in the Java library produce.
+//!
+//! Messages translate as maps from field names to values, in the order of the fields in their
+//! definition, leaving out unset fields and skipping fields that the type doesn't define.
+//! Repeated fields translate as arrays, map fields as maps, enums as the names of their values,
+//! and bytes fields as bytes. Unlike the JSON mapping of Protocol Buffers, 64-bit integers stay
+//! numbers rather than becoming strings, since every output format represents them exactly.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+use std::str;
+use std::sync::Arc;
+
+use serde::de::{self, Deserialize};
+
+use crate::detect::Trial;
+use crate::input::{self, DocumentSizeError, Input, Ref};
+use crate::transcode::{self, Value};
+
+mod descriptor;
+
+use descriptor::{DescriptorError, Field, Kind, Pool};
+
+/// The maximum allowed nesting depth of messages, the same as for BSON.
+pub(crate) const DEPTH_LIMIT: usize = 1024;
+
+/// A Protocol Buffers message type from a compiled descriptor set, for
+/// [`Translator::set_protobuf_message`](crate::Translator::set_protobuf_message).
+///
+/// Cloning a message type is cheap, as clones share the types of the whole descriptor set.
+#[derive(Debug, Clone)]
+pub struct ProtobufMessage {
+	pool: Arc<Pool>,
+	message: usize,
+}
+
+impl ProtobufMessage {
+	/// Finds a message type by its full name, like `package.Message`, within an encoded
+	/// `FileDescriptorSet`.
+	///
+	/// `protoc --descriptor_set_out=FILE --include_imports` writes a descriptor set with the
+	/// definitions of every type that a message refers to, which translation requires.
+	pub fn from_descriptor_set(descriptors: &[u8], name: &str) -> crate::Result<ProtobufMessage> {
+		let pool = descriptor::parse(descriptors)?;
+		let name = name.strip_prefix('.').unwrap_or(name);
+		let Some(&message) = pool.message_names.get(name) else {
+			return Err(DescriptorError::UnknownMessage(name.into()).into());
+		};
+		Ok(ProtobufMessage {
+			pool: Arc::new(pool),
+			message,
+		})
+	}
+
+	/// Returns the full name of the message type.
+	pub fn name(&self) -> &str {
+		&self.pool.messages[self.message].name
+	}
+
+	fn decode(&self, input: &[u8]) -> Result<Value<'static>, DecodeError> {
+		decode_message(&self.pool, self.message, input, DEPTH_LIMIT)
+	}
+}
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// Messages have no structure to recognize without their type, and even then nearly any bytes
+	// decode as some message.
+	Ok(Err("Protocol Buffers is never detected from content".into()))
+}
+
+/// Transcodes a single message, or a stream of length-delimited messages, to the output.
+///
+/// With `max_size`, translation fails on the first message whose size exceeds it, before
+/// reading the rest of the message.
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	output: O,
+	message: Option<&ProtobufMessage>,
+	delimited: bool,
+	max_size: Option<u64>,
+) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	let Some(message) = message else {
+		return Err(MissingMessage.into());
+	};
+	match Input::try_from(input)? {
+		Input::Slice(b) => transcode_from(&mut &*b, output, message, delimited, max_size),
+		Input::Reader(r) => {
+			transcode_from(&mut BufReader::new(r), output, message, delimited, max_size)
+		}
+	}
+}
+
+fn transcode_from<'i, R, O>(
+	r: &mut R,
+	mut output: O,
+	message: &ProtobufMessage,
+	delimited: bool,
+	max_size: Option<u64>,
+) -> crate::Result<()>
+where
+	R: Read,
+	O: crate::Output<'i>,
+{
+	let mut buf = vec![];
+	if !delimited {
+		// An empty input is still a message, with every field unset.
+		let limit = max_size.map_or(u64::MAX, |max| max.saturating_add(1));
+		r.take(limit).read_to_end(&mut buf)?;
+		if let Some(max) = max_size.filter(|&max| buf.len() as u64 > max) {
+			return Err(DocumentSizeError { size: None, max }.into());
+		}
+		return output.transcode_value(message.decode(&buf)?);
+	}
+
+	while let Some(len) = read_length(r)? {
+		if let Some(max) = max_size.filter(|&max| len > max) {
+			return Err(DocumentSizeError {
+				size: Some(len),
+				max,
+			}
+			.into());
+		}
+		buf.clear();
+		if (r.take(len).read_to_end(&mut buf)? as u64) < len {
+			return Err(DecodeError::Invalid("truncated message").into());
+		}
+		output.transcode_value(message.decode(&buf)?)?;
+	}
+	Ok(())
+}
+
+/// Reads the varint length before a delimited message, or returns `None` at the end of the
+/// input.
+fn read_length<R: Read>(r: &mut R) -> crate::Result<Option<u64>> {
+	let mut value = 0;
+	for shift in (0..64).step_by(7) {
+		let mut byte = [0];
+		if r.read(&mut byte)? == 0 {
+			if shift == 0 {
+				return Ok(None);
+			}
+			return Err(DecodeError::Invalid("truncated message length").into());
+		}
+		value |= u64::from(byte[0] & 0x7f) << shift;
+		if byte[0] & 0x80 == 0 {
+			return Ok(Some(value));
+		}
+	}
+	Err(DecodeError::Invalid("invalid message length").into())
+}
+
+/// A reader of the fields of an encoded message.
+struct Reader<'a> {
+	input: &'a [u8],
+	pos: usize,
+}
+
+/// The encoded value of a field, by its wire type.
+#[derive(Clone, Copy)]
+enum Wire<'a> {
+	Varint(u64),
+	Fixed64([u8; 8]),
+	Bytes(&'a [u8]),
+	Fixed32([u8; 4]),
+}
+
+impl<'a> Reader<'a> {
+	fn new(input: &'a [u8]) -> Reader<'a> {
+		Reader { input, pos: 0 }
+	}
+
+	fn is_empty(&self) -> bool {
+		self.pos == self.input.len()
+	}
+
+	/// Reads the number and value of the next field, or returns `None` at the end of the message.
+	fn field(&mut self) -> Result<Option<(u32, Wire<'a>)>, &'static str> {
+		if self.is_empty() {
+			return Ok(None);
+		}
+		let key = self.varint()?;
+		let number = u32::try_from(key >> 3)
+			.ok()
+			.filter(|&n| n != 0)
+			.ok_or("invalid field number")?;
+		let wire = match key & 0b111 {
+			0 => Wire::Varint(self.varint()?),
+			1 => Wire::Fixed64(self.fixed()?),
+			2 => {
+				let len = usize::try_from(self.varint()?).map_err(|_| "field too large")?;
+				Wire::Bytes(self.take(len)?)
+			}
+			3 | 4 => return Err("groups are not supported"),
+			5 => Wire::Fixed32(self.fixed()?),
+			_ => return Err("invalid wire type"),
+		};
+		Ok(Some((number, wire)))
+	}
+
+	/// Reads a single value of a type from the contents of a packed repeated field.
+	fn packed(&mut self, kind: Kind) -> Result<Wire<'a>, &'static str> {
+		Ok(match kind.wire_type() {
+			0 => Wire::Varint(self.varint()?),
+			1 => Wire::Fixed64(self.fixed()?),
+			_ => Wire::Fixed32(self.fixed()?),
+		})
+	}
+
+	fn varint(&mut self) -> Result<u64, &'static str> {
+		let mut value = 0;
+		for shift in (0..64).step_by(7) {
+			let byte = self.take(1)?[0];
+			value |= u64::from(byte & 0x7f) << shift;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err("varint longer than 10 bytes")
+	}
+
+	fn fixed<const N: usize>(&mut self) -> Result<[u8; N], &'static str> {
+		Ok(self.take(N)?.try_into().expect("should have taken N bytes"))
+	}
+
+	fn take(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+		let bytes = self.input[self.pos..]
+			.get(..n)
+			.ok_or("unexpected end of message")?;
+		self.pos += n;
+		Ok(bytes)
+	}
+}
+
+impl<'a> Wire<'a> {
+	fn bytes(self) -> Result<&'a [u8], &'static str> {
+		match self {
+			Wire::Bytes(b) => Ok(b),
+			_ => Err("wire type doesn't match the field"),
+		}
+	}
+
+	fn varint(self) -> Result<u64, &'static str> {
+		match self {
+			Wire::Varint(n) => Ok(n),
+			_ => Err("wire type doesn't match the field"),
+		}
+	}
+}
+
+/// Returns the low 32 bits of a varint, which is how 32-bit fields read longer encodings like
+/// those of negative `int32` values.
+fn low32(n: u64) -> u32 {
+	u32::try_from(n & 0xffff_ffff).expect("masked value should fit in 32 bits")
+}
+
+/// The values that a message has decoded for a field so far, where an empty array or map means
+/// that the field is unset, as the encoding can't tell the difference.
+enum Slot {
+	Single(Option<Value<'static>>),
+	Repeated(Vec<Value<'static>>),
+	Map(Vec<(Value<'static>, Value<'static>)>),
+}
+
+fn decode_message(
+	pool: &Pool,
+	message: usize,
+	input: &[u8],
+	depth: usize,
+) -> Result<Value<'static>, DecodeError> {
+	let message = &pool.messages[message];
+	let Some(depth) = depth.checked_sub(1) else {
+		return Err(DecodeError::Invalid("messages nested too deeply"));
+	};
+	let mut slots: Vec<Slot> = { message.fields.iter() }
+		.map(|field| match field.kind {
+			Kind::Message(entry) if field.repeated && pool.messages[entry].map_entry => {
+				Slot::Map(vec![])
+			}
+			_ if field.repeated => Slot::Repeated(vec![]),
+			_ => Slot::Single(None),
+		})
+		.collect();
+	let mut r = Reader::new(input);
+	while let Some((number, wire)) = r.field().map_err(DecodeError::Invalid)? {
+		// Fields that the type doesn't define may come from a newer version of it.
+		let Some(&i) = message.numbers.get(&number) else {
+			continue;
+		};
+		let field = &message.fields[i];
+		let invalid = |message| DecodeError::Field(field.name.clone(), message);
+		match &mut slots[i] {
+			Slot::Single(value) => *value = Some(decode_value(pool, field, wire, depth)?),
+			Slot::Repeated(values) => match wire {
+				Wire::Bytes(b) if field.kind.is_packable() => {
+					let mut r = Reader::new(b);
+					while !r.is_empty() {
+						let wire = r.packed(field.kind).map_err(invalid)?;
+						values.push(decode_value(pool, field, wire, depth)?);
+					}
+				}
+				wire => values.push(decode_value(pool, field, wire, depth)?),
+			},
+			Slot::Map(entries) => {
+				let Kind::Message(entry) = field.kind else {
+					unreachable!("map fields should have entry types");
+				};
+				let input = wire.bytes().map_err(invalid)?;
+				entries.push(decode_entry(pool, entry, input, depth)?);
+			}
+		}
+	}
+
+	let entries = message
+		.fields
+		.iter()
+		.zip(slots)
+		.filter_map(|(field, slot)| {
+			let value = match slot {
+				Slot::Single(value) => value?,
+				Slot::Repeated(values) if values.is_empty() => return None,
+				Slot::Repeated(values) => Value::Seq(values),
+				Slot::Map(entries) if entries.is_empty() => return None,
+				Slot::Map(entries) => Value::Map(entries),
+			};
+			Some((Value::String(Cow::Owned(field.name.clone())), value))
+		});
+	Ok(Value::Map(entries.collect()))
+}
+
+/// Decodes the key and value of a map field's entry, where either may be missing to stand for
+/// the default value of its type.
+fn decode_entry(
+	pool: &Pool,
+	entry: usize,
+	input: &[u8],
+	depth: usize,
+) -> Result<(Value<'static>, Value<'static>), DecodeError> {
+	let entry = &pool.messages[entry];
+	let (Some(key_field), Some(value_field)) = (entry.field_numbered(1), entry.field_numbered(2))
+	else {
+		return Err(DecodeError::Invalid("map entry type lacks a key or value"));
+	};
+	let (mut key, mut value) = (None, None);
+	let mut r = Reader::new(input);
+	while let Some((number, wire)) = r.field().map_err(DecodeError::Invalid)? {
+		match number {
+			1 => key = Some(decode_value(pool, key_field, wire, depth)?),
+			2 => value = Some(decode_value(pool, value_field, wire, depth)?),
+			_ => {}
+		}
+	}
+	Ok((
+		key.unwrap_or_else(|| default_value(pool, key_field.kind)),
+		value.unwrap_or_else(|| default_value(pool, value_field.kind)),
+	))
+}
+
+fn decode_value(
+	pool: &Pool,
+	field: &Field,
+	wire: Wire,
+	depth: usize,
+) -> Result<Value<'static>, DecodeError> {
+	let invalid = |message| DecodeError::Field(field.name.clone(), message);
+	let value = match (field.kind, wire) {
+		(Kind::Int32, Wire::Varint(n)) => Value::I32(low32(n) as i32),
+		(Kind::Int64, Wire::Varint(n)) => Value::I64(n as i64),
+		(Kind::Uint32, Wire::Varint(n)) => Value::U32(low32(n)),
+		(Kind::Uint64, Wire::Varint(n)) => Value::U64(n),
+		(Kind::Sint32, Wire::Varint(n)) => {
+			let n = low32(n);
+			Value::I32((n >> 1) as i32 ^ -((n & 1) as i32))
+		}
+		(Kind::Sint64, Wire::Varint(n)) => Value::I64((n >> 1) as i64 ^ -((n & 1) as i64)),
+		(Kind::Bool, Wire::Varint(n)) => Value::Bool(n != 0),
+		(Kind::Enum(e), Wire::Varint(n)) => enum_value(pool, e, low32(n) as i32),
+		(Kind::Fixed32, Wire::Fixed32(b)) => Value::U32(u32::from_le_bytes(b)),
+		(Kind::Sfixed32, Wire::Fixed32(b)) => Value::I32(i32::from_le_bytes(b)),
+		(Kind::Float, Wire::Fixed32(b)) => Value::F32(f32::from_le_bytes(b)),
+		(Kind::Fixed64, Wire::Fixed64(b)) => Value::U64(u64::from_le_bytes(b)),
+		(Kind::Sfixed64, Wire::Fixed64(b)) => Value::I64(i64::from_le_bytes(b)),
+		(Kind::Double, Wire::Fixed64(b)) => Value::F64(f64::from_le_bytes(b)),
+		(Kind::String, Wire::Bytes(b)) => {
+			let s = str::from_utf8(b).map_err(|_| invalid("invalid UTF-8 in string"))?;
+			Value::String(Cow::Owned(s.into()))
+		}
+		(Kind::Bytes, Wire::Bytes(b)) => Value::Bytes(Cow::Owned(b.into())),
+		(Kind::Message(m), Wire::Bytes(b)) => decode_message(pool, m, b, depth)?,
+		(Kind::Group, _) => return Err(invalid("groups are not supported")),
+		_ => return Err(invalid("wire type doesn't match the field")),
+	};
+	Ok(value)
+}
+
+/// Returns the name of an enum value, or its number for a value that the type doesn't define.
+fn enum_value(pool: &Pool, e: usize, number: i32) -> Value<'static> {
+	match pool.enums[e].name_of(number) {
+		Some(name) => Value::String(Cow::Owned(name.into())),
+		None => Value::I32(number),
+	}
+}
+
+/// Returns the value of a type that an unset map key or value stands for.
+fn default_value(pool: &Pool, kind: Kind) -> Value<'static> {
+	match kind {
+		Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(0),
+		Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(0),
+		Kind::Uint32 | Kind::Fixed32 => Value::U32(0),
+		Kind::Uint64 | Kind::Fixed64 => Value::U64(0),
+		Kind::Float => Value::F32(0.0),
+		Kind::Double => Value::F64(0.0),
+		Kind::Bool => Value::Bool(false),
+		Kind::String => Value::String(Cow::Borrowed("")),
+		Kind::Bytes => Value::Bytes(Cow::Borrowed(&[])),
+		Kind::Enum(e) => enum_value(pool, e, 0),
+		Kind::Message(_) => Value::Map(vec![]),
+		Kind::Group => Value::Unit,
+	}
+}
+
+/// The error for input that fails to decode as a message of its type.
+#[derive(Debug)]
+pub(crate) enum DecodeError {
+	Invalid(&'static str),
+	Field(String, &'static str),
+}
+
+impl error::Error for DecodeError {}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			DecodeError::Invalid(message) => {
+				write!(f, "invalid Protocol Buffers message: {message}")
+			}
+			DecodeError::Field(name, message) => {
+				write!(f, "invalid Protocol Buffers field {name:?}: {message}")
+			}
+		}
+	}
+}
+
+/// The error for Protocol Buffers input or output without a
+/// [message type](crate::Translator::set_protobuf_message).
+#[derive(Debug)]
+pub(crate) struct MissingMessage;
+
+impl error::Error for MissingMessage {}
+
+impl fmt::Display for MissingMessage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("Protocol Buffers translation needs a message type from a descriptor set")
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+	message: Option<ProtobufMessage>,
+	delimited: bool,
+	written: bool,
+	buf: Vec<u8>,
+}
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output {
+			w,
+			message: None,
+			delimited: false,
+			written: false,
+			buf: vec![],
+		}
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	pub(crate) fn set_message(&mut self, message: Option<ProtobufMessage>) {
+		self.message = message;
+	}
+
+	pub(crate) fn set_delimited(&mut self, delimited: bool) {
+		self.delimited = delimited;
+	}
+
+	fn output_value(&mut self, value: &Value) -> crate::Result<()> {
+		let Some(message) = &self.message else {
+			return Err(MissingMessage.into());
+		};
+		if self.written && !self.delimited {
+			return Err(EncodeError::MultipleMessages.into());
+		}
+		let Value::Map(entries) = value else {
+			return Err(EncodeError::NonMapRoot.into());
+		};
+		self.buf.clear();
+		encode_message(&mut self.buf, &message.pool, message.message, entries)?;
+		if self.delimited {
+			let mut len = vec![];
+			write_varint(&mut len, self.buf.len() as u64);
+			self.w.write_all(&len)?;
+		}
+		self.w.write_all(&self.buf)?;
+		self.written = true;
+		Ok(())
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		let value = Value::deserialize(de)?;
+		self.output_value(&value)
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		self.output_value(&value)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.flush()
+	}
+}
+
+fn encode_message(
+	buf: &mut Vec<u8>,
+	pool: &Pool,
+	message: usize,
+	entries: &[(Value, Value)],
+) -> Result<(), EncodeError> {
+	let message = &pool.messages[message];
+	for (key, value) in entries {
+		let name = match key {
+			Value::String(s) => Cow::Borrowed(&**s),
+			Value::Char(c) => Cow::Owned(c.to_string()),
+			_ => return Err(EncodeError::NonStringKey),
+		};
+		let Some(field) = message.field_named(&name) else {
+			return Err(EncodeError::UnknownField {
+				message: message.name.clone(),
+				field: name.into(),
+			});
+		};
+		encode_field(buf, pool, field, value)?;
+	}
+	Ok(())
+}
+
+fn encode_field(
+	buf: &mut Vec<u8>,
+	pool: &Pool,
+	field: &Field,
+	value: &Value,
+) -> Result<(), EncodeError> {
+	// A null value leaves the field unset, as in the JSON mapping.
+	if matches!(value, Value::Unit) {
+		return Ok(());
+	}
+	if !field.repeated {
+		return encode_value(buf, pool, field, value);
+	}
+	if let Kind::Message(entry) = field.kind {
+		let entry = &pool.messages[entry];
+		if entry.map_entry {
+			let (Some(key_field), Some(value_field)) =
+				(entry.field_numbered(1), entry.field_numbered(2))
+			else {
+				return Err(EncodeError::Type(
+					field.name.clone(),
+					"a valid map entry type",
+				));
+			};
+			let Value::Map(entries) = value else {
+				return Err(EncodeError::Type(field.name.clone(), "a map"));
+			};
+			let mut entry = vec![];
+			for (k, v) in entries {
+				entry.clear();
+				encode_value(&mut entry, pool, key_field, k)?;
+				encode_field(&mut entry, pool, value_field, v)?;
+				write_tag(buf, field.number, 2);
+				write_bytes(buf, &entry);
+			}
+			return Ok(());
+		}
+	}
+	let Value::Seq(values) = value else {
+		return Err(EncodeError::Type(field.name.clone(), "an array"));
+	};
+	if field.packed {
+		let mut packed = vec![];
+		for v in values {
+			encode_scalar(&mut packed, pool, field, v)?;
+		}
+		write_tag(buf, field.number, 2);
+		write_bytes(buf, &packed);
+		return Ok(());
+	}
+	for v in values {
+		encode_value(buf, pool, field, v)?;
+	}
+	Ok(())
+}
+
+/// Encodes a single value of a field with its tag.
+fn encode_value(
+	buf: &mut Vec<u8>,
+	pool: &Pool,
+	field: &Field,
+	value: &Value,
+) -> Result<(), EncodeError> {
+	write_tag(buf, field.number, field.kind.wire_type());
+	match field.kind {
+		Kind::Message(m) => {
+			let Value::Map(entries) = value else {
+				return Err(EncodeError::Type(field.name.clone(), "a map"));
+			};
+			let mut message = vec![];
+			encode_message(&mut message, pool, m, entries)?;
+			write_bytes(buf, &message);
+		}
+		Kind::String => match value {
+			Value::String(s) => write_bytes(buf, s.as_bytes()),
+			Value::Char(c) => write_bytes(buf, c.to_string().as_bytes()),
+			_ => return Err(EncodeError::Type(field.name.clone(), "a string")),
+		},
+		Kind::Bytes => match value {
+			Value::Bytes(b) => write_bytes(buf, b),
+			// Bytes that went through JSON come back as arrays of numbers.
+			Value::Seq(seq) => {
+				let bytes: Option<Vec<u8>> = seq.iter().map(integer).collect();
+				let bytes = bytes.ok_or_else(|| EncodeError::Type(field.name.clone(), "bytes"))?;
+				write_bytes(buf, &bytes);
+			}
+			_ => return Err(EncodeError::Type(field.name.clone(), "bytes")),
+		},
+		Kind::Group => return Err(EncodeError::Group(field.name.clone())),
+		_ => encode_scalar(buf, pool, field, value)?,
+	}
+	Ok(())
+}
+
+/// Encodes a numeric, boolean, or enum value without a tag.
+fn encode_scalar(
+	buf: &mut Vec<u8>,
+	pool: &Pool,
+	field: &Field,
+	value: &Value,
+) -> Result<(), EncodeError> {
+	let mismatch = |expected| EncodeError::Type(field.name.clone(), expected);
+	match field.kind {
+		Kind::Int32 => {
+			let n: i32 = integer(value).ok_or_else(|| mismatch("a 32-bit integer"))?;
+			write_varint(buf, i64::from(n) as u64);
+		}
+		Kind::Int64 => {
+			let n: i64 = integer(value).ok_or_else(|| mismatch("a 64-bit integer"))?;
+			write_varint(buf, n as u64);
+		}
+		Kind::Uint32 => {
+			let n: u32 = integer(value).ok_or_else(|| mismatch("an unsigned 32-bit integer"))?;
+			write_varint(buf, u64::from(n));
+		}
+		Kind::Uint64 => {
+			let n: u64 = integer(value).ok_or_else(|| mismatch("an unsigned 64-bit integer"))?;
+			write_varint(buf, n);
+		}
+		Kind::Sint32 => {
+			let n: i32 = integer(value).ok_or_else(|| mismatch("a 32-bit integer"))?;
+			write_varint(buf, u64::from(((n << 1) ^ (n >> 31)) as u32));
+		}
+		Kind::Sint64 => {
+			let n: i64 = integer(value).ok_or_else(|| mismatch("a 64-bit integer"))?;
+			write_varint(buf, ((n << 1) ^ (n >> 63)) as u64);
+		}
+		Kind::Bool => {
+			let Value::Bool(b) = value else {
+				return Err(mismatch("a boolean"));
+			};
+			write_varint(buf, u64::from(*b));
+		}
+		Kind::Enum(e) => {
+			let n = match value {
+				Value::String(name) => pool.enums[e].number_of(name).ok_or_else(|| {
+					EncodeError::UnknownEnumValue(field.name.clone(), name.to_string())
+				})?,
+				value => integer(value).ok_or_else(|| mismatch("an enum value name or number"))?,
+			};
+			write_varint(buf, i64::from(n) as u64);
+		}
+		Kind::Fixed32 => {
+			let n: u32 = integer(value).ok_or_else(|| mismatch("an unsigned 32-bit integer"))?;
+			buf.extend_from_slice(&n.to_le_bytes());
+		}
+		Kind::Sfixed32 => {
+			let n: i32 = integer(value).ok_or_else(|| mismatch("a 32-bit integer"))?;
+			buf.extend_from_slice(&n.to_le_bytes());
+		}
+		Kind::Fixed64 => {
+			let n: u64 = integer(value).ok_or_else(|| mismatch("an unsigned 64-bit integer"))?;
+			buf.extend_from_slice(&n.to_le_bytes());
+		}
+		Kind::Sfixed64 => {
+			let n: i64 = integer(value).ok_or_else(|| mismatch("a 64-bit integer"))?;
+			buf.extend_from_slice(&n.to_le_bytes());
+		}
+		Kind::Float => {
+			let n = float(value).ok_or_else(|| mismatch("a number"))?;
+			buf.extend_from_slice(&narrow(n).to_le_bytes());
+		}
+		Kind::Double => {
+			let n = float(value).ok_or_else(|| mismatch("a number"))?;
+			buf.extend_from_slice(&n.to_le_bytes());
+		}
+		Kind::String | Kind::Bytes | Kind::Message(_) | Kind::Group => {
+			unreachable!("only scalar fields should encode as scalars")
+		}
+	}
+	Ok(())
+}
+
+/// Returns an integer value in the range of a type, including the decimal strings that the JSON
+/// mapping uses for 64-bit integers.
+fn integer<T: TryFrom<i128>>(value: &Value) -> Option<T> {
+	let n = match *value {
+		Value::I8(n) => i128::from(n),
+		Value::I16(n) => i128::from(n),
+		Value::I32(n) => i128::from(n),
+		Value::I64(n) => i128::from(n),
+		Value::I128(n) => n,
+		Value::U8(n) => i128::from(n),
+		Value::U16(n) => i128::from(n),
+		Value::U32(n) => i128::from(n),
+		Value::U64(n) => i128::from(n),
+		Value::U128(n) => i128::try_from(n).ok()?,
+		Value::String(ref s) => s.parse().ok()?,
+		_ => return None,
+	};
+	T::try_from(n).ok()
+}
+
+fn float(value: &Value) -> Option<f64> {
+	match *value {
+		Value::F32(n) => Some(f64::from(n)),
+		Value::F64(n) => Some(n),
+		_ => integer::<i64>(value).map(|n| n as f64),
+	}
+}
+
+/// Rounds a value to the precision of a `float` field.
+#[allow(clippy::cast_possible_truncation)]
+fn narrow(n: f64) -> f32 {
+	n as f32
+}
+
+fn write_tag(buf: &mut Vec<u8>, number: u32, wire_type: u8) {
+	write_varint(buf, u64::from(number) << 3 | u64::from(wire_type));
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+	write_varint(buf, bytes.len() as u64);
+	buf.extend_from_slice(bytes);
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+	loop {
+		let byte = u8::try_from(n & 0x7f).expect("masked value should fit in a byte");
+		n >>= 7;
+		if n == 0 {
+			buf.push(byte);
+			return;
+		}
+		buf.push(byte | 0x80);
+	}
+}
+
+/// The error for a document that can't encode as a message of the output type.
+#[derive(Debug)]
+pub(crate) enum EncodeError {
+	NonMapRoot,
+	MultipleMessages,
+	NonStringKey,
+	UnknownField { message: String, field: String },
+	UnknownEnumValue(String, String),
+	Type(String, &'static str),
+	Group(String),
+}
+
+impl error::Error for EncodeError {}
+
+impl fmt::Display for EncodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			EncodeError::NonMapRoot => {
+				f.write_str("root of Protocol Buffers message must be a map")
+			}
+			EncodeError::MultipleMessages => f.write_str(
+				"Protocol Buffers output holds a single message unless it's length-delimited",
+			),
+			EncodeError::NonStringKey => {
+				f.write_str("Protocol Buffers field names must be strings")
+			}
+			EncodeError::UnknownField { message, field } => {
+				write!(f, "message type {message} has no field {field:?}")
+			}
+			EncodeError::UnknownEnumValue(field, name) => {
+				write!(f, "field {field:?} has no enum value {name:?}")
+			}
+			EncodeError::Type(field, expected) => write!(f, "field {field:?} expects {expected}"),
+			EncodeError::Group(field) => write!(f, "field {field:?} is an unsupported group"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Returns a descriptor set for a proto2 message type with a single repeated sint32 field,
+	/// which is unpacked by default.
+	fn deltas_descriptor() -> Vec<u8> {
+		let ld = |buf: &mut Vec<u8>, number, bytes: &[u8]| {
+			write_tag(buf, number, 2);
+			write_bytes(buf, bytes);
+		};
+		let mut field = vec![];
+		ld(&mut field, 1, b"deltas");
+		field.extend_from_slice(b"\x18\x01\x20\x03\x28\x11");
+		let mut message = vec![];
+		ld(&mut message, 1, b"Deltas");
+		ld(&mut message, 2, &field);
+		let mut file = vec![];
+		ld(&mut file, 4, &message);
+		let mut set = vec![];
+		ld(&mut set, 1, &file);
+		set
+	}
+
+	#[test]
+	fn repeated_fields_packed_or_not() {
+		let message = ProtobufMessage::from_descriptor_set(&deltas_descriptor(), "Deltas").unwrap();
+		// The values 1, -1, and -300 in zigzag form, unpacked, then packed.
+		const UNPACKED: &[u8] = b"\x08\x02\x08\x01\x08\xd7\x04";
+		const PACKED: &[u8] = b"\x0a\x04\x02\x01\xd7\x04";
+		for input in [UNPACKED, PACKED] {
+			let value = message.decode(input).unwrap();
+			assert_eq!(
+				serde_json::to_string(&value).unwrap(),
+				r#"{"deltas":[1,-1,-300]}"#
+			);
+			let Value::Map(entries) = value else {
+				panic!("message didn't decode as a map");
+			};
+			let mut output = vec![];
+			encode_message(&mut output, &message.pool, message.message, &entries).unwrap();
+			assert_eq!(output, UNPACKED);
+		}
+
+		for (input, want) in [(&b"\x08"[..], "unexpected end"), (b"\x0b", "groups")] {
+			let Err(DecodeError::Invalid(err)) = message.decode(input) else {
+				panic!("decoded an invalid message");
+			};
+			assert!(err.contains(want), "{err}");
+		}
+	}
+}
@@ -0,0 +1,442 @@
+//! The message types of a compiled `FileDescriptorSet`, as `protoc --descriptor_set_out` writes.
+//!
+//! A descriptor set is itself a Protocol Buffers message, whose few fields that matter for
+//! translation are decoded here by their numbers in `google/protobuf/descriptor.proto`. Parsing
+//! resolves the type names of fields to indexes, so that translation never looks up a name.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::str;
+
+use super::{Reader, Wire, low32};
+
+/// The message and enum types of a descriptor set.
+#[derive(Debug)]
+pub(super) struct Pool {
+	pub(super) messages: Vec<MessageType>,
+	pub(super) enums: Vec<EnumType>,
+	/// The index of each message type by its full name, without a leading dot.
+	pub(super) message_names: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+pub(super) struct MessageType {
+	pub(super) name: String,
+	pub(super) fields: Vec<Field>,
+	/// The index of each field by its number.
+	pub(super) numbers: HashMap<u32, usize>,
+	/// Whether this is the entry type that `protoc` generates for a map field, with a key field
+	/// numbered 1 and a value field numbered 2.
+	pub(super) map_entry: bool,
+}
+
+impl MessageType {
+	/// Returns a field by its name, or by the camel case name of the JSON mapping.
+	pub(super) fn field_named(&self, name: &str) -> Option<&Field> {
+		self.fields
+			.iter()
+			.find(|f| f.name == name || f.json_name.as_deref() == Some(name))
+	}
+
+	pub(super) fn field_numbered(&self, number: u32) -> Option<&Field> {
+		self.numbers.get(&number).map(|&i| &self.fields[i])
+	}
+}
+
+#[derive(Debug)]
+pub(super) struct Field {
+	pub(super) name: String,
+	pub(super) json_name: Option<String>,
+	pub(super) number: u32,
+	pub(super) kind: Kind,
+	pub(super) repeated: bool,
+	/// Whether the elements of a repeated field encode together in a single length-delimited
+	/// record, which decoding accepts regardless.
+	pub(super) packed: bool,
+}
+
+/// The type of a field, with message and enum types as indexes into their [`Pool`] lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+	Double,
+	Float,
+	Int64,
+	Uint64,
+	Int32,
+	Fixed64,
+	Fixed32,
+	Bool,
+	String,
+	Group,
+	Message(usize),
+	Bytes,
+	Uint32,
+	Enum(usize),
+	Sfixed32,
+	Sfixed64,
+	Sint32,
+	Sint64,
+}
+
+impl Kind {
+	/// Returns the wire type that encodes a value of this type.
+	pub(super) fn wire_type(self) -> u8 {
+		match self {
+			Kind::Int64
+			| Kind::Uint64
+			| Kind::Int32
+			| Kind::Bool
+			| Kind::Uint32
+			| Kind::Enum(_)
+			| Kind::Sint32
+			| Kind::Sint64 => 0,
+			Kind::Double | Kind::Fixed64 | Kind::Sfixed64 => 1,
+			Kind::String | Kind::Message(_) | Kind::Bytes => 2,
+			Kind::Group => 3,
+			Kind::Float | Kind::Fixed32 | Kind::Sfixed32 => 5,
+		}
+	}
+
+	/// Returns true if repeated values of this type can be packed.
+	pub(super) fn is_packable(self) -> bool {
+		matches!(self.wire_type(), 0 | 1 | 5)
+	}
+}
+
+#[derive(Debug)]
+pub(super) struct EnumType {
+	/// The number and name of each value, in the order of their definition.
+	pub(super) values: Vec<(i32, String)>,
+}
+
+impl EnumType {
+	pub(super) fn name_of(&self, number: i32) -> Option<&str> {
+		self.values
+			.iter()
+			.find(|(n, _)| *n == number)
+			.map(|(_, name)| name.as_str())
+	}
+
+	pub(super) fn number_of(&self, name: &str) -> Option<i32> {
+		self.values
+			.iter()
+			.find(|(_, n)| n == name)
+			.map(|&(number, _)| number)
+	}
+}
+
+/// Parses an encoded `FileDescriptorSet`.
+pub(super) fn parse(input: &[u8]) -> Result<Pool, DescriptorError> {
+	let mut raw = RawPool::default();
+	let mut r = Reader::new(input);
+	while let Some((number, wire)) = r.field()? {
+		if number == 1 {
+			raw.file(wire.bytes()?)?;
+		}
+	}
+	raw.resolve()
+}
+
+/// A message or enum type, before the types of fields resolve to them.
+#[derive(Clone, Copy)]
+enum TypeRef {
+	Message(usize),
+	Enum(usize),
+}
+
+#[derive(Default)]
+struct RawPool {
+	messages: Vec<RawMessage>,
+	enums: Vec<EnumType>,
+	names: HashMap<String, TypeRef>,
+}
+
+struct RawMessage {
+	name: String,
+	fields: Vec<RawField>,
+	map_entry: bool,
+	/// Whether repeated scalar fields are packed unless their options say otherwise, which is
+	/// true of every syntax but proto2.
+	packed_by_default: bool,
+}
+
+#[derive(Default)]
+struct RawField {
+	name: String,
+	json_name: Option<String>,
+	number: u32,
+	repeated: bool,
+	type_number: Option<u64>,
+	type_name: Option<String>,
+	packed: Option<bool>,
+}
+
+impl RawPool {
+	/// Adds the types of an encoded `FileDescriptorProto`.
+	fn file(&mut self, input: &[u8]) -> Result<(), DescriptorError> {
+		let (mut package, mut syntax) = ("", "proto2");
+		let (mut messages, mut enums) = (vec![], vec![]);
+		let mut r = Reader::new(input);
+		while let Some((number, wire)) = r.field()? {
+			match number {
+				2 => package = string(wire)?,
+				4 => messages.push(wire.bytes()?),
+				5 => enums.push(wire.bytes()?),
+				12 => syntax = string(wire)?,
+				_ => {}
+			}
+		}
+		// Files without a syntax are proto2, and editions pack by default like proto3.
+		let packed_by_default = !matches!(syntax, "" | "proto2");
+		for input in messages {
+			self.message(input, package, packed_by_default)?;
+		}
+		for input in enums {
+			self.enumeration(input, package)?;
+		}
+		Ok(())
+	}
+
+	/// Adds an encoded `DescriptorProto` and its nested types, within the scope of a package or
+	/// enclosing message.
+	fn message(
+		&mut self,
+		input: &[u8],
+		scope: &str,
+		packed_by_default: bool,
+	) -> Result<(), DescriptorError> {
+		let mut message = RawMessage {
+			name: String::new(),
+			fields: vec![],
+			map_entry: false,
+			packed_by_default,
+		};
+		let (mut nested, mut enums) = (vec![], vec![]);
+		let mut r = Reader::new(input);
+		while let Some((number, wire)) = r.field()? {
+			match number {
+				1 => message.name = scoped(scope, string(wire)?),
+				2 => message.fields.push(field(wire.bytes()?)?),
+				3 => nested.push(wire.bytes()?),
+				4 => enums.push(wire.bytes()?),
+				7 => {
+					let mut r = Reader::new(wire.bytes()?);
+					while let Some((number, wire)) = r.field()? {
+						if number == 7 {
+							message.map_entry = wire.varint()? != 0;
+						}
+					}
+				}
+				_ => {}
+			}
+		}
+		let name = message.name.clone();
+		self.names
+			.insert(name.clone(), TypeRef::Message(self.messages.len()));
+		self.messages.push(message);
+		for input in nested {
+			self.message(input, &name, packed_by_default)?;
+		}
+		for input in enums {
+			self.enumeration(input, &name)?;
+		}
+		Ok(())
+	}
+
+	/// Adds an encoded `EnumDescriptorProto`.
+	fn enumeration(&mut self, input: &[u8], scope: &str) -> Result<(), DescriptorError> {
+		let mut name = String::new();
+		let mut values = vec![];
+		let mut r = Reader::new(input);
+		while let Some((number, wire)) = r.field()? {
+			match number {
+				1 => name = scoped(scope, string(wire)?),
+				2 => {
+					let (mut value_name, mut value_number) = ("", 0);
+					let mut r = Reader::new(wire.bytes()?);
+					while let Some((number, wire)) = r.field()? {
+						match number {
+							1 => value_name = string(wire)?,
+							2 => value_number = low32(wire.varint()?) as i32,
+							_ => {}
+						}
+					}
+					values.push((value_number, value_name.to_owned()));
+				}
+				_ => {}
+			}
+		}
+		self.names.insert(name, TypeRef::Enum(self.enums.len()));
+		self.enums.push(EnumType { values });
+		Ok(())
+	}
+
+	/// Resolves the types of every field.
+	fn resolve(self) -> Result<Pool, DescriptorError> {
+		let mut messages = Vec::with_capacity(self.messages.len());
+		for message in &self.messages {
+			let mut fields = Vec::with_capacity(message.fields.len());
+			for field in &message.fields {
+				let kind = self.kind(field, &message.name)?;
+				let packed = kind.is_packable()
+					&& field.repeated
+					&& field.packed.unwrap_or(message.packed_by_default);
+				fields.push(Field {
+					name: field.name.clone(),
+					json_name: field.json_name.clone(),
+					number: field.number,
+					kind,
+					repeated: field.repeated,
+					packed,
+				});
+			}
+			let numbers = fields
+				.iter()
+				.enumerate()
+				.map(|(i, field)| (field.number, i))
+				.collect();
+			messages.push(MessageType {
+				name: message.name.clone(),
+				fields,
+				numbers,
+				map_entry: message.map_entry,
+			});
+		}
+		let message_names = self
+			.names
+			.iter()
+			.filter_map(|(name, &r)| match r {
+				TypeRef::Message(i) => Some((name.clone(), i)),
+				TypeRef::Enum(_) => None,
+			})
+			.collect();
+		Ok(Pool {
+			messages,
+			enums: self.enums,
+			message_names,
+		})
+	}
+
+	fn kind(&self, field: &RawField, scope: &str) -> Result<Kind, DescriptorError> {
+		let kind = match field.type_number {
+			Some(1) => Kind::Double,
+			Some(2) => Kind::Float,
+			Some(3) => Kind::Int64,
+			Some(4) => Kind::Uint64,
+			Some(5) => Kind::Int32,
+			Some(6) => Kind::Fixed64,
+			Some(7) => Kind::Fixed32,
+			Some(8) => Kind::Bool,
+			Some(9) => Kind::String,
+			Some(10) => Kind::Group,
+			Some(12) => Kind::Bytes,
+			Some(13) => Kind::Uint32,
+			Some(15) => Kind::Sfixed32,
+			Some(16) => Kind::Sfixed64,
+			Some(17) => Kind::Sint32,
+			Some(18) => Kind::Sint64,
+			// Messages and enums take their types from their names, as do fields with no type
+			// number at all in a descriptor that protoc hasn't fully resolved.
+			Some(11 | 14) | None => {
+				let name = field.type_name.as_deref().unwrap_or_default();
+				match (self.lookup(name, scope), field.type_number) {
+					(Some(TypeRef::Message(i)), Some(11) | None) => Kind::Message(i),
+					(Some(TypeRef::Enum(i)), Some(14) | None) => Kind::Enum(i),
+					_ => return Err(DescriptorError::UnknownType(name.into())),
+				}
+			}
+			Some(_) => return Err(DescriptorError::Invalid("unknown field type")),
+		};
+		Ok(kind)
+	}
+
+	/// Finds a type by a fully qualified name with a leading dot, or else by a relative name
+	/// within each enclosing scope from the innermost outward, as `protoc` resolves names.
+	fn lookup(&self, name: &str, mut scope: &str) -> Option<TypeRef> {
+		if let Some(name) = name.strip_prefix('.') {
+			return self.names.get(name).copied();
+		}
+		loop {
+			if let Some(&r) = self.names.get(&scoped(scope, name)) {
+				return Some(r);
+			}
+			if scope.is_empty() {
+				return None;
+			}
+			scope = scope.rsplit_once('.').map_or("", |(outer, _)| outer);
+		}
+	}
+}
+
+/// Parses an encoded `FieldDescriptorProto`.
+fn field(input: &[u8]) -> Result<RawField, DescriptorError> {
+	let mut field = RawField::default();
+	let mut r = Reader::new(input);
+	while let Some((number, wire)) = r.field()? {
+		match number {
+			1 => field.name = string(wire)?.into(),
+			3 => {
+				field.number = u32::try_from(wire.varint()?)
+					.map_err(|_| DescriptorError::Invalid("invalid field number"))?;
+			}
+			4 => field.repeated = wire.varint()? == 3,
+			5 => field.type_number = Some(wire.varint()?),
+			6 => field.type_name = Some(string(wire)?.into()),
+			8 => {
+				let mut r = Reader::new(wire.bytes()?);
+				while let Some((number, wire)) = r.field()? {
+					if number == 2 {
+						field.packed = Some(wire.varint()? != 0);
+					}
+				}
+			}
+			10 => field.json_name = Some(string(wire)?.into()),
+			_ => {}
+		}
+	}
+	Ok(field)
+}
+
+fn string(wire: Wire<'_>) -> Result<&str, DescriptorError> {
+	str::from_utf8(wire.bytes()?).map_err(|_| DescriptorError::Invalid("invalid UTF-8 in a name"))
+}
+
+/// Returns the full name of a type within a scope.
+fn scoped(scope: &str, name: &str) -> String {
+	if scope.is_empty() {
+		name.into()
+	} else {
+		format!("{scope}.{name}")
+	}
+}
+
+/// The error for a descriptor set that fails to parse, or lacks a type.
+#[derive(Debug)]
+pub(crate) enum DescriptorError {
+	Invalid(&'static str),
+	UnknownType(String),
+	UnknownMessage(String),
+}
+
+impl From<&'static str> for DescriptorError {
+	fn from(message: &'static str) -> Self {
+		DescriptorError::Invalid(message)
+	}
+}
+
+impl error::Error for DescriptorError {}
+
+impl fmt::Display for DescriptorError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			DescriptorError::Invalid(message) => write!(f, "invalid descriptor set: {message}"),
+			DescriptorError::UnknownType(name) => {
+				write!(f, "descriptor set doesn't define the type {name:?}")
+			}
+			DescriptorError::UnknownMessage(name) => {
+				write!(f, "descriptor set doesn't define a message named {name:?}")
+			}
+		}
+	}
+}
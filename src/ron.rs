@@ -0,0 +1,751 @@
+//! The RON format of Rusty Object Notation, as interpreted by xt itself.
+//!
+//! An input is a single document holding one value, after any `#![enable(...)]` attributes,
+//! which xt reads past since they only guide typed deserialization. Lists and tuples translate as
+//! arrays, `()` and `None` as null, and `Some(value)` as its value alone. A struct translates as
+//! a map from its field names to their values, and the name of a struct or an enum variant in
+//! front of its parentheses is dropped, so that `Point(x: 1, y: 2)` translates like the JSON
+//! `{"x": 1, "y": 2}`. A name alone, like the unit variant `Red`, translates as a string.
+//!
+//! Output goes the other way: maps are written as RON maps with their keys as values, so that the
+//! JSON `{"port": 80}` becomes `{"port": 80}`, and null is written as `()`.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::str;
+
+use serde::{Deserialize, de};
+
+use crate::detect::Trial;
+use crate::input::{self, Ref};
+use crate::transcode::{self, Value};
+
+/// The maximum nesting depth of RON input, as for MessagePack input.
+pub(crate) const DEPTH_LIMIT: usize = 1024;
+
+/// The indentation of each nested level of output, as in the ron crate's pretty printer.
+const INDENT: &str = "    ";
+
+pub(crate) fn input_matches(_: Ref) -> io::Result<Trial> {
+	// JSON maps and arrays of strings and numbers are also valid RON, and JSON detection already
+	// claims them.
+	Ok(Err("RON is never detected from content".into()))
+}
+
+pub(crate) fn transcode<'i, O>(input: input::Handle<'i>, mut output: O) -> crate::Result<()>
+where
+	O: crate::Output<'i>,
+{
+	let input: Cow<'_, [u8]> = input.try_into()?;
+	let text = str::from_utf8(&input)?;
+	let mut parser = Parser { text, pos: 0 };
+	if let Some((start, value)) = parser.document()? {
+		output.set_document_offset(start as u64);
+		output.set_document_end(parser.pos as u64);
+		output.transcode_value(value)?;
+	}
+	Ok(())
+}
+
+/// A parser of the value of RON text.
+struct Parser<'t> {
+	text: &'t str,
+	pos: usize,
+}
+
+impl<'t> Parser<'t> {
+	/// Parses the single value of the text, and returns it with the offset where it starts, or
+	/// nothing for text with only whitespace, comments, and attributes.
+	fn document(&mut self) -> Result<Option<(usize, Value<'static>)>, InputError> {
+		self.skip_whitespace()?;
+		while self.rest().starts_with("#![") {
+			let Some(end) = self.rest().find(']') else {
+				return Err(self.error("attribute lacks a closing bracket"));
+			};
+			self.pos += end + 1;
+			self.skip_whitespace()?;
+		}
+		if self.pos == self.text.len() {
+			return Ok(None);
+		}
+		let start = self.pos;
+		let value = self.parse_value(DEPTH_LIMIT)?;
+		self.skip_whitespace()?;
+		if self.pos < self.text.len() {
+			return Err(self.error("input continues after its value"));
+		}
+		Ok(Some((start, value)))
+	}
+
+	fn rest(&self) -> &'t str {
+		&self.text[self.pos..]
+	}
+
+	fn error(&self, message: &'static str) -> InputError {
+		InputError {
+			line: self.text[..self.pos].matches('\n').count() + 1,
+			message,
+		}
+	}
+
+	/// Skips whitespace, line comments, and block comments, which may nest.
+	fn skip_whitespace(&mut self) -> Result<(), InputError> {
+		loop {
+			let rest = self.rest();
+			let trimmed = rest.trim_start();
+			self.pos += rest.len() - trimmed.len();
+			if trimmed.starts_with("//") {
+				self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+			} else if trimmed.starts_with("/*") {
+				let mut depth = 0;
+				loop {
+					let rest = self.rest();
+					if rest.starts_with("/*") {
+						depth += 1;
+					} else if rest.starts_with("*/") {
+						depth -= 1;
+					} else if let Some(c) = rest.chars().next() {
+						self.pos += c.len_utf8();
+						continue;
+					} else {
+						return Err(self.error("block comment lacks a closing */"));
+					}
+					self.pos += 2;
+					if depth == 0 {
+						break;
+					}
+				}
+			} else {
+				return Ok(());
+			}
+		}
+	}
+
+	/// Consumes `c` if it comes next, after any whitespace.
+	fn eat(&mut self, c: char) -> Result<bool, InputError> {
+		self.skip_whitespace()?;
+		let eaten = self.rest().starts_with(c);
+		if eaten {
+			self.pos += 1;
+		}
+		Ok(eaten)
+	}
+
+	fn parse_value(&mut self, depth: usize) -> Result<Value<'static>, InputError> {
+		if depth == 0 {
+			return Err(self.error("nesting is too deep"));
+		}
+		let string = |s: String| Value::String(Cow::Owned(s));
+		let rest = self.rest();
+		let Some(c) = rest.chars().next() else {
+			return Err(self.error("input ends before a value"));
+		};
+		match c {
+			'"' => {
+				self.pos += 1;
+				self.parse_string().map(string)
+			}
+			'\'' => {
+				self.pos += 1;
+				self.parse_char().map(Value::Char)
+			}
+			'r' if rest.starts_with("r\"") || (rest.starts_with("r#") && !is_raw_ident(rest)) => {
+				self.pos += 1;
+				self.parse_raw_string().map(string)
+			}
+			'b' if rest.starts_with("b\"") => {
+				self.pos += 2;
+				self.parse_bytes().map(|b| Value::Bytes(Cow::Owned(b)))
+			}
+			'b' if rest.starts_with("br\"") || rest.starts_with("br#") => {
+				self.pos += 2;
+				let s = self.parse_raw_string()?;
+				Ok(Value::Bytes(Cow::Owned(s.into_bytes())))
+			}
+			'[' => {
+				self.pos += 1;
+				self.parse_elements(']', depth).map(Value::Seq)
+			}
+			'{' => {
+				self.pos += 1;
+				self.parse_map(depth)
+			}
+			'(' => {
+				self.pos += 1;
+				self.parse_group(depth)
+			}
+			'+' | '-' | '0'..='9' | '.' => self.parse_number(),
+			c if c == '_' || c.is_alphabetic() => {
+				let ident = self.ident();
+				match ident {
+					"true" => return Ok(Value::Bool(true)),
+					"false" => return Ok(Value::Bool(false)),
+					"None" => return Ok(Value::Unit),
+					"inf" => return Ok(Value::F64(f64::INFINITY)),
+					"NaN" => return Ok(Value::F64(f64::NAN)),
+					_ => {}
+				}
+				if !self.eat('(')? {
+					return Ok(string(ident.into()));
+				}
+				if ident != "Some" {
+					return self.parse_group(depth);
+				}
+				let value = self.parse_value_within(depth)?;
+				self.eat(',')?;
+				if !self.eat(')')? {
+					return Err(self.error("Some lacks a closing parenthesis"));
+				}
+				Ok(value)
+			}
+			')' | ']' | '}' => Err(self.error("closing delimiter has no opening one")),
+			_ => Err(self.error("unexpected character")),
+		}
+	}
+
+	/// Skips whitespace and parses a value nested one level deeper.
+	fn parse_value_within(&mut self, depth: usize) -> Result<Value<'static>, InputError> {
+		self.skip_whitespace()?;
+		self.parse_value(depth - 1)
+	}
+
+	/// Takes an identifier, without the `r#` of a raw identifier.
+	fn ident(&mut self) -> &'t str {
+		if is_raw_ident(self.rest()) {
+			self.pos += 2;
+		}
+		let rest = self.rest();
+		let len = rest
+			.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+			.unwrap_or(rest.len());
+		self.pos += len;
+		&rest[..len]
+	}
+
+	/// Parses comma-separated elements up to a closing delimiter, which may follow a trailing
+	/// comma.
+	fn parse_elements(
+		&mut self,
+		end: char,
+		depth: usize,
+	) -> Result<Vec<Value<'static>>, InputError> {
+		let mut elements = vec![];
+		loop {
+			if self.eat(end)? {
+				return Ok(elements);
+			}
+			elements.push(self.parse_value_within(depth)?);
+			if !self.eat(',')? && !self.rest().starts_with(end) {
+				return Err(self.error("expected a comma or a closing delimiter"));
+			}
+		}
+	}
+
+	fn parse_map(&mut self, depth: usize) -> Result<Value<'static>, InputError> {
+		let mut entries = vec![];
+		loop {
+			if self.eat('}')? {
+				return Ok(Value::Map(entries));
+			}
+			let key = self.parse_value_within(depth)?;
+			if !self.eat(':')? {
+				return Err(self.error("map key lacks a colon and a value"));
+			}
+			entries.push((key, self.parse_value_within(depth)?));
+			if !self.eat(',')? && !self.rest().starts_with('}') {
+				return Err(self.error("expected a comma or a closing brace"));
+			}
+		}
+	}
+
+	/// Parses the rest of a tuple or a struct after its opening parenthesis.
+	fn parse_group(&mut self, depth: usize) -> Result<Value<'static>, InputError> {
+		if self.eat(')')? {
+			return Ok(Value::Unit);
+		}
+		// A struct's fields each start with a name and a colon, where a tuple's elements can
+		// only start with a name when it's a unit variant or the name of another struct.
+		let start = self.pos;
+		let is_struct = self
+			.rest()
+			.starts_with(|c: char| c == '_' || c.is_alphabetic())
+			&& !self.ident().is_empty()
+			&& self.eat(':')?;
+		self.pos = start;
+		if !is_struct {
+			return self.parse_elements(')', depth).map(Value::Seq);
+		}
+		let mut fields = vec![];
+		loop {
+			if self.eat(')')? {
+				return Ok(Value::Map(fields));
+			}
+			let name = self.ident();
+			if name.is_empty() || !self.eat(':')? {
+				return Err(self.error("struct field lacks a name and a colon"));
+			}
+			let name = Value::String(Cow::Owned(name.into()));
+			fields.push((name, self.parse_value_within(depth)?));
+			if !self.eat(',')? && !self.rest().starts_with(')') {
+				return Err(self.error("expected a comma or a closing parenthesis"));
+			}
+		}
+	}
+
+	fn parse_number(&mut self) -> Result<Value<'static>, InputError> {
+		let rest = self.rest();
+		let unsigned = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+		let sign = &rest[..rest.len() - unsigned.len()];
+		for (name, n) in [("inf", f64::INFINITY), ("NaN", f64::NAN)] {
+			if unsigned.starts_with(name) {
+				self.pos += sign.len() + name.len();
+				return Ok(Value::F64(if sign == "-" { -n } else { n }));
+			}
+		}
+		let radix = match unsigned.get(..2) {
+			Some("0x") => 16,
+			Some("0o") => 8,
+			Some("0b") => 2,
+			_ => 10,
+		};
+		let mut prev = '\0';
+		let len = unsigned
+			.find(|c: char| {
+				let exponent = radix == 10 && matches!(c, '+' | '-') && matches!(prev, 'e' | 'E');
+				prev = c;
+				!(c.is_ascii_alphanumeric() || c == '_' || c == '.' || exponent)
+			})
+			.unwrap_or(unsigned.len());
+		self.pos += sign.len() + len;
+		let token = &unsigned[..len];
+		parse_number(sign == "-", token, radix).ok_or(self.error("invalid number"))
+	}
+
+	/// Parses the rest of a string after its opening quote.
+	fn parse_string(&mut self) -> Result<String, InputError> {
+		let mut s = String::new();
+		let mut chars = self.rest().char_indices();
+		loop {
+			let Some((i, c)) = chars.next() else {
+				return Err(self.error("string lacks a closing quote"));
+			};
+			match c {
+				'"' => {
+					self.pos += i + 1;
+					return Ok(s);
+				}
+				'\\' => s.push(self.parse_escape(&mut chars)?),
+				c => s.push(c),
+			}
+		}
+	}
+
+	/// Parses the rest of a byte string after its opening quote.
+	fn parse_bytes(&mut self) -> Result<Vec<u8>, InputError> {
+		let mut b = vec![];
+		let mut chars = self.rest().char_indices();
+		loop {
+			let Some((i, c)) = chars.next() else {
+				return Err(self.error("byte string lacks a closing quote"));
+			};
+			let c = match c {
+				'"' => {
+					self.pos += i + 1;
+					return Ok(b);
+				}
+				'\\' if chars.clone().next().is_some_and(|(_, c)| c == 'x') => {
+					chars.next();
+					let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+					let byte = u8::from_str_radix(&hex, 16)
+						.ok()
+						.filter(|_| hex.len() == 2)
+						.ok_or(self.error("invalid byte escape in a byte string"))?;
+					b.push(byte);
+					continue;
+				}
+				'\\' => self.parse_escape(&mut chars)?,
+				c => c,
+			};
+			b.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+		}
+	}
+
+	/// Parses the rest of a raw string after its `r`, like `#"text"#`.
+	fn parse_raw_string(&mut self) -> Result<String, InputError> {
+		let rest = self.rest();
+		let hashes = rest.len() - rest.trim_start_matches('#').len();
+		let Some(body) = rest[hashes..].strip_prefix('"') else {
+			return Err(self.error("raw string lacks an opening quote"));
+		};
+		let close = format!("\"{}", "#".repeat(hashes));
+		let Some(len) = body.find(&close) else {
+			return Err(self.error("raw string lacks a closing quote"));
+		};
+		self.pos += hashes + 1 + len + close.len();
+		Ok(body[..len].into())
+	}
+
+	/// Parses the rest of a character literal after its opening quote.
+	fn parse_char(&mut self) -> Result<char, InputError> {
+		let mut chars = self.rest().char_indices();
+		let c = match chars.next() {
+			Some((_, '\\')) => self.parse_escape(&mut chars)?,
+			Some((_, c)) if c != '\'' => c,
+			_ => return Err(self.error("invalid character")),
+		};
+		match chars.next() {
+			Some((i, '\'')) => {
+				self.pos += i + 1;
+				Ok(c)
+			}
+			_ => Err(self.error("character lacks a closing quote")),
+		}
+	}
+
+	/// Parses an escape sequence after its backslash.
+	fn parse_escape(&self, chars: &mut str::CharIndices) -> Result<char, InputError> {
+		Ok(match chars.next().map(|(_, c)| c) {
+			Some('t') => '\t',
+			Some('r') => '\r',
+			Some('n') => '\n',
+			Some('0') => '\0',
+			Some('b') => '\u{8}',
+			Some('f') => '\u{c}',
+			Some(c @ ('\\' | '"' | '\'' | '/')) => c,
+			Some('x') => {
+				let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+				u8::from_str_radix(&hex, 16)
+					.ok()
+					.filter(|&b| hex.len() == 2 && b.is_ascii())
+					.map(char::from)
+					.ok_or(self.error("invalid ASCII escape"))?
+			}
+			Some('u') => {
+				let braced = chars.clone().next().is_some_and(|(_, c)| c == '{');
+				let hex: String = if braced {
+					chars.next();
+					let hex = chars.by_ref().map(|(_, c)| c).take_while(|&c| c != '}');
+					hex.collect()
+				} else {
+					chars.by_ref().take(4).map(|(_, c)| c).collect()
+				};
+				u32::from_str_radix(&hex, 16)
+					.ok()
+					.filter(|_| braced || hex.len() == 4)
+					.and_then(char::from_u32)
+					.ok_or(self.error("invalid unicode escape"))?
+			}
+			_ => return Err(self.error("invalid escape")),
+		})
+	}
+}
+
+/// Returns true if text starts with a raw identifier like `r#type`, rather than a raw string.
+fn is_raw_ident(text: &str) -> bool {
+	text.strip_prefix("r#")
+		.is_some_and(|rest| rest.starts_with(|c: char| c == '_' || c.is_alphabetic()))
+}
+
+/// Parses the text of a number without its sign, ignoring any type suffix like `u8`.
+fn parse_number(negative: bool, token: &str, radix: u32) -> Option<Value<'static>> {
+	const SUFFIXES: &[&str] = &[
+		"i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+		"f32", "f64",
+	];
+	let digits = match radix {
+		16 => token.strip_prefix("0x")?,
+		8 => token.strip_prefix("0o")?,
+		2 => token.strip_prefix("0b")?,
+		_ => token,
+	};
+	let digits = SUFFIXES
+		.iter()
+		// The `f` of a float suffix would be a digit of a hexadecimal integer.
+		.filter(|suffix| radix != 16 || !suffix.starts_with('f'))
+		.find_map(|suffix| digits.strip_suffix(suffix))
+		.unwrap_or(digits);
+	let digits = format!(
+		"{}{}",
+		if negative { "-" } else { "" },
+		digits.replace('_', "")
+	);
+	if radix == 10 && digits.contains(['.', 'e', 'E']) {
+		return digits.parse().ok().map(Value::F64);
+	}
+	if let Ok(n) = i64::from_str_radix(&digits, radix) {
+		return Some(Value::I64(n));
+	}
+	if let Ok(n) = u64::from_str_radix(&digits, radix) {
+		return Some(Value::U64(n));
+	}
+	i128::from_str_radix(&digits, radix).ok().map(Value::I128)
+}
+
+/// The error for input that isn't valid RON.
+#[derive(Debug)]
+pub(crate) struct InputError {
+	line: usize,
+	message: &'static str,
+}
+
+impl error::Error for InputError {}
+
+impl fmt::Display for InputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "invalid RON on line {}: {}", self.line, self.message)
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+	used: bool,
+}
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W) -> Output<W> {
+		Output { w, used: false }
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	fn output_document(&mut self, value: &Value) -> crate::Result<()> {
+		// Like TOML, RON holds a single value with no way to separate documents.
+		if self.used {
+			return Err(OutputError::MultiDocument.into());
+		}
+		let mut text = String::new();
+		write_value(&mut text, value, 0);
+		text.push('\n');
+		self.w.write_all(text.as_bytes())?;
+		self.used = true;
+		Ok(())
+	}
+}
+
+/// Writes the RON text of a value at a level of indentation.
+fn write_value(text: &mut String, value: &Value, level: usize) {
+	let _ = match value {
+		Value::Unit => text.write_str("()"),
+		Value::Bool(b) => write!(text, "{b}"),
+		Value::I8(n) => write!(text, "{n}"),
+		Value::I16(n) => write!(text, "{n}"),
+		Value::I32(n) => write!(text, "{n}"),
+		Value::I64(n) => write!(text, "{n}"),
+		Value::I128(n) => write!(text, "{n}"),
+		Value::U8(n) => write!(text, "{n}"),
+		Value::U16(n) => write!(text, "{n}"),
+		Value::U32(n) => write!(text, "{n}"),
+		Value::U64(n) => write!(text, "{n}"),
+		Value::U128(n) => write!(text, "{n}"),
+		Value::F32(n) => write_float(text, f64::from(*n)),
+		Value::F64(n) => write_float(text, *n),
+		Value::Char('\'') => text.write_str(r"'\''"),
+		Value::Char(c) => {
+			text.push('\'');
+			write_escaped(text, *c);
+			text.write_str("'")
+		}
+		Value::String(s) => {
+			text.push('"');
+			s.chars().for_each(|c| write_escaped(text, c));
+			text.write_str("\"")
+		}
+		Value::Bytes(b) => {
+			text.push_str("b\"");
+			for &b in b.iter() {
+				match b {
+					b'"' | b'\\' => write_escaped(text, char::from(b)),
+					b' '..=b'~' => text.push(char::from(b)),
+					b => {
+						let _ = write!(text, "\\x{b:02x}");
+					}
+				}
+			}
+			text.write_str("\"")
+		}
+		Value::Seq(seq) => {
+			write_collection(text, ['[', ']'], seq, level, |text, v| {
+				write_value(text, v, level + 1);
+			});
+			Ok(())
+		}
+		Value::Map(map) => {
+			write_collection(text, ['{', '}'], map, level, |text, (k, v)| {
+				write_value(text, k, level + 1);
+				text.push_str(": ");
+				write_value(text, v, level + 1);
+			});
+			Ok(())
+		}
+	};
+}
+
+/// Writes a collection with each of its items on its own line, followed by a comma, as the ron
+/// crate's pretty printer does.
+fn write_collection<T>(
+	text: &mut String,
+	[open, close]: [char; 2],
+	items: &[T],
+	level: usize,
+	mut write_item: impl FnMut(&mut String, &T),
+) {
+	text.push(open);
+	if !items.is_empty() {
+		text.push('\n');
+		for item in items {
+			text.push_str(&INDENT.repeat(level + 1));
+			write_item(text, item);
+			text.push_str(",\n");
+		}
+		text.push_str(&INDENT.repeat(level));
+	}
+	text.push(close);
+}
+
+fn write_float(text: &mut String, n: f64) -> fmt::Result {
+	match n {
+		_ if n.is_nan() => text.write_str("NaN"),
+		f64::INFINITY => text.write_str("inf"),
+		f64::NEG_INFINITY => text.write_str("-inf"),
+		// Debug formatting always includes a decimal point or an exponent, which keeps the value
+		// a float when read back.
+		n => write!(text, "{n:?}"),
+	}
+}
+
+/// Writes a character of a string or character literal, escaping it if it needs to be.
+fn write_escaped(text: &mut String, c: char) {
+	match c {
+		'"' => text.push_str("\\\""),
+		'\\' => text.push_str("\\\\"),
+		'\n' => text.push_str("\\n"),
+		'\r' => text.push_str("\\r"),
+		'\t' => text.push_str("\\t"),
+		c if c.is_control() => {
+			let _ = write!(text, "\\u{{{:x}}}", u32::from(c));
+		}
+		c => text.push(c),
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.used {
+			return Err(OutputError::MultiDocument.into());
+		}
+		let value = Value::deserialize(de)?;
+		self.output_document(&value)
+	}
+
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		self.output_document(&value)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.flush()
+	}
+}
+
+/// The error for output that RON can't represent.
+#[derive(Debug)]
+pub(crate) enum OutputError {
+	MultiDocument,
+}
+
+impl error::Error for OutputError {}
+
+impl fmt::Display for OutputError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::MultiDocument => f.write_str("RON does not support multi-document output"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(text: &str) -> Result<Option<Value<'static>>, InputError> {
+		let mut parser = Parser { text, pos: 0 };
+		Ok(parser.document()?.map(|(_, value)| value))
+	}
+
+	#[test]
+	fn parse_and_write_values() {
+		const INPUT: &str = r##"
+#![enable(implicit_some)]
+// a comment
+Config( /* a /* nested */ comment */
+	name: "xt\u{21}",
+	r#type: Some(Red),
+	point: Point(1, -0x10, 1_000u16),
+	ratio: 1.5e0,
+	limits: { "low": None, 'c': (), },
+	raw: r#"a "quoted" word"#,
+	bytes: b"\x00ab",
+	inf: -inf,
+)"##;
+		let value = parse(INPUT).unwrap().unwrap();
+		let mut text = String::new();
+		write_value(&mut text, &value, 0);
+		assert_eq!(
+			text,
+			r#"{
+    "name": "xt!",
+    "type": "Red",
+    "point": [
+        1,
+        -16,
+        1000,
+    ],
+    "ratio": 1.5,
+    "limits": {
+        "low": (),
+        'c': (),
+    },
+    "raw": "a \"quoted\" word",
+    "bytes": b"\x00ab",
+    "inf": -inf,
+}"#
+		);
+		assert!(matches!(parse("  // nothing\n"), Ok(None)));
+
+		for (input, line) in [
+			("(a: 1", 1),
+			("[1\n2]", 2),
+			("{1 2}", 1),
+			("1\n2", 2),
+			("\"\\q\"", 1),
+			("/* open", 1),
+			("0x", 1),
+		] {
+			let Err(err) = parse(input) else {
+				panic!("parsed invalid RON {input:?}");
+			};
+			assert_eq!(err.line, line, "{input:?}");
+		}
+	}
+
+	#[test]
+	fn numbers() {
+		for (input, expected) in [
+			("0b101", Value::I64(5)),
+			("0o17", Value::I64(15)),
+			("-0xff", Value::I64(-255)),
+			("18446744073709551615", Value::U64(u64::MAX)),
+			("-1e3", Value::F64(-1000.0)),
+			("2.5f32", Value::F64(2.5)),
+		] {
+			assert!(parse(input).unwrap() == Some(expected), "{input}");
+		}
+	}
+}
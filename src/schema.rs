@@ -0,0 +1,392 @@
+//! Inference of a JSON Schema describing every document in a stream.
+//!
+//! Inference folds each document into a [`Shape`] recording every type of value observed at each
+//! position, recursing through the properties of maps and the elements of arrays. Properties
+//! present in every map observed at a position become required. Strings at a position with only a
+//! few distinct values, at least one of which repeats, become enums.
+//!
+//! A translator that infers a schema must hold the shape in memory until it finishes the stream,
+//! and only then passes the schema on to the output as a single document. The shape grows with
+//! the variety of the input's structure rather than its size. Counting the types of values at
+//! each path in [`stats`] holds its counts the same way, in place of a shape.
+
+use std::borrow::Cow;
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::transcode::Value;
+
+#[cfg(feature = "rust-types")]
+mod rust;
+mod stats;
+
+/// The JSON Schema dialect of inferred schemas.
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The maximum number of distinct strings at a single position that can form an enum.
+const ENUM_LIMIT: usize = 10;
+
+/// The types of values observed at a single position in the documents of a stream.
+#[derive(Default)]
+pub(crate) struct Shape {
+	null: bool,
+	boolean: bool,
+	integer: bool,
+	number: bool,
+	string: Option<Strings>,
+	array: Option<Box<Shape>>,
+	object: Option<Object>,
+}
+
+/// The strings observed at a single position.
+#[derive(Default)]
+struct Strings {
+	count: u64,
+	/// The distinct strings observed, or `None` after there were too many to form an enum.
+	distinct: Option<Vec<String>>,
+}
+
+/// The maps observed at a single position.
+#[derive(Default)]
+struct Object {
+	count: u64,
+	/// The shape of each property, along with the number of maps containing it.
+	properties: Vec<(String, u64, Shape)>,
+}
+
+impl Shape {
+	/// Folds a value into the shape.
+	fn add(&mut self, value: &Value) {
+		match value {
+			Value::Unit => self.null = true,
+			Value::Bool(_) => self.boolean = true,
+			Value::I8(_)
+			| Value::I16(_)
+			| Value::I32(_)
+			| Value::I64(_)
+			| Value::I128(_)
+			| Value::U8(_)
+			| Value::U16(_)
+			| Value::U32(_)
+			| Value::U64(_)
+			| Value::U128(_) => self.integer = true,
+			Value::F32(_) | Value::F64(_) => self.number = true,
+			Value::Char(c) => self.add_string(&c.to_string()),
+			Value::String(s) => self.add_string(s),
+			// JSON Schema has no notion of binary data, and most text formats that xt outputs
+			// represent it as an array of integers.
+			Value::Bytes(_) => {
+				let items = self.array.get_or_insert_default();
+				items.integer = true;
+			}
+			Value::Seq(seq) => {
+				let items = self.array.get_or_insert_default();
+				for v in seq {
+					items.add(v);
+				}
+			}
+			Value::Map(map) => {
+				let object = self.object.get_or_insert_default();
+				object.count += 1;
+				for (k, v) in map {
+					let Some(key) = key_string(k) else {
+						continue;
+					};
+					let i = match object.properties.iter().position(|(k, ..)| *k == key) {
+						Some(i) => i,
+						None => {
+							object
+								.properties
+								.push((key.into_owned(), 0, Shape::default()));
+							object.properties.len() - 1
+						}
+					};
+					let (_, count, shape) = &mut object.properties[i];
+					*count += 1;
+					shape.add(v);
+				}
+			}
+		}
+	}
+
+	fn add_string(&mut self, s: &str) {
+		let strings = self.string.get_or_insert_with(|| Strings {
+			count: 0,
+			distinct: Some(vec![]),
+		});
+		strings.count += 1;
+		if let Some(distinct) = &mut strings.distinct {
+			if !distinct.iter().any(|d| d == s) {
+				if distinct.len() < ENUM_LIMIT {
+					distinct.push(s.into());
+				} else {
+					strings.distinct = None;
+				}
+			}
+		}
+	}
+
+	/// Returns the schema describing the shape.
+	fn to_schema(&self) -> Vec<(Value<'static>, Value<'static>)> {
+		let mut schema = vec![];
+		let mut types = vec![];
+		if self.null {
+			types.push("null");
+		}
+		if self.boolean {
+			types.push("boolean");
+		}
+		// Every integer is a number, so "integer" alone only applies without any floats.
+		if self.number {
+			types.push("number");
+		} else if self.integer {
+			types.push("integer");
+		}
+		if self.string.is_some() {
+			types.push("string");
+		}
+		if self.array.is_some() {
+			types.push("array");
+		}
+		if self.object.is_some() {
+			types.push("object");
+		}
+		match types[..] {
+			[] => {}
+			[ty] => schema.push((string("type"), string(ty))),
+			_ => schema.push((
+				string("type"),
+				Value::Seq(types.into_iter().map(string).collect()),
+			)),
+		}
+
+		if let Some(Strings {
+			count,
+			distinct: Some(distinct),
+		}) = &self.string
+		{
+			if *count > distinct.len() as u64 {
+				let values = distinct.iter().map(|s| string(s)).collect();
+				schema.push((string("enum"), Value::Seq(values)));
+			}
+		}
+		if let Some(items) = &self.array {
+			let items = items.to_schema();
+			if !items.is_empty() {
+				schema.push((string("items"), Value::Map(items)));
+			}
+		}
+		if let Some(object) = &self.object {
+			let properties = object
+				.properties
+				.iter()
+				.map(|(k, _, shape)| (string(k), Value::Map(shape.to_schema())))
+				.collect();
+			schema.push((string("properties"), Value::Map(properties)));
+			let required: Vec<_> = object
+				.properties
+				.iter()
+				.filter(|(_, count, _)| *count == object.count)
+				.map(|(k, ..)| string(k))
+				.collect();
+			if !required.is_empty() {
+				schema.push((string("required"), Value::Seq(required)));
+			}
+		}
+		schema
+	}
+}
+
+/// Returns a map key as a string, or `None` for keys without a natural string form.
+fn key_string<'k>(key: &'k Value) -> Option<Cow<'k, str>> {
+	Some(match key {
+		Value::String(s) => Cow::Borrowed(s),
+		Value::Char(c) => Cow::Owned(c.to_string()),
+		Value::Bool(b) => Cow::Owned(b.to_string()),
+		Value::I8(n) => Cow::Owned(n.to_string()),
+		Value::I16(n) => Cow::Owned(n.to_string()),
+		Value::I32(n) => Cow::Owned(n.to_string()),
+		Value::I64(n) => Cow::Owned(n.to_string()),
+		Value::I128(n) => Cow::Owned(n.to_string()),
+		Value::U8(n) => Cow::Owned(n.to_string()),
+		Value::U16(n) => Cow::Owned(n.to_string()),
+		Value::U32(n) => Cow::Owned(n.to_string()),
+		Value::U64(n) => Cow::Owned(n.to_string()),
+		Value::U128(n) => Cow::Owned(n.to_string()),
+		_ => return None,
+	})
+}
+
+fn string(s: &str) -> Value<'static> {
+	Value::String(Cow::Owned(s.into()))
+}
+
+/// The kind of description that inference produces.
+pub(crate) enum Target {
+	/// A JSON Schema, output as a document.
+	JsonSchema,
+	/// Rust type definitions, output as source text.
+	#[cfg(feature = "rust-types")]
+	RustTypes,
+	/// Counts of the types of values at each path, output as a document.
+	TypeStats,
+}
+
+/// The state of schema inference across all documents in a stream.
+pub(crate) struct Inference {
+	target: Target,
+	shape: Option<Shape>,
+	stats: stats::TypeStats,
+}
+
+impl Inference {
+	pub(crate) fn new(target: Target) -> Inference {
+		Inference {
+			target,
+			shape: None,
+			stats: stats::TypeStats::default(),
+		}
+	}
+
+	/// Takes the schema inferred from the documents so far, if there were any.
+	fn take_schema(&mut self) -> Option<Value<'static>> {
+		let shape = self.shape.take()?;
+		let mut schema = vec![(string("$schema"), string(DIALECT))];
+		schema.extend(shape.to_schema());
+		Some(Value::Map(schema))
+	}
+}
+
+/// An [`Output`](crate::Output) that infers a schema from every document instead of passing it
+/// through, when inference is enabled.
+pub(crate) struct Output<'s, O> {
+	output: O,
+	inference: Option<&'s mut Inference>,
+}
+
+impl<'s, O> Output<'s, O> {
+	pub(crate) fn new(output: O, inference: Option<&'s mut Inference>) -> Output<'s, O> {
+		Output { output, inference }
+	}
+
+	/// Passes the schema inferred from the stream to the output, if there were any documents.
+	///
+	/// Targets that produce source text rather than a document return the text instead, for the
+	/// caller to write directly to the underlying writer.
+	pub(crate) fn finish<'i>(mut self) -> crate::Result<Option<String>>
+	where
+		O: crate::Output<'i>,
+	{
+		let Some(inference) = self.inference else {
+			return Ok(None);
+		};
+		match inference.target {
+			Target::JsonSchema => {
+				if let Some(schema) = inference.take_schema() {
+					self.output.transcode_value(schema)?;
+				}
+				Ok(None)
+			}
+			#[cfg(feature = "rust-types")]
+			Target::RustTypes => Ok(inference.shape.take().map(|shape| rust::generate(&shape))),
+			Target::TypeStats => {
+				if let Some(stats) = inference.stats.take() {
+					self.output.transcode_value(stats)?;
+				}
+				Ok(None)
+			}
+		}
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		match &mut self.inference {
+			Some(_) => self.transcode_value(Value::deserialize(de)?),
+			None => self.output.transcode_from(de),
+		}
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		match &mut self.inference {
+			Some(inference) => {
+				match inference.target {
+					Target::TypeStats => inference.stats.add(&value),
+					_ => inference.shape.get_or_insert_default().add(&value),
+				}
+				Ok(())
+			}
+			None => self.output.transcode_value(value),
+		}
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		match &mut self.inference {
+			Some(_) => self.transcode_value(value),
+			None => self.output.transcode_borrowed_value(value),
+		}
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn infer_json(docs: &[&str]) -> String {
+		let mut inference = Inference::new(Target::JsonSchema);
+		for doc in docs {
+			let value = Value::deserialize(&mut serde_json::Deserializer::from_str(doc)).unwrap();
+			inference.shape.get_or_insert_default().add(&value);
+		}
+		serde_json::to_string(&inference.take_schema().unwrap()).unwrap()
+	}
+
+	#[test]
+	fn infer_types_optionality_and_enums() {
+		let schema = infer_json(&[
+			r#"{"id":1,"kind":"a","tags":["x"],"size":null}"#,
+			r#"{"id":2,"kind":"b","size":1.5}"#,
+			r#"{"id":3,"kind":"a","tags":[]}"#,
+		]);
+		assert_eq!(
+			schema,
+			concat!(
+				r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","type":"object","#,
+				r#""properties":{"id":{"type":"integer"},"kind":{"type":"string","enum":["a","b"]},"#,
+				r#""tags":{"type":"array","items":{"type":"string"}},"size":{"type":["null","number"]}},"#,
+				r#""required":["id","kind"]}"#,
+			)
+		);
+	}
+
+	#[test]
+	fn infer_without_enums() {
+		let distinct = infer_json(&[r#""a""#, r#""b""#]);
+		assert!(!distinct.contains("enum"), "{distinct}");
+
+		let docs: Vec<_> = (0..=ENUM_LIMIT)
+			.flat_map(|i| [i, i])
+			.map(|i| format!("\"{i}\""))
+			.collect();
+		let docs: Vec<_> = docs.iter().map(String::as_str).collect();
+		let many = infer_json(&docs);
+		assert!(!many.contains("enum"), "{many}");
+	}
+}
@@ -0,0 +1,283 @@
+//! Generation of Rust type definitions from an inferred [`Shape`].
+//!
+//! Every map shape becomes a struct deriving Serde's `Serialize` and `Deserialize`, named after
+//! the property that contains it, with a field for each property. Properties missing from some
+//! maps, or with null values, become `Option`s. Values with more than one type (other than
+//! integers mixed with floats, which become `f64`), or with no observed type at all, fall back to
+//! `serde_json::Value`.
+
+use std::fmt::Write as _;
+use std::mem;
+
+use super::{Object, Shape};
+
+/// The name of the type describing the root of every document.
+const ROOT: &str = "Root";
+
+/// Rust keywords, which need raw identifier syntax as field names.
+const KEYWORDS: &[&str] = &[
+	"abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+	"else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+	"loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+	"static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+	"virtual", "where", "while", "yield",
+];
+
+/// Keywords that can't be raw identifiers, and must be renamed instead.
+const RESERVED: &[&str] = &["crate", "self", "super"];
+
+/// Returns Rust source code defining types for documents with the given shape.
+pub(super) fn generate(shape: &Shape) -> String {
+	let mut generator = Generator {
+		names: vec![],
+		structs: vec![],
+	};
+	// Roots other than maps get a type alias, which must not collide with any struct.
+	let root_is_struct = shape.object.is_some() && shape.is_single_type();
+	if !root_is_struct {
+		generator.names.push(ROOT.into());
+	}
+	let root = generator.type_of(shape, ROOT);
+
+	let mut source = String::from("use serde::{Deserialize, Serialize};\n");
+	if !root_is_struct {
+		let _ = write!(source, "\npub type {ROOT} = {root};\n");
+	}
+	for def in generator.structs {
+		source.push('\n');
+		source.push_str(&def);
+	}
+	source
+}
+
+struct Generator {
+	/// The names of every struct generated so far.
+	names: Vec<String>,
+	/// The definitions of every struct generated so far.
+	structs: Vec<String>,
+}
+
+impl Generator {
+	/// Returns the Rust type for values of the given shape, generating any structs that it needs
+	/// based on `name`.
+	fn type_of(&mut self, shape: &Shape, name: &str) -> String {
+		let ty = if !shape.is_single_type() {
+			"serde_json::Value".into()
+		} else if let Some(object) = &shape.object {
+			self.define_struct(object, name)
+		} else if let Some(items) = &shape.array {
+			format!("Vec<{}>", self.type_of(items, &format!("{name}Item")))
+		} else if shape.string.is_some() {
+			"String".into()
+		} else if shape.number {
+			"f64".into()
+		} else if shape.integer {
+			"i64".into()
+		} else if shape.boolean {
+			"bool".into()
+		} else {
+			"serde_json::Value".into()
+		};
+		if shape.null && shape.is_single_type() && ty != "serde_json::Value" {
+			format!("Option<{ty}>")
+		} else {
+			ty
+		}
+	}
+
+	/// Generates a struct for maps of the given shape, returning its name.
+	fn define_struct(&mut self, object: &Object, name: &str) -> String {
+		let name = self.unique_name(name);
+		self.names.push(name.clone());
+		// Reserve a spot before generating nested structs, so that outer structs come first.
+		let index = self.structs.len();
+		self.structs.push(String::new());
+
+		let mut def = String::from("#[derive(Debug, Clone, Serialize, Deserialize)]\n");
+		let _ = writeln!(def, "pub struct {name} {{");
+		let mut fields: Vec<String> = vec![];
+		for (key, count, shape) in &object.properties {
+			let field = unique(field_name(key), |f| fields.contains(f));
+			let mut ty = self.type_of(shape, &pascal_case(key));
+			if *count < object.count && !ty.starts_with("Option<") {
+				ty = format!("Option<{ty}>");
+			}
+			if field.trim_start_matches("r#") != key {
+				let _ = writeln!(def, "    #[serde(rename = {key:?})]");
+			}
+			let _ = writeln!(def, "    pub {field}: {ty},");
+			fields.push(field);
+		}
+		def.push_str("}\n");
+		self.structs[index] = def;
+		name
+	}
+
+	fn unique_name(&self, name: &str) -> String {
+		unique(name.into(), |n| self.names.contains(n))
+	}
+}
+
+impl Shape {
+	/// Returns true if the shape has at most one type other than null, counting integers and
+	/// floats as one numeric type.
+	fn is_single_type(&self) -> bool {
+		let types = [
+			self.boolean,
+			self.integer || self.number,
+			self.string.is_some(),
+			self.array.is_some(),
+			self.object.is_some(),
+		];
+		types.into_iter().filter(|&t| t).count() <= 1
+	}
+}
+
+/// Returns `name`, or `name` with the smallest numeric suffix that makes it unique.
+fn unique(name: String, taken: impl Fn(&String) -> bool) -> String {
+	if !taken(&name) {
+		return name;
+	}
+	(2..)
+		.map(|i| format!("{name}{i}"))
+		.find(|n| !taken(n))
+		.expect("some numeric suffix should be unused")
+}
+
+/// Splits a key into its words, at non-alphanumeric characters and lowercase-to-uppercase
+/// transitions.
+fn words(key: &str) -> Vec<String> {
+	let mut words = vec![];
+	let mut word = String::new();
+	let mut prev_lower = false;
+	for c in key.chars() {
+		if !c.is_alphanumeric() {
+			words.extend((!word.is_empty()).then(|| mem::take(&mut word)));
+			prev_lower = false;
+			continue;
+		}
+		if c.is_uppercase() && prev_lower {
+			words.push(mem::take(&mut word));
+		}
+		prev_lower = c.is_lowercase() || c.is_numeric();
+		word.push(c);
+	}
+	words.extend((!word.is_empty()).then_some(word));
+	words
+}
+
+fn pascal_case(key: &str) -> String {
+	let name: String = words(key)
+		.iter()
+		.flat_map(|word| {
+			let mut chars = word.chars();
+			let first = chars.next().into_iter().flat_map(char::to_uppercase);
+			first.chain(chars.flat_map(char::to_lowercase))
+		})
+		.collect();
+	match name.chars().next() {
+		None => "Value".into(),
+		Some(c) if c.is_numeric() => format!("Value{name}"),
+		Some(_) if name == "Self" => "SelfValue".into(),
+		Some(_) => name,
+	}
+}
+
+fn field_name(key: &str) -> String {
+	let name = words(key)
+		.iter()
+		.map(|word| word.to_lowercase())
+		.collect::<Vec<_>>()
+		.join("_");
+	match name.chars().next() {
+		None => "field".into(),
+		Some(c) if c.is_numeric() => format!("field_{name}"),
+		Some(_) if KEYWORDS.contains(&name.as_str()) => format!("r#{name}"),
+		Some(_) if RESERVED.contains(&name.as_str()) => format!("{name}_"),
+		Some(_) => name,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serde::Deserialize;
+
+	use crate::transcode::Value;
+
+	fn generate_json(docs: &[&str]) -> String {
+		let mut shape = Shape::default();
+		for doc in docs {
+			shape.add(&Value::deserialize(&mut serde_json::Deserializer::from_str(doc)).unwrap());
+		}
+		generate(&shape)
+	}
+
+	#[test]
+	fn generate_structs() {
+		let source = generate_json(&[
+			r#"{"apiVersion":"v1","type":"a","spec":{"replicas":1,"ratio":1},"items":[{"id":1}]}"#,
+			r#"{"apiVersion":"v1","type":null,"spec":{"replicas":2,"ratio":0.5,"x":[]}}"#,
+		]);
+		assert_eq!(
+			source,
+			concat!(
+				"use serde::{Deserialize, Serialize};\n",
+				"\n",
+				"#[derive(Debug, Clone, Serialize, Deserialize)]\n",
+				"pub struct Root {\n",
+				"    #[serde(rename = \"apiVersion\")]\n",
+				"    pub api_version: String,\n",
+				"    pub r#type: Option<String>,\n",
+				"    pub spec: Spec,\n",
+				"    pub items: Option<Vec<ItemsItem>>,\n",
+				"}\n",
+				"\n",
+				"#[derive(Debug, Clone, Serialize, Deserialize)]\n",
+				"pub struct Spec {\n",
+				"    pub replicas: i64,\n",
+				"    pub ratio: f64,\n",
+				"    pub x: Option<Vec<serde_json::Value>>,\n",
+				"}\n",
+				"\n",
+				"#[derive(Debug, Clone, Serialize, Deserialize)]\n",
+				"pub struct ItemsItem {\n",
+				"    pub id: i64,\n",
+				"}\n",
+			)
+		);
+	}
+
+	#[test]
+	fn generate_type_alias_for_non_maps() {
+		assert_eq!(
+			generate_json(&["[1]", "[2.5]"]),
+			"use serde::{Deserialize, Serialize};\n\npub type Root = Vec<f64>;\n"
+		);
+		assert_eq!(
+			generate_json(&[r#"{"a":1}"#, "1"]),
+			"use serde::{Deserialize, Serialize};\n\npub type Root = serde_json::Value;\n"
+		);
+	}
+
+	#[test]
+	fn identifiers_from_keys() {
+		const CASES: &[(&str, &str, &str)] = &[
+			("apiVersion", "api_version", "ApiVersion"),
+			(
+				"app.kubernetes.io/name",
+				"app_kubernetes_io_name",
+				"AppKubernetesIoName",
+			),
+			("self", "self_", "SelfValue"),
+			("3d", "field_3d", "Value3d"),
+			("", "field", "Value"),
+			("HTTPServer", "httpserver", "Httpserver"),
+		];
+		for &(key, field, ty) in CASES {
+			assert_eq!(field_name(key), field, "{key}");
+			assert_eq!(pascal_case(key), ty, "{key}");
+		}
+	}
+}
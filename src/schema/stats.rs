@@ -0,0 +1,138 @@
+//! Counts of the types of values observed at each path in the documents of a stream.
+//!
+//! Paths use the same syntax as the paths of [`select`](crate::Transform::select) expressions,
+//! except that `[]` stands for every element of an array, so that the elements of every array at
+//! a position count together. The root of each document is at the path `.`, and the elements of
+//! a root array at `[]`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::transcode::Value;
+
+/// The names of the types that the counts distinguish, in the order of their counts.
+const TYPES: [&str; 8] = [
+	"null", "bool", "int", "float", "string", "bytes", "array", "map",
+];
+
+/// The number of values of each type observed at every path, in the order of first observation.
+#[derive(Default)]
+pub(super) struct TypeStats {
+	paths: Vec<(String, [u64; TYPES.len()])>,
+	positions: HashMap<String, usize>,
+}
+
+impl TypeStats {
+	/// Counts the type of every value in a document.
+	pub(super) fn add(&mut self, value: &Value) {
+		self.add_at(&mut String::new(), value);
+	}
+
+	fn add_at(&mut self, path: &mut String, value: &Value) {
+		let counts = match self.positions.get(path.as_str()) {
+			Some(&i) => &mut self.paths[i].1,
+			None => {
+				let name = if path.is_empty() { "." } else { path.as_str() };
+				self.positions.insert(path.clone(), self.paths.len());
+				self.paths.push((name.into(), [0; TYPES.len()]));
+				&mut self.paths.last_mut().expect("a path was just pushed").1
+			}
+		};
+		counts[type_index(value)] += 1;
+
+		let len = path.len();
+		match value {
+			Value::Seq(seq) => {
+				path.push_str("[]");
+				for v in seq {
+					self.add_at(path, v);
+				}
+			}
+			Value::Map(map) => {
+				for (k, v) in map {
+					crate::merge::push_key(path, k);
+					self.add_at(path, v);
+					path.truncate(len);
+				}
+			}
+			_ => {}
+		}
+		path.truncate(len);
+	}
+
+	/// Takes the counts for the documents so far as a map from each path to the count of each
+	/// type observed there, if there were any documents.
+	pub(super) fn take(&mut self) -> Option<Value<'static>> {
+		self.positions.clear();
+		if self.paths.is_empty() {
+			return None;
+		}
+		let string = |s: &str| Value::String(Cow::Owned(s.into()));
+		let paths = self.paths.drain(..).map(|(path, counts)| {
+			let counts = TYPES
+				.iter()
+				.zip(counts)
+				.filter(|&(_, count)| count > 0)
+				.map(|(name, count)| (string(name), Value::U64(count)));
+			(
+				Value::String(Cow::Owned(path)),
+				Value::Map(counts.collect()),
+			)
+		});
+		Some(Value::Map(paths.collect()))
+	}
+}
+
+/// Returns the position of a value's type in [`TYPES`].
+fn type_index(value: &Value) -> usize {
+	match value {
+		Value::Unit => 0,
+		Value::Bool(_) => 1,
+		Value::I8(_)
+		| Value::I16(_)
+		| Value::I32(_)
+		| Value::I64(_)
+		| Value::I128(_)
+		| Value::U8(_)
+		| Value::U16(_)
+		| Value::U32(_)
+		| Value::U64(_)
+		| Value::U128(_) => 2,
+		Value::F32(_) | Value::F64(_) => 3,
+		Value::Char(_) | Value::String(_) => 4,
+		Value::Bytes(_) => 5,
+		Value::Seq(_) => 6,
+		Value::Map(_) => 7,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serde::Deserialize;
+
+	#[test]
+	fn count_types_at_each_path() {
+		const DOCS: &[&str] = &[
+			r#"{"port":80,"hosts":[{"name":"a"},{"name":null}]}"#,
+			r#"{"port":"http","hosts":[],"weird key":1.5}"#,
+			r"[1]",
+		];
+		let mut stats = TypeStats::default();
+		for doc in DOCS {
+			let value = Value::deserialize(&mut serde_json::Deserializer::from_str(doc)).unwrap();
+			stats.add(&value);
+		}
+		let stats = serde_json::to_string(&stats.take().unwrap()).unwrap();
+		assert_eq!(
+			stats,
+			concat!(
+				r#"{".":{"array":1,"map":2},".port":{"int":1,"string":1},".hosts":{"array":2},"#,
+				r#"".hosts[]":{"map":2},".hosts[].name":{"null":1,"string":1},"#,
+				r#"".\"weird key\"":{"float":1},"[]":{"int":1}}"#,
+			)
+		);
+		assert!(TypeStats::default().take().is_none());
+	}
+}
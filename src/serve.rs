@@ -0,0 +1,348 @@
+//! A server that translates requests over a Unix domain socket, for `--serve-unix` and
+//! `--daemon`, and the client of the daemon for `--via-daemon`.
+//!
+//! A connection carries any number of requests, each answered in turn, so a client like an editor
+//! plugin can keep one xt process around instead of spawning one per translation. A request is
+//! three frames: the name of the input format (empty to detect it from the input), the name of the
+//! output format (empty for JSON), and the input itself. A response is a status byte, 0 for
+//! success or 1 for failure, followed by a frame holding the output or the error message. Every
+//! frame is a 32-bit big-endian length followed by that many bytes.
+//!
+//! Each request translates with the options from the command line, as if its input were the only
+//! input of a conversion. Up to [`MAX_CONNECTIONS`] connections are served at once, on threads of
+//! their own, while further clients wait to be accepted. On the first SIGINT, the server stops
+//! accepting connections and removes its socket, then finishes the request that each connection
+//! is on, and closes every connection before it exits.
+//!
+//! The daemon is the same server on a socket private to the user, so that a build system that
+//! converts thousands of small files can run a short-lived client for each one, and leave the
+//! parsing of options and setup of translation to a process that does it once. The socket is
+//! only for the user's own documents, so it lives in a directory that only the user can enter,
+//! and the client refuses to send to a socket or directory that another user owns.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use xt::Format;
+
+use crate::{Cli, InputPath, interrupt};
+
+/// The status byte of a response with output.
+const SUCCESS: u8 = 0;
+
+/// The status byte of a response with an error message.
+const FAILURE: u8 = 1;
+
+/// The most connections that the server serves at once.
+const MAX_CONNECTIONS: usize = 64;
+
+/// How long the server sleeps between checks for a new connection or an interruption.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Serves translation requests on a socket at the `--serve-unix` path, or the daemon's socket,
+/// until interrupted.
+pub(crate) fn serve_unix(args: &Cli) {
+	let path = &args.serve_unix.clone().unwrap_or_else(daemon_socket);
+	// Options that can't configure a translator would fail every request the same way, so they
+	// fail here instead, before any client connects. This also loads the options from files that
+	// every request then shares, so that no request reads a file of its own, or fails on options
+	// that this first translator accepted.
+	let mut translator = crate::output_translator(args, io::sink(), Format::Json);
+	crate::configure_documents(&mut translator, args);
+	drop(translator);
+
+	let private = args.serve_unix.is_none();
+	if private {
+		let dir = socket_dir(path);
+		match fs::DirBuilder::new().mode(0o700).create(dir) {
+			Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+			result => {
+				let Ok(()) = result.map_err(|err| die_in!(dir.display(), "{err}"));
+			}
+		}
+		let Ok(()) = check_private(dir).map_err(|err| die_in!(dir.display(), "{err}"));
+	}
+
+	interrupt::install();
+	let Ok(listener) = bind(path).map_err(|err| die_in!(path.display(), "{err}"));
+	// The private directory already keeps other users out, but the socket shouldn't rely on it.
+	if private {
+		if let Err(err) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+			let _ = fs::remove_file(path);
+			die_in!(path.display(), "{err}");
+		}
+	}
+	// The standard library retries an accept that a signal interrupts, so the server polls for
+	// connections instead of blocking on them, to notice an interruption in good time.
+	if let Err(err) = listener.set_nonblocking(true) {
+		let _ = fs::remove_file(path);
+		die_in!(path.display(), "{err}");
+	}
+	// The open connections, by a number of their own, for the server to close on an interruption.
+	let connections: Mutex<HashMap<u64, UnixStream>> = Mutex::default();
+	let lock = || connections.lock().unwrap_or_else(PoisonError::into_inner);
+	thread::scope(|scope| {
+		let mut next_id = 0;
+		while !interrupt::requested() {
+			if lock().len() >= MAX_CONNECTIONS {
+				thread::sleep(POLL_INTERVAL);
+				continue;
+			}
+			let accepted = listener.accept().and_then(|(stream, _)| {
+				stream.set_nonblocking(false)?;
+				Ok(stream)
+			});
+			match accepted {
+				Ok(stream) => {
+					// A connection that can't be tracked is dropped, which closes it.
+					let Ok(handle) = stream.try_clone() else {
+						continue;
+					};
+					let id = next_id;
+					next_id += 1;
+					lock().insert(id, handle);
+					let spawned = thread::Builder::new().spawn_scoped(scope, move || {
+						// A client that goes away mid-request has nobody to report the error to.
+						let _ = serve_connection(args, stream);
+						lock().remove(&id);
+					});
+					// A thread that can't start drops its stream, which closes the connection too.
+					if spawned.is_err() {
+						lock().remove(&id);
+					}
+				}
+				Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+				Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+				Err(err) => {
+					let _ = fs::remove_file(path);
+					die_in!(path.display(), "{err}");
+				}
+			}
+		}
+		// Shutting down the reading side of a connection ends its wait for another request, but
+		// leaves a request underway to finish and write its response.
+		let _ = fs::remove_file(path);
+		for stream in lock().values() {
+			let _ = stream.shutdown(Shutdown::Read);
+		}
+	});
+	interrupt::exit_if_requested();
+}
+
+/// Translates the single input through the daemon that `xt --daemon` started, rather than in
+/// this process.
+pub(crate) fn via_daemon(args: &Cli) {
+	let path = args
+		.input_paths()
+		.next()
+		.expect("there should always be an input");
+	let from = args.from.or_else(|| args.extension_format(&path));
+	let to = args.to.unwrap_or(Format::Json);
+	crate::check_terminal_output(to, None);
+	let Ok(input) = match &path {
+		InputPath::Stdin => {
+			let mut input = vec![];
+			io::stdin().lock().read_to_end(&mut input).map(|_| input)
+		}
+		InputPath::File(pathname) => fs::read(pathname),
+	}
+	.map_err(|err| die_in!(path, "{err}"));
+
+	let socket = daemon_socket();
+	let dir = socket_dir(&socket);
+	match check_private(dir).and_then(|()| check_owned(&fs::symlink_metadata(&socket)?)) {
+		Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+		Err(err) => die_in!(
+			socket.display(),
+			"refusing to use the daemon's socket: {err}"
+		),
+		Ok(()) => {}
+	}
+	let Ok(stream) = UnixStream::connect(&socket).map_err(|err| match err.kind() {
+		io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => die_in!(
+			socket.display(),
+			"no daemon is listening; start one with 'xt --daemon'"
+		),
+		_ => die_in!(socket.display(), "{err}"),
+	});
+	let Ok((status, output)) =
+		request(&stream, from, to, &input).map_err(|err| die_in!(socket.display(), "{err}"));
+	if status != SUCCESS {
+		die_in!(path, "{}", String::from_utf8_lossy(&output));
+	}
+	// Standard output exits on any error of its own.
+	let _ = crate::die::stdout().write_all(&output);
+}
+
+/// Returns the socket that `--daemon` serves on and `--via-daemon` connects to.
+fn daemon_socket() -> PathBuf {
+	if let Some(dir) = env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+		return PathBuf::from(dir).join("xt.sock");
+	}
+	// The temporary directory is shared with other users, who each need a daemon of their own.
+	env::temp_dir()
+		.join(format!("xt-{}", uid()))
+		.join("xt.sock")
+}
+
+/// Returns the directory of the daemon's socket.
+fn socket_dir(socket: &Path) -> &Path {
+	socket
+		.parent()
+		.expect("the daemon's socket should be in a directory")
+}
+
+/// Returns an error unless the current user owns a directory, and no other user can access it.
+fn check_private(dir: &Path) -> io::Result<()> {
+	let metadata = fs::symlink_metadata(dir)?;
+	check_owned(&metadata)?;
+	if !metadata.is_dir() || metadata.mode() & 0o077 != 0 {
+		return Err(io::Error::new(
+			io::ErrorKind::PermissionDenied,
+			"is not a directory private to the current user",
+		));
+	}
+	Ok(())
+}
+
+/// Returns an error unless the current user owns a file.
+fn check_owned(metadata: &fs::Metadata) -> io::Result<()> {
+	if metadata.uid() != uid() {
+		return Err(io::Error::new(
+			io::ErrorKind::PermissionDenied,
+			"is owned by another user",
+		));
+	}
+	Ok(())
+}
+
+fn uid() -> u32 {
+	// SAFETY: getuid has no preconditions, and always succeeds.
+	unsafe { libc::getuid() }
+}
+
+/// Binds a listener to a socket, replacing any socket that a server left behind without removing.
+fn bind(path: &Path) -> io::Result<UnixListener> {
+	match UnixListener::bind(path) {
+		Err(err)
+			if err.kind() == io::ErrorKind::AddrInUse
+				&& UnixStream::connect(path)
+					.is_err_and(|err| err.kind() == io::ErrorKind::ConnectionRefused) =>
+		{
+			// Only a socket can be stale; anything else at the path is someone's data.
+			if !fs::symlink_metadata(path)?.file_type().is_socket() {
+				return Err(err);
+			}
+			fs::remove_file(path)?;
+			UnixListener::bind(path)
+		}
+		result => result,
+	}
+}
+
+/// Sends a single request to a server, and returns the status and frame of its response.
+fn request(
+	stream: &UnixStream,
+	from: Option<Format>,
+	to: Format,
+	input: &[u8],
+) -> io::Result<(u8, Vec<u8>)> {
+	let mut w = BufWriter::new(stream);
+	write_frame(&mut w, from.map_or("", crate::format_name).as_bytes())?;
+	write_frame(&mut w, crate::format_name(to).as_bytes())?;
+	write_frame(&mut w, input)?;
+	w.flush()?;
+	drop(w);
+	let mut r = BufReader::new(stream);
+	let mut status = [0];
+	r.read_exact(&mut status)?;
+	let frame = read_frame(&mut r)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+	Ok((status[0], frame))
+}
+
+/// Answers every request on a connection until the client closes it.
+fn serve_connection(args: &Cli, stream: UnixStream) -> io::Result<()> {
+	let mut r = BufReader::new(&stream);
+	let mut w = BufWriter::new(&stream);
+	while let Some(from) = read_frame(&mut r)? {
+		let Some(to) = read_frame(&mut r)? else {
+			return Err(io::ErrorKind::UnexpectedEof.into());
+		};
+		let Some(input) = read_frame(&mut r)? else {
+			return Err(io::ErrorKind::UnexpectedEof.into());
+		};
+		match translate(args, &from, &to, &input) {
+			Ok(output) => write_response(&mut w, SUCCESS, &output)?,
+			Err(message) => write_response(&mut w, FAILURE, message.as_bytes())?,
+		}
+		w.flush()?;
+	}
+	Ok(())
+}
+
+/// Translates the input of a single request between the formats that it names.
+fn translate(args: &Cli, from: &[u8], to: &[u8], input: &[u8]) -> Result<Vec<u8>, String> {
+	let from = match from {
+		[] => None,
+		name => Some(format_named(name)?),
+	};
+	let to = match to {
+		[] => Format::Json,
+		name => format_named(name)?,
+	};
+	let mut output = vec![];
+	let mut translator = crate::output_translator(args, &mut output, to);
+	crate::configure_documents(&mut translator, args);
+	// The server waits for a request that started before an interruption to finish.
+	translator.set_interrupt_flag(None);
+	translator
+		.translate_slice(input, from)
+		.and_then(|()| translator.finish())
+		.map_err(|err| err.to_string())?;
+	drop(translator);
+	Ok(output)
+}
+
+fn format_named(name: &[u8]) -> Result<Format, String> {
+	let name = String::from_utf8_lossy(name);
+	crate::try_parse_format(&name).map_err(|err| format!("{err}: {name}"))
+}
+
+/// Reads a frame, or returns `None` if the client closed the connection instead of starting one.
+fn read_frame<R: Read>(r: &mut R) -> io::Result<Option<Vec<u8>>> {
+	let mut len = [0; 4];
+	match r.read_exact(&mut len) {
+		Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+		result => result?,
+	}
+	let len = u32::from_be_bytes(len);
+	// The buffer grows as the frame arrives, rather than trusting the client with the size of an
+	// allocation up front.
+	let mut frame = vec![];
+	r.take(len.into()).read_to_end(&mut frame)?;
+	if frame.len() < len as usize {
+		return Err(io::ErrorKind::UnexpectedEof.into());
+	}
+	Ok(Some(frame))
+}
+
+fn write_response<W: Write>(w: &mut W, status: u8, frame: &[u8]) -> io::Result<()> {
+	w.write_all(&[status])?;
+	write_frame(w, frame)
+}
+
+fn write_frame<W: Write>(w: &mut W, frame: &[u8]) -> io::Result<()> {
+	let len = u32::try_from(frame.len())
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "too large for a frame"))?;
+	w.write_all(&len.to_be_bytes())?;
+	w.write_all(frame)
+}
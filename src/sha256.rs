@@ -0,0 +1,115 @@
+//! The SHA-256 digest, for naming files after their content and for the hash command.
+//!
+//! This follows FIPS 180-4 directly rather than pulling in a dependency for a single use, and
+//! hashes a complete buffer at once since that's all the binary needs.
+
+/// The first 32 bits of the fractional parts of the cube roots of the first 64 primes.
+const K: [u32; 64] = [
+	0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+	0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+	0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+	0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+	0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+	0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+	0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+	0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The first 32 bits of the fractional parts of the square roots of the first 8 primes.
+const INITIAL: [u32; 8] = [
+	0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Returns the SHA-256 digest of the input.
+pub(crate) fn digest(input: &[u8]) -> [u8; 32] {
+	// The padded message ends with a 1 bit, zeros up to 8 bytes short of a 64-byte block, and
+	// the length of the input in bits.
+	let mut message = input.to_vec();
+	message.push(0x80);
+	while message.len() % 64 != 56 {
+		message.push(0);
+	}
+	let bits = (input.len() as u64).wrapping_mul(8);
+	message.extend_from_slice(&bits.to_be_bytes());
+
+	let mut state = INITIAL;
+	for block in message.chunks_exact(64) {
+		compress(&mut state, block);
+	}
+	let mut digest = [0; 32];
+	for (out, word) in digest.chunks_exact_mut(4).zip(state) {
+		out.copy_from_slice(&word.to_be_bytes());
+	}
+	digest
+}
+
+/// Returns the lowercase hexadecimal form of the SHA-256 digest of the input.
+pub(crate) fn hex_digest(input: &[u8]) -> String {
+	digest(input).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Mixes a single 64-byte block of the padded message into the state.
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+	let mut w = [0u32; 64];
+	for (w, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+		*w = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+	}
+	for t in 16..64 {
+		let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+		let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+		w[t] = w[t - 16]
+			.wrapping_add(s0)
+			.wrapping_add(w[t - 7])
+			.wrapping_add(s1);
+	}
+
+	let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+	for (&k, &w) in K.iter().zip(&w) {
+		let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+		let ch = (e & f) ^ (!e & g);
+		let t1 = h
+			.wrapping_add(s1)
+			.wrapping_add(ch)
+			.wrapping_add(k)
+			.wrapping_add(w);
+		let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+		let maj = (a & b) ^ (a & c) ^ (b & c);
+		let t2 = s0.wrapping_add(maj);
+		(h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+	}
+	for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+		*word = word.wrapping_add(value);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn digest_known_answers() {
+		const CASES: &[(&[u8], &str)] = &[
+			(
+				b"",
+				"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+			),
+			(
+				b"abc",
+				"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+			),
+			// The padding of a 448-bit message spills into a second block.
+			(
+				b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+				"248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+			),
+			// A message of exactly one block gets a whole block of padding.
+			(
+				&[b'a'; 64],
+				"ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+			),
+		];
+		for (input, expected) in CASES {
+			assert_eq!(hex_digest(input), *expected, "{input:?}");
+		}
+	}
+}
@@ -0,0 +1,93 @@
+//! Output to sinks that take one complete document at a time.
+//!
+//! A translator created with
+//! [`Translator::with_document_sink`](crate::Translator::with_document_sink) writes to a
+//! [`DocumentWriter`] through an output buffer of capacity 0, and its atomic writer flushes the
+//! layers below it after committing each document. The [`DocumentWriter`] collects everything
+//! written between flushes, so that every flush after a write delivers exactly one serialized
+//! document to the sink.
+
+use std::io::{self, Write};
+
+/// A destination for complete serialized documents, like a message queue or a series of HTTP
+/// requests, that doesn't need the documents concatenated into a single byte stream.
+pub trait DocumentSink {
+	/// Receives a single serialized document, along with its zero-based index among the documents
+	/// that the translator has written to this sink.
+	fn write_document(&mut self, bytes: &[u8], index: usize) -> io::Result<()>;
+}
+
+impl<S: DocumentSink + ?Sized> DocumentSink for &mut S {
+	fn write_document(&mut self, bytes: &[u8], index: usize) -> io::Result<()> {
+		(**self).write_document(bytes, index)
+	}
+}
+
+/// The writer underlying a translator that writes to a [`DocumentSink`].
+pub struct DocumentWriter<S> {
+	sink: S,
+	pending: Vec<u8>,
+	count: usize,
+}
+
+impl<S: DocumentSink> DocumentWriter<S> {
+	pub(crate) fn new(sink: S) -> DocumentWriter<S> {
+		DocumentWriter {
+			sink,
+			pending: vec![],
+			count: 0,
+		}
+	}
+}
+
+impl<S: DocumentSink> Write for DocumentWriter<S> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.pending.extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		if self.pending.is_empty() {
+			return Ok(());
+		}
+		// The document counts as written even if the sink fails, so that a caller who carries on
+		// after the error doesn't see the next document at the same index.
+		let index = self.count;
+		self.count += 1;
+		let result = self.sink.write_document(&self.pending, index);
+		self.pending.clear();
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Default)]
+	struct Collect(Vec<(Vec<u8>, usize)>);
+
+	impl DocumentSink for Collect {
+		fn write_document(&mut self, bytes: &[u8], index: usize) -> io::Result<()> {
+			self.0.push((bytes.to_vec(), index));
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn deliver_documents_between_flushes() {
+		let mut sink = Collect::default();
+		let mut w = DocumentWriter::new(&mut sink);
+		w.flush().unwrap();
+		w.write_all(b"{\"a\":").unwrap();
+		w.write_all(b"1}\n").unwrap();
+		w.flush().unwrap();
+		w.flush().unwrap();
+		w.write_all(b"{}\n").unwrap();
+		w.flush().unwrap();
+		assert_eq!(
+			sink.0,
+			[(b"{\"a\":1}\n".to_vec(), 0), (b"{}\n".to_vec(), 1)]
+		);
+	}
+}
@@ -0,0 +1,167 @@
+//! Profiles of how strictly a translator reads its inputs, and the input checks they bundle.
+//!
+//! Each input format has its own edge cases where a stricter parser would reject input that xt
+//! accepts, or the other way around. A [`Strictness`] profile sets all of them at once, while the
+//! translator's individual settings can still override any one of them afterward.
+
+use std::collections::HashSet;
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::transcode::Value;
+use crate::transform::path::push_key;
+
+/// A profile of settings for how strictly a translator reads its inputs, as set by
+/// [`Translator::set_strictness`](crate::Translator::set_strictness).
+///
+/// A new translator starts between the two profiles, accepting only what each input format
+/// accepts on its own.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Strictness {
+	/// Reject input that other parsers are likely to read differently than xt: maps with
+	/// duplicate keys, YAML plain scalars whose type depends on the YAML version, and anything
+	/// but whitespace between JSON documents. MessagePack extension types are errors.
+	Strict,
+	/// Accept the messier input that real-world tools produce: maps with duplicate keys, any
+	/// YAML plain scalars, and commas and comments between JSON documents. MessagePack extension
+	/// types translate as arrays of a type and data.
+	Lenient,
+}
+
+/// An [`Output`](crate::Output) that rejects documents containing maps with duplicate keys, when
+/// rejection is enabled.
+///
+/// Rejection fully deserializes each document into memory to check it.
+pub(crate) struct Output<O> {
+	output: O,
+	reject_duplicate_keys: bool,
+}
+
+impl<O> Output<O> {
+	pub(crate) fn new(output: O, reject_duplicate_keys: bool) -> Output<O> {
+		Output {
+			output,
+			reject_duplicate_keys,
+		}
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if !self.reject_duplicate_keys {
+			return self.output.transcode_from(de);
+		}
+		self.transcode_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if !self.reject_duplicate_keys {
+			return self.output.transcode_borrowed_from(de);
+		}
+		self.transcode_borrowed_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		if self.reject_duplicate_keys {
+			check_duplicate_keys(&value, &mut String::new())?;
+		}
+		self.output.transcode_value(value)
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		if self.reject_duplicate_keys {
+			check_duplicate_keys(&value, &mut String::new())?;
+		}
+		self.output.transcode_borrowed_value(value)
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+/// Returns an error for the first map within a value that has the same key more than once, where
+/// `path` is the path to the value.
+fn check_duplicate_keys(value: &Value, path: &mut String) -> crate::Result<()> {
+	let len = path.len();
+	match value {
+		Value::Seq(seq) => {
+			for (i, v) in seq.iter().enumerate() {
+				path.push_str(&format!("[{i}]"));
+				check_duplicate_keys(v, path)?;
+				path.truncate(len);
+			}
+		}
+		Value::Map(map) => {
+			let mut strings = HashSet::new();
+			let mut others: Vec<&Value> = vec![];
+			for (k, v) in map {
+				let duplicate = match k {
+					Value::String(key) => !strings.insert(key.as_ref()),
+					k if others.contains(&k) => true,
+					k => {
+						others.push(k);
+						false
+					}
+				};
+				if duplicate {
+					let key = serde_json::to_string(k).unwrap_or_else(|_| "a key".into());
+					let at = if path.is_empty() { "." } else { path };
+					return Err(format!("duplicate key {key} in map at {at}").into());
+				}
+				match k {
+					Value::String(key) => push_key(path, key),
+					k => push_key(path, &serde_json::to_string(k).unwrap_or_default()),
+				}
+				check_duplicate_keys(v, path)?;
+				path.truncate(len);
+			}
+		}
+		_ => {}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::borrow::Cow;
+
+	use super::*;
+
+	#[test]
+	fn reject_duplicate_keys() {
+		let string = |s: &'static str| Value::String(Cow::Borrowed(s));
+		let inner = Value::Map(vec![
+			(Value::U64(1), Value::Unit),
+			(string("a"), Value::Unit),
+			(Value::U64(1), Value::Unit),
+		]);
+		let value = Value::Map(vec![
+			(string("a"), Value::Unit),
+			(string("b c"), Value::Seq(vec![Value::Unit, inner])),
+		]);
+		let err = check_duplicate_keys(&value, &mut String::new()).unwrap_err();
+		assert_eq!(err.to_string(), r#"duplicate key 1 in map at ."b c"[1]"#);
+
+		let value = Value::Map(vec![(string("a"), Value::Unit), (string("b"), Value::Unit)]);
+		assert!(check_duplicate_keys(&value, &mut String::new()).is_ok());
+	}
+}
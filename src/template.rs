@@ -0,0 +1,109 @@
+//! Text output rendered from a template, in place of a serialized format.
+
+use std::io::{self, Write};
+
+use serde::de::{self, Deserialize};
+use tinytemplate::TinyTemplate;
+
+use crate::transcode::Value;
+
+/// The name of the one template that a [`TinyTemplate`] instance holds.
+const NAME: &str = "document";
+
+/// A template that renders each document as text.
+///
+/// Templates use the [TinyTemplate][tinytemplate] syntax, with the document as the rendering
+/// context. Fields of a root map are available by name (e.g. `{metadata.name}`), and the root
+/// value itself is available as `{@root}`. Values are rendered as is, without the HTML escaping
+/// that the `tinytemplate` crate applies by default. Every document renders to its own copy of
+/// the template, with no separator between them.
+///
+/// [tinytemplate]: https://docs.rs/tinytemplate
+#[derive(Clone)]
+pub struct Template {
+	source: String,
+}
+
+impl Template {
+	/// Parses a template from its source text.
+	pub fn new<S: Into<String>>(source: S) -> crate::Result<Template> {
+		let template = Template {
+			source: source.into(),
+		};
+		template.compile()?;
+		Ok(template)
+	}
+
+	/// Compiles the template.
+	///
+	/// A [`TinyTemplate`] borrows its source text, so we compile a fresh instance for every
+	/// document rather than trying to store one alongside the text it borrows from. Compilation is
+	/// cheap next to rendering, which converts the entire document to a JSON value first.
+	fn compile(&self) -> crate::Result<TinyTemplate<'_>> {
+		let mut tt = TinyTemplate::new();
+		tt.set_default_formatter(&tinytemplate::format_unescaped);
+		tt.add_template(NAME, &self.source)?;
+		Ok(tt)
+	}
+}
+
+pub(crate) struct Output<W: Write> {
+	w: W,
+	template: Template,
+}
+
+impl<W: Write> Output<W> {
+	pub(crate) fn new(w: W, template: Template) -> Output<W> {
+		Output { w, template }
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+}
+
+impl<W: Write> crate::Output<'_> for Output<W> {
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		self.transcode_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		let rendered = self.template.compile()?.render(NAME, &value)?;
+		self.w.write_all(rendered.as_bytes())?;
+		Ok(())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.w.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::Output as _;
+
+	#[test]
+	fn render_documents_unescaped() {
+		let template = Template::new("{name}: {{ for i in items }}{i} {{ endfor }}\n").unwrap();
+		let mut output = Output::new(vec![], template);
+		for doc in [
+			r#"{"name":"<a>","items":[1,2]}"#,
+			r#"{"name":"b","items":[]}"#,
+		] {
+			let mut de = serde_json::Deserializer::from_str(doc);
+			output.transcode_from(&mut de).unwrap();
+		}
+		assert_eq!(str::from_utf8(&output.w), Ok("<a>: 1 2 \nb: \n"));
+	}
+
+	#[test]
+	fn invalid_template() {
+		assert!(Template::new("{{ for i in items }}").is_err());
+	}
+}
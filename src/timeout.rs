@@ -0,0 +1,91 @@
+//! Wall-clock limits on the translation of each input.
+//!
+//! A translator with a timeout starts a [`Deadline`] for every input it translates, and checks
+//! it before every document and around every read from a reader input. Neither check can stop a
+//! read that's already blocked, but a read that returns after the deadline fails even if it read
+//! something, so that translation from a stalled source fails as soon as the source produces
+//! anything at all (including the end of its input) rather than carrying on late.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+use crate::input::InputSource;
+
+/// The point in time by which the translation of an input must finish.
+#[derive(Clone, Copy)]
+pub(crate) struct Deadline {
+	at: Option<Instant>,
+	timeout: Duration,
+}
+
+impl Deadline {
+	/// Starts a deadline at the provided duration from now.
+	///
+	/// A duration too long to represent as an [`Instant`] never expires.
+	pub(crate) fn after(timeout: Duration) -> Deadline {
+		Deadline {
+			at: Instant::now().checked_add(timeout),
+			timeout,
+		}
+	}
+
+	/// Returns true if the deadline has passed.
+	fn is_expired(self) -> bool {
+		self.at.is_some_and(|at| Instant::now() >= at)
+	}
+
+	/// Returns an error if the deadline has passed.
+	pub(crate) fn check(self) -> Result<(), TimeoutError> {
+		match self.is_expired() {
+			true => Err(TimeoutError(self.timeout)),
+			false => Ok(()),
+		}
+	}
+}
+
+/// The error produced when the translation of an input takes longer than its timeout.
+#[derive(Debug)]
+pub(crate) struct TimeoutError(pub(crate) Duration);
+
+impl Error for TimeoutError {}
+
+impl Display for TimeoutError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "translation timed out after {:?}", self.0)
+	}
+}
+
+/// An [`InputSource`] that fails every read around which its [`Deadline`] passes.
+pub(crate) struct Reader<R> {
+	inner: R,
+	deadline: Deadline,
+}
+
+impl<R> Reader<R> {
+	pub(crate) fn new(inner: R, deadline: Deadline) -> Reader<R> {
+		Reader { inner, deadline }
+	}
+
+	fn check(&self) -> io::Result<()> {
+		self.deadline
+			.check()
+			.map_err(|err| io::Error::new(io::ErrorKind::TimedOut, err))
+	}
+}
+
+impl<R: Read> Read for Reader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.check()?;
+		let len = self.inner.read(buf)?;
+		self.check()?;
+		Ok(len)
+	}
+}
+
+impl<R: InputSource> InputSource for Reader<R> {
+	fn seek_to(&mut self, offset: u64) -> io::Result<()> {
+		self.inner.seek_to(offset)
+	}
+}
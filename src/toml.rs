@@ -6,55 +6,96 @@ use std::fmt;
 use std::io::{self, Write};
 use std::str;
 
-use serde::{Deserialize, de, ser};
+use serde::{Deserialize, de};
 
+use crate::comment::Comments;
+use crate::detect::Trial;
 use crate::input::{self, Ref};
-
-pub(crate) fn input_matches(mut input: Ref) -> io::Result<bool> {
+use crate::transcode;
+
+/// The size at which detection stops buffering a reader input to try it as TOML.
+///
+/// Our TOML parser requires that we buffer all input into a &str before parsing. However, if we
+/// have an unbounded input stream, we don't want to endlessly fill some poor buffer until we crash
+/// from an allocation failure.
+///
+/// As an arbitrary cutoff, let's say that if you're streaming a TOML document >= 2 MiB in size
+/// into xt, it might be time to stop and think about some things.
+pub(crate) const DETECTION_CUTOFF: usize = 2 * 1024_usize.pow(2);
+
+pub(crate) fn input_matches(mut input: Ref) -> io::Result<Trial> {
 	let input_buf = match input {
 		Ref::Slice(b) => b,
-		Ref::Reader(_) => {
-			// Our TOML parser requires that we buffer all input into a &str before parsing.
-			// However, if we have an unbounded input stream, we don't want to endlessly
-			// fill some poor buffer until we crash from an allocation failure.
-			//
-			// As an arbitrary cutoff, let's say that if you're streaming a TOML document >= 2 MiB
-			// in size into xt, it might be time to stop and think about some things.
-			const SIZE_CUTOFF: usize = 2 * 1024_usize.pow(2);
-			let prefix = input.prefix(SIZE_CUTOFF)?;
-			if prefix.len() >= SIZE_CUTOFF {
-				return Ok(false);
+		Ref::Reader(ref r) => {
+			// A detection limit takes the place of the cutoff when one is set.
+			let limit = r.detection_limit();
+			let cutoff = limit.map_or(DETECTION_CUTOFF, |limit| limit.saturating_add(1));
+			let prefix = input.prefix(cutoff)?;
+			if prefix.len() >= cutoff {
+				return match limit {
+					Some(limit) => Err(input::detection_limit_error(limit)),
+					None => Ok(Err("input is too large to buffer for detection".into())),
+				};
 			}
 			prefix
 		}
 	};
 
-	let Ok(input_str) = str::from_utf8(input_buf) else {
-		return Ok(false);
-	};
-	let Ok(de) = ::toml::Deserializer::parse(input_str) else {
-		return Ok(false);
+	let input_str = match str::from_utf8(input_buf) {
+		Ok(s) => s,
+		Err(err) => return Ok(Err(err.to_string())),
 	};
-	Ok(de::IgnoredAny::deserialize(de).is_ok())
+	// The full form of a TOML error quotes the input across several lines, which is too much for
+	// a summary of why detection failed.
+	let result = ::toml::Deserializer::parse(input_str)
+		.and_then(|de| de::IgnoredAny::deserialize(de).map(|_| ()));
+	Ok(result.map_err(|err| err.message().trim_end().to_owned()))
 }
 
-pub(crate) fn transcode<O>(input: input::Handle, mut output: O) -> crate::Result<()>
+pub(crate) fn transcode<'i, O>(input: input::Handle<'i>, mut output: O) -> crate::Result<()>
 where
-	O: crate::Output,
+	O: crate::Output<'i>,
 {
 	let input: Cow<'_, [u8]> = input.try_into()?;
 	let de = ::toml::Deserializer::parse(str::from_utf8(&input)?)?;
+	output.set_document_offset(0);
+	output.set_document_end(input.len() as u64);
 	output.transcode_from(de)
 }
 
 pub(crate) struct Output<W: Write> {
 	w: W,
 	used: bool,
+	container: Option<String>,
+	comments: Comments,
 }
 
 impl<W: Write> Output<W> {
 	pub(crate) fn new(w: W) -> Output<W> {
-		Output { w, used: false }
+		Output {
+			w,
+			used: false,
+			container: None,
+			comments: Comments::default(),
+		}
+	}
+
+	/// Sets the key of an array of tables that wraps every output document, or `None` to output
+	/// a single document at the root.
+	pub(crate) fn set_container(&mut self, key: Option<String>) {
+		self.container = key;
+	}
+
+	pub(crate) fn set_comments(&mut self, comments: Comments) {
+		self.comments = comments;
+	}
+
+	pub(crate) fn has_comments(&self) -> bool {
+		!self.comments.is_empty()
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
 	}
 
 	fn ensure_one_use(&mut self) -> crate::Result<()> {
@@ -62,14 +103,22 @@ impl<W: Write> Output<W> {
 		// the number of input documents in advance, we fail if someone tries to use us more than
 		// once. We try to run this check before we even deserialize any values, so we don't waste
 		// time on things that will get thrown out.
-		if self.used {
+		//
+		// With a container, each document becomes one more entry in an array of tables, and any
+		// number of documents is fine.
+		if self.used && self.container.is_none() {
 			return Err(TomlOutputError::MultiDocument.into());
 		}
-		self.used = true;
 		Ok(())
 	}
 
-	fn output_value(&mut self, value: &::toml::Value) -> crate::Result<()> {
+	fn output_value(&mut self, value: ::toml::Value) -> crate::Result<()> {
+		let output = self.to_string(value)?;
+		self.write_output(&output)
+	}
+
+	/// Serializes a document as TOML, within the container if there is one.
+	fn to_string(&self, value: ::toml::Value) -> crate::Result<String> {
 		// TOML requires that the root of the document be a table, which we can't guarantee for
 		// arbitrary input. While the toml crate allows deserializing to a toml::Table to avoid
 		// accepting non-table root values, the error message for such values (as of this writing)
@@ -79,34 +128,58 @@ impl<W: Write> Output<W> {
 		// TOML also requires that non-table values appear before any tables at a given level of
 		// nesting, which the toml crate knows how to handle. We enable its "preserve_order"
 		// feature to keep as much of the original input ordering as we can.
-		if let toml::Value::Table(table) = value {
-			let output = ::toml::to_string_pretty(table)?;
-			self.w.write_all(output.as_bytes())?;
-			Ok(())
-		} else {
-			Err(TomlOutputError::NonTableRoot.into())
+		let toml::Value::Table(table) = value else {
+			return Err(TomlOutputError::NonTableRoot.into());
+		};
+
+		let output = match &self.container {
+			None => ::toml::to_string_pretty(&table)?,
+			Some(key) => {
+				// Entries in an array of tables can be written out one at a time, since every
+				// header for the array appends a new table to it. That lets us stream documents
+				// into the container rather than holding all of them until the end.
+				let mut root = ::toml::Table::new();
+				root.insert(key.clone(), ::toml::Value::Array(vec![table.into()]));
+				::toml::to_string_pretty(&root)?
+			}
+		};
+		Ok(output)
+	}
+
+	fn write_output(&mut self, output: &str) -> crate::Result<()> {
+		if self.used {
+			self.w.write_all(b"\n")?;
 		}
+		self.w.write_all(output.as_bytes())?;
+		self.used = true;
+		Ok(())
 	}
 }
 
-impl<W: Write> crate::Output for Output<W> {
+impl<W: Write> crate::Output<'_> for Output<W> {
 	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
 	where
 		D: de::Deserializer<'de, Error = E>,
 		E: de::Error + Send + Sync + 'static,
 	{
 		self.ensure_one_use()?;
+		if self.has_comments() {
+			return self.transcode_value(transcode::Value::deserialize(de)?);
+		}
 		let value = ::toml::Value::deserialize(de)?;
-		self.output_value(&value)
+		self.output_value(value)
 	}
 
-	fn transcode_value<S>(&mut self, value: S) -> crate::Result<()>
-	where
-		S: ser::Serialize,
-	{
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
 		self.ensure_one_use()?;
+		if self.has_comments() {
+			let output = self.comments.write(&value, |value| {
+				self.to_string(::toml::Value::try_from(value)?)
+			})?;
+			return self.write_output(&output);
+		}
 		let value = ::toml::Value::try_from(value)?;
-		self.output_value(&value)
+		self.output_value(value)
 	}
 
 	fn flush(&mut self) -> io::Result<()> {
@@ -4,4 +4,4 @@ mod stream;
 mod value;
 
 pub(crate) use stream::transcode;
-pub(crate) use value::Value;
+pub(crate) use value::{Value, ValueVisitor};
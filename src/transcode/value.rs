@@ -15,6 +15,7 @@ use serde::ser::{Serialize, SerializeMap, Serializer};
 /// It prefers zero-copy deserialization for byte sequences and strings, which limits the lifetime
 /// of the value and the types of inputs it can deserialize from. It represents maps as `Vec`s of
 /// key-value pairs, which preserves ordering but doesn't allow random access to entries.
+#[derive(Clone, PartialEq)]
 pub(crate) enum Value<'a> {
 	Unit,
 	Bool(bool),
@@ -37,6 +38,50 @@ pub(crate) enum Value<'a> {
 	Map(Vec<(Value<'a>, Value<'a>)>),
 }
 
+impl Value<'_> {
+	/// Converts the value into one that owns all of its data, copying any borrowed strings or
+	/// byte sequences.
+	pub(crate) fn into_owned(self) -> Value<'static> {
+		match self {
+			Value::Unit => Value::Unit,
+			Value::Bool(b) => Value::Bool(b),
+			Value::I8(n) => Value::I8(n),
+			Value::I16(n) => Value::I16(n),
+			Value::I32(n) => Value::I32(n),
+			Value::I64(n) => Value::I64(n),
+			Value::I128(n) => Value::I128(n),
+			Value::U8(n) => Value::U8(n),
+			Value::U16(n) => Value::U16(n),
+			Value::U32(n) => Value::U32(n),
+			Value::U64(n) => Value::U64(n),
+			Value::U128(n) => Value::U128(n),
+			Value::F32(f) => Value::F32(f),
+			Value::F64(f) => Value::F64(f),
+			Value::Char(c) => Value::Char(c),
+			Value::String(v) => Value::String(Cow::Owned(v.into_owned())),
+			Value::Bytes(v) => Value::Bytes(Cow::Owned(v.into_owned())),
+			Value::Seq(v) => Value::Seq(v.into_iter().map(Value::into_owned).collect()),
+			Value::Map(m) => Value::Map(
+				m.into_iter()
+					.map(|(k, v)| (k.into_owned(), v.into_owned()))
+					.collect(),
+			),
+		}
+	}
+
+	/// Returns true if any string within the value, including any map key, contains the text.
+	pub(crate) fn contains_str(&self, text: &str) -> bool {
+		match self {
+			Value::String(s) => s.contains(text),
+			Value::Seq(seq) => seq.iter().any(|v| v.contains_str(text)),
+			Value::Map(map) => {
+				{ map.iter() }.any(|(k, v)| k.contains_str(text) || v.contains_str(text))
+			}
+			_ => false,
+		}
+	}
+}
+
 impl Serialize for Value<'_> {
 	fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
 	where
@@ -86,63 +131,64 @@ impl<'de: 'a, 'a> Deserialize<'de> for Value<'a> {
 	where
 		D: Deserializer<'de>,
 	{
-		struct Visitor;
+		d.deserialize_any(ValueVisitor)
+	}
+}
 
-		impl<'a> de::Visitor<'a> for Visitor {
-			type Value = Value<'a>;
+/// The [`de::Visitor`] that produces a [`Value`] from any supported input.
+pub(crate) struct ValueVisitor;
 
-			fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-				f.write_str("any supported value")
-			}
+impl<'a> de::Visitor<'a> for ValueVisitor {
+	type Value = Value<'a>;
 
-			impl_value_scalar_visitors! {
-				visit_unit() => Value::Unit;
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("any supported value")
+	}
 
-				visit_bool(v: bool) => Value::Bool(v);
+	impl_value_scalar_visitors! {
+		visit_unit() => Value::Unit;
 
-				visit_i8(v: i8) => Value::I8(v);
-				visit_i16(v: i16) => Value::I16(v);
-				visit_i32(v: i32) => Value::I32(v);
-				visit_i64(v: i64) => Value::I64(v);
-				visit_i128(v: i128) => Value::I128(v);
+		visit_bool(v: bool) => Value::Bool(v);
 
-				visit_u8(v: u8) => Value::U8(v);
-				visit_u16(v: u16) => Value::U16(v);
-				visit_u32(v: u32) => Value::U32(v);
-				visit_u64(v: u64) => Value::U64(v);
-				visit_u128(v: u128) => Value::U128(v);
+		visit_i8(v: i8) => Value::I8(v);
+		visit_i16(v: i16) => Value::I16(v);
+		visit_i32(v: i32) => Value::I32(v);
+		visit_i64(v: i64) => Value::I64(v);
+		visit_i128(v: i128) => Value::I128(v);
 
-				visit_f32(v: f32) => Value::F32(v);
-				visit_f64(v: f64) => Value::F64(v);
+		visit_u8(v: u8) => Value::U8(v);
+		visit_u16(v: u16) => Value::U16(v);
+		visit_u32(v: u32) => Value::U32(v);
+		visit_u64(v: u64) => Value::U64(v);
+		visit_u128(v: u128) => Value::U128(v);
 
-				visit_char(v: char) => Value::Char(v);
+		visit_f32(v: f32) => Value::F32(v);
+		visit_f64(v: f64) => Value::F64(v);
 
-				visit_borrowed_str(v: &'a str) => Value::String(Cow::Borrowed(v));
-				visit_str(v: &str) => Value::String(Cow::Owned(v.to_owned()));
-				visit_string(v: String) => Value::String(Cow::Owned(v));
+		visit_char(v: char) => Value::Char(v);
 
-				visit_borrowed_bytes(v: &'a [u8]) => Value::Bytes(Cow::Borrowed(v));
-				visit_bytes(v: &[u8]) => Value::Bytes(Cow::Owned(v.to_owned()));
-				visit_byte_buf(v: Vec<u8>) => Value::Bytes(Cow::Owned(v));
-			}
+		visit_borrowed_str(v: &'a str) => Value::String(Cow::Borrowed(v));
+		visit_str(v: &str) => Value::String(Cow::Owned(v.to_owned()));
+		visit_string(v: String) => Value::String(Cow::Owned(v));
 
-			fn visit_seq<A: de::SeqAccess<'a>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
-				let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0));
-				while let Some(e) = seq.next_element()? {
-					vec.push(e);
-				}
-				Ok(Value::Seq(vec))
-			}
+		visit_borrowed_bytes(v: &'a [u8]) => Value::Bytes(Cow::Borrowed(v));
+		visit_bytes(v: &[u8]) => Value::Bytes(Cow::Owned(v.to_owned()));
+		visit_byte_buf(v: Vec<u8>) => Value::Bytes(Cow::Owned(v));
+	}
 
-			fn visit_map<A: de::MapAccess<'a>>(self, mut map: A) -> Result<Self::Value, A::Error> {
-				let mut vec = Vec::with_capacity(map.size_hint().unwrap_or(0));
-				while let Some(entry) = map.next_entry()? {
-					vec.push(entry);
-				}
-				Ok(Value::Map(vec))
-			}
+	fn visit_seq<A: de::SeqAccess<'a>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+		while let Some(e) = seq.next_element()? {
+			vec.push(e);
 		}
+		Ok(Value::Seq(vec))
+	}
 
-		d.deserialize_any(Visitor)
+	fn visit_map<A: de::MapAccess<'a>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+		let mut vec = Vec::with_capacity(map.size_hint().unwrap_or(0));
+		while let Some(entry) = map.next_entry()? {
+			vec.push(entry);
+		}
+		Ok(Value::Map(vec))
 	}
 }
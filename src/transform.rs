@@ -0,0 +1,1299 @@
+//! Transformations of documents between input and output.
+//!
+//! Transforms operate on fully deserialized [`Value`]s, so a translator with any transforms
+//! buffers each document in memory before applying them and passing the result to the output.
+//! Translations without transforms continue to stream documents directly from input to output.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::error;
+use std::fmt;
+use std::io;
+use std::mem;
+
+use serde::de::{self, Deserialize};
+
+use crate::Format;
+use crate::error::Location;
+use crate::transcode::Value;
+
+pub(crate) mod path;
+mod select;
+mod sort;
+
+use path::{Parser, Segment, SyntaxError};
+use select::Predicate;
+use sort::KeyOrder;
+
+pub(crate) use sort::compare_values;
+
+/// A transformation applied to every document before output.
+///
+/// Transforms run in the order they were added to a [`Translator`](crate::Translator).
+#[derive(Clone)]
+pub struct Transform(Kind);
+
+#[derive(Clone)]
+enum Kind {
+	Redact {
+		patterns: Vec<String>,
+		placeholder: String,
+	},
+	Flatten {
+		separator: String,
+	},
+	Unflatten {
+		separator: String,
+	},
+	IndexedArrays,
+	IndexedMaps,
+	MaxDepth(usize),
+	Prune {
+		empty_collections: bool,
+	},
+	Select(Predicate),
+	Coerce(Vec<Coercion>),
+	Stringify,
+	Rename(Vec<Rename>),
+	Provenance(String),
+	SortKeys,
+	OrderKeys(KeyOrder),
+	SortArrays {
+		by: Option<String>,
+	},
+}
+
+/// Information about the origin of the document being transformed.
+#[derive(Default)]
+struct Context<'c> {
+	source: Option<&'c str>,
+	input: Option<usize>,
+	index: u64,
+	offset: Option<u64>,
+}
+
+/// The renaming of a single map key.
+#[derive(Clone)]
+struct Rename {
+	/// The path to the map containing the key.
+	parent: Vec<Segment>,
+	key: String,
+	new_key: String,
+}
+
+/// A type of scalar that [`Transform::coerce`] can produce from a string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Coercion {
+	/// Coerces strings using JSON number syntax (e.g. `42`, `-1.5`, or `6.02e23`) to numbers.
+	///
+	/// Strings with leading zeros (like `007`), a leading `+`, or surrounding whitespace don't
+	/// use JSON number syntax, and remain strings. So do integers too large to represent exactly.
+	Number,
+	/// Coerces the strings `true` and `false` to booleans.
+	Bool,
+	/// Coerces the string `null` to null.
+	Null,
+}
+
+impl Transform {
+	/// The value that [`redact`](Transform::redact) substitutes for redacted values.
+	pub const REDACTED: &str = "[REDACTED]";
+
+	/// Creates a transform that replaces the values of matching map keys with a placeholder.
+	///
+	/// Each pattern matches string keys at any depth, either exactly or as a glob where `*`
+	/// matches any sequence of characters and `?` matches any single character. The entire value
+	/// of a matching key is replaced with the [`REDACTED`](Transform::REDACTED) string,
+	/// regardless of its original type.
+	pub fn redact<I, S>(patterns: I) -> Transform
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		Transform(Kind::Redact {
+			patterns: patterns.into_iter().map(Into::into).collect(),
+			placeholder: Transform::REDACTED.into(),
+		})
+	}
+
+	/// Creates a transform that flattens nested maps into a single root map, joining the keys
+	/// along the path to each value with `separator` (e.g. `a.b.c` for a `.` separator).
+	///
+	/// Flattening stops at values other than non-empty maps, including arrays and any maps nested
+	/// within them, which remain whole values in the flattened map. Integer, boolean, and character
+	/// keys become strings when joined into a path. A nested map with any other type of key remains
+	/// a whole value too. Documents with a root value other than a map are unchanged. A document
+	/// fails to translate if a joined path matches another key of the flattened map, as for
+	/// `{"a": {"b": 1}, "a.b": 2}`.
+	///
+	/// Returns an error if the separator is empty.
+	pub fn flatten<S: Into<String>>(separator: S) -> crate::Result<Transform> {
+		Ok(Transform(Kind::Flatten {
+			separator: non_empty_separator(separator)?,
+		}))
+	}
+
+	/// Creates a transform that expands root map keys containing `separator` into nested maps,
+	/// reversing [`flatten`](Transform::flatten).
+	///
+	/// Keys sharing a common prefix merge into a single nested map, in the order they appear.
+	/// Documents with a root value other than a map are unchanged. A document fails to translate
+	/// if a path passes through a key with a value other than a map, or ends at a key that
+	/// already has a value, as for `{"a.b": 1, "a": 3}`, rather than losing either value.
+	///
+	/// Returns an error if the separator is empty.
+	pub fn unflatten<S: Into<String>>(separator: S) -> crate::Result<Transform> {
+		Ok(Transform(Kind::Unflatten {
+			separator: non_empty_separator(separator)?,
+		}))
+	}
+
+	/// Creates a transform that converts every map whose keys are the indexes `0` through `n - 1`
+	/// into an array of its values in the order of their indexes, as for data that went through
+	/// PHP or HTML form encoding.
+	///
+	/// The keys may be strings of decimal digits without leading zeros, or integers, in any
+	/// order. Maps at any depth convert after the maps within them. Empty maps, and maps with any
+	/// other key or any gap in their indexes, are unchanged.
+	pub fn indexed_arrays() -> Transform {
+		Transform(Kind::IndexedArrays)
+	}
+
+	/// Creates a transform that converts every non-empty array at any depth into a map from the
+	/// string form of each index to its element, reversing
+	/// [`indexed_arrays`](Transform::indexed_arrays).
+	///
+	/// Empty arrays remain arrays, since an empty map converts back to nothing else.
+	pub fn indexed_maps() -> Transform {
+		Transform(Kind::IndexedMaps)
+	}
+
+	/// Creates a transform that keeps `depth` levels of nested arrays and maps, counting the root
+	/// value as the first level, and replaces each non-empty array or map below them with a
+	/// string that summarizes it, like `[… 42 items]` or `{… 3 entries}`.
+	///
+	/// This previews the shape of a large document without all of its contents. Empty arrays and
+	/// maps remain as they are, and a depth of 0 summarizes the root value itself.
+	pub fn max_depth(depth: usize) -> Transform {
+		Transform(Kind::MaxDepth(depth))
+	}
+
+	/// Creates a transform that removes null values from maps and arrays at any depth.
+	///
+	/// With `empty_collections`, the transform also removes empty maps and arrays, including those
+	/// that become empty after pruning their contents. The root value of a document always
+	/// remains, even if it's null or empty.
+	pub fn prune(empty_collections: bool) -> Transform {
+		Transform(Kind::Prune { empty_collections })
+	}
+
+	/// Creates a transform that keeps only the documents matching a predicate expression,
+	/// skipping all others.
+	///
+	/// The expression is a path into the document, optionally followed by a comparison against
+	/// a JSON scalar, e.g. `.kind == "Deployment"` or `.spec.replicas >= 3`. A path consists of
+	/// keys following `.` (quoted with JSON string syntax when they contain other characters than
+	/// letters, digits, `_`, and `-`) and array indexes in brackets, while `.` alone refers to the
+	/// root value. A path without a comparison matches documents where it leads to any value
+	/// other than null or `false`. A path that doesn't exist in a document never matches a
+	/// comparison, except with `!=`.
+	///
+	/// Returns an error if the expression is invalid.
+	pub fn select(expr: &str) -> crate::Result<Transform> {
+		Ok(Transform(Kind::Select(Predicate::parse(expr)?)))
+	}
+
+	/// Creates a transform that coerces string values that look like other scalars into those
+	/// types, following the rules of each [`Coercion`].
+	///
+	/// Coercion applies to values at any depth, but never to map keys. Strings that match none of
+	/// the coercions remain strings.
+	pub fn coerce<I>(coercions: I) -> Transform
+	where
+		I: IntoIterator<Item = Coercion>,
+	{
+		Transform(Kind::Coerce(coercions.into_iter().collect()))
+	}
+
+	/// Creates a transform that converts every scalar value into a string, reversing
+	/// [`coerce`](Transform::coerce).
+	///
+	/// Numbers become strings in JSON number syntax (or as `NaN`, `inf`, and `-inf` for
+	/// non-finite floats), booleans become `true` or `false`, and nulls become `null`. Byte
+	/// sequences and map keys are unchanged.
+	pub fn stringify() -> Transform {
+		Transform(Kind::Stringify)
+	}
+
+	/// Creates a transform that renames map keys, given a mapping from the path of each key to
+	/// its new name.
+	///
+	/// Paths use the same syntax as in [`select`](Transform::select), except that the leading `.`
+	/// is optional, and each path must end with a key. For example, a mapping from
+	/// `spec.replicas` to `count` renames the `replicas` key of the `spec` map, keeping its
+	/// position in the map. A renamed key replaces any existing entry with its new name.
+	/// Renames apply in the order of the mapping, and paths that don't exist in a document are
+	/// ignored.
+	///
+	/// Returns an error if any path is invalid.
+	pub fn rename<I, P, K>(mapping: I) -> crate::Result<Transform>
+	where
+		I: IntoIterator<Item = (P, K)>,
+		P: AsRef<str>,
+		K: Into<String>,
+	{
+		let renames = mapping
+			.into_iter()
+			.map(|(path, new_key)| {
+				let (parent, key) = parse_rename_path(path.as_ref())?;
+				Ok(Rename {
+					parent,
+					key,
+					new_key: new_key.into(),
+				})
+			})
+			.collect::<Result<_, SyntaxError>>()?;
+		Ok(Transform(Kind::Rename(renames)))
+	}
+
+	/// Creates a [`rename`](Transform::rename) transform from a serialized mapping.
+	///
+	/// The input must contain a single document in any supported format, with a root map from
+	/// string paths to string key names. When `from` is `None`, the format is detected from the
+	/// input itself.
+	pub fn rename_from_slice(input: &[u8], from: Option<Format>) -> crate::Result<Transform> {
+		let mut json = vec![];
+		crate::translate_slice(input, from, Format::Json, &mut json)?;
+
+		let mut values = serde_json::Deserializer::from_slice(&json).into_iter::<Value>();
+		let (Some(mapping), None) = (values.next().transpose()?, values.next()) else {
+			return Err(RenameMappingError::NotOneDocument.into());
+		};
+		let Value::Map(mapping) = mapping else {
+			return Err(RenameMappingError::NotStringMap.into());
+		};
+		let mapping = mapping
+			.into_iter()
+			.map(|entry| match entry {
+				(Value::String(path), Value::String(new_key)) => Ok((path, new_key)),
+				_ => Err(RenameMappingError::NotStringMap),
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		Transform::rename(mapping)
+	}
+
+	/// Creates a transform that records where each document came from in a map under `key` at
+	/// the root of the document.
+	///
+	/// The map contains the name of the source set with
+	/// [`Translator::set_source_name`](crate::Translator::set_source_name) under `source` when
+	/// one is set, the zero-based index of the document within its input under `document`, and
+	/// the byte offset where the document starts in its input under `offset` when the input
+	/// format reports it. For documents that a chain of
+	/// [readers](crate::Translator::translate_readers) attributes to one of its readers, the map
+	/// also contains the zero-based index of that reader under `input`. JSON and MessagePack inputs
+	/// report offsets, except for documents split from JSON arrays, and TOML documents always
+	/// start at offset 0. YAML inputs don't report offsets.
+	///
+	/// The map replaces any existing entry with the same key. Documents with a root value other
+	/// than a map are unchanged.
+	pub fn provenance<S: Into<String>>(key: S) -> Transform {
+		Transform(Kind::Provenance(key.into()))
+	}
+
+	/// Creates a transform that sorts the entries of every map by key, at any depth.
+	///
+	/// Keys sort in the same canonical order as the elements of
+	/// [`sort_arrays`](Transform::sort_arrays), with keys other than scalars after every scalar
+	/// key in their original order.
+	pub fn sort_keys() -> Transform {
+		Transform(Kind::SortKeys)
+	}
+
+	/// Creates a transform that orders the entries of maps by a serialized key order, such as the
+	/// conventional order of a project's configuration files.
+	///
+	/// The input must contain a single document in any supported format, with a root map whose
+	/// string keys list the order of the keys of the root map. The value of each key gives the
+	/// order of the map under that key in the same way, or may be an array of string keys to order
+	/// that map without ordering any maps nested in it, or any scalar to leave it as it is. An
+	/// order applies to every map of an array in its position, such as an array of TOML tables.
+	/// Keys that an order doesn't list keep their input order after every listed key, or at the
+	/// position of a `*` key, whose value then orders the maps under them. When `from` is `None`,
+	/// the format is detected from the input itself.
+	///
+	/// For example, the YAML order `{package: [name, version], "*": null, dev-dependencies: null}`
+	/// puts the `name` and `version` of a Cargo manifest's `package` table first, and its
+	/// `dev-dependencies` table last.
+	pub fn key_order_from_slice(input: &[u8], from: Option<Format>) -> crate::Result<Transform> {
+		let mut json = vec![];
+		crate::translate_slice(input, from, Format::Json, &mut json)?;
+
+		let mut values = serde_json::Deserializer::from_slice(&json).into_iter::<Value>();
+		let (Some(order), None) = (values.next().transpose()?, values.next()) else {
+			return Err(KeyOrderError::DocumentCount.into());
+		};
+		if !matches!(order, Value::Map(_)) {
+			return Err(KeyOrderError::RootNotMap.into());
+		}
+		let order = KeyOrder::from_value(order).ok_or(KeyOrderError::KeyNotString)?;
+		Ok(Transform(Kind::OrderKeys(order)))
+	}
+
+	/// Creates a transform that sorts every array of scalars at any depth.
+	///
+	/// Scalars sort first by type, with null before booleans, booleans before numbers, and
+	/// numbers before strings, then by value. Integers and floats of any size compare by their
+	/// exact numeric values. Arrays with any element other than a scalar are unchanged. Together
+	/// with [`sort_keys`](Transform::sort_keys), this produces a canonical form for comparing
+	/// documents whose arrays are unordered sets.
+	pub fn sort_arrays() -> Transform {
+		Transform(Kind::SortArrays { by: None })
+	}
+
+	/// Creates a transform that sorts every array of scalars like
+	/// [`sort_arrays`](Transform::sort_arrays), and also sorts every array of maps by the scalar
+	/// value of `key` in each map.
+	///
+	/// Maps without the key, or with a value other than a scalar for it, sort before every other
+	/// map in their original order. Arrays mixing maps with other values are unchanged.
+	pub fn sort_arrays_by<S: Into<String>>(key: S) -> Transform {
+		Transform(Kind::SortArrays {
+			by: Some(key.into()),
+		})
+	}
+
+	/// Applies the transform to a document, returning false if the document should be skipped.
+	fn apply<'a>(&self, value: &mut Value<'a>, cx: &Context) -> crate::Result<bool> {
+		match &self.0 {
+			Kind::Redact {
+				patterns,
+				placeholder,
+			} => redact(value, patterns, placeholder),
+			Kind::Flatten { separator } => flatten(value, separator)?,
+			Kind::Unflatten { separator } => unflatten(value, separator)?,
+			Kind::IndexedArrays => indexed_arrays(value),
+			Kind::IndexedMaps => indexed_maps(value),
+			Kind::MaxDepth(depth) => max_depth(value, *depth),
+			Kind::Prune { empty_collections } => prune(value, *empty_collections),
+			Kind::Select(predicate) => return Ok(predicate.matches(value)),
+			Kind::Coerce(coercions) => coerce(value, coercions),
+			Kind::Stringify => stringify(value),
+			Kind::Rename(renames) => {
+				for r in renames {
+					rename(value, r);
+				}
+			}
+			Kind::Provenance(key) => provenance(value, key, cx),
+			Kind::SortKeys => sort::sort_keys(value),
+			Kind::OrderKeys(order) => sort::order_keys(value, order),
+			Kind::SortArrays { by } => sort::sort_arrays(value, by.as_deref()),
+		}
+		Ok(true)
+	}
+}
+
+fn redact(value: &mut Value, patterns: &[String], placeholder: &str) {
+	match value {
+		Value::Seq(seq) => {
+			for v in seq {
+				redact(v, patterns, placeholder);
+			}
+		}
+		Value::Map(map) => {
+			for (k, v) in map {
+				if key_matches(k, patterns) {
+					*v = Value::String(Cow::Owned(placeholder.to_owned()));
+				} else {
+					redact(v, patterns, placeholder);
+				}
+			}
+		}
+		_ => {}
+	}
+}
+
+fn key_matches(key: &Value, patterns: &[String]) -> bool {
+	let Value::String(key) = key else {
+		return false;
+	};
+	patterns.iter().any(|p| glob_matches(p, key))
+}
+
+fn non_empty_separator<S: Into<String>>(separator: S) -> crate::Result<String> {
+	let separator = separator.into();
+	if separator.is_empty() {
+		return Err("separator for flattened keys must not be empty".into());
+	}
+	Ok(separator)
+}
+
+fn flatten(value: &mut Value, separator: &str) -> Result<(), KeyConflict> {
+	let Value::Map(map) = value else {
+		return Ok(());
+	};
+	let mut flat = Vec::with_capacity(map.len());
+	let mut seen = HashMap::new();
+	for (k, v) in mem::take(map) {
+		flatten_entry(&mut flat, &mut seen, (k, false), v, separator)?;
+	}
+	*map = flat;
+	Ok(())
+}
+
+/// Adds an entry to a flattened map, given its key and whether that key joins a path, and
+/// records each string key in `seen` with whether any key it came from joined one.
+fn flatten_entry<'a>(
+	flat: &mut Vec<(Value<'a>, Value<'a>)>,
+	seen: &mut HashMap<String, bool>,
+	(key, joined): (Value<'a>, bool),
+	value: Value<'a>,
+	separator: &str,
+) -> Result<(), KeyConflict> {
+	let prefix = match &value {
+		Value::Map(entries)
+			if !entries.is_empty() && entries.iter().all(|(k, _)| key_str(k).is_some()) =>
+		{
+			key_str(&key).map(Cow::into_owned)
+		}
+		_ => None,
+	};
+	let Some(prefix) = prefix else {
+		// Keys that repeat in the input itself are left as they are.
+		if let Value::String(k) = &key {
+			match seen.entry(k.to_string()) {
+				Entry::Occupied(entry) if *entry.get() || joined => {
+					return Err(KeyConflict::Flatten(entry.key().clone()));
+				}
+				Entry::Occupied(_) => {}
+				Entry::Vacant(entry) => {
+					entry.insert(joined);
+				}
+			}
+		}
+		flat.push((key, value));
+		return Ok(());
+	};
+	let Value::Map(entries) = value else {
+		unreachable!()
+	};
+	for (k, v) in entries {
+		let key = key_str(&k).expect("nested keys should have been checked");
+		let path = format!("{prefix}{separator}{key}");
+		let key = (Value::String(Cow::Owned(path)), true);
+		flatten_entry(flat, seen, key, v, separator)?;
+	}
+	Ok(())
+}
+
+fn unflatten(value: &mut Value, separator: &str) -> Result<(), KeyConflict> {
+	let Value::Map(map) = value else {
+		return Ok(());
+	};
+	let mut nested = Vec::with_capacity(map.len());
+	for (k, v) in mem::take(map) {
+		match &k {
+			Value::String(key) => {
+				let path: Vec<&str> = key.split(separator).collect();
+				if !insert_path(&mut nested, &path, v) {
+					return Err(KeyConflict::Unflatten(key.to_string()));
+				}
+			}
+			_ => nested.push((k, v)),
+		}
+	}
+	*map = nested;
+	Ok(())
+}
+
+/// Inserts a value at the provided path of string keys, creating maps along the path as needed,
+/// and returns false instead if the path passes through a value other than a map or ends at an
+/// existing key.
+fn insert_path<'a>(map: &mut Vec<(Value<'a>, Value<'a>)>, path: &[&str], value: Value<'a>) -> bool {
+	let (&first, rest) = path.split_first().expect("paths should not be empty");
+	let index = map
+		.iter()
+		.position(|(k, _)| matches!(k, Value::String(k) if k == first));
+	let entry = match index {
+		Some(_) if rest.is_empty() => return false,
+		Some(index) => &mut map[index].1,
+		None if rest.is_empty() => {
+			map.push((Value::String(Cow::Owned(first.into())), value));
+			return true;
+		}
+		None => {
+			map.push((Value::String(Cow::Owned(first.into())), Value::Map(vec![])));
+			&mut map.last_mut().expect("an entry was just pushed").1
+		}
+	};
+	let Value::Map(inner) = entry else {
+		return false;
+	};
+	insert_path(inner, rest, value)
+}
+
+fn indexed_arrays(value: &mut Value) {
+	match value {
+		Value::Seq(seq) => {
+			for v in seq {
+				indexed_arrays(v);
+			}
+		}
+		Value::Map(map) => {
+			for (_, v) in map.iter_mut() {
+				indexed_arrays(v);
+			}
+			let Some(indexes) = map
+				.iter()
+				.map(|(k, _)| key_index(k))
+				.collect::<Option<Vec<_>>>()
+			else {
+				return;
+			};
+			let mut seen = vec![false; map.len()];
+			for &i in &indexes {
+				if seen.get(i).is_none_or(|&seen| seen) {
+					return;
+				}
+				seen[i] = true;
+			}
+			if indexes.is_empty() {
+				return;
+			}
+			let mut slots: Vec<Option<Value>> = (0..map.len()).map(|_| None).collect();
+			for ((_, v), i) in mem::take(map).into_iter().zip(indexes) {
+				slots[i] = Some(v);
+			}
+			let seq = slots
+				.into_iter()
+				.map(|v| v.expect("every index should appear once"));
+			*value = Value::Seq(seq.collect());
+		}
+		_ => {}
+	}
+}
+
+/// Returns the array index that a map key names, if it's an integer or a string of its digits.
+fn key_index(key: &Value) -> Option<usize> {
+	let digits = match key {
+		Value::Char(_) | Value::Bool(_) => return None,
+		key => key_str(key)?,
+	};
+	if !digits.bytes().all(|b| b.is_ascii_digit()) || (digits.len() > 1 && digits.starts_with('0'))
+	{
+		return None;
+	}
+	digits.parse().ok()
+}
+
+fn indexed_maps(value: &mut Value) {
+	match value {
+		Value::Seq(seq) => {
+			for v in seq.iter_mut() {
+				indexed_maps(v);
+			}
+			if seq.is_empty() {
+				return;
+			}
+			let entries = mem::take(seq).into_iter().enumerate();
+			*value = Value::Map(
+				entries
+					.map(|(i, v)| (Value::String(Cow::Owned(i.to_string())), v))
+					.collect(),
+			);
+		}
+		Value::Map(map) => {
+			for (_, v) in map {
+				indexed_maps(v);
+			}
+		}
+		_ => {}
+	}
+}
+
+fn max_depth(value: &mut Value, depth: usize) {
+	let summary = match value {
+		Value::Seq(seq) if depth == 0 && !seq.is_empty() => match seq.len() {
+			1 => "[… 1 item]".to_owned(),
+			n => format!("[… {n} items]"),
+		},
+		Value::Map(map) if depth == 0 && !map.is_empty() => match map.len() {
+			1 => "{… 1 entry}".to_owned(),
+			n => format!("{{… {n} entries}}"),
+		},
+		Value::Seq(seq) => {
+			for v in seq {
+				max_depth(v, depth - 1);
+			}
+			return;
+		}
+		Value::Map(map) => {
+			for (_, v) in map {
+				max_depth(v, depth - 1);
+			}
+			return;
+		}
+		_ => return,
+	};
+	*value = Value::String(Cow::Owned(summary));
+}
+
+fn prune(value: &mut Value, empty_collections: bool) {
+	let is_pruned = |v: &Value| match v {
+		Value::Unit => true,
+		Value::Seq(seq) => empty_collections && seq.is_empty(),
+		Value::Map(map) => empty_collections && map.is_empty(),
+		_ => false,
+	};
+	match value {
+		Value::Seq(seq) => {
+			for v in seq.iter_mut() {
+				prune(v, empty_collections);
+			}
+			seq.retain(|v| !is_pruned(v));
+		}
+		Value::Map(map) => {
+			for (_, v) in map.iter_mut() {
+				prune(v, empty_collections);
+			}
+			map.retain(|(_, v)| !is_pruned(v));
+		}
+		_ => {}
+	}
+}
+
+fn coerce(value: &mut Value, coercions: &[Coercion]) {
+	match value {
+		Value::Seq(seq) => {
+			for v in seq {
+				coerce(v, coercions);
+			}
+		}
+		Value::Map(map) => {
+			for (_, v) in map {
+				coerce(v, coercions);
+			}
+		}
+		Value::String(s) => {
+			if let Some(coerced) = coercions.iter().find_map(|c| coerce_str(s, *c)) {
+				*value = coerced;
+			}
+		}
+		_ => {}
+	}
+}
+
+fn coerce_str(s: &str, coercion: Coercion) -> Option<Value<'static>> {
+	match coercion {
+		Coercion::Bool => match s {
+			"true" => Some(Value::Bool(true)),
+			"false" => Some(Value::Bool(false)),
+			_ => None,
+		},
+		Coercion::Null => (s == "null").then_some(Value::Unit),
+		Coercion::Number => {
+			// serde_json tolerates surrounding whitespace, which we don't.
+			if s.trim() != s {
+				return None;
+			}
+			let n: serde_json::Number = serde_json::from_str(s).ok()?;
+			if let Some(n) = n.as_u64() {
+				Some(Value::U64(n))
+			} else if let Some(n) = n.as_i64() {
+				Some(Value::I64(n))
+			} else if s.contains(['.', 'e', 'E']) {
+				n.as_f64().map(Value::F64)
+			} else {
+				// An integer that doesn't fit in 64 bits would lose precision as a float.
+				None
+			}
+		}
+	}
+}
+
+fn stringify(value: &mut Value) {
+	let s = match &mut *value {
+		Value::Seq(seq) => {
+			for v in seq {
+				stringify(v);
+			}
+			return;
+		}
+		Value::Map(map) => {
+			for (_, v) in map {
+				stringify(v);
+			}
+			return;
+		}
+		Value::String(_) | Value::Bytes(_) => return,
+		Value::Unit => "null".to_owned(),
+		Value::Char(c) => c.to_string(),
+		Value::F32(f) => float_string((*f).into()),
+		Value::F64(f) => float_string(*f),
+		other => key_str(other)
+			.expect("remaining scalars should have a string form")
+			.into_owned(),
+	};
+	*value = Value::String(Cow::Owned(s));
+}
+
+fn float_string(f: f64) -> String {
+	if f.is_finite() {
+		serde_json::to_string(&f).expect("finite floats should serialize")
+	} else {
+		f.to_string()
+	}
+}
+
+/// Parses a path for renaming into the path to the parent map and the key to rename.
+fn parse_rename_path(path: &str) -> Result<(Vec<Segment>, String), SyntaxError> {
+	let path = if path.starts_with(['.', '[']) {
+		Cow::Borrowed(path)
+	} else {
+		Cow::Owned(format!(".{path}"))
+	};
+	let mut parser = Parser::new("rename path", &path);
+	let mut parent = parser.path()?;
+	if !parser.rest().is_empty() {
+		return Err(parser.error("unexpected characters after path"));
+	}
+	match parent.pop() {
+		Some(Segment::Key(key)) => Ok((parent, key)),
+		_ => Err(parser.error("path must end with a key")),
+	}
+}
+
+fn rename(value: &mut Value, rename: &Rename) {
+	let Some(Value::Map(map)) = path::lookup_mut(value, &rename.parent) else {
+		return;
+	};
+	let Some(index) = map
+		.iter()
+		.position(|(k, _)| path::key_matches(k, &rename.key))
+	else {
+		return;
+	};
+	map[index].0 = Value::String(Cow::Owned(rename.new_key.clone()));
+
+	let mut i = 0;
+	map.retain(|(k, _)| {
+		let keep = i == index || !path::key_matches(k, &rename.new_key);
+		i += 1;
+		keep
+	});
+}
+
+fn provenance(value: &mut Value, key: &str, cx: &Context) {
+	let Value::Map(map) = value else {
+		return;
+	};
+	let string = |s: &str| Value::String(Cow::Owned(s.into()));
+	let mut fields = Vec::with_capacity(4);
+	if let Some(source) = cx.source {
+		fields.push((string("source"), string(source)));
+	}
+	if let Some(input) = cx.input {
+		fields.push((string("input"), Value::U64(input as u64)));
+	}
+	fields.push((string("document"), Value::U64(cx.index)));
+	if let Some(offset) = cx.offset {
+		fields.push((string("offset"), Value::U64(offset)));
+	}
+
+	map.retain(|(k, _)| !path::key_matches(k, key));
+	map.push((string(key), Value::Map(fields)));
+}
+
+/// An error from flattening or unflattening keys into the same path as another key.
+#[derive(Debug)]
+enum KeyConflict {
+	/// A key of the flattened map, which more than one entry would have.
+	Flatten(String),
+	/// A key of the original map, whose path conflicts with another value.
+	Unflatten(String),
+}
+
+impl error::Error for KeyConflict {}
+
+impl fmt::Display for KeyConflict {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			KeyConflict::Flatten(key) => {
+				write!(f, "flattening produces the key {key:?} more than once")
+			}
+			KeyConflict::Unflatten(key) => {
+				write!(
+					f,
+					"unflattening {key:?} conflicts with another value on its path"
+				)
+			}
+		}
+	}
+}
+
+/// An error in the contents of a serialized rename mapping.
+#[derive(Debug)]
+enum RenameMappingError {
+	NotOneDocument,
+	NotStringMap,
+}
+
+impl error::Error for RenameMappingError {}
+
+impl fmt::Display for RenameMappingError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			RenameMappingError::NotOneDocument => {
+				f.write_str("rename mapping must contain exactly one document")
+			}
+			RenameMappingError::NotStringMap => {
+				f.write_str("rename mapping must be a map from string paths to string keys")
+			}
+		}
+	}
+}
+
+/// An error in the contents of a serialized key order.
+#[derive(Debug)]
+enum KeyOrderError {
+	DocumentCount,
+	RootNotMap,
+	KeyNotString,
+}
+
+impl error::Error for KeyOrderError {}
+
+impl fmt::Display for KeyOrderError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			KeyOrderError::DocumentCount => {
+				f.write_str("key order must contain exactly one document")
+			}
+			KeyOrderError::RootNotMap => f.write_str("key order must be a map"),
+			KeyOrderError::KeyNotString => {
+				f.write_str("key order must list only string keys at every depth")
+			}
+		}
+	}
+}
+
+/// Returns the string form of a map key, for keys that have one.
+pub(crate) fn key_str<'v>(key: &'v Value) -> Option<Cow<'v, str>> {
+	Some(match key {
+		Value::String(s) => Cow::Borrowed(s),
+		Value::Char(c) => Cow::Owned(c.to_string()),
+		Value::Bool(b) => Cow::Owned(b.to_string()),
+		Value::I8(n) => Cow::Owned(n.to_string()),
+		Value::I16(n) => Cow::Owned(n.to_string()),
+		Value::I32(n) => Cow::Owned(n.to_string()),
+		Value::I64(n) => Cow::Owned(n.to_string()),
+		Value::I128(n) => Cow::Owned(n.to_string()),
+		Value::U8(n) => Cow::Owned(n.to_string()),
+		Value::U16(n) => Cow::Owned(n.to_string()),
+		Value::U32(n) => Cow::Owned(n.to_string()),
+		Value::U64(n) => Cow::Owned(n.to_string()),
+		Value::U128(n) => Cow::Owned(n.to_string()),
+		_ => return None,
+	})
+}
+
+/// Returns true if `text` matches `pattern`, where `*` matches any sequence of characters and `?`
+/// matches any single character.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+
+	// The classic backtracking matcher: on a mismatch, retry from the most recent `*` with it
+	// consuming one more character of the text.
+	let (mut p, mut t) = (0, 0);
+	let mut star: Option<(usize, usize)> = None;
+	while t < text.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				star = Some((p, t));
+				p += 1;
+			}
+			Some(&c) if c == '?' || c == text[t] => {
+				p += 1;
+				t += 1;
+			}
+			_ => match star {
+				Some((sp, st)) => {
+					star = Some((sp, st + 1));
+					p = sp + 1;
+					t = st + 1;
+				}
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// An [`Output`](crate::Output) that applies transforms to every document before passing it on.
+pub(crate) struct Output<'t, O> {
+	output: O,
+	transforms: &'t [Transform],
+	source: Option<&'t str>,
+	location: Option<Location>,
+}
+
+impl<'t, O> Output<'t, O> {
+	/// Creates an output that applies transforms to the documents from a single input, where
+	/// `source` is the name of the input if known.
+	pub(crate) fn new(
+		output: O,
+		transforms: &'t [Transform],
+		source: Option<&'t str>,
+	) -> Output<'t, O> {
+		Output {
+			output,
+			transforms,
+			source,
+			location: None,
+		}
+	}
+
+	/// Applies every transform to a document, returning false if the document should be skipped.
+	fn apply(&mut self, value: &mut Value) -> crate::Result<bool> {
+		let location = self.location.take();
+		let cx = Context {
+			source: self.source,
+			input: location.and_then(|loc| loc.input),
+			index: location.map_or(0, |loc| loc.index),
+			offset: location.and_then(|loc| loc.offset),
+		};
+		for t in self.transforms {
+			if !t.apply(value, &cx)? {
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.transforms.is_empty() {
+			return self.output.transcode_from(de);
+		}
+		self.transcode_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if self.transforms.is_empty() {
+			return self.output.transcode_borrowed_from(de);
+		}
+		self.transcode_borrowed_value(Value::deserialize(de)?)
+	}
+
+	fn transcode_value(&mut self, mut value: Value) -> crate::Result<()> {
+		if !self.apply(&mut value)? {
+			return Ok(());
+		}
+		self.output.transcode_value(value)
+	}
+
+	fn transcode_borrowed_value(&mut self, mut value: Value<'i>) -> crate::Result<()> {
+		if !self.apply(&mut value)? {
+			return Ok(());
+		}
+		self.output.transcode_borrowed_value(value)
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.location = Some(location);
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn glob_matching() {
+		const CASES: &[(&str, &str, bool)] = &[
+			("password", "password", true),
+			("password", "passwords", false),
+			("*", "", true),
+			("*", "anything", true),
+			("*_token", "api_token", true),
+			("*_token", "api_tokens", false),
+			("api_*", "api_key", true),
+			("a*b*c", "aXXbYYc", true),
+			("a*b*c", "aXXcYYb", false),
+			("?ey", "key", true),
+			("?ey", "ey", false),
+			("*é", "café", true),
+		];
+		for &(pattern, text, want) in CASES {
+			assert_eq!(glob_matches(pattern, text), want, "{pattern:?} {text:?}");
+		}
+	}
+
+	/// Applies a transform to a JSON document, returning the transformed JSON.
+	fn apply_json(transform: Transform, input: &str) -> String {
+		let mut value = Value::deserialize(&mut serde_json::Deserializer::from_str(input)).unwrap();
+		transform.apply(&mut value, &Context::default()).unwrap();
+		serde_json::to_string(&value).unwrap()
+	}
+
+	#[test]
+	fn flatten_nested_maps() {
+		const CASES: &[(&str, &str)] = &[
+			(
+				r#"{"a":{"b":{"c":1},"d":2},"e":3}"#,
+				r#"{"a.b.c":1,"a.d":2,"e":3}"#,
+			),
+			(r#"{"a":{},"b":[{"c":1}]}"#, r#"{"a":{},"b":[{"c":1}]}"#),
+			(r#"[{"a":{"b":1}}]"#, r#"[{"a":{"b":1}}]"#),
+		];
+		for &(input, want) in CASES {
+			assert_eq!(
+				apply_json(Transform::flatten(".").unwrap(), input),
+				want,
+				"{input}"
+			);
+		}
+	}
+
+	#[test]
+	fn unflatten_dotted_keys() {
+		const CASES: &[(&str, &str)] = &[
+			(
+				r#"{"a.b.c":1,"a.d":2,"e":3}"#,
+				r#"{"a":{"b":{"c":1},"d":2},"e":3}"#,
+			),
+			(r#"{"a":{"x":0},"a.b":1}"#, r#"{"a":{"x":0,"b":1}}"#),
+		];
+		for &(input, want) in CASES {
+			assert_eq!(
+				apply_json(Transform::unflatten(".").unwrap(), input),
+				want,
+				"{input}"
+			);
+		}
+	}
+
+	#[test]
+	fn flatten_key_conflicts() {
+		const CASES: &[(&str, &str)] = &[
+			(r#"{"a":{"b":1},"a.b":2}"#, "flatten"),
+			(r#"{"a.b":2,"a":{"b":1}}"#, "flatten"),
+			(r#"{"a":{"b.c":1},"a.b":{"c":2}}"#, "flatten"),
+			(r#"{"a":0,"a.b":1}"#, "unflatten"),
+			(r#"{"a.b":1,"a":3}"#, "unflatten"),
+			(r#"{"a.b":1,"a.b":2}"#, "unflatten"),
+		];
+		for &(input, kind) in CASES {
+			let transform = match kind {
+				"flatten" => Transform::flatten("."),
+				_ => Transform::unflatten("."),
+			};
+			let mut value =
+				Value::deserialize(&mut serde_json::Deserializer::from_str(input)).unwrap();
+			let result = transform.unwrap().apply(&mut value, &Context::default());
+			assert!(result.is_err(), "{kind} {input}");
+		}
+		assert!(Transform::flatten("").is_err());
+		assert!(Transform::unflatten("").is_err());
+	}
+
+	#[test]
+	fn indexed_arrays_and_maps() {
+		const CASES: &[(&str, &str)] = &[
+			(
+				r#"{"items":{"1":"b","0":{"0":"a"}},"n":{"0":1,"2":3}}"#,
+				r#"{"items":[["a"],"b"],"n":{"0":1,"2":3}}"#,
+			),
+			(r#"{"0":1,"01":2}"#, r#"{"0":1,"01":2}"#),
+			(r#"{"0":1,"0":2}"#, r#"{"0":1,"0":2}"#),
+			(r#"[{},{"-0":1}]"#, r#"[{},{"-0":1}]"#),
+		];
+		for &(input, want) in CASES {
+			assert_eq!(
+				apply_json(Transform::indexed_arrays(), input),
+				want,
+				"{input}"
+			);
+		}
+		assert_eq!(
+			apply_json(Transform::indexed_maps(), r#"{"a":[[1],[]]}"#),
+			r#"{"a":{"0":{"0":1},"1":[]}}"#
+		);
+	}
+
+	#[test]
+	fn max_depth_summaries() {
+		const INPUT: &str = r#"{"a":{"b":[1,2,3],"c":{"d":1},"e":[]},"f":[[0]]}"#;
+		assert_eq!(
+			apply_json(Transform::max_depth(2), INPUT),
+			r#"{"a":{"b":"[… 3 items]","c":"{… 1 entry}","e":[]},"f":["[… 1 item]"]}"#
+		);
+		assert_eq!(
+			apply_json(Transform::max_depth(0), INPUT),
+			r#""{… 2 entries}""#
+		);
+		assert_eq!(apply_json(Transform::max_depth(3), INPUT), INPUT);
+	}
+
+	#[test]
+	fn prune_nulls() {
+		const INPUT: &str = r#"{"a":null,"b":[1,null,{"c":null}],"d":{"e":[]}}"#;
+		assert_eq!(
+			apply_json(Transform::prune(false), INPUT),
+			r#"{"b":[1,{}],"d":{"e":[]}}"#
+		);
+		assert_eq!(apply_json(Transform::prune(true), INPUT), r#"{"b":[1]}"#);
+		assert_eq!(apply_json(Transform::prune(true), "[null,{}]"), "[]");
+		assert_eq!(apply_json(Transform::prune(true), "null"), "null");
+	}
+
+	#[test]
+	fn coerce_strings() {
+		const INPUT: &str = r#"["42","-7","1.5e3","007","+1"," 1","18446744073709551616","true","False","null","",{"1":"2"}]"#;
+		assert_eq!(
+			apply_json(
+				Transform::coerce([Coercion::Number, Coercion::Bool, Coercion::Null]),
+				INPUT
+			),
+			r#"[42,-7,1500.0,"007","+1"," 1","18446744073709551616",true,"False",null,"",{"1":2}]"#
+		);
+		assert_eq!(
+			apply_json(Transform::coerce([Coercion::Bool]), r#"["42","true"]"#),
+			r#"["42",true]"#
+		);
+	}
+
+	#[test]
+	fn stringify_scalars() {
+		const INPUT: &str = r#"[42,-7,1500.0,0.1,true,null,"x",{"1":2}]"#;
+		assert_eq!(
+			apply_json(Transform::stringify(), INPUT),
+			r#"["42","-7","1500.0","0.1","true","null","x",{"1":"2"}]"#
+		);
+	}
+
+	#[test]
+	fn rename_keys() {
+		const INPUT: &str = r#"{"a":{"b":1,"c":2},"d":[{"e":3}],"f":4}"#;
+		let rename = |mapping: &[(&str, &str)]| {
+			apply_json(Transform::rename(mapping.iter().copied()).unwrap(), INPUT)
+		};
+		assert_eq!(
+			rename(&[("a.b", "x"), (".d[0].e", "y"), ("f", "g")]),
+			r#"{"a":{"x":1,"c":2},"d":[{"y":3}],"g":4}"#
+		);
+		assert_eq!(
+			rename(&[("a.b", "c")]),
+			r#"{"a":{"c":1},"d":[{"e":3}],"f":4}"#
+		);
+		assert_eq!(
+			rename(&[("a.b", "x"), ("a.x", "y")]),
+			r#"{"a":{"y":1,"c":2},"d":[{"e":3}],"f":4}"#
+		);
+		assert_eq!(rename(&[("missing.b", "x"), ("a.b.c", "x")]), INPUT);
+
+		assert!(Transform::rename([("d[0]", "x")]).is_err());
+		assert!(Transform::rename([("a b", "x")]).is_err());
+	}
+
+	#[test]
+	fn rename_from_mapping_document() {
+		let rename = Transform::rename_from_slice(b"a.b: x\nf: g\n", Some(Format::Yaml)).unwrap();
+		assert_eq!(
+			apply_json(rename, r#"{"a":{"b":1},"f":2}"#),
+			r#"{"a":{"x":1},"g":2}"#
+		);
+
+		for input in [&b"[]"[..], b"{\"a\": 1}", b"{} {}"] {
+			assert!(Transform::rename_from_slice(input, Some(Format::Json)).is_err());
+		}
+	}
+
+	#[test]
+	fn order_keys_from_document() {
+		const ORDER: &[u8] = br#"{"b": {"y": null, "x": null}, "*": ["z"], "a": null}"#;
+		const CASES: &[(&str, &str)] = &[
+			(
+				r#"{"a":1,"c":{"q":1,"z":2},"b":{"x":1,"w":2,"y":3},"d":4}"#,
+				r#"{"b":{"y":3,"x":1,"w":2},"c":{"z":2,"q":1},"d":4,"a":1}"#,
+			),
+			(
+				r#"[{"a":1,"b":[{"x":1,"y":2}]}]"#,
+				r#"[{"b":[{"y":2,"x":1}],"a":1}]"#,
+			),
+		];
+		for &(input, want) in CASES {
+			let order = Transform::key_order_from_slice(ORDER, Some(Format::Json)).unwrap();
+			assert_eq!(apply_json(order, input), want, "{input}");
+		}
+
+		for input in [&b"[]"[..], b"{\"a\": [1]}", b"{} {}"] {
+			assert!(Transform::key_order_from_slice(input, Some(Format::Json)).is_err());
+		}
+	}
+
+	#[test]
+	fn provenance_replaces_existing_key() {
+		const CASES: &[(&str, &str)] = &[
+			(r#"{"a":1,"xt":"old"}"#, r#"{"a":1,"xt":{"document":0}}"#),
+			("[1]", "[1]"),
+		];
+		for &(input, want) in CASES {
+			assert_eq!(
+				apply_json(Transform::provenance("xt"), input),
+				want,
+				"{input}"
+			);
+		}
+	}
+
+	#[test]
+	fn redact_nested_keys() {
+		let mut value = Value::Map(vec![
+			(Value::String("user".into()), Value::String("xt".into())),
+			(
+				Value::String("auth".into()),
+				Value::Seq(vec![Value::Map(vec![
+					(Value::String("api_token".into()), Value::U64(42)),
+					(Value::U64(1), Value::Bool(true)),
+				])]),
+			),
+		]);
+		Transform::redact(["*_token", "u?er"])
+			.apply(&mut value, &Context::default())
+			.unwrap();
+
+		let Value::Map(map) = &value else {
+			unreachable!()
+		};
+		assert!(matches!(&map[0].1, Value::String(s) if s == Transform::REDACTED));
+		let Value::Seq(seq) = &map[1].1 else {
+			unreachable!()
+		};
+		let Value::Map(inner) = &seq[0] else {
+			unreachable!()
+		};
+		assert!(matches!(&inner[0].1, Value::String(s) if s == Transform::REDACTED));
+		assert!(matches!(&inner[1].1, Value::Bool(true)));
+	}
+}
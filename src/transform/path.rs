@@ -0,0 +1,179 @@
+//! Paths to values within documents, and a parser for the expressions that contain them.
+//!
+//! A path consists of keys following `.` and array indexes in brackets, as in
+//! `.items[0].name`. Keys containing characters other than letters, digits, `_`, and `-` are
+//! quoted with JSON string syntax, as in `.labels."app.kubernetes.io/name"`. A `.` alone is the
+//! path to the root value.
+
+use std::error;
+use std::fmt;
+
+use crate::transcode::Value;
+
+/// A single step of a path.
+#[derive(Clone, PartialEq)]
+pub(crate) enum Segment {
+	Key(String),
+	Index(usize),
+}
+
+/// Parses an expression consisting of a single path, where `what` describes the path in errors.
+pub(crate) fn parse(what: &'static str, expr: &str) -> Result<Vec<Segment>, SyntaxError> {
+	let mut parser = Parser::new(what, expr);
+	let path = parser.path()?;
+	parser.skip_whitespace();
+	if !parser.rest().is_empty() {
+		return Err(parser.error("unexpected characters after path"));
+	}
+	Ok(path)
+}
+
+/// Returns the value at the end of a path, if the path exists in the document.
+pub(crate) fn lookup<'v, 'a>(value: &'v Value<'a>, path: &[Segment]) -> Option<&'v Value<'a>> {
+	path.iter()
+		.try_fold(value, |value, segment| match (segment, value) {
+			(Segment::Key(key), Value::Map(map)) => map
+				.iter()
+				.find(|(k, _)| key_matches(k, key))
+				.map(|(_, v)| v),
+			(Segment::Index(i), Value::Seq(seq)) => seq.get(*i),
+			_ => None,
+		})
+}
+
+/// Returns the value at the end of a path for modification, if the path exists in the document.
+pub(crate) fn lookup_mut<'v, 'a>(
+	value: &'v mut Value<'a>,
+	path: &[Segment],
+) -> Option<&'v mut Value<'a>> {
+	path.iter()
+		.try_fold(value, |value, segment| match (segment, value) {
+			(Segment::Key(key), Value::Map(map)) => map
+				.iter_mut()
+				.find(|(k, _)| key_matches(k, key))
+				.map(|(_, v)| v),
+			(Segment::Index(i), Value::Seq(seq)) => seq.get_mut(*i),
+			_ => None,
+		})
+}
+
+/// Returns true if a map key matches a path key.
+pub(crate) fn key_matches(key: &Value, path_key: &str) -> bool {
+	super::key_str(key).is_some_and(|k| k == path_key)
+}
+
+/// Appends a key to a path, quoting the key if a bare key can't spell it.
+pub(crate) fn push_key(path: &mut String, key: &str) {
+	path.push('.');
+	let bare = !key.is_empty()
+		&& key
+			.chars()
+			.all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+	match bare {
+		true => path.push_str(key),
+		false => path.push_str(&serde_json::to_string(key).unwrap_or_default()),
+	}
+}
+
+/// A parser for expressions that start with a path.
+pub(crate) struct Parser<'e> {
+	what: &'static str,
+	expr: &'e str,
+	pos: usize,
+}
+
+impl<'e> Parser<'e> {
+	/// Creates a parser for an expression, where `what` describes the expression in errors.
+	pub(crate) fn new(what: &'static str, expr: &'e str) -> Parser<'e> {
+		Parser { what, expr, pos: 0 }
+	}
+
+	/// Returns the unparsed remainder of the expression.
+	pub(crate) fn rest(&self) -> &'e str {
+		&self.expr[self.pos..]
+	}
+
+	/// Advances past the next `len` bytes of the expression.
+	pub(crate) fn advance(&mut self, len: usize) {
+		self.pos += len;
+	}
+
+	pub(crate) fn skip_whitespace(&mut self) {
+		let rest = self.rest();
+		self.pos += rest.len() - rest.trim_start().len();
+	}
+
+	/// Returns an error at the current position of the parser.
+	pub(crate) fn error(&self, msg: &'static str) -> SyntaxError {
+		SyntaxError {
+			what: self.what,
+			expr: self.expr.into(),
+			pos: self.pos,
+			msg,
+		}
+	}
+
+	/// Parses a path, stopping at the first character that can't continue the path.
+	pub(crate) fn path(&mut self) -> Result<Vec<Segment>, SyntaxError> {
+		self.skip_whitespace();
+		if !self.rest().starts_with(['.', '[']) {
+			return Err(self.error("expected a path starting with '.'"));
+		}
+
+		let mut path = vec![];
+		loop {
+			let rest = self.rest();
+			if let Some(rest) = rest.strip_prefix('[') {
+				let len = rest.find(']').ok_or_else(|| self.error("unclosed '['"))?;
+				let index = rest[..len]
+					.trim()
+					.parse()
+					.map_err(|_| self.error("expected an array index"))?;
+				path.push(Segment::Index(index));
+				self.pos += len + 2;
+			} else if let Some(rest) = rest.strip_prefix('.').filter(|r| r.starts_with('"')) {
+				// Quoted keys follow JSON string syntax, so we let the JSON parser find the end.
+				let mut de = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+				let key = match de.next() {
+					Some(Ok(key)) => key,
+					_ => return Err(self.error("invalid quoted key")),
+				};
+				self.pos += 1 + de.byte_offset();
+				path.push(Segment::Key(key));
+			} else if let Some(rest) = rest.strip_prefix('.') {
+				let len = rest
+					.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+					.unwrap_or(rest.len());
+				if len > 0 {
+					path.push(Segment::Key(rest[..len].into()));
+				} else if !path.is_empty() {
+					return Err(self.error("expected a key after '.'"));
+				}
+				self.pos += 1 + len;
+			} else {
+				return Ok(path);
+			}
+		}
+	}
+}
+
+/// An error in the syntax of an expression.
+#[derive(Debug)]
+pub(crate) struct SyntaxError {
+	what: &'static str,
+	expr: String,
+	pos: usize,
+	msg: &'static str,
+}
+
+impl error::Error for SyntaxError {}
+
+impl fmt::Display for SyntaxError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"invalid {} {:?} at offset {}: {}",
+			self.what, self.expr, self.pos, self.msg
+		)
+	}
+}
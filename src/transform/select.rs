@@ -0,0 +1,231 @@
+//! Predicates for selecting documents by their contents.
+//!
+//! A predicate is a [path](super::path) into the document, optionally followed by a comparison
+//! against a JSON scalar literal:
+//!
+//! ```text
+//! .kind == "Deployment"
+//! .spec.replicas >= 3
+//! .metadata.labels."app.kubernetes.io/name" != "xt"
+//! .items[0].enabled
+//! ```
+//!
+//! A path without a comparison matches when it leads to a value other than null or `false`.
+
+use std::cmp::Ordering;
+
+use super::path::{self, Parser, Segment, SyntaxError};
+use crate::transcode::Value;
+
+/// A parsed document predicate.
+#[derive(Clone)]
+pub(super) struct Predicate {
+	path: Vec<Segment>,
+	comparison: Option<(Op, Scalar)>,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+/// A scalar value that a predicate can compare, or that a sort can order.
+#[derive(Clone)]
+pub(super) enum Scalar {
+	Null,
+	Bool(bool),
+	Int(i128),
+	Float(f64),
+	String(String),
+}
+
+impl Predicate {
+	pub(super) fn parse(expr: &str) -> Result<Predicate, SyntaxError> {
+		let mut parser = Parser::new("select expression", expr);
+		let path = parser.path()?;
+		parser.skip_whitespace();
+		if parser.rest().is_empty() {
+			return Ok(Predicate {
+				path,
+				comparison: None,
+			});
+		}
+		let op = parse_op(&mut parser)?;
+		let literal = parse_literal(&mut parser)?;
+		Ok(Predicate {
+			path,
+			comparison: Some((op, literal)),
+		})
+	}
+
+	/// Returns true if the document matches the predicate.
+	pub(super) fn matches(&self, value: &Value) -> bool {
+		let found = path::lookup(value, &self.path);
+
+		let Some((op, literal)) = &self.comparison else {
+			return !matches!(found, None | Some(Value::Unit | Value::Bool(false)));
+		};
+		let ordering = found
+			.and_then(Scalar::from_value)
+			.and_then(|s| s.compare(literal));
+		match op {
+			Op::Eq => ordering == Some(Ordering::Equal),
+			Op::Ne => ordering != Some(Ordering::Equal),
+			Op::Lt => ordering == Some(Ordering::Less),
+			Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+			Op::Gt => ordering == Some(Ordering::Greater),
+			Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+		}
+	}
+}
+
+impl Scalar {
+	pub(super) fn from_value(value: &Value) -> Option<Scalar> {
+		Some(match value {
+			Value::Unit => Scalar::Null,
+			Value::Bool(b) => Scalar::Bool(*b),
+			Value::I8(n) => Scalar::Int((*n).into()),
+			Value::I16(n) => Scalar::Int((*n).into()),
+			Value::I32(n) => Scalar::Int((*n).into()),
+			Value::I64(n) => Scalar::Int((*n).into()),
+			Value::I128(n) => Scalar::Int(*n),
+			Value::U8(n) => Scalar::Int((*n).into()),
+			Value::U16(n) => Scalar::Int((*n).into()),
+			Value::U32(n) => Scalar::Int((*n).into()),
+			Value::U64(n) => Scalar::Int((*n).into()),
+			Value::U128(n) => match i128::try_from(*n) {
+				Ok(n) => Scalar::Int(n),
+				#[allow(clippy::cast_precision_loss)]
+				Err(_) => Scalar::Float(*n as f64),
+			},
+			Value::F32(f) => Scalar::Float((*f).into()),
+			Value::F64(f) => Scalar::Float(*f),
+			Value::Char(c) => Scalar::String(c.to_string()),
+			Value::String(s) => Scalar::String(s.to_string()),
+			Value::Bytes(_) | Value::Seq(_) | Value::Map(_) => return None,
+		})
+	}
+
+	/// Compares two scalars, or returns `None` if they have incomparable types.
+	fn compare(&self, other: &Scalar) -> Option<Ordering> {
+		match (self, other) {
+			(Scalar::Null, Scalar::Null) => Some(Ordering::Equal),
+			(Scalar::Bool(a), Scalar::Bool(b)) => Some(a.cmp(b)),
+			(Scalar::Int(a), Scalar::Int(b)) => Some(a.cmp(b)),
+			(Scalar::String(a), Scalar::String(b)) => Some(a.cmp(b)),
+			(a, b) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+		}
+	}
+
+	#[allow(clippy::cast_precision_loss)]
+	fn as_f64(&self) -> Option<f64> {
+		match self {
+			Scalar::Int(n) => Some(*n as f64),
+			Scalar::Float(f) => Some(*f),
+			_ => None,
+		}
+	}
+}
+
+fn parse_op(parser: &mut Parser) -> Result<Op, SyntaxError> {
+	const OPS: &[(&str, Op)] = &[
+		("==", Op::Eq),
+		("!=", Op::Ne),
+		("<=", Op::Le),
+		(">=", Op::Ge),
+		("<", Op::Lt),
+		(">", Op::Gt),
+	];
+	let rest = parser.rest();
+	let &(token, op) = OPS
+		.iter()
+		.find(|(token, _)| rest.starts_with(token))
+		.ok_or_else(|| parser.error("expected a comparison operator"))?;
+	parser.advance(token.len());
+	Ok(op)
+}
+
+fn parse_literal(parser: &mut Parser) -> Result<Scalar, SyntaxError> {
+	parser.skip_whitespace();
+	let rest = parser.rest();
+	let literal = match serde_json::from_str(rest) {
+		Ok(serde_json::Value::Null) => Scalar::Null,
+		Ok(serde_json::Value::Bool(b)) => Scalar::Bool(b),
+		Ok(serde_json::Value::Number(n)) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+			(Some(n), _, _) => Scalar::Int(n.into()),
+			(_, Some(n), _) => Scalar::Int(n.into()),
+			(_, _, Some(f)) => Scalar::Float(f),
+			_ => return Err(parser.error("invalid number")),
+		},
+		Ok(serde_json::Value::String(s)) => Scalar::String(s),
+		_ => return Err(parser.error("expected a JSON scalar")),
+	};
+	parser.advance(rest.len());
+	Ok(literal)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serde::Deserialize;
+
+	#[test]
+	fn predicate_matching() {
+		const DOC: &str = r#"{
+			"kind": "Deployment",
+			"spec": {"replicas": 3, "paused": false},
+			"labels": {"app.kubernetes.io/name": "xt"},
+			"items": [{"enabled": true}],
+			"ratio": 0.5
+		}"#;
+		const CASES: &[(&str, bool)] = &[
+			(r#".kind == "Deployment""#, true),
+			(r#".kind != "Deployment""#, false),
+			(r#".kind == "Service""#, false),
+			(".spec.replicas >= 3", true),
+			(".spec.replicas > 3", false),
+			(".spec.replicas == 3.0", true),
+			(".ratio < 1", true),
+			(".spec.paused", false),
+			(".spec", true),
+			(".missing", false),
+			(".missing != 1", true),
+			(".missing == null", false),
+			(r#".labels."app.kubernetes.io/name" == "xt""#, true),
+			(".items[0].enabled", true),
+			(".items[1].enabled", false),
+			(r#".spec == "x""#, false),
+			(".", true),
+		];
+
+		let doc = Value::deserialize(&mut serde_json::Deserializer::from_str(DOC)).unwrap();
+		for &(expr, want) in CASES {
+			let predicate = Predicate::parse(expr).unwrap();
+			assert_eq!(predicate.matches(&doc), want, "{expr}");
+		}
+	}
+
+	#[test]
+	fn predicate_syntax_errors() {
+		const CASES: &[&str] = &[
+			"",
+			"kind == 1",
+			".kind = 1",
+			".kind == Deployment",
+			".kind == [1]",
+			".items[x]",
+			".items[0",
+			".a..b",
+			r#".labels."unclosed"#,
+		];
+		for &expr in CASES {
+			assert!(Predicate::parse(expr).is_err(), "{expr}");
+		}
+	}
+}
@@ -0,0 +1,258 @@
+//! Canonical ordering of map keys and array elements.
+//!
+//! Scalars order first by type, with null before booleans, booleans before numbers, and numbers
+//! before strings, then by value within each type. Integers and floats compare by their exact
+//! numeric values, and NaNs order after every other number.
+
+use std::cmp::Ordering;
+
+use super::select::Scalar;
+use crate::transcode::Value;
+
+/// Sorts the entries of every map by key, at any depth.
+///
+/// Entries with keys other than scalars keep their original order after every scalar key.
+pub(super) fn sort_keys(value: &mut Value) {
+	match value {
+		Value::Seq(seq) => seq.iter_mut().for_each(sort_keys),
+		Value::Map(map) => {
+			map.sort_by(|(a, _), (b, _)| {
+				compare_optional(Scalar::from_value(a), Scalar::from_value(b))
+			});
+			map.iter_mut().for_each(|(_, v)| sort_keys(v));
+		}
+		_ => {}
+	}
+}
+
+/// A preferred order of map keys, with the orders of the maps under each key.
+#[derive(Clone, Default)]
+pub(super) struct KeyOrder(Vec<(String, KeyOrder)>);
+
+impl KeyOrder {
+	/// The key that stands for every key that an order doesn't list.
+	const OTHERS: &str = "*";
+
+	/// Parses an order from a map whose keys list the order, with values that give the orders of
+	/// nested maps, or from an array of string keys with no nested orders.
+	///
+	/// Returns `None` for any other value as the root of an order, or for a map at any depth with
+	/// a key other than a string.
+	pub(super) fn from_value(value: Value) -> Option<KeyOrder> {
+		let keys = match value {
+			Value::Map(map) => map
+				.into_iter()
+				.map(|(k, v)| match (k, v) {
+					(Value::String(k), v @ (Value::Map(_) | Value::Seq(_))) => {
+						Some((k.into_owned(), KeyOrder::from_value(v)?))
+					}
+					(Value::String(k), _) => Some((k.into_owned(), KeyOrder::default())),
+					_ => None,
+				})
+				.collect::<Option<_>>()?,
+			Value::Seq(seq) => seq
+				.into_iter()
+				.map(|v| match v {
+					Value::String(k) => Some((k.into_owned(), KeyOrder::default())),
+					_ => None,
+				})
+				.collect::<Option<_>>()?,
+			_ => return None,
+		};
+		Some(KeyOrder(keys))
+	}
+
+	/// Returns the position of a key in the order, and the order of the map under it.
+	fn find(&self, key: &Value) -> Option<(usize, &KeyOrder)> {
+		let i = (self.0.iter())
+			.position(|(k, _)| super::path::key_matches(key, k))
+			.or_else(|| self.0.iter().position(|(k, _)| k == Self::OTHERS))?;
+		Some((i, &self.0[i].1))
+	}
+}
+
+/// Orders the entries of every map by the position of their keys in an order, and the maps
+/// under those keys by the orders nested under them, including maps within arrays.
+///
+/// Keys that the order doesn't list keep their original order at the position of the `*` key,
+/// or after every listed key without one.
+pub(super) fn order_keys(value: &mut Value, order: &KeyOrder) {
+	match value {
+		Value::Seq(seq) => seq.iter_mut().for_each(|v| order_keys(v, order)),
+		Value::Map(map) if !order.0.is_empty() => {
+			map.sort_by_key(|(k, _)| order.find(k).map_or(order.0.len(), |(i, _)| i));
+			for (k, v) in map {
+				if let Some((_, nested)) = order.find(k) {
+					order_keys(v, nested);
+				}
+			}
+		}
+		_ => {}
+	}
+}
+
+/// Sorts every array of scalars at any depth, along with every array of maps when `by` names a
+/// key to sort them by.
+///
+/// Maps without the key, or with a value other than a scalar for it, keep their original order
+/// before every other map. Arrays mixing scalars with other values, or maps with other values,
+/// are unchanged.
+pub(super) fn sort_arrays(value: &mut Value, by: Option<&str>) {
+	match value {
+		Value::Seq(seq) => {
+			seq.iter_mut().for_each(|v| sort_arrays(v, by));
+			if seq.iter().all(|v| Scalar::from_value(v).is_some()) {
+				seq.sort_by(|a, b| compare_optional(Scalar::from_value(a), Scalar::from_value(b)));
+			} else if let Some(key) = by {
+				if seq.iter().all(|v| matches!(v, Value::Map(_))) {
+					seq.sort_by(|a, b| {
+						let (a, b) = (map_scalar(a, key), map_scalar(b, key));
+						match (a, b) {
+							(Some(a), Some(b)) => compare(&a, &b),
+							(a, b) => a.is_some().cmp(&b.is_some()),
+						}
+					});
+				}
+			}
+		}
+		Value::Map(map) => map.iter_mut().for_each(|(_, v)| sort_arrays(v, by)),
+		_ => {}
+	}
+}
+
+/// Returns the scalar value of `key` in a map.
+fn map_scalar(value: &Value, key: &str) -> Option<Scalar> {
+	let Value::Map(map) = value else {
+		return None;
+	};
+	map.iter()
+		.find(|(k, _)| super::path::key_matches(k, key))
+		.and_then(|(_, v)| Scalar::from_value(v))
+}
+
+/// Compares two optional values in the canonical order of their scalars, ordering values other
+/// than scalars, and `None`, after every scalar.
+pub(crate) fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+	compare_optional(
+		a.and_then(Scalar::from_value),
+		b.and_then(Scalar::from_value),
+	)
+}
+
+/// Compares two optional scalars, ordering `None` after every scalar.
+fn compare_optional(a: Option<Scalar>, b: Option<Scalar>) -> Ordering {
+	match (a, b) {
+		(Some(a), Some(b)) => compare(&a, &b),
+		(a, b) => a.is_none().cmp(&b.is_none()),
+	}
+}
+
+/// Compares two scalars in the canonical order.
+fn compare(a: &Scalar, b: &Scalar) -> Ordering {
+	fn rank(s: &Scalar) -> u8 {
+		match s {
+			Scalar::Null => 0,
+			Scalar::Bool(_) => 1,
+			Scalar::Int(_) | Scalar::Float(_) => 2,
+			Scalar::String(_) => 3,
+		}
+	}
+
+	match (a, b) {
+		(Scalar::Bool(a), Scalar::Bool(b)) => a.cmp(b),
+		(Scalar::Int(a), Scalar::Int(b)) => a.cmp(b),
+		(Scalar::Float(a), Scalar::Float(b)) => compare_floats(*a, *b),
+		(Scalar::Int(a), Scalar::Float(b)) => compare_int_float(*a, *b),
+		(Scalar::Float(a), Scalar::Int(b)) => compare_int_float(*b, *a).reverse(),
+		(Scalar::String(a), Scalar::String(b)) => a.cmp(b),
+		(a, b) => rank(a).cmp(&rank(b)),
+	}
+}
+
+/// Compares two floats, ordering NaNs after every other value.
+fn compare_floats(a: f64, b: f64) -> Ordering {
+	match (a.is_nan(), b.is_nan()) {
+		(false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+		(a, b) => a.cmp(&b),
+	}
+}
+
+/// Compares an integer and a float by their exact values, ordering NaNs after every integer.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn compare_int_float(i: i128, f: f64) -> Ordering {
+	// 2^127 is exactly representable as a float, and bounds the range of i128 on both sides.
+	const BOUND: f64 = i128::MAX as f64;
+	if f.is_nan() || f >= BOUND {
+		return Ordering::Less;
+	}
+	if f < -BOUND {
+		return Ordering::Greater;
+	}
+	let trunc = f.trunc();
+	i.cmp(&(trunc as i128)).then_with(|| {
+		// The integer parts are equal, so any fractional part breaks the tie.
+		0.0.partial_cmp(&(f - trunc)).unwrap_or(Ordering::Equal)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serde::Deserialize;
+
+	fn apply_json(f: impl Fn(&mut Value), input: &str) -> String {
+		let mut value = Value::deserialize(&mut serde_json::Deserializer::from_str(input)).unwrap();
+		f(&mut value);
+		serde_json::to_string(&value).unwrap()
+	}
+
+	#[test]
+	fn sort_keys_at_any_depth() {
+		assert_eq!(
+			apply_json(sort_keys, r#"{"b":1,"a":[{"d":1,"c":2}],"A":null}"#),
+			r#"{"A":null,"a":[{"c":2,"d":1}],"b":1}"#
+		);
+	}
+
+	#[test]
+	fn sort_arrays_of_scalars_and_maps() {
+		const CASES: &[(Option<&str>, &str, &str)] = &[
+			(
+				None,
+				r#"["b",1.5,null,true,-2,"a",false,1]"#,
+				r#"[null,false,true,-2,1,1.5,"a","b"]"#,
+			),
+			(None, r#"[3,[2,1],"x"]"#, r#"[3,[1,2],"x"]"#),
+			(
+				Some("name"),
+				r#"[{"name":"b"},{"id":1},{"name":"a","v":[2,1]}]"#,
+				r#"[{"id":1},{"name":"a","v":[1,2]},{"name":"b"}]"#,
+			),
+			(
+				None,
+				r#"[{"name":"b"},{"name":"a"}]"#,
+				r#"[{"name":"b"},{"name":"a"}]"#,
+			),
+		];
+		for &(by, input, want) in CASES {
+			assert_eq!(apply_json(|v| sort_arrays(v, by), input), want, "{input}");
+		}
+	}
+
+	#[test]
+	fn compare_int_float_exactly() {
+		assert_eq!(compare_int_float(1, 1.0), Ordering::Equal);
+		assert_eq!(compare_int_float(1, 1.5), Ordering::Less);
+		assert_eq!(compare_int_float(-1, -1.5), Ordering::Greater);
+		assert_eq!(
+			compare_int_float(i128::from(u64::MAX), 1e30),
+			Ordering::Less
+		);
+		assert_eq!(
+			compare_int_float(i128::MIN, f64::NEG_INFINITY),
+			Ordering::Greater
+		);
+		assert_eq!(compare_int_float(0, f64::NAN), Ordering::Less);
+	}
+}
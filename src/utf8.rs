@@ -0,0 +1,95 @@
+//! Repair of invalid UTF-8 in inputs whose strings must be valid UTF-8.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str;
+
+/// The invalid UTF-8 that the translator replaced in one input with a
+/// [UTF-8 repair handler](crate::Translator::set_utf8_repair_handler).
+#[derive(Debug, Clone)]
+pub struct Utf8Repair {
+	source: Option<String>,
+	count: usize,
+}
+
+impl Utf8Repair {
+	/// Returns the [source name](crate::Translator::set_source_name) of the repaired input, for a
+	/// named input.
+	pub fn source(&self) -> Option<&str> {
+		self.source.as_deref()
+	}
+
+	/// Returns the number of invalid UTF-8 sequences that the translator replaced.
+	pub fn count(&self) -> usize {
+		self.count
+	}
+}
+
+impl fmt::Display for Utf8Repair {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let count = self.count;
+		let s = if count == 1 { "" } else { "s" };
+		write!(f, "replaced {count} invalid UTF-8 sequence{s} with U+FFFD")
+	}
+}
+
+/// A function that handles the repairs to each input.
+pub(crate) type RepairHandler = dyn FnMut(Utf8Repair) + Send;
+
+/// Replaces invalid UTF-8 in a JSON input, or in the strings of a MessagePack input, with U+FFFD,
+/// and tells the handler how many sequences it replaced if there were any.
+pub(crate) fn repair<'i>(
+	input: Cow<'i, [u8]>,
+	msgpack: bool,
+	source: Option<&str>,
+	handler: &mut RepairHandler,
+) -> Cow<'i, [u8]> {
+	let (input, count) = match msgpack {
+		true => crate::msgpack::repair_strings(input),
+		false => lossy(input),
+	};
+	if count > 0 {
+		handler(Utf8Repair {
+			source: source.map(Into::into),
+			count,
+		});
+	}
+	input
+}
+
+/// Replaces each invalid UTF-8 sequence in the input with U+FFFD, as
+/// [`String::from_utf8_lossy`] does, and returns the result with the number of sequences replaced.
+pub(crate) fn lossy(input: Cow<'_, [u8]>) -> (Cow<'_, [u8]>, usize) {
+	let mut count = 0;
+	let mut rest = &input[..];
+	while let Err(err) = str::from_utf8(rest) {
+		count += 1;
+		match err.error_len() {
+			Some(len) => rest = &rest[err.valid_up_to() + len..],
+			None => break,
+		}
+	}
+	if count == 0 {
+		return (input, 0);
+	}
+	let text = String::from_utf8_lossy(&input).into_owned();
+	(Cow::Owned(text.into_bytes()), count)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lossy_counts_sequences() {
+		for (input, want, count) in [
+			(&b"ok"[..], "ok", 0),
+			(b"a\xffb\xc3", "a\u{fffd}b\u{fffd}", 2),
+			(b"\xf0\x9f\x98x", "\u{fffd}x", 1),
+		] {
+			let (repaired, n) = lossy(Cow::Borrowed(input));
+			assert_eq!(str::from_utf8(&repaired), Ok(want));
+			assert_eq!(n, count, "{input:?}");
+		}
+	}
+}
@@ -0,0 +1,217 @@
+//! Selection of a contiguous range of documents from the stream a translator produces.
+//!
+//! A window skips some number of documents from the start of the stream, takes at most some
+//! number of documents after those (the "head"), and optionally holds back all but the last few
+//! documents it takes (the "tail") until the translator finishes the stream. The window counts
+//! documents across every input of a translator, after any transforms have run.
+//!
+//! When the head is full, the window stops translation of the current input with a [`Stop`]
+//! error that the translator swallows, so that it doesn't have to read any more input. A window
+//! with an interrupt flag also counts as full once the flag is set, so that an interrupted
+//! translation stops at the next document boundary.
+
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::transcode::Value;
+use crate::{intern, msgpack};
+
+/// The range of documents that a translator outputs.
+#[derive(Default)]
+pub(crate) struct Window {
+	skip: u64,
+	head: Option<u64>,
+	tail: Option<usize>,
+	seen: u64,
+	taken: u64,
+	held: VecDeque<Value<'static>>,
+	/// Whether held documents take their map keys from the [`intern`] pool.
+	intern: bool,
+	interrupt: Option<Arc<AtomicBool>>,
+}
+
+impl Window {
+	pub(crate) fn set_skip(&mut self, skip: u64) {
+		self.skip = skip;
+	}
+
+	pub(crate) fn set_head(&mut self, head: Option<u64>) {
+		self.head = head;
+	}
+
+	pub(crate) fn set_tail(&mut self, tail: Option<usize>) {
+		self.tail = tail;
+	}
+
+	pub(crate) fn set_intern_keys(&mut self, intern: bool) {
+		self.intern = intern;
+	}
+
+	pub(crate) fn set_interrupt(&mut self, interrupt: Option<Arc<AtomicBool>>) {
+		self.interrupt = interrupt;
+	}
+
+	/// Returns true if the window won't accept any more documents.
+	pub(crate) fn is_full(&self) -> bool {
+		self.head.is_some_and(|head| self.taken >= head)
+			|| (self.interrupt.as_ref()).is_some_and(|flag| flag.load(Ordering::Relaxed))
+	}
+
+	/// Takes the documents held back for the tail of the stream.
+	pub(crate) fn take_held(&mut self) -> VecDeque<Value<'static>> {
+		mem::take(&mut self.held)
+	}
+
+	/// Counts a new document, returning true if the document falls within the window.
+	fn admit(&mut self) -> crate::Result<bool> {
+		if self.is_full() {
+			return Err(Stop.into());
+		}
+		self.seen += 1;
+		if self.seen <= self.skip {
+			return Ok(false);
+		}
+		self.taken += 1;
+		Ok(true)
+	}
+
+	/// Holds a document for the tail of the stream, returning it back if there's no tail.
+	fn hold<'a>(&mut self, value: Value<'a>) -> Option<Value<'a>> {
+		let Some(tail) = self.tail else {
+			return Some(value);
+		};
+		if tail > 0 {
+			if self.held.len() == tail {
+				self.held.pop_front();
+			}
+			self.held.push_back(intern::into_owned(value, self.intern));
+		}
+		None
+	}
+
+	/// Returns a [`Stop`] error if the document just taken filled the window.
+	fn check_full(&self) -> crate::Result<()> {
+		if self.is_full() {
+			Err(Stop.into())
+		} else {
+			Ok(())
+		}
+	}
+}
+
+/// An error that stops translation once a [`Window`] is full.
+#[derive(Debug)]
+pub(crate) struct Stop;
+
+impl Stop {
+	/// Returns true if the error is a [`Stop`].
+	pub(crate) fn is(err: &crate::Error) -> bool {
+		err.as_ref().is::<Stop>()
+	}
+}
+
+impl error::Error for Stop {}
+
+impl fmt::Display for Stop {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("document window is full")
+	}
+}
+
+/// An [`Output`](crate::Output) that only passes through the documents within a [`Window`].
+pub(crate) struct Output<'w, O> {
+	output: O,
+	window: &'w mut Window,
+}
+
+impl<'w, O> Output<'w, O> {
+	pub(crate) fn new(output: O, window: &'w mut Window) -> Output<'w, O> {
+		Output { output, window }
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if !self.window.admit()? {
+			de::IgnoredAny::deserialize(de)?;
+			return Ok(());
+		}
+		if self.window.tail.is_some() {
+			self.window.hold(Value::deserialize(de)?);
+		} else {
+			self.output.transcode_from(de)?;
+		}
+		self.window.check_full()
+	}
+
+	fn transcode_borrowed_from<D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'i, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		if !self.window.admit()? {
+			de::IgnoredAny::deserialize(de)?;
+			return Ok(());
+		}
+		if self.window.tail.is_some() {
+			self.window.hold(Value::deserialize(de)?);
+		} else {
+			self.output.transcode_borrowed_from(de)?;
+		}
+		self.window.check_full()
+	}
+
+	fn transcode_msgpack(&mut self, input: &[u8]) -> crate::Result<()> {
+		if self.window.tail.is_some() {
+			return msgpack::transcode_document(self, input);
+		}
+		if !self.window.admit()? {
+			return Ok(());
+		}
+		self.output.transcode_msgpack(input)?;
+		self.window.check_full()
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		if !self.window.admit()? {
+			return Ok(());
+		}
+		if let Some(value) = self.window.hold(value) {
+			self.output.transcode_value(value)?;
+		}
+		self.window.check_full()
+	}
+
+	fn transcode_borrowed_value(&mut self, value: Value<'i>) -> crate::Result<()> {
+		if !self.window.admit()? {
+			return Ok(());
+		}
+		if let Some(value) = self.window.hold(value) {
+			self.output.transcode_borrowed_value(value)?;
+		}
+		self.window.check_full()
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
@@ -1,63 +1,158 @@
 //! The YAML data format.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::io::{self, BufRead, BufReader, Write};
 use std::str;
 
-use serde::{de, ser};
+use serde::Deserialize;
+use serde::de;
 
-use crate::input::{self, Input, Ref};
+use crate::comment::Comments;
+use crate::detect::Trial;
+use crate::input::{self, Budget, Input, Ref};
 use crate::transcode;
 
+pub(crate) mod blocks;
 mod chunker;
 mod encoding;
+pub(crate) mod layout;
+pub(crate) mod lint;
+mod parser;
 
-use self::chunker::Chunker;
+use self::blocks::YamlBlockStyle;
+use self::chunker::{Chunker, Document};
 use self::encoding::{Encoder, Encoding};
+use self::lint::Lint;
 
-pub(crate) fn input_matches(mut input: Ref) -> io::Result<bool> {
+/// The linting of a YAML input, along with the name of the input.
+pub(crate) type Linter<'l, 'h, 's> = (&'l RefCell<&'h mut Lint>, Option<&'s str>);
+
+/// Returns the text encoding that an input appears to have from its first few bytes, by the rules
+/// that YAML 1.2 defines for detecting the encoding of a stream.
+///
+/// The rules rely on every valid stream starting with a byte order mark or an ASCII character, so
+/// they suit JSON and TOML inputs too, but not MessagePack inputs.
+pub(crate) fn input_encoding(mut input: Ref) -> io::Result<crate::Encoding> {
+	Ok(
+		match Encoding::detect(input.prefix(Encoding::DETECT_LEN)?) {
+			Encoding::Utf8 => crate::Encoding::Utf8,
+			Encoding::Utf16Big => crate::Encoding::Utf16Be,
+			Encoding::Utf32Big => crate::Encoding::Utf32Be,
+			Encoding::Utf16Little => crate::Encoding::Utf16Le,
+			Encoding::Utf32Little => crate::Encoding::Utf32Le,
+		},
+	)
+}
+
+pub(crate) fn input_matches(mut input: Ref) -> io::Result<Trial> {
 	// YAML can be surprisingly liberal in what it accepts. Many non-YAML text documents can be
 	// parsed as a YAML scalar (i.e. a giant string), including TOML documents that start with
 	// plain key-value assignments rather than a table header. To prevent these kinds of weird
 	// matches, we only detect input as YAML when the first document in the stream encodes a
 	// collection (map or sequence).
 	let encoding = Encoding::detect(input.prefix(Encoding::DETECT_LEN)?);
-	let chunk = match &mut input {
-		Ref::Slice(b) => Chunker::new(Encoder::new(b, encoding)).next(),
-		Ref::Reader(r) => Chunker::new(Encoder::new(BufReader::new(r), encoding)).next(),
-	};
-	match chunk {
-		Some(Ok(doc)) => Ok(doc.is_collection()),
-		Some(Err(err)) if err.kind() == io::ErrorKind::InvalidData => Ok(false),
+	let is_collection = |chunk: Option<io::Result<&Document>>| match chunk {
+		Some(Ok(doc)) if doc.is_collection() => Ok(Ok(())),
+		Some(Ok(_)) => Ok(Err("first document is not a map or sequence".into())),
+		Some(Err(err)) if err.kind() == io::ErrorKind::InvalidData => Ok(Err(err.to_string())),
 		Some(Err(err)) => Err(err),
-		None => Ok(false),
+		None => Ok(Err("input contains no documents".into())),
+	};
+	match &mut input {
+		Ref::Slice(b) => is_collection(
+			Chunker::new(Encoder::new(b, encoding), Budget::UNLIMITED).next_document(),
+		),
+		Ref::Reader(r) => {
+			let budget = r.budget();
+			let mut chunker = Chunker::new(Encoder::new(BufReader::new(r), encoding), budget);
+			is_collection(chunker.next_document())
+		}
 	}
 }
 
-pub(crate) fn transcode<O>(input: input::Handle, mut output: O) -> crate::Result<()>
+/// Transcodes YAML input to the output.
+///
+/// With `reject_risky`, plain scalars that YAML 1.1 would read as something other than a string
+/// are errors.
+///
+/// With `sizes`, each document reports its size to the output before it's parsed, so that the
+/// output can turn away documents larger than a maximum size. With `spans`, each document of a
+/// UTF-8 stream reports its span to the output before it's parsed.
+pub(crate) fn transcode<'i, O>(
+	input: input::Handle<'i>,
+	mut output: O,
+	lint: Option<Linter>,
+	reject_risky: bool,
+	sizes: bool,
+	spans: bool,
+) -> crate::Result<()>
 where
-	O: crate::Output,
+	O: crate::Output<'i>,
 {
-	match input.into() {
-		Input::Reader(r) => transcode_reader(BufReader::new(r), output),
-		Input::Slice(b) => match str::from_utf8(&b) {
+	let budget = input.budget();
+	let checks = Checks {
+		lint,
+		reject_risky,
+		sizes,
+		spans,
+	};
+	match Input::try_from(input)? {
+		Input::Reader(r) => transcode_reader(BufReader::new(r), output, budget, checks),
+		// Only the chunker's parser sees the style of each scalar, and the bounds of each
+		// document, so checking either one reads slices the same way as readers.
+		Input::Slice(b) if checks.is_enabled() || checks.sizes || checks.spans => {
+			transcode_reader(&*b, output, Budget::UNLIMITED, checks)
+		}
+		Input::Slice(Cow::Borrowed(b)) => match str::from_utf8(b) {
 			Ok(s) => {
 				for de in serde_yaml::Deserializer::from_str(s) {
-					output.transcode_from(de)?;
+					output.transcode_borrowed_from(de)?;
 				}
 				Ok(())
 			}
 			Err(_) => {
 				// The reader path re-encodes UTF-16 and UTF-32. See transcode_reader for details.
-				transcode_reader(&*b, output)
+				transcode_reader(b, output, Budget::UNLIMITED, Checks::default())
 			}
 		},
+		Input::Slice(Cow::Owned(b)) => match str::from_utf8(&b) {
+			Ok(s) => {
+				for de in serde_yaml::Deserializer::from_str(s) {
+					output.transcode_from(de)?;
+				}
+				Ok(())
+			}
+			Err(_) => transcode_reader(&*b, output, Budget::UNLIMITED, Checks::default()),
+		},
+	}
+}
+
+/// The checks of the plain scalars in a YAML input, and of the size of its documents, along with
+/// whether its documents report their spans.
+#[derive(Default)]
+struct Checks<'l, 'h, 's> {
+	lint: Option<Linter<'l, 'h, 's>>,
+	reject_risky: bool,
+	sizes: bool,
+	spans: bool,
+}
+
+impl Checks<'_, '_, '_> {
+	fn is_enabled(&self) -> bool {
+		self.lint.is_some() || self.reject_risky
 	}
 }
 
-fn transcode_reader<R, O>(input: R, mut output: O) -> crate::Result<()>
+fn transcode_reader<'i, R, O>(
+	input: R,
+	mut output: O,
+	budget: Budget,
+	checks: Checks,
+) -> crate::Result<()>
 where
 	R: BufRead,
-	O: crate::Output,
+	O: crate::Output<'i>,
 {
 	// serde_yaml imposes a couple of interesting limitations on us, which aren't clear from its
 	// documentation but are reflected in this usage.
@@ -75,44 +170,104 @@ where
 	// the full YAML spec, which allows BOMs in UTF-8 streams and at the starts of individual
 	// documents in the stream. Hopefully these cases are rarer than that of a single BOM at the
 	// start of a UTF-16 or UTF-32 stream.
-	for doc in Chunker::new(Encoder::from_reader(input)?) {
+	let (input, encoding) = Encoder::from_reader(input)?;
+	// Offsets in a re-encoded stream don't match those of the original input.
+	let spans = checks.spans && encoding == Encoding::Utf8;
+	let mut chunker = Chunker::new(input, budget);
+	chunker.set_lint(checks.is_enabled());
+	let mut index = 0;
+	while let Some(doc) = chunker.next_document() {
 		let doc = doc?;
+		if let Some((lint, source)) = &checks.lint {
+			for scalar in doc.risky_scalars() {
+				lint.borrow_mut().warn_input(scalar, index, *source);
+			}
+		}
+		if let (true, Some(scalar)) = (checks.reject_risky, doc.risky_scalars().first()) {
+			return Err(scalar.to_error());
+		}
+		index += 1;
+		let len = doc.content().len() as u64;
+		if checks.sizes {
+			output.set_document_size(len);
+		}
+		if spans {
+			output.set_document_span(doc.offset(), doc.offset() + len);
+		}
 		let de = serde_yaml::Deserializer::from_str(doc.content());
 		output.transcode_from(de)?;
 	}
 	Ok(())
 }
 
-pub(crate) struct Output<W: Write>(W);
+pub(crate) struct Output<W: Write> {
+	w: W,
+	blocks: YamlBlockStyle,
+	comments: Comments,
+}
 
 impl<W: Write> Output<W> {
 	pub(crate) fn new(w: W) -> Output<W> {
-		Output(w)
+		Output {
+			w,
+			blocks: YamlBlockStyle::Auto,
+			comments: Comments::default(),
+		}
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.w
+	}
+
+	pub(crate) fn set_block_style(&mut self, style: YamlBlockStyle) {
+		self.blocks = style;
+	}
+
+	pub(crate) fn set_comments(&mut self, comments: Comments) {
+		self.comments = comments;
+	}
+
+	/// Returns true if the output writes documents in anything but the serializer's own style,
+	/// which requires each document in memory.
+	pub(crate) fn is_styled(&self) -> bool {
+		self.blocks != YamlBlockStyle::Auto || !self.comments.is_empty()
+	}
+
+	fn to_string(&self, value: &transcode::Value) -> crate::Result<String> {
+		Ok(match self.blocks {
+			YamlBlockStyle::Auto => serde_yaml::to_string(value)?,
+			style => blocks::to_string(value.clone(), style)?,
+		})
 	}
 }
 
-impl<W: Write> crate::Output for Output<W> {
+impl<W: Write> crate::Output<'_> for Output<W> {
 	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
 	where
 		D: de::Deserializer<'de, Error = E>,
 		E: de::Error + Send + Sync + 'static,
 	{
-		writeln!(&mut self.0, "---")?;
-		let mut ser = serde_yaml::Serializer::new(&mut self.0);
+		if self.is_styled() {
+			return self.transcode_value(transcode::Value::deserialize(de)?);
+		}
+		writeln!(&mut self.w, "---")?;
+		let mut ser = serde_yaml::Serializer::new(&mut self.w);
 		transcode::transcode(&mut ser, de)?;
 		Ok(())
 	}
 
-	fn transcode_value<S>(&mut self, value: S) -> crate::Result<()>
-	where
-		S: ser::Serialize,
-	{
-		writeln!(&mut self.0, "---")?;
-		serde_yaml::to_writer(&mut self.0, &value)?;
+	fn transcode_value(&mut self, value: transcode::Value) -> crate::Result<()> {
+		writeln!(&mut self.w, "---")?;
+		if !self.is_styled() {
+			serde_yaml::to_writer(&mut self.w, &value)?;
+			return Ok(());
+		}
+		let text = self.comments.write(&value, |value| self.to_string(value))?;
+		self.w.write_all(text.as_bytes())?;
 		Ok(())
 	}
 
 	fn flush(&mut self) -> io::Result<()> {
-		self.0.flush()
+		self.w.flush()
 	}
 }
@@ -0,0 +1,258 @@
+//! Block scalars for the multi-line strings of YAML output.
+//!
+//! The YAML serializer chooses the style of each string by itself, and falls back to double
+//! quotes with `\n` escapes for many multi-line strings that a block scalar could represent, like
+//! those with trailing spaces or tabs. To choose the style instead, output stands a unique plain
+//! placeholder in for each multi-line string value, then replaces each placeholder in the
+//! serialized text with a block scalar of its own.
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+
+use crate::transcode::Value;
+
+/// The style of the multi-line strings of YAML output, as set by
+/// [`Translator::set_yaml_block_style`](crate::Translator::set_yaml_block_style).
+///
+/// Block scalars carry a chomping indicator that keeps the exact number of line breaks at the
+/// end of the string: `|-` for none, `|` for one, and `|+` for more. Strings that no block scalar
+/// can represent, like those with carriage returns or other control characters, stay in double
+/// quotes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum YamlBlockStyle {
+	/// Let the serializer choose, which writes literal block scalars for most multi-line strings
+	/// but double quotes for some that don't need them.
+	#[default]
+	Auto,
+	/// Write every multi-line string that a block scalar can represent as a literal block scalar
+	/// (`|`), which keeps each line as it is.
+	Literal,
+	/// Write multi-line strings as folded block scalars (`>`), which wrap long lines at spaces,
+	/// or as literal block scalars for strings with lines that start with whitespace, which
+	/// folding would change.
+	Folded,
+}
+
+/// The width at which folded block scalars wrap lines that have spaces to wrap at.
+const WIDTH: usize = 80;
+
+/// Serializes a YAML document with its multi-line string values in the given block style.
+pub(crate) fn to_string(mut value: Value, style: YamlBlockStyle) -> serde_yaml::Result<String> {
+	let mut prefix = String::from("xt-block-");
+	while value.contains_str(&prefix) {
+		prefix.push('-');
+	}
+	let mut blocks = vec![];
+	substitute(&mut value, &prefix, &mut blocks);
+	let text = serde_yaml::to_string(&value)?;
+	if blocks.is_empty() {
+		return Ok(text);
+	}
+
+	let mut out = String::with_capacity(text.len());
+	let mut lines = text.split('\n').peekable();
+	while let Some(line) = lines.next() {
+		match placeholder(line, &prefix)
+			.and_then(|(before, parent, i)| Some((before, parent, blocks.get(i)?)))
+		{
+			Some((before, parent, s)) => push_block(&mut out, before, parent, s, style),
+			None => out.push_str(line),
+		}
+		if lines.peek().is_some() {
+			out.push('\n');
+		}
+	}
+	Ok(out)
+}
+
+/// Replaces every multi-line string value that a block scalar can represent with a placeholder
+/// ending in its index among the blocks.
+fn substitute(value: &mut Value, prefix: &str, blocks: &mut Vec<String>) {
+	match value {
+		Value::String(s) if representable(s) => {
+			let placeholder = format!("{prefix}{}", blocks.len());
+			let s = std::mem::replace(s, Cow::Owned(placeholder));
+			blocks.push(s.into_owned());
+		}
+		Value::Seq(seq) => {
+			for v in seq {
+				substitute(v, prefix, blocks);
+			}
+		}
+		Value::Map(map) => {
+			for (_, v) in map {
+				substitute(v, prefix, blocks);
+			}
+		}
+		_ => {}
+	}
+}
+
+/// Returns true if a string spans multiple lines, and a block scalar can represent it exactly.
+fn representable(s: &str) -> bool {
+	let unbreakable = |c: char| {
+		(c.is_control() && !matches!(c, '\n' | '\t'))
+			|| matches!(c, '\u{feff}' | '\u{2028}' | '\u{2029}')
+	};
+	s.contains('\n') && !s.trim().is_empty() && !s.contains(unbreakable)
+}
+
+/// Splits a line of serialized YAML that ends with a placeholder into the text before the
+/// placeholder, the column of the node that contains it (or `None` at the root of the
+/// document), and the index of its block.
+fn placeholder<'t>(line: &'t str, prefix: &str) -> Option<(&'t str, Option<usize>, usize)> {
+	let start = line.rfind(prefix)?;
+	let index = line[start + prefix.len()..].parse().ok()?;
+	let before = &line[..start];
+	let mut rest = before.trim_start_matches(' ');
+	let mut parent = None;
+	let mut col = before.len() - rest.len();
+	while let Some(item) = rest.strip_prefix("- ") {
+		parent = Some(col);
+		(rest, col) = (item, col + 2);
+	}
+	match rest {
+		"" => Some((before, parent, index)),
+		_ if rest.ends_with(": ") => Some((before, Some(col), index)),
+		_ => None,
+	}
+}
+
+/// Writes a block scalar in place of a placeholder, after the text that came before it, for a
+/// node at the given column.
+fn push_block(
+	out: &mut String,
+	before: &str,
+	parent: Option<usize>,
+	s: &str,
+	style: YamlBlockStyle,
+) {
+	let body = s.trim_end_matches('\n');
+	let chomp = match s.len() - body.len() {
+		0 => "-",
+		1 => "",
+		_ => "+",
+	};
+	let lines: Vec<&str> = body.split('\n').collect();
+	let indented =
+		{ lines.iter().find(|line| !line.is_empty()) }.is_some_and(|line| line.starts_with(' '));
+	// An indentation indicator counts from the indentation of the parent node, which the root
+	// of a document doesn't clearly have.
+	if indented && parent.is_none() {
+		out.push_str(before);
+		out.push_str(&serde_json::to_string(s).expect("strings should serialize"));
+		return;
+	}
+	let indent = parent.map_or(2, |col| col + 2);
+	let folded =
+		style == YamlBlockStyle::Folded && !lines.iter().any(|line| line.starts_with([' ', '\t']));
+	let indicator = if indented { "2" } else { "" };
+	let _ = write!(
+		out,
+		"{before}{}{indicator}{chomp}",
+		if folded { '>' } else { '|' }
+	);
+
+	let mut content = vec![];
+	if folded {
+		// A single line break between lines of text folds into a space, so each line break
+		// that follows text needs an empty line to keep it.
+		let mut text = false;
+		for line in &lines {
+			if line.is_empty() {
+				content.push("");
+				continue;
+			}
+			if text {
+				content.push("");
+			}
+			content.extend(wrap(line, WIDTH.saturating_sub(indent).max(20)));
+			text = true;
+		}
+	} else {
+		content = lines;
+	}
+	for line in content {
+		out.push('\n');
+		if !line.is_empty() {
+			out.extend(std::iter::repeat_n(' ', indent));
+			out.push_str(line);
+		}
+	}
+	for _ in 1..s.len() - body.len() {
+		out.push('\n');
+	}
+}
+
+/// Splits a line into pieces of at most `width` characters where possible, at single spaces
+/// between characters other than whitespace, which a folded block scalar joins back together.
+fn wrap(line: &str, width: usize) -> Vec<&str> {
+	let mut pieces = vec![];
+	let mut rest = line;
+	while rest.chars().count() > width {
+		let bytes = rest.as_bytes();
+		let blank = |i: usize| matches!(bytes[i], b' ' | b'\t');
+		let breaks = (1..bytes.len().saturating_sub(1))
+			.filter(|&i| bytes[i] == b' ' && !blank(i - 1) && !blank(i + 1));
+		let mut chosen = None;
+		for i in breaks {
+			if chosen.is_some() && rest[..i].chars().count() > width {
+				break;
+			}
+			chosen = Some(i);
+		}
+		let Some(i) = chosen else {
+			break;
+		};
+		pieces.push(&rest[..i]);
+		rest = &rest[i + 1..];
+	}
+	pieces.push(rest);
+	pieces
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn block_scalars_round_trip() {
+		let long = "word ".repeat(30);
+		let strings = [
+			"a\nb",
+			"a\nb\n",
+			"a\nb\n\n\n",
+			"trail  \n\ttab\n",
+			"  indented\nx",
+			"\n\nleading",
+			"a\n  \nb",
+			"para one\n\npara two\nline",
+			long.trim_end(),
+		];
+		let value = Value::Map(vec![
+			(
+				Value::String("k".into()),
+				Value::Seq(strings.iter().map(|&s| Value::String(s.into())).collect()),
+			),
+			(
+				Value::String("nested".into()),
+				Value::Map(vec![(
+					Value::String("xt-block-0".into()),
+					Value::String("x\r\ny".into()),
+				)]),
+			),
+		]);
+		let want = serde_json::to_value(&value).unwrap();
+		for style in [YamlBlockStyle::Literal, YamlBlockStyle::Folded] {
+			let text = to_string(value.clone(), style).unwrap();
+			let got: serde_json::Value = serde_yaml::from_str(&text).unwrap();
+			assert_eq!(got, want, "{style:?}:\n{text}");
+		}
+
+		let text = to_string(Value::String("a\nb \n".into()), YamlBlockStyle::Literal).unwrap();
+		assert_eq!(text, "|\n  a\n  b \n");
+		let text = to_string(Value::String("a b\nc".into()), YamlBlockStyle::Folded).unwrap();
+		assert_eq!(text, ">-\n  a b\n\n  c\n");
+	}
+}
@@ -11,25 +11,33 @@
 
 use std::io::{self, Read};
 use std::mem;
+use std::str;
 
-mod parser;
+use crate::input::{Budget, Charge, cast_read_offset_usize};
 
-use crate::input::cast_read_offset_usize;
-
-use self::parser::{
+use super::lint::{RiskyScalar, YamlRisk};
+use super::parser::{
 	Parser, YAML_DOCUMENT_END_EVENT, YAML_DOCUMENT_START_EVENT, YAML_MAPPING_START_EVENT,
 	YAML_SCALAR_EVENT, YAML_SEQUENCE_START_EVENT, YAML_STREAM_END_EVENT,
 };
 
-/// An iterator over individual raw documents in a UTF-8-encoded YAML stream.
+/// A lending iterator over individual raw documents in a UTF-8-encoded YAML stream.
+///
+/// To avoid an allocation for every document in the stream, the chunker reuses a single
+/// [`Document`] for the entire stream, and lends it out from [`next_document`](Self::next_document)
+/// until the next call.
 pub(super) struct Chunker<R>
 where
 	R: Read,
 {
 	parser: Parser<ChunkReader<R>>,
-	last_document: Option<Document>,
+	document: Document,
+	document_pending: bool,
 	current_document_kind: Option<DocumentKind>,
 	stream_ended: bool,
+	/// Whether to collect the risky scalars in each document.
+	lint: bool,
+	current_risky: Vec<RiskyScalar>,
 }
 
 impl<R> Chunker<R>
@@ -42,23 +50,34 @@ where
 	/// marks at the start of the stream or individual documents. However, `Chunker` requires a
 	/// UTF-8 stream without BOMs. Consider using the [`encoding`](super::encoding) module to
 	/// re-encode non-UTF-8 streams.
-	pub(super) fn new(reader: R) -> Self {
+	///
+	/// The chunker fails with an error if its buffer would exceed what the translator's other
+	/// buffers leave of the provided memory budget.
+	pub(super) fn new(reader: R, budget: Budget) -> Self {
 		Self {
-			parser: Parser::new(ChunkReader::new(reader)),
-			last_document: None,
+			parser: Parser::new(ChunkReader::new(reader, budget)),
+			document: Document {
+				content: String::new(),
+				offset: 0,
+				kind: None,
+				risky: vec![],
+			},
+			document_pending: false,
 			current_document_kind: None,
 			stream_ended: false,
+			lint: false,
+			current_risky: vec![],
 		}
 	}
-}
 
-impl<R> Iterator for Chunker<R>
-where
-	R: Read,
-{
-	type Item = io::Result<Document>;
+	/// Sets whether each document records the plain scalars in it that YAML parsers might
+	/// misinterpret.
+	pub(super) fn set_lint(&mut self, lint: bool) {
+		self.lint = lint;
+	}
 
-	fn next(&mut self) -> Option<Self::Item> {
+	/// Returns the next document in the stream, or `None` at the end of the stream.
+	pub(super) fn next_document(&mut self) -> Option<io::Result<&Document>> {
 		if self.stream_ended {
 			return None;
 		}
@@ -66,7 +85,7 @@ where
 		loop {
 			let event = match self.parser.next_event() {
 				Ok(event) => event,
-				Err(err) => return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err))),
+				Err(err) => return Some(Err(err)),
 			};
 
 			// Note that while we chunk on DOCUMENT_END events, we don't emit the chunk until the
@@ -79,29 +98,47 @@ where
 					let offset = event.start_offset();
 					self.parser.reader_mut().trim_to_offset(offset);
 					self.current_document_kind = None;
-					if let Some(doc) = self.last_document.take() {
-						return Some(Ok(doc));
+					if self.document_pending {
+						self.document_pending = false;
+						return Some(Ok(&self.document));
 					}
 				}
 				YAML_SCALAR_EVENT => {
 					self.current_document_kind
 						.get_or_insert(DocumentKind::Scalar);
+					if self.lint {
+						let value = event.plain_scalar().and_then(|b| str::from_utf8(b).ok());
+						if let Some((risk, value)) =
+							value.and_then(|v| Some((YamlRisk::classify(v)?, v)))
+						{
+							self.current_risky.push(RiskyScalar {
+								risk,
+								value: value.into(),
+								line_column: event.start_line_column(),
+							});
+						}
+					}
 				}
 				YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT => {
 					self.current_document_kind
 						.get_or_insert(DocumentKind::Collection);
 				}
 				YAML_DOCUMENT_END_EVENT => {
-					let chunk = self.parser.reader_mut().take_to_offset(event.end_offset());
-					self.last_document = Some(Document {
-						kind: self.current_document_kind.take(),
-						content: String::from_utf8(chunk)
-							.expect("libyaml with YAML_UTF8_ENCODING should yield UTF-8 chunks"),
-					});
+					let mut chunk = mem::take(&mut self.document.content).into_bytes();
+					self.document.offset = self
+						.parser
+						.reader_mut()
+						.take_to_offset(event.end_offset(), &mut chunk);
+					self.document.content = String::from_utf8(chunk)
+						.expect("libyaml with YAML_UTF8_ENCODING should yield UTF-8 chunks");
+					self.document.kind = self.current_document_kind.take();
+					self.document.risky = mem::take(&mut self.current_risky);
+					self.document_pending = true;
 				}
 				YAML_STREAM_END_EVENT => {
 					self.stream_ended = true;
-					return self.last_document.take().map(Ok);
+					let pending = mem::take(&mut self.document_pending);
+					return pending.then_some(Ok(&self.document));
 				}
 				_ => {}
 			};
@@ -112,7 +149,9 @@ where
 /// A UTF-8 encoded YAML document.
 pub(super) struct Document {
 	content: String,
+	offset: u64,
 	kind: Option<DocumentKind>,
+	risky: Vec<RiskyScalar>,
 }
 
 /// The type of content contained in a YAML document.
@@ -127,10 +166,21 @@ impl Document {
 		&self.content
 	}
 
+	/// Returns the offset in the stream where the text of the document starts.
+	pub(super) fn offset(&self) -> u64 {
+		self.offset
+	}
+
 	/// Returns true if the content of the document is a collection (sequence or mapping).
 	pub(super) fn is_collection(&self) -> bool {
 		matches!(self.kind, Some(DocumentKind::Collection))
 	}
+
+	/// Returns the plain scalars in the document that YAML parsers might misinterpret, if the
+	/// chunker [lints](Chunker::set_lint) its documents.
+	pub(super) fn risky_scalars(&self) -> &[RiskyScalar] {
+		&self.risky
+	}
 }
 
 /// A reader that captures bytes read from a source and provides them in chunks.
@@ -141,17 +191,19 @@ where
 	reader: R,
 	captured: Vec<u8>,
 	captured_start_offset: u64,
+	charge: Charge,
 }
 
 impl<R> ChunkReader<R>
 where
 	R: Read,
 {
-	fn new(reader: R) -> Self {
+	fn new(reader: R, budget: Budget) -> Self {
 		Self {
 			reader,
 			captured: vec![],
 			captured_start_offset: 0,
+			charge: budget.charge(),
 		}
 	}
 
@@ -161,15 +213,21 @@ where
 		let trim_len = cast_read_offset_usize(offset - self.captured_start_offset);
 		self.captured_start_offset = offset;
 		self.captured.drain(..trim_len);
+		self.charge.shrink(self.captured.len());
 	}
 
-	/// Takes the chunk from the start of the capture buffer up to the specified reader offset,
-	/// leaving bytes beyond the offset in the capture buffer.
-	fn take_to_offset(&mut self, offset: u64) -> Vec<u8> {
-		let take_len = cast_read_offset_usize(offset - self.captured_start_offset);
-		let tail = self.captured.split_off(take_len);
-		self.captured_start_offset = offset;
-		mem::replace(&mut self.captured, tail)
+	/// Moves the chunk from the start of the capture buffer up to the specified reader offset into
+	/// `chunk`, leaving bytes beyond the offset in the capture buffer, and returns the reader
+	/// offset where the chunk starts.
+	///
+	/// The chunk replaces the previous contents of `chunk`, reusing its allocation.
+	fn take_to_offset(&mut self, offset: u64, chunk: &mut Vec<u8>) -> u64 {
+		let start = mem::replace(&mut self.captured_start_offset, offset);
+		let take_len = cast_read_offset_usize(offset - start);
+		chunk.clear();
+		chunk.extend(self.captured.drain(..take_len));
+		self.charge.shrink(self.captured.len());
+		start
 	}
 }
 
@@ -182,7 +240,10 @@ where
 		// assume we might do this. Letting our consumer drive the frequency and size of source
 		// reads makes our presence more transparent to both sides. We try to be good citizens by
 		// only reading the parts of `buf` the source tells us were freshly written.
-		let len = self.reader.read(buf)?;
+		let buf_len = self.charge.limit_read_len(buf.len());
+		let len = self.reader.read(&mut buf[..buf_len])?;
+		self.charge
+			.resize(self.captured.len().saturating_add(len))?;
 		self.captured.extend_from_slice(&buf[..len]);
 		Ok(len)
 	}
@@ -202,10 +263,17 @@ test: true
 [list, of strings]
 ";
 
-		let chunker = Chunker::new(INPUT.as_bytes());
-		let docs = chunker.collect::<Result<Vec<_>, io::Error>>().unwrap();
+		let mut chunker = Chunker::new(INPUT.as_bytes(), Budget::UNLIMITED);
+		let mut contents = vec![];
+		let mut collections = vec![];
+		while let Some(doc) = chunker.next_document() {
+			let doc = doc.unwrap();
+			let offset = usize::try_from(doc.offset()).unwrap();
+			assert_eq!(&INPUT[offset..offset + doc.content().len()], doc.content());
+			contents.push(doc.content().to_owned());
+			collections.push(doc.is_collection());
+		}
 
-		let contents = docs.iter().map(|doc| doc.content()).collect::<Vec<_>>();
 		assert_eq!(
 			&contents,
 			&[
@@ -215,7 +283,6 @@ test: true
 			]
 		);
 
-		let collections: Vec<_> = docs.iter().map(Document::is_collection).collect();
 		assert_eq!(&collections, &[true, false, true]);
 	}
 
@@ -224,15 +291,20 @@ test: true
 	#[test]
 	fn chunker_unknown_anchor() {
 		const INPUT: &str = "*y";
-		let chunker = Chunker::new(INPUT.as_bytes());
-		chunker.collect::<Result<Vec<_>, io::Error>>().unwrap();
+		let mut chunker = Chunker::new(INPUT.as_bytes(), Budget::UNLIMITED);
+		while let Some(doc) = chunker.next_document() {
+			doc.unwrap();
+		}
 	}
 
 	#[test]
 	#[should_panic]
 	fn chunker_misbehaving_reader() {
-		let chunker = Chunker::new(MisbehavingReader("---\nevil: true".as_bytes()));
-		let _ = chunker.collect::<Vec<_>>();
+		let mut chunker = Chunker::new(
+			MisbehavingReader("---\nevil: true".as_bytes()),
+			Budget::UNLIMITED,
+		);
+		while chunker.next_document().is_some() {}
 	}
 
 	/// A reader that always reports having read 1 byte more than the length of the buffer provided
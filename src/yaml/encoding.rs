@@ -29,6 +29,7 @@ use std::io::{self, BufRead, Read, Write};
 use std::mem;
 
 /// The possible text encodings of a valid YAML 1.2 stream.
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub(super) enum Encoding {
 	Utf8,
 	Utf16Big,
@@ -108,16 +109,16 @@ where
 		})
 	}
 
-	/// Creates an encoder by detecting the source encoding from the first bytes of the reader.
-	/// See [`Encoding::detect`] for details.
-	pub(super) fn from_reader(mut reader: R) -> io::Result<impl Read> {
+	/// Creates an encoder by detecting the source encoding from the first bytes of the reader,
+	/// and returns it along with the encoding. See [`Encoding::detect`] for details.
+	pub(super) fn from_reader(mut reader: R) -> io::Result<(impl Read, Encoding)> {
 		let mut prefix = ArrayBuffer::<{ Encoding::DETECT_LEN }>::new();
 		io::copy(
 			&mut reader.by_ref().take(Encoding::DETECT_LEN as u64),
 			&mut prefix,
 		)?;
 		let encoding = Encoding::detect(prefix.unread());
-		Ok(Encoder::new(prefix.chain(reader), encoding))
+		Ok((Encoder::new(prefix.chain(reader), encoding), encoding))
 	}
 }
 
@@ -735,7 +736,7 @@ mod tests {
 		assert_eq!(buf.fill_buf().unwrap(), INPUT.as_bytes());
 
 		buf.consume("hello ".len());
-		assert_eq!(buf.fill_buf().unwrap(), INPUT["hello ".len()..].as_bytes());
+		assert_eq!(buf.fill_buf().unwrap(), &INPUT.as_bytes()["hello ".len()..]);
 
 		assert_eq!(io::read_to_string(buf).unwrap(), "world");
 	}
@@ -0,0 +1,623 @@
+//! Reformatting of YAML streams that keeps their comments and blank lines.
+//!
+//! Translation goes through a data model with no place for comments, so [`reformat`] works from
+//! the parser's events instead. It lays out every block collection in the style of translated
+//! YAML output, with 2-space indentation, one space after each indicator, and sequences flush
+//! with the keys of their parent map. Scalars, aliases, and flow collections keep their source
+//! text, with any continuation lines shifted along with their parent.
+//!
+//! Comments and blank lines live in the gaps between those pieces of source text. A comment that
+//! ends a line with content stays at the end of that line, while full-line comments and blank
+//! lines move with the entry after them.
+
+use std::ops::Range;
+use std::str;
+
+use crate::input::cast_read_offset_usize;
+
+use super::parser::{
+	Event, Parser, YAML_DOCUMENT_START_EVENT, YAML_MAPPING_END_EVENT, YAML_MAPPING_START_EVENT,
+	YAML_SEQUENCE_END_EVENT, YAML_SEQUENCE_START_EVENT,
+};
+
+/// Reformats a UTF-8 YAML stream, keeping its comments and blank lines.
+pub(crate) fn reformat(input: &[u8]) -> crate::Result<Vec<u8>> {
+	let input = input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input);
+	let Ok(text) = str::from_utf8(input) else {
+		return Err("formatting YAML with its comments requires UTF-8 input".into());
+	};
+	let source = Source::new(text);
+	let mut tree = Tree {
+		parser: Parser::new(input),
+		source: text,
+		covered: vec![],
+	};
+	let documents = tree.documents()?;
+
+	let mut writer = Writer {
+		asides: source.asides(&tree.covered),
+		source: &source,
+		next: 0,
+		out: String::with_capacity(text.len()),
+	};
+	for document in &documents {
+		writer.document(document)?;
+	}
+	writer.leading(usize::MAX, 0);
+	let mut out = writer.out;
+	while out.ends_with("\n\n") {
+		out.pop();
+	}
+	Ok(out.into_bytes())
+}
+
+/// The text of a YAML stream, with the offsets where its lines start.
+struct Source<'a> {
+	text: &'a str,
+	lines: Vec<usize>,
+}
+
+impl<'a> Source<'a> {
+	fn new(text: &'a str) -> Source<'a> {
+		let starts = text.match_indices('\n').map(|(i, _)| i + 1);
+		Source {
+			text,
+			lines: std::iter::once(0).chain(starts).collect(),
+		}
+	}
+
+	/// Returns the zero-based line containing an offset.
+	fn line(&self, offset: usize) -> usize {
+		self.lines
+			.partition_point(|&start| start <= offset)
+			.saturating_sub(1)
+	}
+
+	/// Returns the column of an offset in bytes, which for indentation is also in characters.
+	fn column(&self, offset: usize) -> usize {
+		offset - self.lines[self.line(offset)]
+	}
+
+	/// Returns the range of a line's text, without its line break.
+	fn line_range(&self, line: usize) -> Range<usize> {
+		let start = self.lines[line];
+		let end = self
+			.lines
+			.get(line + 1)
+			.map_or(self.text.len(), |next| next - 1);
+		match self.text[start..end].ends_with('\r') {
+			true => start..end - 1,
+			false => start..end,
+		}
+	}
+
+	/// Finds the comments and blank lines outside of the covered ranges of source text, which
+	/// must be in order.
+	fn asides(&self, covered: &[Range<usize>]) -> Vec<Aside<'a>> {
+		let bytes = self.text.as_bytes();
+		let mut covered = covered.iter().filter(|range| !range.is_empty()).peekable();
+		let mut asides = vec![];
+		for line in 0..self.lines.len() {
+			let range = self.line_range(line);
+			let mut content = false;
+			let mut comment = false;
+			let mut i = range.start;
+			loop {
+				// Check even an empty line, which may be within a block scalar.
+				while covered.next_if(|covered| covered.end <= i).is_some() {}
+				match covered.peek() {
+					Some(covered) if covered.start <= i => {
+						content = true;
+						if covered.end >= range.end {
+							break;
+						}
+						i = covered.end;
+						continue;
+					}
+					_ if i >= range.end => break,
+					_ => {}
+				}
+				if bytes[i] == b'#' && (i == range.start || matches!(bytes[i - 1], b' ' | b'\t')) {
+					asides.push(Aside {
+						line,
+						comment: Some(self.text[i..range.end].trim_end()),
+						trailing: content,
+					});
+					comment = true;
+					break;
+				}
+				content |= !bytes[i].is_ascii_whitespace();
+				i += 1;
+			}
+			if !content && !comment {
+				asides.push(Aside {
+					line,
+					comment: None,
+					trailing: false,
+				});
+			}
+		}
+		asides
+	}
+}
+
+/// A comment or blank line outside of every node's source text.
+struct Aside<'a> {
+	line: usize,
+	/// The text of a comment, or `None` for a blank line.
+	comment: Option<&'a str>,
+	/// Whether a comment follows content on the same line.
+	trailing: bool,
+}
+
+/// A document from a YAML stream, with the source ranges of its explicit markers.
+struct Document {
+	/// Any directives and the `---` marker, or an empty range if the start is implicit.
+	start: Range<usize>,
+	root: Node,
+	/// The `...` marker, or an empty range if the end is implicit.
+	end: Range<usize>,
+}
+
+enum Node {
+	/// A scalar, alias, or flow collection, written as its source text.
+	Leaf(Range<usize>),
+	/// A block sequence or mapping, laid out anew.
+	Block(Block),
+}
+
+struct Block {
+	/// The anchor and tag of the collection, or an empty range if it has neither.
+	props: Range<usize>,
+	/// The offset of the first `-` indicator of a sequence, or of the first key of a mapping.
+	body: usize,
+	kind: BlockKind,
+}
+
+enum BlockKind {
+	Seq(Vec<Node>),
+	Map(Vec<(Node, Node)>),
+}
+
+impl Node {
+	/// Returns the offset where the node's source text starts.
+	fn start(&self) -> usize {
+		match self {
+			Node::Leaf(span) => span.start,
+			Node::Block(block) if block.props.is_empty() => block.body,
+			Node::Block(block) => block.props.start,
+		}
+	}
+}
+
+/// A builder of documents from parser events, which records the ranges of source text that the
+/// documents cover.
+struct Tree<'a> {
+	parser: Parser<&'a [u8]>,
+	source: &'a str,
+	covered: Vec<Range<usize>>,
+}
+
+impl Tree<'_> {
+	fn next(&mut self) -> crate::Result<Event> {
+		Ok(self.parser.next_event()?)
+	}
+
+	fn documents(&mut self) -> crate::Result<Vec<Document>> {
+		self.next()?; // STREAM_START
+		let mut documents = vec![];
+		loop {
+			let event = self.next()?;
+			if event.event_type() != YAML_DOCUMENT_START_EVENT {
+				return Ok(documents); // STREAM_END
+			}
+			let start = self.cover(span(&event));
+			let event = self.next()?;
+			let root = self.node(&event)?;
+			let event = self.next()?; // DOCUMENT_END
+			let end = self.cover(span(&event));
+			documents.push(Document { start, root, end });
+		}
+	}
+
+	fn node(&mut self, event: &Event) -> crate::Result<Node> {
+		let span = span(event);
+		match event.event_type() {
+			YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT if event.is_flow_collection() => {
+				let mut depth = 1;
+				let mut end = span.end;
+				while depth > 0 {
+					let event = self.next()?;
+					match event.event_type() {
+						YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT => depth += 1,
+						YAML_SEQUENCE_END_EVENT | YAML_MAPPING_END_EVENT => depth -= 1,
+						_ => {}
+					}
+					end = cast_read_offset_usize(event.end_offset());
+				}
+				Ok(Node::Leaf(self.cover(span.start..end)))
+			}
+			YAML_SEQUENCE_START_EVENT => {
+				let props = self.props(&span);
+				// The event for a sequence flush with its parent's keys includes its first `-`.
+				let body = match self.source[span.clone()].ends_with('-') {
+					true => span.end - 1,
+					false => span.end,
+				};
+				let mut items = vec![];
+				loop {
+					let event = self.next()?;
+					if event.event_type() == YAML_SEQUENCE_END_EVENT {
+						break;
+					}
+					items.push(self.node(&event)?);
+				}
+				Ok(Node::Block(Block {
+					props,
+					body,
+					kind: BlockKind::Seq(items),
+				}))
+			}
+			YAML_MAPPING_START_EVENT => {
+				let props = self.props(&span);
+				let mut entries = vec![];
+				loop {
+					let event = self.next()?;
+					if event.event_type() == YAML_MAPPING_END_EVENT {
+						break;
+					}
+					let key = self.node(&event)?;
+					let event = self.next()?;
+					entries.push((key, self.node(&event)?));
+				}
+				Ok(Node::Block(Block {
+					props,
+					body: span.end,
+					kind: BlockKind::Map(entries),
+				}))
+			}
+			_ => Ok(Node::Leaf(self.cover(span))),
+		}
+	}
+
+	/// Returns the range of the properties at the start of a block collection's start event,
+	/// which runs up to the collection's first entry through any comments before it.
+	fn props(&mut self, span: &Range<usize>) -> Range<usize> {
+		let text = &self.source[span.clone()];
+		if !text.starts_with(['&', '!']) {
+			return span.start..span.start;
+		}
+		let line = text.split('\n').next().unwrap_or_default();
+		let len = line.split(" #").next().unwrap_or_default().trim_end().len();
+		self.cover(span.start..span.start + len)
+	}
+
+	fn cover(&mut self, range: Range<usize>) -> Range<usize> {
+		if !range.is_empty() {
+			self.covered.push(range.clone());
+		}
+		range
+	}
+}
+
+fn span(event: &Event) -> Range<usize> {
+	cast_read_offset_usize(event.start_offset())..cast_read_offset_usize(event.end_offset())
+}
+
+/// The position of a node within its parent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Context {
+	Root,
+	Key,
+	Item,
+}
+
+/// A change in the indentation of a node's continuation lines, from the indentation of its
+/// parent collection in the source to the indentation of its reformatted parent.
+#[derive(Clone, Copy)]
+struct Shift {
+	from: usize,
+	to: usize,
+}
+
+impl Shift {
+	const NONE: Shift = Shift { from: 0, to: 0 };
+}
+
+struct Writer<'a> {
+	source: &'a Source<'a>,
+	asides: Vec<Aside<'a>>,
+	/// The index of the first aside not yet written.
+	next: usize,
+	out: String,
+}
+
+impl Writer<'_> {
+	fn document(&mut self, document: &Document) -> crate::Result<()> {
+		if document.start.is_empty() {
+			self.root(&document.root)?;
+		} else {
+			self.leading(self.source.line(document.start.start), 0);
+			let line = self.leaf(&document.start, Shift::NONE);
+			self.value(&document.root, line, 0, Shift::NONE, Context::Root)?;
+		}
+		if !document.end.is_empty() {
+			let line = self.source.line(document.end.start);
+			self.leading(line, 0);
+			self.out.push_str("...");
+			self.trailing(line);
+		}
+		Ok(())
+	}
+
+	/// Writes the root node of a document without an explicit start.
+	fn root(&mut self, node: &Node) -> crate::Result<()> {
+		match node {
+			Node::Leaf(span) if span.is_empty() => Ok(()),
+			Node::Leaf(span) => {
+				self.leading(self.source.line(span.start), 0);
+				let line = self.leaf(span, Shift::NONE);
+				self.trailing(line);
+				Ok(())
+			}
+			Node::Block(block) => {
+				if !block.props.is_empty() {
+					let line = self.source.line(block.props.start);
+					self.leading(line, 0);
+					self.leaf(&block.props, Shift::NONE);
+					self.trailing(line);
+				}
+				self.children(block, 0, false)
+			}
+		}
+	}
+
+	/// Writes a node after the indicator or key that introduces it, where `line` is the source
+	/// line of that indicator and `indent` is the indentation of its reformatted line.
+	fn value(
+		&mut self,
+		node: &Node,
+		line: usize,
+		indent: usize,
+		shift: Shift,
+		context: Context,
+	) -> crate::Result<()> {
+		let block = match node {
+			Node::Leaf(span) if span.is_empty() => {
+				self.trailing(line);
+				return Ok(());
+			}
+			Node::Leaf(span) => {
+				let start = self.source.line(span.start);
+				let inline = match context {
+					Context::Root => start == line,
+					Context::Key | Context::Item => !self.pending_before(start),
+				};
+				if inline {
+					self.out.push(' ');
+				} else {
+					self.trailing(line);
+					let indent = if context == Context::Root {
+						0
+					} else {
+						indent + 2
+					};
+					self.leading(start, indent);
+					self.indent(indent);
+				}
+				let line = self.leaf(span, shift);
+				self.trailing(line);
+				return Ok(());
+			}
+			Node::Block(block) => block,
+		};
+
+		if !block.props.is_empty() {
+			self.out.push(' ');
+			let line = self.leaf(&block.props, shift);
+			self.trailing(line);
+		} else if context == Context::Item && !self.pending_before(self.source.line(block.body)) {
+			// Like translated output, start a collection in a sequence on the line of its `-`.
+			self.out.push(' ');
+			return self.children(block, indent + 2, true);
+		} else {
+			self.trailing(line);
+		}
+		let indent = match (context, &block.kind) {
+			(Context::Key, BlockKind::Map(_)) | (Context::Item, _) => indent + 2,
+			_ => indent,
+		};
+		self.children(block, indent, false)
+	}
+
+	/// Writes the entries of a block collection at `indent`, where `inline` indicates that the
+	/// first entry continues the current line.
+	fn children(&mut self, block: &Block, indent: usize, inline: bool) -> crate::Result<()> {
+		let shift = Shift {
+			from: self.source.column(block.body),
+			to: indent,
+		};
+		match &block.kind {
+			BlockKind::Seq(items) => {
+				for (i, item) in items.iter().enumerate() {
+					let line = self.indicator_line(item.start());
+					if i > 0 || !inline {
+						self.leading(line, indent);
+						self.indent(indent);
+					}
+					self.out.push('-');
+					self.value(item, line, indent, shift, Context::Item)?;
+				}
+			}
+			BlockKind::Map(entries) => {
+				for (i, (key, value)) in entries.iter().enumerate() {
+					let line = self.source.line(key.start());
+					let key = match key {
+						Node::Leaf(span) if !span.is_empty() => &self.source.text[span.clone()],
+						_ => "\n",
+					};
+					if key.contains('\n') {
+						return Err(format!(
+							"can't format the map key at line {} while keeping comments, since \
+							 it isn't a single-line scalar, alias, or flow collection",
+							line + 1
+						)
+						.into());
+					}
+					if i > 0 || !inline {
+						self.leading(line, indent);
+						self.indent(indent);
+					}
+					self.out.push_str(key);
+					// An alias can end with a colon, so it needs a space before the indicator.
+					self.out
+						.push_str(if key.starts_with('*') { " :" } else { ":" });
+					self.value(value, line, indent, shift, Context::Key)?;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Writes the source text of a node, and returns the source line where it ends.
+	fn leaf(&mut self, span: &Range<usize>, shift: Shift) -> usize {
+		let text = &self.source.text[span.clone()];
+		// A block scalar's text runs through the line break after it, up to the next token.
+		let text = text.trim_end_matches(' ');
+		let text = text.strip_suffix('\n').unwrap_or(text);
+		let mut lines = text.split('\n');
+		if let Some(first) = lines.next() {
+			self.out.push_str(first.strip_suffix('\r').unwrap_or(first));
+		}
+		for line in lines {
+			let line = line.strip_suffix('\r').unwrap_or(line);
+			self.out.push('\n');
+			let rest = line.trim_start_matches(' ');
+			if !line.is_empty() {
+				self.indent((line.len() - rest.len() + shift.to).saturating_sub(shift.from));
+			}
+			self.out.push_str(rest);
+		}
+		self.source.line(span.start + text.len().saturating_sub(1))
+	}
+
+	/// Returns the source line of the `-` indicator before a sequence item.
+	fn indicator_line(&self, start: usize) -> usize {
+		let before = self.source.text[..start].trim_end();
+		match before.ends_with('-') {
+			true => self.source.line(before.len() - 1),
+			false => self.source.line(start),
+		}
+	}
+
+	fn indent(&mut self, indent: usize) {
+		self.out.extend(std::iter::repeat_n(' ', indent));
+	}
+
+	/// Returns whether any comments or blank lines come before a source line.
+	fn pending_before(&self, line: usize) -> bool {
+		self.asides
+			.get(self.next)
+			.is_some_and(|aside| aside.line < line)
+	}
+
+	/// Writes the comments and blank lines before a source line at `indent`.
+	fn leading(&mut self, line: usize, indent: usize) {
+		while let Some(aside) = self.asides.get(self.next) {
+			if aside.line >= line {
+				break;
+			}
+			if let Some(comment) = aside.comment {
+				self.indent(indent);
+				self.out.push_str(comment);
+			}
+			self.out.push('\n');
+			self.next += 1;
+		}
+	}
+
+	/// Ends the current line with the comments that end a source line or any line before it.
+	fn trailing(&mut self, line: usize) {
+		while let Some(aside) = self.asides.get(self.next) {
+			if !aside.trailing || aside.line > line {
+				break;
+			}
+			if let Some(comment) = aside.comment {
+				self.out.push(' ');
+				self.out.push_str(comment);
+			}
+			self.next += 1;
+		}
+		self.out.push('\n');
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const INPUT: &str = r#"# head
+
+%YAML 1.2
+---   # start
+top:   &anchor
+    # about a
+    a:    1   # one
+    b: [1,
+        2]
+    list:
+        -   x
+        - # empty
+
+        -   k: v
+            l: |
+              text
+
+              more
+        - - nested
+          - "two
+            lines"
+plain: *anchor
+...
+--- scalar
+"#;
+
+	const OUTPUT: &str = r#"# head
+
+%YAML 1.2
+--- # start
+top: &anchor
+  # about a
+  a: 1 # one
+  b: [1,
+      2]
+  list:
+  - x
+  - # empty
+
+  - k: v
+    l: |
+      text
+
+      more
+  - - nested
+    - "two
+      lines"
+plain: *anchor
+...
+--- scalar
+"#;
+
+	#[test]
+	fn reformat_with_comments() {
+		let output = reformat(INPUT.as_bytes()).unwrap();
+		assert_eq!(str::from_utf8(&output).unwrap(), OUTPUT);
+		assert_eq!(reformat(OUTPUT.as_bytes()).unwrap(), OUTPUT.as_bytes());
+	}
+
+	#[test]
+	fn reformat_rejects_multi_line_keys() {
+		let err = reformat(b"a: 1\n? - b\n: c\n").unwrap_err();
+		assert!(err.to_string().contains("line 2"), "{err}");
+	}
+}
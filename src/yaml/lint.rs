@@ -0,0 +1,420 @@
+//! Warnings about YAML scalars that other parsers are likely to misinterpret.
+//!
+//! xt reads and writes YAML with the YAML 1.2 core schema, where a plain scalar like `no` is just
+//! a string. Parsers that follow YAML 1.1 still read such scalars as other types, most famously
+//! reading the country code for Norway as `false`. Linting recognizes the plain scalars whose
+//! meaning depends on the schema, both in YAML input (from the parser's events, which preserve
+//! each scalar's style) and in YAML output (from the strings that the serializer writes without
+//! quotes).
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+
+use serde::de::{self, Deserialize};
+
+use crate::error::Location;
+use crate::transcode::Value;
+use crate::transform::path::push_key;
+
+/// A way that a plain YAML scalar can mean something other than a string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum YamlRisk {
+	/// A word like `no`, `on`, or `off` that YAML 1.1 reads as a boolean.
+	Boolean,
+	/// A number with a leading zero, like `0123`, that YAML 1.1 reads as an octal integer.
+	LeadingZero,
+	/// Numbers separated by colons, like `1:20`, that YAML 1.1 reads as a base 60 number.
+	Sexagesimal,
+	/// A date, like `2001-12-14`, that YAML 1.1 reads as a timestamp.
+	Timestamp,
+}
+
+impl YamlRisk {
+	/// Returns the risk of a plain scalar, if it has one.
+	pub(crate) fn classify(s: &str) -> Option<YamlRisk> {
+		if matches!(
+			s,
+			"y" | "Y"
+				| "yes" | "Yes"
+				| "YES" | "n"
+				| "N" | "no" | "No"
+				| "NO" | "on"
+				| "On" | "ON"
+				| "off" | "Off"
+				| "OFF"
+		) {
+			Some(YamlRisk::Boolean)
+		} else if is_leading_zero(s) {
+			Some(YamlRisk::LeadingZero)
+		} else if is_sexagesimal(s) {
+			Some(YamlRisk::Sexagesimal)
+		} else if is_timestamp(s) {
+			Some(YamlRisk::Timestamp)
+		} else {
+			None
+		}
+	}
+
+	fn description(self) -> &'static str {
+		match self {
+			YamlRisk::Boolean => "a boolean",
+			YamlRisk::LeadingZero => "an octal integer",
+			YamlRisk::Sexagesimal => "a base 60 number",
+			YamlRisk::Timestamp => "a timestamp",
+		}
+	}
+}
+
+/// Strips an optional sign from the start of a number.
+fn unsigned(s: &str) -> &str {
+	s.strip_prefix(['-', '+']).unwrap_or(s)
+}
+
+fn is_digits(s: &str) -> bool {
+	!s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Matches YAML 1.1 octal integers, along with leading-zero decimals that some parsers also read
+/// as numbers.
+fn is_leading_zero(s: &str) -> bool {
+	unsigned(s).strip_prefix('0').is_some_and(|rest| {
+		!rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit() || b == b'_')
+	})
+}
+
+/// Matches YAML 1.1 base 60 integers and floats.
+fn is_sexagesimal(s: &str) -> bool {
+	let mut parts = unsigned(s).split(':');
+	let first = parts.next().unwrap_or_default();
+	if !first.starts_with(|c: char| c.is_ascii_digit())
+		|| !first.bytes().all(|b| b.is_ascii_digit() || b == b'_')
+	{
+		return false;
+	}
+	let mut rest = parts.peekable();
+	if rest.peek().is_none() {
+		return false;
+	}
+	while let Some(part) = rest.next() {
+		// Only the last part of a float may have a fraction.
+		let part = match (rest.peek(), part.split_once('.')) {
+			(None, Some((whole, fraction)))
+				if fraction.bytes().all(|b| b.is_ascii_digit() || b == b'_') =>
+			{
+				whole
+			}
+			_ => part,
+		};
+		let sixty = match part.as_bytes() {
+			[d] => d.is_ascii_digit(),
+			[d, e] => (b'0'..=b'5').contains(d) && e.is_ascii_digit(),
+			_ => false,
+		};
+		if !sixty {
+			return false;
+		}
+	}
+	true
+}
+
+/// Matches dates in YAML 1.1 timestamps, alone or followed by a time.
+fn is_timestamp(s: &str) -> bool {
+	let mut parts = s.splitn(3, '-');
+	let (Some(year), Some(month), Some(rest)) = (parts.next(), parts.next(), parts.next()) else {
+		return false;
+	};
+	let (day, time) = rest.split_at(rest.bytes().take_while(u8::is_ascii_digit).count());
+	let date = year.len() == 4
+		&& is_digits(year)
+		&& (1..=2).contains(&month.len())
+		&& is_digits(month)
+		&& (1..=2).contains(&day.len());
+	// Any time follows the date after a 'T' or whitespace.
+	let after_date = time.trim_start_matches(['T', 't', ' ', '\t']);
+	date && (time.is_empty()
+		|| after_date.len() < time.len() && after_date.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// A warning about a YAML scalar that other parsers are likely to misinterpret, as passed to a
+/// [YAML warning handler](crate::Translator::set_yaml_warning_handler).
+#[derive(Debug, Clone)]
+pub struct YamlWarning {
+	risk: YamlRisk,
+	value: String,
+	document: u64,
+	place: Place,
+}
+
+/// Where a warning's scalar appears.
+#[derive(Debug, Clone)]
+enum Place {
+	Input {
+		source: Option<String>,
+		line: u64,
+		column: u64,
+	},
+	Output {
+		path: String,
+	},
+}
+
+impl YamlWarning {
+	/// Returns the way that the scalar could be misinterpreted.
+	pub fn risk(&self) -> YamlRisk {
+		self.risk
+	}
+
+	/// Returns the text of the scalar.
+	pub fn value(&self) -> &str {
+		&self.value
+	}
+
+	/// Returns true if the scalar appears in the translator's output, rather than in its input.
+	pub fn is_output(&self) -> bool {
+		matches!(self.place, Place::Output { .. })
+	}
+
+	/// Returns the zero-based index of the document containing the scalar, within its input for
+	/// input scalars or within the output for output scalars.
+	pub fn document_index(&self) -> u64 {
+		self.document
+	}
+
+	/// Returns the [source name](crate::Translator::set_source_name) of the input containing the
+	/// scalar, for input scalars from a named input.
+	pub fn source(&self) -> Option<&str> {
+		match &self.place {
+			Place::Input { source, .. } => source.as_deref(),
+			Place::Output { .. } => None,
+		}
+	}
+
+	/// Returns the one-based line and column where the scalar starts within its input, for input
+	/// scalars.
+	pub fn line_column(&self) -> Option<(u64, u64)> {
+		match &self.place {
+			Place::Input { line, column, .. } => Some((*line, *column)),
+			Place::Output { .. } => None,
+		}
+	}
+
+	/// Returns the path to the scalar within its document, in the syntax of
+	/// [`Transform::select`](crate::Transform::select), for output scalars.
+	pub fn path(&self) -> Option<&str> {
+		match &self.place {
+			Place::Input { .. } => None,
+			Place::Output { path } => Some(path),
+		}
+	}
+}
+
+impl fmt::Display for YamlWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let (value, risk) = (&self.value, self.risk.description());
+		match &self.place {
+			Place::Input { line, column, .. } => write!(
+				f,
+				"unquoted scalar {value:?} at line {line}, column {column} (in document {}) is a string, but {risk} in YAML 1.1",
+				self.document
+			),
+			Place::Output { path } => write!(
+				f,
+				"string {value:?} at {path} (in output document {}) is written without quotes, but is {risk} in YAML 1.1",
+				self.document
+			),
+		}
+	}
+}
+
+/// A plain scalar with a risk, as found by the chunker in a YAML input.
+pub(super) struct RiskyScalar {
+	pub(super) risk: YamlRisk,
+	pub(super) value: String,
+	/// The zero-based line and column where the scalar starts.
+	pub(super) line_column: (u64, u64),
+}
+
+impl RiskyScalar {
+	/// Returns the error for a scalar in an input that rejects risky scalars.
+	pub(super) fn to_error(&self) -> crate::Error {
+		let (line, column) = self.line_column;
+		format!(
+			"unquoted scalar {:?} at line {}, column {} is a string, but {} in YAML 1.1; quote it to make it unambiguous",
+			self.value,
+			line + 1,
+			column + 1,
+			self.risk.description(),
+		)
+		.into()
+	}
+}
+
+/// A function that handles YAML warnings.
+pub(crate) type WarningHandler = dyn FnMut(YamlWarning) + Send;
+
+/// The state of YAML linting across all documents in a stream.
+pub(crate) struct Lint {
+	handler: Box<WarningHandler>,
+	/// The number of YAML documents output so far.
+	output_documents: u64,
+}
+
+impl Lint {
+	pub(crate) fn new(handler: Box<WarningHandler>) -> Lint {
+		Lint {
+			handler,
+			output_documents: 0,
+		}
+	}
+
+	/// Reports a risky scalar in the document at the given index within an input.
+	pub(super) fn warn_input(&mut self, scalar: &RiskyScalar, document: u64, source: Option<&str>) {
+		let (line, column) = scalar.line_column;
+		(self.handler)(YamlWarning {
+			risk: scalar.risk,
+			value: scalar.value.clone(),
+			document,
+			place: Place::Input {
+				source: source.map(Into::into),
+				line: line + 1,
+				column: column + 1,
+			},
+		});
+	}
+
+	/// Reports the risky strings in a document about to be output.
+	fn check_output(&mut self, value: &Value) {
+		let document = self.output_documents;
+		self.output_documents += 1;
+		let mut path = String::new();
+		visit(value, &mut path, &mut |path, s| {
+			let Some(risk) = YamlRisk::classify(s) else {
+				return;
+			};
+			// The serializer quotes strings that a YAML 1.2 parser wouldn't read back as strings,
+			// which covers some of these.
+			let quoted = serde_yaml::to_string(s).map_or(true, |out| out.starts_with(['\'', '"']));
+			if !quoted {
+				(self.handler)(YamlWarning {
+					risk,
+					value: s.into(),
+					document,
+					place: Place::Output {
+						path: if path.is_empty() {
+							".".into()
+						} else {
+							path.into()
+						},
+					},
+				});
+			}
+		});
+	}
+}
+
+/// Calls `f` with every string key and value within a value, along with its path.
+fn visit(value: &Value, path: &mut String, f: &mut dyn FnMut(&str, &str)) {
+	let len = path.len();
+	match value {
+		Value::String(s) => f(path, s),
+		Value::Seq(seq) => {
+			for (i, v) in seq.iter().enumerate() {
+				path.push_str(&format!("[{i}]"));
+				visit(v, path, f);
+				path.truncate(len);
+			}
+		}
+		Value::Map(map) => {
+			for (k, v) in map {
+				match k {
+					Value::String(key) => {
+						push_key(path, key);
+						f(path, key);
+					}
+					k => visit(k, path, f),
+				}
+				visit(v, path, f);
+				path.truncate(len);
+			}
+		}
+		_ => {}
+	}
+}
+
+/// An [`Output`](crate::Output) that reports risky strings in every document on its way to YAML
+/// output, when linting is enabled.
+pub(crate) struct Output<'l, 'h, O> {
+	output: O,
+	lint: Option<&'l RefCell<&'h mut Lint>>,
+}
+
+impl<'l, 'h, O> Output<'l, 'h, O> {
+	pub(crate) fn new(output: O, lint: Option<&'l RefCell<&'h mut Lint>>) -> Output<'l, 'h, O> {
+		Output { output, lint }
+	}
+}
+
+impl<'i, O> crate::Output<'i> for Output<'_, '_, O>
+where
+	O: crate::Output<'i>,
+{
+	fn transcode_from<'de, D, E>(&mut self, de: D) -> crate::Result<()>
+	where
+		D: de::Deserializer<'de, Error = E>,
+		E: de::Error + Send + Sync + 'static,
+	{
+		match self.lint {
+			Some(_) => self.transcode_value(Value::deserialize(de)?),
+			None => self.output.transcode_from(de),
+		}
+	}
+
+	fn transcode_value(&mut self, value: Value) -> crate::Result<()> {
+		if let Some(lint) = self.lint {
+			lint.borrow_mut().check_output(&value);
+		}
+		self.output.transcode_value(value)
+	}
+
+	fn set_document_location(&mut self, location: Location) {
+		self.output.set_document_location(location);
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.output.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn classify_risky_scalars() {
+		const CASES: &[(&str, Option<YamlRisk>)] = &[
+			("no", Some(YamlRisk::Boolean)),
+			("Off", Some(YamlRisk::Boolean)),
+			("none", None),
+			("true", None),
+			("0123", Some(YamlRisk::LeadingZero)),
+			("-0_7", Some(YamlRisk::LeadingZero)),
+			("0", None),
+			("0.5", None),
+			("1:20", Some(YamlRisk::Sexagesimal)),
+			("190:20:30.15", Some(YamlRisk::Sexagesimal)),
+			("1:60", None),
+			("12:", None),
+			("a:1", None),
+			("2001-12-14", Some(YamlRisk::Timestamp)),
+			("2001-1-4 10:00", Some(YamlRisk::Timestamp)),
+			("2001-12-14t21:59:43.10-05:00", Some(YamlRisk::Timestamp)),
+			("2001-12-14x", None),
+			("01-12-14", None),
+		];
+		for &(s, want) in CASES {
+			assert_eq!(YamlRisk::classify(s), want, "{s}");
+		}
+	}
+}
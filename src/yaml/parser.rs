@@ -12,6 +12,7 @@ use std::fmt::{self, Display};
 use std::io::{self, Read};
 use std::mem::MaybeUninit;
 use std::ptr;
+use std::slice;
 
 use unsafe_libyaml::{
 	yaml_encoding_t::YAML_UTF8_ENCODING, yaml_event_delete, yaml_event_t, yaml_event_type_t,
@@ -20,6 +21,9 @@ use unsafe_libyaml::{
 };
 
 pub(super) use unsafe_libyaml::yaml_event_type_t::*;
+use unsafe_libyaml::yaml_mapping_style_t::YAML_FLOW_MAPPING_STYLE;
+use unsafe_libyaml::yaml_scalar_style_t::YAML_PLAIN_SCALAR_STYLE;
+use unsafe_libyaml::yaml_sequence_style_t::YAML_FLOW_SEQUENCE_STYLE;
 
 pub(super) struct Parser<R>
 where
@@ -224,6 +228,48 @@ impl Event {
 	pub(super) fn end_offset(&self) -> u64 {
 		self.0.end_mark.index
 	}
+
+	/// Returns the zero-based line and column where the event starts.
+	pub(super) fn start_line_column(&self) -> (u64, u64) {
+		(self.0.start_mark.line, self.0.start_mark.column)
+	}
+
+	/// Returns whether a sequence or mapping start event opens a flow collection, written between
+	/// brackets or braces rather than in block style.
+	pub(super) fn is_flow_collection(&self) -> bool {
+		match self.0.type_ {
+			YAML_SEQUENCE_START_EVENT => {
+				// SAFETY: libyaml initializes the sequence_start member of the data union for
+				// sequence start events.
+				let start = unsafe { &self.0.data.sequence_start };
+				start.style == YAML_FLOW_SEQUENCE_STYLE
+			}
+			YAML_MAPPING_START_EVENT => {
+				// SAFETY: libyaml initializes the mapping_start member of the data union for
+				// mapping start events.
+				let start = unsafe { &self.0.data.mapping_start };
+				start.style == YAML_FLOW_MAPPING_STYLE
+			}
+			_ => false,
+		}
+	}
+
+	/// Returns the value of a scalar event that is neither quoted nor tagged, whose type a YAML
+	/// parser resolves from its content alone.
+	pub(super) fn plain_scalar(&self) -> Option<&[u8]> {
+		if self.0.type_ != YAML_SCALAR_EVENT {
+			return None;
+		}
+		// SAFETY: libyaml initializes the scalar member of the data union for scalar events.
+		let scalar = unsafe { &self.0.data.scalar };
+		if scalar.style != YAML_PLAIN_SCALAR_STYLE || !scalar.tag.is_null() {
+			return None;
+		}
+		let len = usize::try_from(scalar.length).ok()?;
+		// SAFETY: libyaml allocates the value of a scalar event with the given length, and keeps
+		// it alive until the event is deleted, which the lifetime of the slice bounds.
+		Some(unsafe { slice::from_raw_parts(scalar.value, len) })
+	}
 }
 
 impl Drop for Event {
@@ -18,7 +18,12 @@
 #![allow(clippy::items_after_test_module)]
 
 use std::io;
+use std::num::NonZeroUsize;
 use std::str::from_utf8;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 use rstest::rstest;
 
@@ -99,6 +104,19 @@ fn translate_single_reader_explicit(
 	}
 }
 
+#[rstest]
+fn translate_single_seekable_detected(
+	#[values(Format::Json, Format::Msgpack, Format::Toml, Format::Yaml)] from: Format,
+	#[values(Format::Json, Format::Msgpack, Format::Toml, Format::Yaml)] to: Format,
+) {
+	xt_assert_translation! {
+		input_source = get_single_document_input;
+		translator = translate_seekable_slice;
+		translation = from => to;
+		source_format = None;
+	}
+}
+
 #[rstest]
 fn translate_multi_slice_detected(
 	#[values(Format::Json, Format::Msgpack, Format::Yaml)] from: Format,
@@ -151,6 +169,50 @@ fn translate_multi_reader_explicit(
 	}
 }
 
+#[rstest]
+fn translate_multi_seekable_detected(
+	#[values(Format::Json, Format::Msgpack, Format::Yaml)] from: Format,
+	#[values(Format::Json, Format::Msgpack, Format::Yaml)] to: Format,
+) {
+	xt_assert_translation! {
+		input_source = get_multi_document_input;
+		translator = translate_seekable_slice;
+		translation = from => to;
+		source_format = None;
+	}
+}
+
+/// Translates a slice through a seekable reader over it.
+fn translate_seekable_slice(
+	input: &[u8],
+	from: Option<Format>,
+	to: Format,
+	output: &mut Vec<u8>,
+) -> xt::Result<()> {
+	xt::translate_seekable(io::Cursor::new(input), from, to, output)
+}
+
+/// Tests that parallel serialization produces the same output as translation on
+/// a single thread, for both slice and reader inputs.
+#[rstest]
+fn translate_multi_parallel(
+	#[values(Format::Json, Format::Msgpack, Format::Yaml)] from: Format,
+	#[values(Format::Json, Format::Msgpack, Format::Yaml)] to: Format,
+) {
+	let input = get_multi_document_input(from);
+	let expected = get_multi_document_input(to).repeat(2);
+	let mut output = Vec::with_capacity(expected.len());
+
+	{
+		let mut translator = xt::Translator::new(&mut output, to);
+		translator.set_threads(NonZeroUsize::new(4).unwrap());
+		translator.translate_slice(input, Some(from)).unwrap();
+		translator.translate_reader(input, Some(from)).unwrap();
+	}
+
+	similar_asserts::assert_eq!(expected, output);
+}
+
 /// Returns the single-document test input for a given format.
 ///
 /// TOML's limitations impose several restrictions on these inputs:
@@ -216,6 +278,61 @@ fn get_yaml_encoding_input(name: &str) -> &'static [u8] {
 	}
 }
 
+/// Tests that detection reports the encoding of YAML inputs, and that JSON and
+/// TOML inputs in encodings other than UTF-8 fail with errors that name them.
+#[rstest]
+#[case("utf16be", xt::Encoding::Utf16Be)]
+#[case("utf16le", xt::Encoding::Utf16Le)]
+#[case("utf32le", xt::Encoding::Utf32Le)]
+#[case("utf32lebom", xt::Encoding::Utf32Le)]
+fn input_encoding_reporting(#[case] name: &str, #[case] encoding: xt::Encoding) {
+	let input = get_yaml_encoding_input(name);
+	let detection = xt::detect_slice(input).unwrap();
+	assert_eq!(detection.encoding(), encoding);
+	let detection = xt::detect_slice(YAML_ENCODING_RESULT.as_bytes()).unwrap();
+	assert_eq!(detection.encoding(), xt::Encoding::Utf8);
+
+	for from in [Format::Json, Format::Toml] {
+		let err = xt::translate_reader(input, Some(from), Format::Json, io::sink()).unwrap_err();
+		let expected = format!("input appears to be {encoding}, but {from} input must be UTF-8");
+		assert!(err.to_string().starts_with(&expected), "{err}");
+	}
+}
+
+/// Tests that YAML output in each text encoding translates back to the
+/// original documents, including through parallel serialization.
+#[rstest]
+fn yaml_output_encoding(
+	#[values(
+		xt::Encoding::Utf16Be,
+		xt::Encoding::Utf16Le,
+		xt::Encoding::Utf32Be,
+		xt::Encoding::Utf32Le
+	)]
+	encoding: xt::Encoding,
+	#[values(false, true)] bom: bool,
+	#[values(1, 2)] threads: usize,
+) {
+	let input = YAML_ENCODING_RESULT.repeat(2);
+	let mut yaml = vec![];
+	let mut translator = xt::Translator::new(&mut yaml, Format::Yaml);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.set_output_encoding(encoding);
+	translator.set_byte_order_mark(bom);
+	translator
+		.translate_slice(input.as_bytes(), Some(Format::Json))
+		.unwrap();
+	drop(translator);
+	assert_eq!(
+		yaml.starts_with(&[0xfe, 0xff]),
+		bom && encoding == xt::Encoding::Utf16Be
+	);
+
+	let mut output = vec![];
+	xt::translate_slice(&yaml, Some(Format::Yaml), Format::Json, &mut output).unwrap();
+	assert_eq!(from_utf8(&output), Ok(input.as_str()));
+}
+
 /// Tests that TOML output re-orders inputs as needed to meet TOML-specific
 /// requirements, in particular that all non-table values must appear before any
 /// tables at the same level.
@@ -228,6 +345,29 @@ fn toml_reordering() {
 	assert_eq!(std::str::from_utf8(&output), Ok(EXPECTED));
 }
 
+/// Tests that TOML output with a container wraps every document in an array of
+/// tables, and that the result parses back to the same documents.
+#[test]
+fn toml_container_multi_document() {
+	const INPUT: &[u8] = b"a: 1\nb: {c: 2}\n---\na: 3\n";
+	const EXPECTED: &str = "[[documents]]\na = 1\n\n[documents.b]\nc = 2\n\n[[documents]]\na = 3\n";
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Toml);
+	translator.set_toml_container(Some("documents".into()));
+	translator
+		.translate_slice(INPUT, Some(Format::Yaml))
+		.unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+
+	let mut json = vec![];
+	xt::translate_slice(&output, Some(Format::Toml), Format::Json, &mut json).unwrap();
+	assert_eq!(
+		from_utf8(&json),
+		Ok("{\"documents\":[{\"a\":1,\"b\":{\"c\":2}},{\"a\":3}]}\n")
+	);
+}
+
 /// Tests that a TOML input that starts with a table is not accidentally
 /// mis-detected as YAML. This happened with an early version of streaming YAML
 /// input support, since a YAML parser can successfully parse a TOML table
@@ -250,3 +390,2236 @@ fn yaml_halting_without_panic() {
 	const INPUT: &[u8] = include_bytes!("nullkey.yaml");
 	let _ = xt::translate_slice(INPUT, Some(Format::Yaml), Format::Json, std::io::sink());
 }
+
+/// Tests that a translator buffers its output internally unless told not to,
+/// so that unbuffered writers don't see a separate write for every small piece
+/// of every document.
+#[test]
+fn translator_output_buffering() {
+	const INPUT: &[u8] = include_bytes!("multi.json");
+
+	let mut buffered = WriteCounter::default();
+	xt::Translator::new(&mut buffered, Format::Json)
+		.translate_slice(INPUT, Some(Format::Json))
+		.unwrap();
+	assert_eq!(buffered.writes, 1);
+
+	let mut unbuffered = WriteCounter::default();
+	let mut translator = xt::Translator::with_capacity(0, &mut unbuffered, Format::Json);
+	translator.set_atomic_documents(false);
+	translator
+		.translate_slice(INPUT, Some(Format::Json))
+		.unwrap();
+	drop(translator);
+	assert!(unbuffered.writes > 1);
+	assert_eq!(buffered.bytes, unbuffered.bytes);
+}
+
+/// Tests that a translator without an output buffer still writes complete
+/// documents in batches, rather than making a separate write for each one,
+/// unless it flushes each document.
+#[rstest]
+fn translator_unbuffered_batching(#[values(false, true)] flush_each: bool) {
+	const COUNT: usize = 10_000;
+	let input = "{\"xt\": 1}\n".repeat(COUNT);
+
+	let mut output = WriteCounter::default();
+	let mut translator = xt::Translator::with_capacity(0, &mut output, Format::Json);
+	translator.set_flush_each_document(flush_each);
+	translator
+		.translate_slice(input.as_bytes(), Some(Format::Json))
+		.unwrap();
+	drop(translator);
+
+	if flush_each {
+		assert_eq!(output.writes, COUNT);
+	} else {
+		assert!(output.writes <= output.bytes / 8192 + 1);
+	}
+	assert_eq!(output.bytes, "{\"xt\":1}\n".len() * COUNT);
+}
+
+/// A writer that counts the calls made to it.
+#[derive(Default)]
+struct WriteCounter {
+	writes: usize,
+	bytes: usize,
+	flushes: usize,
+}
+
+impl io::Write for WriteCounter {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.writes += 1;
+		self.bytes += buf.len();
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.flushes += 1;
+		Ok(())
+	}
+}
+
+/// Tests that a translator's memory budget limits the buffering of reader
+/// inputs, where each of the formats may buffer input in a different way.
+#[rstest]
+#[case::detect_json(b"{\"xt\": \"long enough\"}", None)]
+#[case::toml_full_input(b"xt = \"long enough\"", Some(Format::Toml))]
+#[case::yaml_document(b"xt: long enough to go over", Some(Format::Yaml))]
+fn translator_memory_budget_exceeded(#[case] input: &[u8], #[case] from: Option<Format>) {
+	let mut translator = xt::Translator::new(io::sink(), Format::Json);
+	translator.set_memory_budget(Some(16));
+	let err = translator.translate_reader(input, from).unwrap_err();
+	assert!(
+		err.to_string().contains("memory budget of 16 bytes"),
+		"{err}"
+	);
+
+	// Slices are already fully in memory, and shouldn't be subject to the budget.
+	translator.translate_slice(input, from).unwrap();
+}
+
+/// Tests that format detection on seekable readers seeks back to the start of
+/// the input rather than buffering it under the memory budget.
+#[test]
+fn translator_memory_budget_seekable_detection() {
+	const INPUT: &[u8] = b"{\"xt\": \"long enough\"}";
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Msgpack);
+	translator.set_memory_budget(Some(16));
+	translator
+		.translate_seekable(io::Cursor::new(INPUT), None)
+		.unwrap();
+	drop(translator);
+
+	let mut expected = vec![];
+	xt::translate_slice(INPUT, Some(Format::Json), Format::Msgpack, &mut expected).unwrap();
+	assert_eq!(output, expected);
+}
+
+/// Tests that a translator's memory budget limits the total size of its input
+/// buffers, where detecting a YAML input keeps the same bytes in both the
+/// capture buffer and the YAML document buffer, but neither buffer alone goes
+/// over the budget.
+#[test]
+fn translator_memory_budget_combined_buffers() {
+	const INPUT: &[u8] = b"xt: long enough to go over";
+	let mut translator = xt::Translator::new(io::sink(), Format::Json);
+	translator.set_memory_budget(Some(40));
+	translator
+		.translate_reader(INPUT, Some(Format::Yaml))
+		.unwrap();
+	let err = translator.translate_reader(INPUT, None).unwrap_err();
+	assert!(
+		err.to_string().contains("memory budget of 40 bytes"),
+		"{err}"
+	);
+}
+
+/// Tests that a translator's memory budget applies to individual YAML documents
+/// rather than an entire streaming input.
+#[test]
+fn translator_memory_budget_yaml_stream() {
+	const BUDGET: usize = 64 * 1024;
+	let input = "---\nxt: true\n".repeat(2 * BUDGET / 13);
+	let mut translator = xt::Translator::new(io::sink(), Format::Json);
+	translator.set_memory_budget(Some(BUDGET));
+	translator
+		.translate_reader(input.as_bytes(), Some(Format::Yaml))
+		.unwrap();
+}
+
+/// Tests that splitting top-level JSON arrays produces the same output as a
+/// stream of the individual elements, for both slice and reader inputs.
+#[test]
+fn split_json_arrays() {
+	const INPUT: &[u8] = br#"[{"xt": 1}, [2, 3], "four"] 5 [] [6]"#;
+	const EXPECTED: &str = "{\"xt\":1}\n[2,3]\n\"four\"\n5\n6\n";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_split_json_arrays(true);
+	translator
+		.translate_slice(INPUT, Some(Format::Json))
+		.unwrap();
+	translator
+		.translate_reader(INPUT, Some(Format::Json))
+		.unwrap();
+	translator.translate_reader(INPUT, None).unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED.repeat(3).as_str()));
+}
+
+/// Tests that an output error while splitting a JSON array reports the original
+/// output error, for both slice and reader inputs.
+#[rstest]
+fn split_json_arrays_output_error(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = br#"[{"xt": 1}, {"xt": 2}]"#;
+
+	let mut translator = xt::Translator::new(io::sink(), Format::Toml);
+	translator.set_split_json_arrays(true);
+	let err = if reader {
+		translator.translate_reader(INPUT, Some(Format::Json))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Json))
+	}
+	.unwrap_err();
+	assert!(!err.to_string().contains("translation failed"), "{err}");
+}
+
+/// Tests that splitting a JSON array outputs each element as soon as it's
+/// parsed, rather than once the whole array is, for both slice and reader
+/// inputs.
+#[rstest]
+fn split_json_arrays_incrementally(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"[1, 2, {";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_split_json_arrays(true);
+	let result = if reader {
+		translator.translate_reader(INPUT, Some(Format::Json))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Json))
+	};
+	assert!(result.is_err());
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("1\n2\n"));
+}
+
+/// Tests that a translator can flush its output after every document, rather
+/// than only at the end of each input.
+#[test]
+fn translator_flush_each_document() {
+	const INPUT: &[u8] = b"{}\n{}\n{}\n";
+
+	let mut counter = WriteCounter::default();
+	xt::Translator::new(&mut counter, Format::Json)
+		.translate_reader(INPUT, Some(Format::Json))
+		.unwrap();
+	assert_eq!(counter.flushes, 1);
+
+	let mut counter = WriteCounter::default();
+	let mut translator = xt::Translator::new(&mut counter, Format::Json);
+	translator.set_flush_each_document(true);
+	translator
+		.translate_reader(INPUT, Some(Format::Json))
+		.unwrap();
+	drop(translator);
+	assert_eq!(counter.writes, 3);
+	assert_eq!(counter.flushes, 4);
+}
+
+/// Tests that interning the keys of documents held in memory leaves the
+/// output of a translator unchanged.
+#[rstest]
+fn translator_intern_keys(#[values(1, 2)] threads: usize) {
+	const INPUT: &[u8] = br#"{"k":"a","v":{"n":1}} {"k":"b","v":{"n":2}} {"k":"a","v":[]}"#;
+	const EXPECTED: &str =
+		r#"{"a":[{"k":"a","v":{"n":1}},{"k":"a","v":[]}],"b":[{"k":"b","v":{"n":2}}]}"#;
+
+	let translate = |configure: fn(&mut xt::Translator<&mut Vec<u8>>)| {
+		let mut output = vec![];
+		let mut translator = xt::Translator::new(&mut output, Format::Json);
+		translator.set_threads(NonZeroUsize::new(threads).unwrap());
+		translator.set_intern_keys(true);
+		configure(&mut translator);
+		translator
+			.translate_reader(INPUT, Some(Format::Json))
+			.unwrap();
+		translator.finish().unwrap();
+		drop(translator);
+		String::from_utf8(output).unwrap()
+	};
+	assert_eq!(
+		translate(|_| {}),
+		from_utf8(INPUT).unwrap().replace(' ', "\n") + "\n"
+	);
+	assert_eq!(
+		translate(|t| t.set_tail(Some(2))),
+		from_utf8(INPUT)
+			.unwrap()
+			.split_once(' ')
+			.unwrap()
+			.1
+			.replace(' ', "\n")
+			+ "\n"
+	);
+	assert_eq!(
+		translate(|t| t.set_group_by(Some(".k")).unwrap()),
+		EXPECTED.to_owned() + "\n"
+	);
+}
+
+/// Tests that a translator pretty prints JSON output with the given
+/// indentation, whether or not it could serialize on multiple threads.
+#[rstest]
+fn translator_json_indent(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	const INPUT: &[u8] = br#"{"a":[1,{"b":null}]} {}"#;
+	const EXPECTED: &str =
+		"{\n   \"a\": [\n      1,\n      {\n         \"b\": null\n      }\n   ]\n}\n{}\n";
+
+	let mut output = Vec::with_capacity(EXPECTED.len());
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.set_json_indent(Some(3));
+	if reader {
+		translator.translate_reader(INPUT, Some(Format::Json))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Json))
+	}
+	.unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output).unwrap(), EXPECTED);
+}
+
+/// Tests that a translator warns about risky YAML scalars that are unquoted in
+/// its input, and unquoted in its output.
+#[rstest]
+fn translator_yaml_warnings(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"a: no\nb: '0123'\n---\n- 1:20\n- !!str 2001-12-14\n";
+
+	let warnings = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Yaml);
+	translator.set_source_name(Some("in.yaml".into()));
+	translator.set_yaml_warning_handler({
+		let warnings = Arc::clone(&warnings);
+		move |warning| warnings.lock().unwrap().push(warning)
+	});
+	if reader {
+		translator.translate_reader(INPUT, Some(Format::Yaml))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Yaml))
+	}
+	.unwrap();
+	drop(translator);
+
+	let warnings = warnings.lock().unwrap();
+	let got: Vec<_> = warnings
+		.iter()
+		.map(|w| {
+			let place = match w.path() {
+				Some(path) => path.to_owned(),
+				None => format!("{:?}", w.line_column().unwrap()),
+			};
+			(w.risk(), w.value(), w.document_index(), place)
+		})
+		.collect();
+	assert_eq!(
+		got,
+		[
+			(xt::YamlRisk::Boolean, "no", 0, "(1, 4)".to_owned()),
+			(xt::YamlRisk::Boolean, "no", 0, ".a".to_owned()),
+			(xt::YamlRisk::Sexagesimal, "1:20", 1, "(4, 3)".to_owned()),
+			(xt::YamlRisk::Sexagesimal, "1:20", 1, "[0]".to_owned()),
+			(xt::YamlRisk::Timestamp, "2001-12-14", 1, "[1]".to_owned()),
+		]
+	);
+	assert!(warnings.iter().all(|w| w.is_output() == w.path().is_some()));
+	assert!(warnings[0].source() == Some("in.yaml") && warnings[1].source().is_none());
+}
+
+/// Tests that a lenient translator skips commas and comments between JSON
+/// documents, while keeping the offsets of each document in the original input.
+#[rstest]
+fn translator_lenient_json(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"# header\n{\"a\":\"x,#\"},\n// note\n[1, 2]\n,3\n";
+
+	let offsets = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_lenient_json(true);
+	translator.set_document_handler({
+		let offsets = Arc::clone(&offsets);
+		move |summary| {
+			offsets.lock().unwrap().push(summary?.offset());
+			Ok(())
+		}
+	});
+	if reader {
+		translator.translate_reader(INPUT, Some(Format::Json))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Json))
+	}
+	.unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output).unwrap(), "{\"a\":\"x,#\"}\n[1,2]\n3\n");
+	assert_eq!(*offsets.lock().unwrap(), [Some(9), Some(30), Some(38)]);
+}
+
+/// Tests that a translator produces the same JSON from MessagePack input
+/// whether or not it translates documents without serde, including documents
+/// that only serde can translate.
+#[rstest]
+fn translator_msgpack_to_json(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	// {"a": [1, -1.5, bin [0xff]]}; {1: "x"}; "\u{2028}"; [null, true]
+	const INPUT: &[u8] =
+		b"\x81\xa1a\x93\x01\xcb\xbf\xf8\0\0\0\0\0\0\xc4\x01\xff\x81\x01\xa1x\xa3\xe2\x80\xa8\x92\xc0\xc3";
+	const EXPECTED: &str = "{\"a\":[1,-1.5,[255]]}\n{\"1\":\"x\"}\n\"\u{2028}\"\n[null,true]\n";
+
+	let translate = |head| {
+		let mut output = vec![];
+		let mut translator = xt::Translator::new(&mut output, Format::Json);
+		translator.set_threads(NonZeroUsize::new(threads).unwrap());
+		translator.set_head(head);
+		if reader {
+			translator.translate_reader(INPUT, Some(Format::Msgpack))
+		} else {
+			translator.translate_slice(INPUT, Some(Format::Msgpack))
+		}
+		.unwrap();
+		drop(translator);
+		String::from_utf8(output).unwrap()
+	};
+	assert_eq!(translate(None), EXPECTED);
+	assert_eq!(
+		translate(Some(2)),
+		"{\"a\":[1,-1.5,[255]]}\n{\"1\":\"x\"}\n"
+	);
+}
+
+/// Tests that a translator produces the same MessagePack from JSON input
+/// whether or not it translates documents without serde, and that errors from
+/// documents only serde can translate keep their locations.
+#[rstest]
+fn translator_json_to_msgpack(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	const INPUT: &[u8] = br#"{"a": [1, -1.5, "\u00e9"]} null [] [1,]"#;
+	const EXPECTED: &[u8] = b"\x81\xa1a\x93\x01\xcb\xbf\xf8\0\0\0\0\0\0\xa2\xc3\xa9\xc0\x90";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Msgpack);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	let err = if reader {
+		translator.translate_reader(INPUT, Some(Format::Json))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Json))
+	}
+	.unwrap_err();
+	drop(translator);
+	assert_eq!(output, EXPECTED);
+	assert_eq!(err.document_index(), Some(3));
+	assert_eq!(err.document_offset(), reader.then_some(35));
+}
+
+/// Tests that strictness profiles accept or reject each kind of input they
+/// cover, and that the individual setting for that kind of input overrides
+/// them. `set` accepts the input when called with `accepts`.
+#[rstest]
+#[case::duplicate_keys(
+	Format::Json,
+	br#"{"a":1,"a":2}"#,
+	Sink::set_reject_duplicate_keys,
+	false
+)]
+#[case::json_separators(Format::Json, br#"{"a":1},{"a":2}"#, Sink::set_lenient_json, true)]
+#[case::yaml_scalars(Format::Yaml, b"a: no\n", Sink::set_reject_risky_yaml, false)]
+#[case::msgpack_ext(
+	Format::Msgpack,
+	b"\x91\xd4\x05\xaa",
+	Sink::set_translate_msgpack_ext,
+	true
+)]
+fn translator_strictness(
+	#[case] from: Format,
+	#[case] input: &[u8],
+	#[case] set: fn(&mut Sink, bool),
+	#[case] accepts: bool,
+) {
+	let translate = |strictness, accept: Option<bool>| {
+		let mut translator = xt::Translator::new(io::sink(), Format::Json);
+		translator.set_strictness(strictness);
+		if let Some(accept) = accept {
+			set(&mut translator, accept == accepts);
+		}
+		translator.translate_slice(input, Some(from))
+	};
+	translate(xt::Strictness::Strict, None).unwrap_err();
+	translate(xt::Strictness::Lenient, None).unwrap();
+	translate(xt::Strictness::Strict, Some(true)).unwrap();
+	translate(xt::Strictness::Lenient, Some(false)).unwrap_err();
+}
+
+#[test]
+fn reformat_yaml_keeps_comments() {
+	const INPUT: &[u8] = b"# config\nserver:\n    host:   example.com  # primary\n\n    ports:\n        - 80\n        # tls\n        - 443\n";
+	const OUTPUT: &str =
+		"# config\nserver:\n  host: example.com # primary\n\n  ports:\n  - 80\n  # tls\n  - 443\n";
+
+	let output = xt::reformat_yaml(INPUT).unwrap();
+	assert_eq!(from_utf8(&output).unwrap(), OUTPUT);
+
+	let translate = |input: &[u8]| {
+		let mut json = vec![];
+		xt::translate_slice(input, Some(Format::Yaml), Format::Json, &mut json).unwrap();
+		json
+	};
+	assert_eq!(translate(INPUT), translate(&output));
+}
+
+/// A translator that discards its output.
+type Sink = xt::Translator<io::Sink>;
+
+/// Tests that a failure partway through a document leaves none of that document
+/// in the output, unless atomic documents are disabled.
+#[rstest]
+fn translator_atomic_documents(#[values(false, true)] atomic: bool) {
+	const INPUT: &[u8] = b"{\"a\":1}\n{\"b\":[1,2,";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_atomic_documents(atomic);
+	translator
+		.translate_reader(INPUT, Some(Format::Json))
+		.unwrap_err();
+	drop(translator);
+	let expected = if atomic {
+		"{\"a\":1}\n"
+	} else {
+		"{\"a\":1}\n{\"b\":[1,2"
+	};
+	assert_eq!(from_utf8(&output), Ok(expected));
+}
+
+/// Tests that redaction replaces the values of matching keys at any depth, for
+/// both slice and reader inputs and for parallel serialization.
+#[rstest]
+fn translator_redact(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	const INPUT: &[u8] =
+		b"user: xt\npassword: hunter2\n---\nauth:\n  - api_token: 42\n    api_url: x\n";
+	const EXPECTED: &str = concat!(
+		r#"{"user":"xt","password":"[REDACTED]"}"#,
+		"\n",
+		r#"{"auth":[{"api_token":"[REDACTED]","api_url":"x"}]}"#,
+		"\n",
+	);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.add_transform(xt::Transform::redact(["password", "*_token"]));
+	if reader {
+		translator
+			.translate_reader(INPUT, Some(Format::Yaml))
+			.unwrap();
+	} else {
+		translator
+			.translate_slice(INPUT, Some(Format::Yaml))
+			.unwrap();
+	}
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that provenance records the source name, document index, and byte
+/// offset of each document, for both slice and reader inputs and for parallel
+/// serialization.
+#[rstest]
+fn translator_provenance(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	const INPUT: &[u8] = b"{\"a\":1}\n  {\"b\":2}\n[3]\n";
+	const EXPECTED: &str = concat!(
+		r#"{"a":1,"xt":{"source":"in.json","document":0,"offset":0}}"#,
+		"\n",
+		r#"{"b":2,"xt":{"source":"in.json","document":1,"offset":10}}"#,
+		"\n",
+		"[3]\n",
+		r#"{"a":1,"xt":{"document":0,"offset":0}}"#,
+		"\n",
+		r#"{"b":2,"xt":{"document":1,"offset":10}}"#,
+		"\n",
+		"[3]\n",
+	);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.add_transform(xt::Transform::provenance("xt"));
+	for source in [Some("in.json".into()), None] {
+		translator.set_source_name(source);
+		if reader {
+			translator
+				.translate_reader(INPUT, Some(Format::Json))
+				.unwrap();
+		} else {
+			translator
+				.translate_slice(INPUT, Some(Format::Json))
+				.unwrap();
+		}
+	}
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that an error from translating a document reports the index and offset
+/// of the document, including a serialization error from a worker thread.
+#[rstest]
+fn translator_error_location(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	// The second document is a map with an array key, which JSON can't represent.
+	const INPUT: &[u8] = &[0x81, 0xa1, b'a', 0x01, 0x81, 0x90, 0x01];
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	let err = if reader {
+		translator.translate_reader(INPUT, Some(Format::Msgpack))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Msgpack))
+	}
+	.unwrap_err();
+	assert_eq!(err.document_index(), Some(1));
+	assert_eq!(err.document_offset(), Some(4));
+	assert!(
+		err.to_string()
+			.ends_with(" (in document 1, starting at byte 4)"),
+		"{err}"
+	);
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("{\"a\":1}\n"));
+}
+
+/// Tests that chained readers translate as one input, with documents and errors
+/// attributed to the reader they start in, even when a document spans readers.
+#[rstest]
+fn translator_chained_readers(#[values(1, 2)] threads: usize) {
+	const INPUTS: &[&[u8]] = &[b"{\"a\":1}\n{\"b\":", b"2}\n", b"", b"{\"c\":3}\n{\"d\"}"];
+	const EXPECTED: &str = concat!(
+		r#"{"a":1,"xt":{"input":0,"document":0,"offset":0}}"#,
+		"\n",
+		r#"{"b":2,"xt":{"input":0,"document":1,"offset":8}}"#,
+		"\n",
+		r#"{"c":3,"xt":{"input":3,"document":0,"offset":0}}"#,
+		"\n",
+	);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.add_transform(xt::Transform::provenance("xt"));
+	let err = translator
+		.translate_readers(INPUTS.iter().copied(), Some(Format::Json))
+		.unwrap_err();
+	assert_eq!(err.input_index(), Some(3));
+	assert_eq!(err.document_index(), Some(1));
+	assert_eq!(err.document_offset(), Some(8));
+	assert!(
+		err.to_string()
+			.ends_with(" (in input 3, document 1, starting at byte 8)"),
+		"{err}"
+	);
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that a syntax error reports the index of the document it occurs in,
+/// including for slice inputs that parse each document before outputting it.
+#[rstest]
+fn translator_syntax_error_location(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"{\"a\":1}\n{\"b\":";
+
+	let mut translator = xt::Translator::new(io::sink(), Format::Json);
+	let err = if reader {
+		translator.translate_reader(INPUT, Some(Format::Json))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Json))
+	}
+	.unwrap_err();
+	assert_eq!(err.document_index(), Some(1));
+}
+
+/// Tests that a document handler sees the result of every document, and can
+/// skip failed documents to continue translation.
+#[rstest]
+fn translator_document_handler(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"{\"a\":1}\n[2]\n{\"b\":3}\n";
+
+	let results = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Toml);
+	translator.set_toml_container(Some("docs".into()));
+	translator.set_document_handler({
+		let results = Arc::clone(&results);
+		move |result| {
+			let mut results = results.lock().unwrap();
+			results.push(match result {
+				Ok(summary) => (summary.index(), summary.offset(), true),
+				Err(err) => (err.document_index().unwrap(), err.document_offset(), false),
+			});
+			Ok(())
+		}
+	});
+	if reader {
+		translator
+			.translate_reader(INPUT, Some(Format::Json))
+			.unwrap();
+	} else {
+		translator
+			.translate_slice(INPUT, Some(Format::Json))
+			.unwrap();
+	}
+	drop(translator);
+	assert_eq!(
+		from_utf8(&output),
+		Ok("[[docs]]\na = 1\n\n[[docs]]\nb = 3\n")
+	);
+	assert_eq!(
+		*results.lock().unwrap(),
+		[(0, Some(0), true), (1, Some(8), false), (2, Some(12), true)]
+	);
+}
+
+/// Tests that a document handler can stop translation at the first failure.
+#[test]
+fn translator_document_handler_abort() {
+	const INPUT: &[u8] = b"{\"a\":1}\n[2]\n{\"b\":3}\n";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Toml);
+	translator.set_toml_container(Some("docs".into()));
+	translator.set_document_handler(|result| result.map(|_| ()));
+	let err = translator
+		.translate_slice(INPUT, Some(Format::Json))
+		.unwrap_err();
+	assert_eq!(err.document_index(), Some(1));
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("[[docs]]\na = 1\n"));
+}
+
+/// Tests that the detection order decides between formats that both match an
+/// input, and can restrict detection to a subset of formats.
+#[test]
+fn translator_detection_order() {
+	const INPUT: &[u8] = b"[xt]\n";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.translate_slice(INPUT, None).unwrap();
+	translator.set_detection_order(&[Format::Toml, Format::Yaml]);
+	translator.translate_slice(INPUT, None).unwrap();
+	translator.set_detection_order(&[Format::Json]);
+	let err = translator.translate_slice(INPUT, None).unwrap_err();
+	assert!(err.detection().is_some_and(|d| d.probes().len() == 1));
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("[\"xt\"]\n{\"xt\":{}}\n"));
+}
+
+/// Tests that a detection limit keeps formats from matching reader inputs that
+/// need more than the limit, and that the detection error says so.
+#[rstest]
+#[case::json(format!("[{}]", ["\"xt\""; 2048].join(",")), Format::Json)]
+#[case::toml((0..1024).map(|i| format!("xt{i} = true\n")).collect(), Format::Toml)]
+fn translator_detection_limit(#[case] input: String, #[case] format: Format) {
+	let mut translator = xt::Translator::new(io::sink(), Format::Json);
+	translator.set_detection_order(&[format]);
+	translator.set_detection_limit(Some(input.len() - 1));
+	let err = translator
+		.translate_reader(input.as_bytes(), None)
+		.unwrap_err();
+	let limit_message = format!("detection limit of {} bytes", input.len() - 1);
+	assert!(err.to_string().contains(&limit_message), "{err}");
+
+	translator.set_detection_limit(Some(input.len()));
+	translator.translate_reader(input.as_bytes(), None).unwrap();
+	translator.translate_slice(input.as_bytes(), None).unwrap();
+}
+
+/// Tests that documents larger than the maximum size fail, and that a document
+/// handler can skip them wherever their size is known before parsing.
+#[rstest]
+#[case::json(b"[1] 12345 [1,2,3,4,5,6,7,8,9] 7", Format::Json, 5, false)]
+#[case::json_number(b"[1] 12345 123456 7", Format::Json, 5, false)]
+#[case::msgpack(
+	b"\x91\x01\xcd\x30\x39\x99\x01\x02\x03\x04\x05\x06\x07\x08\x09\x07",
+	Format::Msgpack,
+	3,
+	false
+)]
+#[case::yaml(
+	b"--- [1]\n--- 12345\n--- [1,2,3,4,5,6,7,8,9]\n--- 7\n",
+	Format::Yaml,
+	10,
+	true
+)]
+fn translator_max_document_size(
+	#[case] input: &[u8],
+	#[case] format: Format,
+	#[case] max: u64,
+	#[case] skips_from_reader: bool,
+	#[values(false, true)] reader: bool,
+) {
+	let errors = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_max_document_size(Some(max));
+	translator.set_document_handler({
+		let errors = Arc::clone(&errors);
+		move |result| {
+			if let Err(err) = result {
+				errors.lock().unwrap().push(err.to_string());
+			}
+			Ok(())
+		}
+	});
+	let result = if reader {
+		translator.translate_reader(input, Some(format))
+	} else {
+		translator.translate_slice(input, Some(format))
+	};
+	drop(translator);
+
+	let message = format!("exceeds the maximum document size of {max} bytes");
+	if reader && !skips_from_reader {
+		let err = result.unwrap_err();
+		assert!(err.to_string().contains(&message), "{err}");
+		assert_eq!(err.document_index(), Some(2));
+		assert_eq!(from_utf8(&output), Ok("[1]\n12345\n"));
+	} else {
+		result.unwrap();
+		let errors = errors.lock().unwrap();
+		assert!(
+			errors.len() == 1 && errors[0].contains(&message),
+			"{errors:?}"
+		);
+		assert_eq!(from_utf8(&output), Ok("[1]\n12345\n7\n"));
+	}
+}
+
+/// Tests that skip, head, and tail select a range of documents across inputs,
+/// for both slice and reader inputs and for parallel serialization.
+#[rstest]
+fn translator_document_window(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.set_skip(1);
+	translator.set_head(Some(4));
+	translator.set_tail(Some(2));
+	for input in [&b"1 2 3"[..], b"4 5 6", b"not json"] {
+		if reader {
+			translator
+				.translate_reader(input, Some(Format::Json))
+				.unwrap();
+		} else {
+			translator
+				.translate_slice(input, Some(Format::Json))
+				.unwrap();
+		}
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("4\n5\n"));
+}
+
+/// Tests that a full head stops parsing the input, so that invalid input after
+/// the head doesn't fail the translation.
+#[rstest]
+fn translator_head_stops_parsing(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"{\"a\":1}\n{\"b\":2}\n{invalid";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Yaml);
+	translator.set_head(Some(2));
+	if reader {
+		translator
+			.translate_reader(INPUT, Some(Format::Json))
+			.unwrap();
+	} else {
+		translator
+			.translate_slice(INPUT, Some(Format::Json))
+			.unwrap();
+	}
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("---\na: 1\n---\nb: 2\n"));
+}
+
+/// Tests that setting the interrupt flag stops translation after the current
+/// document, and skips every later input.
+#[rstest]
+fn translator_interrupt_flag(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"1 2 3 {invalid";
+
+	let flag = Arc::new(AtomicBool::new(false));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_interrupt_flag(Some(Arc::clone(&flag)));
+	translator.set_document_handler({
+		let flag = Arc::clone(&flag);
+		move |summary| {
+			if summary?.index() == 1 {
+				flag.store(true, Ordering::Relaxed);
+			}
+			Ok(())
+		}
+	});
+	for input in [INPUT, b"4"] {
+		if reader {
+			translator
+				.translate_reader(input, Some(Format::Json))
+				.unwrap();
+		} else {
+			translator
+				.translate_slice(input, Some(Format::Json))
+				.unwrap();
+		}
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("1\n2\n"));
+}
+
+/// Tests that the span handler receives the bounds of every input document,
+/// whether the input is a slice or a reader, and whether or not the document
+/// makes it to the output. Translations without transforms between JSON and
+/// MessagePack take the direct path through MessagePack.
+#[rstest]
+#[case(Format::Json, b"{\"a\":1}  [2]\n-3.5e1 \"x\" 42", &[(0, 7), (9, 12), (13, 19), (20, 23), (24, 26)])]
+#[case(Format::Msgpack, b"\x81\xa1a\x01\x91\x02\xa1x", &[(0, 4), (4, 6), (6, 8)])]
+#[case(Format::Yaml, b"a: 1\n---\n- 2\n...\n--- x\n", &[(0, 5), (5, 16), (17, 23)])]
+#[case(Format::Toml, b"a = 1\n", &[(0, 6)])]
+fn translator_document_spans(
+	#[case] from: Format,
+	#[case] input: &[u8],
+	#[case] expected: &[(u64, u64)],
+	#[values(false, true)] reader: bool,
+	#[values(false, true)] direct: bool,
+) {
+	let to = match from {
+		Format::Json => Format::Msgpack,
+		_ => Format::Json,
+	};
+	let spans = Arc::new(Mutex::new(vec![]));
+	let mut translator = xt::Translator::new(io::sink(), to);
+	translator.set_document_span_handler({
+		let spans = Arc::clone(&spans);
+		move |span| spans.lock().unwrap().push(span)
+	});
+	translator.set_skip(1);
+	if !direct {
+		translator.add_transform(xt::Transform::sort_keys());
+	}
+	if reader {
+		translator.translate_reader(input, Some(from)).unwrap();
+	} else {
+		translator.translate_slice(input, Some(from)).unwrap();
+	}
+	drop(translator);
+
+	let spans = spans.lock().unwrap();
+	let ranges: Vec<_> = spans
+		.iter()
+		.map(|span| (span.range().start, span.range().end))
+		.collect();
+	assert_eq!(ranges, expected);
+	assert!(
+		spans
+			.iter()
+			.map(xt::DocumentSpan::index)
+			.eq(0..spans.len() as u64)
+	);
+}
+
+/// Tests that null output writes nothing, but still parses every document far
+/// enough to fail on invalid input, and can't be the format of an input.
+#[rstest]
+#[case(Format::Json, b"{\"a\":[1,2]} {\"b\":tru}")]
+#[case(Format::Yaml, b"a: [1, 2]\n---\nb: [\n")]
+#[case(Format::Msgpack, b"\x81\xa1a\x92\x01\x02\x81\xa1b\xc1")]
+fn translator_null_output(
+	#[case] from: Format,
+	#[case] input: &[u8],
+	#[values(false, true)] reader: bool,
+	#[values(1, 2)] threads: usize,
+) {
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Null);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	let err = if reader {
+		translator.translate_reader(input, Some(from)).unwrap_err()
+	} else {
+		translator.translate_slice(input, Some(from)).unwrap_err()
+	};
+	assert_eq!(err.document_index(), Some(1));
+	translator.finish().unwrap();
+	drop(translator);
+	assert!(output.is_empty());
+
+	let err = xt::translate_slice(b"{}", Some(Format::Null), Format::Json, io::sink());
+	assert!(
+		err.unwrap_err()
+			.to_string()
+			.starts_with("null is an output format only")
+	);
+}
+
+/// Tests that CSV and TSV inputs translate each record after the header as a
+/// map of strings, whether the input is a slice or a reader.
+#[rstest]
+#[case(
+	Format::Csv,
+	b"name,note\r\nada,\"first, \"\"only\"\"\"\r\n\r\nbob,\r\n"
+)]
+#[case(Format::Tsv, b"name\tnote\nada\tfirst, \"only\"\nbob\t")]
+fn translator_csv_input(
+	#[case] from: Format,
+	#[case] input: &[u8],
+	#[values(false, true)] reader: bool,
+) {
+	const EXPECTED: &str = concat!(
+		r#"{"name":"ada","note":"first, \"only\""}"#,
+		"\n",
+		r#"{"name":"bob","note":""}"#,
+		"\n",
+	);
+
+	let mut output = vec![];
+	if reader {
+		xt::translate_reader(input, Some(from), Format::Json, &mut output).unwrap();
+	} else {
+		xt::translate_slice(input, Some(from), Format::Json, &mut output).unwrap();
+	}
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that CSV output writes a header from the keys of the first record,
+/// followed by a record for each map document or map in an array document, and
+/// that records with other keys fail or get empty fields as requested.
+#[rstest]
+fn translator_csv_output() {
+	const INPUT: &[u8] = br#"{"name":"ada","note":"first, \"only\"","n":1}
+[{"n":2.5,"name":"bob","note":null},{"name":"cy","note":"a\tb","n":true}]
+{"name":"dee","extra":1}
+"#;
+	const EXPECTED: &str = "name,note,n\nada,\"first, \"\"only\"\"\",1\nbob,,2.5\ncy,a\tb,true\n";
+
+	let mut output = vec![];
+	let err = xt::translate_slice(INPUT, Some(Format::Json), Format::Csv, &mut output);
+	assert!(
+		err.unwrap_err()
+			.to_string()
+			.contains("record has the field \"extra\" outside the header")
+	);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+
+	let mut output = vec![];
+	{
+		let mut translator = xt::Translator::new(&mut output, Format::Csv);
+		translator.set_csv_mismatch(xt::CsvMismatch::Empty);
+		translator
+			.translate_slice(INPUT, Some(Format::Json))
+			.unwrap();
+		translator.finish().unwrap();
+	}
+	assert_eq!(
+		from_utf8(&output),
+		Ok(format!("{EXPECTED}dee,,\n").as_str())
+	);
+
+	let mut output = vec![];
+	{
+		let mut translator = xt::Translator::new(&mut output, Format::Tsv);
+		translator
+			.translate_slice(br#"{"a":"x\ty","b":[1]}"#, Some(Format::Json))
+			.unwrap_err();
+		translator
+			.translate_slice(br#"{"a":"x\ty","b":"z"}"#, Some(Format::Json))
+			.unwrap();
+		translator.finish().unwrap();
+	}
+	assert_eq!(from_utf8(&output), Ok("a\tb\n\"x\ty\"\tz\n"));
+}
+
+/// Tests that JSON Lines input reads as JSON, and that JSON Lines output puts
+/// each document on a single line even when JSON output would be indented.
+#[rstest]
+fn translator_json_lines_output() {
+	const INPUT: &[u8] = b"{\"a\": [1, 2]}\n\"two\"\n";
+
+	let mut output = vec![];
+	{
+		let mut translator = xt::Translator::new(&mut output, Format::JsonLines);
+		translator.set_json_indent(Some(2));
+		translator
+			.translate_slice(INPUT, Some(Format::JsonLines))
+			.unwrap();
+		translator.finish().unwrap();
+	}
+	assert_eq!(from_utf8(&output), Ok("{\"a\":[1,2]}\n\"two\"\n"));
+}
+
+/// Tests that JSON documents survive a round trip through a stream of BSON
+/// documents, whether the BSON input is a slice or a reader, including the
+/// Extended JSON forms of ObjectIds and datetimes.
+#[rstest]
+fn translator_bson_round_trip(#[values(false, true)] reader: bool) {
+	const INPUT: &str = concat!(
+		r#"{"_id":{"$oid":"5f1e2d3c4b5a697887a6b5c4"},"at":{"$date":1700000000000},"#,
+		r#""n":[1,4294967296,-2.5,true,null],"s":"x"}"#,
+		"\n",
+		r#"{"nested":{"a":{}}}"#,
+		"\n",
+	);
+
+	let mut bson = vec![];
+	xt::translate_slice(INPUT.as_bytes(), None, Format::Bson, &mut bson).unwrap();
+	assert_eq!(bson[..4], 87_i32.to_le_bytes());
+
+	let mut output = vec![];
+	if reader {
+		xt::translate_reader(&*bson, Some(Format::Bson), Format::Json, &mut output).unwrap();
+	} else {
+		xt::translate_slice(&bson, Some(Format::Bson), Format::Json, &mut output).unwrap();
+	}
+	assert_eq!(from_utf8(&output), Ok(INPUT));
+
+	let err = xt::translate_slice(b"[1]", None, Format::Bson, io::sink());
+	assert!(
+		err.unwrap_err()
+			.to_string()
+			.starts_with("root of BSON output must be a map")
+	);
+}
+
+/// Tests that BSON input from a reader fails on a document that ends before its
+/// length says, without first making room for all of that length.
+#[test]
+fn translator_bson_oversized_length() {
+	/// A reader that fails any read into a buffer far larger than its input.
+	struct SmallReads<'a>(&'a [u8]);
+
+	impl io::Read for SmallReads<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			assert!(buf.len() < 1 << 20, "read into {} bytes", buf.len());
+			self.0.read(buf)
+		}
+	}
+
+	let input = SmallReads(b"\xff\xff\xff\x7f\x00");
+	let err = xt::translate_reader(input, Some(Format::Bson), Format::Json, io::sink());
+	assert_eq!(
+		err.unwrap_err().to_string(),
+		"invalid BSON at byte 0: truncated document (in document 0)"
+	);
+}
+
+/// Tests the translation of the records in an Avro object container file
+/// with a deflate-compressed block, whether the input is a slice or a reader.
+#[rstest]
+fn translator_avro_input(#[values(false, true)] reader: bool) {
+	const SCHEMA: &str = concat!(
+		r#"{"type":"record","name":"User","fields":[{"name":"name","type":"string"},"#,
+		r#"{"name":"age","type":["null","int"]},"#,
+		r#"{"name":"tags","type":{"type":"array","items":"string"}}]}"#,
+	);
+	// The two records, as compressed by Python's zlib with a window of -15 bits.
+	const BLOCK: &[u8] =
+		b"\xe3\x4a\xcc\xc9\x4c\x4e\x65\xb2\x61\x61\xaa\x60\xaa\x64\x60\x4b\xca\x4f\x62\x60\x00\x00";
+	const SYNC: &[u8; 16] = b"0123456789abcdef";
+
+	let mut avro = b"Obj\x01\x04\x16avro.schema\xd0\x02".to_vec();
+	avro.extend_from_slice(SCHEMA.as_bytes());
+	avro.extend_from_slice(b"\x14avro.codec\x0edeflate\x00");
+	avro.extend_from_slice(SYNC);
+	avro.extend_from_slice(b"\x04\x2c");
+	avro.extend_from_slice(BLOCK);
+	avro.extend_from_slice(SYNC);
+
+	let mut output = vec![];
+	if reader {
+		xt::translate_reader(&*avro, Some(Format::Avro), Format::Json, &mut output).unwrap();
+	} else {
+		xt::translate_slice(&avro, Some(Format::Avro), Format::Json, &mut output).unwrap();
+	}
+	assert_eq!(
+		from_utf8(&output),
+		Ok(concat!(
+			r#"{"name":"alice","age":30,"tags":["x","y"]}"#,
+			"\n",
+			r#"{"name":"bob","age":null,"tags":[]}"#,
+			"\n",
+		))
+	);
+
+	let err = xt::translate_slice(b"{}", None, Format::Avro, io::sink());
+	assert!(
+		err.unwrap_err()
+			.to_string()
+			.starts_with("Avro is an input format only")
+	);
+}
+
+/// Tests the translation of Protocol Buffers messages to JSON and back, as single messages and
+/// as a length-delimited stream.
+#[test]
+fn translator_protobuf() {
+	// The descriptor set of test.proto in package "test", as encoded by hand:
+	//
+	//     message Item {
+	//       enum Kind { UNKNOWN = 0; BIG = 1; }
+	//       string name = 1;
+	//       int64 id = 2;
+	//       repeated int32 scores = 3;
+	//       Kind kind = 4;
+	//       map<string, int32> labels = 5;
+	//       Item child = 6;
+	//       uint32 user_id = 7;
+	//     }
+	const DESCRIPTORS: &[u8] = b"\
+		\x0a\x84\x02\x0a\x0a\x74\x65\x73\x74\x2e\x70\x72\x6f\x74\x6f\x12\x04\x74\x65\x73\x74\x22\
+		\xe7\x01\x0a\x04\x49\x74\x65\x6d\x12\x0c\x0a\x04\x6e\x61\x6d\x65\x18\x01\x20\x01\x28\x09\
+		\x12\x0a\x0a\x02\x69\x64\x18\x02\x20\x01\x28\x03\x12\x0e\x0a\x06\x73\x63\x6f\x72\x65\x73\
+		\x18\x03\x20\x03\x28\x05\x12\x12\x0a\x04\x6b\x69\x6e\x64\x18\x04\x20\x01\x28\x0e\x32\x04\
+		\x4b\x69\x6e\x64\x12\x26\x0a\x06\x6c\x61\x62\x65\x6c\x73\x18\x05\x20\x03\x28\x0b\x32\x16\
+		\x2e\x74\x65\x73\x74\x2e\x49\x74\x65\x6d\x2e\x4c\x61\x62\x65\x6c\x73\x45\x6e\x74\x72\x79\
+		\x12\x13\x0a\x05\x63\x68\x69\x6c\x64\x18\x06\x20\x01\x28\x0b\x32\x04\x49\x74\x65\x6d\x12\
+		\x17\x0a\x07\x75\x73\x65\x72\x5f\x69\x64\x18\x07\x20\x01\x28\x0d\x52\x06\x75\x73\x65\x72\
+		\x49\x64\x1a\x2d\x0a\x0b\x4c\x61\x62\x65\x6c\x73\x45\x6e\x74\x72\x79\x12\x0b\x0a\x03\x6b\
+		\x65\x79\x18\x01\x20\x01\x28\x09\x12\x0d\x0a\x05\x76\x61\x6c\x75\x65\x18\x02\x20\x01\x28\
+		\x05\x3a\x02\x38\x01\x22\x1c\x0a\x04\x4b\x69\x6e\x64\x12\x0b\x0a\x07\x55\x4e\x4b\x4e\x4f\
+		\x57\x4e\x10\x00\x12\x07\x0a\x03\x42\x49\x47\x10\x01\x62\x06\x70\x72\x6f\x74\x6f\x33";
+	const MESSAGE: &[u8] =
+		b"\x0a\x01\x61\x10\xfe\xff\xff\xff\xff\xff\xff\xff\xff\x01\x1a\x03\x01\xac\x02\x20\x01\
+		\x2a\x05\x0a\x01\x78\x10\x01\x32\x03\x0a\x01\x62\x38\x07";
+	const JSON: &str = concat!(
+		r#"{"name":"a","id":-2,"scores":[1,300],"kind":"BIG","labels":{"x":1},"#,
+		r#""child":{"name":"b"},"user_id":7}"#,
+		"\n",
+	);
+	let message = xt::ProtobufMessage::from_descriptor_set(DESCRIPTORS, "test.Item").unwrap();
+	assert_eq!(message.name(), "test.Item");
+
+	let translate = |input: &[u8], from, to, delimited| {
+		let mut output = vec![];
+		let mut translator = xt::Translator::new(&mut output, to);
+		translator.set_protobuf_message(Some(message.clone()));
+		translator.set_protobuf_delimited(delimited);
+		let result = translator.translate_slice(input, Some(from));
+		drop(translator);
+		result.map(|()| output)
+	};
+	let json = translate(MESSAGE, Format::Protobuf, Format::Json, false).unwrap();
+	assert_eq!(from_utf8(&json), Ok(JSON));
+	let encoded = translate(&json, Format::Json, Format::Protobuf, false).unwrap();
+	assert_eq!(encoded, MESSAGE);
+
+	// Output takes the JSON names of fields, and enums by number.
+	let stream = translate(
+		br#"{"userId":3,"kind":1} {"child":{"id":5}}"#,
+		Format::Json,
+		Format::Protobuf,
+		true,
+	)
+	.unwrap();
+	assert_eq!(stream, b"\x04\x38\x03\x20\x01\x04\x32\x02\x10\x05");
+	let json = translate(&stream, Format::Protobuf, Format::Json, true).unwrap();
+	assert_eq!(
+		from_utf8(&json),
+		Ok("{\"kind\":\"BIG\",\"user_id\":3}\n{\"child\":{\"id\":5}}\n")
+	);
+
+	let err = translate(b"{} {}", Format::Json, Format::Protobuf, false).unwrap_err();
+	assert!(err.to_string().contains("single message"), "{err}");
+	let err = translate(br#"{"nope":1}"#, Format::Json, Format::Protobuf, false).unwrap_err();
+	assert!(err.to_string().contains("no field"), "{err}");
+	let err = xt::translate_slice(MESSAGE, Some(Format::Protobuf), Format::Json, io::sink());
+	assert!(
+		err.unwrap_err()
+			.to_string()
+			.contains("needs a message type")
+	);
+	assert!(xt::ProtobufMessage::from_descriptor_set(DESCRIPTORS, "Item").is_err());
+}
+
+/// Tests the translation of INI sections to TOML and back, and the failure of INI output on
+/// deeper nesting than sections allow.
+#[test]
+fn translator_ini() {
+	const INI: &str = "\
+; settings of the old tool
+name = legacy
+[server]
+host = example.com
+port: 8080
+[empty]
+";
+	let mut toml = vec![];
+	xt::translate_slice(INI.as_bytes(), Some(Format::Ini), Format::Toml, &mut toml).unwrap();
+	assert_eq!(
+		from_utf8(&toml),
+		Ok(concat!(
+			"name = \"legacy\"\n\n",
+			"[server]\nhost = \"example.com\"\nport = \"8080\"\n\n",
+			"[empty]\n",
+		))
+	);
+
+	let mut ini = vec![];
+	xt::translate_slice(&toml, Some(Format::Toml), Format::Ini, &mut ini).unwrap();
+	assert_eq!(
+		from_utf8(&ini),
+		Ok("name = legacy\n\n[server]\nhost = example.com\nport = 8080\n\n[empty]\n")
+	);
+
+	for input in [r#"{"a": {"b": {"c": 1}}}"#, r#"{"a": [1]}"#, "{} {}"] {
+		let result = xt::translate_slice(
+			input.as_bytes(),
+			Some(Format::Json),
+			Format::Ini,
+			io::sink(),
+		);
+		assert!(result.is_err(), "{input}");
+	}
+}
+
+/// Tests the translation of EDN keywords, collections, and tagged literals
+/// to JSON and back, under each tag policy.
+#[rstest]
+#[case::passthrough(xt::EdnTags::Passthrough, r#""2024-01-02""#)]
+#[case::stringify(xt::EdnTags::Stringify, r##""#inst \"2024-01-02\"""##)]
+fn translator_edn(#[case] tags: xt::EdnTags, #[case] when: &str) {
+	const EDN: &str =
+		"; a comment\n{:name \"xt\", :tags #{:a}, :when #inst \"2024-01-02\"}\n(1 nil)\n";
+
+	let mut json = vec![];
+	let mut translator = xt::Translator::new(&mut json, Format::Json);
+	translator.set_edn_tags(tags);
+	translator
+		.translate_slice(EDN.as_bytes(), Some(Format::Edn))
+		.unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(
+		from_utf8(&json),
+		Ok(format!("{{\"name\":\"xt\",\"tags\":[\"a\"],\"when\":{when}}}\n[1,null]\n").as_str())
+	);
+
+	let mut edn = vec![];
+	xt::translate_slice(&json, Some(Format::Json), Format::Edn, &mut edn).unwrap();
+	assert_eq!(
+		from_utf8(&edn),
+		Ok(format!("{{:name \"xt\", :tags [\"a\"], :when {when}}}\n[1 nil]\n").as_str())
+	);
+}
+
+/// Tests the translation of RON structs, options, and unit variants to JSON and
+/// back, and that RON output holds only one document.
+#[test]
+fn translator_ron() {
+	const RON: &str =
+		"// a comment\nConfig(name: \"xt\", port: Some(80), color: Red, dims: (1, 2))\n";
+
+	let mut json = vec![];
+	xt::translate_slice(RON.as_bytes(), Some(Format::Ron), Format::Json, &mut json).unwrap();
+	assert_eq!(
+		from_utf8(&json),
+		Ok("{\"name\":\"xt\",\"port\":80,\"color\":\"Red\",\"dims\":[1,2]}\n")
+	);
+
+	let mut ron = vec![];
+	xt::translate_slice(&json, Some(Format::Json), Format::Ron, &mut ron).unwrap();
+	assert_eq!(
+		from_utf8(&ron),
+		Ok(concat!(
+			"{\n",
+			"    \"name\": \"xt\",\n",
+			"    \"port\": 80,\n",
+			"    \"color\": \"Red\",\n",
+			"    \"dims\": [\n",
+			"        1,\n",
+			"        2,\n",
+			"    ],\n",
+			"}\n",
+		))
+	);
+
+	let result = xt::translate_slice(b"{}\n{}\n", Some(Format::Json), Format::Ron, io::sink());
+	assert!(result.is_err());
+}
+
+/// Tests that bencode input translates byte strings that aren't valid UTF-8 as
+/// binary data, whether the input is a slice or a reader, and that output to
+/// bencode fails.
+#[rstest]
+fn translator_bencode(#[values(false, true)] reader: bool) {
+	const TORRENT: &[u8] = b"d8:announce9:http://tr4:infod6:lengthi42e6:pieces2:\xff\x00ee";
+
+	let mut json = vec![];
+	let mut translator = xt::Translator::new(&mut json, Format::Json);
+	if reader {
+		translator.translate_reader(TORRENT, Some(Format::Bencode))
+	} else {
+		translator.translate_slice(TORRENT, Some(Format::Bencode))
+	}
+	.unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(
+		from_utf8(&json),
+		Ok("{\"announce\":\"http://tr\",\"info\":{\"length\":42,\"pieces\":[255,0]}}\n")
+	);
+
+	let mut bencode = vec![];
+	let result = xt::translate_slice(&json, Some(Format::Json), Format::Bencode, &mut bencode);
+	assert!(result.is_err());
+}
+
+/// Tests that YAML output writes multi-line strings as block scalars in the
+/// chosen style, with chomping indicators that keep their final line breaks.
+#[rstest]
+#[case::literal(
+	xt::YamlBlockStyle::Literal,
+	"a: |\n  one  \n  \ttwo\nb:\n- |-\n  x\n  y\n"
+)]
+#[case::folded(
+	xt::YamlBlockStyle::Folded,
+	"a: |\n  one  \n  \ttwo\nb:\n- >-\n  x\n\n  y\n"
+)]
+fn translator_yaml_block_style(#[case] style: xt::YamlBlockStyle, #[case] want: &str) {
+	const JSON: &str = r#"{"a": "one  \n\ttwo\n", "b": ["x\ny"]}"#;
+
+	let mut yaml = vec![];
+	let mut translator = xt::Translator::new(&mut yaml, Format::Yaml);
+	translator.set_yaml_block_style(style);
+	translator
+		.translate_slice(JSON.as_bytes(), Some(Format::Json))
+		.unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&yaml), Ok(format!("---\n{want}").as_str()));
+
+	let mut json = vec![];
+	xt::translate_slice(&yaml, Some(Format::Yaml), Format::Json, &mut json).unwrap();
+	assert_eq!(
+		from_utf8(&json),
+		Ok("{\"a\":\"one  \\n\\ttwo\\n\",\"b\":[\"x\\ny\"]}\n")
+	);
+}
+
+/// Tests that YAML and TOML output write comments before the entries at
+/// their paths and before each document, and still read back as the same data.
+#[rstest]
+#[case::yaml(
+	Format::Yaml,
+	"---\n# generated\nname: web\nspec:\n  # managed\n  # by xt\n  replicas: 3\n"
+)]
+#[case::toml(
+	Format::Toml,
+	"# generated\nname = \"web\"\n\n[spec]\n# managed\n# by xt\nreplicas = 3\n"
+)]
+fn translator_output_comments(#[case] format: Format, #[case] want: &str) {
+	const JSON: &str = r#"{"name": "web", "spec": {"replicas": 3}}"#;
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, format);
+	translator.add_output_comment(".", "generated").unwrap();
+	translator
+		.add_output_comment(".spec.replicas", "managed\nby xt")
+		.unwrap();
+	translator
+		.add_output_comment(".missing", "nowhere")
+		.unwrap();
+	assert!(translator.add_output_comment(".spec[0]", "index").is_err());
+	translator
+		.translate_slice(JSON.as_bytes(), Some(Format::Json))
+		.unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(want));
+
+	let mut json = vec![];
+	xt::translate_slice(&output, Some(format), Format::Json, &mut json).unwrap();
+	assert_eq!(
+		from_utf8(&json),
+		Ok("{\"name\":\"web\",\"spec\":{\"replicas\":3}}\n")
+	);
+}
+
+/// Tests that the capabilities of the build describe its version, features,
+/// and formats.
+#[test]
+fn capabilities() {
+	let capabilities = xt::capabilities();
+	assert_eq!(capabilities.version(), env!("CARGO_PKG_VERSION"));
+	assert_eq!(
+		capabilities.features().contains(&"template"),
+		cfg!(feature = "template")
+	);
+	assert!(capabilities.formats().contains(&Format::Edn));
+	assert!(!capabilities.supports_input(Format::Null));
+	assert!(capabilities.supports_output(Format::Null));
+	assert!(!capabilities.supports_output(Format::Bencode));
+	assert!(capabilities.is_detected(Format::Yaml));
+	assert!(!capabilities.is_detected(Format::Csv));
+	assert!(!capabilities.supports_multi_document_output(Format::Toml));
+	assert_eq!(capabilities.depth_limit(Format::Msgpack), Some(1024));
+	assert_eq!(capabilities.depth_limit(Format::Toml), None);
+}
+
+/// Tests that a MessagePack resync handler turns decoding errors into skipped
+/// stretches of input, with translation resuming at the next map or array that
+/// decodes, whether the input is a slice or a reader.
+#[rstest]
+fn translator_msgpack_resync(#[values(false, true)] reader: bool) {
+	// {"a": 1}; a never-used marker and a fixint; [2]; a truncated array.
+	const INPUT: &[u8] = b"\x81\xa1a\x01\xc1\x05\x91\x02\x92\x01";
+
+	let resyncs = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_source_name(Some("in.msgpack".into()));
+	translator.set_msgpack_resync_handler({
+		let resyncs = Arc::clone(&resyncs);
+		move |resync| resyncs.lock().unwrap().push(resync)
+	});
+	if reader {
+		translator.translate_reader(INPUT, Some(Format::Msgpack))
+	} else {
+		translator.translate_slice(INPUT, Some(Format::Msgpack))
+	}
+	.unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("{\"a\":1}\n[2]\n"));
+
+	let resyncs = resyncs.lock().unwrap();
+	let got: Vec<_> = resyncs
+		.iter()
+		.map(|r| (r.source(), r.offset(), r.resume_offset()))
+		.collect();
+	assert_eq!(
+		got,
+		[
+			(Some("in.msgpack"), 4, Some(6)),
+			(Some("in.msgpack"), 8, None)
+		]
+	);
+	assert!(
+		resyncs[0]
+			.to_string()
+			.starts_with("skipped corrupt input from byte 4 to byte 6: ")
+	);
+}
+
+/// Tests that a UTF-8 repair handler hears how many invalid sequences each
+/// JSON or MessagePack input had, and that translation replaces them with
+/// U+FFFD, including where that lengthens a MessagePack string past the limit
+/// of its encoding.
+#[rstest]
+#[case::json(
+	Format::Json,
+	b"{\"a\":\"x\xffy\"}\n[\"\xc3\x28\", \"\xf0\x9f\x98\"]\n"
+)]
+#[case::msgpack(
+	Format::Msgpack,
+	b"\x81\xa1a\xa3x\xffy\x92\xa2\xc3\x28\xa3\xf0\x9f\x98\x91\xbfaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\xff"
+)]
+fn translator_utf8_repair(#[case] from: Format, #[case] input: &[u8]) {
+	let repairs = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_source_name(Some("in".into()));
+	translator.set_utf8_repair_handler({
+		let repairs = Arc::clone(&repairs);
+		move |repair| repairs.lock().unwrap().push(repair)
+	});
+	translator.translate_slice(input, Some(from)).unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+
+	let mut want = "{\"a\":\"x\u{fffd}y\"}\n[\"\u{fffd}(\",\"\u{fffd}\"]\n".to_owned();
+	if from == Format::Msgpack {
+		want.push_str(&format!("[\"{}\u{fffd}\"]\n", "a".repeat(30)));
+	}
+	assert_eq!(from_utf8(&output), Ok(want.as_str()));
+	let repairs = repairs.lock().unwrap();
+	let got: Vec<_> = repairs.iter().map(|r| (r.source(), r.count())).collect();
+	let count = if from == Format::Msgpack { 4 } else { 3 };
+	assert_eq!(got, [(Some("in"), count)]);
+}
+
+/// Tests that each MessagePack key scheme converts integer, boolean, and binary
+/// map keys at any depth to strings, while preserving them leaves them for the
+/// output format to reject.
+#[rstest]
+#[case::preserve(xt::MsgpackKeys::Preserve, None)]
+#[case::decimal(
+	xt::MsgpackKeys::Decimal,
+	Some(r#"{"-1":[{"true":"x"}],"0x0102":"y","s":1}"#)
+)]
+#[case::hex(
+	xt::MsgpackKeys::Hex,
+	Some(r#"{"-0x1":[{"true":"x"}],"0x0102":"y","s":1}"#)
+)]
+#[case::base64(
+	xt::MsgpackKeys::Base64,
+	Some(r#"{"-1":[{"true":"x"}],"AQI=":"y","s":1}"#)
+)]
+fn translator_msgpack_keys(#[case] keys: xt::MsgpackKeys, #[case] want: Option<&str>) {
+	// {-1: [{true: "x"}], bin [1, 2]: "y", "s": 1}
+	const INPUT: &[u8] = b"\x83\xff\x91\x81\xc3\xa1x\xc4\x02\x01\x02\xa1y\xa1s\x01";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_msgpack_keys(keys);
+	let result = translator.translate_slice(INPUT, Some(Format::Msgpack));
+	drop(translator);
+
+	match want {
+		Some(want) => {
+			result.unwrap();
+			assert_eq!(from_utf8(&output), Ok(format!("{want}\n").as_str()));
+		}
+		None => assert!(result.is_err()),
+	}
+}
+
+/// Tests that known keys from a reference document reject the first unknown
+/// key of an input, or report every unknown key to a handler without
+/// affecting translation.
+#[rstest]
+fn translator_known_keys(#[values(false, true)] warn: bool) {
+	const REFERENCE: &[u8] = b"server:\n  port: 80\nhosts:\n  - name: a\n";
+	const INPUT: &str = concat!(
+		r#"{"server":{"port":1},"hosts":[{"name":"x"}]}"#,
+		"\n",
+		r#"{"server":{"prot":1},"hosts":[{"nmae":"y"}]}"#,
+		"\n",
+	);
+
+	let known = xt::KnownKeys::from_slice(REFERENCE, Some(Format::Yaml)).unwrap();
+	let unknown = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_known_keys(Some(known));
+	if warn {
+		let unknown = Arc::clone(&unknown);
+		translator.set_unknown_key_handler(move |key| unknown.lock().unwrap().push(key));
+	}
+	let result = translator.translate_slice(INPUT.as_bytes(), Some(Format::Json));
+	drop(translator);
+
+	if warn {
+		result.unwrap();
+		assert_eq!(from_utf8(&output), Ok(INPUT));
+		let unknown = unknown.lock().unwrap();
+		let got: Vec<_> = unknown
+			.iter()
+			.map(|u| (u.document_index(), u.path(), u.key()))
+			.collect();
+		assert_eq!(got, [(1, ".server", "prot"), (1, ".hosts[0]", "nmae")]);
+	} else {
+		let err = result.unwrap_err();
+		assert_eq!(err.document_index(), Some(1));
+		assert!(
+			err.to_string()
+				.starts_with(r#"unknown key "prot" in map at .server"#)
+		);
+		assert_eq!(
+			from_utf8(&output),
+			Ok("{\"server\":{\"port\":1},\"hosts\":[{\"name\":\"x\"}]}\n")
+		);
+	}
+}
+
+/// Tests that a string issue handler receives the path of every string with
+/// a control or replacement character, and that invalid UTF-8 and lone
+/// surrogates only fail translation without one.
+#[rstest]
+fn translator_string_audit(#[values(false, true)] audit: bool) {
+	const INPUT: &[u8] = b"{\"a\":\"ok\\t\"}\n{\"b\\u001b\":[\"\\udc00\",\"\xff\"]}\n";
+
+	let issues = Arc::new(Mutex::new(vec![]));
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	if audit {
+		let issues = Arc::clone(&issues);
+		translator.set_string_issue_handler(move |issue| issues.lock().unwrap().push(issue));
+	}
+	let result = translator.translate_slice(INPUT, Some(Format::Json));
+	drop(translator);
+
+	if audit {
+		result.unwrap();
+		assert_eq!(
+			from_utf8(&output),
+			Ok("{\"a\":\"ok\\t\"}\n{\"b\\u001b\":[\"\u{fffd}\",\"\u{fffd}\"]}\n")
+		);
+		let issues = issues.lock().unwrap();
+		let got: Vec<_> = issues
+			.iter()
+			.map(|i| (i.document_index(), i.path(), i.is_key(), i.character()))
+			.collect();
+		assert_eq!(
+			got,
+			[
+				(1, r#"."b\u001b""#, true, '\u{1b}'),
+				(1, r#"."b\u001b"[0]"#, false, '\u{fffd}'),
+				(1, r#"."b\u001b"[1]"#, false, '\u{fffd}'),
+			]
+		);
+	} else {
+		assert!(result.is_err());
+	}
+}
+
+/// Tests that document comments name the index and source of each YAML output
+/// document, and that other output formats ignore them.
+#[test]
+fn translator_document_comments() {
+	let translate = |to| {
+		let mut output = vec![];
+		let mut translator = xt::Translator::new(&mut output, to);
+		translator.set_document_comments(true);
+		translator.set_source_name(Some("a.json".into()));
+		translator
+			.translate_slice(b"{\"x\":1}\n{\"x\":2}\n", Some(Format::Json))
+			.unwrap();
+		translator.set_source_name(None);
+		translator
+			.translate_slice(b"{\"y\":3}\n", Some(Format::Json))
+			.unwrap();
+		translator.finish().unwrap();
+		drop(translator);
+		String::from_utf8(output).unwrap()
+	};
+
+	assert_eq!(
+		translate(Format::Yaml),
+		"# doc 0 from a.json\n---\nx: 1\n# doc 1 from a.json\n---\nx: 2\n# doc 0\n---\ny: 3\n"
+	);
+	assert_eq!(translate(Format::Json), "{\"x\":1}\n{\"x\":2}\n{\"y\":3}\n");
+}
+
+/// Tests that a timeout fails translation at the first document or read after
+/// it runs out, keeping the output of earlier documents.
+#[test]
+fn translator_timeout() {
+	/// Reads a few documents, then stalls before reading any more.
+	struct StallingReader(usize);
+
+	impl io::Read for StallingReader {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			self.0 += 1;
+			let chunk: &[u8] = match self.0 {
+				1 => b"1 2 ",
+				_ => {
+					thread::sleep(Duration::from_millis(100));
+					b"3 "
+				}
+			};
+			buf[..chunk.len()].copy_from_slice(chunk);
+			Ok(chunk.len())
+		}
+	}
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_timeout(Some(Duration::from_millis(50)));
+	let err = translator
+		.translate_reader(StallingReader(0), Some(Format::Json))
+		.unwrap_err();
+	assert!(
+		err.to_string()
+			.starts_with("translation timed out after 50ms"),
+		"{err}"
+	);
+	assert_eq!(err.document_index(), Some(2));
+
+	translator.set_timeout(Some(Duration::ZERO));
+	let err = translator
+		.translate_slice(b"4", Some(Format::Json))
+		.unwrap_err();
+	assert_eq!(err.document_index(), Some(0));
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("1\n2\n"));
+}
+
+/// Tests that skipping bytes starts translation at a later document offset.
+#[rstest]
+fn translator_skip_bytes(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = b"{invalid}\n{\"a\":1}\n";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_skip_bytes(10);
+	if reader {
+		translator.translate_reader(INPUT, None).unwrap();
+	} else {
+		translator.translate_slice(INPUT, None).unwrap();
+	}
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("{\"a\":1}\n"));
+}
+
+/// Tests that peeking at an input doesn't consume it, and that translation
+/// starts from the beginning of the input or after any skipped bytes.
+#[rstest]
+fn translator_peek_input(#[values(0, 1, 2)] kind: usize, #[values(0, 10)] skip: u64) {
+	const INPUT: &[u8] = b"{invalid}\n{\"a\":1}\n";
+
+	let mut input = match kind {
+		0 => xt::Input::from_slice(INPUT),
+		1 => xt::Input::from_reader(INPUT),
+		_ => xt::Input::from_seekable(io::Cursor::new(INPUT)).unwrap(),
+	};
+	assert_eq!(input.peek(4).unwrap(), b"{inv");
+	assert_eq!(input.peek(64).unwrap(), INPUT);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_skip_bytes(skip);
+	let result = translator.translate_input(input, Some(Format::Json));
+	assert_eq!(result.is_ok(), skip > 0, "{result:?}");
+	drop(translator);
+	let expected = if skip > 0 { "{\"a\":1}\n" } else { "" };
+	assert_eq!(from_utf8(&output), Ok(expected));
+}
+
+/// Tests that skipping documents counts each input's documents before any
+/// transforms, and keeps their indexes for the documents that follow.
+#[rstest]
+fn translator_skip_documents(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	const INPUT: &[u8] = b"{\"a\":1}\n{\"b\":[2]}\n{\"c\":3}\n";
+	const EXPECTED: &str = concat!(
+		r#"{"c":3,"xt":{"document":2,"offset":18}}"#,
+		"\n",
+		r#"{"c":3,"xt":{"document":2,"offset":18}}"#,
+		"\n",
+	);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.set_skip_documents(2);
+	translator.add_transform(xt::Transform::provenance("xt"));
+	for _ in 0..2 {
+		if reader {
+			translator.translate_reader(INPUT, None).unwrap();
+		} else {
+			translator.translate_slice(INPUT, None).unwrap();
+		}
+	}
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that the index of the output records the input and output byte ranges
+/// of every output document, and only output ranges for merged documents.
+#[rstest]
+fn translator_index(#[values(1, 2)] threads: usize) {
+	const INPUT: &[u8] = b"{\"a\":1}  {\"b\":[]}\n{\"b\":[2]}";
+	const OUTPUT: &str = "---\na: 1\n---\nb: []\n---\nb:\n- 2\n";
+
+	let index = SharedBuffer::default();
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Yaml);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.set_index_output(index.clone(), Format::Json);
+	translator.set_source_name(Some("in.json".into()));
+	translator.translate_slice(INPUT, None).unwrap();
+	translator.set_merge(Some(xt::ArrayMerge::Concat));
+	translator.translate_slice(INPUT, None).unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(
+		from_utf8(&output),
+		Ok(format!("{OUTPUT}---\na: 1\nb:\n- 2\n").as_str())
+	);
+	assert_eq!(
+		from_utf8(&index.0.lock().unwrap()),
+		Ok(concat!(
+			r#"{"document":0,"source":"in.json","input_document":0,"input_offset":0,"input_end":7,"output_offset":0,"output_end":9}"#,
+			"\n",
+			r#"{"document":1,"source":"in.json","input_document":1,"input_offset":9,"input_end":17,"output_offset":9,"output_end":19}"#,
+			"\n",
+			r#"{"document":2,"source":"in.json","input_document":2,"input_offset":18,"input_end":27,"output_offset":19,"output_end":30}"#,
+			"\n",
+			r#"{"document":3,"output_offset":30,"output_end":46}"#,
+			"\n",
+		))
+	);
+}
+
+/// Tests that a document sink receives each output document on its own, even
+/// when documents come from a tail held until the end of the stream or would
+/// otherwise serialize in parallel.
+#[rstest]
+#[case(Format::Json, &["{\"a\":1}\n", "{\"b\":[2]}\n", "\"c\"\n"])]
+#[case(Format::Yaml, &["---\na: 1\n", "---\nb:\n- 2\n", "---\nc\n"])]
+#[case(Format::Msgpack, &["\u{81}\u{a1}a\u{01}", "\u{81}\u{a1}b\u{91}\u{02}", "\u{a1}c"])]
+fn translator_document_sink(
+	#[case] to: Format,
+	#[case] expected: &[&str],
+	#[values(1, 2)] threads: usize,
+) {
+	const INPUT: &[u8] = b"{\"a\":1} {\"b\":[2]} \"c\"";
+
+	#[derive(Default)]
+	struct Collect(Vec<(Vec<u8>, usize)>);
+
+	impl xt::DocumentSink for Collect {
+		fn write_document(&mut self, bytes: &[u8], index: usize) -> io::Result<()> {
+			self.0.push((bytes.to_vec(), index));
+			Ok(())
+		}
+	}
+
+	let mut sink = Collect::default();
+	let mut translator = xt::Translator::with_document_sink(&mut sink, to);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.translate_slice(INPUT, None).unwrap();
+	translator.set_tail(Some(1));
+	translator.translate_slice(INPUT, None).unwrap();
+	translator.finish().unwrap();
+	drop(translator);
+
+	let expected: Vec<_> = expected
+		.iter()
+		.chain(&expected[2..])
+		.enumerate()
+		.map(|(i, doc)| {
+			let bytes = match to {
+				Format::Msgpack => doc.chars().map(|c| u8::try_from(c).unwrap()).collect(),
+				_ => doc.as_bytes().to_vec(),
+			};
+			(bytes, i)
+		})
+		.collect();
+	assert_eq!(sink.0, expected);
+}
+
+/// A writer to a buffer shared across threads.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Tests that merging combines documents from multiple inputs into a single
+/// document, even for output formats without multi-document support.
+#[rstest]
+fn translator_merge_by_key(#[values(false, true)] reader: bool, #[values(1, 2)] threads: usize) {
+	const INPUTS: &[&[u8]] = &[
+		b"name: app\ncontainers:\n  - name: web\n    image: web:1\n",
+		b"containers:\n  - name: web\n    image: web:2\n  - name: sidecar\n    image: proxy\n",
+	];
+	const EXPECTED: &str = concat!(
+		"name = \"app\"\n\n",
+		"[[containers]]\nname = \"web\"\nimage = \"web:2\"\n\n",
+		"[[containers]]\nname = \"sidecar\"\nimage = \"proxy\"\n",
+	);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Toml);
+	translator.set_threads(NonZeroUsize::new(threads).unwrap());
+	translator.set_merge(Some(xt::ArrayMerge::ByKey("name".into())));
+	for input in INPUTS {
+		if reader {
+			translator
+				.translate_reader(*input, Some(Format::Yaml))
+				.unwrap();
+		} else {
+			translator
+				.translate_slice(input, Some(Format::Yaml))
+				.unwrap();
+		}
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that a merge with a conflict report outputs the sources of every
+/// value at each path where the inputs disagreed, rather than the merged
+/// document, regardless of the order of the settings.
+#[rstest]
+fn translator_merge_conflict_report(#[values(false, true)] report_first: bool) {
+	const INPUTS: &[(&str, &[u8])] = &[
+		(
+			"base.yaml",
+			b"replicas: 1
+image: {tag: v1}
+ports: [80]
+",
+		),
+		(
+			"prod.yaml",
+			b"replicas: 3
+image: {tag: v1, pull: always}
+",
+		),
+		(
+			"local.yaml",
+			b"ports: [8080]
+image: {tag: dev}
+",
+		),
+	];
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	if report_first {
+		translator.set_report_merge_conflicts(true);
+	}
+	translator.set_merge(Some(xt::ArrayMerge::Replace));
+	translator.set_report_merge_conflicts(true);
+	for (source, input) in INPUTS {
+		translator.set_source_name(Some((*source).into()));
+		translator
+			.translate_slice(input, Some(Format::Yaml))
+			.unwrap();
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(
+		from_utf8(&output),
+		Ok(concat!(
+			r#"[{"path":".replicas","winner":"prod.yaml","values":[{"source":"base.yaml","value":1},{"source":"prod.yaml","value":3}]},"#,
+			r#"{"path":".ports","winner":"local.yaml","values":[{"source":"base.yaml","value":[80]},{"source":"local.yaml","value":[8080]}]},"#,
+			r#"{"path":".image.tag","winner":"local.yaml","values":[{"source":"base.yaml","value":"v1"},{"source":"local.yaml","value":"dev"}]}]"#,
+			"\n",
+		))
+	);
+}
+
+/// Tests that sorting keys and arrays produces identical output for documents
+/// that differ only in ordering.
+#[test]
+fn translator_sort_canonical_form() {
+	const INPUTS: &[&[u8]] = &[
+		br#"{"tags":["b","a"],"items":[{"name":"y","n":1},{"name":"x"}],"id":1}"#,
+		br#"{"id":1,"items":[{"name":"x"},{"n":1,"name":"y"}],"tags":["a","b"]}"#,
+	];
+
+	let outputs: Vec<_> = INPUTS
+		.iter()
+		.map(|input| {
+			let mut output = vec![];
+			let mut translator = xt::Translator::new(&mut output, Format::Json);
+			translator.add_transform(xt::Transform::sort_keys());
+			translator.add_transform(xt::Transform::sort_arrays_by("name"));
+			translator
+				.translate_slice(input, Some(Format::Json))
+				.unwrap();
+			drop(translator);
+			output
+		})
+		.collect();
+	assert_eq!(
+		from_utf8(&outputs[0]),
+		Ok(
+			"{\"id\":1,\"items\":[{\"name\":\"x\"},{\"n\":1,\"name\":\"y\"}],\"tags\":[\"a\",\"b\"]}\n"
+		)
+	);
+	assert_eq!(outputs[0], outputs[1]);
+}
+
+/// Tests that schema inference outputs a single schema describing every
+/// document from every input.
+#[rstest]
+fn translator_infer_schema(#[values(false, true)] reader: bool) {
+	const INPUTS: &[&[u8]] = &[b"name: a\nport: 80\n---\nname: b\n", b"{\"name\":\"a\"}"];
+	const EXPECTED: &str = concat!(
+		r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","type":"object","#,
+		r#""properties":{"name":{"type":"string","enum":["a","b"]},"port":{"type":"integer"}},"#,
+		r#""required":["name"]}"#,
+		"\n",
+	);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_infer_schema(true);
+	for input in INPUTS {
+		if reader {
+			translator.translate_reader(*input, None).unwrap();
+		} else {
+			translator.translate_slice(input, None).unwrap();
+		}
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that type statistics count the types of values at each path of every
+/// document from every input.
+#[rstest]
+fn translator_type_stats() {
+	const INPUTS: &[&[u8]] = &[
+		b"port: 80
+---
+port: http
+",
+		b"{\"port\":[8080]}",
+	];
+	const EXPECTED: &str = "---
+.:
+  map: 3
+.port:
+  int: 1
+  string: 1
+  array: 1
+.port[]:
+  int: 1
+";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Yaml);
+	translator.set_type_stats(true);
+	for input in INPUTS {
+		translator.translate_slice(input, None).unwrap();
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that grouping collects documents into arrays keyed by their values at
+/// a path, in order of first appearance.
+#[rstest]
+fn translator_group_by(#[values(false, true)] reader: bool) {
+	const INPUT: &[u8] = br#"{"kind":"a","n":1} {"kind":"b","n":2} {"kind":"a","n":3} {"n":4}"#;
+	const EXPECTED: &str = concat!(
+		"---\na:\n- kind: a\n  n: 1\n- kind: a\n  n: 3\n",
+		"b:\n- kind: b\n  n: 2\n",
+		"null:\n- n: 4\n",
+	);
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Yaml);
+	translator.set_group_by(Some(".kind")).unwrap();
+	if reader {
+		translator
+			.translate_reader(INPUT, Some(Format::Json))
+			.unwrap();
+	} else {
+		translator
+			.translate_slice(INPUT, Some(Format::Json))
+			.unwrap();
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+}
+
+/// Tests that a stream order interleaves documents from each input in turn, or
+/// sorts the whole stream by the value at a path.
+#[rstest]
+#[case::interleave(xt::StreamOrder::Interleave, "3\n-1\n2\n1\n{}\n5\n")]
+#[case::sort_by(xt::StreamOrder::SortBy(".".into()), "-1\n1\n2\n3\n5\n{}\n")]
+fn translator_stream_order(#[case] order: xt::StreamOrder, #[case] expected: &str) {
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.set_stream_order(Some(order)).unwrap();
+	for input in [&b"3 2 {}"[..], b"", b"-1 1 5"] {
+		translator
+			.translate_reader(input, Some(Format::Json))
+			.unwrap();
+	}
+	translator.finish().unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(expected));
+}
+
+/// Tests that flattening and then unflattening a document reproduces the
+/// original document.
+#[test]
+fn translator_flatten_unflatten() {
+	const INPUT: &[u8] = include_bytes!("single.json");
+
+	let mut flat = vec![];
+	let mut translator = xt::Translator::new(&mut flat, Format::Json);
+	translator.add_transform(xt::Transform::flatten(".").unwrap());
+	translator
+		.translate_slice(INPUT, Some(Format::Json))
+		.unwrap();
+	drop(translator);
+	assert!(from_utf8(&flat).unwrap().contains("\"meta.language\""));
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.add_transform(xt::Transform::unflatten(".").unwrap());
+	translator
+		.translate_slice(&flat, Some(Format::Json))
+		.unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), from_utf8(INPUT));
+}
+
+/// Tests that pruning removes nulls and empty collections before output, which
+/// lets otherwise unrepresentable documents translate to TOML.
+#[test]
+fn translator_prune_for_toml() {
+	const INPUT: &[u8] = br#"{"xt": true, "unset": null, "empty": {"list": [null]}}"#;
+
+	let err = xt::translate_slice(INPUT, Some(Format::Json), Format::Toml, io::sink());
+	assert!(err.is_err());
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Toml);
+	translator.add_transform(xt::Transform::prune(true));
+	translator
+		.translate_slice(INPUT, Some(Format::Json))
+		.unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok("xt = true\n"));
+}
+
+/// Tests that selecting documents skips those that don't match the predicate,
+/// including on streaming input.
+#[test]
+fn translator_select() {
+	const INPUT: &[u8] = b"kind: Service\n---\nkind: Deployment\nspec: {replicas: 3}\n---\nkind: Deployment\nspec: {replicas: 1}\n";
+	const EXPECTED: &str = "{\"kind\":\"Deployment\",\"spec\":{\"replicas\":3}}\n";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.add_transform(xt::Transform::select(".kind == \"Deployment\"").unwrap());
+	translator.add_transform(xt::Transform::select(".spec.replicas > 1").unwrap());
+	translator
+		.translate_reader(INPUT, Some(Format::Yaml))
+		.unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(EXPECTED));
+
+	assert!(xt::Transform::select("kind == 1").is_err());
+}
+
+/// Tests that coercing and stringifying scalars round-trip a document whose
+/// values start out as strings.
+#[test]
+fn translator_coerce_stringify() {
+	const INPUT: &[u8] = br#"{"port":"8080","debug":"false","proxy":"null","zip":"02134"}"#;
+	const COERCED: &str = "port = 8080\ndebug = false\nzip = \"02134\"\n";
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Toml);
+	translator.add_transform(xt::Transform::coerce([
+		xt::Coercion::Number,
+		xt::Coercion::Bool,
+		xt::Coercion::Null,
+	]));
+	translator.add_transform(xt::Transform::prune(false));
+	translator
+		.translate_slice(INPUT, Some(Format::Json))
+		.unwrap();
+	drop(translator);
+	assert_eq!(from_utf8(&output), Ok(COERCED));
+
+	let mut output = vec![];
+	let mut translator = xt::Translator::new(&mut output, Format::Json);
+	translator.add_transform(xt::Transform::stringify());
+	translator
+		.translate_slice(COERCED.as_bytes(), Some(Format::Toml))
+		.unwrap();
+	drop(translator);
+	assert_eq!(
+		from_utf8(&output),
+		Ok("{\"port\":\"8080\",\"debug\":\"false\",\"zip\":\"02134\"}\n")
+	);
+}